@@ -0,0 +1,368 @@
+use async_trait::async_trait;
+use opensearch_sdk_rs::client::{
+    BulkRequest, FieldMapping, GetRequest, IndexRequest, Mappings, SdkClient, SearchRequest,
+    SystemIndexDescriptor, SystemIndexManager,
+};
+use opensearch_sdk_rs::extension::logging::{init_logging, LogFormat};
+use opensearch_sdk_rs::extension::{
+    Extension, ExtensionBuilder, ExtensionContext, ExtensionError, Setting, SettingRegistry,
+};
+use opensearch_sdk_rs::rest::{ExtensionRestRequest, Method, RestHandler, RestResponse, Route};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const UNIQUE_ID: &str = "security-analytics";
+const EVENTS_INDEX: &str = ".security-analytics-events";
+const ALERTS_INDEX: &str = ".security-analytics-alerts";
+/// Asset inventory maintained outside the extension, keyed by IP address.
+const ASSETS_INDEX: &str = "assets";
+
+fn webhook_url() -> Setting<String> {
+    Setting::string("security_analytics.webhook_url").validator(|url| {
+        if url.starts_with("http://") {
+            Ok(())
+        } else {
+            Err("only http:// webhooks are supported".to_string())
+        }
+    })
+}
+
+fn interval_secs() -> Setting<i64> {
+    Setting::int("security_analytics.interval_secs")
+        .default(60)
+        .min(1)
+        .dynamic()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Asset {
+    owner: String,
+    criticality: String,
+}
+
+/// Asset lookups by IP, cached for `ttl` including misses.
+struct AssetCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Option<Asset>, Instant)>>,
+}
+
+impl AssetCache {
+    fn new(ttl: Duration) -> Self {
+        AssetCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn lookup(&self, client: &SdkClient, ip: &str) -> Option<Asset> {
+        if let Some((asset, at)) = self.entries.lock().unwrap().get(ip) {
+            if at.elapsed() < self.ttl {
+                return asset.clone();
+            }
+        }
+        let asset = match client.get::<Asset>(GetRequest::new(ASSETS_INDEX, ip)).await {
+            Ok(response) => response.source,
+            Err(e) => {
+                warn!("Asset lookup for {} failed: {}", ip, e);
+                return None;
+            }
+        };
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(ip.to_string(), (asset.clone(), Instant::now()));
+        asset
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SecurityEvent {
+    #[serde(rename = "@timestamp", default)]
+    timestamp: u64,
+    source_ip: String,
+    action: String,
+    outcome: String,
+    #[serde(default)]
+    asset: Option<Asset>,
+}
+
+/// Ingests events at `POST /_security_analytics/events`, enriching each
+/// with its asset before bulk indexing.
+struct EventsHandler {
+    assets: Arc<AssetCache>,
+}
+
+#[async_trait]
+impl RestHandler for EventsHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![Route::new(Method::Post, "/_security_analytics/events")
+            .named("security_analytics:ingest")]
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        let events: Vec<SecurityEvent> = request.content_as()?;
+        let client = SdkClient::new(context.transport_client.clone(), UNIQUE_ID);
+
+        let mut bulk = BulkRequest::new();
+        for mut event in events {
+            if event.timestamp == 0 {
+                event.timestamp = now_millis();
+            }
+            event.asset = self.assets.lookup(&client, &event.source_ip).await;
+            bulk = bulk.operation(IndexRequest::new(EVENTS_INDEX).document(&event)?);
+        }
+        if bulk.is_empty() {
+            return Ok(RestResponse::bad_request().text("no events"));
+        }
+
+        let response = client.bulk(bulk).await?;
+        let failed = response.failures().count();
+        RestResponse::ok()
+            .json(&json!({ "indexed": response.items.len() - failed, "failed": failed }))
+    }
+}
+
+/// Alert when at least `threshold` events match `query` within `window`.
+struct ThresholdRule {
+    name: &'static str,
+    query: Value,
+    window: &'static str,
+    threshold: u64,
+}
+
+impl ThresholdRule {
+    async fn evaluate(&self, client: &SdkClient) -> Result<Option<Value>, ExtensionError> {
+        let query = json!({"bool": {"filter": [
+            self.query,
+            {"range": {"@timestamp": {"gte": format!("now-{}", self.window)}}}
+        ]}});
+        let response = client
+            .search::<Value>(SearchRequest::new(&[EVENTS_INDEX]).query(query).size(0))
+            .await?;
+        let count = response.hits.total.map_or(0, |total| total.value);
+        Ok((count >= self.threshold).then(|| {
+            json!({
+                "@timestamp": now_millis(),
+                "rule": self.name,
+                "count": count,
+                "window": self.window,
+            })
+        }))
+    }
+}
+
+/// POST `body` to a plain `http://host:port/path` webhook.
+async fn notify(url: &str, body: &Value) -> Result<(), ExtensionError> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        ExtensionError::configuration(format!("Only http:// webhooks are supported, got {}", url))
+    })?;
+    let (authority, path) = rest
+        .split_once('/')
+        .map_or((rest, "/".to_string()), |(a, p)| (a, format!("/{}", p)));
+    let payload = body.to_string();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, authority, payload.len(), payload
+    );
+
+    let mut stream = TcpStream::connect(authority).await?;
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    match response.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        status => Err(ExtensionError::transport(format!(
+            "Webhook {} answered {:?}",
+            url, status
+        ))),
+    }
+}
+
+async fn run_detection(
+    client: SdkClient,
+    rules: Vec<ThresholdRule>,
+    webhook: Option<String>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        for rule in &rules {
+            let alert = match rule.evaluate(&client).await {
+                Ok(Some(alert)) => alert,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Rule {} failed: {}", rule.name, e);
+                    continue;
+                }
+            };
+            info!("Rule {} triggered: {}", rule.name, alert);
+            let indexed = IndexRequest::new(ALERTS_INDEX).document(&alert);
+            if let Err(e) = async { client.index(indexed?).await }.await {
+                warn!("Failed to store alert for {}: {}", rule.name, e);
+            }
+            if let Some(url) = &webhook {
+                if let Err(e) = notify(url, &alert).await {
+                    warn!("Failed to notify {}: {}", url, e);
+                }
+            }
+        }
+    }
+}
+
+struct SecurityAnalytics {
+    assets: Arc<AssetCache>,
+    detection: Option<JoinHandle<()>>,
+}
+
+#[async_trait]
+impl Extension for SecurityAnalytics {
+    fn name(&self) -> &str {
+        "Security Analytics"
+    }
+
+    fn unique_id(&self) -> &str {
+        UNIQUE_ID
+    }
+
+    fn version(&self) -> &str {
+        "0.1.0"
+    }
+
+    fn opensearch_version(&self) -> &str {
+        "3.0.0"
+    }
+
+    fn setting_registry(&self) -> SettingRegistry {
+        let mut registry = SettingRegistry::new();
+        registry
+            .register(&webhook_url())
+            .expect("unique setting keys");
+        registry
+            .register(&interval_secs())
+            .expect("unique setting keys");
+        registry
+    }
+
+    fn rest_handlers(&self) -> Vec<Arc<dyn RestHandler>> {
+        vec![Arc::new(EventsHandler {
+            assets: self.assets.clone(),
+        })]
+    }
+
+    async fn initialize(&mut self, context: &ExtensionContext) -> Result<(), ExtensionError> {
+        let client = SdkClient::new(context.transport_client.clone(), UNIQUE_ID);
+
+        let mut indices = SystemIndexManager::new(client.clone());
+        indices.register(
+            SystemIndexDescriptor::new(EVENTS_INDEX, 1).mappings(
+                Mappings::new()
+                    .field(
+                        "@timestamp",
+                        FieldMapping::date().param("format", "epoch_millis"),
+                    )
+                    .field("source_ip", FieldMapping::new("ip"))
+                    .field("action", FieldMapping::keyword())
+                    .field("outcome", FieldMapping::keyword())
+                    .field(
+                        "asset",
+                        FieldMapping::object(
+                            Mappings::new()
+                                .field("owner", FieldMapping::keyword())
+                                .field("criticality", FieldMapping::keyword()),
+                        ),
+                    ),
+            ),
+        )?;
+        indices.register(
+            SystemIndexDescriptor::new(ALERTS_INDEX, 1).mappings(
+                Mappings::new()
+                    .field(
+                        "@timestamp",
+                        FieldMapping::date().param("format", "epoch_millis"),
+                    )
+                    .field("rule", FieldMapping::keyword())
+                    .field("count", FieldMapping::long()),
+            ),
+        )?;
+        indices.ensure().await?;
+
+        let rules = vec![
+            ThresholdRule {
+                name: "brute_force_login",
+                query: json!({"bool": {"filter": [{"term": {"action": "login"}}, {"term": {"outcome": "failure"}}]}}),
+                window: "5m",
+                threshold: 20,
+            },
+            ThresholdRule {
+                name: "critical_asset_denied",
+                query: json!({"bool": {"filter": [{"term": {"asset.criticality": "high"}}, {"term": {"outcome": "denied"}}]}}),
+                window: "15m",
+                threshold: 5,
+            },
+        ];
+        let webhook = webhook_url().get_opt(&context.settings)?;
+        let interval = interval_secs().get(&context.settings)?;
+        self.detection = Some(tokio::spawn(run_detection(
+            client,
+            rules,
+            webhook,
+            Duration::from_secs(interval as u64),
+        )));
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> Result<(), ExtensionError> {
+        if let Some(detection) = self.detection.take() {
+            detection.abort();
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // LOG_FORMAT=json for log aggregation.
+    let format = std::env::var("LOG_FORMAT")
+        .ok()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(LogFormat::Text);
+    init_logging(format)?;
+
+    let extension = SecurityAnalytics {
+        assets: Arc::new(AssetCache::new(Duration::from_secs(300))),
+        detection: None,
+    };
+    let mut runner = ExtensionBuilder::new("Security Analytics")
+        .unique_id(UNIQUE_ID)
+        .version("0.1.0")
+        .port(1235)
+        .transport_endpoint("localhost", 9300)
+        .setting(
+            "security_analytics.webhook_url",
+            "http://localhost:8080/alerts",
+        )
+        .setting("security_analytics.interval_secs", 60i64)
+        .build(extension)?;
+
+    runner.run().await?;
+
+    Ok(())
+}
@@ -0,0 +1,631 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::client::{
+    DeleteRequest, DocWriteResult, IndexRequest, Refresh, SdkClient, UpdateRequest,
+};
+use crate::extension::{ExtensionError, RetryPolicy, Retryable};
+
+pub const BULK_ACTION: &str = "indices:data/write/bulk";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOperation {
+    Index(IndexRequest),
+    Update(UpdateRequest),
+    Delete(DeleteRequest),
+}
+
+impl BulkOperation {
+    pub fn index(&self) -> &str {
+        match self {
+            BulkOperation::Index(request) => &request.index,
+            BulkOperation::Update(request) => &request.index,
+            BulkOperation::Delete(request) => &request.index,
+        }
+    }
+
+    /// Size of the operation once serialized.
+    pub fn estimated_size(&self) -> Result<usize, ExtensionError> {
+        serde_json::to_vec(self)
+            .map(|bytes| bytes.len())
+            .map_err(|e| {
+                ExtensionError::serialization(format!("Failed to serialize bulk operation: {}", e))
+            })
+    }
+}
+
+impl From<IndexRequest> for BulkOperation {
+    fn from(request: IndexRequest) -> Self {
+        BulkOperation::Index(request)
+    }
+}
+
+impl From<UpdateRequest> for BulkOperation {
+    fn from(request: UpdateRequest) -> Self {
+        BulkOperation::Update(request)
+    }
+}
+
+impl From<DeleteRequest> for BulkOperation {
+    fn from(request: DeleteRequest) -> Self {
+        BulkOperation::Delete(request)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BulkRequest {
+    pub operations: Vec<BulkOperation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<Refresh>,
+}
+
+impl BulkRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn operation(mut self, operation: impl Into<BulkOperation>) -> Self {
+        self.operations.push(operation.into());
+        self
+    }
+
+    pub fn refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = Some(refresh);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// The single index the operations target, or `_bulk` when they span several.
+    pub(crate) fn usage_index(&self) -> &str {
+        match self.operations.split_first() {
+            Some((first, rest)) if rest.iter().all(|op| op.index() == first.index()) => {
+                first.index()
+            }
+            _ => "_bulk",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkItemResponse {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_id", default)]
+    pub id: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub result: Option<DocWriteResult>,
+    #[serde(rename = "_version", default)]
+    pub version: Option<u64>,
+    #[serde(default)]
+    pub error: Option<Value>,
+}
+
+impl BulkItemResponse {
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Rejected because the cluster is overloaded, and worth sending again.
+    pub fn is_retryable(&self) -> bool {
+        self.status == 429
+    }
+}
+
+/// One entry of `items`: the operation (`index`, `create`, `update` or
+/// `delete`) and its outcome.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "BTreeMap<String, BulkItemResponse>")]
+pub struct BulkItem {
+    pub operation: String,
+    pub response: BulkItemResponse,
+}
+
+impl TryFrom<BTreeMap<String, BulkItemResponse>> for BulkItem {
+    type Error = String;
+
+    fn try_from(map: BTreeMap<String, BulkItemResponse>) -> Result<Self, Self::Error> {
+        let mut entries = map.into_iter();
+        match (entries.next(), entries.next()) {
+            (Some((operation, response)), None) => Ok(BulkItem {
+                operation,
+                response,
+            }),
+            _ => Err("bulk item must have exactly one operation".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BulkResponse {
+    pub took: u64,
+    pub errors: bool,
+    pub items: Vec<BulkItem>,
+}
+
+impl BulkResponse {
+    pub fn failures(&self) -> impl Iterator<Item = &BulkItem> {
+        self.items.iter().filter(|item| item.response.is_failed())
+    }
+}
+
+impl SdkClient {
+    pub async fn bulk(&self, request: BulkRequest) -> Result<BulkResponse, ExtensionError> {
+        if request.is_empty() {
+            return Err(ExtensionError::invalid_request(
+                "Bulk request has no operations",
+            ));
+        }
+        let index = request.usage_index().to_string();
+        self.execute(BULK_ACTION, &index, &request).await
+    }
+}
+
+/// Callbacks around each batch sent by a `BulkProcessor`. `after_bulk`
+/// receives the final outcome of every operation, after retries.
+pub trait BulkListener: Send + Sync {
+    fn before_bulk(&self, _batch: u64, _request: &BulkRequest) {}
+
+    fn after_bulk(&self, _batch: u64, _request: &BulkRequest, _response: &BulkResponse) {}
+
+    /// The whole batch failed, after retries.
+    fn on_failure(&self, _batch: u64, _request: &BulkRequest, _error: &ExtensionError) {}
+}
+
+pub struct BulkProcessorBuilder {
+    client: SdkClient,
+    max_actions: usize,
+    max_bytes: usize,
+    flush_interval: Option<Duration>,
+    concurrent_requests: usize,
+    retry: RetryPolicy,
+    refresh: Option<Refresh>,
+    listeners: Vec<Arc<dyn BulkListener>>,
+}
+
+impl BulkProcessorBuilder {
+    /// Flush once this many operations are buffered. Defaults to 1000.
+    pub fn max_actions(mut self, max_actions: usize) -> Self {
+        self.max_actions = max_actions.max(1);
+        self
+    }
+
+    /// Flush once the buffered operations reach this size. Defaults to 5mb.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Batches in flight at once; `add` waits when the limit is reached.
+    /// Defaults to 1.
+    pub fn concurrent_requests(mut self, concurrent_requests: usize) -> Self {
+        self.concurrent_requests = concurrent_requests.max(1);
+        self
+    }
+
+    /// Backoff for rejected operations and failed batches. Batches failing
+    /// with a non-retryable error, such as a mapping error, are not retried.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = Some(refresh);
+        self
+    }
+
+    pub fn listener(mut self, listener: Arc<dyn BulkListener>) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// Must be called within a tokio runtime when a flush interval is set.
+    pub fn build(self) -> BulkProcessor {
+        let inner = Arc::new(ProcessorInner {
+            permits: Arc::new(Semaphore::new(self.concurrent_requests)),
+            pending: Mutex::new(Pending::default()),
+            next_batch: AtomicU64::new(1),
+            client: self.client,
+            max_actions: self.max_actions,
+            max_bytes: self.max_bytes,
+            concurrent_requests: self.concurrent_requests,
+            retry: self.retry,
+            refresh: self.refresh,
+            listeners: self.listeners,
+        });
+
+        let ticker = self.flush_interval.map(|interval| {
+            let inner = Arc::downgrade(&inner);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    match inner.upgrade() {
+                        Some(inner) => inner.flush().await,
+                        None => break,
+                    }
+                }
+            })
+        });
+
+        BulkProcessor { inner, ticker }
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    operations: Vec<BulkOperation>,
+    bytes: usize,
+}
+
+struct ProcessorInner {
+    client: SdkClient,
+    max_actions: usize,
+    max_bytes: usize,
+    concurrent_requests: usize,
+    retry: RetryPolicy,
+    refresh: Option<Refresh>,
+    listeners: Vec<Arc<dyn BulkListener>>,
+    pending: Mutex<Pending>,
+    permits: Arc<Semaphore>,
+    next_batch: AtomicU64,
+}
+
+impl ProcessorInner {
+    async fn flush(self: &Arc<Self>) {
+        let operations = std::mem::take(&mut *self.pending.lock().await).operations;
+        self.dispatch(operations).await;
+    }
+
+    /// Send `operations` in the background once a slot is free.
+    async fn dispatch(self: &Arc<Self>, operations: Vec<BulkOperation>) {
+        if operations.is_empty() {
+            return;
+        }
+        let Ok(permit) = self.permits.clone().acquire_owned().await else {
+            return;
+        };
+        let inner = self.clone();
+        tokio::spawn(async move {
+            inner.execute(operations).await;
+            drop(permit);
+        });
+    }
+
+    async fn execute(&self, operations: Vec<BulkOperation>) {
+        let batch = self.next_batch.fetch_add(1, Ordering::SeqCst);
+        let request = BulkRequest {
+            operations,
+            refresh: self.refresh,
+        };
+        for listener in &self.listeners {
+            listener.before_bulk(batch, &request);
+        }
+
+        let mut outcomes: Vec<Option<BulkItem>> = vec![None; request.len()];
+        let mut remaining: Vec<usize> = (0..request.len()).collect();
+        let mut took = 0;
+        let mut attempt = 0;
+
+        while !remaining.is_empty() {
+            attempt += 1;
+            let last_attempt = attempt >= self.retry.max_attempts;
+            let attempt_request = BulkRequest {
+                operations: remaining
+                    .iter()
+                    .map(|&i| request.operations[i].clone())
+                    .collect(),
+                refresh: request.refresh,
+            };
+
+            let response = match self.client.bulk(attempt_request).await {
+                Ok(response) => response,
+                Err(e) if last_attempt || e.retryable() == Retryable::NonRetryable => {
+                    warn!(
+                        "Bulk batch {} failed after {} attempts: {}",
+                        batch, attempt, e
+                    );
+                    for listener in &self.listeners {
+                        listener.on_failure(batch, &request, &e);
+                    }
+                    return;
+                }
+                Err(e) => {
+                    tokio::time::sleep(self.retry.retry_delay(attempt, &e)).await;
+                    continue;
+                }
+            };
+
+            took += response.took;
+            let mut retry = Vec::new();
+            for (&i, item) in remaining.iter().zip(response.items) {
+                if item.response.is_retryable() && !last_attempt {
+                    retry.push(i);
+                }
+                outcomes[i] = Some(item);
+            }
+            remaining = retry;
+            if !remaining.is_empty() {
+                tokio::time::sleep(self.retry.delay(attempt)).await;
+            }
+        }
+
+        let items: Vec<BulkItem> = outcomes.into_iter().flatten().collect();
+        let response = BulkResponse {
+            took,
+            errors: items.iter().any(|item| item.response.is_failed()),
+            items,
+        };
+        for listener in &self.listeners {
+            listener.after_bulk(batch, &request, &response);
+        }
+    }
+}
+
+/// Buffers operations and sends them as bulk requests when `max_actions`,
+/// `max_bytes` or the flush interval is reached, retrying rejected
+/// operations with backoff.
+pub struct BulkProcessor {
+    inner: Arc<ProcessorInner>,
+    ticker: Option<JoinHandle<()>>,
+}
+
+impl BulkProcessor {
+    pub fn builder(client: SdkClient) -> BulkProcessorBuilder {
+        BulkProcessorBuilder {
+            client,
+            max_actions: 1000,
+            max_bytes: 5 * 1024 * 1024,
+            flush_interval: None,
+            concurrent_requests: 1,
+            retry: RetryPolicy::default(),
+            refresh: None,
+            listeners: Vec::new(),
+        }
+    }
+
+    pub async fn add(&self, operation: impl Into<BulkOperation>) -> Result<(), ExtensionError> {
+        let operation = operation.into();
+        let size = operation.estimated_size()?;
+
+        let full = {
+            let mut pending = self.inner.pending.lock().await;
+            pending.operations.push(operation);
+            pending.bytes += size;
+            if pending.operations.len() >= self.inner.max_actions
+                || pending.bytes >= self.inner.max_bytes
+            {
+                Some(std::mem::take(&mut *pending).operations)
+            } else {
+                None
+            }
+        };
+        if let Some(operations) = full {
+            self.inner.dispatch(operations).await;
+        }
+        Ok(())
+    }
+
+    /// Send whatever is buffered without waiting for it to complete.
+    pub async fn flush(&self) {
+        self.inner.flush().await;
+    }
+
+    /// Flush and wait for every batch in flight to finish.
+    pub async fn close(mut self) {
+        if let Some(ticker) = self.ticker.take() {
+            ticker.abort();
+        }
+        self.inner.flush().await;
+        let _ = self
+            .inner
+            .permits
+            .acquire_many(self.inner.concurrent_requests as u32)
+            .await;
+    }
+}
+
+impl Drop for BulkProcessor {
+    fn drop(&mut self) {
+        if let Some(ticker) = self.ticker.take() {
+            ticker.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{RemoteExtensionActionResponse, TransportActionRequestFromExtension};
+    use crate::interface::{Deserialize as _, Serialize as _};
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+    use crate::transport::TransportClient;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<(u64, usize, Vec<u16>)>>);
+
+    impl BulkListener for Recorder {
+        fn after_bulk(&self, batch: u64, request: &BulkRequest, response: &BulkResponse) {
+            let statuses = response
+                .items
+                .iter()
+                .map(|item| item.response.status)
+                .collect();
+            self.0
+                .lock()
+                .unwrap()
+                .push((batch, request.len(), statuses));
+        }
+    }
+
+    /// Answers bulk requests, rejecting the document with id "busy" the first time it is seen.
+    async fn bulk_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut rejected = false;
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let message = read_message(&mut stream).await.unwrap().unwrap();
+                let request = TransportActionRequestFromExtension::deserialize(
+                    &mut message.content.as_slice(),
+                )
+                .unwrap();
+                let body: Value = serde_json::from_slice(&request.request_bytes).unwrap();
+
+                let items: Vec<Value> = body["operations"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|op| {
+                        let id = op["index"]["id"].as_str().unwrap();
+                        let status = if id == "busy" && !std::mem::replace(&mut rejected, true) {
+                            429
+                        } else {
+                            201
+                        };
+                        json!({"index": {"_index": "logs", "_id": id, "status": status}})
+                    })
+                    .collect();
+                let response = RemoteExtensionActionResponse {
+                    success: true,
+                    response_bytes: json!({"took": 1, "errors": false, "items": items})
+                        .to_string()
+                        .into_bytes(),
+                };
+                let mut bytes = Vec::new();
+                response.serialize(&mut bytes).unwrap();
+                write_response(
+                    &mut stream,
+                    &message.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_bulk_processor_batches_and_retries() {
+        let port = bulk_server().await;
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let recorder = Arc::new(Recorder::default());
+        let processor = BulkProcessor::builder(client)
+            .max_actions(2)
+            .retry_policy(RetryPolicy {
+                initial_delay: Duration::from_millis(1),
+                jitter: false,
+                ..Default::default()
+            })
+            .listener(recorder.clone())
+            .build();
+
+        for id in ["1", "busy", "3"] {
+            processor
+                .add(IndexRequest::new("logs").id(id))
+                .await
+                .unwrap();
+        }
+        processor.close().await;
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![(1, 2, vec![201, 201]), (2, 1, vec![201])]
+        );
+    }
+
+    #[derive(Default)]
+    struct Failures(std::sync::Mutex<Vec<String>>);
+
+    impl BulkListener for Failures {
+        fn on_failure(&self, _batch: u64, _request: &BulkRequest, error: &ExtensionError) {
+            self.0.lock().unwrap().push(error.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_processor_does_not_retry_rejected_batch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = calls.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let message = read_message(&mut stream).await.unwrap().unwrap();
+                counted.fetch_add(1, Ordering::SeqCst);
+                let error = json!({"status": 400, "error": {"type": "mapper_parsing_exception", "reason": "failed to parse"}});
+                let mut bytes = Vec::new();
+                RemoteExtensionActionResponse {
+                    success: false,
+                    response_bytes: error.to_string().into_bytes(),
+                }
+                .serialize(&mut bytes)
+                .unwrap();
+                write_response(
+                    &mut stream,
+                    &message.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+        });
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let failures = Arc::new(Failures::default());
+        let processor = BulkProcessor::builder(client)
+            .retry_policy(RetryPolicy {
+                initial_delay: Duration::from_millis(1),
+                jitter: false,
+                ..Default::default()
+            })
+            .listener(failures.clone())
+            .build();
+
+        processor
+            .add(IndexRequest::new("logs").id("1"))
+            .await
+            .unwrap();
+        processor.close().await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(failures.0.lock().unwrap().len(), 1);
+        assert!(failures.0.lock().unwrap()[0].contains("mapper_parsing_exception"));
+    }
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const CLUSTER_HEALTH_ACTION: &str = "cluster:monitor/health";
+pub const CLUSTER_STATE_ACTION: &str = "cluster:monitor/state";
+pub const CLUSTER_GET_SETTINGS_ACTION: &str = "cluster:monitor/settings";
+pub const CLUSTER_UPDATE_SETTINGS_ACTION: &str = "cluster:admin/settings/update";
+pub const NODES_INFO_ACTION: &str = "cluster:monitor/nodes/info";
+
+/// Cluster or index health, ordered from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Red,
+    Yellow,
+    Green,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClusterHealthRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub indices: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_for_status: Option<HealthStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_for_nodes: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+}
+
+impl ClusterHealthRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn indices(mut self, indices: &[&str]) -> Self {
+        self.indices = indices.iter().map(|i| i.to_string()).collect();
+        self
+    }
+
+    /// Wait until the cluster (or the requested indices) reach `status`.
+    pub fn wait_for_status(mut self, status: HealthStatus) -> Self {
+        self.wait_for_status = Some(status);
+        self
+    }
+
+    /// Wait for a node count such as `"3"` or `">=2"`.
+    pub fn wait_for_nodes(mut self, nodes: impl Into<String>) -> Self {
+        self.wait_for_nodes = Some(nodes.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: impl Into<String>) -> Self {
+        self.timeout = Some(timeout.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterHealthResponse {
+    pub cluster_name: String,
+    pub status: HealthStatus,
+    #[serde(default)]
+    pub timed_out: bool,
+    pub number_of_nodes: u32,
+    pub number_of_data_nodes: u32,
+    #[serde(default)]
+    pub active_primary_shards: u32,
+    #[serde(default)]
+    pub active_shards: u32,
+    #[serde(default)]
+    pub relocating_shards: u32,
+    #[serde(default)]
+    pub initializing_shards: u32,
+    #[serde(default)]
+    pub unassigned_shards: u32,
+    #[serde(default)]
+    pub number_of_pending_tasks: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClusterStateRequest {
+    /// Parts of the state to return, e.g. `nodes`, `metadata`, `routing_table`.
+    /// Empty returns everything.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub metrics: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub indices: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub local: bool,
+}
+
+impl ClusterStateRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn metrics(mut self, metrics: &[&str]) -> Self {
+        self.metrics = metrics.iter().map(|m| m.to_string()).collect();
+        self
+    }
+
+    pub fn indices(mut self, indices: &[&str]) -> Self {
+        self.indices = indices.iter().map(|i| i.to_string()).collect();
+        self
+    }
+
+    /// Read the state of the node handling the request rather than the
+    /// cluster manager's.
+    pub fn local(mut self, local: bool) -> Self {
+        self.local = local;
+        self
+    }
+}
+
+/// Cluster state. Only the fields common to every request are typed; the
+/// requested metrics are left as JSON in `parts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterStateResponse {
+    pub cluster_name: String,
+    #[serde(default)]
+    pub cluster_uuid: Option<String>,
+    #[serde(default)]
+    pub version: Option<u64>,
+    #[serde(default)]
+    pub cluster_manager_node: Option<String>,
+    #[serde(flatten)]
+    pub parts: BTreeMap<String, Value>,
+}
+
+/// Persistent, transient and (when requested) default cluster settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClusterSettingsResponse {
+    #[serde(default)]
+    pub persistent: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub transient: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub defaults: BTreeMap<String, Value>,
+}
+
+impl ClusterSettingsResponse {
+    /// Effective value of `key`: transient over persistent over default.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.transient
+            .get(key)
+            .or_else(|| self.persistent.get(key))
+            .or_else(|| self.defaults.get(key))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub(crate) struct ClusterGetSettingsRequest {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub include_defaults: bool,
+}
+
+/// Settings to change. A `null` value resets the setting to its default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClusterUpdateSettingsRequest {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub persistent: BTreeMap<String, Value>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub transient: BTreeMap<String, Value>,
+}
+
+impl ClusterUpdateSettingsRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn persistent(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.persistent.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn transient(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.transient.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn reset_persistent(self, key: impl Into<String>) -> Self {
+        self.persistent(key, Value::Null)
+    }
+
+    pub fn reset_transient(self, key: impl Into<String>) -> Self {
+        self.transient(key, Value::Null)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.persistent.is_empty() && self.transient.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClusterUpdateSettingsResponse {
+    pub acknowledged: bool,
+    #[serde(default)]
+    pub persistent: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub transient: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NodesInfoRequest {
+    /// Node ids, names or selectors such as `_local`. Empty means all nodes.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub node_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub metrics: Vec<String>,
+}
+
+impl NodesInfoRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn node_ids(mut self, node_ids: &[&str]) -> Self {
+        self.node_ids = node_ids.iter().map(|n| n.to_string()).collect();
+        self
+    }
+
+    pub fn metrics(mut self, metrics: &[&str]) -> Self {
+        self.metrics = metrics.iter().map(|m| m.to_string()).collect();
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub name: String,
+    pub transport_address: String,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub ip: Option<String>,
+    pub version: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    /// Sections for the requested metrics, such as `os` or `plugins`.
+    #[serde(flatten)]
+    pub metrics: BTreeMap<String, Value>,
+}
+
+impl NodeInfo {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodesInfoResponse {
+    pub cluster_name: String,
+    /// Nodes by node id.
+    pub nodes: BTreeMap<String, NodeInfo>,
+}
+
+impl NodesInfoResponse {
+    /// Nodes with `role`, e.g. `data` or `cluster_manager`.
+    pub fn nodes_with_role<'a>(
+        &'a self,
+        role: &'a str,
+    ) -> impl Iterator<Item = (&'a String, &'a NodeInfo)> {
+        self.nodes
+            .iter()
+            .filter(move |(_, node)| node.has_role(role))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cluster_responses() {
+        let health: ClusterHealthResponse = serde_json::from_value(json!({
+            "cluster_name": "docker-cluster", "status": "yellow", "timed_out": false,
+            "number_of_nodes": 1, "number_of_data_nodes": 1, "unassigned_shards": 2,
+            "discovered_cluster_manager": true
+        }))
+        .unwrap();
+        assert_eq!(health.status, HealthStatus::Yellow);
+        assert!(health.status >= HealthStatus::Yellow && health.status < HealthStatus::Green);
+
+        let settings: ClusterSettingsResponse = serde_json::from_value(json!({
+            "persistent": {"cluster.routing.allocation.enable": "primaries", "action.auto_create_index": "false"},
+            "transient": {"cluster.routing.allocation.enable": "all"},
+        }))
+        .unwrap();
+        assert_eq!(
+            settings.get("cluster.routing.allocation.enable").unwrap(),
+            "all"
+        );
+        assert_eq!(settings.get("action.auto_create_index").unwrap(), "false");
+        assert!(settings.get("missing").is_none());
+
+        let update = ClusterUpdateSettingsRequest::new()
+            .reset_transient("cluster.routing.allocation.enable");
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({"transient": {"cluster.routing.allocation.enable": null}})
+        );
+
+        let nodes: NodesInfoResponse = serde_json::from_value(json!({
+            "cluster_name": "docker-cluster",
+            "nodes": {
+                "n1": {"name": "node-1", "transport_address": "10.0.0.1:9300", "version": "3.0.0",
+                       "roles": ["cluster_manager", "data"], "os": {"name": "Linux"}},
+                "n2": {"name": "node-2", "transport_address": "10.0.0.2:9300", "version": "3.0.0",
+                       "roles": ["ingest"]}
+            }
+        }))
+        .unwrap();
+        let data: Vec<_> = nodes
+            .nodes_with_role("data")
+            .map(|(id, _)| id.as_str())
+            .collect();
+        assert_eq!(data, vec!["n1"]);
+        assert_eq!(nodes.nodes["n1"].metrics["os"]["name"], "Linux");
+    }
+}
@@ -0,0 +1,349 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::extension::ExtensionError;
+
+pub const INDEX_ACTION: &str = "indices:data/write/index";
+pub const GET_ACTION: &str = "indices:data/read/get";
+pub const UPDATE_ACTION: &str = "indices:data/write/update";
+pub const DELETE_ACTION: &str = "indices:data/write/delete";
+
+/// The `refresh` parameter of write requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Refresh {
+    True,
+    False,
+    WaitFor,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpType {
+    #[default]
+    Index,
+    /// Fail if a document with the same id already exists.
+    Create,
+}
+
+pub(crate) fn to_value(value: &impl Serialize) -> Result<Value, ExtensionError> {
+    serde_json::to_value(value)
+        .map_err(|e| ExtensionError::serialization(format!("Failed to serialize document: {}", e)))
+}
+
+/// Builder for `SdkClient::index`, e.g.
+/// `IndexRequest::new("logs").id("1").document(&entry)?`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndexRequest {
+    pub index: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub op_type: OpType,
+    pub source: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<Refresh>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub if_seq_no: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub if_primary_term: Option<u64>,
+}
+
+impl IndexRequest {
+    pub fn new(index: impl Into<String>) -> Self {
+        IndexRequest {
+            index: index.into(),
+            id: None,
+            op_type: OpType::Index,
+            source: Value::Object(Default::default()),
+            routing: None,
+            refresh: None,
+            if_seq_no: None,
+            if_primary_term: None,
+        }
+    }
+
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    pub fn document(mut self, document: &impl Serialize) -> Result<Self, ExtensionError> {
+        self.source = to_value(document)?;
+        Ok(self)
+    }
+
+    pub fn op_type(mut self, op_type: OpType) -> Self {
+        self.op_type = op_type;
+        self
+    }
+
+    pub fn routing(mut self, routing: impl Into<String>) -> Self {
+        self.routing = Some(routing.into());
+        self
+    }
+
+    pub fn refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = Some(refresh);
+        self
+    }
+
+    /// Only write if the document is still at this sequence number and primary term.
+    pub fn if_seq_no_primary_term(mut self, seq_no: u64, primary_term: u64) -> Self {
+        self.if_seq_no = Some(seq_no);
+        self.if_primary_term = Some(primary_term);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GetRequest {
+    pub index: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_includes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub source_excludes: Vec<String>,
+}
+
+impl GetRequest {
+    pub fn new(index: impl Into<String>, id: impl Into<String>) -> Self {
+        GetRequest {
+            index: index.into(),
+            id: id.into(),
+            routing: None,
+            source_includes: Vec::new(),
+            source_excludes: Vec::new(),
+        }
+    }
+
+    pub fn routing(mut self, routing: impl Into<String>) -> Self {
+        self.routing = Some(routing.into());
+        self
+    }
+
+    pub fn source_includes(mut self, fields: &[&str]) -> Self {
+        self.source_includes = fields.iter().map(|f| f.to_string()).collect();
+        self
+    }
+
+    pub fn source_excludes(mut self, fields: &[&str]) -> Self {
+        self.source_excludes = fields.iter().map(|f| f.to_string()).collect();
+        self
+    }
+}
+
+/// Builder for `SdkClient::update`: a partial `doc` or a `script`, with an
+/// optional `upsert` document.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpdateRequest {
+    pub index: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upsert: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub doc_as_upsert: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_on_conflict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<Refresh>,
+}
+
+impl UpdateRequest {
+    pub fn new(index: impl Into<String>, id: impl Into<String>) -> Self {
+        UpdateRequest {
+            index: index.into(),
+            id: id.into(),
+            doc: None,
+            script: None,
+            upsert: None,
+            doc_as_upsert: false,
+            retry_on_conflict: None,
+            routing: None,
+            refresh: None,
+        }
+    }
+
+    pub fn doc(mut self, doc: &impl Serialize) -> Result<Self, ExtensionError> {
+        self.doc = Some(to_value(doc)?);
+        Ok(self)
+    }
+
+    /// A script such as `{"source": "ctx._source.count += params.n", "params": {"n": 1}}`.
+    pub fn script(mut self, script: Value) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    pub fn upsert(mut self, document: &impl Serialize) -> Result<Self, ExtensionError> {
+        self.upsert = Some(to_value(document)?);
+        Ok(self)
+    }
+
+    pub fn doc_as_upsert(mut self, doc_as_upsert: bool) -> Self {
+        self.doc_as_upsert = doc_as_upsert;
+        self
+    }
+
+    pub fn retry_on_conflict(mut self, retries: u32) -> Self {
+        self.retry_on_conflict = Some(retries);
+        self
+    }
+
+    pub fn routing(mut self, routing: impl Into<String>) -> Self {
+        self.routing = Some(routing.into());
+        self
+    }
+
+    pub fn refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = Some(refresh);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), ExtensionError> {
+        match (&self.doc, &self.script) {
+            (None, None) => Err(ExtensionError::invalid_request(
+                "Update requires a doc or a script",
+            )),
+            (Some(_), Some(_)) => Err(ExtensionError::invalid_request(
+                "Update cannot have both a doc and a script",
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeleteRequest {
+    pub index: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<Refresh>,
+}
+
+impl DeleteRequest {
+    pub fn new(index: impl Into<String>, id: impl Into<String>) -> Self {
+        DeleteRequest {
+            index: index.into(),
+            id: id.into(),
+            routing: None,
+            refresh: None,
+        }
+    }
+
+    pub fn routing(mut self, routing: impl Into<String>) -> Self {
+        self.routing = Some(routing.into());
+        self
+    }
+
+    pub fn refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = Some(refresh);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocWriteResult {
+    Created,
+    Updated,
+    Deleted,
+    NotFound,
+    Noop,
+}
+
+/// Response to index, update and delete requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocWriteResponse {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_version", default)]
+    pub version: u64,
+    pub result: DocWriteResult,
+    #[serde(rename = "_seq_no", default)]
+    pub seq_no: u64,
+    #[serde(rename = "_primary_term", default)]
+    pub primary_term: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+pub struct GetResponse<T = Value> {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub found: bool,
+    #[serde(rename = "_version", default)]
+    pub version: Option<u64>,
+    #[serde(rename = "_seq_no", default)]
+    pub seq_no: Option<u64>,
+    #[serde(rename = "_primary_term", default)]
+    pub primary_term: Option<u64>,
+    #[serde(rename = "_source", default)]
+    pub source: Option<T>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Deserialize)]
+    struct LogEntry {
+        message: String,
+    }
+
+    #[test]
+    fn test_document_requests() {
+        let request = IndexRequest::new("logs")
+            .id("1")
+            .op_type(OpType::Create)
+            .refresh(Refresh::WaitFor)
+            .document(&json!({"message": "hello"}))
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "index": "logs", "id": "1", "op_type": "create",
+                "source": {"message": "hello"}, "refresh": "wait_for"
+            })
+        );
+
+        assert!(UpdateRequest::new("logs", "1").validate().is_err());
+        let update = UpdateRequest::new("logs", "1")
+            .doc(&json!({"n": 1}))
+            .unwrap()
+            .doc_as_upsert(true);
+        assert!(update.validate().is_ok());
+        assert_eq!(
+            serde_json::to_value(&update).unwrap()["doc_as_upsert"],
+            true
+        );
+
+        let response: GetResponse<LogEntry> = serde_json::from_value(json!({
+            "_index": "logs", "_id": "1", "found": true, "_version": 2, "_seq_no": 5,
+            "_primary_term": 1, "_source": {"message": "hello"}
+        }))
+        .unwrap();
+        assert_eq!(response.source.unwrap().message, "hello");
+
+        let missing: GetResponse =
+            serde_json::from_value(json!({"_index": "logs", "_id": "2", "found": false})).unwrap();
+        assert!(!missing.found && missing.source.is_none());
+    }
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::extension::ExtensionError;
+
+pub const CREATE_INDEX_ACTION: &str = "indices:admin/create";
+pub const DELETE_INDEX_ACTION: &str = "indices:admin/delete";
+pub const INDICES_EXISTS_ACTION: &str = "indices:admin/exists";
+pub const GET_MAPPINGS_ACTION: &str = "indices:admin/mappings/get";
+pub const PUT_MAPPING_ACTION: &str = "indices:admin/mapping/put";
+pub const OPEN_INDEX_ACTION: &str = "indices:admin/open";
+pub const CLOSE_INDEX_ACTION: &str = "indices:admin/close";
+pub const ALIASES_ACTION: &str = "indices:admin/aliases";
+
+/// How unmapped fields in new documents are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dynamic {
+    True,
+    False,
+    Strict,
+}
+
+/// Mapping of a single field, e.g. `FieldMapping::keyword()` or
+/// `FieldMapping::new("date").param("format", "epoch_millis")`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldMapping(Map<String, Value>);
+
+impl FieldMapping {
+    pub fn new(field_type: impl Into<String>) -> Self {
+        let mut params = Map::new();
+        params.insert("type".to_string(), Value::String(field_type.into()));
+        FieldMapping(params)
+    }
+
+    pub fn keyword() -> Self {
+        Self::new("keyword")
+    }
+
+    pub fn text() -> Self {
+        Self::new("text")
+    }
+
+    pub fn long() -> Self {
+        Self::new("long")
+    }
+
+    pub fn double() -> Self {
+        Self::new("double")
+    }
+
+    pub fn boolean() -> Self {
+        Self::new("boolean")
+    }
+
+    pub fn date() -> Self {
+        Self::new("date")
+    }
+
+    /// An object field with its own properties.
+    pub fn object(mappings: Mappings) -> Self {
+        let mut params = Map::new();
+        if let Some(dynamic) = mappings.dynamic {
+            params.insert(
+                "dynamic".to_string(),
+                serde_json::to_value(dynamic).expect("enum serializes"),
+            );
+        }
+        params.insert(
+            "properties".to_string(),
+            properties_value(mappings.properties),
+        );
+        FieldMapping(params)
+    }
+
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.0.insert(name.into(), value.into());
+        self
+    }
+}
+
+fn properties_value(properties: BTreeMap<String, FieldMapping>) -> Value {
+    Value::Object(
+        properties
+            .into_iter()
+            .map(|(name, field)| (name, Value::Object(field.0)))
+            .collect(),
+    )
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Mappings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic: Option<Dynamic>,
+    pub properties: BTreeMap<String, FieldMapping>,
+    #[serde(rename = "_meta", skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, Value>,
+}
+
+impl Mappings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dynamic(mut self, dynamic: Dynamic) -> Self {
+        self.dynamic = Some(dynamic);
+        self
+    }
+
+    pub fn field(mut self, name: impl Into<String>, mapping: FieldMapping) -> Self {
+        self.properties.insert(name.into(), mapping);
+        self
+    }
+
+    /// Application metadata kept with the mapping under `_meta`.
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.meta.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Index settings, written with their full `index.` keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IndexSettings(BTreeMap<String, Value>);
+
+impl IndexSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn number_of_shards(self, shards: u32) -> Self {
+        self.setting("number_of_shards", shards)
+    }
+
+    pub fn number_of_replicas(self, replicas: u32) -> Self {
+        self.setting("number_of_replicas", replicas)
+    }
+
+    /// Mark the index hidden, as extensions usually want for system indices.
+    pub fn hidden(self, hidden: bool) -> Self {
+        self.setting("hidden", hidden)
+    }
+
+    pub fn refresh_interval(self, interval: impl Into<String>) -> Self {
+        self.setting("refresh_interval", interval.into())
+    }
+
+    /// Any other setting; the `index.` prefix is added when missing.
+    pub fn setting(mut self, key: &str, value: impl Into<Value>) -> Self {
+        let key = if key.starts_with("index.") {
+            key.to_string()
+        } else {
+            format!("index.{}", key)
+        };
+        self.0.insert(key, value.into());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CreateIndexRequest {
+    pub index: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mappings: Option<Mappings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<IndexSettings>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Value>,
+}
+
+impl CreateIndexRequest {
+    pub fn new(index: impl Into<String>) -> Self {
+        CreateIndexRequest {
+            index: index.into(),
+            mappings: None,
+            settings: None,
+            aliases: BTreeMap::new(),
+        }
+    }
+
+    pub fn mappings(mut self, mappings: Mappings) -> Self {
+        self.mappings = Some(mappings);
+        self
+    }
+
+    pub fn settings(mut self, settings: IndexSettings) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.insert(alias.into(), Value::Object(Map::new()));
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), ExtensionError> {
+        validate_index_name(&self.index)
+    }
+}
+
+/// Check `index` against OpenSearch's index naming rules.
+pub fn validate_index_name(index: &str) -> Result<(), ExtensionError> {
+    let invalid = |reason: &str| {
+        Err(ExtensionError::invalid_request(format!(
+            "Invalid index name [{}]: {}",
+            index, reason
+        )))
+    };
+    if index.is_empty() {
+        return invalid("must not be empty");
+    }
+    if index != index.to_lowercase() {
+        return invalid("must be lowercase");
+    }
+    if index.starts_with(['-', '_', '+']) {
+        return invalid("must not start with '_', '-' or '+'");
+    }
+    if index == "." || index == ".." {
+        return invalid("must not be '.' or '..'");
+    }
+    if let Some(c) = index.chars().find(|c| r#"\/*?"<>| ,#:"#.contains(*c)) {
+        return invalid(&format!("must not contain '{}'", c));
+    }
+    if index.len() > 255 {
+        return invalid("must not be longer than 255 bytes");
+    }
+    Ok(())
+}
+
+/// Request naming one or more indices, used by delete, exists, open, close
+/// and get mapping.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndicesRequest {
+    pub indices: Vec<String>,
+}
+
+impl IndicesRequest {
+    pub fn new(indices: &[&str]) -> Self {
+        IndicesRequest {
+            indices: indices.iter().map(|i| i.to_string()).collect(),
+        }
+    }
+
+    /// Name used to attribute usage of this request.
+    pub(crate) fn usage_index(&self) -> String {
+        self.indices.join(",")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PutMappingRequest {
+    pub indices: Vec<String>,
+    pub mappings: Mappings,
+}
+
+impl PutMappingRequest {
+    pub fn new(indices: &[&str], mappings: Mappings) -> Self {
+        PutMappingRequest {
+            indices: indices.iter().map(|i| i.to_string()).collect(),
+            mappings,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AliasAction {
+    Add {
+        index: String,
+        alias: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        filter: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        is_write_index: Option<bool>,
+    },
+    Remove {
+        index: String,
+        alias: String,
+    },
+}
+
+/// Alias changes applied atomically, e.g. to switch an alias between indices.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AliasesRequest {
+    pub actions: Vec<AliasAction>,
+}
+
+impl AliasesRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_alias(mut self, index: impl Into<String>, alias: impl Into<String>) -> Self {
+        self.actions.push(AliasAction::Add {
+            index: index.into(),
+            alias: alias.into(),
+            filter: None,
+            is_write_index: None,
+        });
+        self
+    }
+
+    pub fn add_write_alias(mut self, index: impl Into<String>, alias: impl Into<String>) -> Self {
+        self.actions.push(AliasAction::Add {
+            index: index.into(),
+            alias: alias.into(),
+            filter: None,
+            is_write_index: Some(true),
+        });
+        self
+    }
+
+    pub fn remove_alias(mut self, index: impl Into<String>, alias: impl Into<String>) -> Self {
+        self.actions.push(AliasAction::Remove {
+            index: index.into(),
+            alias: alias.into(),
+        });
+        self
+    }
+
+    pub(crate) fn usage_index(&self) -> String {
+        let mut indices: Vec<&str> = self
+            .actions
+            .iter()
+            .map(|action| match action {
+                AliasAction::Add { index, .. } | AliasAction::Remove { index, .. } => {
+                    index.as_str()
+                }
+            })
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices.join(",")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Acknowledged {
+    pub acknowledged: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateIndexResponse {
+    pub acknowledged: bool,
+    #[serde(default)]
+    pub shards_acknowledged: bool,
+    pub index: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ExistsResponse {
+    pub exists: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexMappings {
+    #[serde(default)]
+    pub mappings: Value,
+}
+
+/// Mappings of each requested index, by index name.
+pub type GetMappingsResponse = BTreeMap<String, IndexMappings>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_create_index_request() {
+        let request = CreateIndexRequest::new(".hello-state")
+            .settings(IndexSettings::new().number_of_shards(1).hidden(true))
+            .mappings(
+                Mappings::new()
+                    .dynamic(Dynamic::Strict)
+                    .field("name", FieldMapping::keyword())
+                    .field(
+                        "updated",
+                        FieldMapping::date().param("format", "epoch_millis"),
+                    )
+                    .field(
+                        "owner",
+                        FieldMapping::object(Mappings::new().field("id", FieldMapping::long())),
+                    ),
+            )
+            .alias("hello-state");
+        assert!(request.validate().is_ok());
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "index": ".hello-state",
+                "settings": {"index.hidden": true, "index.number_of_shards": 1},
+                "mappings": {
+                    "dynamic": "strict",
+                    "properties": {
+                        "name": {"type": "keyword"},
+                        "updated": {"type": "date", "format": "epoch_millis"},
+                        "owner": {"properties": {"id": {"type": "long"}}}
+                    }
+                },
+                "aliases": {"hello-state": {}}
+            })
+        );
+
+        assert!(validate_index_name("Logs").is_err());
+        assert!(validate_index_name("_logs").is_err());
+        assert!(validate_index_name("logs*").is_err());
+
+        let aliases = AliasesRequest::new()
+            .remove_alias("logs-1", "logs")
+            .add_write_alias("logs-2", "logs");
+        assert_eq!(aliases.usage_index(), "logs-1,logs-2");
+        assert_eq!(
+            serde_json::to_value(&aliases).unwrap()["actions"][1],
+            json!({
+                "add": {"index": "logs-2", "alias": "logs", "is_write_index": true}
+            })
+        );
+    }
+}
@@ -0,0 +1,732 @@
+pub mod bulk;
+pub mod cluster;
+pub mod document;
+pub mod indices;
+pub mod multi;
+pub mod policy;
+pub mod search;
+pub mod security;
+pub mod system_index;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::extension::identity::PRINCIPAL_HEADER;
+use crate::extension::{ExtensionError, PrincipalIdentifier};
+use crate::interface::{
+    self, read_bool, read_byte_array, read_string, write_bool, write_byte_array, write_string,
+};
+use crate::transport::{Deadline, ThreadContext, TransportClient, UsageScope};
+
+pub use bulk::{BulkOperation, BulkProcessor, BulkRequest, BulkResponse};
+pub use cluster::{
+    ClusterHealthRequest, ClusterHealthResponse, ClusterSettingsResponse, ClusterStateRequest,
+    ClusterStateResponse, ClusterUpdateSettingsRequest, ClusterUpdateSettingsResponse,
+    HealthStatus, NodeInfo, NodesInfoRequest, NodesInfoResponse,
+};
+pub use document::{
+    DeleteRequest, DocWriteResponse, DocWriteResult, GetRequest, GetResponse, IndexRequest, OpType,
+    Refresh, UpdateRequest,
+};
+pub use indices::{
+    Acknowledged, AliasesRequest, CreateIndexRequest, CreateIndexResponse, Dynamic, FieldMapping,
+    GetMappingsResponse, IndexSettings, IndicesRequest, Mappings, PutMappingRequest,
+};
+pub use multi::{
+    ItemResult, MultiGetRequest, MultiGetResponse, MultiSearchRequest, MultiSearchResponse,
+};
+pub use policy::{
+    ClientPolicies, OperationCategory, OperationPolicy, PolicyReport, POLICY_SETTINGS_PREFIX,
+};
+pub use search::{Hit, ScrollStream, SearchRequest, SearchResponse};
+pub use security::{OnBehalfOfRequest, OnBehalfOfToken, SecurityClient, AUTHORIZATION_HEADER};
+pub use system_index::{Migration, SystemIndexDescriptor, SystemIndexManager, SystemIndexStatus};
+
+/// Lets an extension run a cluster transport action through OpenSearch.
+pub const PROXY_ACTION: &str = "internal:extensions/request-transportaction-from-extension";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportActionRequestFromExtension {
+    pub action: String,
+    pub request_bytes: Vec<u8>,
+    pub unique_id: String,
+}
+
+impl interface::Serialize for TransportActionRequestFromExtension {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_string(buf, &self.action)?;
+        written += write_byte_array(buf, &self.request_bytes)?;
+        written += write_string(buf, &self.unique_id)?;
+        Ok(written)
+    }
+}
+
+impl interface::Deserialize for TransportActionRequestFromExtension {
+    type Output = Self;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self> {
+        Ok(TransportActionRequestFromExtension {
+            action: read_string(buf)?,
+            request_bytes: read_byte_array(buf)?,
+            unique_id: read_string(buf)?,
+        })
+    }
+}
+
+/// Reply to `PROXY_ACTION`. On failure `response_bytes` holds an
+/// OpenSearch error body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteExtensionActionResponse {
+    pub success: bool,
+    pub response_bytes: Vec<u8>,
+}
+
+impl interface::Serialize for RemoteExtensionActionResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let written = write_bool(buf, self.success)?;
+        Ok(written + write_byte_array(buf, &self.response_bytes)?)
+    }
+}
+
+impl interface::Deserialize for RemoteExtensionActionResponse {
+    type Output = Self;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self> {
+        Ok(RemoteExtensionActionResponse {
+            success: read_bool(buf)?,
+            response_bytes: read_byte_array(buf)?,
+        })
+    }
+}
+
+/// Client for the cluster the extension is connected to, running actions
+/// through OpenSearch's extension proxy.
+#[derive(Clone)]
+pub struct SdkClient {
+    transport: Arc<TransportClient>,
+    unique_id: String,
+    policies: Arc<ClientPolicies>,
+    policy: Option<Arc<OperationPolicy>>,
+    principal: Option<PrincipalIdentifier>,
+}
+
+impl SdkClient {
+    pub fn new(transport: Arc<TransportClient>, unique_id: impl Into<String>) -> Self {
+        SdkClient {
+            transport,
+            unique_id: unique_id.into(),
+            policies: Arc::new(ClientPolicies::default()),
+            policy: None,
+            principal: None,
+        }
+    }
+
+    /// Retry, timeout and circuit policies per operation category.
+    pub fn with_policies(mut self, policies: Arc<ClientPolicies>) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    /// A client running every call under `policy` instead of its category's,
+    /// e.g. `client.with_policy(OperationPolicy::new().retries(5)).search(request)`.
+    pub fn with_policy(&self, policy: OperationPolicy) -> Self {
+        SdkClient {
+            policy: Some(Arc::new(policy)),
+            ..self.clone()
+        }
+    }
+
+    /// A client running every call on behalf of `principal`, usually
+    /// `request.principal()` of the REST request being handled, so the
+    /// cluster applies that user's permissions rather than the extension's.
+    pub fn on_behalf_of(&self, principal: &PrincipalIdentifier) -> Self {
+        SdkClient {
+            principal: Some(principal.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Unique id of the extension the calls are made for.
+    pub fn unique_id(&self) -> &str {
+        &self.unique_id
+    }
+
+    pub fn security(&self) -> SecurityClient {
+        SecurityClient::new(self.clone())
+    }
+
+    pub fn policies(&self) -> &Arc<ClientPolicies> {
+        &self.policies
+    }
+
+    pub async fn index(&self, request: IndexRequest) -> Result<DocWriteResponse, ExtensionError> {
+        let index = request.index.clone();
+        self.execute(document::INDEX_ACTION, &index, &request).await
+    }
+
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        request: GetRequest,
+    ) -> Result<GetResponse<T>, ExtensionError> {
+        let index = request.index.clone();
+        self.execute(document::GET_ACTION, &index, &request).await
+    }
+
+    pub async fn update(&self, request: UpdateRequest) -> Result<DocWriteResponse, ExtensionError> {
+        request.validate()?;
+        let index = request.index.clone();
+        self.execute(document::UPDATE_ACTION, &index, &request)
+            .await
+    }
+
+    pub async fn delete(&self, request: DeleteRequest) -> Result<DocWriteResponse, ExtensionError> {
+        let index = request.index.clone();
+        self.execute(document::DELETE_ACTION, &index, &request)
+            .await
+    }
+
+    pub async fn create_index(
+        &self,
+        request: CreateIndexRequest,
+    ) -> Result<CreateIndexResponse, ExtensionError> {
+        request.validate()?;
+        let index = request.index.clone();
+        self.execute(indices::CREATE_INDEX_ACTION, &index, &request)
+            .await
+    }
+
+    /// Create the index unless it already exists, returning whether it was
+    /// created. Meant for bootstrapping system indices in `initialize`,
+    /// where several nodes of the extension may race to create them.
+    pub async fn ensure_index(&self, request: CreateIndexRequest) -> Result<bool, ExtensionError> {
+        if self.index_exists(&request.index).await? {
+            return Ok(false);
+        }
+        match self.create_index(request).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_remote_exception(400, "resource_already_exists_exception") => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn delete_index(&self, indices: &[&str]) -> Result<Acknowledged, ExtensionError> {
+        let request = IndicesRequest::new(indices);
+        self.execute(
+            indices::DELETE_INDEX_ACTION,
+            &request.usage_index(),
+            &request,
+        )
+        .await
+    }
+
+    pub async fn index_exists(&self, index: &str) -> Result<bool, ExtensionError> {
+        let request = IndicesRequest::new(&[index]);
+        let response: indices::ExistsResponse = self
+            .execute(indices::INDICES_EXISTS_ACTION, index, &request)
+            .await?;
+        Ok(response.exists)
+    }
+
+    pub async fn get_mappings(
+        &self,
+        indices: &[&str],
+    ) -> Result<GetMappingsResponse, ExtensionError> {
+        let request = IndicesRequest::new(indices);
+        self.execute(
+            indices::GET_MAPPINGS_ACTION,
+            &request.usage_index(),
+            &request,
+        )
+        .await
+    }
+
+    pub async fn put_mapping(
+        &self,
+        request: PutMappingRequest,
+    ) -> Result<Acknowledged, ExtensionError> {
+        let index = request.indices.join(",");
+        self.execute(indices::PUT_MAPPING_ACTION, &index, &request)
+            .await
+    }
+
+    pub async fn open_index(&self, indices: &[&str]) -> Result<Acknowledged, ExtensionError> {
+        let request = IndicesRequest::new(indices);
+        self.execute(indices::OPEN_INDEX_ACTION, &request.usage_index(), &request)
+            .await
+    }
+
+    pub async fn close_index(&self, indices: &[&str]) -> Result<Acknowledged, ExtensionError> {
+        let request = IndicesRequest::new(indices);
+        self.execute(
+            indices::CLOSE_INDEX_ACTION,
+            &request.usage_index(),
+            &request,
+        )
+        .await
+    }
+
+    pub async fn update_aliases(
+        &self,
+        request: AliasesRequest,
+    ) -> Result<Acknowledged, ExtensionError> {
+        if request.actions.is_empty() {
+            return Err(ExtensionError::invalid_request(
+                "Alias update requires at least one action",
+            ));
+        }
+        self.execute(indices::ALIASES_ACTION, &request.usage_index(), &request)
+            .await
+    }
+
+    pub async fn search<T: DeserializeOwned>(
+        &self,
+        request: SearchRequest,
+    ) -> Result<SearchResponse<T>, ExtensionError> {
+        self.execute(search::SEARCH_ACTION, &request.usage_index(), &request)
+            .await
+    }
+
+    /// Stream every hit matching `request`, a page of `request.size` hits
+    /// at a time. See `ScrollStream`.
+    pub fn scroll_stream<T: DeserializeOwned>(&self, request: SearchRequest) -> ScrollStream<T> {
+        ScrollStream::new(self.clone(), request)
+    }
+
+    pub async fn cluster_health(
+        &self,
+        request: ClusterHealthRequest,
+    ) -> Result<ClusterHealthResponse, ExtensionError> {
+        let index = request.indices.join(",");
+        self.execute(cluster::CLUSTER_HEALTH_ACTION, &index, &request)
+            .await
+    }
+
+    pub async fn cluster_state(
+        &self,
+        request: ClusterStateRequest,
+    ) -> Result<ClusterStateResponse, ExtensionError> {
+        let index = request.indices.join(",");
+        self.execute(cluster::CLUSTER_STATE_ACTION, &index, &request)
+            .await
+    }
+
+    pub async fn cluster_settings(
+        &self,
+        include_defaults: bool,
+    ) -> Result<ClusterSettingsResponse, ExtensionError> {
+        let request = cluster::ClusterGetSettingsRequest { include_defaults };
+        self.execute(cluster::CLUSTER_GET_SETTINGS_ACTION, "", &request)
+            .await
+    }
+
+    pub async fn update_cluster_settings(
+        &self,
+        request: ClusterUpdateSettingsRequest,
+    ) -> Result<ClusterUpdateSettingsResponse, ExtensionError> {
+        if request.is_empty() {
+            return Err(ExtensionError::invalid_request(
+                "Cluster settings update has no settings",
+            ));
+        }
+        self.execute(cluster::CLUSTER_UPDATE_SETTINGS_ACTION, "", &request)
+            .await
+    }
+
+    pub async fn nodes_info(
+        &self,
+        request: NodesInfoRequest,
+    ) -> Result<NodesInfoResponse, ExtensionError> {
+        self.execute(cluster::NODES_INFO_ACTION, "", &request).await
+    }
+
+    /// Run `action` with a JSON `request` and deserialize the JSON response.
+    /// Usage is attributed to `index` within the current `UsageScope`, or
+    /// left unattributed when `index` is empty. Sending follows the policy
+    /// of the action's `OperationCategory`: connection failures, timeouts
+    /// and 429/502/503/504 answers are retried, other errors are not. Inside
+    /// a `Deadline::run` the call, retries included, ends with the deadline.
+    pub async fn execute<Req, Resp>(
+        &self,
+        action: &str,
+        index: &str,
+        request: &Req,
+    ) -> Result<Resp, ExtensionError>
+    where
+        Req: Serialize + ?Sized,
+        Resp: DeserializeOwned,
+    {
+        let request_bytes = serde_json::to_vec(request).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize {} request: {}", action, e))
+        })?;
+        let proxied = TransportActionRequestFromExtension {
+            action: action.to_string(),
+            request_bytes,
+            unique_id: self.unique_id.clone(),
+        };
+        let mut thread_context = ThreadContext::new();
+        match (&self.principal, OnBehalfOfToken::current()) {
+            (Some(principal), _) => {
+                thread_context.put_request_header(PRINCIPAL_HEADER, principal.token())
+            }
+            (None, Some(token)) => thread_context
+                .put_request_header(AUTHORIZATION_HEADER, format!("Bearer {}", token.token())),
+            (None, None) => {}
+        }
+        let mut bytes = Vec::new();
+        interface::Serialize::serialize(&proxied, &mut bytes).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize {} request: {}", action, e))
+        })?;
+
+        let mut scope = UsageScope::current();
+        if !index.is_empty() {
+            scope = scope.index(index);
+        }
+        let sent = self.policies.run(action, self.policy.as_deref(), || async {
+            let sent =
+                self.transport
+                    .send_request_with_context(action, &bytes, thread_context.clone());
+            let response = scope.clone().run(sent).await?;
+            let response = <RemoteExtensionActionResponse as interface::Deserialize>::deserialize(
+                &mut response.as_slice(),
+            )
+            .map_err(|e| {
+                ExtensionError::protocol(format!("Invalid response to {}: {}", action, e))
+            });
+            Ok(match response {
+                Ok(response) if response.success => Ok(response.response_bytes),
+                Ok(response) if is_retryable_failure(&response.response_bytes) => {
+                    return Err(remote_error(action, &response.response_bytes));
+                }
+                Ok(response) => Err(remote_error(action, &response.response_bytes)),
+                Err(e) => Err(e),
+            })
+        });
+        let response_bytes = Deadline::enforce(action, sent).await?;
+        serde_json::from_slice(&response_bytes).map_err(|e| {
+            ExtensionError::serialization(format!(
+                "Failed to deserialize {} response: {}",
+                action, e
+            ))
+        })
+    }
+}
+
+/// Whether an OpenSearch error body reports the cluster as overloaded or
+/// unavailable (429, 502, 503 or 504), so the call may succeed if retried.
+fn is_retryable_failure(body: &[u8]) -> bool {
+    let Ok(body) = serde_json::from_slice::<Value>(body) else {
+        return false;
+    };
+    match body.get("status").and_then(Value::as_u64) {
+        Some(status) => matches!(status, 429 | 502 | 503 | 504),
+        None => matches!(
+            body.pointer("/error/type").and_then(Value::as_str),
+            Some(
+                "rejected_execution_exception"
+                    | "opensearch_rejected_execution_exception"
+                    | "no_shard_available_action_exception"
+                    | "node_not_connected_exception"
+                    | "receive_timeout_transport_exception"
+            )
+        ),
+    }
+}
+
+/// Error for a failed remote action, with the `type` and `reason` of its
+/// OpenSearch error body when there is one. A 429 answer is `Throttled`,
+/// honouring the error's `Retry-After` header; other answers with a status
+/// are `Remote` errors keeping it.
+fn remote_error(action: &str, body: &[u8]) -> ExtensionError {
+    let parsed = serde_json::from_slice::<Value>(body).ok();
+    let status = parsed
+        .as_ref()
+        .and_then(|body| body.get("status"))
+        .and_then(Value::as_u64);
+    let error = parsed.as_ref().and_then(|body| body.get("error"));
+    let message = match error {
+        Some(Value::Object(error)) => format!(
+            "{} [{}]",
+            error
+                .get("reason")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown reason"),
+            error
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or("exception"),
+        ),
+        Some(Value::String(reason)) => reason.clone(),
+        _ => String::from_utf8_lossy(body).into_owned(),
+    };
+    let message = format!("{} failed: {}", action, message);
+    let error_type = error
+        .and_then(|error| error.get("type"))
+        .and_then(Value::as_str);
+    match (
+        status.and_then(|status| u16::try_from(status).ok()),
+        error_type,
+    ) {
+        (Some(429), _) => ExtensionError::throttled(message, error.and_then(retry_after)),
+        (Some(status), Some(error_type)) if status >= 400 => {
+            ExtensionError::remote_exception(status, error_type, message)
+        }
+        (Some(status), None) if status >= 400 => ExtensionError::remote(status, message),
+        _ => ExtensionError::transport(message),
+    }
+}
+
+/// Seconds from the `Retry-After` header OpenSearch renders into an error
+/// body, as a single value or a list.
+fn retry_after(error: &Value) -> Option<Duration> {
+    let value = error.pointer("/header/Retry-After")?;
+    let value = match value {
+        Value::Array(values) => values.first()?,
+        value => value,
+    };
+    value.as_str()?.trim().parse().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::Serialize as _;
+    use crate::transport::inbound::{read_message, write_response};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    type Received = (TransportActionRequestFromExtension, ThreadContext);
+
+    /// Accepts one connection per response and answers it, returning the
+    /// requests with their headers.
+    async fn serve(
+        responses: Vec<RemoteExtensionActionResponse>,
+    ) -> (u16, tokio::task::JoinHandle<Vec<Received>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let message = read_message(&mut stream).await.unwrap().unwrap();
+                let mut bytes = Vec::new();
+                response.serialize(&mut bytes).unwrap();
+                write_response(
+                    &mut stream,
+                    &message.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+                let request =
+                    <TransportActionRequestFromExtension as interface::Deserialize>::deserialize(
+                        &mut message.content.as_slice(),
+                    )
+                    .unwrap();
+                requests.push((request, message.thread_context));
+            }
+            requests
+        });
+        (port, handle)
+    }
+
+    /// Accepts one connection and answers it with `response`, returning the request.
+    async fn serve_once(
+        response: RemoteExtensionActionResponse,
+    ) -> (u16, tokio::task::JoinHandle<Received>) {
+        let (port, server) = serve(vec![response]).await;
+        (
+            port,
+            tokio::spawn(async move { server.await.unwrap().remove(0) }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_index_through_proxy() {
+        let body = json!({"_index": "logs", "_id": "1", "_version": 1, "result": "created", "_seq_no": 0, "_primary_term": 1});
+        let (port, server) = serve_once(RemoteExtensionActionResponse {
+            success: true,
+            response_bytes: body.to_string().into_bytes(),
+        })
+        .await;
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let request = IndexRequest::new("logs")
+            .id("1")
+            .document(&json!({"message": "hi"}))
+            .unwrap();
+        let response = client.index(request).await.unwrap();
+        assert_eq!(
+            (response.id.as_str(), response.result),
+            ("1", DocWriteResult::Created)
+        );
+
+        let (proxied, headers) = server.await.unwrap();
+        assert_eq!(proxied.action, document::INDEX_ACTION);
+        assert_eq!(proxied.unique_id, "hello-world");
+        assert_eq!(headers.request_header(PRINCIPAL_HEADER), None);
+        let sent: Value = serde_json::from_slice(&proxied.request_bytes).unwrap();
+        assert_eq!(sent["source"]["message"], "hi");
+    }
+
+    #[tokio::test]
+    async fn test_call_on_behalf_of_principal() {
+        let (port, server) = serve_once(RemoteExtensionActionResponse {
+            success: true,
+            response_bytes: br#"{"exists": true}"#.to_vec(),
+        })
+        .await;
+
+        let mut request =
+            crate::rest::ExtensionRestRequest::new(crate::rest::Method::Get, "/_hello");
+        request.principal_token = "alice|ops|reader|".to_string();
+        let principal = request.principal().unwrap();
+        assert_eq!(principal.user().unwrap().name, "alice");
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        client
+            .on_behalf_of(&principal)
+            .index_exists("logs")
+            .await
+            .unwrap();
+        let (_, headers) = server.await.unwrap();
+        assert_eq!(
+            headers.request_header(PRINCIPAL_HEADER),
+            Some("alice|ops|reader|")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ensure_existing_index() {
+        let (port, server) = serve_once(RemoteExtensionActionResponse {
+            success: true,
+            response_bytes: br#"{"exists": true}"#.to_vec(),
+        })
+        .await;
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let created = client
+            .ensure_index(CreateIndexRequest::new(".hello-state"))
+            .await
+            .unwrap();
+        assert!(!created);
+        assert_eq!(
+            server.await.unwrap().0.action,
+            indices::INDICES_EXISTS_ACTION
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ensure_index_lost_race() {
+        let exists = json!({"error": {"type": "resource_already_exists_exception", "reason": "index [.hello-state] already exists"}, "status": 400});
+        let (port, server) = serve(vec![
+            RemoteExtensionActionResponse {
+                success: true,
+                response_bytes: br#"{"exists": false}"#.to_vec(),
+            },
+            RemoteExtensionActionResponse {
+                success: false,
+                response_bytes: exists.to_string().into_bytes(),
+            },
+        ])
+        .await;
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        assert!(!client
+            .ensure_index(CreateIndexRequest::new(".hello-state"))
+            .await
+            .unwrap());
+        assert_eq!(
+            server.await.unwrap()[1].0.action,
+            indices::CREATE_INDEX_ACTION
+        );
+
+        // A mention of the type in the reason alone does not count.
+        let other = remote_error(indices::CREATE_INDEX_ACTION, json!({
+            "error": {"type": "illegal_argument_exception", "reason": "[resource_already_exists_exception]"}, "status": 400
+        }).to_string().as_bytes());
+        assert!(!other.is_remote_exception(400, "resource_already_exists_exception"));
+    }
+
+    #[tokio::test]
+    async fn test_remote_failure() {
+        let body = json!({"error": {"type": "version_conflict_engine_exception", "reason": "[1]: version conflict"}, "status": 409});
+        let (port, _server) = serve_once(RemoteExtensionActionResponse {
+            success: false,
+            response_bytes: body.to_string().into_bytes(),
+        })
+        .await;
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let error = client
+            .delete(DeleteRequest::new("logs", "1"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Remote error (409): indices:data/write/delete failed: [1]: version conflict [version_conflict_engine_exception]"
+        );
+        assert_eq!(error.retryable(), crate::extension::Retryable::NonRetryable);
+    }
+
+    #[tokio::test]
+    async fn test_retry_rejected_execution() {
+        let rejected = json!({"error": {"type": "rejected_execution_exception", "reason": "queue full"}, "status": 429});
+        let missing = json!({"error": {"type": "index_not_found_exception", "reason": "no such index [logs]"}, "status": 404});
+        let (port, server) = serve(vec![
+            RemoteExtensionActionResponse {
+                success: false,
+                response_bytes: rejected.to_string().into_bytes(),
+            },
+            RemoteExtensionActionResponse {
+                success: false,
+                response_bytes: missing.to_string().into_bytes(),
+            },
+        ])
+        .await;
+
+        let policy = OperationPolicy::new()
+            .retries(3)
+            .backoff(std::time::Duration::from_millis(1));
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        )
+        .with_policies(Arc::new(ClientPolicies::new(policy)));
+        let error = client
+            .delete(DeleteRequest::new("logs", "1"))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("[index_not_found_exception]"));
+        assert_eq!(server.await.unwrap().len(), 2);
+        assert_eq!(
+            remote_error("indices:data/write/delete", rejected.to_string().as_bytes()).retryable(),
+            crate::extension::Retryable::Throttled(None)
+        );
+        let hinted = json!({"error": {"type": "rejected_execution_exception", "reason": "queue full", "header": {"Retry-After": ["3"]}}, "status": 429});
+        assert_eq!(
+            remote_error("indices:data/write/delete", hinted.to_string().as_bytes()).retryable(),
+            crate::extension::Retryable::Throttled(Some(Duration::from_secs(3)))
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::client::document::{GetRequest, GetResponse};
+use crate::client::search::{SearchRequest, SearchResponse};
+use crate::client::SdkClient;
+use crate::extension::ExtensionError;
+
+pub const MULTI_GET_ACTION: &str = "indices:data/read/mget";
+pub const MULTI_SEARCH_ACTION: &str = "indices:data/read/msearch";
+
+/// The single index `indices` all name, or `fallback` when they differ.
+fn usage_index<'a>(mut indices: impl Iterator<Item = &'a str>, fallback: &'a str) -> &'a str {
+    match indices.next() {
+        Some(first) if indices.all(|index| index == first) => first,
+        _ => fallback,
+    }
+}
+
+/// Failure of a single item of a multi-get or multi-search.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemFailure {
+    #[serde(rename = "_index", default)]
+    pub index: Option<String>,
+    #[serde(rename = "_id", default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub status: Option<u16>,
+    pub error: Value,
+}
+
+impl ItemFailure {
+    pub fn error_type(&self) -> &str {
+        self.error
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or("exception")
+    }
+
+    pub fn reason(&self) -> &str {
+        match &self.error {
+            Value::String(reason) => reason,
+            error => error
+                .get("reason")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown reason"),
+        }
+    }
+}
+
+/// Outcome of one item: its response, or why it failed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ItemResult<R> {
+    Failed(ItemFailure),
+    Ok(R),
+}
+
+impl<R> ItemResult<R> {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, ItemResult::Failed(_))
+    }
+
+    pub fn ok(&self) -> Option<&R> {
+        match self {
+            ItemResult::Ok(response) => Some(response),
+            ItemResult::Failed(_) => None,
+        }
+    }
+
+    pub fn into_result(self) -> Result<R, ExtensionError> {
+        match self {
+            ItemResult::Ok(response) => Ok(response),
+            ItemResult::Failed(failure) => Err(ExtensionError::transport(format!(
+                "{} [{}]",
+                failure.reason(),
+                failure.error_type()
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MultiGetRequest {
+    pub docs: Vec<GetRequest>,
+}
+
+impl MultiGetRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn item(mut self, request: GetRequest) -> Self {
+        self.docs.push(request);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+}
+
+/// Items in the order they were requested.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+pub struct MultiGetResponse<T = Value> {
+    pub docs: Vec<ItemResult<GetResponse<T>>>,
+}
+
+impl<T> MultiGetResponse<T> {
+    pub fn failures(&self) -> impl Iterator<Item = &ItemFailure> {
+        self.docs.iter().filter_map(|item| match item {
+            ItemResult::Failed(failure) => Some(failure),
+            ItemResult::Ok(_) => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MultiSearchRequest {
+    pub searches: Vec<SearchRequest>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_searches: Option<u32>,
+}
+
+impl MultiSearchRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn item(mut self, request: SearchRequest) -> Self {
+        self.searches.push(request);
+        self
+    }
+
+    /// How many of the searches the cluster runs at once.
+    pub fn max_concurrent_searches(mut self, max: u32) -> Self {
+        self.max_concurrent_searches = Some(max);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.searches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.searches.is_empty()
+    }
+}
+
+/// Responses in the order the searches were requested.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+pub struct MultiSearchResponse<T = Value> {
+    #[serde(default)]
+    pub took: u64,
+    pub responses: Vec<ItemResult<SearchResponse<T>>>,
+}
+
+impl SdkClient {
+    /// Fetch several documents in one round trip. Missing documents come
+    /// back with `found: false`; items that could not be read are failures.
+    pub async fn mget<T: DeserializeOwned>(
+        &self,
+        request: MultiGetRequest,
+    ) -> Result<MultiGetResponse<T>, ExtensionError> {
+        if request.is_empty() {
+            return Err(ExtensionError::invalid_request(
+                "Multi-get request has no documents",
+            ));
+        }
+        let index =
+            usage_index(request.docs.iter().map(|doc| doc.index.as_str()), "_mget").to_string();
+        self.execute(MULTI_GET_ACTION, &index, &request).await
+    }
+
+    /// Run several searches in one round trip, each succeeding or failing on its own.
+    pub async fn msearch<T: DeserializeOwned>(
+        &self,
+        request: MultiSearchRequest,
+    ) -> Result<MultiSearchResponse<T>, ExtensionError> {
+        if request.is_empty() {
+            return Err(ExtensionError::invalid_request(
+                "Multi-search request has no searches",
+            ));
+        }
+        let indices: Vec<String> = request
+            .searches
+            .iter()
+            .map(SearchRequest::usage_index)
+            .collect();
+        let index = usage_index(indices.iter().map(String::as_str), "_msearch").to_string();
+        self.execute(MULTI_SEARCH_ACTION, &index, &request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_per_item_results() {
+        let response: MultiGetResponse = serde_json::from_value(json!({"docs": [
+            {"_index": "logs", "_id": "1", "found": true, "_source": {"n": 1}},
+            {"_index": "logs", "_id": "2", "found": false},
+            {"_index": "missing", "_id": "3", "error": {"type": "index_not_found_exception", "reason": "no such index [missing]"}}
+        ]}))
+        .unwrap();
+        assert!(response.docs[0].ok().unwrap().found);
+        assert!(!response.docs[1].ok().unwrap().found);
+        let failures: Vec<_> = response.failures().map(|f| f.id.as_deref()).collect();
+        assert_eq!(failures, vec![Some("3")]);
+        assert_eq!(
+            response.docs[2]
+                .clone()
+                .into_result()
+                .unwrap_err()
+                .to_string(),
+            "Transport error: no such index [missing] [index_not_found_exception]"
+        );
+
+        let response: MultiSearchResponse = serde_json::from_value(json!({"took": 3, "responses": [
+            {"took": 1, "hits": {"hits": [{"_index": "logs", "_id": "1"}]}},
+            {"error": {"type": "parsing_exception", "reason": "unknown query [mtch]"}, "status": 400}
+        ]}))
+        .unwrap();
+        assert_eq!(response.responses[0].ok().unwrap().hits.hits[0].id, "1");
+        assert!(matches!(&response.responses[1], ItemResult::Failed(f) if f.status == Some(400)));
+
+        assert_eq!(usage_index(["a", "a"].into_iter(), "_mget"), "a");
+        assert_eq!(usage_index(["a", "b"].into_iter(), "_mget"), "_mget");
+    }
+}
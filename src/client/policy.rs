@@ -0,0 +1,572 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::resilience::{CircuitBreakerRegistry, CircuitState, RetryBudget};
+use crate::extension::{CircuitBreaker, ExtensionError, HealthService, RetryPolicy, Retryable};
+
+/// Settings prefix policies are loaded from, e.g. `client.policy.search.retries`.
+pub const POLICY_SETTINGS_PREFIX: &str = "client.policy";
+
+/// Kinds of client operation that share a policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationCategory {
+    Read,
+    Write,
+    Search,
+    Bulk,
+    Admin,
+}
+
+impl OperationCategory {
+    pub const ALL: [OperationCategory; 5] = [
+        OperationCategory::Read,
+        OperationCategory::Write,
+        OperationCategory::Search,
+        OperationCategory::Bulk,
+        OperationCategory::Admin,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OperationCategory::Read => "read",
+            OperationCategory::Write => "write",
+            OperationCategory::Search => "search",
+            OperationCategory::Bulk => "bulk",
+            OperationCategory::Admin => "admin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.name() == name)
+    }
+
+    /// Category of a transport action. Anything outside `indices:data/` is admin.
+    pub fn of(action: &str) -> Self {
+        if action == super::bulk::BULK_ACTION {
+            OperationCategory::Bulk
+        } else if action.starts_with("indices:data/read/search")
+            || action.starts_with("indices:data/read/msearch")
+            || action.starts_with("indices:data/read/scroll")
+            || action.starts_with("indices:data/read/point_in_time")
+        {
+            OperationCategory::Search
+        } else if action.starts_with("indices:data/read/") {
+            OperationCategory::Read
+        } else if action.starts_with("indices:data/write/") {
+            OperationCategory::Write
+        } else {
+            OperationCategory::Admin
+        }
+    }
+}
+
+/// Trips after `failure_threshold` consecutive failures, rejecting calls for
+/// `open_for` before letting `success_threshold` trial calls through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitPolicy {
+    pub failure_threshold: u32,
+    pub success_threshold: u32,
+    pub open_for: Duration,
+}
+
+/// Retries, timeout and circuit breaking for one category of operation.
+/// Only failures to reach the cluster are retried; errors the cluster
+/// answers with are returned as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+    pub timeout: Option<Duration>,
+    pub circuit: Option<CircuitPolicy>,
+}
+
+impl Default for OperationPolicy {
+    fn default() -> Self {
+        OperationPolicy {
+            retries: 0,
+            backoff: Duration::from_millis(100),
+            timeout: None,
+            circuit: None,
+        }
+    }
+}
+
+impl OperationPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Delay before the first retry, doubling for each one after.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Bound on each attempt, not on the call as a whole.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn circuit_breaker(
+        mut self,
+        failure_threshold: u32,
+        success_threshold: u32,
+        open_for: Duration,
+    ) -> Self {
+        self.circuit = Some(CircuitPolicy {
+            failure_threshold,
+            success_threshold,
+            open_for,
+        });
+        self
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retries + 1,
+            initial_delay: self.backoff,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// Apply `settings` under `prefix` (e.g. `client.policy.search`) on top of this policy.
+    fn load(mut self, settings: &Settings, prefix: &str) -> Result<Self, ExtensionError> {
+        let integer = |name: &str| -> Result<Option<u64>, ExtensionError> {
+            let key = format!("{}.{}", prefix, name);
+            let value = match settings.get(&key)? {
+                None => return Ok(None),
+                Some(SettingValue::Integer(value)) => u64::try_from(value).ok(),
+                // Cluster settings arrive as strings
+                Some(SettingValue::String(value)) => value.trim().parse().ok(),
+                Some(_) => None,
+            };
+            value.map(Some).ok_or_else(|| {
+                ExtensionError::configuration(format!(
+                    "Setting '{}' must be a non-negative integer",
+                    key
+                ))
+            })
+        };
+
+        if let Some(retries) = integer("retries")? {
+            self.retries = retries.min(u32::MAX as u64) as u32;
+        }
+        if let Some(backoff) = integer("backoff_ms")? {
+            self.backoff = Duration::from_millis(backoff);
+        }
+        if let Some(timeout) = integer("timeout_ms")? {
+            self.timeout = (timeout > 0).then(|| Duration::from_millis(timeout));
+        }
+        if let Some(failures) = integer("circuit.failure_threshold")? {
+            let current = self.circuit.unwrap_or(CircuitPolicy {
+                failure_threshold: 0,
+                success_threshold: 1,
+                open_for: Duration::from_secs(30),
+            });
+            self.circuit = (failures > 0).then_some(CircuitPolicy {
+                failure_threshold: failures.min(u32::MAX as u64) as u32,
+                ..current
+            });
+        }
+        if let Some(circuit) = &mut self.circuit {
+            if let Some(successes) = integer("circuit.success_threshold")? {
+                circuit.success_threshold = successes.clamp(1, u32::MAX as u64) as u32;
+            }
+            if let Some(open) = integer("circuit.open_ms")? {
+                circuit.open_for = Duration::from_millis(open);
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Policies of a client keyed by operation category. Categories without a
+/// policy of their own use the default one. Circuit breakers are kept per
+/// action, so a failing endpoint does not trip calls to healthy ones.
+pub struct ClientPolicies {
+    policies: RwLock<Arc<PolicySet>>,
+    breakers: CircuitBreakerRegistry,
+    budget: Option<Arc<RetryBudget>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PolicySet {
+    default: OperationPolicy,
+    categories: BTreeMap<OperationCategory, OperationPolicy>,
+}
+
+impl PolicySet {
+    fn from_settings(settings: &Settings) -> Result<Self, ExtensionError> {
+        let default = OperationPolicy::default()
+            .load(settings, &format!("{}.default", POLICY_SETTINGS_PREFIX))?;
+        let mut categories = BTreeMap::new();
+        for category in OperationCategory::ALL {
+            let policy = default.clone().load(
+                settings,
+                &format!("{}.{}", POLICY_SETTINGS_PREFIX, category.name()),
+            )?;
+            if policy != default {
+                categories.insert(category, policy);
+            }
+        }
+        Ok(PolicySet {
+            default,
+            categories,
+        })
+    }
+}
+
+impl Default for ClientPolicies {
+    fn default() -> Self {
+        Self::new(OperationPolicy::default())
+    }
+}
+
+impl ClientPolicies {
+    pub fn new(default: OperationPolicy) -> Self {
+        ClientPolicies {
+            policies: RwLock::new(Arc::new(PolicySet {
+                default,
+                categories: BTreeMap::new(),
+            })),
+            breakers: CircuitBreakerRegistry::default(),
+            budget: None,
+        }
+    }
+
+    /// Draw the retries of every category from `budget`.
+    pub fn with_retry_budget(mut self, budget: Arc<RetryBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Report the state of the per-action breakers as the
+    /// `circuit_breakers` check of `health`.
+    pub fn with_health(mut self, health: HealthService) -> Self {
+        self.breakers = self.breakers.with_health(health);
+        self
+    }
+
+    pub fn breakers(&self) -> &CircuitBreakerRegistry {
+        &self.breakers
+    }
+
+    pub fn category(mut self, category: OperationCategory, policy: OperationPolicy) -> Self {
+        let policies = self
+            .policies
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        Arc::make_mut(policies).categories.insert(category, policy);
+        self
+    }
+
+    /// Load policies from `client.policy.default.*` and
+    /// `client.policy.<category>.*`, each category starting from the default.
+    /// Recognised keys are `retries`, `backoff_ms`, `timeout_ms` (0 for none),
+    /// `circuit.failure_threshold` (0 for no breaker), `circuit.success_threshold`
+    /// and `circuit.open_ms`.
+    pub fn from_settings(settings: &Settings) -> Result<Self, ExtensionError> {
+        let policies = ClientPolicies::default();
+        policies.reload(settings)?;
+        Ok(policies)
+    }
+
+    /// Replace the policies with those in `settings`, as `from_settings`
+    /// loads them, returning whether any changed. Breakers are created
+    /// afresh on next use so they pick up new thresholds. On an invalid
+    /// setting the current policies are kept.
+    pub fn reload(&self, settings: &Settings) -> Result<bool, ExtensionError> {
+        let loaded = PolicySet::from_settings(settings)?;
+        let mut policies = self
+            .policies
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if **policies == loaded {
+            return Ok(false);
+        }
+        *policies = Arc::new(loaded);
+        drop(policies);
+        self.breakers.clear()?;
+        Ok(true)
+    }
+
+    /// The current policies. Swapped whole on reload, so a poisoned lock
+    /// still holds a consistent set.
+    fn current(&self) -> Arc<PolicySet> {
+        self.policies
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn policy(&self, category: OperationCategory) -> OperationPolicy {
+        let policies = self.current();
+        policies
+            .categories
+            .get(&category)
+            .unwrap_or(&policies.default)
+            .clone()
+    }
+
+    /// Breaker for `action`, created on first use from its category's policy.
+    fn breaker(
+        &self,
+        action: &str,
+        category: OperationCategory,
+    ) -> Result<Option<Arc<CircuitBreaker>>, ExtensionError> {
+        let Some(circuit) = self.policy(category).circuit else {
+            return Ok(None);
+        };
+        let breaker = self.breakers.get_with(action, || {
+            CircuitBreaker::new(
+                circuit.failure_threshold,
+                circuit.success_threshold,
+                circuit.open_for,
+            )
+        })?;
+        Ok(Some(breaker))
+    }
+
+    /// Run `attempt` for `action` under the policy of its category, or
+    /// `policy` when the caller overrides it. The action's circuit breaker
+    /// applies either way.
+    ///
+    /// `attempt` fails with `Err` when the cluster could not serve the call,
+    /// which is counted by the breaker and retried unless the error is
+    /// `Retryable::NonRetryable`, and with `Ok(Err)` when
+    /// the cluster answered with an error, which is returned as it is.
+    pub(crate) async fn run<F, Fut, T>(
+        &self,
+        action: &str,
+        policy: Option<&OperationPolicy>,
+        mut attempt: F,
+    ) -> Result<T, ExtensionError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Result<T, ExtensionError>, ExtensionError>>,
+    {
+        let category = OperationCategory::of(action);
+        let policy = policy.cloned().unwrap_or_else(|| self.policy(category));
+        let retry = policy.retry_policy();
+        let breaker = self.breaker(action, category)?;
+        if let Some(budget) = &self.budget {
+            budget.record_request()?;
+        }
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let timed = async {
+                match policy.timeout {
+                    Some(timeout) => {
+                        tokio::time::timeout(timeout, attempt())
+                            .await
+                            .map_err(|_| {
+                                ExtensionError::timeout(format!(
+                                    "{} operation timed out after {:?}",
+                                    category.name(),
+                                    timeout
+                                ))
+                            })?
+                    }
+                    None => attempt().await,
+                }
+            };
+            let result = match &breaker {
+                Some(breaker) => breaker.call(|| timed).await,
+                None => timed.await,
+            };
+            match result {
+                Err(e)
+                    if attempts < retry.max_attempts
+                        && e.retryable() != Retryable::NonRetryable =>
+                {
+                    if let Some(budget) = &self.budget {
+                        if !budget.try_withdraw()? {
+                            return Err(e);
+                        }
+                    }
+                    sleep(retry.retry_delay(attempts, &e)).await
+                }
+                result => return result?,
+            }
+        }
+    }
+
+    /// Effective policy and breaker state of every category.
+    pub async fn report(&self) -> Result<Vec<PolicyReport>, ExtensionError> {
+        let states = self.breakers.states()?;
+        let mut reports = Vec::new();
+        for category in OperationCategory::ALL {
+            let policy = self.policy(category);
+            let circuit = match policy.circuit {
+                Some(circuit) => {
+                    let actions = states
+                        .iter()
+                        .filter(|(action, _)| OperationCategory::of(action) == category)
+                        .map(|(action, state)| {
+                            let state = match state {
+                                CircuitState::Closed => "closed",
+                                CircuitState::Open => "open",
+                                CircuitState::HalfOpen => "half_open",
+                            };
+                            (action.clone(), state)
+                        })
+                        .collect();
+                    Some(CircuitReport {
+                        failure_threshold: circuit.failure_threshold,
+                        success_threshold: circuit.success_threshold,
+                        open_millis: circuit.open_for.as_millis() as u64,
+                        actions,
+                    })
+                }
+                None => None,
+            };
+            reports.push(PolicyReport {
+                category,
+                overridden: self.current().categories.contains_key(&category),
+                retries: policy.retries,
+                backoff_millis: policy.backoff.as_millis() as u64,
+                timeout_millis: policy.timeout.map(|timeout| timeout.as_millis() as u64),
+                circuit,
+            });
+        }
+        Ok(reports)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CircuitReport {
+    pub failure_threshold: u32,
+    pub success_threshold: u32,
+    pub open_millis: u64,
+    /// Breaker state of each action of the category called so far.
+    pub actions: BTreeMap<String, &'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PolicyReport {
+    pub category: OperationCategory,
+    /// Whether the category has a policy of its own rather than the default.
+    pub overridden: bool,
+    pub retries: u32,
+    pub backoff_millis: u64,
+    pub timeout_millis: Option<u64>,
+    pub circuit: Option<CircuitReport>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_policies_from_settings() {
+        let settings = Settings::new();
+        settings
+            .set("client.policy.default.timeout_ms", 10_000)
+            .unwrap();
+        settings.set("client.policy.search.retries", 2).unwrap();
+        settings.set("client.policy.search.backoff_ms", 1).unwrap();
+        settings
+            .set("client.policy.search.timeout_ms", 5_000)
+            .unwrap();
+        settings
+            .set("client.policy.bulk.timeout_ms", 30_000)
+            .unwrap();
+        settings
+            .set("client.policy.bulk.circuit.failure_threshold", 3)
+            .unwrap();
+        let policies = ClientPolicies::from_settings(&settings).unwrap();
+
+        assert_eq!(policies.policy(OperationCategory::Search).retries, 2);
+        assert_eq!(
+            policies.policy(OperationCategory::Search).timeout,
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            policies.policy(OperationCategory::Read).timeout,
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            OperationCategory::of("indices:data/read/msearch"),
+            OperationCategory::Search
+        );
+        assert_eq!(
+            OperationCategory::of("indices:data/read/get"),
+            OperationCategory::Read
+        );
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = policies
+            .run("indices:data/read/search", None, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(ExtensionError::transport("connection refused"))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), _> = policies
+            .run("indices:data/read/search", None, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(Err(ExtensionError::transport(
+                    "unknown query [mtch] [parsing_exception]",
+                )))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        for _ in 0..3 {
+            let _: Result<(), _> = policies
+                .run("indices:data/write/bulk", None, || async {
+                    Err(ExtensionError::transport("rejected"))
+                })
+                .await;
+        }
+        let report = policies.report().await.unwrap();
+        let bulk = report
+            .iter()
+            .find(|r| r.category == OperationCategory::Bulk)
+            .unwrap();
+        assert_eq!((bulk.retries, bulk.timeout_millis), (0, Some(30_000)));
+        assert_eq!(
+            bulk.circuit.as_ref().unwrap().actions["indices:data/write/bulk"],
+            "open"
+        );
+        assert!(
+            !report
+                .iter()
+                .find(|r| r.category == OperationCategory::Admin)
+                .unwrap()
+                .overridden
+        );
+
+        assert!(!policies.reload(&settings).unwrap());
+        settings
+            .set("client.policy.bulk.circuit.failure_threshold", 5)
+            .unwrap();
+        assert!(policies.reload(&settings).unwrap());
+        assert!(policies.breakers().states().unwrap().is_empty());
+
+        settings.set("client.policy.read.retries", "many").unwrap();
+        assert!(ClientPolicies::from_settings(&settings).is_err());
+        assert!(policies.reload(&settings).is_err());
+        assert_eq!(policies.policy(OperationCategory::Search).retries, 2);
+    }
+}
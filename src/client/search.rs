@@ -0,0 +1,422 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use tracing::warn;
+
+use crate::client::SdkClient;
+use crate::extension::ExtensionError;
+
+pub const SEARCH_ACTION: &str = "indices:data/read/search";
+pub const SCROLL_ACTION: &str = "indices:data/read/scroll";
+pub const CLEAR_SCROLL_ACTION: &str = "indices:data/read/scroll/clear";
+pub const CREATE_PIT_ACTION: &str = "indices:data/read/point_in_time/create";
+pub const DELETE_PIT_ACTION: &str = "indices:data/read/point_in_time/delete";
+
+/// Page size used by `ScrollStream` when the request does not set one.
+pub const DEFAULT_PAGE_SIZE: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointInTime {
+    pub id: String,
+    pub keep_alive: String,
+}
+
+/// Builder for `SdkClient::search`, e.g.
+/// `SearchRequest::new(&["logs"]).query(json!({"match_all": {}})).size(100)`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SearchRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub indices: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sort: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_after: Option<Vec<Value>>,
+    #[serde(rename = "_source", skip_serializing_if = "Option::is_none")]
+    pub source: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scroll: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pit: Option<PointInTime>,
+}
+
+impl SearchRequest {
+    pub fn new(indices: &[&str]) -> Self {
+        SearchRequest {
+            indices: indices.iter().map(|i| i.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn query(mut self, query: Value) -> Self {
+        self.query = Some(query);
+        self
+    }
+
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Add a sort clause such as `json!({"timestamp": "desc"})`.
+    pub fn sort(mut self, sort: Value) -> Self {
+        self.sort.push(sort);
+        self
+    }
+
+    /// Source filtering, e.g. `json!(["message", "timestamp"])` or `json!(false)`.
+    pub fn source(mut self, source: Value) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub(crate) fn usage_index(&self) -> String {
+        self.indices.join(",")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TotalHits {
+    pub value: u64,
+    pub relation: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+pub struct Hit<T = Value> {
+    #[serde(rename = "_index")]
+    pub index: String,
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_score", default)]
+    pub score: Option<f64>,
+    #[serde(rename = "_source", default)]
+    pub source: Option<T>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sort: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+pub struct Hits<T = Value> {
+    #[serde(default)]
+    pub total: Option<TotalHits>,
+    #[serde(default = "Vec::new")]
+    pub hits: Vec<Hit<T>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned"))]
+pub struct SearchResponse<T = Value> {
+    #[serde(default)]
+    pub took: u64,
+    #[serde(default)]
+    pub timed_out: bool,
+    #[serde(rename = "_scroll_id", default)]
+    pub scroll_id: Option<String>,
+    #[serde(default)]
+    pub pit_id: Option<String>,
+    pub hits: Hits<T>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ScrollRequest<'a> {
+    scroll_id: &'a str,
+    scroll: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ClearScrollRequest {
+    scroll_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct CreatePitRequest<'a> {
+    indices: &'a [String],
+    keep_alive: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct CreatePitResponse {
+    pit_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct DeletePitRequest {
+    pit_id: Vec<String>,
+}
+
+/// Search context held open on the cluster while streaming.
+#[derive(Debug, Clone, PartialEq)]
+enum SearchContext {
+    Scroll(String),
+    Pit(String),
+}
+
+impl SearchContext {
+    /// Release the context. Failures are only logged: the cluster frees it
+    /// once its keep-alive runs out anyway.
+    async fn release(self, client: &SdkClient) {
+        let result: Result<Value, ExtensionError> = match &self {
+            SearchContext::Scroll(id) => {
+                let request = ClearScrollRequest {
+                    scroll_ids: vec![id.clone()],
+                };
+                client.execute(CLEAR_SCROLL_ACTION, "", &request).await
+            }
+            SearchContext::Pit(id) => {
+                let request = DeletePitRequest {
+                    pit_id: vec![id.clone()],
+                };
+                client.execute(DELETE_PIT_ACTION, "", &request).await
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to release search context {:?}: {}", self, e);
+        }
+    }
+}
+
+/// Iterates every hit of a search one page at a time, so memory use stays
+/// bounded by the page size. Call `next` until it returns `None`, as with
+/// a tokio channel receiver.
+///
+/// Uses a scroll by default, or a point in time with `search_after` after
+/// `point_in_time`. The scroll or point in time is released when the last
+/// page has been read, on `close`, or in the background when the stream is
+/// dropped early.
+pub struct ScrollStream<T = Value> {
+    client: SdkClient,
+    request: SearchRequest,
+    keep_alive: String,
+    use_pit: bool,
+    context: Option<SearchContext>,
+    buffer: VecDeque<Hit<T>>,
+    exhausted: bool,
+}
+
+impl<T: DeserializeOwned> ScrollStream<T> {
+    pub(crate) fn new(client: SdkClient, request: SearchRequest) -> Self {
+        ScrollStream {
+            client,
+            request,
+            keep_alive: "1m".to_string(),
+            use_pit: false,
+            context: None,
+            buffer: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    /// How long the cluster keeps the search context between pages.
+    pub fn keep_alive(mut self, keep_alive: impl Into<String>) -> Self {
+        self.keep_alive = keep_alive.into();
+        self
+    }
+
+    /// Page with a point in time and `search_after` instead of a scroll.
+    pub fn point_in_time(mut self) -> Self {
+        self.use_pit = true;
+        self
+    }
+
+    /// The next hit, fetching another page when the current one is used up.
+    /// After an error the stream is finished.
+    pub async fn next(&mut self) -> Option<Result<Hit<T>, ExtensionError>> {
+        loop {
+            if let Some(hit) = self.buffer.pop_front() {
+                return Some(Ok(hit));
+            }
+            if self.exhausted {
+                self.close().await;
+                return None;
+            }
+            if let Err(e) = self.fetch_page().await {
+                self.exhausted = true;
+                self.close().await;
+                return Some(Err(e));
+            }
+        }
+    }
+
+    /// Release the search context now rather than when the stream is dropped.
+    pub async fn close(&mut self) {
+        self.exhausted = true;
+        self.buffer.clear();
+        if let Some(context) = self.context.take() {
+            context.release(&self.client).await;
+        }
+    }
+
+    async fn fetch_page(&mut self) -> Result<(), ExtensionError> {
+        let page_size = self.request.size.unwrap_or(DEFAULT_PAGE_SIZE);
+        let response: SearchResponse<T> = match (&self.context, self.use_pit) {
+            (None, false) => {
+                let mut request = self.request.clone();
+                request.size = Some(page_size);
+                request.scroll = Some(self.keep_alive.clone());
+                self.client
+                    .execute(SEARCH_ACTION, &request.usage_index(), &request)
+                    .await?
+            }
+            (Some(SearchContext::Scroll(id)), _) => {
+                let request = ScrollRequest {
+                    scroll_id: id,
+                    scroll: &self.keep_alive,
+                };
+                self.client
+                    .execute(SCROLL_ACTION, &self.request.usage_index(), &request)
+                    .await?
+            }
+            (None, true) => {
+                let request = CreatePitRequest {
+                    indices: &self.request.indices,
+                    keep_alive: &self.keep_alive,
+                };
+                let pit: CreatePitResponse = self
+                    .client
+                    .execute(CREATE_PIT_ACTION, &self.request.usage_index(), &request)
+                    .await?;
+                self.context = Some(SearchContext::Pit(pit.pit_id));
+                return Ok(());
+            }
+            (Some(SearchContext::Pit(id)), _) => {
+                let mut request = self.request.clone();
+                request.indices.clear();
+                request.size = Some(page_size);
+                request.pit = Some(PointInTime {
+                    id: id.clone(),
+                    keep_alive: self.keep_alive.clone(),
+                });
+                if request.sort.is_empty() {
+                    request.sort.push(json!({"_shard_doc": "asc"}));
+                }
+                self.client
+                    .execute(SEARCH_ACTION, &self.request.usage_index(), &request)
+                    .await?
+            }
+        };
+        if let Some(id) = response.scroll_id {
+            self.context = Some(SearchContext::Scroll(id));
+        } else if let (Some(id), Some(SearchContext::Pit(_))) = (response.pit_id, &self.context) {
+            self.context = Some(SearchContext::Pit(id));
+        }
+
+        let hits = response.hits.hits;
+        if (hits.len() as u32) < page_size {
+            self.exhausted = true;
+        }
+        if let (true, Some(last)) = (self.use_pit, hits.last()) {
+            self.request.search_after = Some(last.sort.clone());
+        }
+        self.buffer.extend(hits);
+        Ok(())
+    }
+}
+
+impl<T> Drop for ScrollStream<T> {
+    fn drop(&mut self) {
+        let Some(context) = self.context.take() else {
+            return;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let client = self.client.clone();
+                runtime.spawn(async move { context.release(&client).await });
+            }
+            Err(_) => warn!(
+                "Search context {:?} left open until its keep-alive expires",
+                context
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{RemoteExtensionActionResponse, TransportActionRequestFromExtension};
+    use crate::interface::{self, Serialize as _};
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+    use crate::transport::TransportClient;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// Answers one connection per body in order, returning the actions received.
+    async fn serve(bodies: Vec<Value>) -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let mut actions = Vec::new();
+            for body in bodies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let message = read_message(&mut stream).await.unwrap().unwrap();
+                let request =
+                    <TransportActionRequestFromExtension as interface::Deserialize>::deserialize(
+                        &mut message.content.as_slice(),
+                    )
+                    .unwrap();
+                actions.push(request.action);
+
+                let mut bytes = Vec::new();
+                RemoteExtensionActionResponse {
+                    success: true,
+                    response_bytes: body.to_string().into_bytes(),
+                }
+                .serialize(&mut bytes)
+                .unwrap();
+                write_response(
+                    &mut stream,
+                    &message.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+            actions
+        });
+        (port, handle)
+    }
+
+    fn page(scroll_id: &str, ids: &[&str]) -> Value {
+        let hits: Vec<Value> = ids
+            .iter()
+            .map(|id| json!({"_index": "logs", "_id": id, "_source": {"n": id}}))
+            .collect();
+        json!({"took": 1, "_scroll_id": scroll_id, "hits": {"total": {"value": 3, "relation": "eq"}, "hits": hits}})
+    }
+
+    #[tokio::test]
+    async fn test_scroll_stream_reads_all_pages() {
+        let (port, server) = serve(vec![
+            page("s1", &["1", "2"]),
+            page("s2", &["3"]),
+            json!({"succeeded": true, "num_freed": 1}),
+        ])
+        .await;
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let mut stream = client.scroll_stream::<Value>(SearchRequest::new(&["logs"]).size(2));
+        let mut ids = Vec::new();
+        while let Some(hit) = stream.next().await {
+            ids.push(hit.unwrap().id);
+        }
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        assert!(stream.next().await.is_none());
+
+        assert_eq!(
+            server.await.unwrap(),
+            vec![SEARCH_ACTION, SCROLL_ACTION, CLEAR_SCROLL_ACTION]
+        );
+    }
+}
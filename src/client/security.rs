@@ -0,0 +1,285 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use crate::client::SdkClient;
+use crate::extension::{ExtensionError, PrincipalIdentifier};
+
+pub const GENERATE_OBO_TOKEN_ACTION: &str = "cluster:admin/security/onbehalfof/generate";
+pub const HAS_PERMISSIONS_ACTION: &str = "cluster:admin/security/permissions/check";
+
+/// Request header carrying an on-behalf-of token as `Bearer <token>`.
+pub const AUTHORIZATION_HEADER: &str = "Authorization";
+
+tokio::task_local! {
+    static ON_BEHALF_OF: OnBehalfOfToken;
+}
+
+/// Body of an on-behalf-of token request, as the security plugin expects it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OnBehalfOfRequest {
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(rename = "durationSeconds", skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct OnBehalfOfResponse {
+    user: String,
+    #[serde(rename = "authenticationToken")]
+    authentication_token: String,
+    #[serde(rename = "durationSeconds", default, deserialize_with = "seconds")]
+    duration_seconds: Option<u64>,
+}
+
+/// The plugin reports the duration as a number or a numeric string.
+fn seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_u64(),
+        Some(Value::String(s)) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct PermissionsRequest<'a> {
+    permissions: &'a [String],
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct PermissionsResponse {
+    #[serde(default)]
+    permissions: BTreeMap<String, bool>,
+}
+
+/// A short-lived token for acting as a user, obtained with
+/// `SecurityClient::exchange_on_behalf_of`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct OnBehalfOfToken {
+    pub user: String,
+    token: String,
+    expires_at: Option<SystemTime>,
+}
+
+impl OnBehalfOfToken {
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= SystemTime::now())
+    }
+
+    /// Run `future`, making the `SdkClient` calls in it as this token's user
+    /// unless a client is explicitly `on_behalf_of` another principal.
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        ON_BEHALF_OF.scope(self, future).await
+    }
+
+    /// The token of the enclosing `run`, if any.
+    pub fn current() -> Option<OnBehalfOfToken> {
+        ON_BEHALF_OF.try_with(Clone::clone).ok()
+    }
+}
+
+impl std::fmt::Debug for OnBehalfOfToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OnBehalfOfToken")
+            .field("user", &self.user)
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Security plugin APIs, from `SdkClient::security`.
+pub struct SecurityClient {
+    client: SdkClient,
+}
+
+impl SecurityClient {
+    pub fn new(client: SdkClient) -> Self {
+        SecurityClient { client }
+    }
+
+    /// Obtain a token scoped to acting as `principal`, typically the user of
+    /// the REST request being handled. Calls made inside `token.run(...)`
+    /// then carry it automatically.
+    pub async fn exchange_on_behalf_of(
+        &self,
+        principal: &PrincipalIdentifier,
+    ) -> Result<OnBehalfOfToken, ExtensionError> {
+        let request = OnBehalfOfRequest {
+            description: format!(
+                "Extension {} acting on behalf of a user",
+                self.client.unique_id
+            ),
+            service: Some(self.client.unique_id.clone()),
+            duration_seconds: None,
+        };
+        self.generate_on_behalf_of(principal, request).await
+    }
+
+    /// The subset of `permissions` that `principal` lacks, empty when the
+    /// user holds them all.
+    pub async fn missing_permissions(
+        &self,
+        principal: &PrincipalIdentifier,
+        permissions: &[String],
+    ) -> Result<Vec<String>, ExtensionError> {
+        let response: PermissionsResponse = self
+            .client
+            .on_behalf_of(principal)
+            .execute(
+                HAS_PERMISSIONS_ACTION,
+                "",
+                &PermissionsRequest { permissions },
+            )
+            .await?;
+        Ok(permissions
+            .iter()
+            .filter(|permission| {
+                !response
+                    .permissions
+                    .get(*permission)
+                    .copied()
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect())
+    }
+
+    pub async fn generate_on_behalf_of(
+        &self,
+        principal: &PrincipalIdentifier,
+        request: OnBehalfOfRequest,
+    ) -> Result<OnBehalfOfToken, ExtensionError> {
+        let response: OnBehalfOfResponse = self
+            .client
+            .on_behalf_of(principal)
+            .execute(GENERATE_OBO_TOKEN_ACTION, "", &request)
+            .await?;
+        if response.authentication_token.is_empty() {
+            return Err(ExtensionError::protocol(
+                "Security plugin returned an empty on-behalf-of token",
+            ));
+        }
+        Ok(OnBehalfOfToken {
+            user: response.user,
+            token: response.authentication_token,
+            expires_at: response
+                .duration_seconds
+                .map(|secs| SystemTime::now() + Duration::from_secs(secs)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{RemoteExtensionActionResponse, TransportActionRequestFromExtension};
+    use crate::extension::identity::PRINCIPAL_HEADER;
+    use crate::interface::{self, Serialize as _};
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+    use crate::transport::TransportClient;
+    use serde_json::json;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// Answers one connection per body in order, returning the requests received.
+    async fn serve(
+        bodies: Vec<Value>,
+    ) -> (
+        u16,
+        tokio::task::JoinHandle<Vec<(TransportActionRequestFromExtension, ThreadContext)>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for body in bodies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let message = read_message(&mut stream).await.unwrap().unwrap();
+                let request =
+                    <TransportActionRequestFromExtension as interface::Deserialize>::deserialize(
+                        &mut message.content.as_slice(),
+                    )
+                    .unwrap();
+                requests.push((request, message.thread_context));
+
+                let mut bytes = Vec::new();
+                RemoteExtensionActionResponse {
+                    success: true,
+                    response_bytes: body.to_string().into_bytes(),
+                }
+                .serialize(&mut bytes)
+                .unwrap();
+                write_response(
+                    &mut stream,
+                    &message.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+            requests
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn test_exchange_and_scope_on_behalf_of_token() {
+        let (port, server) = serve(vec![
+            json!({"user": "alice", "authenticationToken": "obo.jwt.sig", "durationSeconds": "300"}),
+            json!({"exists": true}),
+            json!({"exists": true}),
+        ])
+        .await;
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+
+        let principal = PrincipalIdentifier::new("alice|ops|reader|");
+        let token = client
+            .security()
+            .exchange_on_behalf_of(&principal)
+            .await
+            .unwrap();
+        assert_eq!(token.user, "alice");
+        assert!(!token.is_expired());
+        assert!(!format!("{:?}", token).contains("obo.jwt.sig"));
+
+        token
+            .run(async { client.index_exists("logs").await.unwrap() })
+            .await;
+        client.index_exists("logs").await.unwrap();
+        assert!(OnBehalfOfToken::current().is_none());
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests[0].0.action, GENERATE_OBO_TOKEN_ACTION);
+        assert_eq!(
+            requests[0].1.request_header(PRINCIPAL_HEADER),
+            Some("alice|ops|reader|")
+        );
+        let sent: Value = serde_json::from_slice(&requests[0].0.request_bytes).unwrap();
+        assert_eq!(sent["service"], "hello-world");
+        assert_eq!(
+            requests[1].1.request_header(AUTHORIZATION_HEADER),
+            Some("Bearer obo.jwt.sig")
+        );
+        assert_eq!(requests[1].1.request_header(PRINCIPAL_HEADER), None);
+        assert_eq!(requests[2].1.request_header(AUTHORIZATION_HEADER), None);
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+use crate::client::indices::validate_index_name;
+use crate::client::{
+    AliasesRequest, CreateIndexRequest, IndexSettings, Mappings, PutMappingRequest, SdkClient,
+};
+use crate::extension::ExtensionError;
+
+pub const REINDEX_ACTION: &str = "indices:data/write/reindex";
+
+/// Key under the mapping's `_meta` holding the schema version of a system index.
+pub const SCHEMA_VERSION_META: &str = "schema_version";
+
+/// How a system index is brought up to a newer schema version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Migration {
+    /// The new schema only adds fields, so the mapping is updated in place.
+    UpdateMapping,
+    /// Copy the documents into a new index and move the alias over. The old
+    /// index is kept so it can be inspected or restored.
+    Reindex,
+}
+
+/// An index owned by the extension. It is accessed through the alias
+/// `name`, which points at the concrete index `<name>-<version>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemIndexDescriptor {
+    pub name: String,
+    pub version: u32,
+    pub mappings: Mappings,
+    pub settings: IndexSettings,
+    pub migration: Migration,
+}
+
+impl SystemIndexDescriptor {
+    pub fn new(name: impl Into<String>, version: u32) -> Self {
+        SystemIndexDescriptor {
+            name: name.into(),
+            version,
+            mappings: Mappings::new(),
+            settings: IndexSettings::new().hidden(true),
+            migration: Migration::UpdateMapping,
+        }
+    }
+
+    pub fn mappings(mut self, mappings: Mappings) -> Self {
+        self.mappings = mappings;
+        self
+    }
+
+    pub fn settings(mut self, settings: IndexSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn migration(mut self, migration: Migration) -> Self {
+        self.migration = migration;
+        self
+    }
+
+    /// Pattern matching every version of the index.
+    pub fn pattern(&self) -> String {
+        format!("{}-*", self.name)
+    }
+
+    pub fn concrete_index(&self) -> String {
+        format!("{}-{}", self.name, self.version)
+    }
+
+    fn versioned_mappings(&self) -> Mappings {
+        self.mappings
+            .clone()
+            .meta(SCHEMA_VERSION_META, self.version)
+    }
+
+    fn create_request(&self) -> CreateIndexRequest {
+        CreateIndexRequest::new(self.concrete_index())
+            .mappings(self.versioned_mappings())
+            .settings(self.settings.clone())
+    }
+}
+
+/// What `SystemIndexManager::ensure` did for an index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SystemIndexStatus {
+    Created,
+    UpToDate,
+    MappingUpdated {
+        from: u32,
+    },
+    Reindexed {
+        from: u32,
+        documents: u64,
+    },
+    /// A newer version of the extension owns the index; it is left alone.
+    Newer {
+        found: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ReindexRequest {
+    source: Value,
+    dest: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct ReindexResponse {
+    #[serde(default)]
+    created: u64,
+    #[serde(default)]
+    failures: Vec<Value>,
+}
+
+/// Schema version recorded in a mapping, or 0 for an unversioned one.
+pub(crate) fn schema_version(mappings: &Value) -> u32 {
+    mappings
+        .pointer(&format!("/_meta/{}", SCHEMA_VERSION_META))
+        .and_then(Value::as_u64)
+        .map_or(0, |version| version.min(u32::MAX as u64) as u32)
+}
+
+/// Declares the extension's system indices and keeps them at the declared
+/// schema version. Call `ensure` from `Extension::initialize`.
+pub struct SystemIndexManager {
+    client: SdkClient,
+    indices: Vec<SystemIndexDescriptor>,
+}
+
+impl SystemIndexManager {
+    pub fn new(client: SdkClient) -> Self {
+        SystemIndexManager {
+            client,
+            indices: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, descriptor: SystemIndexDescriptor) -> Result<(), ExtensionError> {
+        validate_index_name(&descriptor.name)?;
+        if self
+            .indices
+            .iter()
+            .any(|index| index.name == descriptor.name)
+        {
+            return Err(ExtensionError::configuration(format!(
+                "System index '{}' is already registered",
+                descriptor.name
+            )));
+        }
+        self.indices.push(descriptor);
+        Ok(())
+    }
+
+    pub fn indices(&self) -> &[SystemIndexDescriptor] {
+        &self.indices
+    }
+
+    /// Create missing indices and migrate outdated ones, by index name.
+    pub async fn ensure(&self) -> Result<BTreeMap<String, SystemIndexStatus>, ExtensionError> {
+        let mut statuses = BTreeMap::new();
+        for descriptor in &self.indices {
+            let status = self.ensure_index(descriptor).await?;
+            info!(
+                "System index {} at version {}: {:?}",
+                descriptor.name, descriptor.version, status
+            );
+            statuses.insert(descriptor.name.clone(), status);
+        }
+        Ok(statuses)
+    }
+
+    async fn ensure_index(
+        &self,
+        descriptor: &SystemIndexDescriptor,
+    ) -> Result<SystemIndexStatus, ExtensionError> {
+        if !self.client.index_exists(&descriptor.name).await?
+            && self
+                .client
+                .ensure_index(descriptor.create_request().alias(&descriptor.name))
+                .await?
+        {
+            return Ok(SystemIndexStatus::Created);
+        }
+
+        let mappings = self.client.get_mappings(&[&descriptor.name]).await?;
+        let (current_index, current) = mappings
+            .iter()
+            .map(|(index, mappings)| (index.clone(), schema_version(&mappings.mappings)))
+            .max_by_key(|(_, version)| *version)
+            .ok_or_else(|| {
+                ExtensionError::transport(format!(
+                    "No mappings returned for system index {}",
+                    descriptor.name
+                ))
+            })?;
+
+        if current == descriptor.version {
+            return Ok(SystemIndexStatus::UpToDate);
+        }
+        if current > descriptor.version {
+            warn!(
+                "System index {} is at schema version {}, newer than {}; leaving it as is",
+                descriptor.name, current, descriptor.version
+            );
+            return Ok(SystemIndexStatus::Newer { found: current });
+        }
+
+        match descriptor.migration {
+            Migration::UpdateMapping => {
+                let request =
+                    PutMappingRequest::new(&[&current_index], descriptor.versioned_mappings());
+                self.client.put_mapping(request).await?;
+                Ok(SystemIndexStatus::MappingUpdated { from: current })
+            }
+            Migration::Reindex => {
+                let target = descriptor.concrete_index();
+                self.client
+                    .ensure_index(descriptor.create_request())
+                    .await?;
+                let request = ReindexRequest {
+                    source: json!({ "index": current_index }),
+                    dest: json!({ "index": target }),
+                };
+                let response: ReindexResponse = self
+                    .client
+                    .execute(REINDEX_ACTION, &target, &request)
+                    .await?;
+                if !response.failures.is_empty() {
+                    return Err(ExtensionError::transport(format!(
+                        "Reindexing system index {} from {} to {} failed for {} documents",
+                        descriptor.name,
+                        current_index,
+                        target,
+                        response.failures.len()
+                    )));
+                }
+                let aliases = AliasesRequest::new()
+                    .remove_alias(&current_index, &descriptor.name)
+                    .add_alias(&target, &descriptor.name);
+                self.client.update_aliases(aliases).await?;
+                Ok(SystemIndexStatus::Reindexed {
+                    from: current,
+                    documents: response.created,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::FieldMapping;
+    use crate::transport::TransportClient;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_system_index_descriptor() {
+        let descriptor = SystemIndexDescriptor::new(".hello-jobs", 3)
+            .mappings(Mappings::new().field("name", FieldMapping::keyword()))
+            .migration(Migration::Reindex);
+        assert_eq!(descriptor.pattern(), ".hello-jobs-*");
+
+        let request =
+            serde_json::to_value(descriptor.create_request().alias(".hello-jobs")).unwrap();
+        assert_eq!(request["index"], ".hello-jobs-3");
+        assert_eq!(request["settings"]["index.hidden"], true);
+        assert_eq!(request["aliases"], json!({".hello-jobs": {}}));
+        assert_eq!(schema_version(&request["mappings"]), 3);
+        assert_eq!(schema_version(&json!({"properties": {}})), 0);
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("localhost", 9300)),
+            "hello-world",
+        );
+        let mut manager = SystemIndexManager::new(client);
+        manager.register(descriptor.clone()).unwrap();
+        assert!(manager.register(descriptor).is_err());
+        assert!(manager
+            .register(SystemIndexDescriptor::new("Jobs", 1))
+            .is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use serde_json::Value;
+use std::io::{Read, Write};
+
+use crate::codec::{BadRecordPolicy, Document, RecordError, RecordSchema};
+use crate::extension::ExtensionError;
+
+/// Streams CSV rows as documents, using the first row as column names.
+pub struct CsvRecordReader<R: Read> {
+    reader: ::csv::Reader<R>,
+    columns: Vec<String>,
+    schema: RecordSchema,
+    skipped: Vec<RecordError>,
+    failed: bool,
+}
+
+impl<R: Read> CsvRecordReader<R> {
+    pub fn new(reader: R, schema: RecordSchema) -> Result<Self, ExtensionError> {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(reader);
+        let columns = reader
+            .headers()
+            .map_err(|e| {
+                ExtensionError::serialization(format!("Failed to read CSV header: {}", e))
+            })?
+            .iter()
+            .map(|column| column.trim().to_string())
+            .collect();
+
+        Ok(CsvRecordReader {
+            reader,
+            columns,
+            schema,
+            skipped: Vec::new(),
+            failed: false,
+        })
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Records dropped under `BadRecordPolicy::Skip`.
+    pub fn skipped(&self) -> &[RecordError] {
+        &self.skipped
+    }
+
+    fn read_record(&self, record: &::csv::StringRecord) -> Result<Document, String> {
+        if record.len() != self.columns.len() {
+            return Err(format!(
+                "expected {} columns, found {}",
+                self.columns.len(),
+                record.len()
+            ));
+        }
+
+        let values = self
+            .columns
+            .iter()
+            .zip(record.iter())
+            .map(|(column, value)| (column.clone(), Value::String(value.to_string())));
+        self.schema.map_record(values)
+    }
+}
+
+impl<R: Read> Iterator for CsvRecordReader<R> {
+    type Item = Result<Document, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let mut record = ::csv::StringRecord::new();
+        loop {
+            let result = match self.reader.read_record(&mut record) {
+                Ok(false) => return None,
+                Ok(true) => {
+                    let line = record.position().map(|p| p.line()).unwrap_or_default();
+                    self.read_record(&record)
+                        .map_err(|reason| RecordError::new(line, reason))
+                }
+                Err(e) => {
+                    let line = e.position().map(|p| p.line()).unwrap_or_default();
+                    Err(RecordError::new(line, e.to_string()))
+                }
+            };
+
+            match result {
+                Err(e) if self.schema.bad_records == BadRecordPolicy::Skip => self.skipped.push(e),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+                Ok(document) => return Some(Ok(document)),
+            }
+        }
+    }
+}
+
+/// Writes documents as CSV rows with a fixed set of columns.
+pub struct CsvRecordWriter<W: Write> {
+    writer: ::csv::Writer<W>,
+    columns: Vec<String>,
+}
+
+impl<W: Write> CsvRecordWriter<W> {
+    pub fn new(writer: W, columns: Vec<String>) -> Result<Self, ExtensionError> {
+        let mut writer = ::csv::Writer::from_writer(writer);
+        writer.write_record(&columns).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to write CSV header: {}", e))
+        })?;
+        Ok(CsvRecordWriter { writer, columns })
+    }
+
+    /// Write one row; missing fields are left empty and non-string values are written as JSON.
+    pub fn write(&mut self, document: &Document) -> Result<(), ExtensionError> {
+        let row = self
+            .columns
+            .iter()
+            .map(|column| match document.get(column) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+            });
+        self.writer
+            .write_record(row)
+            .map_err(|e| ExtensionError::serialization(format!("Failed to write CSV row: {}", e)))
+    }
+
+    pub fn into_inner(self) -> Result<W, ExtensionError> {
+        self.writer.into_inner().map_err(|e| {
+            ExtensionError::serialization(format!("Failed to flush CSV writer: {}", e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::{FieldMapping, FieldType};
+    use serde_json::json;
+
+    #[test]
+    fn test_csv_read_and_write() {
+        let input = "id,name,city\n1,\"Doe, Jane\",Pune\nx,Bad,Row\n3,Sam,\"New\nYork\"\n";
+        let schema = RecordSchema::new()
+            .field(FieldMapping::new("id", FieldType::Long))
+            .on_bad_record(BadRecordPolicy::Skip);
+
+        let mut reader = CsvRecordReader::new(input.as_bytes(), schema).unwrap();
+        let documents: Vec<Document> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(documents.len(), 2);
+        assert_eq!(
+            Value::Object(documents[0].clone()),
+            json!({"id": 1, "name": "Doe, Jane", "city": "Pune"})
+        );
+        assert_eq!(reader.skipped().len(), 1);
+        assert_eq!(reader.skipped()[0].line, 3);
+
+        let mut writer =
+            CsvRecordWriter::new(Vec::new(), vec!["id".to_string(), "name".to_string()]).unwrap();
+        for document in &documents {
+            writer.write(document).unwrap();
+        }
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(output, "id,name\n1,\"Doe, Jane\"\n3,Sam\n");
+    }
+}
@@ -0,0 +1,38 @@
+//! Record codecs for bulk data paths such as uploads and exports.
+
+pub mod csv;
+pub mod ndjson;
+pub mod schema;
+
+use std::fmt;
+
+pub use self::csv::{CsvRecordReader, CsvRecordWriter};
+pub use ndjson::{NdjsonReader, NdjsonWriter};
+pub use schema::{BadRecordPolicy, FieldMapping, FieldType, RecordSchema};
+
+/// A decoded record, ready to be indexed as a document.
+pub type Document = serde_json::Map<String, serde_json::Value>;
+
+/// A record that could not be parsed or mapped, with its 1-based line number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordError {
+    pub line: u64,
+    pub reason: String,
+}
+
+impl RecordError {
+    pub fn new(line: u64, reason: impl Into<String>) -> Self {
+        RecordError {
+            line,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for RecordError {}
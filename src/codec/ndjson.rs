@@ -0,0 +1,133 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+use crate::codec::{BadRecordPolicy, Document, RecordError, RecordSchema};
+use crate::extension::ExtensionError;
+
+/// Streams newline-delimited JSON objects as documents, skipping blank lines.
+pub struct NdjsonReader<R: BufRead> {
+    reader: R,
+    line: u64,
+    schema: RecordSchema,
+    skipped: Vec<RecordError>,
+    failed: bool,
+}
+
+impl<R: BufRead> NdjsonReader<R> {
+    pub fn new(reader: R, schema: RecordSchema) -> Self {
+        NdjsonReader {
+            reader,
+            line: 0,
+            schema,
+            skipped: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Records dropped under `BadRecordPolicy::Skip`.
+    pub fn skipped(&self) -> &[RecordError] {
+        &self.skipped
+    }
+
+    fn parse_line(&self, line: &str) -> Result<Document, String> {
+        match serde_json::from_str::<Value>(line) {
+            Ok(Value::Object(object)) => self.schema.map_record(object),
+            Ok(other) => Err(format!("expected a JSON object, found {}", other)),
+            Err(e) => Err(format!("invalid JSON: {}", e)),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonReader<R> {
+    type Item = Result<Document, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            let result = match self.reader.read_line(&mut buf) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    self.line += 1;
+                    let line = buf.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    self.parse_line(line)
+                        .map_err(|reason| RecordError::new(self.line, reason))
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(RecordError::new(self.line + 1, e.to_string())));
+                }
+            };
+
+            match result {
+                Err(e) if self.schema.bad_records == BadRecordPolicy::Skip => self.skipped.push(e),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+                Ok(document) => return Some(Ok(document)),
+            }
+        }
+    }
+}
+
+pub struct NdjsonWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        NdjsonWriter { writer }
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<(), ExtensionError> {
+        serde_json::to_writer(&mut self.writer, record).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to write NDJSON record: {}", e))
+        })?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W, ExtensionError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ndjson_read_and_write() {
+        let input = "{\"id\":1}\n\n[1,2]\n{\"id\":2}\n";
+
+        let mut reader = NdjsonReader::new(input.as_bytes(), RecordSchema::new());
+        assert_eq!(reader.next().unwrap().unwrap()["id"], json!(1));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.line, 3);
+        assert!(reader.next().is_none());
+
+        let skipping = NdjsonReader::new(
+            input.as_bytes(),
+            RecordSchema::new().on_bad_record(BadRecordPolicy::Skip),
+        );
+        let documents: Vec<Document> = skipping.collect::<Result<_, _>>().unwrap();
+        assert_eq!(documents.len(), 2);
+
+        let mut writer = NdjsonWriter::new(Vec::new());
+        for document in &documents {
+            writer.write(document).unwrap();
+        }
+        assert_eq!(writer.into_inner().unwrap(), b"{\"id\":1}\n{\"id\":2}\n");
+    }
+}
@@ -0,0 +1,221 @@
+use serde_json::Value;
+
+use crate::codec::Document;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Text,
+    Long,
+    Double,
+    Boolean,
+    /// The value is itself JSON; strings are parsed, anything else is kept as is.
+    Json,
+}
+
+impl FieldType {
+    /// Coerce a raw value into this type. Empty strings become `null`.
+    pub fn coerce(&self, value: Value) -> Result<Value, String> {
+        let value = match value {
+            Value::String(s) if s.trim().is_empty() => return Ok(Value::Null),
+            Value::Null => return Ok(Value::Null),
+            value => value,
+        };
+
+        match (self, value) {
+            (FieldType::Text, Value::String(s)) => Ok(Value::String(s)),
+            (FieldType::Text, other) => Ok(Value::String(other.to_string())),
+
+            (FieldType::Long, Value::Number(n)) => n
+                .as_i64()
+                .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+                .map(Value::from)
+                .ok_or_else(|| format!("{} is not a whole number", n)),
+            (FieldType::Long, Value::String(s)) => s
+                .trim()
+                .parse::<i64>()
+                .map(Value::from)
+                .map_err(|_| format!("'{}' is not a whole number", s)),
+
+            (FieldType::Double, Value::Number(n)) => n
+                .as_f64()
+                .map(Value::from)
+                .ok_or_else(|| format!("{} is not a number", n)),
+            (FieldType::Double, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .map(Value::from)
+                .map_err(|_| format!("'{}' is not a number", s)),
+
+            (FieldType::Boolean, Value::Bool(b)) => Ok(Value::Bool(b)),
+            (FieldType::Boolean, Value::String(s)) => {
+                match s.trim().to_ascii_lowercase().as_str() {
+                    "true" | "1" => Ok(Value::Bool(true)),
+                    "false" | "0" => Ok(Value::Bool(false)),
+                    _ => Err(format!("'{}' is not a boolean", s)),
+                }
+            }
+
+            (FieldType::Json, Value::String(s)) => {
+                serde_json::from_str(&s).map_err(|e| format!("'{}' is not valid JSON: {}", s, e))
+            }
+            (FieldType::Json, other) => Ok(other),
+
+            (field_type, other) => Err(format!("cannot convert {} to {:?}", other, field_type)),
+        }
+    }
+}
+
+/// Maps a source column (CSV header or top-level NDJSON key) to a document field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMapping {
+    pub column: String,
+    pub field: String,
+    pub field_type: FieldType,
+    pub required: bool,
+}
+
+impl FieldMapping {
+    pub fn new(column: impl Into<String>, field_type: FieldType) -> Self {
+        let column = column.into();
+        FieldMapping {
+            field: column.clone(),
+            column,
+            field_type,
+            required: false,
+        }
+    }
+
+    pub fn to_field(mut self, field: impl Into<String>) -> Self {
+        self.field = field.into();
+        self
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BadRecordPolicy {
+    /// Stop reading at the first bad record.
+    #[default]
+    Fail,
+    /// Skip bad records, keeping their errors for the caller to report.
+    Skip,
+}
+
+/// How raw records are turned into documents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordSchema {
+    pub fields: Vec<FieldMapping>,
+    pub include_unmapped: bool,
+    pub bad_records: BadRecordPolicy,
+}
+
+impl Default for RecordSchema {
+    fn default() -> Self {
+        RecordSchema {
+            fields: Vec::new(),
+            include_unmapped: true,
+            bad_records: BadRecordPolicy::Fail,
+        }
+    }
+}
+
+impl RecordSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field(mut self, mapping: FieldMapping) -> Self {
+        self.fields.push(mapping);
+        self
+    }
+
+    /// Drop columns that have no mapping instead of copying them through.
+    pub fn mapped_only(mut self) -> Self {
+        self.include_unmapped = false;
+        self
+    }
+
+    pub fn on_bad_record(mut self, policy: BadRecordPolicy) -> Self {
+        self.bad_records = policy;
+        self
+    }
+
+    pub fn map_record(
+        &self,
+        record: impl IntoIterator<Item = (String, Value)>,
+    ) -> Result<Document, String> {
+        let mut document = Document::new();
+        let mut seen = vec![false; self.fields.len()];
+
+        for (column, value) in record {
+            match self
+                .fields
+                .iter()
+                .position(|mapping| mapping.column == column)
+            {
+                Some(index) => {
+                    let mapping = &self.fields[index];
+                    let value = mapping
+                        .field_type
+                        .coerce(value)
+                        .map_err(|e| format!("column '{}': {}", column, e))?;
+                    if !value.is_null() {
+                        seen[index] = true;
+                        document.insert(mapping.field.clone(), value);
+                    }
+                }
+                None if self.include_unmapped => {
+                    document.insert(column, value);
+                }
+                None => {}
+            }
+        }
+
+        if let Some(missing) = self
+            .fields
+            .iter()
+            .zip(&seen)
+            .find(|(mapping, seen)| mapping.required && !**seen)
+        {
+            return Err(format!("missing required column '{}'", missing.0.column));
+        }
+
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_mapping_and_coercion() {
+        let schema = RecordSchema::new()
+            .field(FieldMapping::new("id", FieldType::Long).required())
+            .field(FieldMapping::new("ok", FieldType::Boolean).to_field("active"))
+            .field(FieldMapping::new("score", FieldType::Double))
+            .mapped_only();
+
+        let document = schema
+            .map_record(vec![
+                ("id".to_string(), json!("42")),
+                ("ok".to_string(), json!("TRUE")),
+                ("score".to_string(), json!("")),
+                ("extra".to_string(), json!("dropped")),
+            ])
+            .unwrap();
+        assert_eq!(Value::Object(document), json!({"id": 42, "active": true}));
+
+        assert!(schema
+            .map_record(vec![("id".to_string(), json!("x"))])
+            .is_err());
+        assert!(schema
+            .map_record(vec![("ok".to_string(), json!("1"))])
+            .is_err());
+    }
+}
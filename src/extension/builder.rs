@@ -1,11 +1,25 @@
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 
 use crate::extension::{
-    Extension, ExtensionContext, ExtensionError, ExtensionRunner,
-    context::Settings,
+    config_watcher::ConfigWatcher,
+    context::{SettingValue, Settings},
+    descriptor::ExtensionDescriptor,
+    discovery_backend,
+    health::HealthService,
+    setting::{Setting, SettingKind},
+    settings_validation::SettingsValidator,
+    Extension, ExtensionContext, ExtensionError, ExtensionRunner, ServiceAccount, SlowLog,
+};
+use crate::metrics::MetricsRegistry;
+use crate::rest::{ErrorMapper, RestMiddleware};
+use crate::transport::inbound::ProtocolMode;
+use crate::transport::{
+    ConnectionRegistry, EndpointSet, EndpointWatcher, MessageSigner, ResponseSpooler,
+    TransportClient, UsageTracker,
 };
-use crate::transport::TransportClient;
 
 pub struct ExtensionBuilder {
     name: String,
@@ -13,10 +27,23 @@ pub struct ExtensionBuilder {
     version: String,
     opensearch_version: String,
     settings: Settings,
+    settings_files: Vec<PathBuf>,
+    env_prefix: Option<String>,
+    setting_errors: Vec<ExtensionError>,
     port: u16,
     transport_host: String,
     transport_port: u16,
     thread_pool: Option<Arc<Runtime>>,
+    rest_middleware: Vec<Arc<dyn RestMiddleware>>,
+    usage_tracker: Option<Arc<UsageTracker>>,
+    error_mappers: Vec<Arc<dyn ErrorMapper>>,
+    strict_protocol: bool,
+    dns_refresh_interval: Option<Duration>,
+    connection_registry: Option<Arc<ConnectionRegistry>>,
+    response_spooler: Option<Arc<ResponseSpooler>>,
+    metrics: Option<Arc<MetricsRegistry>>,
+    health: Option<HealthService>,
+    slow_log: SlowLog,
 }
 
 impl ExtensionBuilder {
@@ -27,109 +54,323 @@ impl ExtensionBuilder {
             version: "1.0.0".to_string(),
             opensearch_version: "3.0.0".to_string(),
             settings: Settings::new(),
+            settings_files: Vec::new(),
+            env_prefix: None,
+            setting_errors: Vec::new(),
             port: 1234,
             transport_host: "localhost".to_string(),
             transport_port: 9300,
             thread_pool: None,
+            rest_middleware: Vec::new(),
+            usage_tracker: None,
+            error_mappers: Vec::new(),
+            strict_protocol: false,
+            dns_refresh_interval: None,
+            connection_registry: None,
+            response_spooler: None,
+            metrics: None,
+            health: None,
+            slow_log: SlowLog::new(),
         }
     }
-    
+
+    /// Seed the builder from a `hello.json`-style descriptor, including its bind address.
+    pub fn from_descriptor(descriptor: &ExtensionDescriptor) -> Self {
+        ExtensionBuilder::new(descriptor.name.clone())
+            .unique_id(descriptor.unique_id.clone())
+            .version(descriptor.version.clone())
+            .opensearch_version(descriptor.opensearch_version.clone())
+            .port(descriptor.port)
+            .setting("bind_address", descriptor.host_address.clone())
+    }
+
     pub fn unique_id(mut self, id: impl Into<String>) -> Self {
         self.unique_id = id.into();
         self
     }
-    
+
     pub fn version(mut self, version: impl Into<String>) -> Self {
         self.version = version.into();
         self
     }
-    
+
     pub fn opensearch_version(mut self, version: impl Into<String>) -> Self {
         self.opensearch_version = version.into();
         self
     }
-    
+
     pub fn port(mut self, port: u16) -> Self {
         self.port = port;
         self
     }
-    
+
     pub fn transport_endpoint(mut self, host: impl Into<String>, port: u16) -> Self {
         self.transport_host = host.into();
         self.transport_port = port;
         self
     }
-    
-    pub fn setting<T: Into<crate::extension::context::SettingValue>>(
-        self,
-        key: impl Into<String>,
-        value: T,
+
+    /// Set `key` explicitly. Failures are reported by `build`.
+    pub fn setting<T: Into<SettingValue>>(mut self, key: impl Into<String>, value: T) -> Self {
+        if let Err(e) = self.settings.set(key, value) {
+            self.setting_errors.push(e);
+        }
+        self
+    }
+
+    /// Set a typed setting, checked against its validators. Invalid values
+    /// are reported by `build`.
+    pub fn typed_setting<T: SettingKind>(
+        mut self,
+        setting: &Setting<T>,
+        value: impl Into<T>,
     ) -> Self {
-        self.settings.set(key, value).unwrap_or(());
+        if let Err(e) = setting.set(&self.settings, value) {
+            self.setting_errors.push(e);
+        }
         self
     }
-    
+
     pub fn settings(mut self, settings: Settings) -> Self {
         self.settings = settings;
         self
     }
-    
+
+    /// Load settings from a YAML or TOML file. Later files override earlier
+    /// ones; the environment and explicit settings override both.
+    pub fn settings_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings_files.push(path.into());
+        self
+    }
+
+    /// Load settings from environment variables starting with `prefix`,
+    /// see `Settings::from_env`.
+    pub fn settings_from_env(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Environment and explicit settings, which settings files cannot override.
+    fn override_settings(&self) -> Result<Settings, ExtensionError> {
+        let mut overrides = Settings::new();
+        if let Some(prefix) = &self.env_prefix {
+            overrides.merge(&Settings::from_env(prefix))?;
+        }
+        overrides.merge(&self.settings)?;
+        Ok(overrides)
+    }
+
+    /// Explicit settings layered over the configured files and environment.
+    fn layered_settings(&self) -> Result<Settings, ExtensionError> {
+        let mut layered = Settings::new();
+        for path in &self.settings_files {
+            layered.merge(&Settings::from_file(path)?)?;
+        }
+        layered.merge(&self.override_settings()?)?;
+
+        let mut settings = self.settings.clone();
+        settings.merge(&layered)?;
+        Ok(settings)
+    }
+
     pub fn thread_pool(mut self, pool: Arc<Runtime>) -> Self {
         self.thread_pool = Some(pool);
         self
     }
-    
+
+    /// Add middleware wrapping every REST route. Per-route middleware comes
+    /// from `RestHandler::middleware`.
+    pub fn rest_middleware(mut self, middleware: Arc<dyn RestMiddleware>) -> Self {
+        self.rest_middleware.push(middleware);
+        self
+    }
+
+    /// Add a mapper from handler errors to REST responses, consulted before
+    /// `DefaultErrorMapper`.
+    pub fn error_mapper(mut self, mapper: Arc<dyn ErrorMapper>) -> Self {
+        self.error_mappers.push(mapper);
+        self
+    }
+
+    /// Reject inbound messages that deviate from the transport protocol in
+    /// any field, logging a report of every violation.
+    pub fn strict_protocol(mut self, strict: bool) -> Self {
+        self.strict_protocol = strict;
+        self
+    }
+
+    /// Re-resolve the transport host every `interval`, following OpenSearch
+    /// nodes as their addresses change. With a `discovery.backend` set, the
+    /// nodes are located through it instead, every 30 seconds by default.
+    pub fn watch_transport_dns(mut self, interval: Duration) -> Self {
+        self.dns_refresh_interval = Some(interval);
+        self
+    }
+
+    /// Track inbound OpenSearch connections in `registry`.
+    pub fn connection_registry(mut self, registry: Arc<ConnectionRegistry>) -> Self {
+        self.connection_registry = Some(registry);
+        self
+    }
+
+    /// Spool transport responses larger than the spooler's threshold to
+    /// temporary files instead of holding them in memory while sending.
+    /// Only used once the node has agreed to `STREAMING_RESPONSES`.
+    pub fn response_spooler(mut self, spooler: Arc<ResponseSpooler>) -> Self {
+        self.response_spooler = Some(spooler);
+        self
+    }
+
+    /// Log requests slower than the thresholds of `slow_log`.
+    pub fn slow_log(mut self, slow_log: SlowLog) -> Self {
+        self.slow_log = slow_log;
+        self
+    }
+
+    /// Export metrics through `registry`. Set `metrics::metrics_port` to
+    /// serve them for Prometheus.
+    pub fn metrics(mut self, registry: Arc<MetricsRegistry>) -> Self {
+        self.metrics = Some(registry);
+        self
+    }
+
+    /// Restart or exit when `health` stays unhealthy, as configured by the
+    /// `supervisor` settings.
+    pub fn health(mut self, health: HealthService) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Account every call made to the cluster in `tracker`.
+    pub fn usage_tracker(mut self, tracker: Arc<UsageTracker>) -> Self {
+        self.usage_tracker = Some(tracker);
+        self
+    }
+
     pub fn build<E: Extension>(self, extension: E) -> Result<ExtensionRunner, ExtensionError> {
         if self.unique_id.is_empty() {
             return Err(ExtensionError::configuration("Unique ID is required"));
         }
-        
+
         let provided_name = extension.name();
         if provided_name != self.name {
-            return Err(ExtensionError::configuration(
-                format!("Extension name mismatch: builder has '{}', extension has '{}'", 
-                    self.name, provided_name)
-            ));
+            return Err(ExtensionError::configuration(format!(
+                "Extension name mismatch: builder has '{}', extension has '{}'",
+                self.name, provided_name
+            )));
         }
-        
+
         let provided_id = extension.unique_id();
         if provided_id != self.unique_id {
-            return Err(ExtensionError::configuration(
-                format!("Extension ID mismatch: builder has '{}', extension has '{}'", 
-                    self.unique_id, provided_id)
-            ));
+            return Err(ExtensionError::configuration(format!(
+                "Extension ID mismatch: builder has '{}', extension has '{}'",
+                self.unique_id, provided_id
+            )));
         }
-        
+
         let provided_version = extension.version();
         if provided_version != self.version {
-            return Err(ExtensionError::configuration(
-                format!("Extension version mismatch: builder has '{}', extension has '{}'", 
-                    self.version, provided_version)
-            ));
-        }
-        
-        let transport_client = Arc::new(
-            TransportClient::new(self.transport_host, self.transport_port)
-        );
-        
+            return Err(ExtensionError::configuration(format!(
+                "Extension version mismatch: builder has '{}', extension has '{}'",
+                self.version, provided_version
+            )));
+        }
+
+        let settings = self.layered_settings()?;
+        let mut report =
+            SettingsValidator::new(extension.setting_registry(), extension.custom_settings())
+                .validate(&settings)?;
+        let config_watcher = match self.settings_files.is_empty() {
+            true => None,
+            false => Some(Arc::new(ConfigWatcher::new(
+                self.settings_files.clone(),
+                settings.clone(),
+                self.override_settings()?,
+                SettingsValidator::new(extension.setting_registry(), extension.custom_settings()),
+            ))),
+        };
+        for e in self.setting_errors {
+            report.push_error(e);
+        }
+        report.into_result()?;
+
         let thread_pool = match self.thread_pool {
             Some(pool) => pool,
-            None => {
-                Runtime::new()
-                    .map(Arc::new)
-                    .map_err(|e| ExtensionError::initialization(
-                        format!("Failed to create runtime: {}", e)
-                    ))?
-            }
+            None => Runtime::new().map(Arc::new).map_err(|e| {
+                ExtensionError::initialization(format!("Failed to create runtime: {}", e))
+            })?,
         };
-        
+
+        let mut transport_client =
+            TransportClient::new(self.transport_host.clone(), self.transport_port)
+                .with_service_account(Arc::new(ServiceAccount::new()), self.unique_id.clone());
+        if let Some(signer) = MessageSigner::from_settings(&settings)? {
+            transport_client = transport_client.with_signer(Arc::new(signer));
+        }
+        if let Some(tracker) = self.usage_tracker {
+            if let Some(metrics) = &self.metrics {
+                metrics.register_collector(tracker.clone());
+            }
+            transport_client = transport_client.with_usage_tracker(tracker);
+        }
+        let backend = discovery_backend::backend_from_settings(&settings)?;
+        if backend.is_some() || self.dns_refresh_interval.is_some() {
+            let endpoints = Arc::new(EndpointSet::default());
+            transport_client = transport_client.with_endpoints(endpoints.clone());
+            let interval = self.dns_refresh_interval.unwrap_or(Duration::from_secs(30));
+            let watcher = match backend {
+                Some(backend) => {
+                    let service = discovery_backend::opensearch_service().get(&settings)?;
+                    EndpointWatcher::new(service, self.transport_port, endpoints)
+                        .with_backend(backend)
+                }
+                None => EndpointWatcher::new(self.transport_host, self.transport_port, endpoints),
+            }
+            .with_interval(interval);
+            let _runtime = thread_pool.enter();
+            Arc::new(watcher).start();
+        }
+        let transport_client = Arc::new(transport_client);
+
         let context = ExtensionContext::builder()
-            .settings(self.settings)
+            .settings(settings)
             .transport_client(transport_client)
             .thread_pool(thread_pool)
             .build()?;
-        
-        ExtensionRunner::new(Box::new(extension), context, self.port)
+
+        let protocol_mode = if self.strict_protocol {
+            ProtocolMode::Strict
+        } else {
+            ProtocolMode::Lenient
+        };
+        let connection_registry = self.connection_registry.unwrap_or_default();
+        let response_spooler = self.response_spooler;
+        let metrics = self.metrics;
+        let health = self.health;
+        ExtensionRunner::new(Box::new(extension), context, self.port).map(|runner| {
+            let runner = runner
+                .with_rest_middleware(self.rest_middleware)
+                .with_error_mappers(self.error_mappers)
+                .with_protocol_mode(protocol_mode)
+                .with_connection_registry(connection_registry)
+                .with_slow_log(self.slow_log);
+            let runner = match config_watcher {
+                Some(watcher) => runner.with_config_watcher(watcher),
+                None => runner,
+            };
+            let runner = match response_spooler {
+                Some(spooler) => runner.with_response_spooler(spooler),
+                None => runner,
+            };
+            let runner = match health {
+                Some(health) => runner.with_health(health),
+                None => runner,
+            };
+            match metrics {
+                Some(metrics) => runner.with_metrics(metrics),
+                None => runner,
+            }
+        })
     }
 }
 
@@ -143,29 +384,37 @@ impl Default for ExtensionBuilder {
 mod tests {
     use super::*;
     use async_trait::async_trait;
-    
+
     struct TestExtension {
         name: String,
         unique_id: String,
         version: String,
     }
-    
+
     #[async_trait]
     impl Extension for TestExtension {
-        fn name(&self) -> &str { &self.name }
-        fn unique_id(&self) -> &str { &self.unique_id }
-        fn version(&self) -> &str { &self.version }
-        fn opensearch_version(&self) -> &str { "3.0.0" }
-        
+        fn name(&self) -> &str {
+            &self.name
+        }
+        fn unique_id(&self) -> &str {
+            &self.unique_id
+        }
+        fn version(&self) -> &str {
+            &self.version
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
         async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
             Ok(())
         }
-        
+
         async fn shutdown(&mut self) -> Result<(), ExtensionError> {
             Ok(())
         }
     }
-    
+
     #[test]
     fn test_builder_validation() {
         let extension = TestExtension {
@@ -173,15 +422,40 @@ mod tests {
             unique_id: "test-ext".to_string(),
             version: "1.0.0".to_string(),
         };
-        
+
         let result = ExtensionBuilder::new("test")
             .unique_id("test-ext")
             .version("1.0.0")
             .build(extension);
-        
+
         assert!(result.is_ok());
     }
-    
+
+    #[test]
+    fn test_builder_reports_setting_errors() {
+        let extension = TestExtension {
+            name: "test".to_string(),
+            unique_id: "test-ext".to_string(),
+            version: "1.0.0".to_string(),
+        };
+        let batch_size = Setting::int("test.batch_size").min(1);
+        let mode = Setting::string("test.mode").one_of(&["fast", "safe"]);
+
+        let error = ExtensionBuilder::new("test")
+            .unique_id("test-ext")
+            .typed_setting(&batch_size, 0)
+            .typed_setting(&mode, "slow")
+            .typed_setting(&mode, "safe")
+            .build(extension)
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(error.starts_with("Configuration error: 2 settings problems:"));
+        assert!(error.contains("Invalid value 0 for setting 'test.batch_size': must be at least 1"));
+        assert!(error.contains("Invalid value \"slow\" for setting 'test.mode'"));
+    }
+
     #[test]
     fn test_builder_validation_fails() {
         let extension = TestExtension {
@@ -189,12 +463,12 @@ mod tests {
             unique_id: "test-ext".to_string(),
             version: "1.0.0".to_string(),
         };
-        
+
         let result = ExtensionBuilder::new("wrong-name")
             .unique_id("test-ext")
             .version("1.0.0")
             .build(extension);
-        
+
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+}
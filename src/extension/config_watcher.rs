@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::setting::Setting;
+use crate::extension::settings_validation::SettingsValidator;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+
+/// How often `ConfigWatcher` checks its settings files for changes.
+pub fn settings_reload_interval() -> Setting<TimeValue> {
+    Setting::time("extension.settings_reload_interval")
+        .default(TimeValue::seconds(10))
+        .min(TimeValue::seconds(1))
+}
+
+/// What the settings files held when last applied.
+#[derive(Default)]
+struct Loaded {
+    modified: Vec<Option<SystemTime>>,
+    values: BTreeMap<String, SettingValue>,
+}
+
+/// Reloads settings files while the extension runs. A reload re-reads every
+/// file, validates the result like at startup and, only if it is valid,
+/// applies the changed values to the live `Settings`. Keys set by the
+/// environment or explicitly in the builder override the files and are
+/// never touched.
+pub struct ConfigWatcher {
+    files: Vec<PathBuf>,
+    settings: Settings,
+    overrides: Settings,
+    validator: SettingsValidator,
+    loaded: Mutex<Loaded>,
+    trigger: Notify,
+}
+
+impl ConfigWatcher {
+    /// Watch `files`, as loaded into `settings` at startup, with the keys
+    /// of `overrides` taking precedence.
+    pub fn new(
+        files: Vec<PathBuf>,
+        settings: Settings,
+        overrides: Settings,
+        validator: SettingsValidator,
+    ) -> Self {
+        let watcher = ConfigWatcher {
+            files,
+            settings,
+            overrides,
+            validator,
+            loaded: Mutex::new(Loaded::default()),
+            trigger: Notify::new(),
+        };
+        let values = watcher
+            .read_files()
+            .map(|values| watcher.without_overrides(values))
+            .unwrap_or_default();
+        *watcher
+            .loaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Loaded {
+            modified: watcher.modified_times(),
+            values,
+        };
+        watcher
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Ask a running watcher to reload now, e.g. from a REST handler.
+    pub fn trigger(&self) {
+        self.trigger.notify_one();
+    }
+
+    fn modified_times(&self) -> Vec<Option<SystemTime>> {
+        self.files
+            .iter()
+            .map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Whether any file changed on disk since it was last applied.
+    pub fn changed_on_disk(&self) -> bool {
+        self.loaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .modified
+            != self.modified_times()
+    }
+
+    fn read_files(&self) -> Result<BTreeMap<String, SettingValue>, ExtensionError> {
+        let mut layered = Settings::new();
+        for path in &self.files {
+            layered.merge(&Settings::from_file(path)?)?;
+        }
+        let mut values = BTreeMap::new();
+        for key in layered.keys()? {
+            if let Some(value) = layered.get(&key)? {
+                values.insert(key, value);
+            }
+        }
+        Ok(values)
+    }
+
+    fn without_overrides(
+        &self,
+        mut values: BTreeMap<String, SettingValue>,
+    ) -> BTreeMap<String, SettingValue> {
+        values.retain(|key, _| !matches!(self.overrides.get(key), Ok(Some(_))));
+        values
+    }
+
+    /// Re-read the files and apply what changed, returning the changed keys.
+    /// Invalid files leave the settings as they were.
+    pub fn reload(&self) -> Result<Vec<String>, ExtensionError> {
+        let modified = self.modified_times();
+        let values = self.without_overrides(self.read_files()?);
+
+        let previous = std::mem::take(
+            &mut self
+                .loaded
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .values,
+        );
+        let mut updates: Vec<(String, Option<SettingValue>)> = previous
+            .keys()
+            .filter(|key| !values.contains_key(*key))
+            .map(|key| (key.clone(), None))
+            .collect();
+        updates.extend(
+            values
+                .iter()
+                .map(|(key, value)| (key.clone(), Some(value.clone()))),
+        );
+
+        let mut candidate = Settings::new();
+        candidate.merge(&self.settings)?;
+        let result = candidate
+            .apply_updates(updates.clone())
+            .and_then(|_| self.validator.validate(&candidate)?.into_result())
+            .and_then(|_| self.settings.apply_updates(updates));
+
+        let mut loaded = self.loaded.lock().unwrap_or_else(PoisonError::into_inner);
+        loaded.modified = modified;
+        loaded.values = if result.is_ok() { values } else { previous };
+        result
+    }
+
+    /// Reload whenever a file changes, checked every `interval`, on
+    /// `trigger` and, on Unix, on SIGHUP. `on_change` receives the changed
+    /// keys of each reload that changed any. Runs until the task is aborted.
+    pub async fn run<F, Fut>(&self, interval: Duration, mut on_change: F)
+    where
+        F: FnMut(Vec<String>) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .inspect_err(|e| {
+                warn!(
+                    "Failed to listen for SIGHUP, settings reload on signal is off: {}",
+                    e
+                )
+            })
+            .ok();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            #[cfg(unix)]
+            let hangup_received = async {
+                match hangup.as_mut() {
+                    Some(hangup) => hangup.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let hangup_received = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if !self.changed_on_disk() {
+                        continue;
+                    }
+                }
+                _ = self.trigger.notified() => {}
+                _ = hangup_received => info!("Received SIGHUP, reloading settings"),
+            }
+            match self.reload() {
+                Ok(changed) if !changed.is_empty() => {
+                    info!(
+                        "Reloaded settings from {:?}, changed {:?}",
+                        self.files, changed
+                    );
+                    on_change(changed).await;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Rejected settings reload, keeping current settings: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::setting::SettingRegistry;
+
+    #[tokio::test]
+    async fn test_reload_applies_valid_changes_only() {
+        let dir = std::env::temp_dir().join(format!("config-watcher-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("hello.yml");
+        std::fs::write(&path, "hello:\n  greeting: hi\n  shards: 2\n  stale: x\n").unwrap();
+
+        let shards = Setting::int("hello.shards").min(1).dynamic();
+        let mut registry = SettingRegistry::new();
+        registry.register(&shards).unwrap();
+        registry
+            .register(&Setting::string("hello.greeting"))
+            .unwrap();
+        registry.register(&Setting::string("hello.stale")).unwrap();
+        let mut settings = Settings::from_file(&path).unwrap();
+        let overrides = Settings::new();
+        overrides.set("hello.greeting", "explicit").unwrap();
+        settings.merge(&overrides).unwrap();
+        let watcher = ConfigWatcher::new(
+            vec![path.clone()],
+            settings.clone(),
+            overrides,
+            SettingsValidator::new(registry, vec![]),
+        );
+        assert!(!watcher.changed_on_disk());
+
+        std::fs::write(&path, "hello:\n  greeting: hello\n  shards: 0\n").unwrap();
+        assert!(watcher.reload().is_err());
+        assert_eq!(settings.get_integer("hello.shards").unwrap(), Some(2));
+
+        std::fs::write(&path, "hello:\n  greeting: hello\n  shards: 3\n").unwrap();
+        let mut changed = watcher.reload().unwrap();
+        changed.sort();
+        assert_eq!(changed, vec!["hello.shards", "hello.stale"]);
+        assert_eq!(settings.get_integer("hello.shards").unwrap(), Some(3));
+        assert_eq!(
+            settings.get_string("hello.greeting").unwrap(),
+            Some("explicit".to_string())
+        );
+        assert_eq!(settings.get("hello.stale").unwrap(), None);
+        assert!(watcher.reload().unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
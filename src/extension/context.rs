@@ -1,18 +1,29 @@
-use std::sync::Arc;
+use crate::extension::diagnostics::Diagnostics;
+use crate::extension::events::EventBus;
+use crate::extension::lifecycle::LifecycleManager;
+use crate::extension::logging::LoggingControl;
+use crate::extension::service_account::ServiceAccount;
+use crate::extension::setting::{Setting, SettingKind, UpdateConsumer};
+use crate::extension::units::{ByteSizeValue, TimeValue};
+use crate::extension::{ExtensionError, NegotiatedFeatures};
+use crate::transport::TransportClient;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, PoisonError};
 use tokio::runtime::Runtime;
 use tracing::Level;
-use crate::transport::TransportClient;
-use crate::extension::ExtensionError;
-use std::collections::HashMap;
 
 pub type Logger = tracing::Span;
 
 #[derive(Clone)]
 pub struct Settings {
     values: Arc<std::sync::RwLock<HashMap<String, SettingValue>>>,
+    consumers: Arc<std::sync::RwLock<Vec<UpdateConsumer>>>,
+    secure_keys: Arc<std::sync::RwLock<BTreeSet<String>>>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
 pub enum SettingValue {
     String(String),
     Integer(i64),
@@ -20,28 +31,34 @@ pub enum SettingValue {
     Boolean(bool),
     List(Vec<SettingValue>),
     Map(HashMap<String, SettingValue>),
+    ByteSize(ByteSizeValue),
+    Time(TimeValue),
 }
 
 impl Settings {
     pub fn new() -> Self {
         Settings {
             values: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            consumers: Arc::new(std::sync::RwLock::new(Vec::new())),
+            secure_keys: Arc::new(std::sync::RwLock::new(BTreeSet::new())),
         }
     }
-    
-    pub fn set(&self, key: impl Into<String>, value: impl Into<SettingValue>) -> Result<(), ExtensionError> {
-        let mut values = self.values.write()
-            .map_err(|_| ExtensionError::configuration("Settings lock poisoned"))?;
+
+    pub fn set(
+        &self,
+        key: impl Into<String>,
+        value: impl Into<SettingValue>,
+    ) -> Result<(), ExtensionError> {
+        let mut values = self.values.write().unwrap_or_else(PoisonError::into_inner);
         values.insert(key.into(), value.into());
         Ok(())
     }
-    
+
     pub fn get(&self, key: &str) -> Result<Option<SettingValue>, ExtensionError> {
-        let values = self.values.read()
-            .map_err(|_| ExtensionError::configuration("Settings lock poisoned"))?;
+        let values = self.values.read().unwrap_or_else(PoisonError::into_inner);
         Ok(values.get(key).cloned())
     }
-    
+
     pub fn get_string(&self, key: &str) -> Result<Option<String>, ExtensionError> {
         match self.get(key)? {
             Some(SettingValue::String(s)) => Ok(Some(s)),
@@ -49,7 +66,7 @@ impl Settings {
             None => Ok(None),
         }
     }
-    
+
     pub fn get_integer(&self, key: &str) -> Result<Option<i64>, ExtensionError> {
         match self.get(key)? {
             Some(SettingValue::Integer(i)) => Ok(Some(i)),
@@ -57,7 +74,7 @@ impl Settings {
             None => Ok(None),
         }
     }
-    
+
     pub fn get_float(&self, key: &str) -> Result<Option<f64>, ExtensionError> {
         match self.get(key)? {
             Some(SettingValue::Float(f)) => Ok(Some(f)),
@@ -65,7 +82,7 @@ impl Settings {
             None => Ok(None),
         }
     }
-    
+
     pub fn get_boolean(&self, key: &str) -> Result<Option<bool>, ExtensionError> {
         match self.get(key)? {
             Some(SettingValue::Boolean(b)) => Ok(Some(b)),
@@ -73,17 +90,166 @@ impl Settings {
             None => Ok(None),
         }
     }
-    
+
+    /// Byte sizes may also be stored as strings such as `"512mb"`.
+    pub fn get_byte_size(&self, key: &str) -> Result<Option<ByteSizeValue>, ExtensionError> {
+        match self.get(key)? {
+            Some(SettingValue::ByteSize(b)) => Ok(Some(b)),
+            Some(SettingValue::String(s)) => s.parse().map(Some),
+            Some(_) => Ok(None),
+            None => Ok(None),
+        }
+    }
+
+    /// Time values may also be stored as strings such as `"30s"`.
+    pub fn get_time(&self, key: &str) -> Result<Option<TimeValue>, ExtensionError> {
+        match self.get(key)? {
+            Some(SettingValue::Time(t)) => Ok(Some(t)),
+            Some(SettingValue::String(s)) => s.parse().map(Some),
+            Some(_) => Ok(None),
+            None => Ok(None),
+        }
+    }
+
+    /// All keys, sorted.
+    pub fn keys(&self) -> Result<Vec<String>, ExtensionError> {
+        let values = self.values.read().unwrap_or_else(PoisonError::into_inner);
+        let keys: BTreeSet<String> = values.keys().cloned().collect();
+        Ok(keys.into_iter().collect())
+    }
+
+    /// The settings under `prefix.`, with the prefix removed.
+    pub fn get_by_prefix(&self, prefix: &str) -> Result<Settings, ExtensionError> {
+        let prefix = format!("{}.", prefix.trim_end_matches('.'));
+        let values = self.values.read().unwrap_or_else(PoisonError::into_inner);
+        let settings = Settings::new();
+        for (key, value) in values.iter() {
+            if let Some(rest) = key.strip_prefix(&prefix).filter(|rest| !rest.is_empty()) {
+                settings.set(rest, value.clone())?;
+            }
+        }
+        Ok(settings)
+    }
+
+    /// The groups under `prefix` by name, so `myext.targets.east.url` is
+    /// `url` in the `east` group of `get_group("myext.targets")`.
+    pub fn get_group(&self, prefix: &str) -> Result<BTreeMap<String, Settings>, ExtensionError> {
+        let nested = self.get_by_prefix(prefix)?;
+        let mut groups = BTreeMap::new();
+        for key in nested.keys()? {
+            if let Some((name, _)) = key.split_once('.') {
+                if !groups.contains_key(name) {
+                    groups.insert(name.to_string(), nested.get_by_prefix(name)?);
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Call `consumer` with the new value whenever the dynamic `setting` is
+    /// changed by `apply_updates`.
+    pub fn add_update_consumer<T: SettingKind>(
+        &self,
+        setting: &Setting<T>,
+        consumer: impl Fn(T) + Send + Sync + 'static,
+    ) -> Result<(), ExtensionError> {
+        let consumer = UpdateConsumer::new(setting, consumer)?;
+        self.consumers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(consumer);
+        Ok(())
+    }
+
+    /// Keys of the settings with update consumers.
+    pub fn update_consumer_keys(&self) -> Result<Vec<String>, ExtensionError> {
+        let consumers = self
+            .consumers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let keys: BTreeSet<String> = consumers.iter().map(|c| c.key().to_string()).collect();
+        Ok(keys.into_iter().collect())
+    }
+
+    /// Apply changed values, `None` removing a setting, and notify their
+    /// update consumers. If any consumer's setting rejects its new value,
+    /// nothing is applied. Returns the keys that actually changed.
+    pub fn apply_updates(
+        &self,
+        updates: impl IntoIterator<Item = (String, Option<SettingValue>)>,
+    ) -> Result<Vec<String>, ExtensionError> {
+        let consumers = self
+            .consumers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut values = self.values.write().unwrap_or_else(PoisonError::into_inner);
+
+        let changed: Vec<(String, Option<SettingValue>)> = updates
+            .into_iter()
+            .filter(|(key, value)| values.get(key) != value.as_ref())
+            .collect();
+        for (key, value) in &changed {
+            for consumer in consumers.iter().filter(|c| c.key() == key) {
+                consumer.accepts(value.as_ref())?;
+            }
+        }
+        for (key, value) in &changed {
+            match value {
+                Some(value) => values.insert(key.clone(), value.clone()),
+                None => values.remove(key),
+            };
+        }
+        drop(values);
+
+        for (key, _) in &changed {
+            for consumer in consumers.iter().filter(|c| c.key() == key) {
+                consumer.notify(self);
+            }
+        }
+        Ok(changed.into_iter().map(|(key, _)| key).collect())
+    }
+
     pub fn merge(&mut self, other: &Settings) -> Result<(), ExtensionError> {
-        let mut values = self.values.write()
-            .map_err(|_| ExtensionError::configuration("Settings lock poisoned"))?;
-        let other_values = other.values.read()
-            .map_err(|_| ExtensionError::configuration("Settings lock poisoned"))?;
+        let mut values = self.values.write().unwrap_or_else(PoisonError::into_inner);
+        let other_values = other.values.read().unwrap_or_else(PoisonError::into_inner);
         for (key, value) in other_values.iter() {
             values.insert(key.clone(), value.clone());
         }
+        for key in other.secure_keys()? {
+            self.mark_secure(&key)?;
+        }
         Ok(())
     }
+
+    /// Remember that `key` holds a secret, so diagnostics redact it.
+    /// `Setting::secure` marks its key on first read.
+    pub fn mark_secure(&self, key: &str) -> Result<(), ExtensionError> {
+        let mut secure_keys = self
+            .secure_keys
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !secure_keys.contains(key) {
+            secure_keys.insert(key.to_string());
+        }
+        Ok(())
+    }
+
+    pub fn is_secure(&self, key: &str) -> Result<bool, ExtensionError> {
+        let secure_keys = self
+            .secure_keys
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(secure_keys.contains(key))
+    }
+
+    fn secure_keys(&self) -> Result<Vec<String>, ExtensionError> {
+        let secure_keys = self
+            .secure_keys
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(secure_keys.iter().cloned().collect())
+    }
 }
 
 impl Default for Settings {
@@ -134,11 +300,35 @@ impl From<bool> for SettingValue {
     }
 }
 
+impl From<ByteSizeValue> for SettingValue {
+    fn from(value: ByteSizeValue) -> Self {
+        SettingValue::ByteSize(value)
+    }
+}
+
+impl From<TimeValue> for SettingValue {
+    fn from(value: TimeValue) -> Self {
+        SettingValue::Time(value)
+    }
+}
+
 pub struct ExtensionContext {
     pub settings: Settings,
     pub transport_client: Arc<TransportClient>,
     pub thread_pool: Arc<Runtime>,
     pub logger: Logger,
+    /// Features agreed with OpenSearch during init. Check these before using
+    /// an optional protocol capability.
+    pub features: NegotiatedFeatures,
+    /// Credentials OpenSearch issued to the extension at registration. The
+    /// same account as the transport client's, if it has one.
+    pub service_account: Arc<ServiceAccount>,
+    /// SDK events such as lifecycle transitions and connection errors.
+    pub events: EventBus,
+    /// State history, recent errors and other support information.
+    pub diagnostics: Arc<Diagnostics>,
+    /// Lifecycle state of the extension, e.g. to pause and resume it.
+    pub lifecycle: Arc<LifecycleManager>,
 }
 
 impl ExtensionContext {
@@ -148,24 +338,39 @@ impl ExtensionContext {
         thread_pool: Arc<Runtime>,
     ) -> Self {
         let logger = tracing::span!(Level::INFO, "extension");
-        
+        let service_account = transport_client
+            .service_account()
+            .cloned()
+            .unwrap_or_default();
+
         ExtensionContext {
             settings,
             transport_client,
             thread_pool,
             logger,
+            features: NegotiatedFeatures::default(),
+            service_account,
+            events: EventBus::new(),
+            diagnostics: Arc::new(Diagnostics::new()),
+            lifecycle: Arc::new(LifecycleManager::new()),
         }
     }
-    
+
     pub fn builder() -> ExtensionContextBuilder {
         ExtensionContextBuilder::new()
     }
+
+    /// Log levels of the extension, changeable while it runs.
+    pub fn logging(&self) -> LoggingControl {
+        LoggingControl::global()
+    }
 }
 
 pub struct ExtensionContextBuilder {
     settings: Settings,
     transport_client: Option<Arc<TransportClient>>,
     thread_pool: Option<Arc<Runtime>>,
+    features: Option<NegotiatedFeatures>,
 }
 
 impl ExtensionContextBuilder {
@@ -174,46 +379,52 @@ impl ExtensionContextBuilder {
             settings: Settings::new(),
             transport_client: None,
             thread_pool: None,
+            features: None,
         }
     }
-    
+
     pub fn settings(mut self, settings: Settings) -> Self {
         self.settings = settings;
         self
     }
-    
+
     pub fn transport_client(mut self, client: Arc<TransportClient>) -> Self {
         self.transport_client = Some(client);
         self
     }
-    
+
     pub fn thread_pool(mut self, pool: Arc<Runtime>) -> Self {
         self.thread_pool = Some(pool);
         self
     }
-    
+
+    /// Features to offer OpenSearch instead of `features::SDK_FEATURES`.
+    pub fn supported_features(mut self, features: Vec<String>) -> Self {
+        self.features = Some(NegotiatedFeatures::new(features));
+        self
+    }
+
     pub fn build(self) -> Result<ExtensionContext, ExtensionError> {
-        let transport_client = self.transport_client
+        let transport_client = self
+            .transport_client
             .ok_or_else(|| ExtensionError::configuration("Transport client is required"))?;
-        
+
         let thread_pool = match self.thread_pool {
             Some(pool) => pool,
-            None => {
-                tokio::runtime::Builder::new_multi_thread()
-                    .enable_all()
-                    .build()
-                    .map(Arc::new)
-                    .map_err(|e| ExtensionError::initialization(
-                        format!("Failed to create thread pool: {}", e)
-                    ))?
-            }
+            None => tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .map(Arc::new)
+                .map_err(|e| {
+                    ExtensionError::initialization(format!("Failed to create thread pool: {}", e))
+                })?,
         };
-        
-        Ok(ExtensionContext::new(
-            self.settings,
-            transport_client,
-            thread_pool,
-        ))
+
+        let mut context = ExtensionContext::new(self.settings, transport_client, thread_pool);
+        if let Some(features) = self.features {
+            context.features = features;
+        }
+        Ok(context)
     }
 }
 
@@ -226,36 +437,49 @@ impl Default for ExtensionContextBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_settings() {
         let settings = Settings::new();
-        
+
         settings.set("test.string", "value").unwrap();
         settings.set("test.integer", 42).unwrap();
         settings.set("test.float", 3.14159).unwrap();
         settings.set("test.boolean", true).unwrap();
-        
-        assert_eq!(settings.get_string("test.string").unwrap(), Some("value".to_string()));
+
+        assert_eq!(
+            settings.get_string("test.string").unwrap(),
+            Some("value".to_string())
+        );
         assert_eq!(settings.get_integer("test.integer").unwrap(), Some(42));
         assert_eq!(settings.get_float("test.float").unwrap(), Some(3.14159));
         assert_eq!(settings.get_boolean("test.boolean").unwrap(), Some(true));
     }
-    
+
     #[test]
     fn test_settings_merge() {
         let mut settings1 = Settings::new();
         settings1.set("key1", "value1").unwrap();
         settings1.set("key2", "value2").unwrap();
-        
+
         let settings2 = Settings::new();
         settings2.set("key2", "updated").unwrap();
         settings2.set("key3", "value3").unwrap();
-        
+
         settings1.merge(&settings2).unwrap();
-        
-        assert_eq!(settings1.get_string("key1").unwrap(), Some("value1".to_string()));
-        assert_eq!(settings1.get_string("key2").unwrap(), Some("updated".to_string()));
-        assert_eq!(settings1.get_string("key3").unwrap(), Some("value3".to_string()));
+
+        assert_eq!(
+            settings1.get_string("key1").unwrap(),
+            Some("value1".to_string())
+        );
+        assert_eq!(
+            settings1.get_string("key2").unwrap(),
+            Some("updated".to_string())
+        );
+        assert_eq!(
+            settings1.get_string("key3").unwrap(),
+            Some("value3".to_string())
+        );
     }
-}
\ No newline at end of file
+}
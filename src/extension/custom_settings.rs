@@ -0,0 +1,310 @@
+use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
+use std::io::{self, Read, Write};
+
+use crate::extension::{context::SettingValue, ExtensionError};
+use crate::interface::{
+    read_bool, read_string, read_task_id, read_vint, write_bool, write_empty_task_id, write_string,
+    write_vint, Deserialize, Serialize,
+};
+use crate::transport::TransportClient;
+
+pub const REGISTER_CUSTOM_SETTINGS_ACTION: &str = "internal:discovery/registercustomsettings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerdeSerialize, SerdeDeserialize)]
+pub enum SettingType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    List,
+    Map,
+    ByteSize,
+    Time,
+}
+
+impl SettingType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettingType::String => "string",
+            SettingType::Integer => "integer",
+            SettingType::Float => "float",
+            SettingType::Boolean => "boolean",
+            SettingType::List => "list",
+            SettingType::Map => "map",
+            SettingType::ByteSize => "byte_size",
+            SettingType::Time => "time",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(SettingType::String),
+            "integer" => Some(SettingType::Integer),
+            "float" => Some(SettingType::Float),
+            "boolean" => Some(SettingType::Boolean),
+            "list" => Some(SettingType::List),
+            "map" => Some(SettingType::Map),
+            "byte_size" => Some(SettingType::ByteSize),
+            "time" => Some(SettingType::Time),
+            _ => None,
+        }
+    }
+
+    pub fn of(value: &SettingValue) -> Self {
+        match value {
+            SettingValue::String(_) => SettingType::String,
+            SettingValue::Integer(_) => SettingType::Integer,
+            SettingValue::Float(_) => SettingType::Float,
+            SettingValue::Boolean(_) => SettingType::Boolean,
+            SettingValue::List(_) => SettingType::List,
+            SettingValue::Map(_) => SettingType::Map,
+            SettingValue::ByteSize(_) => SettingType::ByteSize,
+            SettingValue::Time(_) => SettingType::Time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, SerdeSerialize, SerdeDeserialize)]
+pub enum SettingScope {
+    #[default]
+    Node,
+    Index,
+}
+
+impl SettingScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettingScope::Node => "node",
+            SettingScope::Index => "index",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "node" => Some(SettingScope::Node),
+            "index" => Some(SettingScope::Index),
+            _ => None,
+        }
+    }
+}
+
+/// A setting an extension advertises to the cluster at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomSettingDescriptor {
+    pub key: String,
+    pub setting_type: SettingType,
+    pub default_value: Option<SettingValue>,
+    pub dynamic: bool,
+    pub scope: SettingScope,
+}
+
+impl CustomSettingDescriptor {
+    pub fn new(key: impl Into<String>, setting_type: SettingType) -> Self {
+        CustomSettingDescriptor {
+            key: key.into(),
+            setting_type,
+            default_value: None,
+            dynamic: false,
+            scope: SettingScope::Node,
+        }
+    }
+
+    pub fn with_default(mut self, value: impl Into<SettingValue>) -> Self {
+        self.default_value = Some(value.into());
+        self
+    }
+
+    pub fn dynamic(mut self) -> Self {
+        self.dynamic = true;
+        self
+    }
+
+    pub fn scope(mut self, scope: SettingScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), ExtensionError> {
+        if self.key.is_empty() {
+            return Err(ExtensionError::configuration(
+                "Setting key must not be empty",
+            ));
+        }
+
+        if let Some(default) = &self.default_value {
+            if SettingType::of(default) != self.setting_type {
+                return Err(ExtensionError::configuration(format!(
+                    "Default for setting '{}' is a {}, expected {}",
+                    self.key,
+                    SettingType::of(default).name(),
+                    self.setting_type.name()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for CustomSettingDescriptor {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_string(buf, &self.key)?;
+        written += write_string(buf, self.setting_type.name())?;
+
+        match &self.default_value {
+            Some(value) => {
+                let json = serde_json::to_string(value)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                written += write_bool(buf, true)?;
+                written += write_string(buf, &json)?;
+            }
+            None => written += write_bool(buf, false)?,
+        }
+
+        written += write_bool(buf, self.dynamic)?;
+        written += write_string(buf, self.scope.name())?;
+        Ok(written)
+    }
+}
+
+impl Deserialize for CustomSettingDescriptor {
+    type Output = CustomSettingDescriptor;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let key = read_string(buf)?;
+        let type_name = read_string(buf)?;
+        let setting_type = SettingType::from_name(&type_name)
+            .ok_or_else(|| invalid(format!("Unknown setting type: {}", type_name)))?;
+
+        let default_value = if read_bool(buf)? {
+            let json = read_string(buf)?;
+            Some(serde_json::from_str(&json).map_err(|e| invalid(e.to_string()))?)
+        } else {
+            None
+        };
+
+        let dynamic = read_bool(buf)?;
+        let scope_name = read_string(buf)?;
+        let scope = SettingScope::from_name(&scope_name)
+            .ok_or_else(|| invalid(format!("Unknown setting scope: {}", scope_name)))?;
+
+        Ok(CustomSettingDescriptor {
+            key,
+            setting_type,
+            default_value,
+            dynamic,
+            scope,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterCustomSettingsRequest {
+    pub unique_id: String,
+    pub settings: Vec<CustomSettingDescriptor>,
+}
+
+impl RegisterCustomSettingsRequest {
+    pub fn new(unique_id: impl Into<String>, settings: Vec<CustomSettingDescriptor>) -> Self {
+        RegisterCustomSettingsRequest {
+            unique_id: unique_id.into(),
+            settings,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ExtensionError> {
+        for setting in &self.settings {
+            setting.validate()?;
+        }
+
+        let mut buf = Vec::new();
+        self.serialize(&mut buf).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize custom settings: {}", e))
+        })?;
+        Ok(buf)
+    }
+}
+
+impl Serialize for RegisterCustomSettingsRequest {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_empty_task_id(buf)?;
+        written += write_string(buf, &self.unique_id)?;
+        written += write_vint(buf, self.settings.len() as u32)?;
+        for setting in &self.settings {
+            written += setting.serialize(buf)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Deserialize for RegisterCustomSettingsRequest {
+    type Output = RegisterCustomSettingsRequest;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        read_task_id(buf)?;
+        let unique_id = read_string(buf)?;
+        let count = read_vint(buf)?;
+        let settings = (0..count)
+            .map(|_| CustomSettingDescriptor::deserialize(buf))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(RegisterCustomSettingsRequest {
+            unique_id,
+            settings,
+        })
+    }
+}
+
+pub async fn register_custom_settings(
+    client: &TransportClient,
+    request: &RegisterCustomSettingsRequest,
+) -> Result<(), ExtensionError> {
+    client
+        .expect_ack(REGISTER_CUSTOM_SETTINGS_ACTION, &request.to_bytes()?)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_round_trip() {
+        let request = RegisterCustomSettingsRequest::new(
+            "hello-world-rs",
+            vec![
+                CustomSettingDescriptor::new("hello.greeting", SettingType::String)
+                    .with_default("Hello from Rust!")
+                    .dynamic(),
+                CustomSettingDescriptor::new("hello.shards", SettingType::Integer)
+                    .with_default(2i64)
+                    .scope(SettingScope::Index),
+                CustomSettingDescriptor::new("hello.enabled", SettingType::Boolean),
+            ],
+        );
+
+        let bytes = request.to_bytes().unwrap();
+        assert_eq!(&bytes[..16], b"\x00\x0ehello-world-rs");
+        let decoded = RegisterCustomSettingsRequest::deserialize(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(decoded, request);
+        assert!(decoded.settings[0].dynamic);
+        assert_eq!(decoded.settings[1].scope, SettingScope::Index);
+        assert_eq!(decoded.settings[2].default_value, None);
+    }
+
+    #[test]
+    fn test_default_type_mismatch_rejected() {
+        let request = RegisterCustomSettingsRequest::new(
+            "hello-world-rs",
+            vec![
+                CustomSettingDescriptor::new("hello.shards", SettingType::Integer)
+                    .with_default("two"),
+            ],
+        );
+
+        assert!(request.to_bytes().is_err());
+    }
+}
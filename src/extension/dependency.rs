@@ -1,6 +1,17 @@
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::extension::ExtensionError;
+use crate::interface::{
+    self, parse_proto_request, proto_request_bytes, read_string, read_vint, write_string,
+    write_vint,
+};
+use crate::proto::{ExtensionIdentity, ExtensionRequest, RequestType};
+use crate::transport::TransportClient;
+
+pub const EXTENSION_DEPENDENCY_ACTION: &str = "internal:discovery/extension-dependency";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ExtensionDependency {
@@ -15,12 +26,15 @@ impl ExtensionDependency {
             version,
         }
     }
-    
-    pub fn from_str(unique_id: impl Into<String>, version_str: &str) -> Result<Self, semver::Error> {
+
+    pub fn from_str(
+        unique_id: impl Into<String>,
+        version_str: &str,
+    ) -> Result<Self, semver::Error> {
         let version = Version::parse(version_str)?;
         Ok(Self::new(unique_id, version))
     }
-    
+
     pub fn satisfies(&self, other_version: &Version) -> bool {
         // A dependency is satisfied if the provided version is >= the required version
         *other_version >= self.version
@@ -33,6 +47,118 @@ impl fmt::Display for ExtensionDependency {
     }
 }
 
+/// Dependency information one extension reports about itself over
+/// `internal:discovery/extension-dependency`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionDependencyResponse {
+    pub unique_id: String,
+    pub version: Version,
+    pub dependencies: Vec<ExtensionDependency>,
+}
+
+impl ExtensionDependencyResponse {
+    pub fn new(
+        unique_id: impl Into<String>,
+        version: Version,
+        dependencies: Vec<ExtensionDependency>,
+    ) -> Self {
+        ExtensionDependencyResponse {
+            unique_id: unique_id.into(),
+            version,
+            dependencies,
+        }
+    }
+}
+
+fn read_version(buf: &mut impl Read) -> io::Result<Version> {
+    let version = read_string(buf)?;
+    Version::parse(&version).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl interface::Serialize for ExtensionDependencyResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_string(buf, &self.unique_id)?;
+        written += write_string(buf, &self.version.to_string())?;
+        written += write_vint(buf, self.dependencies.len() as u32)?;
+        for dependency in &self.dependencies {
+            written += write_string(buf, &dependency.unique_id)?;
+            written += write_string(buf, &dependency.version.to_string())?;
+        }
+        Ok(written)
+    }
+}
+
+impl interface::Deserialize for ExtensionDependencyResponse {
+    type Output = ExtensionDependencyResponse;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let unique_id = read_string(buf)?;
+        let version = read_version(buf)?;
+        let count = read_vint(buf)?;
+        let mut dependencies = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let dependency_id = read_string(buf)?;
+            dependencies.push(ExtensionDependency::new(dependency_id, read_version(buf)?));
+        }
+
+        Ok(ExtensionDependencyResponse {
+            unique_id,
+            version,
+            dependencies,
+        })
+    }
+}
+
+/// Build the request asking OpenSearch for the dependency information of `unique_id`.
+pub fn dependency_request_bytes(unique_id: &str) -> Vec<u8> {
+    proto_request_bytes(&ExtensionRequest {
+        identity: Some(ExtensionIdentity {
+            unique_id: unique_id.to_string(),
+        }),
+        request_type: RequestType::RequestExtensionDependencyInformation as i32,
+    })
+}
+
+/// Decode a dependency request, returning the unique ID it asks about.
+pub fn parse_dependency_request(bytes: &[u8]) -> Result<String, ExtensionError> {
+    let request: ExtensionRequest = parse_proto_request(bytes).map_err(|e| {
+        ExtensionError::serialization(format!("Failed to decode dependency request: {}", e))
+    })?;
+
+    if request.request_type() != RequestType::RequestExtensionDependencyInformation {
+        return Err(ExtensionError::protocol(format!(
+            "Expected a dependency information request, got {}",
+            request.request_type().as_str_name()
+        )));
+    }
+
+    Ok(request
+        .identity
+        .map(|identity| identity.unique_id)
+        .unwrap_or_default())
+}
+
+pub async fn request_dependency_info(
+    client: &TransportClient,
+    unique_id: &str,
+) -> Result<ExtensionDependencyResponse, ExtensionError> {
+    use crate::interface::Deserialize;
+
+    let response = client
+        .send_request(
+            EXTENSION_DEPENDENCY_ACTION,
+            &dependency_request_bytes(unique_id),
+        )
+        .await?;
+
+    ExtensionDependencyResponse::deserialize(&mut response.as_slice()).map_err(|e| {
+        ExtensionError::serialization(format!(
+            "Failed to deserialize dependency information for {}: {}",
+            unique_id, e
+        ))
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct DependencyResolver {
     extensions: Vec<ExtensionInfo>,
@@ -51,7 +177,7 @@ impl DependencyResolver {
             extensions: Vec::new(),
         }
     }
-    
+
     pub fn add_extension(
         &mut self,
         unique_id: impl Into<String>,
@@ -64,18 +190,26 @@ impl DependencyResolver {
             dependencies,
         });
     }
-    
+
+    pub fn add_dependency_info(&mut self, info: &ExtensionDependencyResponse) {
+        self.add_extension(
+            info.unique_id.clone(),
+            info.version.clone(),
+            info.dependencies.clone(),
+        );
+    }
+
     pub fn resolve(&self) -> Result<Vec<String>, String> {
         let mut resolved = Vec::new();
         let mut visited = std::collections::HashSet::new();
-        
+
         for ext in &self.extensions {
             self.resolve_extension(&ext.unique_id, &mut resolved, &mut visited)?;
         }
-        
+
         Ok(resolved)
     }
-    
+
     fn resolve_extension(
         &self,
         unique_id: &str,
@@ -85,14 +219,21 @@ impl DependencyResolver {
         if resolved.contains(&unique_id.to_string()) {
             return Ok(());
         }
-        
+
         if !visited.insert(unique_id.to_string()) {
-            return Err(format!("Circular dependency detected for extension: {}", unique_id));
+            return Err(format!(
+                "Circular dependency detected for extension: {}",
+                unique_id
+            ));
         }
-        
+
         if let Some(ext) = self.extensions.iter().find(|e| e.unique_id == unique_id) {
             for dep in &ext.dependencies {
-                if let Some(dep_ext) = self.extensions.iter().find(|e| e.unique_id == dep.unique_id) {
+                if let Some(dep_ext) = self
+                    .extensions
+                    .iter()
+                    .find(|e| e.unique_id == dep.unique_id)
+                {
                     if !dep.satisfies(&dep_ext.version) {
                         return Err(format!(
                             "Dependency version mismatch: {} requires {} {}, but found {}",
@@ -101,12 +242,15 @@ impl DependencyResolver {
                     }
                     self.resolve_extension(&dep.unique_id, resolved, visited)?;
                 } else {
-                    return Err(format!("Missing dependency: {} requires {}", unique_id, dep.unique_id));
+                    return Err(format!(
+                        "Missing dependency: {} requires {}",
+                        unique_id, dep.unique_id
+                    ));
                 }
             }
             resolved.push(unique_id.to_string());
         }
-        
+
         visited.remove(unique_id);
         Ok(())
     }
@@ -121,14 +265,14 @@ impl Default for DependencyResolver {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn test_dependency_creation() {
         let dep = ExtensionDependency::from_str("test-ext", "1.0.0").unwrap();
         assert_eq!(dep.unique_id, "test-ext");
         assert_eq!(dep.version, Version::new(1, 0, 0));
     }
-    
+
     #[test]
     fn test_dependency_satisfies() {
         let dep = ExtensionDependency::from_str("test-ext", "1.0.0").unwrap();
@@ -138,11 +282,11 @@ mod tests {
         assert!(dep.satisfies(&Version::new(2, 0, 0))); // Higher major version satisfies
         assert!(!dep.satisfies(&Version::new(0, 9, 0))); // Lower version does not satisfy
     }
-    
+
     #[test]
     fn test_dependency_resolver() {
         let mut resolver = DependencyResolver::new();
-        
+
         resolver.add_extension("ext-a", Version::new(1, 0, 0), vec![]);
         resolver.add_extension(
             "ext-b",
@@ -157,15 +301,54 @@ mod tests {
                 ExtensionDependency::from_str("ext-b", "1.0.0").unwrap(),
             ],
         );
-        
+
         let resolved = resolver.resolve().unwrap();
         assert_eq!(resolved, vec!["ext-a", "ext-b", "ext-c"]);
     }
-    
+
+    #[test]
+    fn test_dependency_response_round_trip() {
+        use crate::interface::{Deserialize, Serialize};
+
+        let response = ExtensionDependencyResponse::new(
+            "ext-b",
+            Version::new(1, 2, 0),
+            vec![ExtensionDependency::from_str("ext-a", "1.0.0").unwrap()],
+        );
+
+        let mut bytes = Vec::new();
+        response.serialize(&mut bytes).unwrap();
+        let decoded = ExtensionDependencyResponse::deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, response);
+
+        assert_eq!(
+            parse_dependency_request(&dependency_request_bytes("ext-b")).unwrap(),
+            "ext-b"
+        );
+    }
+
+    #[test]
+    fn test_resolver_with_dependency_info() {
+        let mut resolver = DependencyResolver::new();
+        resolver.add_dependency_info(&ExtensionDependencyResponse::new(
+            "ext-a",
+            Version::new(0, 9, 0),
+            vec![],
+        ));
+        resolver.add_extension(
+            "ext-b",
+            Version::new(1, 0, 0),
+            vec![ExtensionDependency::from_str("ext-a", "1.0.0").unwrap()],
+        );
+
+        let result = resolver.resolve();
+        assert!(result.unwrap_err().contains("version mismatch"));
+    }
+
     #[test]
     fn test_circular_dependency_detection() {
         let mut resolver = DependencyResolver::new();
-        
+
         resolver.add_extension(
             "ext-a",
             Version::new(1, 0, 0),
@@ -176,9 +359,9 @@ mod tests {
             Version::new(1, 0, 0),
             vec![ExtensionDependency::from_str("ext-a", "1.0.0").unwrap()],
         );
-        
+
         let result = resolver.resolve();
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Circular dependency"));
     }
-}
\ No newline at end of file
+}
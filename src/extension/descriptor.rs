@@ -0,0 +1,159 @@
+use semver::Version;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::Path;
+
+use crate::extension::{ExtensionError, ExtensionIdentity};
+
+/// Extension descriptor in the `hello.json` format used to register extensions
+/// with OpenSearch, loadable from JSON or YAML.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionDescriptor {
+    pub name: String,
+    pub unique_id: String,
+    pub host_address: String,
+    #[serde(deserialize_with = "deserialize_port")]
+    pub port: u16,
+    pub version: String,
+    pub opensearch_version: String,
+    pub minimum_compatible_version: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PortValue {
+    Number(u16),
+    String(String),
+}
+
+// OpenSearch descriptors carry the port as a string, but accept a number too.
+fn deserialize_port<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    match PortValue::deserialize(deserializer)? {
+        PortValue::Number(port) => Ok(port),
+        PortValue::String(port) => port.trim().parse().map_err(serde::de::Error::custom),
+    }
+}
+
+impl ExtensionDescriptor {
+    pub fn from_json(content: &str) -> Result<Self, ExtensionError> {
+        let descriptor: Self = serde_json::from_str(content).map_err(|e| {
+            ExtensionError::configuration(format!("Invalid extension descriptor: {}", e))
+        })?;
+        descriptor.validate()?;
+        Ok(descriptor)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, ExtensionError> {
+        let descriptor: Self = serde_yaml::from_str(content).map_err(|e| {
+            ExtensionError::configuration(format!("Invalid extension descriptor: {}", e))
+        })?;
+        descriptor.validate()?;
+        Ok(descriptor)
+    }
+
+    /// Load a descriptor, treating `.yml`/`.yaml` files as YAML and anything else as JSON.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ExtensionError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            ExtensionError::configuration(format!(
+                "Failed to read extension descriptor {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yml") | Some("yaml") => Self::from_yaml(&content),
+            _ => Self::from_json(&content),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ExtensionError> {
+        if self.unique_id.is_empty() {
+            return Err(ExtensionError::configuration(
+                "Extension descriptor is missing uniqueId",
+            ));
+        }
+
+        let opensearch_version = parse_version("opensearchVersion", &self.opensearch_version)?;
+        let minimum = parse_version("minimumCompatibleVersion", &self.minimum_compatible_version)?;
+        if minimum > opensearch_version {
+            return Err(ExtensionError::configuration(format!(
+                "minimumCompatibleVersion {} is newer than opensearchVersion {}",
+                minimum, opensearch_version
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Whether an OpenSearch node at `version` can host this extension.
+    pub fn is_compatible_with(&self, version: &Version) -> bool {
+        Version::parse(&self.minimum_compatible_version)
+            .map(|minimum| *version >= minimum)
+            .unwrap_or(false)
+    }
+
+    pub fn identity(&self) -> ExtensionIdentity {
+        ExtensionIdentity {
+            name: self.name.clone(),
+            unique_id: self.unique_id.clone(),
+            version: self.version.clone(),
+            opensearch_version: self.opensearch_version.clone(),
+            java_version: "11".to_string(),
+            description: None,
+            vendor: None,
+            license: None,
+            dependencies: vec![],
+        }
+    }
+}
+
+fn parse_version(field: &str, version: &str) -> Result<Version, ExtensionError> {
+    Version::parse(version).map_err(|e| {
+        ExtensionError::configuration(format!("Invalid {} '{}': {}", field, version, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_JSON: &str = r#"{
+        "name":"Hello World",
+        "uniqueId":"hello-world-rs",
+        "hostAddress":"127.0.0.1",
+        "port":"1234",
+        "version":"0.1.0",
+        "opensearchVersion":"3.0.0",
+        "minimumCompatibleVersion":"3.0.0"
+    }"#;
+
+    #[test]
+    fn test_descriptor_from_json_and_yaml() {
+        let json = ExtensionDescriptor::from_json(HELLO_JSON).unwrap();
+        assert_eq!(json.port, 1234);
+        assert_eq!(json.identity().unique_id, "hello-world-rs");
+        assert!(json.is_compatible_with(&Version::new(3, 1, 0)));
+        assert!(!json.is_compatible_with(&Version::new(2, 19, 0)));
+
+        let yaml = ExtensionDescriptor::from_yaml(
+            "name: Hello World\nuniqueId: hello-world-rs\nhostAddress: 127.0.0.1\nport: 1234\n\
+             version: 0.1.0\nopensearchVersion: 3.0.0\nminimumCompatibleVersion: 3.0.0\n",
+        )
+        .unwrap();
+        assert_eq!(yaml, json);
+    }
+
+    #[test]
+    fn test_descriptor_validation() {
+        let invalid = HELLO_JSON.replace(
+            r#""minimumCompatibleVersion":"3.0.0""#,
+            r#""minimumCompatibleVersion":"4.0.0""#,
+        );
+        assert!(ExtensionDescriptor::from_json(&invalid).is_err());
+
+        let bad_port = HELLO_JSON.replace(r#""port":"1234""#, r#""port":"http""#);
+        assert!(ExtensionDescriptor::from_json(&bad_port).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::events::{EventBus, SdkEvent};
+use crate::extension::lifecycle::{ExtensionState, StateListener};
+use crate::extension::resilience::CircuitState;
+use crate::extension::resources::RuntimeStats;
+use crate::transport::{ConnectionRegistry, ConnectionStats};
+
+/// Transitions and errors kept for the bundle; older ones are dropped.
+const HISTORY_CAPACITY: usize = 100;
+
+/// Setting keys containing one of these have their values redacted.
+const SECRET_MARKERS: &[&str] = &[
+    "password",
+    "secret",
+    "token",
+    "credential",
+    "api_key",
+    "private_key",
+];
+
+const REDACTED: &str = "<redacted>";
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StateTransition {
+    pub from: ExtensionState,
+    pub to: ExtensionState,
+    pub at_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorRecord {
+    pub source: String,
+    pub message: String,
+    pub at_millis: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuntimeDiagnostics {
+    pub sdk_version: &'static str,
+    pub pid: u32,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub uptime_seconds: u64,
+    pub tokio: Option<RuntimeStats>,
+}
+
+/// Everything `Diagnostics` knows, as served at `GET /_diag`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiagnosticBundle {
+    pub generated_at_millis: u64,
+    pub state: Option<ExtensionState>,
+    pub state_history: Vec<StateTransition>,
+    pub settings: BTreeMap<String, SettingValue>,
+    pub connections: Vec<ConnectionStats>,
+    pub recent_errors: Vec<ErrorRecord>,
+    /// Panics caught per handler since start.
+    pub handler_panics: BTreeMap<String, u64>,
+    pub runtime: RuntimeDiagnostics,
+}
+
+/// Collects what support needs to investigate a running extension: state
+/// history, recent errors, connections and runtime details. The runner
+/// feeds it; `bundle` reads it with secret settings redacted.
+pub struct Diagnostics {
+    started: Instant,
+    history: Mutex<VecDeque<StateTransition>>,
+    errors: Mutex<VecDeque<ErrorRecord>>,
+    panics: Mutex<BTreeMap<String, u64>>,
+    connections: RwLock<Option<Arc<ConnectionRegistry>>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics {
+            started: Instant::now(),
+            history: Mutex::new(VecDeque::new()),
+            errors: Mutex::new(VecDeque::new()),
+            panics: Mutex::new(BTreeMap::new()),
+            connections: RwLock::new(None),
+        }
+    }
+
+    /// Report the connections of `registry` in bundles.
+    pub fn attach_connections(&self, registry: Arc<ConnectionRegistry>) {
+        *self
+            .connections
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(registry);
+    }
+
+    pub fn record_transition(&self, from: ExtensionState, to: ExtensionState) {
+        push_bounded(
+            &self.history,
+            StateTransition {
+                from,
+                to,
+                at_millis: now_millis(),
+            },
+        );
+    }
+
+    /// Remember an error from `source`, e.g. `"transport"` or `"registration"`.
+    pub fn record_error(&self, source: impl Into<String>, message: impl Into<String>) {
+        push_bounded(
+            &self.errors,
+            ErrorRecord {
+                source: source.into(),
+                message: message.into(),
+                at_millis: now_millis(),
+            },
+        );
+    }
+
+    pub fn recent_errors(&self) -> Vec<ErrorRecord> {
+        self.errors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Count a panic caught in `handler`, a route or action name, and
+    /// remember it as a `"panic"` error.
+    pub fn record_panic(&self, handler: &str, message: &str) {
+        *self
+            .panics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(handler.to_string())
+            .or_default() += 1;
+        self.record_error("panic", format!("{}: {}", handler, message));
+    }
+
+    pub fn handler_panics(&self) -> BTreeMap<String, u64> {
+        self.panics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Record the error events published on `events` until the returned
+    /// task is aborted or the bus is dropped.
+    pub fn follow(self: Arc<Self>, events: &EventBus) -> JoinHandle<()> {
+        let mut subscription = events.subscribe();
+        tokio::spawn(async move {
+            while let Some(event) = subscription.recv().await {
+                match event {
+                    SdkEvent::ConnectionError { peer, error } => {
+                        self.record_error("connection", format!("{}: {}", peer, error))
+                    }
+                    SdkEvent::RegistrationFailed { error } => {
+                        self.record_error("registration", error)
+                    }
+                    SdkEvent::CircuitStateChanged {
+                        breaker,
+                        to: CircuitState::Open,
+                        ..
+                    } => self.record_error(
+                        "circuit_breaker",
+                        format!("circuit breaker [{}] opened", breaker),
+                    ),
+                    _ => {}
+                }
+            }
+        })
+    }
+
+    pub fn bundle(&self, settings: &Settings) -> DiagnosticBundle {
+        let state_history: Vec<StateTransition> = self
+            .history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect();
+        let connections = self
+            .connections
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map(|registry| registry.connections())
+            .unwrap_or_default();
+        DiagnosticBundle {
+            generated_at_millis: now_millis(),
+            state: state_history.last().map(|transition| transition.to),
+            state_history,
+            settings: redacted_settings(settings),
+            connections,
+            recent_errors: self.recent_errors(),
+            handler_panics: self.handler_panics(),
+            runtime: RuntimeDiagnostics {
+                sdk_version: env!("CARGO_PKG_VERSION"),
+                pid: std::process::id(),
+                os: std::env::consts::OS,
+                arch: std::env::consts::ARCH,
+                uptime_seconds: self.started.elapsed().as_secs(),
+                tokio: RuntimeStats::current(),
+            },
+        }
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn push_bounded<T>(buffer: &Mutex<VecDeque<T>>, item: T) {
+    let mut buffer = buffer.lock().unwrap_or_else(PoisonError::into_inner);
+    if buffer.len() == HISTORY_CAPACITY {
+        buffer.pop_front();
+    }
+    buffer.push_back(item);
+}
+
+/// Keys read through a `Setting::secure`, or named like a secret.
+fn is_secret(settings: &Settings, key: &str) -> bool {
+    let lowercase = key.to_ascii_lowercase();
+    settings.is_secure(key).unwrap_or(true)
+        || SECRET_MARKERS
+            .iter()
+            .any(|marker| lowercase.contains(marker))
+}
+
+fn redacted_settings(settings: &Settings) -> BTreeMap<String, SettingValue> {
+    settings
+        .keys()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|key| {
+            let value = settings.get(&key).ok()??;
+            let value = if is_secret(settings, &key) {
+                SettingValue::String(REDACTED.to_string())
+            } else {
+                value
+            };
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// Records lifecycle transitions in `Diagnostics`.
+pub struct DiagnosticsStateListener(pub Arc<Diagnostics>);
+
+#[async_trait::async_trait]
+impl StateListener for DiagnosticsStateListener {
+    async fn on_state_change(&self, old_state: ExtensionState, new_state: ExtensionState) {
+        self.0.record_transition(old_state, new_state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bundle_redacts_secrets_and_keeps_recent_history() {
+        let diagnostics = Arc::new(Diagnostics::new());
+        let events = EventBus::new();
+        let follower = diagnostics.clone().follow(&events);
+        events.publish(SdkEvent::RegistrationFailed {
+            error: "connection refused".to_string(),
+        });
+
+        diagnostics.record_transition(ExtensionState::Created, ExtensionState::Initializing);
+        for i in 0..150 {
+            diagnostics.record_error("transport", format!("error {}", i));
+        }
+        let registry = Arc::new(ConnectionRegistry::new());
+        let _connection = registry.open("127.0.0.1:9300".parse().unwrap());
+        diagnostics.attach_connections(registry);
+
+        let settings = Settings::new();
+        settings
+            .set("extension.signing.secret", "0123456789abcdef")
+            .unwrap();
+        settings.set("extension.metrics.port", 9600).unwrap();
+        settings.set("myext.hmac_key", "inline").unwrap();
+        settings.mark_secure("myext.hmac_key").unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while !diagnostics
+                .recent_errors()
+                .iter()
+                .any(|e| e.source == "registration")
+            {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        follower.abort();
+
+        let bundle = diagnostics.bundle(&settings);
+        assert_eq!(bundle.state, Some(ExtensionState::Initializing));
+        assert_eq!(
+            bundle.settings["extension.signing.secret"],
+            SettingValue::String(REDACTED.to_string())
+        );
+        assert_eq!(
+            bundle.settings["extension.metrics.port"],
+            SettingValue::Integer(9600)
+        );
+        assert_eq!(
+            bundle.settings["myext.hmac_key"],
+            SettingValue::String(REDACTED.to_string())
+        );
+        assert_eq!(bundle.connections.len(), 1);
+        assert_eq!(bundle.recent_errors.len(), HISTORY_CAPACITY);
+        assert_eq!(bundle.recent_errors.last().unwrap().source, "registration");
+
+        let json = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(json["state_history"][0]["to"], "initializing");
+        assert!(!json.to_string().contains("0123456789abcdef"));
+    }
+}
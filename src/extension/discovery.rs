@@ -1,8 +1,42 @@
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use crate::extension::discovery_store::{
+    snapshot_bytes, DiscoveryStore, PendingWrite, StoreEntry, StoreState, StoreView,
+};
+use crate::extension::{
+    registration::{Capability, ExtensionCapabilities, ExtensionRegistration},
+    ExtensionError,
+};
+use crate::transport::action::TransportAction;
 use serde::{Deserialize, Serialize};
-use crate::extension::{ExtensionError, registration::ExtensionRegistration};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, TryRecvError},
+};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+/// Action a `DiscoveryClient` heartbeats on.
+pub const DISCOVERY_HEARTBEAT_ACTION: &str = "internal:discovery/heartbeat";
+
+/// Action a `DiscoveryClient` queries for one extension or by capability.
+pub const DISCOVERY_QUERY_ACTION: &str = "internal:discovery/query";
+
+/// Action a `DiscoveryClient` lists extensions on, whole, by page or by delta.
+pub const DISCOVERY_LIST_ACTION: &str = "internal:discovery/list";
+
+/// Action discovery services exchange `RegistryDigest`s on.
+pub const DISCOVERY_GOSSIP_ACTION: &str = "internal:discovery/gossip";
+
+/// How long an unregistration is remembered and gossiped.
+const TOMBSTONE_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Largest page `DiscoveryService::list_page` returns.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Changes a `DiscoveryService` remembers for delta sync. Clients further
+/// behind than this get the full registry.
+const MAX_DELTA_CHANGES: usize = 1024;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveredExtension {
@@ -19,9 +53,224 @@ pub enum ExtensionStatus {
     Unknown,
 }
 
+/// Predicates over `ExtensionCapabilities`, as sent in a query. An
+/// extension matches when it supports every capability in `all_of`, at
+/// least one in `any_of` if that is not empty, and none in `none_of`.
+/// Only active extensions match unless `include_inactive` is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapabilityQuery {
+    pub all_of: Vec<Capability>,
+    pub any_of: Vec<Capability>,
+    pub none_of: Vec<Capability>,
+    pub include_inactive: bool,
+}
+
+impl CapabilityQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn require(mut self, capability: Capability) -> Self {
+        self.all_of.push(capability);
+        self
+    }
+
+    pub fn any_of(mut self, capabilities: impl IntoIterator<Item = Capability>) -> Self {
+        self.any_of.extend(capabilities);
+        self
+    }
+
+    pub fn exclude(mut self, capability: Capability) -> Self {
+        self.none_of.push(capability);
+        self
+    }
+
+    pub fn include_inactive(mut self) -> Self {
+        self.include_inactive = true;
+        self
+    }
+
+    pub fn matches_capabilities(&self, capabilities: &ExtensionCapabilities) -> bool {
+        self.all_of.iter().all(|c| capabilities.supports(*c))
+            && (self.any_of.is_empty() || self.any_of.iter().any(|c| capabilities.supports(*c)))
+            && !self.none_of.iter().any(|c| capabilities.supports(*c))
+    }
+
+    pub fn matches(&self, extension: &DiscoveredExtension) -> bool {
+        (self.include_inactive || extension.status == ExtensionStatus::Active)
+            && self.matches_capabilities(&extension.registration.capabilities)
+    }
+}
+
+/// Payload of `DISCOVERY_QUERY_ACTION`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum DiscoveryQuery {
+    Extension { unique_id: String },
+    Capabilities { capabilities: CapabilityQuery },
+}
+
+/// Payload of `DISCOVERY_LIST_ACTION`. An empty payload lists everything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum DiscoveryListRequest {
+    Page { page: usize, size: usize },
+    Delta { since: Option<String> },
+}
+
+/// One page of the registry, ordered by unique id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryPage {
+    pub extensions: Vec<DiscoveredExtension>,
+    pub page: usize,
+    pub size: usize,
+    pub total: usize,
+    /// Version of the registry the page was read at.
+    pub etag: String,
+}
+
+impl DiscoveryPage {
+    pub fn has_more(&self) -> bool {
+        (self.page + 1) * self.size < self.total
+    }
+}
+
+/// What changed since the etag a client last saw. With `full` set the
+/// client's etag was unknown or too old, and `upserted` is the whole
+/// registry, replacing what the client has.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryDelta {
+    pub etag: String,
+    pub full: bool,
+    pub upserted: Vec<DiscoveredExtension>,
+    pub removed: Vec<String>,
+}
+
+/// Unique ids changed at each registry version. Heartbeats that only move
+/// `last_seen` are not versioned, so deltas carry them with the next change.
+struct ChangeLog {
+    epoch: u64,
+    version: u64,
+    changes: VecDeque<(u64, String)>,
+}
+
+impl ChangeLog {
+    fn etag(&self) -> String {
+        format!("{:x}-{}", self.epoch, self.version)
+    }
+
+    /// Unique ids changed after `etag`, or `None` when it cannot be served
+    /// from the log.
+    fn changed_since(&self, etag: &str) -> Option<Vec<&str>> {
+        let (epoch, version) = etag.split_once('-')?;
+        let version: u64 = version.parse().ok()?;
+        if u64::from_str_radix(epoch, 16).ok()? != self.epoch || version > self.version {
+            return None;
+        }
+        let oldest = self.changes.front().map_or(self.version, |(v, _)| v - 1);
+        if version < oldest {
+            return None;
+        }
+        let mut ids: Vec<&str> = self
+            .changes
+            .iter()
+            .filter(|(v, _)| *v > version)
+            .map(|(_, id)| id.as_str())
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        Some(ids)
+    }
+}
+
+/// An unregistration, kept so that peers do not bring the extension back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub unique_id: String,
+    pub removed_at: std::time::SystemTime,
+}
+
+/// Everything a `DiscoveryService` knows, as exchanged by gossip.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistryDigest {
+    pub extensions: Vec<DiscoveredExtension>,
+    pub tombstones: Vec<Tombstone>,
+}
+
+/// A change to the registry of a `DiscoveryService`.
+#[derive(Debug, Clone)]
+pub enum RegistryEvent {
+    Registered {
+        extension: Box<DiscoveredExtension>,
+    },
+    Unregistered {
+        unique_id: String,
+    },
+    StatusChanged {
+        unique_id: String,
+        from: ExtensionStatus,
+        to: ExtensionStatus,
+    },
+    /// Missed heartbeats for long enough to be marked `Inactive`.
+    Stale {
+        unique_id: String,
+    },
+}
+
+/// Registry changes published after `DiscoveryService::watch` was called.
+pub struct RegistryWatch {
+    receiver: broadcast::Receiver<RegistryEvent>,
+}
+
+impl RegistryWatch {
+    /// Wait for the next change. `None` once the service is gone.
+    pub async fn recv(&mut self) -> Option<RegistryEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Registry watcher lagged, missed {} changes", missed)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The next change if one is waiting.
+    pub fn try_recv(&mut self) -> Option<RegistryEvent> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Lagged(missed)) => {
+                    tracing::warn!("Registry watcher lagged, missed {} changes", missed)
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Write `pending` to `store` on a blocking thread, off the runtime's workers.
+async fn write_store(
+    store: &Arc<DiscoveryStore>,
+    pending: PendingWrite,
+) -> Result<(), ExtensionError> {
+    let store = store.clone();
+    tokio::task::spawn_blocking(move || store.write(pending))
+        .await
+        .map_err(|e| {
+            ExtensionError::unknown(format!("Discovery store write did not finish: {}", e))
+        })?
+}
+
 pub struct DiscoveryService {
     extensions: Arc<RwLock<HashMap<String, DiscoveredExtension>>>,
     discovery_interval: std::time::Duration,
+    store: Option<Arc<DiscoveryStore>>,
+    events: broadcast::Sender<RegistryEvent>,
+    changes: Mutex<ChangeLog>,
+    tombstones: Mutex<HashMap<String, std::time::SystemTime>>,
 }
 
 impl DiscoveryService {
@@ -29,9 +278,127 @@ impl DiscoveryService {
         DiscoveryService {
             extensions: Arc::new(RwLock::new(HashMap::new())),
             discovery_interval,
+            store: None,
+            events: broadcast::channel(256).0,
+            changes: Mutex::new(ChangeLog {
+                epoch: rand::random(),
+                version: 0,
+                changes: VecDeque::new(),
+            }),
+            tombstones: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Follow registrations, removals and status changes from now on.
+    pub fn watch(&self) -> RegistryWatch {
+        RegistryWatch {
+            receiver: self.events.subscribe(),
         }
     }
-    
+
+    /// Called with the registry locked for writing, after each change.
+    fn publish(&self, event: RegistryEvent) {
+        let unique_id = match &event {
+            RegistryEvent::Registered { extension } => &extension.registration.identity.unique_id,
+            RegistryEvent::Unregistered { unique_id }
+            | RegistryEvent::StatusChanged { unique_id, .. }
+            | RegistryEvent::Stale { unique_id } => unique_id,
+        };
+        let mut log = self.changes.lock().unwrap_or_else(PoisonError::into_inner);
+        log.version += 1;
+        let version = log.version;
+        log.changes.push_back((version, unique_id.clone()));
+        if log.changes.len() > MAX_DELTA_CHANGES {
+            log.changes.pop_front();
+        }
+        drop(log);
+        let _ = self.events.send(event);
+    }
+
+    /// Keep the registry in `store`, starting from what it holds.
+    pub fn with_store(mut self, store: DiscoveryStore) -> Result<Self, ExtensionError> {
+        let state = store.load()?;
+        self.extensions = Arc::new(RwLock::new(state.extensions));
+        self.tombstones = Mutex::new(state.tombstones);
+        self.store = Some(Arc::new(store));
+        Ok(self)
+    }
+
+    fn tombstones(&self) -> std::sync::MutexGuard<'_, HashMap<String, std::time::SystemTime>> {
+        self.tombstones
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Log `entry` with the registry after it, `extensions`. Callers hold
+    /// the registry lock until this returns, so entries are logged in the
+    /// order they were made. Only `durable` entries wait for the disk.
+    async fn persist(
+        &self,
+        entry: StoreEntry,
+        extensions: &HashMap<String, DiscoveredExtension>,
+        durable: bool,
+    ) -> Result<(), ExtensionError> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let pending = store.prepare(
+            &[entry],
+            StoreView {
+                extensions,
+                tombstones: &self.tombstones(),
+            },
+        )?;
+        write_store(
+            store,
+            if durable {
+                pending
+            } else {
+                pending.without_sync()
+            },
+        )
+        .await
+    }
+
+    async fn persist_put(
+        &self,
+        unique_id: &str,
+        extensions: &HashMap<String, DiscoveredExtension>,
+        durable: bool,
+    ) -> Result<(), ExtensionError> {
+        match extensions.get(unique_id) {
+            Some(extension) => {
+                self.persist(
+                    StoreEntry::Put {
+                        extension: Box::new(extension.clone()),
+                    },
+                    extensions,
+                    durable,
+                )
+                .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Fold the store's log into a fresh snapshot.
+    pub async fn compact_store(&self) -> Result<(), ExtensionError> {
+        let extensions = self.extensions.read().await;
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let snapshot = snapshot_bytes(StoreView {
+            extensions: &extensions,
+            tombstones: &self.tombstones(),
+        })?;
+        let store = store.clone();
+        tokio::task::spawn_blocking(move || store.replace_snapshot(&snapshot))
+            .await
+            .map_err(|e| {
+                ExtensionError::unknown(format!("Discovery store compaction did not finish: {}", e))
+            })?
+    }
+
     pub async fn register_extension(
         &self,
         registration: ExtensionRegistration,
@@ -41,33 +408,245 @@ impl DiscoveryService {
             status: ExtensionStatus::Active,
             last_seen: std::time::SystemTime::now(),
         };
-        
+
+        let unique_id = registration.identity.unique_id.clone();
         let mut extensions = self.extensions.write().await;
-        extensions.insert(registration.identity.unique_id.clone(), discovered);
-        
+        self.tombstones().remove(&unique_id);
+        extensions.insert(unique_id.clone(), discovered.clone());
+        self.persist_put(&unique_id, &extensions, true).await?;
+        self.publish(RegistryEvent::Registered {
+            extension: Box::new(discovered),
+        });
         Ok(())
     }
-    
+
     pub async fn unregister_extension(&self, unique_id: &str) -> Result<(), ExtensionError> {
         let mut extensions = self.extensions.write().await;
-        extensions.remove(unique_id)
-            .ok_or_else(|| ExtensionError::unknown(
-                format!("Extension {} not found", unique_id)
-            ))?;
-        
+        extensions
+            .remove(unique_id)
+            .ok_or_else(|| ExtensionError::unknown(format!("Extension {} not found", unique_id)))?;
+
+        let removed_at = std::time::SystemTime::now();
+        self.tombstones().insert(unique_id.to_string(), removed_at);
+        self.persist(
+            StoreEntry::Remove {
+                unique_id: unique_id.to_string(),
+                removed_at: Some(removed_at),
+            },
+            &extensions,
+            true,
+        )
+        .await?;
+        self.publish(RegistryEvent::Unregistered {
+            unique_id: unique_id.to_string(),
+        });
         Ok(())
     }
-    
+
+    /// The registry and recent unregistrations, for a peer to merge.
+    pub async fn digest(&self) -> Result<RegistryDigest, ExtensionError> {
+        let extensions = self.extensions.read().await;
+        let mut tombstones = self.tombstones();
+        tombstones
+            .retain(|_, removed_at| removed_at.elapsed().map_or(true, |age| age < TOMBSTONE_TTL));
+        Ok(RegistryDigest {
+            extensions: extensions.values().cloned().collect(),
+            tombstones: tombstones
+                .iter()
+                .map(|(unique_id, removed_at)| Tombstone {
+                    unique_id: unique_id.clone(),
+                    removed_at: *removed_at,
+                })
+                .collect(),
+        })
+    }
+
+    /// Merge a peer's digest into the registry. Of two copies of an
+    /// extension the one seen last wins, and a tombstone wins over copies
+    /// last seen before the removal. The changes are worked out on a copy
+    /// and persisted before the registry is updated, so a digest is either
+    /// adopted whole or not at all. Returns how many extensions changed.
+    pub async fn merge(&self, digest: RegistryDigest) -> Result<usize, ExtensionError> {
+        let ids = digest
+            .extensions
+            .iter()
+            .map(|ext| &ext.registration.identity.unique_id);
+        if ids
+            .chain(digest.tombstones.iter().map(|t| &t.unique_id))
+            .any(String::is_empty)
+        {
+            return Err(ExtensionError::invalid_request(
+                "Gossip digest has an entry without a unique id",
+            ));
+        }
+
+        let mut extensions = self.extensions.write().await;
+        let tombstones = self.tombstones().clone();
+        let mut merged = StoreState {
+            extensions: extensions.clone(),
+            tombstones,
+        };
+        let mut entries = Vec::new();
+        let mut events = Vec::new();
+        let mut adopted = 0;
+
+        for tombstone in digest.tombstones {
+            let unique_id = tombstone.unique_id;
+            if merged
+                .tombstones
+                .get(&unique_id)
+                .is_some_and(|removed_at| *removed_at >= tombstone.removed_at)
+            {
+                continue;
+            }
+            if merged
+                .extensions
+                .get(&unique_id)
+                .is_some_and(|ext| ext.last_seen < tombstone.removed_at)
+            {
+                events.push(RegistryEvent::Unregistered {
+                    unique_id: unique_id.clone(),
+                });
+                adopted += 1;
+            }
+            let entry = StoreEntry::Remove {
+                unique_id,
+                removed_at: Some(tombstone.removed_at),
+            };
+            entry.clone().apply(&mut merged);
+            entries.push(entry);
+        }
+
+        for remote in digest.extensions {
+            let unique_id = remote.registration.identity.unique_id.clone();
+            if merged
+                .tombstones
+                .get(&unique_id)
+                .is_some_and(|removed_at| *removed_at >= remote.last_seen)
+            {
+                continue;
+            }
+            let event = match merged.extensions.get(&unique_id) {
+                Some(local) if local.last_seen >= remote.last_seen => continue,
+                Some(local) if local.status != remote.status => {
+                    Some(RegistryEvent::StatusChanged {
+                        unique_id: unique_id.clone(),
+                        from: local.status,
+                        to: remote.status,
+                    })
+                }
+                Some(_) => None,
+                None => Some(RegistryEvent::Registered {
+                    extension: Box::new(remote.clone()),
+                }),
+            };
+            let entry = StoreEntry::Put {
+                extension: Box::new(remote),
+            };
+            entry.clone().apply(&mut merged);
+            entries.push(entry);
+            events.extend(event);
+            adopted += 1;
+        }
+
+        if entries.is_empty() {
+            return Ok(0);
+        }
+        if let Some(store) = &self.store {
+            write_store(store, store.prepare(&entries, merged.view())?).await?;
+        }
+        // Tombstones only change under the registry write lock, still held.
+        *extensions = merged.extensions;
+        *self.tombstones() = merged.tombstones;
+        for event in events {
+            self.publish(event);
+        }
+        Ok(adopted)
+    }
+
     pub async fn get_extension(&self, unique_id: &str) -> Option<DiscoveredExtension> {
         let extensions = self.extensions.read().await;
         extensions.get(unique_id).cloned()
     }
-    
+
     pub async fn list_extensions(&self) -> Vec<DiscoveredExtension> {
         let extensions = self.extensions.read().await;
         extensions.values().cloned().collect()
     }
-    
+
+    /// Extensions, in any status, whose capabilities satisfy `filter`.
+    pub async fn discover_extensions_with(
+        &self,
+        filter: impl Fn(&ExtensionCapabilities) -> bool,
+    ) -> Vec<DiscoveredExtension> {
+        let extensions = self.extensions.read().await;
+        extensions
+            .values()
+            .filter(|ext| filter(&ext.registration.capabilities))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn query_extensions(&self, query: &CapabilityQuery) -> Vec<DiscoveredExtension> {
+        let extensions = self.extensions.read().await;
+        extensions
+            .values()
+            .filter(|ext| query.matches(ext))
+            .cloned()
+            .collect()
+    }
+
+    /// Page `page`, counting from 0, of `size` extensions ordered by unique id.
+    pub async fn list_page(&self, page: usize, size: usize) -> DiscoveryPage {
+        let size = size.clamp(1, MAX_PAGE_SIZE);
+        let extensions = self.extensions.read().await;
+        let mut ids: Vec<&String> = extensions.keys().collect();
+        ids.sort_unstable();
+        DiscoveryPage {
+            extensions: ids
+                .iter()
+                .skip(page.saturating_mul(size))
+                .take(size)
+                .map(|id| extensions[*id].clone())
+                .collect(),
+            page,
+            size,
+            total: ids.len(),
+            etag: self
+                .changes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .etag(),
+        }
+    }
+
+    /// Changes since `etag`, or the whole registry when there is none.
+    pub async fn changes_since(&self, etag: Option<&str>) -> DiscoveryDelta {
+        let extensions = self.extensions.read().await;
+        let log = self.changes.lock().unwrap_or_else(PoisonError::into_inner);
+        match etag.and_then(|etag| log.changed_since(etag)) {
+            Some(ids) => {
+                let (upserted, removed): (Vec<_>, Vec<_>) =
+                    ids.into_iter().partition(|id| extensions.contains_key(*id));
+                DiscoveryDelta {
+                    etag: log.etag(),
+                    full: false,
+                    upserted: upserted
+                        .into_iter()
+                        .map(|id| extensions[id].clone())
+                        .collect(),
+                    removed: removed.into_iter().map(str::to_string).collect(),
+                }
+            }
+            None => DiscoveryDelta {
+                etag: log.etag(),
+                full: true,
+                upserted: extensions.values().cloned().collect(),
+                removed: Vec::new(),
+            },
+        }
+    }
+
     pub async fn list_active_extensions(&self) -> Vec<DiscoveredExtension> {
         let extensions = self.extensions.read().await;
         extensions
@@ -76,99 +655,477 @@ impl DiscoveryService {
             .cloned()
             .collect()
     }
-    
+
     pub async fn update_extension_status(
         &self,
         unique_id: &str,
         status: ExtensionStatus,
     ) -> Result<(), ExtensionError> {
         let mut extensions = self.extensions.write().await;
-        let extension = extensions.get_mut(unique_id)
-            .ok_or_else(|| ExtensionError::unknown(
-                format!("Extension {} not found", unique_id)
-            ))?;
-        
-        extension.status = status;
+        let extension = extensions
+            .get_mut(unique_id)
+            .ok_or_else(|| ExtensionError::unknown(format!("Extension {} not found", unique_id)))?;
+
+        let previous = std::mem::replace(&mut extension.status, status);
         extension.last_seen = std::time::SystemTime::now();
-        
+
+        self.persist_put(unique_id, &extensions, true).await?;
+        if previous != status {
+            self.publish(RegistryEvent::StatusChanged {
+                unique_id: unique_id.to_string(),
+                from: previous,
+                to: status,
+            });
+        }
         Ok(())
     }
-    
+
+    /// Record that `unique_id` is alive, reactivating it if it had gone stale.
     pub async fn heartbeat(&self, unique_id: &str) -> Result<(), ExtensionError> {
         let mut extensions = self.extensions.write().await;
-        let extension = extensions.get_mut(unique_id)
-            .ok_or_else(|| ExtensionError::unknown(
-                format!("Extension {} not found", unique_id)
-            ))?;
-        
+        let extension = extensions
+            .get_mut(unique_id)
+            .ok_or_else(|| ExtensionError::unknown(format!("Extension {} not found", unique_id)))?;
+
         extension.last_seen = std::time::SystemTime::now();
-        
+        let revived = extension.status == ExtensionStatus::Inactive;
+        if revived {
+            extension.status = ExtensionStatus::Active;
+        }
+
+        // A heartbeat that only moves `last_seen` is not worth an fsync.
+        self.persist_put(unique_id, &extensions, revived).await?;
+        if revived {
+            self.publish(RegistryEvent::StatusChanged {
+                unique_id: unique_id.to_string(),
+                from: ExtensionStatus::Inactive,
+                to: ExtensionStatus::Active,
+            });
+        }
         Ok(())
     }
-    
+
+    /// Mark extensions that stopped heartbeating as `Inactive` every
+    /// `interval` until the returned task is aborted.
+    pub fn start_reaper(self: Arc<Self>, interval: std::time::Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let stale = self.check_stale_extensions().await;
+                if !stale.is_empty() {
+                    tracing::debug!("Extensions without recent heartbeats: {:?}", stale);
+                }
+            }
+        })
+    }
+
     pub async fn check_stale_extensions(&self) -> Vec<String> {
         let mut stale_extensions = Vec::new();
         let mut extensions = self.extensions.write().await;
         let now = std::time::SystemTime::now();
-        
+
         for (id, extension) in extensions.iter_mut() {
             if let Ok(elapsed) = now.duration_since(extension.last_seen) {
                 if elapsed > self.discovery_interval * 3 {
+                    if extension.status != ExtensionStatus::Inactive {
+                        self.publish(RegistryEvent::Stale {
+                            unique_id: id.clone(),
+                        });
+                    }
                     extension.status = ExtensionStatus::Inactive;
                     stale_extensions.push(id.clone());
                 }
             }
         }
-        
+        for id in &stale_extensions {
+            if let Err(e) = self.persist_put(id, &extensions, true).await {
+                tracing::warn!("Failed to persist status of stale extension {}: {}", id, e);
+            }
+        }
+
         stale_extensions
     }
 }
 
+/// Serves `DISCOVERY_HEARTBEAT_ACTION` from a `DiscoveryService`.
+pub struct DiscoveryHeartbeatAction {
+    service: Arc<DiscoveryService>,
+}
+
+impl DiscoveryHeartbeatAction {
+    pub fn new(service: Arc<DiscoveryService>) -> Self {
+        DiscoveryHeartbeatAction { service }
+    }
+}
+
+#[async_trait::async_trait]
+impl TransportAction for DiscoveryHeartbeatAction {
+    fn name(&self) -> &str {
+        DISCOVERY_HEARTBEAT_ACTION
+    }
+
+    async fn execute(&self, request: Vec<u8>) -> Result<Vec<u8>, ExtensionError> {
+        let request: serde_json::Value = serde_json::from_slice(&request).map_err(|e| {
+            ExtensionError::serialization(format!("Invalid heartbeat request: {}", e))
+        })?;
+        let unique_id = request["unique_id"].as_str().ok_or_else(|| {
+            ExtensionError::invalid_request("Heartbeat request without unique_id")
+        })?;
+        self.service.heartbeat(unique_id).await?;
+        Ok(br#"{"acknowledged":true}"#.to_vec())
+    }
+}
+
+/// Serves `DISCOVERY_QUERY_ACTION` from a `DiscoveryService`: a
+/// `{"unique_id"}` query answers with that extension or `{"found":false}`,
+/// a `{"capabilities"}` query with the list of matching extensions.
+pub struct DiscoveryQueryAction {
+    service: Arc<DiscoveryService>,
+}
+
+impl DiscoveryQueryAction {
+    pub fn new(service: Arc<DiscoveryService>) -> Self {
+        DiscoveryQueryAction { service }
+    }
+}
+
+#[async_trait::async_trait]
+impl TransportAction for DiscoveryQueryAction {
+    fn name(&self) -> &str {
+        DISCOVERY_QUERY_ACTION
+    }
+
+    async fn execute(&self, request: Vec<u8>) -> Result<Vec<u8>, ExtensionError> {
+        let query: DiscoveryQuery = serde_json::from_slice(&request).map_err(|e| {
+            ExtensionError::invalid_request(format!("Invalid discovery query: {}", e))
+        })?;
+        let response = match query {
+            DiscoveryQuery::Extension { unique_id } => {
+                match self.service.get_extension(&unique_id).await {
+                    Some(extension) => serde_json::to_vec(&extension),
+                    None => serde_json::to_vec(&serde_json::json!({ "found": false })),
+                }
+            }
+            DiscoveryQuery::Capabilities { capabilities } => {
+                serde_json::to_vec(&self.service.query_extensions(&capabilities).await)
+            }
+        };
+        response.map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize discovery response: {}", e))
+        })
+    }
+}
+
+/// Serves `DISCOVERY_LIST_ACTION` from a `DiscoveryService`: the whole
+/// registry for an empty request, a `DiscoveryPage` for `{"page","size"}`
+/// and a `DiscoveryDelta` for `{"since"}`.
+pub struct DiscoveryListAction {
+    service: Arc<DiscoveryService>,
+}
+
+impl DiscoveryListAction {
+    pub fn new(service: Arc<DiscoveryService>) -> Self {
+        DiscoveryListAction { service }
+    }
+}
+
+#[async_trait::async_trait]
+impl TransportAction for DiscoveryListAction {
+    fn name(&self) -> &str {
+        DISCOVERY_LIST_ACTION
+    }
+
+    async fn execute(&self, request: Vec<u8>) -> Result<Vec<u8>, ExtensionError> {
+        let response = if request.is_empty() {
+            serde_json::to_vec(&self.service.list_extensions().await)
+        } else {
+            let request: DiscoveryListRequest = serde_json::from_slice(&request).map_err(|e| {
+                ExtensionError::invalid_request(format!("Invalid discovery list request: {}", e))
+            })?;
+            match request {
+                DiscoveryListRequest::Page { page, size } => {
+                    serde_json::to_vec(&self.service.list_page(page, size).await)
+                }
+                DiscoveryListRequest::Delta { since } => {
+                    serde_json::to_vec(&self.service.changes_since(since.as_deref()).await)
+                }
+            }
+        };
+        response.map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize discovery response: {}", e))
+        })
+    }
+}
+
+/// Registry as of `etag`, kept by a `DiscoveryClient` between syncs.
+#[derive(Default)]
+struct SyncedRegistry {
+    etag: Option<String>,
+    extensions: HashMap<String, DiscoveredExtension>,
+}
+
+/// Serves `DISCOVERY_GOSSIP_ACTION` from a `DiscoveryService`: merges the
+/// peer's digest and answers with the merged registry.
+pub struct DiscoveryGossipAction {
+    service: Arc<DiscoveryService>,
+}
+
+impl DiscoveryGossipAction {
+    pub fn new(service: Arc<DiscoveryService>) -> Self {
+        DiscoveryGossipAction { service }
+    }
+}
+
+#[async_trait::async_trait]
+impl TransportAction for DiscoveryGossipAction {
+    fn name(&self) -> &str {
+        DISCOVERY_GOSSIP_ACTION
+    }
+
+    async fn execute(&self, request: Vec<u8>) -> Result<Vec<u8>, ExtensionError> {
+        let digest: RegistryDigest = serde_json::from_slice(&request).map_err(|e| {
+            ExtensionError::invalid_request(format!("Invalid gossip digest: {}", e))
+        })?;
+        self.service.merge(digest).await?;
+        serde_json::to_vec(&self.service.digest().await?).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize gossip digest: {}", e))
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct DiscoveryClient {
     service_url: String,
+    synced: Arc<Mutex<SyncedRegistry>>,
 }
 
 impl DiscoveryClient {
     pub fn new(service_url: impl Into<String>) -> Self {
         DiscoveryClient {
             service_url: service_url.into(),
+            synced: Arc::default(),
+        }
+    }
+
+    async fn list_request<T: serde::de::DeserializeOwned>(
+        &self,
+        request: &DiscoveryListRequest,
+    ) -> Result<T, ExtensionError> {
+        use crate::transport::TransportClient;
+
+        let (host, port) = self.parse_host_port(&self.service_url)?;
+        let request = serde_json::to_vec(request).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize list request: {}", e))
+        })?;
+        let response = TransportClient::new(host, port)
+            .send_request(DISCOVERY_LIST_ACTION, &request)
+            .await?;
+        serde_json::from_slice(&response).map_err(|e| {
+            ExtensionError::serialization(format!(
+                "Failed to deserialize discovery response: {}",
+                e
+            ))
+        })
+    }
+
+    /// Page `page`, counting from 0, of `size` extensions.
+    pub async fn discover_extensions_page(
+        &self,
+        page: usize,
+        size: usize,
+    ) -> Result<DiscoveryPage, ExtensionError> {
+        self.list_request(&DiscoveryListRequest::Page { page, size })
+            .await
+    }
+
+    /// Bring the local copy of the registry up to date, fetching only what
+    /// changed since the last sync, and return it.
+    pub async fn sync(&self) -> Result<Vec<DiscoveredExtension>, ExtensionError> {
+        let since = self
+            .synced
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .etag
+            .clone();
+        let delta: DiscoveryDelta = self
+            .list_request(&DiscoveryListRequest::Delta { since })
+            .await?;
+
+        let mut synced = self.synced.lock().unwrap_or_else(PoisonError::into_inner);
+        if delta.full {
+            synced.extensions.clear();
+        }
+        for id in &delta.removed {
+            synced.extensions.remove(id);
+        }
+        for extension in delta.upserted {
+            synced
+                .extensions
+                .insert(extension.registration.identity.unique_id.clone(), extension);
+        }
+        synced.etag = Some(delta.etag);
+        Ok(synced.extensions.values().cloned().collect())
+    }
+
+    /// The registry through `sync`, or listed whole from services that do
+    /// not support deltas.
+    async fn synced_extensions(&self) -> Result<Vec<DiscoveredExtension>, ExtensionError> {
+        match self.sync().await {
+            Ok(extensions) => Ok(extensions),
+            Err(e) => {
+                tracing::debug!("Delta sync failed ({}), listing all extensions", e);
+                self.discover_extensions().await
+            }
         }
     }
-    
+
     fn parse_host_port(&self, url: &str) -> Result<(String, u16), ExtensionError> {
         // Simple host:port parsing
         if let Some(colon_pos) = url.rfind(':') {
             let host = &url[..colon_pos];
             let port_str = &url[colon_pos + 1..];
-            let port = port_str.parse::<u16>()
-                .map_err(|_| ExtensionError::configuration(
-                    format!("Invalid port in service URL: {}", port_str)
-                ))?;
+            let port = port_str.parse::<u16>().map_err(|_| {
+                ExtensionError::configuration(format!("Invalid port in service URL: {}", port_str))
+            })?;
             Ok((host.to_string(), port))
         } else {
             // Default to port 9300 if not specified
             Ok((url.to_string(), 9300))
         }
     }
-    
+
+    /// Tell the discovery service that `unique_id` is alive.
+    pub async fn heartbeat(&self, unique_id: &str) -> Result<(), ExtensionError> {
+        use crate::transport::TransportClient;
+
+        let (host, port) = self.parse_host_port(&self.service_url)?;
+        let request =
+            serde_json::to_vec(&serde_json::json!({ "unique_id": unique_id })).map_err(|e| {
+                ExtensionError::serialization(format!("Failed to serialize heartbeat: {}", e))
+            })?;
+        TransportClient::new(host, port)
+            .send_request(DISCOVERY_HEARTBEAT_ACTION, &request)
+            .await?;
+        Ok(())
+    }
+
+    /// Send `digest` to the discovery service and return the service's own,
+    /// merged with it.
+    pub async fn gossip(&self, digest: &RegistryDigest) -> Result<RegistryDigest, ExtensionError> {
+        use crate::transport::TransportClient;
+
+        let (host, port) = self.parse_host_port(&self.service_url)?;
+        let request = serde_json::to_vec(digest).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize gossip digest: {}", e))
+        })?;
+        let response = TransportClient::new(host, port)
+            .send_request(DISCOVERY_GOSSIP_ACTION, &request)
+            .await?;
+        serde_json::from_slice(&response).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to deserialize gossip digest: {}", e))
+        })
+    }
+
+    pub fn service_url(&self) -> &str {
+        &self.service_url
+    }
+
+    /// Heartbeat for `unique_id` every `interval` until the returned task
+    /// is aborted. Failed heartbeats are logged and retried next interval.
+    pub fn start_heartbeat(
+        self,
+        unique_id: impl Into<String>,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        let unique_id = unique_id.into();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.heartbeat(&unique_id).await {
+                    tracing::warn!(
+                        "Heartbeat of {} to {} failed: {}",
+                        unique_id,
+                        self.service_url,
+                        e
+                    );
+                }
+            }
+        })
+    }
+
     pub async fn discover_extensions(&self) -> Result<Vec<DiscoveredExtension>, ExtensionError> {
         use crate::transport::TransportClient;
-        
+
         // Parse host and port from service_url
         let (host, port) = self.parse_host_port(&self.service_url)?;
-        
+
         let client = TransportClient::new(host, port);
-        let response = client
-            .send_request("internal:discovery/list", &[])
-            .await?;
-        
-        serde_json::from_slice(&response)
-            .map_err(|e| ExtensionError::serialization(
-                format!("Failed to deserialize discovery response: {}", e)
+        let response = client.send_request(DISCOVERY_LIST_ACTION, &[]).await?;
+
+        serde_json::from_slice(&response).map_err(|e| {
+            ExtensionError::serialization(format!(
+                "Failed to deserialize discovery response: {}",
+                e
             ))
+        })
+    }
+
+    /// Extensions, in any status, whose capabilities satisfy `filter`.
+    pub async fn discover_extensions_with(
+        &self,
+        filter: impl Fn(&ExtensionCapabilities) -> bool,
+    ) -> Result<Vec<DiscoveredExtension>, ExtensionError> {
+        let extensions = self.synced_extensions().await?;
+        Ok(extensions
+            .into_iter()
+            .filter(|ext| filter(&ext.registration.capabilities))
+            .collect())
+    }
+
+    /// Extensions matching `query`, filtered by the service when it
+    /// supports capability queries and locally otherwise.
+    pub async fn query_extensions(
+        &self,
+        query: &CapabilityQuery,
+    ) -> Result<Vec<DiscoveredExtension>, ExtensionError> {
+        use crate::transport::TransportClient;
+
+        let (host, port) = self.parse_host_port(&self.service_url)?;
+        let request = serde_json::to_vec(&DiscoveryQuery::Capabilities {
+            capabilities: query.clone(),
+        })
+        .map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize query request: {}", e))
+        })?;
+        let direct = TransportClient::new(host, port)
+            .send_request(DISCOVERY_QUERY_ACTION, &request)
+            .await
+            .and_then(|response| {
+                serde_json::from_slice::<Vec<DiscoveredExtension>>(&response).map_err(|e| {
+                    ExtensionError::serialization(format!(
+                        "Failed to deserialize query response: {}",
+                        e
+                    ))
+                })
+            });
+        match direct {
+            Ok(extensions) => Ok(extensions),
+            Err(e) => {
+                tracing::debug!(
+                    "Capability query failed ({}), falling back to list-and-filter",
+                    e
+                );
+                let extensions = self.synced_extensions().await?;
+                Ok(extensions
+                    .into_iter()
+                    .filter(|ext| query.matches(ext))
+                    .collect())
+            }
+        }
     }
-    
+
     pub async fn query_extension(
         &self,
         unique_id: &str,
@@ -180,46 +1137,45 @@ impl DiscoveryClient {
                 // If the direct query fails (e.g., endpoint not available),
                 // fall back to the less efficient list-and-filter approach
                 tracing::debug!(
-                    "Direct query failed ({}), falling back to list-and-filter", 
+                    "Direct query failed ({}), falling back to list-and-filter",
                     e
                 );
                 self.query_extension_fallback(unique_id).await
             }
         }
     }
-    
+
     /// Direct query for a single extension - more efficient
     async fn query_extension_direct(
         &self,
         unique_id: &str,
     ) -> Result<Option<DiscoveredExtension>, ExtensionError> {
         use crate::transport::TransportClient;
-        
+
         // Parse host and port from service_url
         let (host, port) = self.parse_host_port(&self.service_url)?;
-        
+
         let client = TransportClient::new(host, port);
-        
+
         // Create query request for specific extension
         let query_request = serde_json::json!({
             "unique_id": unique_id
         });
-        
-        let request_bytes = serde_json::to_vec(&query_request)
-            .map_err(|e| ExtensionError::serialization(
-                format!("Failed to serialize query request: {}", e)
-            ))?;
-        
+
+        let request_bytes = serde_json::to_vec(&query_request).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize query request: {}", e))
+        })?;
+
         // Use targeted query endpoint
         let response = client
-            .send_request("internal:discovery/query", &request_bytes)
+            .send_request(DISCOVERY_QUERY_ACTION, &request_bytes)
             .await?;
-        
+
         // Handle empty response as None
         if response.is_empty() {
             return Ok(None);
         }
-        
+
         // Try to deserialize as a single extension
         serde_json::from_slice::<DiscoveredExtension>(&response)
             .map(Some)
@@ -229,25 +1185,28 @@ impl DiscoveryClient {
                     if error_response.get("found").and_then(|v| v.as_bool()) == Some(false) {
                         Ok(None)
                     } else {
-                        Err(ExtensionError::serialization(
-                            format!("Unexpected response format: {:?}", error_response)
-                        ))
+                        Err(ExtensionError::serialization(format!(
+                            "Unexpected response format: {:?}",
+                            error_response
+                        )))
                     }
                 } else {
                     Err(ExtensionError::serialization(
-                        "Failed to deserialize query response"
+                        "Failed to deserialize query response",
                     ))
                 }
             })
     }
-    
+
     /// Fallback implementation that fetches all extensions and filters
     async fn query_extension_fallback(
         &self,
         unique_id: &str,
     ) -> Result<Option<DiscoveredExtension>, ExtensionError> {
-        let extensions = self.discover_extensions().await?;
-        Ok(extensions.into_iter().find(|ext| ext.registration.identity.unique_id == unique_id))
+        let extensions = self.synced_extensions().await?;
+        Ok(extensions
+            .into_iter()
+            .find(|ext| ext.registration.identity.unique_id == unique_id))
     }
 }
 
@@ -255,11 +1214,11 @@ impl DiscoveryClient {
 mod tests {
     use super::*;
     use crate::extension::registration::ExtensionIdentity;
-    
+
     #[tokio::test]
     async fn test_discovery_service() {
         let service = DiscoveryService::new(std::time::Duration::from_secs(30));
-        
+
         let identity = ExtensionIdentity {
             name: "test".to_string(),
             unique_id: "test-ext".to_string(),
@@ -271,60 +1230,408 @@ mod tests {
             license: None,
             dependencies: vec![],
         };
-        
-        let registration = ExtensionRegistration::new(
-            identity,
-            "127.0.0.1".to_string(),
-            1234,
-        );
-        
+
+        let registration = ExtensionRegistration::new(identity, "127.0.0.1".to_string(), 1234);
+
         service.register_extension(registration).await.unwrap();
-        
+
         let extensions = service.list_extensions().await;
         assert_eq!(extensions.len(), 1);
         assert_eq!(extensions[0].registration.identity.unique_id, "test-ext");
-        
+
         let active = service.list_active_extensions().await;
         assert_eq!(active.len(), 1);
-        
-        service.update_extension_status("test-ext", ExtensionStatus::Inactive).await.unwrap();
-        
+
+        service
+            .update_extension_status("test-ext", ExtensionStatus::Inactive)
+            .await
+            .unwrap();
+
         let active_after = service.list_active_extensions().await;
         assert_eq!(active_after.len(), 0);
-        
+
         service.unregister_extension("test-ext").await.unwrap();
-        
+
         let extensions_after = service.list_extensions().await;
         assert_eq!(extensions_after.len(), 0);
     }
-    
+
+    #[tokio::test]
+    async fn test_watch_reports_registry_changes() {
+        let service = DiscoveryService::new(std::time::Duration::from_millis(1));
+        let mut watch = service.watch();
+        let identity = ExtensionIdentity {
+            name: "test".to_string(),
+            unique_id: "test-ext".to_string(),
+            version: "1.0.0".to_string(),
+            opensearch_version: "3.0.0".to_string(),
+            java_version: "11".to_string(),
+            description: None,
+            vendor: None,
+            license: None,
+            dependencies: vec![],
+        };
+        service
+            .register_extension(ExtensionRegistration::new(
+                identity,
+                "127.0.0.1".to_string(),
+                1234,
+            ))
+            .await
+            .unwrap();
+        service
+            .update_extension_status("test-ext", ExtensionStatus::Failed)
+            .await
+            .unwrap();
+        service
+            .update_extension_status("test-ext", ExtensionStatus::Failed)
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        service.check_stale_extensions().await;
+        service.check_stale_extensions().await;
+        service.unregister_extension("test-ext").await.unwrap();
+
+        assert!(
+            matches!(watch.recv().await, Some(RegistryEvent::Registered { extension }) if extension.status == ExtensionStatus::Active)
+        );
+        assert!(matches!(
+            watch.recv().await,
+            Some(RegistryEvent::StatusChanged {
+                from: ExtensionStatus::Active,
+                to: ExtensionStatus::Failed,
+                ..
+            })
+        ));
+        assert!(
+            matches!(watch.recv().await, Some(RegistryEvent::Stale { unique_id }) if unique_id == "test-ext")
+        );
+        assert!(
+            matches!(watch.recv().await, Some(RegistryEvent::Unregistered { unique_id }) if unique_id == "test-ext")
+        );
+        assert!(watch.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reaper_and_heartbeats_drive_status() {
+        let service = Arc::new(DiscoveryService::new(std::time::Duration::from_millis(5)));
+        let identity = ExtensionIdentity {
+            name: "test".to_string(),
+            unique_id: "test-ext".to_string(),
+            version: "1.0.0".to_string(),
+            opensearch_version: "3.0.0".to_string(),
+            java_version: "11".to_string(),
+            description: None,
+            vendor: None,
+            license: None,
+            dependencies: vec![],
+        };
+        service
+            .register_extension(ExtensionRegistration::new(
+                identity,
+                "127.0.0.1".to_string(),
+                1234,
+            ))
+            .await
+            .unwrap();
+        let mut watch = service.watch();
+        let reaper = service
+            .clone()
+            .start_reaper(std::time::Duration::from_millis(5));
+
+        let stale = tokio::time::timeout(std::time::Duration::from_secs(5), watch.recv())
+            .await
+            .unwrap();
+        assert!(matches!(stale, Some(RegistryEvent::Stale { .. })));
+        assert_eq!(
+            service.get_extension("test-ext").await.unwrap().status,
+            ExtensionStatus::Inactive
+        );
+
+        let action = DiscoveryHeartbeatAction::new(service.clone());
+        action
+            .execute(br#"{"unique_id":"test-ext"}"#.to_vec())
+            .await
+            .unwrap();
+        assert!(matches!(
+            watch.recv().await,
+            Some(RegistryEvent::StatusChanged {
+                from: ExtensionStatus::Inactive,
+                to: ExtensionStatus::Active,
+                ..
+            })
+        ));
+        assert!(action
+            .execute(br#"{"unique_id":"other"}"#.to_vec())
+            .await
+            .is_err());
+        reaper.abort();
+    }
+
+    #[tokio::test]
+    async fn test_registry_survives_restart() {
+        let dir = std::env::temp_dir().join(format!("discovery-service-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let open = || {
+            DiscoveryService::new(std::time::Duration::from_secs(30))
+                .with_store(DiscoveryStore::open(&dir).unwrap())
+                .unwrap()
+        };
+
+        let service = open();
+        for unique_id in ["a", "b"] {
+            let identity = ExtensionIdentity {
+                name: unique_id.to_string(),
+                unique_id: unique_id.to_string(),
+                version: "1.0.0".to_string(),
+                opensearch_version: "3.0.0".to_string(),
+                java_version: "11".to_string(),
+                description: None,
+                vendor: None,
+                license: None,
+                dependencies: vec![],
+            };
+            service
+                .register_extension(ExtensionRegistration::new(
+                    identity,
+                    "127.0.0.1".to_string(),
+                    1234,
+                ))
+                .await
+                .unwrap();
+        }
+        service
+            .update_extension_status("a", ExtensionStatus::Failed)
+            .await
+            .unwrap();
+        let stale = service.get_extension("b").await.unwrap();
+        service.unregister_extension("b").await.unwrap();
+        let last_seen = service.get_extension("a").await.unwrap().last_seen;
+        drop(service);
+
+        // The tombstone outlives the restart, so a stale peer can't bring "b" back.
+        let stale_digest = || RegistryDigest {
+            extensions: vec![stale.clone()],
+            tombstones: vec![],
+        };
+        let restarted = open();
+        let a = restarted.get_extension("a").await.unwrap();
+        assert_eq!(
+            (a.status, a.last_seen),
+            (ExtensionStatus::Failed, last_seen)
+        );
+        assert_eq!(restarted.merge(stale_digest()).await.unwrap(), 0);
+        assert!(restarted.get_extension("b").await.is_none());
+        restarted.compact_store().await.unwrap();
+        let compacted = open();
+        assert_eq!(compacted.merge(stale_digest()).await.unwrap(), 0);
+        assert_eq!(compacted.list_extensions().await.len(), 1);
+
+        // A digest with a bad entry is rejected whole.
+        let mut revived = stale.clone();
+        revived.last_seen = std::time::SystemTime::now();
+        let bad = Tombstone {
+            unique_id: String::new(),
+            removed_at: std::time::SystemTime::now(),
+        };
+        let digest = RegistryDigest {
+            extensions: vec![revived],
+            tombstones: vec![bad],
+        };
+        assert!(compacted.merge(digest).await.is_err());
+        assert!(compacted.get_extension("b").await.is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_query_by_capability() {
+        let service = Arc::new(DiscoveryService::new(std::time::Duration::from_secs(30)));
+        for (unique_id, rest, settings) in [
+            ("rest", true, false),
+            ("settings", false, true),
+            ("both", true, true),
+        ] {
+            let identity = ExtensionIdentity {
+                name: unique_id.to_string(),
+                unique_id: unique_id.to_string(),
+                version: "1.0.0".to_string(),
+                opensearch_version: "3.0.0".to_string(),
+                java_version: "11".to_string(),
+                description: None,
+                vendor: None,
+                license: None,
+                dependencies: vec![],
+            };
+            let capabilities = ExtensionCapabilities {
+                supports_rest_actions: rest,
+                supports_settings_extension: settings,
+                ..ExtensionCapabilities::default()
+            };
+            let registration = ExtensionRegistration::new(identity, "127.0.0.1".to_string(), 1234)
+                .with_capabilities(capabilities);
+            service.register_extension(registration).await.unwrap();
+        }
+        service
+            .update_extension_status("both", ExtensionStatus::Failed)
+            .await
+            .unwrap();
+        let ids = |extensions: Vec<DiscoveredExtension>| {
+            let mut ids: Vec<String> = extensions
+                .into_iter()
+                .map(|ext| ext.registration.identity.unique_id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(
+            ids(service
+                .discover_extensions_with(|caps| caps.supports_rest_actions)
+                .await),
+            vec!["both", "rest"]
+        );
+        let query = CapabilityQuery::new().require(Capability::RestActions);
+        assert_eq!(ids(service.query_extensions(&query).await), vec!["rest"]);
+        let query = query
+            .include_inactive()
+            .exclude(Capability::SettingsExtension);
+        assert_eq!(ids(service.query_extensions(&query).await), vec!["rest"]);
+
+        let action = DiscoveryQueryAction::new(service);
+        let response = action
+            .execute(
+                br#"{"capabilities":{"any_of":["settings_extension"],"include_inactive":true}}"#
+                    .to_vec(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(serde_json::from_slice(&response).unwrap()),
+            vec!["both", "settings"]
+        );
+        let response = action
+            .execute(br#"{"unique_id":"missing"}"#.to_vec())
+            .await
+            .unwrap();
+        assert_eq!(response, br#"{"found":false}"#);
+        assert!(action
+            .execute(br#"{"capabilities":{"all_of":["teleport"]}}"#.to_vec())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pages_and_delta_sync() {
+        let service = Arc::new(DiscoveryService::new(std::time::Duration::from_secs(30)));
+        let register = |unique_id: &str| {
+            let identity = ExtensionIdentity {
+                name: unique_id.to_string(),
+                unique_id: unique_id.to_string(),
+                version: "1.0.0".to_string(),
+                opensearch_version: "3.0.0".to_string(),
+                java_version: "11".to_string(),
+                description: None,
+                vendor: None,
+                license: None,
+                dependencies: vec![],
+            };
+            service.register_extension(ExtensionRegistration::new(
+                identity,
+                "127.0.0.1".to_string(),
+                1234,
+            ))
+        };
+        for unique_id in ["c", "a", "e", "b", "d"] {
+            register(unique_id).await.unwrap();
+        }
+        let page = service.list_page(1, 2).await;
+        let ids: Vec<_> = page
+            .extensions
+            .iter()
+            .map(|ext| ext.registration.identity.unique_id.as_str())
+            .collect();
+        assert_eq!(
+            (ids, page.total, page.has_more()),
+            (vec!["c", "d"], 5, true)
+        );
+        assert!(!service.list_page(2, 2).await.has_more());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let action = DiscoveryListAction::new(service.clone());
+        let server = tokio::spawn(async move {
+            use crate::transport::inbound::{read_message, write_response};
+            use crate::transport::ThreadContext;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let request = read_message(&mut stream).await.unwrap().unwrap();
+                let reply = action.execute(request.content).await.unwrap();
+                write_response(
+                    &mut stream,
+                    &request.header,
+                    &ThreadContext::new(),
+                    &reply,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+        });
+        let client = DiscoveryClient::new(format!("127.0.0.1:{}", port));
+        assert_eq!(client.sync().await.unwrap().len(), 5);
+
+        service.unregister_extension("a").await.unwrap();
+        service
+            .update_extension_status("b", ExtensionStatus::Failed)
+            .await
+            .unwrap();
+        let etag = client.synced.lock().unwrap().etag.clone();
+        let delta = service.changes_since(etag.as_deref()).await;
+        assert!(!delta.full);
+        assert_eq!(delta.removed, vec!["a"]);
+        assert_eq!(delta.upserted.len(), 1);
+        assert_eq!(service.changes_since(Some("bogus")).await.upserted.len(), 4);
+
+        assert_eq!(client.sync().await.unwrap().len(), 4);
+        assert_eq!(
+            client
+                .query_extension_fallback("b")
+                .await
+                .unwrap()
+                .unwrap()
+                .status,
+            ExtensionStatus::Failed
+        );
+        server.abort();
+    }
+
     #[test]
     fn test_parse_host_port() {
         let client = DiscoveryClient::new("localhost:9300");
-        
+
         let (host, port) = client.parse_host_port("localhost:9300").unwrap();
         assert_eq!(host, "localhost");
         assert_eq!(port, 9300);
-        
+
         let (host, port) = client.parse_host_port("example.com").unwrap();
         assert_eq!(host, "example.com");
         assert_eq!(port, 9300); // Default port
-        
+
         let (host, port) = client.parse_host_port("192.168.1.1:8080").unwrap();
         assert_eq!(host, "192.168.1.1");
         assert_eq!(port, 8080);
-        
+
         // Test invalid port
         let result = client.parse_host_port("localhost:invalid");
         assert!(result.is_err());
     }
-    
+
     #[tokio::test]
     async fn test_query_extension_direct() {
         let client = DiscoveryClient::new("localhost:9300");
-        
+
         // This will fail since we don't have a real server, but it tests the logic
         let result = client.query_extension_direct("test-ext").await;
         assert!(result.is_err()); // Expected to fail without a server
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,478 @@
+use async_trait::async_trait;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tracing::debug;
+
+use crate::extension::context::Settings;
+use crate::extension::setting::Setting;
+use crate::extension::ExtensionError;
+
+const NAMESPACE_FILE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+
+/// How OpenSearch nodes and peer extensions are located: `static` uses the
+/// configured host and port, `dns` and `kubernetes` look up SRV records.
+pub fn discovery_backend() -> Setting<String> {
+    Setting::string("discovery.backend")
+        .default("static".to_string())
+        .one_of(&["static", "dns", "kubernetes"])
+}
+
+/// Name of the OpenSearch service to locate.
+pub fn opensearch_service() -> Setting<String> {
+    Setting::string("discovery.opensearch_service").default("opensearch".to_string())
+}
+
+/// Domain appended to service names by the `dns` backend.
+pub fn dns_domain() -> Setting<String> {
+    Setting::string("discovery.dns.domain")
+}
+
+/// Nameserver to query, `host:port`, instead of the first in `/etc/resolv.conf`.
+pub fn dns_nameserver() -> Setting<String> {
+    Setting::string("discovery.dns.nameserver")
+}
+
+/// Port name of the SRV records, as in `_transport._tcp.opensearch`.
+pub fn dns_port_name() -> Setting<String> {
+    Setting::string("discovery.dns.port_name").default("transport".to_string())
+}
+
+/// Port used for services that have address records but no SRV records.
+pub fn dns_default_port() -> Setting<i64> {
+    Setting::int("discovery.dns.default_port")
+        .default(9300)
+        .min(1)
+        .max(u16::MAX as i64)
+}
+
+/// Namespace of the services, by default the pod's own.
+pub fn kubernetes_namespace() -> Setting<String> {
+    Setting::string("discovery.kubernetes.namespace")
+}
+
+pub fn kubernetes_cluster_domain() -> Setting<String> {
+    Setting::string("discovery.kubernetes.cluster_domain").default("cluster.local".to_string())
+}
+
+/// Finds the addresses behind a service name, so that nodes and peers can
+/// move without the extension being reconfigured.
+#[async_trait]
+pub trait DiscoveryBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Addresses of `service`, the OpenSearch service or the unique id of a
+    /// peer extension, in the order they should be tried.
+    async fn locate(&self, service: &str) -> Result<Vec<SocketAddr>, ExtensionError>;
+}
+
+/// The backend selected by the `discovery` settings, or `None` for `static`.
+pub fn backend_from_settings(
+    settings: &Settings,
+) -> Result<Option<Arc<dyn DiscoveryBackend>>, ExtensionError> {
+    let nameserver = || match dns_nameserver().get_opt(settings)? {
+        Some(nameserver) => parse_nameserver(&nameserver),
+        None => system_nameserver(),
+    };
+    let backend = match discovery_backend().get(settings)?.as_str() {
+        "dns" => DnsDiscoveryBackend::new(nameserver()?).domain(dns_domain().get_opt(settings)?),
+        "kubernetes" => {
+            let namespace = match kubernetes_namespace().get_opt(settings)? {
+                Some(namespace) => namespace,
+                None => pod_namespace(),
+            };
+            DnsDiscoveryBackend::kubernetes(
+                nameserver()?,
+                &namespace,
+                &kubernetes_cluster_domain().get(settings)?,
+            )
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(Arc::new(
+        backend
+            .port_name(dns_port_name().get(settings)?)
+            .default_port(dns_default_port().get(settings)? as u16),
+    )))
+}
+
+fn parse_nameserver(nameserver: &str) -> Result<SocketAddr, ExtensionError> {
+    nameserver
+        .parse()
+        .or_else(|_| nameserver.parse().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| ExtensionError::configuration(format!("Invalid nameserver '{}'", nameserver)))
+}
+
+fn system_nameserver() -> Result<SocketAddr, ExtensionError> {
+    let conf = std::fs::read_to_string(RESOLV_CONF).map_err(|e| {
+        ExtensionError::configuration(format!("Failed to read {}: {}", RESOLV_CONF, e))
+    })?;
+    conf.lines()
+        .find_map(|line| line.trim().strip_prefix("nameserver").map(str::trim))
+        .ok_or_else(|| ExtensionError::configuration(format!("No nameserver in {}", RESOLV_CONF)))
+        .and_then(parse_nameserver)
+}
+
+fn pod_namespace() -> String {
+    std::env::var("POD_NAMESPACE")
+        .ok()
+        .or_else(|| std::fs::read_to_string(NAMESPACE_FILE).ok())
+        .map(|namespace| namespace.trim().to_string())
+        .filter(|namespace| !namespace.is_empty())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// A record of a service instance, from an SRV lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// Locates services through DNS SRV records, `_<port_name>._tcp.<service>`,
+/// falling back to the service's address records and `default_port` when
+/// it has none. In Kubernetes, headless services publish these records for
+/// their ready endpoints under `<namespace>.svc.<cluster domain>`.
+pub struct DnsDiscoveryBackend {
+    name: &'static str,
+    nameserver: SocketAddr,
+    domain: Option<String>,
+    port_name: String,
+    default_port: u16,
+    timeout: Duration,
+}
+
+impl DnsDiscoveryBackend {
+    pub fn new(nameserver: SocketAddr) -> Self {
+        DnsDiscoveryBackend {
+            name: "dns",
+            nameserver,
+            domain: None,
+            port_name: "transport".to_string(),
+            default_port: 9300,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Services of `namespace` in the cluster DNS zone.
+    pub fn kubernetes(nameserver: SocketAddr, namespace: &str, cluster_domain: &str) -> Self {
+        DnsDiscoveryBackend {
+            name: "kubernetes",
+            ..Self::new(nameserver).domain(Some(format!("{}.svc.{}", namespace, cluster_domain)))
+        }
+    }
+
+    pub fn domain(mut self, domain: Option<String>) -> Self {
+        self.domain = domain
+            .map(|domain| domain.trim_matches('.').to_string())
+            .filter(|domain| !domain.is_empty());
+        self
+    }
+
+    pub fn port_name(mut self, port_name: impl Into<String>) -> Self {
+        self.port_name = port_name.into();
+        self
+    }
+
+    pub fn default_port(mut self, default_port: u16) -> Self {
+        self.default_port = default_port;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn qualify(&self, service: &str) -> String {
+        match &self.domain {
+            Some(domain) => format!("{}.{}", service, domain),
+            None => service.to_string(),
+        }
+    }
+
+    /// SRV records for `name`, by priority and then weight.
+    pub async fn lookup_srv(&self, name: &str) -> Result<Vec<SrvRecord>, ExtensionError> {
+        let id = rand::random();
+        let query = encode_query(id, name)?;
+        let response = tokio::time::timeout(self.timeout, self.exchange(&query))
+            .await
+            .map_err(|_| ExtensionError::timeout(format!("DNS lookup of {} timed out", name)))??;
+        let mut records = parse_srv_response(id, &response)?;
+        records.sort_by(|a, b| a.priority.cmp(&b.priority).then(b.weight.cmp(&a.weight)));
+        Ok(records)
+    }
+
+    async fn exchange(&self, query: &[u8]) -> Result<Vec<u8>, ExtensionError> {
+        let dns_error = |e: std::io::Error| {
+            ExtensionError::transport(format!("DNS query to {} failed: {}", self.nameserver, e))
+        };
+        let local: SocketAddr = if self.nameserver.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local).await.map_err(dns_error)?;
+        socket
+            .send_to(query, self.nameserver)
+            .await
+            .map_err(dns_error)?;
+        let mut response = vec![0; 4096];
+        let len = socket.recv(&mut response).await.map_err(dns_error)?;
+        response.truncate(len);
+        if len < 3 || response[2] & 0x02 == 0 {
+            return Ok(response);
+        }
+
+        debug!(
+            "DNS response from {} truncated, retrying over TCP",
+            self.nameserver
+        );
+        let mut stream = TcpStream::connect(self.nameserver)
+            .await
+            .map_err(dns_error)?;
+        stream
+            .write_all(&(query.len() as u16).to_be_bytes())
+            .await
+            .map_err(dns_error)?;
+        stream.write_all(query).await.map_err(dns_error)?;
+        let len = stream.read_u16().await.map_err(dns_error)?;
+        let mut response = vec![0; len as usize];
+        stream.read_exact(&mut response).await.map_err(dns_error)?;
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl DiscoveryBackend for DnsDiscoveryBackend {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn locate(&self, service: &str) -> Result<Vec<SocketAddr>, ExtensionError> {
+        let host = self.qualify(service);
+        let records = self
+            .lookup_srv(&format!("_{}._tcp.{}", self.port_name, host))
+            .await?;
+        let targets: Vec<(String, u16)> = match records.is_empty() {
+            true => vec![(host, self.default_port)],
+            false => records
+                .into_iter()
+                .map(|record| (record.target, record.port))
+                .collect(),
+        };
+
+        let mut addresses = Vec::new();
+        for (target, port) in targets {
+            match tokio::net::lookup_host((target.as_str(), port)).await {
+                Ok(resolved) => {
+                    for addr in resolved {
+                        if !addresses.contains(&addr) {
+                            addresses.push(addr);
+                        }
+                    }
+                }
+                Err(e) => debug!("Skipping {}:{} of service {}: {}", target, port, service, e),
+            }
+        }
+        Ok(addresses)
+    }
+}
+
+fn encode_query(id: u16, name: &str) -> Result<Vec<u8>, ExtensionError> {
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question.
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(ExtensionError::invalid_request(format!(
+                "Invalid DNS name '{}'",
+                name
+            )));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+fn malformed() -> ExtensionError {
+    ExtensionError::protocol("Malformed DNS response")
+}
+
+fn read_u16(message: &[u8], at: usize) -> Result<u16, ExtensionError> {
+    message
+        .get(at..at + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(malformed)
+}
+
+/// Read the possibly compressed name at `at`, returning it and the offset
+/// just past it.
+fn read_name(message: &[u8], mut at: usize) -> Result<(String, usize), ExtensionError> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    for _ in 0..128 {
+        let len = *message.get(at).ok_or_else(malformed)? as usize;
+        match len {
+            0 => {
+                let name = labels.join(".");
+                return Ok((name, end.unwrap_or(at + 1)));
+            }
+            len if len & 0xC0 == 0xC0 => {
+                end.get_or_insert(at + 2);
+                at = (read_u16(message, at)? & 0x3FFF) as usize;
+            }
+            len => {
+                let label = message.get(at + 1..at + 1 + len).ok_or_else(malformed)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                at += 1 + len;
+            }
+        }
+    }
+    Err(malformed())
+}
+
+fn parse_srv_response(id: u16, message: &[u8]) -> Result<Vec<SrvRecord>, ExtensionError> {
+    if read_u16(message, 0)? != id {
+        return Err(ExtensionError::protocol(
+            "DNS response does not match the query",
+        ));
+    }
+    match read_u16(message, 2)? & 0x000F {
+        0 => {}
+        // The name does not exist.
+        3 => return Ok(Vec::new()),
+        rcode => {
+            return Err(ExtensionError::transport(format!(
+                "DNS lookup failed with rcode {}",
+                rcode
+            )))
+        }
+    }
+    let questions = read_u16(message, 4)?;
+    let answers = read_u16(message, 6)?;
+    let mut at = 12;
+    for _ in 0..questions {
+        at = read_name(message, at)?.1 + 4;
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        at = read_name(message, at)?.1;
+        let record_type = read_u16(message, at)?;
+        let data_len = read_u16(message, at + 8)? as usize;
+        let data = at + 10;
+        if record_type == TYPE_SRV {
+            records.push(SrvRecord {
+                priority: read_u16(message, data)?,
+                weight: read_u16(message, data + 2)?,
+                port: read_u16(message, data + 4)?,
+                target: read_name(message, data + 6)?.0,
+            });
+        }
+        at = data + data_len;
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn srv_answer(query: &[u8], records: &[(u16, u16, &str)]) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[7] = records.len() as u8;
+        for (priority, port, target) in records {
+            let mut data = Vec::new();
+            data.extend_from_slice(&priority.to_be_bytes());
+            data.extend_from_slice(&10u16.to_be_bytes());
+            data.extend_from_slice(&port.to_be_bytes());
+            for label in target.split('.') {
+                data.push(label.len() as u8);
+                data.extend_from_slice(label.as_bytes());
+            }
+            data.push(0);
+            // Name compressed to the question, type, class, ttl, data.
+            response.extend_from_slice(&[0xC0, 12, 0, 33, 0, 1, 0, 0, 0, 30]);
+            response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            response.extend_from_slice(&data);
+        }
+        response
+    }
+
+    #[tokio::test]
+    async fn test_locates_kubernetes_service_by_srv() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let nameserver = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut query = [0u8; 512];
+            while let Ok((len, from)) = server.recv_from(&mut query).await {
+                let (name, _) = read_name(&query[..len], 12).unwrap();
+                let records: &[(u16, u16, &str)] = match name.as_str() {
+                    "_transport._tcp.opensearch.search.svc.cluster.local" => {
+                        &[(20, 9301, "127.0.0.1"), (10, 9300, "127.0.0.1")]
+                    }
+                    _ => &[],
+                };
+                server
+                    .send_to(&srv_answer(&query[..len], records), from)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let backend = DnsDiscoveryBackend::kubernetes(nameserver, "search", "cluster.local");
+        assert_eq!(backend.name(), "kubernetes");
+        let addresses = backend.locate("opensearch").await.unwrap();
+        assert_eq!(
+            addresses,
+            vec![
+                "127.0.0.1:9300".parse().unwrap(),
+                "127.0.0.1:9301".parse().unwrap()
+            ]
+        );
+
+        let fallback = DnsDiscoveryBackend::new(nameserver)
+            .default_port(9400)
+            .locate("localhost")
+            .await
+            .unwrap();
+        assert!(fallback
+            .iter()
+            .all(|addr| addr.port() == 9400 && addr.ip().is_loopback()));
+    }
+
+    #[test]
+    fn test_backend_from_settings() {
+        let settings = Settings::new();
+        assert!(backend_from_settings(&settings).unwrap().is_none());
+
+        discovery_backend()
+            .set(&settings, "dns".to_string())
+            .unwrap();
+        dns_nameserver()
+            .set(&settings, "10.0.0.10".to_string())
+            .unwrap();
+        let backend = backend_from_settings(&settings).unwrap().unwrap();
+        assert_eq!(backend.name(), "dns");
+
+        dns_nameserver()
+            .set(&settings, "not an address".to_string())
+            .unwrap();
+        assert!(backend_from_settings(&settings).is_err());
+    }
+}
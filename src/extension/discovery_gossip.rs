@@ -0,0 +1,218 @@
+use rand::seq::SliceRandom;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::extension::context::Settings;
+use crate::extension::discovery::{DiscoveryClient, DiscoveryService};
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+
+/// Other discovery services to replicate with, as comma-separated `host:port`.
+pub fn gossip_peers() -> Setting<String> {
+    Setting::string("discovery.gossip.peers").default(String::new())
+}
+
+/// Time between gossip rounds.
+pub fn gossip_interval() -> Setting<TimeValue> {
+    Setting::time("discovery.gossip.interval")
+        .default(TimeValue::seconds(10))
+        .min(TimeValue::millis(100))
+}
+
+/// Peers exchanged with each round.
+pub fn gossip_fanout() -> Setting<i64> {
+    Setting::int("discovery.gossip.fanout").default(2).min(1)
+}
+
+/// Replicates a `DiscoveryService` with its peers by anti-entropy: every
+/// round it exchanges full digests with `fanout` random peers, and both
+/// sides merge what the other knows. Any service can then answer for the
+/// whole registry, and a lost service is caught up when it returns.
+pub struct DiscoveryGossip {
+    service: Arc<DiscoveryService>,
+    peers: Vec<DiscoveryClient>,
+    interval: Duration,
+    fanout: usize,
+}
+
+impl DiscoveryGossip {
+    pub fn new(service: Arc<DiscoveryService>, peers: Vec<String>) -> Self {
+        DiscoveryGossip {
+            service,
+            peers: peers.into_iter().map(DiscoveryClient::new).collect(),
+            interval: Duration::from_secs(10),
+            fanout: 2,
+        }
+    }
+
+    /// Gossip configured by the `gossip_*` settings, or `None` when there
+    /// are no peers.
+    pub fn from_settings(
+        service: Arc<DiscoveryService>,
+        settings: &Settings,
+    ) -> Result<Option<Self>, ExtensionError> {
+        let peers: Vec<String> = gossip_peers()
+            .get(settings)?
+            .split(',')
+            .map(str::trim)
+            .filter(|peer| !peer.is_empty())
+            .map(String::from)
+            .collect();
+        if peers.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            Self::new(service, peers)
+                .interval(gossip_interval().get(settings)?.as_duration())
+                .fanout(gossip_fanout().get(settings)? as usize),
+        ))
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn fanout(mut self, fanout: usize) -> Self {
+        self.fanout = fanout.max(1);
+        self
+    }
+
+    /// Exchange digests with `peer`, returning how many changes were
+    /// adopted from it.
+    pub async fn exchange(&self, peer: &DiscoveryClient) -> Result<usize, ExtensionError> {
+        let digest = self.service.digest().await?;
+        let remote = peer.gossip(&digest).await?;
+        self.service.merge(remote).await
+    }
+
+    /// Gossip with `fanout` random peers, returning the changes adopted.
+    /// Unreachable peers are logged and skipped.
+    pub async fn round(&self) -> usize {
+        let peers: Vec<&DiscoveryClient> = self
+            .peers
+            .choose_multiple(&mut rand::thread_rng(), self.fanout)
+            .collect();
+        let mut adopted = 0;
+        for peer in peers {
+            match self.exchange(peer).await {
+                Ok(changes) => adopted += changes,
+                Err(e) => warn!(
+                    "Gossip with discovery peer {} failed: {}",
+                    peer.service_url(),
+                    e
+                ),
+            }
+        }
+        adopted
+    }
+
+    /// Gossip every interval until the returned task is aborted.
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                let adopted = self.round().await;
+                if adopted > 0 {
+                    debug!("Adopted {} registry changes from discovery peers", adopted);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::discovery::{DiscoveryGossipAction, ExtensionStatus};
+    use crate::extension::registration::{ExtensionIdentity, ExtensionRegistration};
+    use crate::transport::action::TransportAction;
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+
+    fn registration(unique_id: &str) -> ExtensionRegistration {
+        let identity = ExtensionIdentity {
+            name: unique_id.to_string(),
+            unique_id: unique_id.to_string(),
+            version: "1.0.0".to_string(),
+            opensearch_version: "3.0.0".to_string(),
+            java_version: "11".to_string(),
+            description: None,
+            vendor: None,
+            license: None,
+            dependencies: vec![],
+        };
+        ExtensionRegistration::new(identity, "127.0.0.1".to_string(), 1234)
+    }
+
+    async fn serve(service: Arc<DiscoveryService>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let action = DiscoveryGossipAction::new(service);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let request = read_message(&mut stream).await.unwrap().unwrap();
+                let reply = action.execute(request.content).await.unwrap();
+                write_response(
+                    &mut stream,
+                    &request.header,
+                    &ThreadContext::new(),
+                    &reply,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+        });
+        addr.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_gossip_converges_without_resurrecting_removals() {
+        let a = Arc::new(DiscoveryService::new(Duration::from_secs(30)));
+        let b = Arc::new(DiscoveryService::new(Duration::from_secs(30)));
+        a.register_extension(registration("x")).await.unwrap();
+        a.register_extension(registration("y")).await.unwrap();
+        b.register_extension(registration("z")).await.unwrap();
+        let gossip = DiscoveryGossip::new(a.clone(), vec![serve(b.clone()).await]);
+
+        assert_eq!(gossip.round().await, 1);
+        assert_eq!(b.list_extensions().await.len(), 3);
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        b.update_extension_status("y", ExtensionStatus::Failed)
+            .await
+            .unwrap();
+        a.unregister_extension("x").await.unwrap();
+        gossip.round().await;
+        for service in [&a, &b] {
+            assert!(service.get_extension("x").await.is_none());
+            assert_eq!(
+                service.get_extension("y").await.unwrap().status,
+                ExtensionStatus::Failed
+            );
+            assert_eq!(service.list_extensions().await.len(), 2);
+        }
+        assert_eq!(gossip.round().await, 0);
+
+        let settings = Settings::new();
+        assert!(DiscoveryGossip::from_settings(a.clone(), &settings)
+            .unwrap()
+            .is_none());
+        gossip_peers()
+            .set(&settings, "node-1:9300, node-2:9300".to_string())
+            .unwrap();
+        assert_eq!(
+            DiscoveryGossip::from_settings(a, &settings)
+                .unwrap()
+                .unwrap()
+                .peers
+                .len(),
+            2
+        );
+    }
+}
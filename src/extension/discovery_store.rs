@@ -0,0 +1,439 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::extension::discovery::DiscoveredExtension;
+use crate::extension::ExtensionError;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const WAL_FILE: &str = "wal.jsonl";
+
+/// One change to the registry, as written to the log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum StoreEntry {
+    Put {
+        extension: Box<DiscoveredExtension>,
+    },
+    /// An unregistration at `removed_at`, which leaves a tombstone and only
+    /// removes copies last seen before it. Logs written before tombstones
+    /// were kept have no time and remove unconditionally.
+    Remove {
+        unique_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        removed_at: Option<SystemTime>,
+    },
+}
+
+impl StoreEntry {
+    pub fn apply(self, state: &mut StoreState) {
+        match self {
+            StoreEntry::Put { extension } => {
+                let unique_id = extension.registration.identity.unique_id.clone();
+                state.tombstones.remove(&unique_id);
+                state.extensions.insert(unique_id, *extension);
+            }
+            StoreEntry::Remove {
+                unique_id,
+                removed_at: None,
+            } => {
+                state.extensions.remove(&unique_id);
+            }
+            StoreEntry::Remove {
+                unique_id,
+                removed_at: Some(removed_at),
+            } => {
+                if state
+                    .extensions
+                    .get(&unique_id)
+                    .is_some_and(|ext| ext.last_seen < removed_at)
+                {
+                    state.extensions.remove(&unique_id);
+                }
+                let tombstone = state.tombstones.entry(unique_id).or_insert(removed_at);
+                *tombstone = (*tombstone).max(removed_at);
+            }
+        }
+    }
+}
+
+/// The registry and the times extensions were unregistered, as kept on disk.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StoreState {
+    pub extensions: HashMap<String, DiscoveredExtension>,
+    #[serde(default)]
+    pub tombstones: HashMap<String, SystemTime>,
+}
+
+impl StoreState {
+    pub fn view(&self) -> StoreView<'_> {
+        StoreView {
+            extensions: &self.extensions,
+            tombstones: &self.tombstones,
+        }
+    }
+}
+
+/// A `StoreState` borrowed from wherever its parts are held.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StoreView<'a> {
+    pub extensions: &'a HashMap<String, DiscoveredExtension>,
+    pub tombstones: &'a HashMap<String, SystemTime>,
+}
+
+/// Snapshots written before tombstones were kept hold the registry alone.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Snapshot {
+    State(StoreState),
+    Registry(HashMap<String, DiscoveredExtension>),
+}
+
+/// `state` as written to the snapshot file.
+pub fn snapshot_bytes(state: StoreView<'_>) -> Result<Vec<u8>, ExtensionError> {
+    serde_json::to_vec(&state).map_err(|e| {
+        ExtensionError::serialization(format!("Failed to serialize discovery snapshot: {}", e))
+    })
+}
+
+/// A batch serialized by `DiscoveryStore::prepare`, ready to be written.
+#[derive(Debug)]
+pub struct PendingWrite {
+    lines: Vec<u8>,
+    entries: usize,
+    snapshot: Option<Vec<u8>>,
+    sync: bool,
+}
+
+impl PendingWrite {
+    /// Leave flushing the log to the OS, for entries that only refresh
+    /// `last_seen`: losing them in a crash just makes an extension look
+    /// staler than it is until its next heartbeat.
+    pub fn without_sync(mut self) -> Self {
+        self.sync = false;
+        self
+    }
+}
+
+fn io_error(what: &str, path: &Path, e: std::io::Error) -> ExtensionError {
+    ExtensionError::unknown(format!("Failed to {} {}: {}", what, path.display(), e))
+}
+
+/// Keeps the discovery registry on disk as a JSON snapshot plus a log of
+/// the changes since, so known extensions survive restarts of the host.
+/// The log is folded into a new snapshot every `compact_after` entries.
+/// A torn last log line is cut off on load, and an unreadable snapshot is
+/// moved aside so the service starts from the log alone.
+pub struct DiscoveryStore {
+    dir: PathBuf,
+    compact_after: usize,
+    wal: Mutex<Option<(File, usize)>>,
+}
+
+impl DiscoveryStore {
+    /// A store in `dir`, created if missing.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, ExtensionError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| io_error("create", &dir, e))?;
+        Ok(DiscoveryStore {
+            dir,
+            compact_after: 1000,
+            wal: Mutex::new(None),
+        })
+    }
+
+    pub fn compact_after(mut self, entries: usize) -> Self {
+        self.compact_after = entries.max(1);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read the snapshot and replay the log over it.
+    pub fn load(&self) -> Result<StoreState, ExtensionError> {
+        let mut state = self.read_snapshot()?;
+        let wal_path = self.dir.join(WAL_FILE);
+        let mut entries = 0;
+        if wal_path.exists() {
+            let file = File::open(&wal_path).map_err(|e| io_error("open", &wal_path, e))?;
+            let mut reader = BufReader::new(file);
+            let mut valid_len = 0;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let read = reader
+                    .read_line(&mut line)
+                    .map_err(|e| io_error("read", &wal_path, e))?;
+                if read == 0 {
+                    break;
+                }
+                match serde_json::from_str::<StoreEntry>(line.trim_end()) {
+                    Ok(entry) if line.ends_with('\n') => {
+                        entry.apply(&mut state);
+                        valid_len += read as u64;
+                        entries += 1;
+                    }
+                    _ => {
+                        warn!(
+                            "Discarding unreadable discovery log from byte {} of {}",
+                            valid_len,
+                            wal_path.display()
+                        );
+                        let file = OpenOptions::new()
+                            .write(true)
+                            .open(&wal_path)
+                            .map_err(|e| io_error("open", &wal_path, e))?;
+                        file.set_len(valid_len)
+                            .map_err(|e| io_error("truncate", &wal_path, e))?;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&wal_path)
+            .map_err(|e| io_error("open", &wal_path, e))?;
+        *self.wal_lock() = Some((wal, entries));
+        info!(
+            "Loaded {} known extensions from {}",
+            state.extensions.len(),
+            self.dir.display()
+        );
+        Ok(state)
+    }
+
+    fn wal_lock(&self) -> std::sync::MutexGuard<'_, Option<(File, usize)>> {
+        self.wal.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read_snapshot(&self) -> Result<StoreState, ExtensionError> {
+        let path = self.dir.join(SNAPSHOT_FILE);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(StoreState::default()),
+            Err(e) => return Err(io_error("read", &path, e)),
+        };
+        match serde_json::from_slice(&bytes) {
+            Ok(Snapshot::State(state)) => Ok(state),
+            Ok(Snapshot::Registry(extensions)) => Ok(StoreState {
+                extensions,
+                tombstones: HashMap::new(),
+            }),
+            Err(e) => {
+                let millis = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                let aside = self
+                    .dir
+                    .join(format!("{}.corrupt-{}", SNAPSHOT_FILE, millis));
+                warn!(
+                    "Discovery snapshot {} is unreadable ({}), moving it to {}",
+                    path.display(),
+                    e,
+                    aside.display()
+                );
+                std::fs::rename(&path, &aside).map_err(|e| io_error("move", &path, e))?;
+                Ok(StoreState::default())
+            }
+        }
+    }
+
+    /// Log `batch` in one write, compacting into a snapshot of `state`, the
+    /// state after the batch, once the log is long enough.
+    pub fn append(&self, batch: &[StoreEntry], state: StoreView<'_>) -> Result<(), ExtensionError> {
+        self.write(self.prepare(batch, state)?)
+    }
+
+    /// Serialize `batch`, and `state` too when the batch will trigger a
+    /// compaction, so `write` can run away from whatever holds `state`,
+    /// e.g. on a blocking thread.
+    pub fn prepare(
+        &self,
+        batch: &[StoreEntry],
+        state: StoreView<'_>,
+    ) -> Result<PendingWrite, ExtensionError> {
+        let mut lines = Vec::new();
+        for entry in batch {
+            serde_json::to_writer(&mut lines, entry).map_err(|e| {
+                ExtensionError::serialization(format!("Failed to serialize discovery entry: {}", e))
+            })?;
+            lines.push(b'\n');
+        }
+        let logged = self.wal_lock().as_ref().map_or(0, |(_, entries)| *entries);
+        let snapshot = if logged + batch.len() >= self.compact_after {
+            Some(snapshot_bytes(state)?)
+        } else {
+            None
+        };
+        Ok(PendingWrite {
+            lines,
+            entries: batch.len(),
+            snapshot,
+            sync: true,
+        })
+    }
+
+    /// Log a prepared batch. The batch is durable once logged, so a failed
+    /// compaction is only logged and retried on a later append.
+    pub fn write(&self, pending: PendingWrite) -> Result<(), ExtensionError> {
+        let wal_path = self.dir.join(WAL_FILE);
+        let mut wal = self.wal_lock();
+        let (file, entries) = wal.as_mut().ok_or_else(|| {
+            ExtensionError::unknown("Discovery store written before it was loaded")
+        })?;
+        file.write_all(&pending.lines)
+            .and_then(|_| {
+                if pending.sync {
+                    file.sync_data()
+                } else {
+                    Ok(())
+                }
+            })
+            .map_err(|e| io_error("write", &wal_path, e))?;
+        *entries += pending.entries;
+        if let Some(snapshot) = pending.snapshot.filter(|_| *entries >= self.compact_after) {
+            match self.write_snapshot(&snapshot) {
+                Ok(()) => {
+                    file.set_len(0)
+                        .map_err(|e| io_error("truncate", &wal_path, e))?;
+                    *entries = 0;
+                }
+                Err(e) => warn!("Failed to compact discovery log: {}", e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the snapshot with `state` and empty the log.
+    pub fn compact(&self, state: StoreView<'_>) -> Result<(), ExtensionError> {
+        self.replace_snapshot(&snapshot_bytes(state)?)
+    }
+
+    /// Like `compact`, with the state already serialized by `snapshot_bytes`.
+    pub fn replace_snapshot(&self, snapshot: &[u8]) -> Result<(), ExtensionError> {
+        let mut wal = self.wal_lock();
+        self.write_snapshot(snapshot)?;
+        if let Some((file, entries)) = wal.as_mut() {
+            let wal_path = self.dir.join(WAL_FILE);
+            file.set_len(0)
+                .map_err(|e| io_error("truncate", &wal_path, e))?;
+            *entries = 0;
+        }
+        Ok(())
+    }
+
+    fn write_snapshot(&self, bytes: &[u8]) -> Result<(), ExtensionError> {
+        let path = self.dir.join(SNAPSHOT_FILE);
+        let temp = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        File::create(&temp)
+            .and_then(|mut file| file.write_all(bytes).and_then(|_| file.sync_all()))
+            .map_err(|e| io_error("write", &temp, e))?;
+        std::fs::rename(&temp, &path).map_err(|e| io_error("replace", &path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::discovery::ExtensionStatus;
+    use crate::extension::registration::{ExtensionIdentity, ExtensionRegistration};
+
+    fn extension(unique_id: &str) -> Box<DiscoveredExtension> {
+        let identity = ExtensionIdentity {
+            name: unique_id.to_string(),
+            unique_id: unique_id.to_string(),
+            version: "1.0.0".to_string(),
+            opensearch_version: "3.0.0".to_string(),
+            java_version: "11".to_string(),
+            description: None,
+            vendor: None,
+            license: None,
+            dependencies: vec![],
+        };
+        Box::new(DiscoveredExtension {
+            registration: ExtensionRegistration::new(identity, "127.0.0.1".to_string(), 1234),
+            status: ExtensionStatus::Active,
+            last_seen: SystemTime::now(),
+        })
+    }
+
+    #[test]
+    fn test_recovers_from_torn_log_and_corrupt_snapshot() {
+        let dir = std::env::temp_dir().join(format!("discovery-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = DiscoveryStore::open(&dir).unwrap().compact_after(3);
+        let mut state = store.load().unwrap();
+        for entry in [
+            StoreEntry::Put {
+                extension: extension("a"),
+            },
+            StoreEntry::Put {
+                extension: extension("b"),
+            },
+            StoreEntry::Put {
+                extension: extension("c"),
+            },
+            StoreEntry::Remove {
+                unique_id: "a".to_string(),
+                removed_at: Some(SystemTime::now()),
+            },
+        ] {
+            entry.clone().apply(&mut state);
+            store
+                .append(std::slice::from_ref(&entry), state.view())
+                .unwrap();
+        }
+        assert!(dir.join(SNAPSHOT_FILE).exists());
+        let mut wal = OpenOptions::new()
+            .append(true)
+            .open(dir.join(WAL_FILE))
+            .unwrap();
+        wal.write_all(br#"{"op":"put","exten"#).unwrap();
+
+        let reopened = DiscoveryStore::open(&dir).unwrap().load().unwrap();
+        let mut ids: Vec<String> = reopened.extensions.into_keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["b", "c"]);
+        assert!(reopened.tombstones.contains_key("a"));
+        assert_eq!(
+            std::fs::read_to_string(dir.join(WAL_FILE))
+                .unwrap()
+                .lines()
+                .count(),
+            1
+        );
+
+        std::fs::write(dir.join(SNAPSHOT_FILE), b"{not json").unwrap();
+        let recovered = DiscoveryStore::open(&dir).unwrap().load().unwrap();
+        assert_eq!(recovered.extensions.len(), 0);
+        assert!(std::fs::read_dir(&dir).unwrap().any(|f| f
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .contains(".corrupt-")));
+
+        // Snapshots from before tombstones were kept are the bare registry.
+        let legacy = HashMap::from([("d".to_string(), *extension("d"))]);
+        std::fs::write(
+            dir.join(SNAPSHOT_FILE),
+            serde_json::to_vec(&legacy).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.join(WAL_FILE), b"").unwrap();
+        let upgraded = DiscoveryStore::open(&dir).unwrap().load().unwrap();
+        assert!(upgraded.extensions.contains_key("d") && upgraded.tombstones.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
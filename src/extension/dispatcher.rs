@@ -0,0 +1,378 @@
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::extension::dependency::{
+    parse_dependency_request, ExtensionDependencyResponse, EXTENSION_DEPENDENCY_ACTION,
+};
+use crate::extension::init::{ExtensionInit, EXTENSION_INIT_ACTION};
+use crate::extension::settings_update::{UpdateSettingsRequest, UPDATE_SETTINGS_ACTION};
+use crate::extension::slow_log::SlowLog;
+use crate::extension::{ExtensionContext, ExtensionError, NegotiatedFeatures};
+use crate::interface::{Deserialize, Serialize};
+use crate::rest::handler::REST_EXECUTE_ON_EXTENSION_ACTION;
+use crate::rest::{ExtensionRestRequest, RestRouter};
+use crate::transport::action::{
+    ExtensionActionRequest, TransportActionRegistry, HANDLE_TRANSPORT_ACTION,
+};
+use crate::transport::inbound::InboundMessage;
+use crate::transport::{AcknowledgedResponse, MessageSigner, HANDSHAKE_ACTION};
+
+/// Routes inbound transport requests to the handler registered for their action.
+#[derive(Default)]
+pub struct RequestDispatcher {
+    transport_actions: TransportActionRegistry,
+    dependency_info: Option<ExtensionDependencyResponse>,
+    init: Option<ExtensionInit>,
+    rest_router: RestRouter,
+    context: Option<Arc<ExtensionContext>>,
+    features: Option<NegotiatedFeatures>,
+    signer: Option<Arc<MessageSigner>>,
+    slow_log: SlowLog,
+}
+
+impl RequestDispatcher {
+    pub fn new(transport_actions: TransportActionRegistry) -> Self {
+        RequestDispatcher {
+            transport_actions,
+            dependency_info: None,
+            init: None,
+            rest_router: RestRouter::new(),
+            context: None,
+            features: None,
+            signer: None,
+            slow_log: SlowLog::new(),
+        }
+    }
+
+    /// Serve REST requests with `router`, passing `context` to its handlers.
+    pub fn with_rest_router(mut self, router: RestRouter, context: Arc<ExtensionContext>) -> Self {
+        self.rest_router = router;
+        self.context = Some(context);
+        self
+    }
+
+    pub fn with_dependency_info(mut self, info: ExtensionDependencyResponse) -> Self {
+        self.dependency_info = Some(info);
+        self
+    }
+
+    /// Answer `internal:discovery/extensions` with `init`'s response.
+    pub fn with_init(mut self, init: ExtensionInit) -> Self {
+        self.init = Some(init);
+        self
+    }
+
+    /// Negotiate `features` with the node sending the init request.
+    pub fn with_features(mut self, features: NegotiatedFeatures) -> Self {
+        self.features = Some(features);
+        self
+    }
+
+    /// Reject requests without a valid `signer` signature. Only the
+    /// `internal:tcp/handshake` handshake is exempt.
+    pub fn with_signer(mut self, signer: Arc<MessageSigner>) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    pub fn signer(&self) -> Option<&Arc<MessageSigner>> {
+        self.signer.as_ref()
+    }
+
+    /// Log requests slower than `slow_log`'s thresholds.
+    pub fn with_slow_log(mut self, slow_log: SlowLog) -> Self {
+        self.slow_log = slow_log;
+        self
+    }
+
+    pub fn slow_log(&self) -> &SlowLog {
+        &self.slow_log
+    }
+
+    pub fn transport_actions(&self) -> &TransportActionRegistry {
+        &self.transport_actions
+    }
+
+    pub fn rest_router(&self) -> &RestRouter {
+        &self.rest_router
+    }
+
+    pub async fn dispatch(&self, message: &InboundMessage) -> Result<Vec<u8>, ExtensionError> {
+        let action = message.action.as_deref().unwrap_or_default();
+        debug!(
+            "Dispatching transport request {} for action '{}'",
+            message.header.request_id, action
+        );
+        // The status byte is the sender's to set, so only the handshake
+        // action itself may carry it.
+        if message.is_handshake() && action != HANDSHAKE_ACTION {
+            return Err(ExtensionError::protocol(format!(
+                "Handshake status on non-handshake action '{}'",
+                action
+            )));
+        }
+        if let Some(signer) = self.signer.as_ref().filter(|_| !message.is_handshake()) {
+            signer.verify_request(
+                &message.thread_context,
+                message.header.request_id,
+                action,
+                &message.content,
+            )?;
+        }
+
+        match action {
+            HANDLE_TRANSPORT_ACTION => {
+                let request = ExtensionActionRequest::deserialize(&mut message.content.as_slice())
+                    .map_err(|e| {
+                        ExtensionError::serialization(format!(
+                            "Failed to deserialize transport action request: {}",
+                            e
+                        ))
+                    })?;
+                if let Some(context) = &self.context {
+                    if context.lifecycle.is_paused().await {
+                        return Err(ExtensionError::throttled("Extension is paused", None));
+                    }
+                }
+                let response = self.transport_actions.handle(request).await?;
+                Self::encode(&response)
+            }
+            REST_EXECUTE_ON_EXTENSION_ACTION => {
+                let context = self
+                    .context
+                    .as_ref()
+                    .ok_or_else(|| ExtensionError::protocol("REST handlers are not configured"))?;
+                let request = ExtensionRestRequest::deserialize(&mut message.content.as_slice())
+                    .map_err(|e| {
+                        ExtensionError::serialization(format!(
+                            "Failed to deserialize REST request: {}",
+                            e
+                        ))
+                    })?;
+                let response = self.rest_router.handle(request, context).await?;
+                Self::encode(&response)
+            }
+            EXTENSION_DEPENDENCY_ACTION => {
+                let unique_id = parse_dependency_request(&message.content)?;
+                match &self.dependency_info {
+                    Some(info) if unique_id.is_empty() || unique_id == info.unique_id => {
+                        Self::encode(info)
+                    }
+                    _ => Err(ExtensionError::dependency(format!(
+                        "No dependency information for extension '{}'",
+                        unique_id
+                    ))),
+                }
+            }
+            EXTENSION_INIT_ACTION => {
+                let init = self
+                    .init
+                    .as_ref()
+                    .ok_or_else(|| ExtensionError::protocol("Extension init is not configured"))?;
+                if let Some(features) = &self.features {
+                    let agreed = features.negotiate(&message.features);
+                    debug!(
+                        "Negotiated features {:?} with node offering {:?}",
+                        agreed, message.features
+                    );
+                }
+                Self::encode(&init.response(message.header.version)?)
+            }
+            UPDATE_SETTINGS_ACTION => {
+                let context = self.context.as_ref().ok_or_else(|| {
+                    ExtensionError::protocol("Settings updates are not configured")
+                })?;
+                let request = UpdateSettingsRequest::deserialize(&mut message.content.as_slice())
+                    .map_err(|e| {
+                    ExtensionError::serialization(format!(
+                        "Failed to deserialize settings update: {}",
+                        e
+                    ))
+                })?;
+                let changed = request.apply_to(&context.settings)?;
+                debug!("Applied settings update for {:?}", changed);
+                Self::encode(&AcknowledgedResponse::acknowledged())
+            }
+            HANDSHAKE_ACTION if message.is_handshake() => Ok(Vec::new()),
+            other => Err(ExtensionError::protocol(format!(
+                "Unsupported transport action: '{}'",
+                other
+            ))),
+        }
+    }
+
+    fn encode(response: &impl Serialize) -> Result<Vec<u8>, ExtensionError> {
+        let mut bytes = Vec::new();
+        response.serialize(&mut bytes).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize response: {}", e))
+        })?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::action::{ExtensionActionResponse, TransportAction};
+    use crate::transport::{ThreadContext, TransportTcpHeader};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    struct UppercaseAction;
+
+    #[async_trait]
+    impl TransportAction for UppercaseAction {
+        fn name(&self) -> &str {
+            "cluster:admin/hello/upper"
+        }
+
+        async fn execute(&self, request: Vec<u8>) -> Result<Vec<u8>, ExtensionError> {
+            Ok(request.to_ascii_uppercase())
+        }
+    }
+
+    fn message(action: &str, content: Vec<u8>) -> InboundMessage {
+        InboundMessage {
+            header: TransportTcpHeader::new(1, 0, 1, content.len() as u32, 0),
+            thread_context: ThreadContext::new(),
+            features: vec![],
+            action: Some(action.to_string()),
+            content,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_transport_action() {
+        let mut registry = TransportActionRegistry::new();
+        registry.register(Arc::new(UppercaseAction)).unwrap();
+        let dispatcher = RequestDispatcher::new(registry);
+
+        let mut content = Vec::new();
+        ExtensionActionRequest::new("cluster:admin/hello/upper", b"hello".to_vec())
+            .serialize(&mut content)
+            .unwrap();
+
+        let bytes = dispatcher
+            .dispatch(&message(HANDLE_TRANSPORT_ACTION, content))
+            .await
+            .unwrap();
+        let response = ExtensionActionResponse::deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(response.response_bytes, b"HELLO");
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_verifies_signatures() {
+        let mut registry = TransportActionRegistry::new();
+        registry.register(Arc::new(UppercaseAction)).unwrap();
+        let signer = Arc::new(MessageSigner::new("0123456789abcdef").unwrap());
+        let dispatcher = RequestDispatcher::new(registry).with_signer(signer.clone());
+
+        let mut content = Vec::new();
+        ExtensionActionRequest::new("cluster:admin/hello/upper", b"hello".to_vec())
+            .serialize(&mut content)
+            .unwrap();
+        let mut signed = message(HANDLE_TRANSPORT_ACTION, content.clone());
+        assert!(dispatcher
+            .dispatch(&signed)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("is not signed"));
+
+        signer.sign_request(
+            &mut signed.thread_context,
+            signed.header.request_id,
+            HANDLE_TRANSPORT_ACTION,
+            &content,
+        );
+        assert!(dispatcher.dispatch(&signed).await.is_ok());
+        signed.header.request_id += 1;
+        assert!(dispatcher
+            .dispatch(&signed)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid signature"));
+        signed.header.request_id -= 1;
+        signed.content = b"tampered".to_vec();
+        assert!(dispatcher
+            .dispatch(&signed)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid signature"));
+
+        let mut disguised = message(HANDLE_TRANSPORT_ACTION, content);
+        disguised.header.status = crate::transport::transport_status::STATUS_HANDSHAKE;
+        let error = dispatcher.dispatch(&disguised).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Handshake status on non-handshake action"));
+
+        let mut handshake = message(HANDSHAKE_ACTION, vec![]);
+        handshake.header.status = crate::transport::transport_status::STATUS_HANDSHAKE;
+        assert!(dispatcher.dispatch(&handshake).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_dependency_info() {
+        use crate::extension::dependency::{dependency_request_bytes, ExtensionDependency};
+
+        let info = ExtensionDependencyResponse::new(
+            "hello-world",
+            semver::Version::new(1, 0, 0),
+            vec![ExtensionDependency::from_str("common-utils", "2.0.0").unwrap()],
+        );
+        let dispatcher = RequestDispatcher::default().with_dependency_info(info.clone());
+
+        let bytes = dispatcher
+            .dispatch(&message(
+                EXTENSION_DEPENDENCY_ACTION,
+                dependency_request_bytes("hello-world"),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            ExtensionDependencyResponse::deserialize(&mut bytes.as_slice()).unwrap(),
+            info
+        );
+
+        let other = dispatcher
+            .dispatch(&message(
+                EXTENSION_DEPENDENCY_ACTION,
+                dependency_request_bytes("other"),
+            ))
+            .await;
+        assert!(other.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_init_negotiates_features() {
+        use crate::extension::features::{FeaturesSection, STREAMING_RESPONSES};
+        use crate::extension::init::InitializeExtensionResponse;
+
+        let features = NegotiatedFeatures::default();
+        let mut init = ExtensionInit::new("hello-world", vec![]);
+        init.register(Arc::new(FeaturesSection(features.clone())))
+            .unwrap();
+        let dispatcher = RequestDispatcher::default()
+            .with_init(init)
+            .with_features(features.clone());
+
+        let mut request = message(EXTENSION_INIT_ACTION, vec![]);
+        request.features = vec![STREAMING_RESPONSES.to_string(), "other".to_string()];
+        let bytes = dispatcher.dispatch(&request).await.unwrap();
+        let response = InitializeExtensionResponse::deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(response.state[0].name, "features");
+        assert_eq!(features.enabled(), vec![STREAMING_RESPONSES.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_unknown_action() {
+        let dispatcher = RequestDispatcher::default();
+        let result = dispatcher
+            .dispatch(&message("internal:unknown", vec![]))
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::ExtensionError;
+use crate::interface::{
+    proto_request_bytes, read_bool, read_string, read_vint, write_string, write_vint, Deserialize,
+    Serialize,
+};
+use crate::proto::{ExtensionIdentity, ExtensionRequest, RequestType};
+use crate::transport::TransportClient;
+
+// Spelling matches the action name registered by OpenSearch's ExtensionsManager
+pub const ENVIRONMENT_SETTINGS_ACTION: &str = "internal:discovery/enviornmentsettings";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentSettingsRequest {
+    pub unique_id: String,
+}
+
+impl EnvironmentSettingsRequest {
+    pub fn new(unique_id: impl Into<String>) -> Self {
+        EnvironmentSettingsRequest {
+            unique_id: unique_id.into(),
+        }
+    }
+
+    /// The body of Java's `ExtensionRequest`: the parent task id, then the
+    /// protobuf request as a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        proto_request_bytes(&ExtensionRequest {
+            identity: Some(ExtensionIdentity {
+                unique_id: self.unique_id.clone(),
+            }),
+            request_type: RequestType::RequestExtensionEnvironmentSettings as i32,
+        })
+    }
+}
+
+// Type bytes of Java's `StreamOutput.writeGenericValue`.
+const GENERIC_NULL: u8 = 0xff;
+const GENERIC_STRING: u8 = 0;
+const GENERIC_INT: u8 = 1;
+const GENERIC_LONG: u8 = 2;
+const GENERIC_DOUBLE: u8 = 4;
+const GENERIC_BOOLEAN: u8 = 5;
+const GENERIC_LIST: u8 = 7;
+
+/// Node settings as OpenSearch's `Settings.writeSettingsToStream` sends them:
+/// each value is a Java generic value, in practice a string or a list of
+/// strings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvironmentSettingsResponse {
+    pub settings: HashMap<String, SettingValue>,
+}
+
+impl EnvironmentSettingsResponse {
+    pub fn new(settings: HashMap<String, SettingValue>) -> Self {
+        EnvironmentSettingsResponse { settings }
+    }
+
+    /// Copy the node environment settings into `settings`. Keys the extension
+    /// already configured explicitly are left untouched.
+    pub fn apply_to(&self, settings: &Settings) -> Result<usize, ExtensionError> {
+        let mut applied = 0;
+        for (key, value) in &self.settings {
+            if settings.get(key)?.is_none() {
+                settings.set(key.clone(), value.clone())?;
+                applied += 1;
+            }
+        }
+        Ok(applied)
+    }
+}
+
+impl Serialize for EnvironmentSettingsResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_vint(buf, self.settings.len() as u32)?;
+        for (key, value) in &self.settings {
+            written += write_string(buf, key)?;
+            written += write_generic_value(buf, value)?;
+        }
+        Ok(written)
+    }
+}
+
+fn write_generic_value(buf: &mut impl Write, value: &SettingValue) -> io::Result<usize> {
+    let mut written = 1;
+    match value {
+        SettingValue::String(value) => {
+            buf.write_all(&[GENERIC_STRING])?;
+            written += write_string(buf, value)?;
+        }
+        SettingValue::Integer(value) => {
+            buf.write_all(&[GENERIC_LONG])?;
+            buf.write_all(&value.to_be_bytes())?;
+            written += 8;
+        }
+        SettingValue::Float(value) => {
+            buf.write_all(&[GENERIC_DOUBLE])?;
+            buf.write_all(&value.to_be_bytes())?;
+            written += 8;
+        }
+        SettingValue::Boolean(value) => {
+            buf.write_all(&[GENERIC_BOOLEAN, u8::from(*value)])?;
+            written += 1;
+        }
+        SettingValue::List(values) => {
+            buf.write_all(&[GENERIC_LIST])?;
+            written += write_vint(buf, values.len() as u32)?;
+            for value in values {
+                written += write_generic_value(buf, value)?;
+            }
+        }
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Cannot write setting value {:?} as a generic value", other),
+            ));
+        }
+    }
+    Ok(written)
+}
+
+/// A generic value, or `None` for a Java null. Lists may not nest, as
+/// settings values never do.
+fn read_generic_value(buf: &mut impl Read, in_list: bool) -> io::Result<Option<SettingValue>> {
+    let mut fixed = [0u8; 8];
+    let mut type_byte = [0u8];
+    buf.read_exact(&mut type_byte)?;
+    Ok(Some(match type_byte[0] {
+        GENERIC_NULL => return Ok(None),
+        GENERIC_STRING => SettingValue::String(read_string(buf)?),
+        GENERIC_INT => {
+            buf.read_exact(&mut fixed[..4])?;
+            SettingValue::Integer(
+                i32::from_be_bytes([fixed[0], fixed[1], fixed[2], fixed[3]]) as i64
+            )
+        }
+        GENERIC_LONG => {
+            buf.read_exact(&mut fixed)?;
+            SettingValue::Integer(i64::from_be_bytes(fixed))
+        }
+        GENERIC_DOUBLE => {
+            buf.read_exact(&mut fixed)?;
+            SettingValue::Float(f64::from_be_bytes(fixed))
+        }
+        GENERIC_BOOLEAN => SettingValue::Boolean(read_bool(buf)?),
+        GENERIC_LIST if !in_list => {
+            let count = read_vint(buf)?;
+            let mut values = Vec::new();
+            for _ in 0..count {
+                values.extend(read_generic_value(buf, true)?);
+            }
+            SettingValue::List(values)
+        }
+        other => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsupported generic value type {} in environment settings",
+                    other
+                ),
+            ));
+        }
+    }))
+}
+
+impl Deserialize for EnvironmentSettingsResponse {
+    type Output = EnvironmentSettingsResponse;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let count = read_vint(buf)?;
+        let mut settings = HashMap::new();
+        for _ in 0..count {
+            let key = read_string(buf)?;
+            if let Some(value) = read_generic_value(buf, false)? {
+                settings.insert(key, value);
+            }
+        }
+        Ok(EnvironmentSettingsResponse { settings })
+    }
+}
+
+pub async fn request_environment_settings(
+    client: &TransportClient,
+    unique_id: &str,
+) -> Result<EnvironmentSettingsResponse, ExtensionError> {
+    let request = EnvironmentSettingsRequest::new(unique_id);
+    let response = client
+        .send_request(ENVIRONMENT_SETTINGS_ACTION, &request.to_bytes())
+        .await?;
+
+    EnvironmentSettingsResponse::deserialize(&mut response.as_slice()).map_err(|e| {
+        ExtensionError::serialization(format!(
+            "Failed to deserialize environment settings response: {}",
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_encoding() {
+        use prost::Message;
+
+        let bytes = EnvironmentSettingsRequest::new("hello-world-rs").to_bytes();
+        let proto = ExtensionRequest {
+            identity: Some(ExtensionIdentity {
+                unique_id: "hello-world-rs".to_string(),
+            }),
+            request_type: RequestType::RequestExtensionEnvironmentSettings as i32,
+        }
+        .encode_to_vec();
+        // TransportRequest(StreamInput) reads the parent TaskId, an empty
+        // node id string, then ExtensionRequest reads the proto with readByteArray.
+        let mut expected = vec![0x00, proto.len() as u8];
+        expected.extend_from_slice(&proto);
+        assert_eq!(bytes, expected);
+
+        let decoded: ExtensionRequest = crate::interface::parse_proto_request(&bytes).unwrap();
+        assert_eq!(
+            decoded.request_type(),
+            RequestType::RequestExtensionEnvironmentSettings
+        );
+        assert_eq!(decoded.identity.unwrap().unique_id, "hello-world-rs");
+    }
+
+    #[test]
+    fn test_response_round_trip_and_apply() {
+        let mut values = HashMap::new();
+        values.insert(
+            "path.home".to_string(),
+            SettingValue::String("/usr/share/opensearch".to_string()),
+        );
+        values.insert(
+            "node.name".to_string(),
+            SettingValue::String("node-1".to_string()),
+        );
+        values.insert(
+            "node.roles".to_string(),
+            SettingValue::List(vec![SettingValue::String("data".to_string())]),
+        );
+        let response = EnvironmentSettingsResponse::new(values);
+
+        let mut bytes = Vec::new();
+        response.serialize(&mut bytes).unwrap();
+        let decoded = EnvironmentSettingsResponse::deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, response);
+
+        let settings = Settings::new();
+        settings.set("node.name", "explicit").unwrap();
+
+        let applied = decoded.apply_to(&settings).unwrap();
+        assert_eq!(applied, 2);
+        assert_eq!(
+            settings.get_string("path.home").unwrap(),
+            Some("/usr/share/opensearch".to_string())
+        );
+        assert_eq!(
+            settings.get_string("node.name").unwrap(),
+            Some("explicit".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_java_settings() {
+        // Settings.builder().put("node.name", "node-1").putList("node.roles", "data", "ingest")
+        // .putNull("path.logs"), as Settings.writeSettingsToStream writes it.
+        let bytes = b"\x03\
+            \x09node.name\x00\x06node-1\
+            \x0anode.roles\x07\x02\x00\x04data\x00\x06ingest\
+            \x09path.logs\xff";
+        let response = EnvironmentSettingsResponse::deserialize(&mut &bytes[..]).unwrap();
+
+        assert_eq!(response.settings.len(), 2);
+        assert_eq!(
+            response.settings["node.name"],
+            SettingValue::String("node-1".to_string())
+        );
+        assert_eq!(
+            response.settings["node.roles"],
+            SettingValue::List(vec![
+                SettingValue::String("data".to_string()),
+                SettingValue::String("ingest".to_string())
+            ])
+        );
+
+        let nested = b"\x01\x01a\x07\x01\x07\x00";
+        assert!(EnvironmentSettingsResponse::deserialize(&mut &nested[..]).is_err());
+        let unknown = b"\x01\x01a\x0a\x00";
+        assert!(EnvironmentSettingsResponse::deserialize(&mut &unknown[..]).is_err());
+    }
+}
@@ -1,37 +1,64 @@
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ExtensionError {
     #[error("Initialization failed: {0}")]
     InitializationError(String),
-    
+
     #[error("Transport error: {0}")]
     TransportError(String),
-    
+
     #[error("Configuration error: {0}")]
     ConfigurationError(String),
-    
+
     #[error("Registration failed: {0}")]
     RegistrationError(String),
-    
+
     #[error("Dependency error: {0}")]
     DependencyError(String),
-    
+
     #[error("Shutdown error: {0}")]
     ShutdownError(String),
-    
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
-    
+
     #[error("Serialization error: {0}")]
     SerializationError(String),
-    
+
     #[error("Protocol error: {0}")]
     ProtocolError(String),
-    
+
     #[error("Timeout error: {0}")]
     TimeoutError(String),
-    
+
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+
+    /// The callee is overloaded; `retry_after` is its hint of when to try again.
+    #[error("Throttled: {message}")]
+    Throttled {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+
+    /// A remote call answered with an HTTP error `status`, kept so it can
+    /// be passed on, and the OpenSearch exception type if it named one, e.g.
+    /// `resource_already_exists_exception`. Client errors (4xx) are not
+    /// worth retrying.
+    #[error("Remote error ({status}): {message}")]
+    Remote {
+        status: u16,
+        error_type: Option<String>,
+        message: String,
+    },
+
+    /// The health supervisor gave up on the extension. Binaries exit with
+    /// `UNHEALTHY_EXIT_CODE` on it.
+    #[error("Extension unhealthy: {0}")]
+    Unhealthy(String),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
@@ -40,40 +67,148 @@ impl ExtensionError {
     pub fn initialization<S: Into<String>>(msg: S) -> Self {
         ExtensionError::InitializationError(msg.into())
     }
-    
+
     pub fn transport<S: Into<String>>(msg: S) -> Self {
         ExtensionError::TransportError(msg.into())
     }
-    
+
     pub fn configuration<S: Into<String>>(msg: S) -> Self {
         ExtensionError::ConfigurationError(msg.into())
     }
-    
+
     pub fn registration<S: Into<String>>(msg: S) -> Self {
         ExtensionError::RegistrationError(msg.into())
     }
-    
+
     pub fn dependency<S: Into<String>>(msg: S) -> Self {
         ExtensionError::DependencyError(msg.into())
     }
-    
+
     pub fn shutdown<S: Into<String>>(msg: S) -> Self {
         ExtensionError::ShutdownError(msg.into())
     }
-    
+
     pub fn serialization<S: Into<String>>(msg: S) -> Self {
         ExtensionError::SerializationError(msg.into())
     }
-    
+
     pub fn protocol<S: Into<String>>(msg: S) -> Self {
         ExtensionError::ProtocolError(msg.into())
     }
-    
+
     pub fn timeout<S: Into<String>>(msg: S) -> Self {
         ExtensionError::TimeoutError(msg.into())
     }
-    
+
+    pub fn invalid_request<S: Into<String>>(msg: S) -> Self {
+        ExtensionError::InvalidRequest(msg.into())
+    }
+
     pub fn unknown<S: Into<String>>(msg: S) -> Self {
         ExtensionError::Unknown(msg.into())
     }
-}
\ No newline at end of file
+
+    pub fn throttled<S: Into<String>>(msg: S, retry_after: Option<Duration>) -> Self {
+        ExtensionError::Throttled {
+            message: msg.into(),
+            retry_after,
+        }
+    }
+
+    pub fn remote<S: Into<String>>(status: u16, msg: S) -> Self {
+        ExtensionError::Remote {
+            status,
+            error_type: None,
+            message: msg.into(),
+        }
+    }
+
+    /// A `Remote` error OpenSearch reported as an exception of `error_type`.
+    pub fn remote_exception<S: Into<String>>(
+        status: u16,
+        error_type: impl Into<String>,
+        msg: S,
+    ) -> Self {
+        ExtensionError::Remote {
+            status,
+            error_type: Some(error_type.into()),
+            message: msg.into(),
+        }
+    }
+
+    /// Whether this is a `Remote` error with `status` and exception `error_type`.
+    pub fn is_remote_exception(&self, status: u16, error_type: &str) -> bool {
+        matches!(self, ExtensionError::Remote { status: s, error_type: Some(t), .. } if *s == status && t == error_type)
+    }
+
+    pub fn unhealthy<S: Into<String>>(msg: S) -> Self {
+        ExtensionError::Unhealthy(msg.into())
+    }
+
+    /// Whether the failed operation is worth another attempt. Errors in the
+    /// request or the extension's own setup fail the same way every time.
+    pub fn retryable(&self) -> Retryable {
+        match self {
+            ExtensionError::Throttled { retry_after, .. } => Retryable::Throttled(*retry_after),
+            ExtensionError::Remote {
+                status: 400..=499, ..
+            } => Retryable::NonRetryable,
+            ExtensionError::Remote { .. } => Retryable::Retryable,
+            ExtensionError::ConfigurationError(_)
+            | ExtensionError::InitializationError(_)
+            | ExtensionError::InvalidRequest(_)
+            | ExtensionError::SerializationError(_)
+            | ExtensionError::ProtocolError(_)
+            | ExtensionError::ShutdownError(_)
+            | ExtensionError::Unhealthy(_) => Retryable::NonRetryable,
+            ExtensionError::TransportError(_)
+            | ExtensionError::TimeoutError(_)
+            | ExtensionError::IoError(_)
+            | ExtensionError::DependencyError(_)
+            | ExtensionError::RegistrationError(_)
+            | ExtensionError::Unknown(_) => Retryable::Retryable,
+        }
+    }
+}
+
+/// How a retry loop should treat an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retryable {
+    Retryable,
+    NonRetryable,
+    /// Retry, but not before the hinted delay when there is one.
+    Throttled(Option<Duration>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retryable_classification() {
+        assert_eq!(
+            ExtensionError::transport("connection reset").retryable(),
+            Retryable::Retryable
+        );
+        assert_eq!(
+            ExtensionError::configuration("missing port").retryable(),
+            Retryable::NonRetryable
+        );
+        assert_eq!(
+            ExtensionError::invalid_request("bad body").retryable(),
+            Retryable::NonRetryable
+        );
+        assert_eq!(
+            ExtensionError::remote(404, "no such index").retryable(),
+            Retryable::NonRetryable
+        );
+        assert_eq!(
+            ExtensionError::remote(503, "no master").retryable(),
+            Retryable::Retryable
+        );
+        assert_eq!(
+            ExtensionError::throttled("queue full", Some(Duration::from_secs(2))).retryable(),
+            Retryable::Throttled(Some(Duration::from_secs(2)))
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+use tracing::warn;
+
+use crate::extension::health::HealthStatus;
+use crate::extension::lifecycle::{ExtensionState, StateListener};
+use crate::extension::resilience::CircuitState;
+
+/// Events buffered per subscriber before the oldest are dropped.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Something that happened inside the SDK.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SdkEvent {
+    StateChanged {
+        from: ExtensionState,
+        to: ExtensionState,
+    },
+    Registered {
+        cluster_name: Option<String>,
+    },
+    RegistrationFailed {
+        error: String,
+    },
+    CircuitStateChanged {
+        breaker: String,
+        from: CircuitState,
+        to: CircuitState,
+    },
+    HealthChanged {
+        check: String,
+        from: HealthStatus,
+        to: HealthStatus,
+    },
+    ConnectionError {
+        peer: String,
+        error: String,
+    },
+}
+
+/// Publishes `SdkEvent`s to every subscriber. Publishing never blocks:
+/// a subscriber that falls more than the bus capacity behind misses the
+/// oldest events.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<SdkEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        EventBus { sender }
+    }
+
+    /// Send `event` to the current subscribers, returning how many there are.
+    pub fn publish(&self, event: SdkEvent) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Receive the events published from now on.
+    pub fn subscribe(&self) -> EventSubscription {
+        EventSubscription {
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<SdkEvent>,
+}
+
+impl EventSubscription {
+    /// Wait for the next event. `None` once every `EventBus` handle is gone.
+    pub async fn recv(&mut self) -> Option<SdkEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Event subscriber lagged, missed {} events", missed)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The next event if one is waiting.
+    pub fn try_recv(&mut self) -> Option<SdkEvent> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Lagged(missed)) => {
+                    warn!("Event subscriber lagged, missed {} events", missed)
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Publishes lifecycle transitions as `SdkEvent::StateChanged`.
+pub struct EventStateListener(pub EventBus);
+
+#[async_trait::async_trait]
+impl StateListener for EventStateListener {
+    async fn on_state_change(&self, old_state: ExtensionState, new_state: ExtensionState) {
+        self.0.publish(SdkEvent::StateChanged {
+            from: old_state,
+            to: new_state,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::health::HealthService;
+    use crate::extension::lifecycle::LifecycleManager;
+    use crate::extension::CircuitBreaker;
+    use crate::extension::ExtensionError;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_sdk_events() {
+        let bus = EventBus::new();
+        let mut events = bus.subscribe();
+        assert_eq!(bus.subscriber_count(), 1);
+
+        let lifecycle = LifecycleManager::new();
+        lifecycle
+            .add_listener(Box::new(EventStateListener(bus.clone())))
+            .await;
+        lifecycle
+            .transition_to(ExtensionState::Initializing)
+            .await
+            .unwrap();
+        assert_eq!(
+            events.recv().await,
+            Some(SdkEvent::StateChanged {
+                from: ExtensionState::Created,
+                to: ExtensionState::Initializing
+            })
+        );
+
+        let breaker =
+            CircuitBreaker::new(1, 1, Duration::from_secs(60)).with_events("cluster", bus.clone());
+        let _ = breaker
+            .call(|| async { Err::<(), _>(ExtensionError::unknown("down")) })
+            .await;
+        assert_eq!(
+            events.recv().await,
+            Some(SdkEvent::CircuitStateChanged {
+                breaker: "cluster".to_string(),
+                from: CircuitState::Closed,
+                to: CircuitState::Open
+            })
+        );
+
+        let health = HealthService::new().with_events(bus.clone());
+        health.register_check("index").await;
+        health
+            .update_check("index", HealthStatus::Healthy, None)
+            .await
+            .unwrap();
+        health
+            .update_check("index", HealthStatus::Degraded, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            events.recv().await,
+            Some(SdkEvent::HealthChanged {
+                check: "index".to_string(),
+                from: HealthStatus::Healthy,
+                to: HealthStatus::Degraded
+            })
+        );
+        assert_eq!(events.try_recv(), None);
+
+        drop((bus, lifecycle, breaker, health));
+        assert_eq!(events.recv().await, None);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::extension::init::InitStateProvider;
+use crate::extension::ExtensionError;
+use crate::interface::write_string_array;
+
+/// Large responses are streamed from a temporary file rather than sent
+/// from memory. See `ResponseSpooler`.
+pub const STREAMING_RESPONSES: &str = "extension.streaming_responses";
+
+/// Features this SDK implements and advertises by default.
+pub const SDK_FEATURES: &[&str] = &[STREAMING_RESPONSES];
+
+/// Features the extension supports and, once a node has sent its own in
+/// the handshake or init request, those both sides agreed on. Cloning
+/// shares the negotiated state.
+#[derive(Debug, Clone)]
+pub struct NegotiatedFeatures {
+    supported: Arc<BTreeSet<String>>,
+    negotiated: Arc<RwLock<Option<BTreeSet<String>>>>,
+}
+
+impl Default for NegotiatedFeatures {
+    fn default() -> Self {
+        Self::new(SDK_FEATURES.iter().map(|feature| feature.to_string()))
+    }
+}
+
+impl NegotiatedFeatures {
+    pub fn new(supported: impl IntoIterator<Item = String>) -> Self {
+        NegotiatedFeatures {
+            supported: Arc::new(supported.into_iter().collect()),
+            negotiated: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn supported(&self) -> Vec<String> {
+        self.supported.iter().cloned().collect()
+    }
+
+    /// Agree on the features supported by both the extension and a node
+    /// advertising `remote`, replacing any earlier negotiation.
+    pub fn negotiate(&self, remote: &[String]) -> Vec<String> {
+        let agreed: BTreeSet<String> = remote
+            .iter()
+            .filter(|feature| self.supported.contains(*feature))
+            .cloned()
+            .collect();
+        let features = agreed.iter().cloned().collect();
+        *self
+            .negotiated
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(agreed);
+        features
+    }
+
+    pub fn is_negotiated(&self) -> bool {
+        self.negotiated
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    /// Whether `feature` may be used. Nothing is enabled before negotiation.
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        self.negotiated
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .is_some_and(|agreed| agreed.contains(feature))
+    }
+
+    pub fn enabled(&self) -> Vec<String> {
+        self.negotiated
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Advertises the extension's supported features to OpenSearch as the
+/// `features` section of the init response.
+pub struct FeaturesSection(pub NegotiatedFeatures);
+
+impl InitStateProvider for FeaturesSection {
+    fn name(&self) -> &str {
+        "features"
+    }
+
+    fn serialize(&self, _transport_version: u32) -> Result<Vec<u8>, ExtensionError> {
+        let mut payload = Vec::new();
+        write_string_array(&mut payload, &self.0.supported()).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize features: {}", e))
+        })?;
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::read_string_array;
+
+    #[test]
+    fn test_negotiate_features() {
+        let features = NegotiatedFeatures::new(["a".to_string(), STREAMING_RESPONSES.to_string()]);
+        let shared = features.clone();
+        assert!(!features.is_enabled(STREAMING_RESPONSES));
+
+        let agreed = features.negotiate(&[STREAMING_RESPONSES.to_string(), "b".to_string()]);
+        assert_eq!(agreed, vec![STREAMING_RESPONSES.to_string()]);
+        assert!(shared.is_negotiated());
+        assert!(shared.is_enabled(STREAMING_RESPONSES));
+        assert!(!shared.is_enabled("a"));
+
+        let payload = FeaturesSection(features).serialize(0).unwrap();
+        assert_eq!(
+            read_string_array(&mut payload.as_slice()).unwrap(),
+            vec!["a", STREAMING_RESPONSES]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::extension::ExtensionError;
+
+/// Request header carrying the principal token on calls made on behalf of a user.
+pub const PRINCIPAL_HEADER: &str = "X-Extension-Principal";
+
+/// The token OpenSearch forwards with a REST request to identify the user
+/// it authenticated. OpenSearch has already verified it, so signatures are
+/// not checked here.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrincipalIdentifier {
+    token: String,
+}
+
+impl PrincipalIdentifier {
+    pub fn new(token: impl Into<String>) -> Self {
+        PrincipalIdentifier {
+            token: token.into(),
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// The user the token identifies. Tokens are either JWTs, whose `sub`,
+    /// `roles` and `backend_roles` claims are read, or security plugin user
+    /// info of the form `name|backend_roles|roles|tenant` (a bare name is
+    /// also accepted).
+    pub fn user(&self) -> Result<User, ExtensionError> {
+        let token = self.token.trim();
+        let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+        if token.is_empty() {
+            return Err(ExtensionError::invalid_request("Principal token is empty"));
+        }
+        match token.split('.').collect::<Vec<_>>()[..] {
+            [_, payload, _] if !token.contains('|') => User::from_jwt_payload(payload),
+            _ => Ok(User::from_user_info(token)),
+        }
+    }
+}
+
+/// An authenticated user on whose behalf a request runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct User {
+    pub name: String,
+    pub backend_roles: Vec<String>,
+    pub roles: Vec<String>,
+    pub requested_tenant: Option<String>,
+    /// Remaining string claims of a JWT.
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl User {
+    pub fn new(name: impl Into<String>) -> Self {
+        User {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    fn from_user_info(info: &str) -> Self {
+        let mut parts = info.split('|');
+        let list = |part: Option<&str>| -> Vec<String> {
+            part.unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        User {
+            name: parts.next().unwrap_or_default().to_string(),
+            backend_roles: list(parts.next()),
+            roles: list(parts.next()),
+            requested_tenant: parts.next().filter(|t| !t.is_empty()).map(String::from),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    fn from_jwt_payload(payload: &str) -> Result<Self, ExtensionError> {
+        let invalid = |reason: String| {
+            ExtensionError::invalid_request(format!("Invalid principal token: {}", reason))
+        };
+        let bytes = decode_base64url(payload)
+            .ok_or_else(|| invalid("payload is not base64url".to_string()))?;
+        let claims: BTreeMap<String, Value> =
+            serde_json::from_slice(&bytes).map_err(|e| invalid(e.to_string()))?;
+
+        let list = |claim: &str| -> Vec<String> {
+            match claims.get(claim) {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(String::from)
+                    .collect(),
+                Some(Value::String(s)) => s
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+        let name = claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("missing 'sub' claim".to_string()))?;
+        let attributes = claims
+            .iter()
+            .filter(|(claim, _)| {
+                !matches!(claim.as_str(), "sub" | "roles" | "backend_roles" | "tenant")
+            })
+            .filter_map(|(claim, value)| {
+                value
+                    .as_str()
+                    .map(|value| (claim.clone(), value.to_string()))
+            })
+            .collect();
+        Ok(User {
+            name: name.to_string(),
+            backend_roles: list("backend_roles"),
+            roles: list("roles"),
+            requested_tenant: claims
+                .get("tenant")
+                .and_then(Value::as_str)
+                .map(String::from),
+            attributes,
+        })
+    }
+}
+
+/// Decode unpadded (or padded) base64url, as used by JWTs.
+pub(crate) fn decode_base64url(input: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in input.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'-' | b'+' => 62,
+            b'_' | b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_principal_tokens() {
+        let user = PrincipalIdentifier::new("alice|ldap-admins,ops|admin,reader|analytics")
+            .user()
+            .unwrap();
+        assert_eq!(user.name, "alice");
+        assert_eq!(user.backend_roles, vec!["ldap-admins", "ops"]);
+        assert!(user.has_role("reader"));
+        assert_eq!(user.requested_tenant.as_deref(), Some("analytics"));
+        assert_eq!(
+            PrincipalIdentifier::new("admin").user().unwrap(),
+            User::new("admin")
+        );
+
+        // {"sub":"bob","roles":["reader"],"email":"bob@example.com"}
+        let jwt = "Bearer eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiJib2IiLCJyb2xlcyI6WyJyZWFkZXIiXSwiZW1haWwiOiJib2JAZXhhbXBsZS5jb20ifQ.c2ln";
+        let user = PrincipalIdentifier::new(jwt).user().unwrap();
+        assert_eq!(user.name, "bob");
+        assert_eq!(user.roles, vec!["reader"]);
+        assert_eq!(user.attributes["email"], "bob@example.com");
+
+        assert!(PrincipalIdentifier::new("a.!!!.c").user().is_err());
+        assert!(PrincipalIdentifier::new("").user().is_err());
+    }
+}
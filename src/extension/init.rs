@@ -0,0 +1,210 @@
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use crate::extension::ExtensionError;
+use crate::interface::{
+    read_byte_array, read_string, read_string_array, read_vint, write_byte_array, write_string,
+    write_string_array, write_vint, Deserialize, Serialize,
+};
+
+pub const EXTENSION_INIT_ACTION: &str = "internal:discovery/extensions";
+
+/// Largest payload a single section may carry.
+pub const DEFAULT_MAX_SECTION_BYTES: usize = 1024 * 1024;
+
+/// Contributes a named section of custom state, such as processor configs,
+/// to the init response sent to OpenSearch.
+pub trait InitStateProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Oldest transport version that understands this section. Older peers
+    /// do not receive it.
+    fn min_transport_version(&self) -> u32 {
+        0
+    }
+
+    /// Serialize the state for a peer speaking `transport_version`.
+    fn serialize(&self, transport_version: u32) -> Result<Vec<u8>, ExtensionError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitStateSection {
+    pub name: String,
+    /// Transport version the payload was serialized for.
+    pub version: u32,
+    pub payload: Vec<u8>,
+}
+
+/// Reply to `internal:discovery/extensions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitializeExtensionResponse {
+    pub name: String,
+    pub implemented_interfaces: Vec<String>,
+    pub state: Vec<InitStateSection>,
+}
+
+impl Serialize for InitializeExtensionResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_string(buf, &self.name)?;
+        written += write_string_array(buf, &self.implemented_interfaces)?;
+        written += write_vint(buf, self.state.len() as u32)?;
+        for section in &self.state {
+            written += write_string(buf, &section.name)?;
+            written += write_vint(buf, section.version)?;
+            written += write_byte_array(buf, &section.payload)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Deserialize for InitializeExtensionResponse {
+    type Output = Self;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self> {
+        let name = read_string(buf)?;
+        let implemented_interfaces = read_string_array(buf)?;
+        let count = read_vint(buf)?;
+        let mut state = Vec::new();
+        for _ in 0..count {
+            state.push(InitStateSection {
+                name: read_string(buf)?,
+                version: read_vint(buf)?,
+                payload: read_byte_array(buf)?,
+            });
+        }
+        Ok(InitializeExtensionResponse {
+            name,
+            implemented_interfaces,
+            state,
+        })
+    }
+}
+
+/// Builds the init response, collecting custom state from the registered
+/// providers for the version of the requesting node.
+#[derive(Clone)]
+pub struct ExtensionInit {
+    name: String,
+    implemented_interfaces: Vec<String>,
+    providers: Vec<Arc<dyn InitStateProvider>>,
+    max_section_bytes: usize,
+}
+
+impl ExtensionInit {
+    pub fn new(name: impl Into<String>, implemented_interfaces: Vec<String>) -> Self {
+        ExtensionInit {
+            name: name.into(),
+            implemented_interfaces,
+            providers: Vec::new(),
+            max_section_bytes: DEFAULT_MAX_SECTION_BYTES,
+        }
+    }
+
+    pub fn with_max_section_bytes(mut self, max_section_bytes: usize) -> Self {
+        self.max_section_bytes = max_section_bytes;
+        self
+    }
+
+    pub fn register(&mut self, provider: Arc<dyn InitStateProvider>) -> Result<(), ExtensionError> {
+        let name = provider.name();
+        if name.is_empty() {
+            return Err(ExtensionError::configuration(
+                "Init state section name must not be empty",
+            ));
+        }
+        if self.providers.iter().any(|p| p.name() == name) {
+            return Err(ExtensionError::configuration(format!(
+                "Init state section '{}' is already registered",
+                name
+            )));
+        }
+        self.providers.push(provider);
+        Ok(())
+    }
+
+    pub fn response(
+        &self,
+        transport_version: u32,
+    ) -> Result<InitializeExtensionResponse, ExtensionError> {
+        let mut state = Vec::new();
+        for provider in &self.providers {
+            if transport_version < provider.min_transport_version() {
+                continue;
+            }
+            let payload = provider.serialize(transport_version)?;
+            if payload.len() > self.max_section_bytes {
+                return Err(ExtensionError::serialization(format!(
+                    "Init state section '{}' is {} bytes, more than the limit of {}",
+                    provider.name(),
+                    payload.len(),
+                    self.max_section_bytes
+                )));
+            }
+            state.push(InitStateSection {
+                name: provider.name().to_string(),
+                version: transport_version,
+                payload,
+            });
+        }
+
+        Ok(InitializeExtensionResponse {
+            name: self.name.clone(),
+            implemented_interfaces: self.implemented_interfaces.clone(),
+            state,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ProcessorConfigs(usize);
+
+    impl InitStateProvider for ProcessorConfigs {
+        fn name(&self) -> &str {
+            "processors"
+        }
+
+        fn min_transport_version(&self) -> u32 {
+            2
+        }
+
+        fn serialize(&self, transport_version: u32) -> Result<Vec<u8>, ExtensionError> {
+            let mut payload = vec![transport_version as u8];
+            payload.resize(self.0, 0);
+            Ok(payload)
+        }
+    }
+
+    #[test]
+    fn test_init_state_sections() {
+        let mut init = ExtensionInit::new("hello-world", vec!["ActionExtension".to_string()])
+            .with_max_section_bytes(8);
+        init.register(Arc::new(ProcessorConfigs(4))).unwrap();
+        assert!(init.register(Arc::new(ProcessorConfigs(4))).is_err());
+
+        assert!(init.response(1).unwrap().state.is_empty());
+
+        let response = init.response(3).unwrap();
+        assert_eq!(
+            response.state,
+            vec![InitStateSection {
+                name: "processors".to_string(),
+                version: 3,
+                payload: vec![3, 0, 0, 0],
+            }]
+        );
+
+        let mut bytes = Vec::new();
+        response.serialize(&mut bytes).unwrap();
+        assert_eq!(
+            InitializeExtensionResponse::deserialize(&mut bytes.as_slice()).unwrap(),
+            response
+        );
+
+        let mut oversized = ExtensionInit::new("hello-world", vec![]).with_max_section_bytes(8);
+        oversized.register(Arc::new(ProcessorConfigs(9))).unwrap();
+        assert!(oversized.response(3).is_err());
+    }
+}
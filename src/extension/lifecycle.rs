@@ -1,13 +1,20 @@
-use std::sync::Arc;
-use tokio::sync::RwLock;
 use crate::extension::ExtensionError;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, RwLock};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ExtensionState {
     Created,
     Initializing,
     Initialized,
     Running,
+    /// Registered but refusing new requests, e.g. during maintenance.
+    Paused,
+    /// Lost the cluster and registering again.
+    Reconnecting,
     Stopping,
     Stopped,
     Failed,
@@ -15,32 +22,55 @@ pub enum ExtensionState {
 
 impl ExtensionState {
     pub fn can_transition_to(&self, next: ExtensionState) -> bool {
-        match (*self, next) {
-            (ExtensionState::Created, ExtensionState::Initializing) => true,
-            (ExtensionState::Initializing, ExtensionState::Initialized) => true,
-            (ExtensionState::Initializing, ExtensionState::Failed) => true,
-            (ExtensionState::Initialized, ExtensionState::Running) => true,
-            (ExtensionState::Initialized, ExtensionState::Stopping) => true,
-            (ExtensionState::Running, ExtensionState::Stopping) => true,
-            (ExtensionState::Stopping, ExtensionState::Stopped) => true,
-            (ExtensionState::Stopping, ExtensionState::Failed) => true,
-            (_, ExtensionState::Failed) => true,
-            _ => false,
-        }
+        matches!(
+            (*self, next),
+            (ExtensionState::Created, ExtensionState::Initializing)
+                | (ExtensionState::Initializing, ExtensionState::Initialized)
+                | (ExtensionState::Initializing, ExtensionState::Failed)
+                | (ExtensionState::Initialized, ExtensionState::Running)
+                | (ExtensionState::Initialized, ExtensionState::Stopping)
+                | (ExtensionState::Running, ExtensionState::Stopping)
+                | (ExtensionState::Running, ExtensionState::Paused)
+                | (ExtensionState::Paused, ExtensionState::Running)
+                | (ExtensionState::Paused, ExtensionState::Stopping)
+                | (ExtensionState::Running, ExtensionState::Reconnecting)
+                // Taken by the reconnect watchdog once registered again.
+                | (ExtensionState::Reconnecting, ExtensionState::Running)
+                | (ExtensionState::Reconnecting, ExtensionState::Stopping)
+                | (ExtensionState::Stopping, ExtensionState::Stopped)
+                | (ExtensionState::Stopping, ExtensionState::Failed)
+                | (_, ExtensionState::Failed)
+        )
     }
-    
+
     pub fn is_terminal(&self) -> bool {
         matches!(self, ExtensionState::Stopped | ExtensionState::Failed)
     }
-    
+
     pub fn is_running(&self) -> bool {
         matches!(self, ExtensionState::Running)
     }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self, ExtensionState::Paused)
+    }
+
+    /// Started and not yet stopping: running, paused or reconnecting.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            ExtensionState::Running | ExtensionState::Paused | ExtensionState::Reconnecting
+        )
+    }
 }
 
+/// Tracks the extension's state. Besides listeners and hooks, anyone can
+/// `subscribe` to the state or `await_state` a particular one.
 pub struct LifecycleManager {
-    state: Arc<RwLock<ExtensionState>>,
+    state: watch::Sender<ExtensionState>,
     state_listeners: Arc<RwLock<Vec<Box<dyn StateListener>>>>,
+    hooks: RwLock<Vec<Arc<dyn TransitionHook>>>,
+    transitioning: Mutex<()>,
 }
 
 #[async_trait::async_trait]
@@ -48,52 +78,154 @@ pub trait StateListener: Send + Sync {
     async fn on_state_change(&self, old_state: ExtensionState, new_state: ExtensionState);
 }
 
+/// Code run around state transitions. Unlike a `StateListener`, a hook
+/// runs before the change too and can veto it.
+#[async_trait::async_trait]
+pub trait TransitionHook: Send + Sync {
+    /// Runs before the state changes; an error cancels the transition.
+    async fn before(
+        &self,
+        _from: ExtensionState,
+        _to: ExtensionState,
+    ) -> Result<(), ExtensionError> {
+        Ok(())
+    }
+
+    /// Runs after the state changed. `failure` is the error passed to
+    /// `LifecycleManager::fail`, if that caused the transition.
+    async fn after(
+        &self,
+        _from: ExtensionState,
+        _to: ExtensionState,
+        _failure: Option<&ExtensionError>,
+    ) {
+    }
+}
+
 impl LifecycleManager {
     pub fn new() -> Self {
         LifecycleManager {
-            state: Arc::new(RwLock::new(ExtensionState::Created)),
+            state: watch::Sender::new(ExtensionState::Created),
             state_listeners: Arc::new(RwLock::new(Vec::new())),
+            hooks: RwLock::new(Vec::new()),
+            transitioning: Mutex::new(()),
         }
     }
-    
+
     pub async fn current_state(&self) -> ExtensionState {
-        *self.state.read().await
+        *self.state.borrow()
+    }
+
+    /// A receiver of the current state and every change after it.
+    pub fn subscribe(&self) -> watch::Receiver<ExtensionState> {
+        self.state.subscribe()
+    }
+
+    /// Wait until the extension is in `target`. Fails once `timeout` passes
+    /// or when it ends up in another terminal state instead.
+    pub async fn await_state(
+        &self,
+        target: ExtensionState,
+        timeout: Duration,
+    ) -> Result<(), ExtensionError> {
+        let mut states = self.subscribe();
+        let reached = tokio::time::timeout(
+            timeout,
+            states.wait_for(|state| *state == target || state.is_terminal()),
+        )
+        .await
+        .map_err(|_| {
+            ExtensionError::timeout(format!("Extension not {:?} after {:?}", target, timeout))
+        })?
+        .map(|state| *state)
+        .map_err(|_| ExtensionError::unknown("Lifecycle manager dropped"))?;
+        if reached != target {
+            return Err(ExtensionError::unknown(format!(
+                "Extension ended {:?} instead of {:?}",
+                reached, target
+            )));
+        }
+        Ok(())
     }
-    
+
     pub async fn transition_to(&self, new_state: ExtensionState) -> Result<(), ExtensionError> {
-        let mut current = self.state.write().await;
-        
-        if !current.can_transition_to(new_state) {
-            return Err(ExtensionError::initialization(
-                format!("Invalid state transition from {:?} to {:?}", *current, new_state)
-            ));
+        self.transition(None, new_state, None).await
+    }
+
+    /// Move to `Failed` because of `error`, which hooks receive.
+    pub async fn fail(&self, error: &ExtensionError) -> Result<(), ExtensionError> {
+        self.transition(None, ExtensionState::Failed, Some(error))
+            .await
+    }
+
+    /// Move to `new_state`, only from `from` if given.
+    async fn transition(
+        &self,
+        from: Option<ExtensionState>,
+        new_state: ExtensionState,
+        failure: Option<&ExtensionError>,
+    ) -> Result<(), ExtensionError> {
+        let _transitioning = self.transitioning.lock().await;
+        let old_state = self.current_state().await;
+
+        if from.is_some_and(|from| from != old_state) || !old_state.can_transition_to(new_state) {
+            return Err(ExtensionError::initialization(format!(
+                "Invalid state transition from {:?} to {:?}",
+                old_state, new_state
+            )));
         }
-        
-        let old_state = *current;
-        *current = new_state;
-        drop(current);
-        
+
+        let hooks = self.hooks.read().await.clone();
+        for hook in &hooks {
+            hook.before(old_state, new_state).await?;
+        }
+
+        self.state.send_replace(new_state);
+
         self.notify_listeners(old_state, new_state).await;
-        
+        for hook in &hooks {
+            hook.after(old_state, new_state, failure).await;
+        }
+
         Ok(())
     }
-    
+
+    pub async fn add_hook(&self, hook: Arc<dyn TransitionHook>) {
+        self.hooks.write().await.push(hook);
+    }
+
     pub async fn add_listener(&self, listener: Box<dyn StateListener>) {
         let mut listeners = self.state_listeners.write().await;
         listeners.push(listener);
     }
-    
+
     async fn notify_listeners(&self, old_state: ExtensionState, new_state: ExtensionState) {
         let listeners = self.state_listeners.read().await;
         for listener in listeners.iter() {
             listener.on_state_change(old_state, new_state).await;
         }
     }
-    
+
     pub async fn is_running(&self) -> bool {
         self.current_state().await.is_running()
     }
-    
+
+    pub async fn is_paused(&self) -> bool {
+        self.current_state().await.is_paused()
+    }
+
+    /// Stop serving new requests while staying registered.
+    pub async fn pause(&self) -> Result<(), ExtensionError> {
+        self.transition_to(ExtensionState::Paused).await
+    }
+
+    /// Serve requests again after `pause`. Only a paused extension can be
+    /// resumed; a reconnecting one runs again once it has registered.
+    pub async fn resume(&self) -> Result<(), ExtensionError> {
+        self.transition(Some(ExtensionState::Paused), ExtensionState::Running, None)
+            .await
+    }
+
     pub async fn is_terminal(&self) -> bool {
         self.current_state().await.is_terminal()
     }
@@ -122,7 +254,7 @@ impl StateListener for LoggingStateListener {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn test_state_transitions() {
         assert!(ExtensionState::Created.can_transition_to(ExtensionState::Initializing));
@@ -130,24 +262,176 @@ mod tests {
         assert!(ExtensionState::Initialized.can_transition_to(ExtensionState::Running));
         assert!(ExtensionState::Running.can_transition_to(ExtensionState::Stopping));
         assert!(ExtensionState::Stopping.can_transition_to(ExtensionState::Stopped));
-        
+
+        assert!(ExtensionState::Running.can_transition_to(ExtensionState::Paused));
+        assert!(ExtensionState::Paused.can_transition_to(ExtensionState::Running));
+        assert!(ExtensionState::Paused.can_transition_to(ExtensionState::Stopping));
+        assert!(ExtensionState::Running.can_transition_to(ExtensionState::Reconnecting));
+        assert!(ExtensionState::Reconnecting.can_transition_to(ExtensionState::Running));
+
         assert!(!ExtensionState::Created.can_transition_to(ExtensionState::Running));
+        assert!(!ExtensionState::Initialized.can_transition_to(ExtensionState::Paused));
         assert!(!ExtensionState::Stopped.can_transition_to(ExtensionState::Running));
     }
-    
+
     #[tokio::test]
     async fn test_lifecycle_manager() {
         let manager = LifecycleManager::new();
-        
+
         assert_eq!(manager.current_state().await, ExtensionState::Created);
-        
-        manager.transition_to(ExtensionState::Initializing).await.unwrap();
+
+        manager
+            .transition_to(ExtensionState::Initializing)
+            .await
+            .unwrap();
         assert_eq!(manager.current_state().await, ExtensionState::Initializing);
-        
-        manager.transition_to(ExtensionState::Initialized).await.unwrap();
+
+        manager
+            .transition_to(ExtensionState::Initialized)
+            .await
+            .unwrap();
         assert_eq!(manager.current_state().await, ExtensionState::Initialized);
-        
+
         let result = manager.transition_to(ExtensionState::Created).await;
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_await_state() {
+        let manager = Arc::new(LifecycleManager::new());
+        let mut states = manager.subscribe();
+        let starting = manager.clone();
+        tokio::spawn(async move {
+            for state in [
+                ExtensionState::Initializing,
+                ExtensionState::Initialized,
+                ExtensionState::Running,
+            ] {
+                starting.transition_to(state).await.unwrap();
+            }
+        });
+        manager
+            .await_state(ExtensionState::Running, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(*states.borrow_and_update(), ExtensionState::Running);
+
+        let error = manager
+            .await_state(ExtensionState::Stopped, Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ExtensionError::TimeoutError(_)));
+
+        manager
+            .fail(&ExtensionError::transport("connection reset"))
+            .await
+            .unwrap();
+        assert!(states.has_changed().unwrap());
+        let error = manager
+            .await_state(ExtensionState::Stopped, Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown error: Extension ended Failed instead of Stopped"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_only_when_paused() {
+        let manager = LifecycleManager::new();
+        for state in [
+            ExtensionState::Initializing,
+            ExtensionState::Initialized,
+            ExtensionState::Running,
+        ] {
+            manager.transition_to(state).await.unwrap();
+        }
+        manager.pause().await.unwrap();
+        manager.resume().await.unwrap();
+        assert_eq!(manager.current_state().await, ExtensionState::Running);
+
+        manager
+            .transition_to(ExtensionState::Reconnecting)
+            .await
+            .unwrap();
+        let error = manager.resume().await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Initialization failed: Invalid state transition from Reconnecting to Running"
+        );
+        assert_eq!(manager.current_state().await, ExtensionState::Reconnecting);
+        manager
+            .transition_to(ExtensionState::Running)
+            .await
+            .unwrap();
+    }
+
+    struct RecordingHook(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl TransitionHook for RecordingHook {
+        async fn before(
+            &self,
+            from: ExtensionState,
+            to: ExtensionState,
+        ) -> Result<(), ExtensionError> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("before {:?}->{:?}", from, to));
+            if to == ExtensionState::Running {
+                return Err(ExtensionError::initialization("cache warm-up failed"));
+            }
+            Ok(())
+        }
+
+        async fn after(
+            &self,
+            from: ExtensionState,
+            to: ExtensionState,
+            failure: Option<&ExtensionError>,
+        ) {
+            let failure = failure.map(|e| format!(" ({})", e)).unwrap_or_default();
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("after {:?}->{:?}{}", from, to, failure));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_around_transitions() {
+        let manager = LifecycleManager::new();
+        let hook = Arc::new(RecordingHook(std::sync::Mutex::new(Vec::new())));
+        manager.add_hook(hook.clone()).await;
+
+        manager
+            .transition_to(ExtensionState::Initializing)
+            .await
+            .unwrap();
+        manager
+            .transition_to(ExtensionState::Initialized)
+            .await
+            .unwrap();
+        let error = manager
+            .transition_to(ExtensionState::Running)
+            .await
+            .unwrap_err();
+        assert_eq!(manager.current_state().await, ExtensionState::Initialized);
+        manager.fail(&error).await.unwrap();
+
+        assert_eq!(
+            *hook.0.lock().unwrap(),
+            vec![
+                "before Created->Initializing",
+                "after Created->Initializing",
+                "before Initializing->Initialized",
+                "after Initializing->Initialized",
+                "before Initialized->Running",
+                "before Initialized->Failed",
+                "after Initialized->Failed (Initialization failed: cache warm-up failed)",
+            ]
+        );
+    }
+}
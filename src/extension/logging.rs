@@ -0,0 +1,306 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use tracing::level_filters::LevelFilter;
+use tracing::{info_span, Span, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::extension::setting::Setting;
+use crate::extension::ExtensionError;
+
+/// Filter used when `RUST_LOG` is not set.
+const DEFAULT_FILTER: &str = "info";
+
+static LOGGING: OnceLock<LoggingControl> = OnceLock::new();
+
+/// `text` for people reading a terminal, `json` for log aggregation.
+pub fn log_format() -> Setting<String> {
+    Setting::string("extension.log.format")
+        .default("text".to_string())
+        .one_of(&["text", "json"])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = ExtensionError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(ExtensionError::configuration(format!(
+                "Unknown log format '{}', expected text or json",
+                other
+            ))),
+        }
+    }
+}
+
+/// Install the global subscriber. Every event carries the fields of the
+/// spans it happens in, so events inside `connection_span` and
+/// `request_span` are tagged with the extension id, peer, request id and
+/// action. The level filter is read from `RUST_LOG` and can be changed
+/// later through `ExtensionContext::logging`.
+pub fn init_logging(format: LogFormat) -> Result<(), ExtensionError> {
+    let default = std::env::var(EnvFilter::DEFAULT_ENV)
+        .ok()
+        .filter(|filter| EnvFilter::try_new(filter).is_ok())
+        .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+    let (subscriber, control) = subscriber(format, &default, std::io::stdout)?;
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| ExtensionError::initialization(format!("Failed to install logging: {}", e)))?;
+    let _ = LOGGING.set(control);
+    Ok(())
+}
+
+fn subscriber<W>(
+    format: LogFormat,
+    default: &str,
+    writer: W,
+) -> Result<(Box<dyn Subscriber + Send + Sync>, LoggingControl), ExtensionError>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let control = LoggingControl::new(default);
+    let (filter, handle) = reload::Layer::new(control.filter()?);
+    *control
+        .handle
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = Some(handle);
+
+    let registry = tracing_subscriber::registry().with(filter);
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    let subscriber: Box<dyn Subscriber + Send + Sync> = match format {
+        LogFormat::Text => Box::new(registry.with(layer)),
+        LogFormat::Json => {
+            Box::new(registry.with(layer.json().with_current_span(false).with_span_list(true)))
+        }
+    };
+    Ok((subscriber, control))
+}
+
+/// The log filter in effect: a default directive plus per-module levels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogLevels {
+    pub default: String,
+    pub modules: BTreeMap<String, String>,
+}
+
+impl LogLevels {
+    /// The filter in `RUST_LOG` syntax, e.g. `info,opensearch_sdk_rs::transport=debug`.
+    pub fn directives(&self) -> String {
+        std::iter::once(self.default.clone())
+            .chain(
+                self.modules
+                    .iter()
+                    .map(|(module, level)| format!("{}={}", module, level)),
+            )
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Changes the level filter of the subscriber installed by `init_logging`
+/// while the extension runs.
+#[derive(Clone)]
+pub struct LoggingControl {
+    levels: Arc<Mutex<LogLevels>>,
+    handle: Arc<Mutex<Option<reload::Handle<EnvFilter, Registry>>>>,
+}
+
+impl LoggingControl {
+    fn new(default: &str) -> Self {
+        LoggingControl {
+            levels: Arc::new(Mutex::new(LogLevels {
+                default: default.to_string(),
+                modules: BTreeMap::new(),
+            })),
+            handle: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The control of the global subscriber. Changes fail if logging was
+    /// not installed with `init_logging`.
+    pub fn global() -> Self {
+        LOGGING
+            .get()
+            .cloned()
+            .unwrap_or_else(|| LoggingControl::new(DEFAULT_FILTER))
+    }
+
+    pub fn levels(&self) -> LogLevels {
+        self.levels
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Set the default level, e.g. `"debug"`. Module levels are kept.
+    pub fn set_level(&self, level: &str) -> Result<(), ExtensionError> {
+        let level = parse_level(level)?;
+        self.update(|levels| levels.default = level)
+    }
+
+    /// Set the level of `module` and the modules under it.
+    pub fn set_module_level(&self, module: &str, level: &str) -> Result<(), ExtensionError> {
+        if module.is_empty() || module.contains([',', '=', ' ']) {
+            return Err(ExtensionError::invalid_request(format!(
+                "Invalid module name '{}'",
+                module
+            )));
+        }
+        let level = parse_level(level)?;
+        self.update(|levels| {
+            levels.modules.insert(module.to_string(), level);
+        })
+    }
+
+    /// Drop the level set for `module`, so it follows the default again.
+    pub fn clear_module_level(&self, module: &str) -> Result<(), ExtensionError> {
+        self.update(|levels| {
+            levels.modules.remove(module);
+        })
+    }
+
+    fn filter(&self) -> Result<EnvFilter, ExtensionError> {
+        let directives = self.levels().directives();
+        EnvFilter::try_new(&directives).map_err(|e| {
+            ExtensionError::configuration(format!("Invalid log filter '{}': {}", directives, e))
+        })
+    }
+
+    fn update(&self, change: impl FnOnce(&mut LogLevels)) -> Result<(), ExtensionError> {
+        let handle = self.handle.lock().unwrap_or_else(PoisonError::into_inner);
+        let handle = handle.as_ref().ok_or_else(|| {
+            ExtensionError::configuration("Logging was not installed with init_logging")
+        })?;
+        let previous = self.levels();
+        change(&mut self.levels.lock().unwrap_or_else(PoisonError::into_inner));
+        let filter = self.filter().inspect_err(|_| {
+            *self.levels.lock().unwrap_or_else(PoisonError::into_inner) = previous.clone()
+        })?;
+        handle.reload(filter).map_err(|e| {
+            ExtensionError::configuration(format!("Failed to change log levels: {}", e))
+        })?;
+        tracing::info!("Log filter changed to '{}'", self.levels().directives());
+        Ok(())
+    }
+}
+
+fn parse_level(level: &str) -> Result<String, ExtensionError> {
+    LevelFilter::from_str(level)
+        .map(|level| level.to_string().to_lowercase())
+        .map_err(|_| {
+            ExtensionError::invalid_request(format!(
+                "Unknown log level '{}', expected trace, debug, info, warn, error or off",
+                level
+            ))
+        })
+}
+
+/// Span for an OpenSearch connection to the extension.
+pub fn connection_span(extension_id: &str, peer: impl fmt::Display) -> Span {
+    info_span!("connection", extension_id = %extension_id, peer = %peer)
+}
+
+/// Span for one transport request, nested in its connection's span.
+pub fn request_span(request_id: u64, action: &str) -> Span {
+    info_span!("request", request_id, action = %action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_events_carry_correlation_fields() {
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+
+        let buffer = Buffer::default();
+        let (subscriber, _) = subscriber(LogFormat::Json, "info", buffer.clone()).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            let _connection = connection_span("hello-world", "127.0.0.1:9300").entered();
+            let _request = request_span(42, "internal:discovery/extensions").entered();
+            tracing::info!(status = "ok", "Handled request");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let event: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(event["fields"]["message"], "Handled request");
+        assert_eq!(event["spans"][0]["extension_id"], "hello-world");
+        assert_eq!(event["spans"][0]["peer"], "127.0.0.1:9300");
+        assert_eq!(event["spans"][1]["request_id"], 42);
+        assert_eq!(event["spans"][1]["action"], "internal:discovery/extensions");
+    }
+
+    #[test]
+    fn test_change_levels_at_runtime() {
+        assert!(LoggingControl::new("info").set_level("debug").is_err());
+
+        let buffer = Buffer::default();
+        let (subscriber, control) = subscriber(LogFormat::Text, "info", buffer.clone()).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("hidden");
+            control.set_level("DEBUG").unwrap();
+            tracing::debug!("shown at debug");
+
+            control.set_level("warn").unwrap();
+            control
+                .set_module_level("opensearch_sdk_rs::extension", "trace")
+                .unwrap();
+            tracing::trace!("shown for module");
+            tracing::info!(target: "other", "hidden for other");
+
+            assert!(control.set_level("verbose").is_err());
+            assert!(control.set_module_level("a=b", "info").is_err());
+            assert_eq!(
+                control.levels().directives(),
+                "warn,opensearch_sdk_rs::extension=trace"
+            );
+            control
+                .clear_module_level("opensearch_sdk_rs::extension")
+                .unwrap();
+            tracing::trace!("hidden again");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("shown at debug"));
+        assert!(output.contains("shown for module"));
+        assert!(!output.contains("hidden"));
+    }
+}
@@ -0,0 +1,259 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::extension::discovery::{
+    DiscoveredExtension, DiscoveryClient, DiscoveryService, ExtensionStatus,
+};
+use crate::extension::resilience::{
+    retry_with_policy, Bulkhead, CircuitBreaker, CircuitBreakerRegistry, RetryPolicy,
+};
+use crate::extension::ExtensionError;
+use crate::transport::TransportClient;
+
+/// Looks up where an extension can be reached.
+#[async_trait]
+pub trait ExtensionResolver: Send + Sync {
+    async fn resolve(&self, unique_id: &str)
+        -> Result<Option<DiscoveredExtension>, ExtensionError>;
+}
+
+#[async_trait]
+impl ExtensionResolver for DiscoveryService {
+    async fn resolve(
+        &self,
+        unique_id: &str,
+    ) -> Result<Option<DiscoveredExtension>, ExtensionError> {
+        Ok(self.get_extension(unique_id).await)
+    }
+}
+
+#[async_trait]
+impl ExtensionResolver for DiscoveryClient {
+    async fn resolve(
+        &self,
+        unique_id: &str,
+    ) -> Result<Option<DiscoveredExtension>, ExtensionError> {
+        self.query_extension(unique_id).await
+    }
+}
+
+/// The client and connection limit for one target, kept until discovery
+/// reports the target at another address.
+struct MeshTarget {
+    host: String,
+    port: u16,
+    client: TransportClient,
+    connections: Bulkhead,
+}
+
+/// Calls actions on other extensions by unique id. Each call resolves the
+/// target through discovery, then sends the request through that target's
+/// client, which allows at most `max_connections` requests in flight at
+/// once, under a circuit breaker per target and the retry policy. Failing
+/// to resolve a target is retried but does not count against its breaker.
+pub struct ExtensionServiceMesh {
+    resolver: Arc<dyn ExtensionResolver>,
+    targets: Mutex<HashMap<String, Arc<MeshTarget>>>,
+    breakers: CircuitBreakerRegistry,
+    retry: RetryPolicy,
+    max_connections: usize,
+    max_queued: usize,
+    timeout: Duration,
+}
+
+impl ExtensionServiceMesh {
+    pub fn new(resolver: Arc<dyn ExtensionResolver>) -> Self {
+        ExtensionServiceMesh {
+            resolver,
+            targets: Mutex::new(HashMap::new()),
+            breakers: CircuitBreakerRegistry::new(|_| {
+                CircuitBreaker::new(5, 2, Duration::from_secs(30))
+            }),
+            retry: RetryPolicy::default(),
+            max_connections: 16,
+            max_queued: 64,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Breakers to use, keyed by target unique id.
+    pub fn breakers(mut self, breakers: CircuitBreakerRegistry) -> Self {
+        self.breakers = breakers;
+        self
+    }
+
+    /// Concurrent requests per target, and how many more may wait for one.
+    pub fn max_connections(mut self, max_connections: usize, max_queued: usize) -> Self {
+        self.max_connections = max_connections;
+        self.max_queued = max_queued;
+        self
+    }
+
+    /// Timeout of each connection attempt.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn breaker_registry(&self) -> &CircuitBreakerRegistry {
+        &self.breakers
+    }
+
+    /// Invoke `action` on the extension `target` with `payload`.
+    pub async fn call(
+        &self,
+        target: &str,
+        action: &str,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, ExtensionError> {
+        let breaker = self.breakers.get(target)?;
+        retry_with_policy(&self.retry, || async {
+            let target = self.target(target).await?;
+            breaker
+                .call(|| {
+                    target
+                        .connections
+                        .call(|| target.client.send_request(action, payload))
+                })
+                .await
+        })
+        .await
+    }
+
+    async fn target(&self, unique_id: &str) -> Result<Arc<MeshTarget>, ExtensionError> {
+        let extension = self.resolver.resolve(unique_id).await?.ok_or_else(|| {
+            ExtensionError::dependency(format!("Extension '{}' is not registered", unique_id))
+        })?;
+        if extension.status != ExtensionStatus::Active {
+            return Err(ExtensionError::dependency(format!(
+                "Extension '{}' is {:?}",
+                unique_id, extension.status
+            )));
+        }
+
+        let registration = extension.registration;
+        let mut targets = self.targets.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(target) = targets.get(unique_id) {
+            if target.host == registration.host && target.port == registration.port {
+                return Ok(target.clone());
+            }
+            debug!(
+                "Extension {} moved to {}:{}",
+                unique_id, registration.host, registration.port
+            );
+        }
+        let target = Arc::new(MeshTarget {
+            client: TransportClient::new(registration.host.clone(), registration.port)
+                .with_timeout(self.timeout),
+            connections: Bulkhead::new(
+                format!("mesh:{}", unique_id),
+                self.max_connections,
+                self.max_queued,
+            ),
+            host: registration.host,
+            port: registration.port,
+        });
+        targets.insert(unique_id.to_string(), target.clone());
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::registration::{ExtensionIdentity, ExtensionRegistration};
+    use crate::extension::resilience::CircuitState;
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+
+    fn registration(unique_id: &str, port: u16) -> ExtensionRegistration {
+        let identity = ExtensionIdentity {
+            name: unique_id.to_string(),
+            unique_id: unique_id.to_string(),
+            version: "1.0.0".to_string(),
+            opensearch_version: "3.0.0".to_string(),
+            java_version: "11".to_string(),
+            description: None,
+            vendor: None,
+            license: None,
+            dependencies: vec![],
+        };
+        ExtensionRegistration::new(identity, "127.0.0.1".to_string(), port)
+    }
+
+    #[tokio::test]
+    async fn test_calls_resolved_extension_and_trips_breaker() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let request = read_message(&mut stream).await.unwrap().unwrap();
+                let reply = request.content.to_ascii_uppercase();
+                write_response(
+                    &mut stream,
+                    &request.header,
+                    &ThreadContext::new(),
+                    &reply,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+        });
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let discovery = Arc::new(DiscoveryService::new(Duration::from_secs(60)));
+        discovery
+            .register_extension(registration("other-ext", port))
+            .await
+            .unwrap();
+        discovery
+            .register_extension(registration("down-ext", closed_port))
+            .await
+            .unwrap();
+        let mesh = ExtensionServiceMesh::new(discovery)
+            .retry(RetryPolicy {
+                max_attempts: 2,
+                initial_delay: Duration::from_millis(1),
+                jitter: false,
+                ..RetryPolicy::default()
+            })
+            .breakers(CircuitBreakerRegistry::new(|_| {
+                CircuitBreaker::new(2, 1, Duration::from_secs(60))
+            }));
+
+        assert_eq!(
+            mesh.call("other-ext", "action", b"ping").await.unwrap(),
+            b"PING"
+        );
+        assert!(mesh
+            .call("missing", "action", b"ping")
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("not registered"));
+        assert!(mesh.call("down-ext", "action", b"ping").await.is_err());
+        assert_eq!(
+            mesh.breaker_registry()
+                .get("down-ext")
+                .unwrap()
+                .get_state()
+                .await,
+            CircuitState::Open
+        );
+        assert_eq!(
+            mesh.call("other-ext", "action", b"pong").await.unwrap(),
+            b"PONG"
+        );
+    }
+}
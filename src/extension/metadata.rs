@@ -1,6 +1,6 @@
-use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
 use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtensionMetadata {
@@ -53,11 +53,155 @@ pub struct OsInfo {
     pub architecture: String,
 }
 
+/// Relative error of `LatencyHistogram` quantiles.
+const LATENCY_RELATIVE_ACCURACY: f64 = 0.01;
+
+/// Bucket limit of `LatencyHistogram`. At 1% accuracy this covers
+/// durations from microseconds to days before buckets are merged.
+const LATENCY_MAX_BUCKETS: usize = 2048;
+
+/// Latency distribution with logarithmic buckets, in the style of
+/// DDSketch. Quantiles are within 1% of the true value and memory stays
+/// bounded however many durations are recorded. When the bucket limit is
+/// reached the lowest buckets are merged, so only small quantiles lose
+/// accuracy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    buckets: BTreeMap<i32, u64>,
+    zero_count: u64,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        LatencyHistogram {
+            buckets: BTreeMap::new(),
+            zero_count: 0,
+            count: 0,
+            sum: 0.0,
+            min: 0.0,
+            max: 0.0,
+        }
+    }
+
+    fn gamma() -> f64 {
+        (1.0 + LATENCY_RELATIVE_ACCURACY) / (1.0 - LATENCY_RELATIVE_ACCURACY)
+    }
+
+    /// Record one duration. Negative and NaN values count as zero.
+    pub fn record(&mut self, value: f64) {
+        let value = if value.is_nan() { 0.0 } else { value.max(0.0) };
+        let first = self.count == 0;
+        self.count += 1;
+        self.sum += value;
+        self.min = if first { value } else { self.min.min(value) };
+        self.max = if first { value } else { self.max.max(value) };
+
+        if value < f64::MIN_POSITIVE {
+            self.zero_count += 1;
+            return;
+        }
+        let index = (value.ln() / Self::gamma().ln()).ceil() as i32;
+        *self.buckets.entry(index).or_default() += 1;
+        self.collapse();
+    }
+
+    /// Fold the lowest buckets into their neighbours until the limit holds.
+    fn collapse(&mut self) {
+        while self.buckets.len() > LATENCY_MAX_BUCKETS {
+            let (_, count) = self.buckets.pop_first().unwrap();
+            *self.buckets.first_entry().unwrap().get_mut() += count;
+        }
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            self.min = other.min;
+            self.max = other.max;
+        }
+        for (index, count) in &other.buckets {
+            *self.buckets.entry(*index).or_default() += count;
+        }
+        self.collapse();
+        self.zero_count += other.zero_count;
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The value below which a fraction `q` of the recorded durations fall,
+    /// e.g. `quantile(0.99)` for p99. `None` while nothing is recorded.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+        if rank < self.zero_count {
+            return Some(0.0);
+        }
+        let gamma = Self::gamma();
+        let mut seen = self.zero_count;
+        for (index, count) in &self.buckets {
+            seen += count;
+            if seen > rank {
+                let estimate = 2.0 * gamma.powi(*index) / (gamma + 1.0);
+                return Some(estimate.clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    pub fn p50(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    pub fn p90(&self) -> Option<f64> {
+        self.quantile(0.9)
+    }
+
+    pub fn p99(&self) -> Option<f64> {
+        self.quantile(0.99)
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtensionMetrics {
     pub requests_total: u64,
     pub requests_failed: u64,
-    pub requests_duration_ms: VecDeque<f64>,
+    pub requests_duration_ms: LatencyHistogram,
     pub memory_usage_bytes: Option<u64>,
     pub cpu_usage_percent: Option<f32>,
     pub uptime_seconds: u64,
@@ -67,28 +211,24 @@ impl ExtensionMetrics {
     pub fn new() -> Self {
         Self::default()
     }
-    
+
     pub fn record_request(&mut self, duration_ms: f64, success: bool) {
         self.requests_total += 1;
         if !success {
             self.requests_failed += 1;
         }
-        self.requests_duration_ms.push_back(duration_ms);
-        
-        if self.requests_duration_ms.len() > 1000 {
-            self.requests_duration_ms.pop_front();
-        }
+        self.requests_duration_ms.record(duration_ms);
     }
-    
+
     pub fn average_request_duration(&self) -> Option<f64> {
-        if self.requests_duration_ms.is_empty() {
-            None
-        } else {
-            let sum: f64 = self.requests_duration_ms.iter().sum();
-            Some(sum / self.requests_duration_ms.len() as f64)
-        }
+        self.requests_duration_ms.mean()
     }
-    
+
+    /// Request duration at quantile `q`, e.g. 0.99 for p99.
+    pub fn request_duration_percentile(&self, q: f64) -> Option<f64> {
+        self.requests_duration_ms.quantile(q)
+    }
+
     pub fn success_rate(&self) -> f64 {
         if self.requests_total == 0 {
             1.0
@@ -112,17 +252,17 @@ impl MetadataBuilder {
             custom_metadata: HashMap::new(),
         }
     }
-    
+
     pub fn runtime_info(mut self, info: RuntimeInfo) -> Self {
         self.runtime_info = Some(info);
         self
     }
-    
+
     pub fn custom_field(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
         self.custom_metadata.insert(key.into(), value);
         self
     }
-    
+
     pub fn build(self) -> ExtensionMetadata {
         let runtime_info = self.runtime_info.unwrap_or_else(|| RuntimeInfo {
             startup_time: std::time::SystemTime::now(),
@@ -136,7 +276,7 @@ impl MetadataBuilder {
                 architecture: std::env::consts::ARCH.to_string(),
             },
         });
-        
+
         ExtensionMetadata {
             manifest: self.manifest,
             runtime_info,
@@ -153,21 +293,68 @@ pub trait MetadataProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn test_extension_metrics() {
         let mut metrics = ExtensionMetrics::new();
-        
+
         metrics.record_request(100.0, true);
         metrics.record_request(200.0, true);
         metrics.record_request(150.0, false);
-        
+
         assert_eq!(metrics.requests_total, 3);
         assert_eq!(metrics.requests_failed, 1);
         assert_eq!(metrics.average_request_duration(), Some(150.0));
         assert!((metrics.success_rate() - 0.666).abs() < 0.01);
+        assert!((metrics.request_duration_percentile(0.5).unwrap() - 150.0).abs() <= 1.5);
     }
-    
+
+    #[test]
+    fn test_latency_histogram_percentiles() {
+        let mut histogram = LatencyHistogram::new();
+        assert_eq!(histogram.p99(), None);
+        let json = serde_json::to_string(&ExtensionMetrics::new()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ExtensionMetrics>(&json)
+                .unwrap()
+                .requests_duration_ms,
+            histogram
+        );
+
+        for ms in 1..=10_000 {
+            histogram.record(ms as f64 / 10.0);
+        }
+        histogram.record(0.0);
+        assert_eq!(histogram.count(), 10_001);
+        for (q, expected) in [(0.5, 500.0), (0.9, 900.0), (0.99, 990.0)] {
+            let value = histogram.quantile(q).unwrap();
+            assert!(
+                (value - expected).abs() <= expected * 0.011,
+                "q{} = {}",
+                q,
+                value
+            );
+        }
+        assert_eq!(histogram.quantile(0.0), Some(0.0));
+        assert_eq!(histogram.max(), Some(1000.0));
+
+        let mut merged = LatencyHistogram::new();
+        merged.merge(&histogram);
+        merged.merge(&histogram);
+        assert_eq!(merged.count(), 20_002);
+        assert_eq!(merged.p90(), histogram.p90());
+
+        let mut wide = LatencyHistogram::new();
+        for exponent in -300..300 {
+            for step in 0..10 {
+                wide.record(10f64.powi(exponent) * (1.0 + step as f64 / 10.0));
+            }
+        }
+        assert!(wide.buckets.len() <= LATENCY_MAX_BUCKETS);
+        let p99 = wide.p99().unwrap();
+        assert!(p99 > 1e293 && p99 < 1e295, "p99 = {}", p99);
+    }
+
     #[test]
     fn test_metadata_builder() {
         let manifest = ExtensionManifest {
@@ -191,12 +378,15 @@ mod tests {
                 url: None,
             }],
         };
-        
+
         let metadata = MetadataBuilder::new(manifest.clone())
             .custom_field("test_field", serde_json::json!("test_value"))
             .build();
-        
+
         assert_eq!(metadata.manifest.name, "test-extension");
-        assert_eq!(metadata.custom_metadata.get("test_field").unwrap(), "test_value");
+        assert_eq!(
+            metadata.custom_metadata.get("test_field").unwrap(),
+            "test_value"
+        );
     }
-}
\ No newline at end of file
+}
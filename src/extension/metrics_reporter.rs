@@ -0,0 +1,323 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::client::{CreateIndexRequest, FieldMapping, IndexRequest, Mappings, SdkClient};
+use crate::extension::context::Settings;
+use crate::extension::health::{HealthService, HealthStatus};
+use crate::extension::metadata::ExtensionMetrics;
+use crate::extension::resources::{ResourceSampler, ResourceUsage};
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+use crate::rest::middleware::ResponseTimeMetrics;
+use crate::transport::action::ActionMetrics;
+
+/// Index the reporter writes snapshots to. Reporting is off while unset.
+pub fn metrics_report_index() -> Setting<String> {
+    Setting::string("extension.metrics.report.index").node_scope()
+}
+
+pub fn metrics_report_interval() -> Setting<TimeValue> {
+    Setting::time("extension.metrics.report.interval")
+        .default(TimeValue::seconds(60))
+        .min(TimeValue::seconds(1))
+        .node_scope()
+}
+
+/// Request statistics of one route or action in a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequestStats {
+    pub requests_total: u64,
+    pub requests_failed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p50_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p90_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p99_ms: Option<f64>,
+}
+
+impl From<&ExtensionMetrics> for RequestStats {
+    fn from(metrics: &ExtensionMetrics) -> Self {
+        RequestStats {
+            requests_total: metrics.requests_total,
+            requests_failed: metrics.requests_failed,
+            avg_ms: metrics.average_request_duration(),
+            p50_ms: metrics.request_duration_percentile(0.5),
+            p90_ms: metrics.request_duration_percentile(0.9),
+            p99_ms: metrics.request_duration_percentile(0.99),
+        }
+    }
+}
+
+/// One document written by `MetricsReporter`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricsSnapshot {
+    #[serde(rename = "@timestamp")]
+    pub timestamp: u64,
+    pub extension: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<HealthStatus>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, HealthStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceUsage>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub routes: BTreeMap<String, RequestStats>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub actions: BTreeMap<String, RequestStats>,
+}
+
+/// Periodically writes metrics and health snapshots to an index through an
+/// `SdkClient`, so they can be charted next to the cluster's own metrics.
+/// Each source is optional; a snapshot holds whatever was attached.
+pub struct MetricsReporter {
+    client: SdkClient,
+    index: String,
+    interval: Duration,
+    routes: Option<Arc<ResponseTimeMetrics>>,
+    actions: Option<Arc<ActionMetrics>>,
+    resources: Option<Arc<ResourceSampler>>,
+    health: Option<HealthService>,
+}
+
+impl MetricsReporter {
+    pub fn new(client: SdkClient, index: impl Into<String>) -> Self {
+        MetricsReporter {
+            client,
+            index: index.into(),
+            interval: Duration::from_secs(60),
+            routes: None,
+            actions: None,
+            resources: None,
+            health: None,
+        }
+    }
+
+    /// A reporter for the `metrics_report_index` setting, or `None` when it
+    /// is unset.
+    pub fn from_settings(
+        client: SdkClient,
+        settings: &Settings,
+    ) -> Result<Option<Self>, ExtensionError> {
+        let Some(index) = metrics_report_index().get_opt(settings)? else {
+            return Ok(None);
+        };
+        let interval = metrics_report_interval().get(settings)?.as_duration();
+        Ok(Some(Self::new(client, index).interval(interval)))
+    }
+
+    pub fn index(&self) -> &str {
+        &self.index
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn routes(mut self, routes: Arc<ResponseTimeMetrics>) -> Self {
+        self.routes = Some(routes);
+        self
+    }
+
+    pub fn actions(mut self, actions: Arc<ActionMetrics>) -> Self {
+        self.actions = Some(actions);
+        self
+    }
+
+    pub fn resources(mut self, resources: Arc<ResourceSampler>) -> Self {
+        self.resources = Some(resources);
+        self
+    }
+
+    pub fn health(mut self, health: HealthService) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    pub async fn snapshot(&self) -> MetricsSnapshot {
+        let (status, checks) = match &self.health {
+            Some(health) => {
+                let report = health.get_health_report().await;
+                let checks = report
+                    .checks
+                    .into_iter()
+                    .map(|check| (check.name, check.status))
+                    .collect();
+                (Some(report.status), checks)
+            }
+            None => (None, BTreeMap::new()),
+        };
+        let stats = |metrics: std::collections::HashMap<String, ExtensionMetrics>| {
+            metrics
+                .iter()
+                .map(|(name, metrics)| (name.clone(), RequestStats::from(metrics)))
+                .collect()
+        };
+        MetricsSnapshot {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            extension: self.client.unique_id().to_string(),
+            status,
+            checks,
+            resources: self.resources.as_ref().map(|sampler| sampler.latest()),
+            routes: self
+                .routes
+                .as_ref()
+                .map(|routes| {
+                    stats(
+                        routes
+                            .snapshot()
+                            .into_iter()
+                            .map(|(route, m)| (route.to_string(), m))
+                            .collect(),
+                    )
+                })
+                .unwrap_or_default(),
+            actions: self
+                .actions
+                .as_ref()
+                .map(|actions| stats(actions.snapshot()))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn create_index_request(&self) -> CreateIndexRequest {
+        let mappings = Mappings::new()
+            .field(
+                "@timestamp",
+                FieldMapping::date().param("format", "epoch_millis"),
+            )
+            .field("extension", FieldMapping::keyword())
+            .field("status", FieldMapping::keyword());
+        CreateIndexRequest::new(self.index.clone()).mappings(mappings)
+    }
+
+    /// Write one snapshot now, creating the index on first use.
+    pub async fn report(&self) -> Result<(), ExtensionError> {
+        self.client
+            .ensure_index(self.create_index_request())
+            .await?;
+        let snapshot = self.snapshot().await;
+        let response = self
+            .client
+            .index(IndexRequest::new(self.index.clone()).document(&snapshot)?)
+            .await?;
+        debug!(
+            "Reported metrics snapshot {} to index {}",
+            response.id, self.index
+        );
+        Ok(())
+    }
+
+    /// Report every `interval` until the returned task is aborted. Failed
+    /// writes are logged and retried at the next tick.
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.report().await {
+                    warn!("Failed to report metrics to index {}: {}", self.index, e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{RemoteExtensionActionResponse, TransportActionRequestFromExtension};
+    use crate::interface::{self, Serialize as _};
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+    use crate::transport::TransportClient;
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_report_writes_snapshot() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            let bodies = [
+                json!({"exists": true}),
+                json!({"_index": "extension-metrics", "_id": "1", "result": "created"}),
+            ];
+            for body in bodies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_message(&mut stream).await.unwrap().unwrap();
+                requests.push(
+                    <TransportActionRequestFromExtension as interface::Deserialize>::deserialize(
+                        &mut request.content.as_slice(),
+                    )
+                    .unwrap(),
+                );
+                let mut bytes = Vec::new();
+                RemoteExtensionActionResponse {
+                    success: true,
+                    response_bytes: body.to_string().into_bytes(),
+                }
+                .serialize(&mut bytes)
+                .unwrap();
+                write_response(
+                    &mut stream,
+                    &request.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+            requests
+        });
+
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let settings = Settings::new();
+        assert!(MetricsReporter::from_settings(client.clone(), &settings)
+            .unwrap()
+            .is_none());
+        settings
+            .set("extension.metrics.report.index", "extension-metrics")
+            .unwrap();
+        let actions = Arc::new(ActionMetrics::new());
+        actions.record("cluster:admin/hello/echo", 12.0, true);
+        let health = HealthService::new();
+        health.register_check("index").await;
+        let reporter = MetricsReporter::from_settings(client, &settings)
+            .unwrap()
+            .unwrap()
+            .actions(actions)
+            .health(health);
+        assert_eq!(reporter.interval, Duration::from_secs(60));
+
+        reporter.report().await.unwrap();
+        let requests = server.await.unwrap();
+        let sent: Value = serde_json::from_slice(&requests[1].request_bytes).unwrap();
+        assert_eq!(sent["index"], "extension-metrics");
+        assert_eq!(sent["source"]["extension"], "hello-world");
+        assert_eq!(sent["source"]["status"], "Healthy");
+        assert_eq!(sent["source"]["checks"]["index"], "Healthy");
+        assert_eq!(
+            sent["source"]["actions"]["cluster:admin/hello/echo"]["requests_total"],
+            1
+        );
+        assert!(sent["source"].get("routes").is_none());
+    }
+}
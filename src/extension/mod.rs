@@ -1,25 +1,75 @@
 pub mod builder;
+pub mod config_watcher;
 pub mod context;
+pub mod custom_settings;
 pub mod dependency;
+pub mod descriptor;
+pub mod diagnostics;
 pub mod discovery;
+pub mod discovery_backend;
+pub mod discovery_gossip;
+pub mod discovery_store;
+pub mod dispatcher;
+pub mod environment;
 pub mod error;
+pub mod events;
+pub mod features;
 pub mod health;
+pub mod identity;
+pub mod init;
 pub mod lifecycle;
+pub mod logging;
+pub mod mesh;
 pub mod metadata;
+pub mod metrics_reporter;
+pub mod persisted_settings;
+pub mod probe;
+pub mod reconnect;
 pub mod registration;
 pub mod resilience;
+pub mod resources;
 pub mod runner;
+pub mod service_account;
+pub mod setting;
+pub mod settings_loader;
+pub mod settings_update;
+pub mod settings_validation;
+pub mod slow_log;
+pub mod startup;
+pub mod supervisor;
 pub mod traits;
+pub mod units;
 
 pub use builder::ExtensionBuilder;
 pub use context::ExtensionContext;
 pub use dependency::ExtensionDependency;
-pub use discovery::{DiscoveryService, DiscoveryClient};
-pub use error::ExtensionError;
-pub use health::{HealthService, HealthStatus, HealthCheck};
-pub use lifecycle::{LifecycleManager, ExtensionState};
-pub use metadata::{ExtensionMetadata, ExtensionManifest};
-pub use registration::{ExtensionRegistration, ExtensionIdentity};
-pub use resilience::{RetryPolicy, CircuitBreaker, retry_with_policy};
-pub use runner::ExtensionRunner;
-pub use traits::Extension;
\ No newline at end of file
+pub use descriptor::ExtensionDescriptor;
+pub use diagnostics::Diagnostics;
+pub use discovery::{
+    CapabilityQuery, DiscoveryClient, DiscoveryService, RegistryEvent, RegistryWatch,
+};
+pub use error::{ExtensionError, Retryable};
+pub use events::{EventBus, SdkEvent};
+pub use features::NegotiatedFeatures;
+pub use health::{HealthCheck, HealthService, HealthStatus};
+pub use identity::{PrincipalIdentifier, User};
+pub use init::{ExtensionInit, InitStateProvider};
+pub use lifecycle::{ExtensionState, LifecycleManager};
+pub use mesh::{ExtensionResolver, ExtensionServiceMesh};
+pub use metadata::{ExtensionManifest, ExtensionMetadata};
+pub use persisted_settings::PersistedSettings;
+pub use probe::{ProbeRunner, SyntheticProbe};
+pub use registration::{
+    Capability, ExtensionCapabilities, ExtensionIdentity, ExtensionRegistration,
+};
+pub use resilience::{
+    retry_with_policy, with_cache, with_fallback, with_timeout, AdaptiveLimiter, Bulkhead,
+    CircuitBreaker, CircuitBreakerRegistry, RateLimiter, ResultCache, RetryBudget, RetryPolicy,
+};
+pub use resources::{ResourceSampler, ResourceUsage};
+pub use runner::{ExtensionHandle, ExtensionRunner};
+pub use service_account::{ServiceAccount, ServiceAccountToken};
+pub use setting::{AffixSetting, Setting, SettingRegistry};
+pub use slow_log::SlowLog;
+pub use traits::Extension;
+pub use units::{ByteSizeValue, TimeValue};
@@ -0,0 +1,167 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tracing::debug;
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::custom_settings::{CustomSettingDescriptor, SettingType};
+use crate::extension::ExtensionError;
+
+/// Format version written by this SDK.
+pub const SETTINGS_FORMAT_VERSION: u32 = 1;
+
+/// Settings as stored in a shared file or document. Keys this version does
+/// not declare, values of an unexpected type and fields added by newer
+/// formats are kept as-is, so rewriting the document from an older version
+/// never loses what a newer one wrote.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedSettings {
+    pub format_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_by: Option<String>,
+    #[serde(default)]
+    pub settings: BTreeMap<String, Value>,
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+impl Default for PersistedSettings {
+    fn default() -> Self {
+        PersistedSettings {
+            format_version: SETTINGS_FORMAT_VERSION,
+            written_by: None,
+            settings: BTreeMap::new(),
+            other: BTreeMap::new(),
+        }
+    }
+}
+
+impl PersistedSettings {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ExtensionError> {
+        serde_json::from_slice(bytes).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to parse persisted settings: {}", e))
+        })
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>, ExtensionError> {
+        serde_json::to_vec_pretty(self).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize persisted settings: {}", e))
+        })
+    }
+
+    /// Whether the document was written in a newer format than this SDK's.
+    pub fn is_newer_format(&self) -> bool {
+        self.format_version > SETTINGS_FORMAT_VERSION
+    }
+
+    /// Copy the declared settings into `settings`, returning how many were
+    /// applied. Everything else stays in the document only.
+    pub fn apply_to(
+        &self,
+        settings: &Settings,
+        known: &[CustomSettingDescriptor],
+    ) -> Result<usize, ExtensionError> {
+        let mut applied = 0;
+        for descriptor in known {
+            let Some(raw) = self.settings.get(&descriptor.key) else {
+                continue;
+            };
+            match typed_value(raw, descriptor.setting_type) {
+                Some(value) => {
+                    settings.set(descriptor.key.clone(), value)?;
+                    applied += 1;
+                }
+                None => debug!(
+                    "Keeping persisted setting '{}' untouched: expected a {}",
+                    descriptor.key,
+                    descriptor.setting_type.name()
+                ),
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Write the declared settings that have a value in `settings` into the
+    /// document, leaving every other key and field as it was. The format
+    /// version is never lowered.
+    pub fn update_from(
+        &mut self,
+        settings: &Settings,
+        known: &[CustomSettingDescriptor],
+        writer_version: &str,
+    ) -> Result<(), ExtensionError> {
+        for descriptor in known {
+            if let Some(value) = settings.get(&descriptor.key)? {
+                let value = serde_json::to_value(&value).map_err(|e| {
+                    ExtensionError::serialization(format!(
+                        "Failed to serialize setting '{}': {}",
+                        descriptor.key, e
+                    ))
+                })?;
+                self.settings.insert(descriptor.key.clone(), value);
+            }
+        }
+        self.format_version = self.format_version.max(SETTINGS_FORMAT_VERSION);
+        self.written_by = Some(writer_version.to_string());
+        Ok(())
+    }
+}
+
+/// `raw` as a value of `setting_type`, treating whole numbers as floats
+/// where a float is expected and parsing byte sizes and time values.
+fn typed_value(raw: &Value, setting_type: SettingType) -> Option<SettingValue> {
+    let value: SettingValue = serde_json::from_value(raw.clone()).ok()?;
+    match (value, setting_type) {
+        (SettingValue::Integer(i), SettingType::Float) => Some(SettingValue::Float(i as f64)),
+        (SettingValue::String(s), SettingType::ByteSize) => {
+            s.parse().ok().map(SettingValue::ByteSize)
+        }
+        (SettingValue::String(s), SettingType::Time) => s.parse().ok().map(SettingValue::Time),
+        (value, expected) if SettingType::of(&value) == expected => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_older_writer_preserves_newer_data() {
+        let written_by_newer = br#"{
+            "format_version": 2,
+            "written_by": "2.0.0",
+            "checksum": "abc",
+            "settings": {
+                "hello.greeting": "hi",
+                "hello.ratio": 3,
+                "hello.retries": "three",
+                "hello.new_feature": {"enabled": true}
+            }
+        }"#;
+        let known = vec![
+            CustomSettingDescriptor::new("hello.greeting", SettingType::String),
+            CustomSettingDescriptor::new("hello.ratio", SettingType::Float),
+            CustomSettingDescriptor::new("hello.retries", SettingType::Integer),
+        ];
+
+        let mut document = PersistedSettings::from_slice(written_by_newer).unwrap();
+        assert!(document.is_newer_format());
+
+        let settings = Settings::new();
+        assert_eq!(document.apply_to(&settings, &known).unwrap(), 2);
+        assert_eq!(settings.get_float("hello.ratio").unwrap(), Some(3.0));
+        assert_eq!(settings.get_integer("hello.retries").unwrap(), None);
+
+        settings.set("hello.greeting", "hello").unwrap();
+        document.update_from(&settings, &known, "1.0.0").unwrap();
+        let rewritten = PersistedSettings::from_slice(&document.to_vec().unwrap()).unwrap();
+
+        assert_eq!(rewritten.format_version, 2);
+        assert_eq!(rewritten.written_by.as_deref(), Some("1.0.0"));
+        assert_eq!(rewritten.other["checksum"], "abc");
+        assert_eq!(rewritten.settings["hello.greeting"], "hello");
+        assert_eq!(rewritten.settings["hello.retries"], "three");
+        assert_eq!(rewritten.settings["hello.new_feature"]["enabled"], true);
+    }
+}
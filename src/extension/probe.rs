@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::extension::health::{HealthService, HealthStatus};
+use crate::extension::metadata::ExtensionMetrics;
+use crate::extension::ExtensionContext;
+use crate::rest::{ExtensionRestRequest, RestRouter, RestStatus};
+
+/// A synthetic check of one of the extension's own critical paths.
+#[async_trait::async_trait]
+pub trait SyntheticProbe: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn probe(&self) -> Result<(), String>;
+}
+
+/// Calls one of the extension's REST routes in-process and expects `expected`.
+pub struct RestRouteProbe {
+    name: String,
+    router: RestRouter,
+    context: Arc<ExtensionContext>,
+    request: ExtensionRestRequest,
+    expected: RestStatus,
+}
+
+impl RestRouteProbe {
+    pub fn new(
+        name: impl Into<String>,
+        router: RestRouter,
+        context: Arc<ExtensionContext>,
+        request: ExtensionRestRequest,
+    ) -> Self {
+        RestRouteProbe {
+            name: name.into(),
+            router,
+            context,
+            request,
+            expected: RestStatus::Ok,
+        }
+    }
+
+    pub fn expect_status(mut self, status: RestStatus) -> Self {
+        self.expected = status;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl SyntheticProbe for RestRouteProbe {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn probe(&self) -> Result<(), String> {
+        let response = self
+            .router
+            .handle(self.request.clone(), &self.context)
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status == self.expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} {} returned {} instead of {}",
+                self.request.method,
+                self.request.path,
+                response.status.code(),
+                self.expected.code()
+            ))
+        }
+    }
+}
+
+type ProbeFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+/// A probe backed by a closure, for checks such as canary queries or
+/// writing and reading back a canary document.
+pub struct FnProbe {
+    name: String,
+    probe: Box<dyn Fn() -> ProbeFuture + Send + Sync>,
+}
+
+impl FnProbe {
+    pub fn new<F, Fut>(name: impl Into<String>, probe: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        FnProbe {
+            name: name.into(),
+            probe: Box::new(move || Box::pin(probe())),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SyntheticProbe for FnProbe {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn probe(&self) -> Result<(), String> {
+        (self.probe)().await
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeEvent {
+    /// The probe has failed `failure_threshold` times in a row.
+    Failing {
+        probe: String,
+        consecutive_failures: u32,
+        error: String,
+    },
+    /// The probe succeeded again after having raised `Failing`.
+    Recovered { probe: String },
+}
+
+#[async_trait::async_trait]
+pub trait ProbeListener: Send + Sync {
+    async fn on_probe_event(&self, event: &ProbeEvent);
+}
+
+pub struct LoggingProbeListener;
+
+#[async_trait::async_trait]
+impl ProbeListener for LoggingProbeListener {
+    async fn on_probe_event(&self, event: &ProbeEvent) {
+        match event {
+            ProbeEvent::Failing {
+                probe,
+                consecutive_failures,
+                error,
+            } => {
+                warn!(
+                    "Probe '{}' failed {} times in a row: {}",
+                    probe, consecutive_failures, error
+                )
+            }
+            ProbeEvent::Recovered { probe } => info!("Probe '{}' recovered", probe),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ProbeState {
+    consecutive_failures: u32,
+    alerting: bool,
+    metrics: ExtensionMetrics,
+}
+
+/// Runs synthetic probes on an interval. Each probe is a health check in
+/// `health`: degraded after one failure and unhealthy once failures reach
+/// `failure_threshold`, at which point listeners get a `Failing` event.
+pub struct ProbeRunner {
+    probes: Vec<Box<dyn SyntheticProbe>>,
+    health: HealthService,
+    interval: Duration,
+    timeout: Duration,
+    failure_threshold: u32,
+    listeners: Vec<Box<dyn ProbeListener>>,
+    states: RwLock<HashMap<String, ProbeState>>,
+}
+
+impl ProbeRunner {
+    pub fn new(health: HealthService) -> Self {
+        ProbeRunner {
+            probes: Vec::new(),
+            health,
+            interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+            failure_threshold: 3,
+            listeners: vec![Box::new(LoggingProbeListener)],
+            states: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn probe(mut self, probe: impl SyntheticProbe + 'static) -> Self {
+        self.probes.push(Box::new(probe));
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    pub fn listener(mut self, listener: Box<dyn ProbeListener>) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// Request count, failures and durations recorded for a probe.
+    pub async fn metrics(&self, probe: &str) -> Option<ExtensionMetrics> {
+        self.states
+            .read()
+            .await
+            .get(probe)
+            .map(|state| state.metrics.clone())
+    }
+
+    /// Run every probe once, updating health checks, metrics and listeners.
+    pub async fn run_once(&self) {
+        for probe in &self.probes {
+            let name = probe.name().to_string();
+            if self.health.get_check(&name).await.is_none() {
+                self.health.register_check(name.clone()).await;
+            }
+
+            let start = Instant::now();
+            let result = match tokio::time::timeout(self.timeout, probe.probe()).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("timed out after {:?}", self.timeout)),
+            };
+            let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+            let (event, status, failures) = {
+                let mut states = self.states.write().await;
+                let state = states.entry(name.clone()).or_default();
+                state.metrics.record_request(elapsed_ms, result.is_ok());
+
+                match &result {
+                    Ok(()) => {
+                        state.consecutive_failures = 0;
+                        let recovered = std::mem::take(&mut state.alerting);
+                        let event = recovered.then(|| ProbeEvent::Recovered {
+                            probe: name.clone(),
+                        });
+                        (event, HealthStatus::Healthy, 0)
+                    }
+                    Err(error) => {
+                        state.consecutive_failures += 1;
+                        let failures = state.consecutive_failures;
+                        if failures >= self.failure_threshold {
+                            let event = (!state.alerting).then(|| ProbeEvent::Failing {
+                                probe: name.clone(),
+                                consecutive_failures: failures,
+                                error: error.clone(),
+                            });
+                            state.alerting = true;
+                            (event, HealthStatus::Unhealthy, failures)
+                        } else {
+                            (None, HealthStatus::Degraded, failures)
+                        }
+                    }
+                }
+            };
+
+            let _ = self.health.update_check(&name, status, result.err()).await;
+            let _ = self
+                .health
+                .add_detail(&name, "consecutive_failures", failures.into())
+                .await;
+            let _ = self
+                .health
+                .add_detail(&name, "last_duration_ms", elapsed_ms.into())
+                .await;
+
+            if let Some(event) = event {
+                for listener in &self.listeners {
+                    listener.on_probe_event(&event).await;
+                }
+            }
+        }
+    }
+
+    /// Run the probes every `interval` until the returned task is aborted.
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                self.run_once().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    struct RecordingListener(Arc<std::sync::Mutex<Vec<ProbeEvent>>>);
+
+    #[async_trait::async_trait]
+    impl ProbeListener for RecordingListener {
+        async fn on_probe_event(&self, event: &ProbeEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failure_streak_alerts_and_recovers() {
+        let healthy = Arc::new(AtomicBool::new(false));
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let health = HealthService::new();
+
+        let flag = healthy.clone();
+        let runner = ProbeRunner::new(health.clone())
+            .failure_threshold(2)
+            .listener(Box::new(RecordingListener(events.clone())))
+            .probe(FnProbe::new("canary", move || {
+                let ok = flag.load(Ordering::SeqCst);
+                async move {
+                    if ok {
+                        Ok(())
+                    } else {
+                        Err("canary missing".to_string())
+                    }
+                }
+            }));
+
+        runner.run_once().await;
+        assert_eq!(
+            health.get_check("canary").await.unwrap().status,
+            HealthStatus::Degraded
+        );
+        runner.run_once().await;
+        runner.run_once().await;
+        assert_eq!(
+            health.get_check("canary").await.unwrap().status,
+            HealthStatus::Unhealthy
+        );
+
+        healthy.store(true, Ordering::SeqCst);
+        runner.run_once().await;
+        assert_eq!(health.get_overall_status().await, HealthStatus::Healthy);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ProbeEvent::Failing {
+                    probe: "canary".to_string(),
+                    consecutive_failures: 2,
+                    error: "canary missing".to_string(),
+                },
+                ProbeEvent::Recovered {
+                    probe: "canary".to_string()
+                },
+            ]
+        );
+        let metrics = runner.metrics("canary").await.unwrap();
+        assert_eq!((metrics.requests_total, metrics.requests_failed), (4, 3));
+    }
+}
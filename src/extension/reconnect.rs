@@ -0,0 +1,242 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::extension::context::Settings;
+use crate::extension::lifecycle::{ExtensionState, LifecycleManager};
+use crate::extension::resilience::RetryPolicy;
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+use crate::transport::TransportClient;
+
+/// Whether the runner re-registers after losing the cluster.
+pub fn reconnect_enabled() -> Setting<bool> {
+    Setting::bool("extension.reconnect.enabled").default(true)
+}
+
+/// How often the cluster's transport endpoint is checked.
+pub fn heartbeat_interval() -> Setting<TimeValue> {
+    Setting::time("extension.reconnect.heartbeat_interval")
+        .default(TimeValue::seconds(10))
+        .min(TimeValue::seconds(1))
+}
+
+/// Consecutive failed heartbeats after which the cluster counts as lost.
+pub fn heartbeat_failures() -> Setting<i64> {
+    Setting::int("extension.reconnect.heartbeat_failures")
+        .default(3)
+        .min(1)
+}
+
+/// Watches the connection to OpenSearch while the extension runs. Once
+/// `failure_threshold` heartbeats in a row fail, for instance because the
+/// node restarted, the extension moves to `Reconnecting` and registration
+/// is retried with backoff until it succeeds, then it is `Running` again.
+pub struct ReconnectWatchdog {
+    lifecycle: Arc<LifecycleManager>,
+    client: Arc<TransportClient>,
+    interval: Duration,
+    failure_threshold: u32,
+    backoff: RetryPolicy,
+}
+
+impl ReconnectWatchdog {
+    pub fn new(lifecycle: Arc<LifecycleManager>, client: Arc<TransportClient>) -> Self {
+        ReconnectWatchdog {
+            lifecycle,
+            client,
+            interval: Duration::from_secs(10),
+            failure_threshold: 3,
+            backoff: RetryPolicy {
+                max_attempts: u32::MAX,
+                initial_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(60),
+                ..RetryPolicy::default()
+            },
+        }
+    }
+
+    /// A watchdog configured by the `reconnect_*` and `heartbeat_*`
+    /// settings, or `None` when reconnecting is disabled.
+    pub fn from_settings(
+        lifecycle: Arc<LifecycleManager>,
+        client: Arc<TransportClient>,
+        settings: &Settings,
+    ) -> Result<Option<Self>, ExtensionError> {
+        if !reconnect_enabled().get(settings)? {
+            return Ok(None);
+        }
+        Ok(Some(
+            Self::new(lifecycle, client)
+                .interval(heartbeat_interval().get(settings)?.as_duration())
+                .failure_threshold(heartbeat_failures().get(settings)? as u32),
+        ))
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// Delays between registration attempts while reconnecting.
+    pub fn backoff(mut self, backoff: RetryPolicy) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    async fn heartbeat(&self) -> Result<(), ExtensionError> {
+        self.client.connect().await.map(drop)
+    }
+
+    /// Heartbeat every `interval` and, once the cluster is lost, call
+    /// `register` until it succeeds. Returns when the extension stops.
+    pub async fn run<F, Fut>(&self, mut register: F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), ExtensionError>>,
+    {
+        let mut ticker = tokio::time::interval(self.interval);
+        let mut failures = 0;
+        loop {
+            ticker.tick().await;
+            let state = self.lifecycle.current_state().await;
+            if state.is_terminal() || state == ExtensionState::Stopping {
+                return;
+            }
+            if !state.is_running() {
+                continue;
+            }
+            match self.heartbeat().await {
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    debug!(
+                        "Heartbeat {} of {} to OpenSearch failed: {}",
+                        failures, self.failure_threshold, e
+                    );
+                }
+            }
+            if failures < self.failure_threshold {
+                continue;
+            }
+            failures = 0;
+            if let Err(e) = self
+                .lifecycle
+                .transition_to(ExtensionState::Reconnecting)
+                .await
+            {
+                warn!("Cannot start reconnecting: {}", e);
+                continue;
+            }
+            warn!("Lost the connection to OpenSearch, registering again");
+            self.reconnect(&mut register).await;
+            ticker.reset();
+        }
+    }
+
+    async fn reconnect<F, Fut>(&self, register: &mut F)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), ExtensionError>>,
+    {
+        let mut attempt = 0;
+        while self.lifecycle.current_state().await == ExtensionState::Reconnecting {
+            attempt += 1;
+            match register().await {
+                Ok(()) => {
+                    info!(
+                        "Registered with OpenSearch again after {} attempts",
+                        attempt
+                    );
+                    if let Err(e) = self.lifecycle.transition_to(ExtensionState::Running).await {
+                        warn!("Cannot resume running after reconnecting: {}", e);
+                    }
+                    return;
+                }
+                Err(e) => {
+                    let delay = self.backoff.retry_delay(attempt, &e);
+                    warn!(
+                        "Registration attempt {} failed, retrying in {:?}: {}",
+                        attempt, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_reregisters_after_lost_heartbeats() {
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let lifecycle = Arc::new(LifecycleManager::new());
+        for state in [
+            ExtensionState::Initializing,
+            ExtensionState::Initialized,
+            ExtensionState::Running,
+        ] {
+            lifecycle.transition_to(state).await.unwrap();
+        }
+        let watchdog = ReconnectWatchdog::new(
+            lifecycle.clone(),
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+        )
+        .interval(Duration::from_millis(5))
+        .failure_threshold(2)
+        .backoff(RetryPolicy {
+            initial_delay: Duration::from_millis(1),
+            jitter: false,
+            ..RetryPolicy::default()
+        });
+
+        let attempts = Arc::new(AtomicU32::new(0));
+        let registered = attempts.clone();
+        let watching = tokio::spawn(async move {
+            watchdog
+                .run(move || {
+                    let attempt = registered.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        match attempt {
+                            0 => Err(ExtensionError::transport("connection refused")),
+                            _ => Ok(()),
+                        }
+                    }
+                })
+                .await
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while attempts.load(Ordering::SeqCst) < 2
+                || lifecycle.current_state().await != ExtensionState::Running
+            {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        lifecycle
+            .transition_to(ExtensionState::Stopping)
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), watching)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -1,6 +1,7 @@
+use crate::extension::{Extension, ExtensionDependency, ExtensionError};
+use crate::transport::TransportClient;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
-use crate::extension::{Extension, ExtensionDependency, ExtensionError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtensionIdentity {
@@ -29,17 +30,17 @@ impl ExtensionIdentity {
             dependencies: extension.dependencies(),
         }
     }
-    
+
     pub fn with_description(mut self, description: impl Into<String>) -> Self {
         self.description = Some(description.into());
         self
     }
-    
+
     pub fn with_vendor(mut self, vendor: impl Into<String>) -> Self {
         self.vendor = Some(vendor.into());
         self
     }
-    
+
     pub fn with_license(mut self, license: impl Into<String>) -> Self {
         self.license = Some(license.into());
         self
@@ -63,6 +64,29 @@ pub struct ExtensionCapabilities {
     pub supports_cluster_settings: bool,
 }
 
+/// One of the `ExtensionCapabilities` flags, for discovery queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    RestActions,
+    NamedWriteable,
+    ActionExtension,
+    SettingsExtension,
+    ClusterSettings,
+}
+
+impl ExtensionCapabilities {
+    pub fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::RestActions => self.supports_rest_actions,
+            Capability::NamedWriteable => self.supports_named_writeable,
+            Capability::ActionExtension => self.supports_action_extension,
+            Capability::SettingsExtension => self.supports_settings_extension,
+            Capability::ClusterSettings => self.supports_cluster_settings,
+        }
+    }
+}
+
 impl ExtensionRegistration {
     pub fn new(identity: ExtensionIdentity, host: String, port: u16) -> Self {
         ExtensionRegistration {
@@ -72,18 +96,17 @@ impl ExtensionRegistration {
             capabilities: ExtensionCapabilities::default(),
         }
     }
-    
+
     pub fn with_capabilities(mut self, capabilities: ExtensionCapabilities) -> Self {
         self.capabilities = capabilities;
         self
     }
-    
+
     pub fn socket_address(&self) -> Result<SocketAddr, ExtensionError> {
         let addr_str = format!("{}:{}", self.host, self.port);
-        addr_str.parse()
-            .map_err(|e| ExtensionError::configuration(
-                format!("Invalid socket address {}: {}", addr_str, e)
-            ))
+        addr_str.parse().map_err(|e| {
+            ExtensionError::configuration(format!("Invalid socket address {}: {}", addr_str, e))
+        })
     }
 }
 
@@ -95,50 +118,53 @@ impl RegistrationProtocol {
     pub fn new(registration: ExtensionRegistration) -> Self {
         RegistrationProtocol { registration }
     }
-    
+
     pub async fn register_with_opensearch(
         &self,
         opensearch_addr: &str,
     ) -> Result<RegistrationResponse, ExtensionError> {
-        use crate::transport::TransportClient;
-        
         // Parse address to extract host and port
         let (host, port) = if let Some(colon_pos) = opensearch_addr.rfind(':') {
             let host = &opensearch_addr[..colon_pos];
             let port_str = &opensearch_addr[colon_pos + 1..];
-            let port = port_str
-                .parse::<u16>()
-                .map_err(|_| ExtensionError::configuration(
-                    format!("Invalid port '{}' in address '{}'", port_str, opensearch_addr)
-                ))?;
+            let port = port_str.parse::<u16>().map_err(|_| {
+                ExtensionError::configuration(format!(
+                    "Invalid port '{}' in address '{}'",
+                    port_str, opensearch_addr
+                ))
+            })?;
             (host, port)
         } else {
             (opensearch_addr, 9300)
         };
-        
-        let client = TransportClient::new(host, port);
-        
+
+        self.register_with(&TransportClient::new(host, port)).await
+    }
+
+    /// Register through `client`, i.e. with the node it is configured for.
+    pub async fn register_with(
+        &self,
+        client: &TransportClient,
+    ) -> Result<RegistrationResponse, ExtensionError> {
         let registration_bytes = self.serialize_registration()?;
-        
+
         let response_bytes = client
             .send_request("internal:discovery/register", &registration_bytes)
             .await?;
-        
+
         self.deserialize_response(&response_bytes)
     }
-    
+
     fn serialize_registration(&self) -> Result<Vec<u8>, ExtensionError> {
-        serde_json::to_vec(&self.registration)
-            .map_err(|e| ExtensionError::serialization(
-                format!("Failed to serialize registration: {}", e)
-            ))
+        serde_json::to_vec(&self.registration).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize registration: {}", e))
+        })
     }
-    
+
     fn deserialize_response(&self, bytes: &[u8]) -> Result<RegistrationResponse, ExtensionError> {
-        serde_json::from_slice(bytes)
-            .map_err(|e| ExtensionError::serialization(
-                format!("Failed to deserialize response: {}", e)
-            ))
+        serde_json::from_slice(bytes).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to deserialize response: {}", e))
+        })
     }
 }
 
@@ -149,32 +175,44 @@ pub struct RegistrationResponse {
     pub message: Option<String>,
     pub cluster_name: Option<String>,
     pub cluster_uuid: Option<String>,
+    /// Token for the extension's service account, when the cluster runs
+    /// the security plugin.
+    #[serde(default)]
+    pub service_account_token: Option<String>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use async_trait::async_trait;
     use crate::extension::{Extension, ExtensionContext};
-    
+    use async_trait::async_trait;
+
     struct TestExtension;
-    
+
     #[async_trait]
     impl Extension for TestExtension {
-        fn name(&self) -> &str { "test" }
-        fn unique_id(&self) -> &str { "test-ext" }
-        fn version(&self) -> &str { "1.0.0" }
-        fn opensearch_version(&self) -> &str { "3.0.0" }
-        
+        fn name(&self) -> &str {
+            "test"
+        }
+        fn unique_id(&self) -> &str {
+            "test-ext"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
         async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
             Ok(())
         }
-        
+
         async fn shutdown(&mut self) -> Result<(), ExtensionError> {
             Ok(())
         }
     }
-    
+
     #[test]
     fn test_extension_identity_creation() {
         let extension = TestExtension;
@@ -182,7 +220,7 @@ mod tests {
             .with_description("Test extension")
             .with_vendor("Test Inc")
             .with_license("MIT");
-        
+
         assert_eq!(identity.name, "test");
         assert_eq!(identity.unique_id, "test-ext");
         assert_eq!(identity.version, "1.0.0");
@@ -190,12 +228,12 @@ mod tests {
         assert_eq!(identity.vendor, Some("Test Inc".to_string()));
         assert_eq!(identity.license, Some("MIT".to_string()));
     }
-    
+
     #[test]
     fn test_registration_socket_address() {
         let identity = ExtensionIdentity::from_extension(&TestExtension);
         let registration = ExtensionRegistration::new(identity, "127.0.0.1".to_string(), 1234);
-        
+
         let addr = registration.socket_address().unwrap();
         assert_eq!(addr.to_string(), "127.0.0.1:1234");
     }
@@ -205,17 +243,20 @@ mod tests {
         let identity = ExtensionIdentity::from_extension(&TestExtension);
         let registration = ExtensionRegistration::new(identity, "127.0.0.1".to_string(), 1234);
         let protocol = RegistrationProtocol::new(registration.clone());
-        
+
         // Test serialization
         let result = protocol.serialize_registration();
         assert!(result.is_ok());
         let bytes = result.unwrap();
         assert!(!bytes.is_empty());
-        
+
         // Verify it's valid JSON
         let parsed: Result<ExtensionRegistration, _> = serde_json::from_slice(&bytes);
         assert!(parsed.is_ok());
-        assert_eq!(parsed.unwrap().identity.unique_id, registration.identity.unique_id);
+        assert_eq!(
+            parsed.unwrap().identity.unique_id,
+            registration.identity.unique_id
+        );
     }
 
     #[test]
@@ -223,17 +264,18 @@ mod tests {
         let protocol = RegistrationProtocol::new(ExtensionRegistration::new(
             ExtensionIdentity::from_extension(&TestExtension),
             "127.0.0.1".to_string(),
-            1234
+            1234,
         ));
-        
+
         // Test successful response
-        let success_json = r#"{"success": true, "message": "Registered", "cluster_name": "test-cluster"}"#;
+        let success_json =
+            r#"{"success": true, "message": "Registered", "cluster_name": "test-cluster"}"#;
         let result = protocol.deserialize_response(success_json.as_bytes());
         assert!(result.is_ok());
         let response = result.unwrap();
         assert!(response.success);
         assert_eq!(response.cluster_name, Some("test-cluster".to_string()));
-        
+
         // Test error response
         let error_json = r#"{"success": false, "message": "Failed to register"}"#;
         let result = protocol.deserialize_response(error_json.as_bytes());
@@ -241,10 +283,10 @@ mod tests {
         let response = result.unwrap();
         assert!(!response.success);
         assert_eq!(response.message, Some("Failed to register".to_string()));
-        
+
         // Test malformed response
         let malformed = b"not json";
         let result = protocol.deserialize_response(malformed);
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,16 @@
-use std::sync::Arc;
+use crate::extension::events::{EventBus, SdkEvent};
+use crate::extension::health::{HealthCheck, HealthCheckProvider, HealthService, HealthStatus};
+use crate::extension::{ExtensionError, Retryable};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError};
 use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
 use tokio::time::sleep;
-use crate::extension::ExtensionError;
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct RetryPolicy {
@@ -11,6 +19,8 @@ pub struct RetryPolicy {
     pub max_delay: Duration,
     pub exponential_base: f32,
     pub jitter: bool,
+    /// Budget shared with other policies that retries are drawn from.
+    pub budget: Option<Arc<RetryBudget>>,
 }
 
 impl Default for RetryPolicy {
@@ -21,10 +31,46 @@ impl Default for RetryPolicy {
             max_delay: Duration::from_secs(30),
             exponential_base: 2.0,
             jitter: true,
+            budget: None,
         }
     }
 }
 
+impl RetryPolicy {
+    pub fn with_budget(mut self, budget: Arc<RetryBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Delay to wait after the `attempt`th failed attempt, counting from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = Duration::from_secs_f32(
+            (self.initial_delay.as_secs_f32() * self.exponential_base.powi(exponent))
+                .min(self.max_delay.as_secs_f32()),
+        );
+        if self.jitter {
+            delay.mul_f32(1.0 + rand::random::<f32>() * 0.3)
+        } else {
+            delay
+        }
+    }
+
+    /// Delay after the `attempt`th attempt failed with `error`: the
+    /// callee's retry-after hint when it asks for longer than `delay`.
+    pub fn retry_delay(&self, attempt: u32, error: &ExtensionError) -> Duration {
+        let delay = self.delay(attempt);
+        match error.retryable() {
+            Retryable::Throttled(Some(retry_after)) => delay.max(retry_after),
+            _ => delay,
+        }
+    }
+}
+
+/// Run `operation` until it succeeds or `policy.max_attempts` are used up.
+/// Non-retryable errors are returned at once, and throttled ones wait at
+/// least as long as the callee asked. With a budget, an error is also
+/// returned at once when the budget has no retry left.
 pub async fn retry_with_policy<F, Fut, T>(
     policy: &RetryPolicy,
     mut operation: F,
@@ -34,48 +80,247 @@ where
     Fut: std::future::Future<Output = Result<T, ExtensionError>>,
 {
     let mut attempt = 0;
-    let mut delay = policy.initial_delay;
-    
+    if let Some(budget) = &policy.budget {
+        budget.record_request()?;
+    }
+
     loop {
         attempt += 1;
-        
+
         match operation().await {
             Ok(result) => return Ok(result),
+            Err(e) if e.retryable() == Retryable::NonRetryable => return Err(e),
             Err(e) if attempt >= policy.max_attempts => {
-                return Err(ExtensionError::unknown(
-                    format!("Operation failed after {} attempts: {}", policy.max_attempts, e)
-                ));
+                return Err(ExtensionError::unknown(format!(
+                    "Operation failed after {} attempts: {}",
+                    policy.max_attempts, e
+                )));
             }
-            Err(_) => {
-                let jittered_delay = if policy.jitter {
-                    let jitter = rand::random::<f32>() * 0.3;
-                    delay.mul_f32(1.0 + jitter)
-                } else {
-                    delay
-                };
-                
-                sleep(jittered_delay).await;
-                
-                delay = Duration::from_secs_f32(
-                    (delay.as_secs_f32() * policy.exponential_base).min(policy.max_delay.as_secs_f32())
-                );
+            Err(e) => {
+                if let Some(budget) = &policy.budget {
+                    if !budget.try_withdraw()? {
+                        warn!("Not retrying, retry budget exhausted: {}", e);
+                        return Err(e);
+                    }
+                }
+                sleep(policy.retry_delay(attempt, &e)).await
             }
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Health check `CircuitBreakerRegistry` reports breaker states under.
+pub const CIRCUIT_BREAKERS_CHECK: &str = "circuit_breakers";
+
+/// Fail `operation` with a timeout error if it takes longer than `timeout`.
+/// Wrapped in a retry closure, each attempt gets the full `timeout`.
+pub async fn with_timeout<F, T>(operation: F, timeout: Duration) -> Result<T, ExtensionError>
+where
+    F: Future<Output = Result<T, ExtensionError>>,
+{
+    tokio::time::timeout(timeout, operation)
+        .await
+        .map_err(|_| ExtensionError::timeout(format!("Operation timed out after {:?}", timeout)))?
+}
+
+/// Run `fallback` with the error if `operation` fails, e.g. to serve a
+/// default or stale value while a breaker is open.
+pub async fn with_fallback<F, T, Fb, FbFut>(operation: F, fallback: Fb) -> Result<T, ExtensionError>
+where
+    F: Future<Output = Result<T, ExtensionError>>,
+    Fb: FnOnce(ExtensionError) -> FbFut,
+    FbFut: Future<Output = Result<T, ExtensionError>>,
+{
+    match operation.await {
+        Ok(value) => Ok(value),
+        Err(e) => fallback(e).await,
+    }
+}
+
+/// Successful results kept for `ttl`, for use with `with_cache`.
+pub struct ResultCache<K, V> {
+    ttl: Duration,
+    entries: std::sync::Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> ResultCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        ResultCache {
+            ttl,
+            entries: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The value cached for `key` if it has not expired.
+    pub fn get(&self, key: &K) -> Result<Option<V>, ExtensionError> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(match entries.get(key) {
+            Some((at, value)) if at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        })
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Result<(), ExtensionError> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|_, (at, _)| at.elapsed() < self.ttl);
+        entries.insert(key, (Instant::now(), value));
+        Ok(())
+    }
+
+    pub fn invalidate(&self, key: &K) -> Result<(), ExtensionError> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+        Ok(())
+    }
+}
+
+/// Serve `key` from `cache` while fresh, otherwise run `operation` and
+/// cache its result. Errors are not cached.
+pub async fn with_cache<K, V, F>(
+    cache: &ResultCache<K, V>,
+    key: K,
+    operation: F,
+) -> Result<V, ExtensionError>
+where
+    K: Eq + Hash,
+    V: Clone,
+    F: Future<Output = Result<V, ExtensionError>>,
+{
+    if let Some(value) = cache.get(&key)? {
+        return Ok(value);
+    }
+    let value = operation.await?;
+    cache.insert(key, value.clone())?;
+    Ok(value)
+}
+
+/// Number of buckets a `RetryBudget` window is split into.
+const BUDGET_BUCKETS: u64 = 10;
+
+/// Caps retries at a share of the requests made over a sliding window, so
+/// a mass failure does not multiply the load on a struggling cluster.
+/// Share one budget between the policies calling the same dependency.
+#[derive(Debug)]
+pub struct RetryBudget {
+    ratio: f64,
+    min_retries: u64,
+    bucket_width: Duration,
+    started: Instant,
+    /// `(bucket index, requests, retries)` per slot of the ring.
+    buckets: std::sync::Mutex<Vec<(u64, u64, u64)>>,
+}
+
+impl RetryBudget {
+    /// Allow retries of up to `ratio` of the requests in the last `window`,
+    /// e.g. `RetryBudget::new(0.2, Duration::from_secs(10))`.
+    pub fn new(ratio: f64, window: Duration) -> Self {
+        RetryBudget {
+            ratio,
+            min_retries: 10,
+            bucket_width: (window / BUDGET_BUCKETS as u32).max(Duration::from_millis(1)),
+            started: Instant::now(),
+            buckets: std::sync::Mutex::new(vec![(0, 0, 0); BUDGET_BUCKETS as usize]),
+        }
+    }
+
+    /// Retries allowed per window regardless of the ratio, so that a
+    /// trickle of requests can still be retried. Defaults to 10.
+    pub fn min_retries(mut self, min_retries: u64) -> Self {
+        self.min_retries = min_retries;
+        self
+    }
+
+    fn current_bucket(&self) -> u64 {
+        (self.started.elapsed().as_nanos() / self.bucket_width.as_nanos()) as u64
+    }
+
+    fn update(
+        &self,
+        change: impl FnOnce(&mut (u64, u64, u64), u64, u64) -> bool,
+    ) -> Result<bool, ExtensionError> {
+        let now = self.current_bucket();
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let (mut requests, mut retries) = (0, 0);
+        for (index, bucket_requests, bucket_retries) in buckets.iter() {
+            if now.saturating_sub(*index) < BUDGET_BUCKETS {
+                requests += bucket_requests;
+                retries += bucket_retries;
+            }
+        }
+        let slot = &mut buckets[(now % BUDGET_BUCKETS) as usize];
+        if slot.0 != now {
+            *slot = (now, 0, 0);
+        }
+        Ok(change(slot, requests, retries))
+    }
+
+    /// Count a first attempt.
+    pub fn record_request(&self) -> Result<(), ExtensionError> {
+        self.update(|slot, _, _| {
+            slot.1 += 1;
+            true
+        })?;
+        Ok(())
+    }
+
+    /// Take a retry from the budget, returning `false` if none is left.
+    pub fn try_withdraw(&self) -> Result<bool, ExtensionError> {
+        let (ratio, min_retries) = (self.ratio, self.min_retries);
+        self.update(|slot, requests, retries| {
+            let allowed = ((requests as f64 * ratio) as u64).max(min_retries);
+            if retries >= allowed {
+                return false;
+            }
+            slot.2 += 1;
+            true
+        })
+    }
+
+    /// Requests and retries in the current window.
+    pub fn usage(&self) -> Result<(u64, u64), ExtensionError> {
+        let mut usage = (0, 0);
+        self.update(|_, requests, retries| {
+            usage = (requests, retries);
+            true
+        })?;
+        Ok(usage)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CircuitState {
     Closed,
     Open,
     HalfOpen,
 }
 
+/// Called with the old and new state on every transition of a breaker.
+pub type CircuitListener = dyn Fn(CircuitState, CircuitState) + Send + Sync;
+
+/// Trip when at least `failure_rate` of the calls in the last `window`
+/// failed, once there were at least `minimum_calls` of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FailureRateWindow {
+    pub failure_rate: f64,
+    pub window: Duration,
+    pub minimum_calls: u32,
+}
+
 pub struct CircuitBreaker {
     state: Arc<Mutex<CircuitBreakerState>>,
     failure_threshold: u32,
     success_threshold: u32,
     timeout: Duration,
+    failure_rate: Option<FailureRateWindow>,
+    events: Option<(String, EventBus)>,
+    listeners: Vec<Arc<CircuitListener>>,
 }
 
 struct CircuitBreakerState {
@@ -83,6 +328,8 @@ struct CircuitBreakerState {
     failure_count: u32,
     success_count: u32,
     last_failure_time: Option<Instant>,
+    /// Outcomes of the calls within the failure-rate window, oldest first.
+    outcomes: VecDeque<(Instant, bool)>,
 }
 
 impl CircuitBreaker {
@@ -93,39 +340,115 @@ impl CircuitBreaker {
                 failure_count: 0,
                 success_count: 0,
                 last_failure_time: None,
+                outcomes: VecDeque::new(),
             })),
             failure_threshold,
             success_threshold,
             timeout,
+            failure_rate: None,
+            events: None,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Trip on the failure rate over a rolling window instead of on
+    /// `failure_threshold` consecutive failures.
+    pub fn with_failure_rate(
+        mut self,
+        failure_rate: f64,
+        window: Duration,
+        minimum_calls: u32,
+    ) -> Self {
+        self.failure_rate = Some(FailureRateWindow {
+            failure_rate,
+            window,
+            minimum_calls: minimum_calls.max(1),
+        });
+        self
+    }
+
+    /// Call `listener` on every state change.
+    pub fn with_listener(
+        mut self,
+        listener: impl Fn(CircuitState, CircuitState) + Send + Sync + 'static,
+    ) -> Self {
+        self.listeners.push(Arc::new(listener));
+        self
+    }
+
+    /// Publish this breaker's state changes on `events` under `name`.
+    pub fn with_events(mut self, name: impl Into<String>, events: EventBus) -> Self {
+        self.events = Some((name.into(), events));
+        self
+    }
+
+    fn transition(&self, state: &mut CircuitBreakerState, to: CircuitState) {
+        let from = std::mem::replace(&mut state.state, to);
+        if from == to {
+            return;
+        }
+        state.outcomes.clear();
+        if let Some((name, events)) = &self.events {
+            events.publish(SdkEvent::CircuitStateChanged {
+                breaker: name.clone(),
+                from,
+                to,
+            });
+        }
+        for listener in &self.listeners {
+            listener(from, to);
+        }
+    }
+
+    /// Record an outcome in the window and return whether a closed breaker
+    /// should trip.
+    fn record(&self, state: &mut CircuitBreakerState, success: bool) -> bool {
+        let Some(window) = self.failure_rate else {
+            return !success && state.failure_count >= self.failure_threshold;
+        };
+        let now = Instant::now();
+        state.outcomes.push_back((now, success));
+        while state
+            .outcomes
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > window.window)
+        {
+            state.outcomes.pop_front();
         }
+        let calls = state.outcomes.len();
+        let failures = state
+            .outcomes
+            .iter()
+            .filter(|(_, success)| !success)
+            .count();
+        !success
+            && calls >= window.minimum_calls as usize
+            && failures as f64 / calls as f64 >= window.failure_rate
     }
-    
+
     pub async fn call<F, Fut, T>(&self, operation: F) -> Result<T, ExtensionError>
     where
         F: FnOnce() -> Fut,
         Fut: std::future::Future<Output = Result<T, ExtensionError>>,
     {
         let mut state = self.state.lock().await;
-        
-        match state.state {
-            CircuitState::Open => {
-                if let Some(last_failure) = state.last_failure_time {
-                    if last_failure.elapsed() >= self.timeout {
-                        state.state = CircuitState::HalfOpen;
-                        state.failure_count = 0;
-                        state.success_count = 0;
-                    } else {
-                        return Err(ExtensionError::unknown("Circuit breaker is open"));
-                    }
+
+        if state.state == CircuitState::Open {
+            if let Some(last_failure) = state.last_failure_time {
+                if last_failure.elapsed() >= self.timeout {
+                    self.transition(&mut state, CircuitState::HalfOpen);
+                    state.failure_count = 0;
+                    state.success_count = 0;
                 } else {
                     return Err(ExtensionError::unknown("Circuit breaker is open"));
                 }
+            } else {
+                return Err(ExtensionError::unknown("Circuit breaker is open"));
             }
-            _ => {}
         }
-        
+
         drop(state);
-        
+
         match operation().await {
             Ok(result) => {
                 let mut state = self.state.lock().await;
@@ -133,12 +456,13 @@ impl CircuitBreaker {
                     CircuitState::HalfOpen => {
                         state.success_count += 1;
                         if state.success_count >= self.success_threshold {
-                            state.state = CircuitState::Closed;
+                            self.transition(&mut state, CircuitState::Closed);
                             state.failure_count = 0;
                         }
                     }
                     CircuitState::Closed => {
                         state.failure_count = 0;
+                        self.record(&mut state, true);
                     }
                     _ => {}
                 }
@@ -148,33 +472,582 @@ impl CircuitBreaker {
                 let mut state = self.state.lock().await;
                 state.failure_count += 1;
                 state.last_failure_time = Some(Instant::now());
-                
-                match state.state {
-                    CircuitState::Closed => {
-                        if state.failure_count >= self.failure_threshold {
-                            state.state = CircuitState::Open;
-                        }
+
+                let current = state.state;
+                match current {
+                    CircuitState::Closed if self.record(&mut state, false) => {
+                        self.transition(&mut state, CircuitState::Open);
                     }
                     CircuitState::HalfOpen => {
-                        state.state = CircuitState::Open;
+                        self.transition(&mut state, CircuitState::Open);
                     }
                     _ => {}
                 }
-                
+
                 Err(e)
             }
         }
     }
-    
+
     pub async fn get_state(&self) -> CircuitState {
         self.state.lock().await.state
     }
 }
 
+type BreakerFactory = dyn Fn(&str) -> CircuitBreaker + Send + Sync;
+
+/// Circuit breakers keyed by endpoint or action, created on first use, so
+/// each downstream dependency breaks independently of the others. Breaker
+/// states are reported as the `circuit_breakers` health check: degraded
+/// while any breaker is not closed.
+pub struct CircuitBreakerRegistry {
+    factory: Box<BreakerFactory>,
+    breakers: std::sync::Mutex<BTreeMap<String, Arc<CircuitBreaker>>>,
+    states: Arc<std::sync::Mutex<BTreeMap<String, CircuitState>>>,
+    events: Option<EventBus>,
+    health: Option<HealthService>,
+}
+
+impl CircuitBreakerRegistry {
+    /// A registry creating the breaker of each key with `factory`.
+    pub fn new(factory: impl Fn(&str) -> CircuitBreaker + Send + Sync + 'static) -> Self {
+        CircuitBreakerRegistry {
+            factory: Box::new(factory),
+            breakers: std::sync::Mutex::new(BTreeMap::new()),
+            states: Arc::new(std::sync::Mutex::new(BTreeMap::new())),
+            events: None,
+            health: None,
+        }
+    }
+
+    /// Publish state changes of the breakers on `events`, named by key.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Keep the `circuit_breakers` check of `health` up to date.
+    pub fn with_health(mut self, health: HealthService) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// The breaker of `key`, created on first use.
+    pub fn get(&self, key: &str) -> Result<Arc<CircuitBreaker>, ExtensionError> {
+        self.get_with(key, || (self.factory)(key))
+    }
+
+    /// The breaker of `key`, created with `make` instead of the registry's
+    /// factory on first use.
+    pub fn get_with(
+        &self,
+        key: &str,
+        make: impl FnOnce() -> CircuitBreaker,
+    ) -> Result<Arc<CircuitBreaker>, ExtensionError> {
+        let mut breakers = self.breakers.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(breaker) = breakers.get(key) {
+            return Ok(breaker.clone());
+        }
+        let mut breaker = make();
+        if let Some(events) = &self.events {
+            breaker = breaker.with_events(key, events.clone());
+        }
+        let (states, health, name) = (self.states.clone(), self.health.clone(), key.to_string());
+        breaker = breaker.with_listener(move |_, to| {
+            let mut states = states.lock().unwrap_or_else(PoisonError::into_inner);
+            states.insert(name.clone(), to);
+            let snapshot = states.clone();
+            drop(states);
+            if let (Some(health), Ok(runtime)) =
+                (health.clone(), tokio::runtime::Handle::try_current())
+            {
+                runtime.spawn(async move { update_health(&health, &snapshot).await });
+            }
+        });
+        self.state_map()
+            .insert(key.to_string(), CircuitState::Closed);
+        let breaker = Arc::new(breaker);
+        breakers.insert(key.to_string(), breaker.clone());
+        Ok(breaker)
+    }
+
+    /// Drop every breaker, so each is created afresh on next use.
+    pub fn clear(&self) -> Result<(), ExtensionError> {
+        self.breakers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.state_map().clear();
+        if let (Some(health), Ok(runtime)) =
+            (self.health.clone(), tokio::runtime::Handle::try_current())
+        {
+            runtime.spawn(async move { update_health(&health, &BTreeMap::new()).await });
+        }
+        Ok(())
+    }
+
+    fn state_map(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, CircuitState>> {
+        self.states.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// State of every breaker created so far, by key.
+    pub fn states(&self) -> Result<BTreeMap<String, CircuitState>, ExtensionError> {
+        Ok(self.state_map().clone())
+    }
+
+    /// Write the current states to the `circuit_breakers` check of `health`.
+    pub async fn report_health(&self, health: &HealthService) -> Result<(), ExtensionError> {
+        update_health(health, &self.states()?).await;
+        Ok(())
+    }
+}
+
+impl Default for CircuitBreakerRegistry {
+    /// Breakers tripping after 5 consecutive failures and trying again after 30s.
+    fn default() -> Self {
+        Self::new(|_| CircuitBreaker::new(5, 1, Duration::from_secs(30)))
+    }
+}
+
+fn circuit_health(states: &BTreeMap<String, CircuitState>) -> (HealthStatus, Option<String>) {
+    let open: Vec<&str> = states
+        .iter()
+        .filter(|(_, state)| **state != CircuitState::Closed)
+        .map(|(key, _)| key.as_str())
+        .collect();
+    if open.is_empty() {
+        (HealthStatus::Healthy, None)
+    } else {
+        (
+            HealthStatus::Degraded,
+            Some(format!(
+                "circuit breakers not closed: [{}]",
+                open.join(", ")
+            )),
+        )
+    }
+}
+
+async fn update_health(health: &HealthService, states: &BTreeMap<String, CircuitState>) {
+    if health.get_check(CIRCUIT_BREAKERS_CHECK).await.is_none() {
+        health.register_check(CIRCUIT_BREAKERS_CHECK).await;
+    }
+    let (status, message) = circuit_health(states);
+    let _ = health
+        .update_check(CIRCUIT_BREAKERS_CHECK, status, message)
+        .await;
+    for (key, state) in states {
+        let _ = health
+            .add_detail(
+                CIRCUIT_BREAKERS_CHECK,
+                key.clone(),
+                serde_json::json!(state),
+            )
+            .await;
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheckProvider for CircuitBreakerRegistry {
+    async fn check_health(&self) -> HealthCheck {
+        let (states, (status, message)) = match self.states() {
+            Ok(states) => {
+                let health = circuit_health(&states);
+                (states, health)
+            }
+            Err(e) => (
+                BTreeMap::new(),
+                (HealthStatus::Unhealthy, Some(e.to_string())),
+            ),
+        };
+        HealthCheck {
+            name: CIRCUIT_BREAKERS_CHECK.to_string(),
+            status,
+            message,
+            details: states
+                .into_iter()
+                .map(|(key, state)| (key, serde_json::json!(state)))
+                .collect(),
+            last_check: std::time::SystemTime::now(),
+        }
+    }
+}
+
+/// Token bucket allowing `rate` operations per second on average, with
+/// bursts of up to `burst` after a quiet period.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: std::sync::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn per_second(rate: f64, burst: u32) -> Self {
+        RateLimiter {
+            rate,
+            burst: burst.max(1) as f64,
+            bucket: std::sync::Mutex::new((burst.max(1) as f64, Instant::now())),
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst as u32
+    }
+
+    fn bucket(&self) -> std::sync::MutexGuard<'_, (f64, Instant)> {
+        self.bucket.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Take a token if one is available, otherwise fail as throttled with
+    /// how long until one will be.
+    pub fn try_acquire(&self) -> Result<(), ExtensionError> {
+        let mut bucket = self.bucket();
+        let (tokens, last) = &mut *bucket;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * self.rate).min(self.burst);
+        *last = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            return Ok(());
+        }
+        let wait = if self.rate > 0.0 {
+            Duration::from_secs_f64((1.0 - *tokens) / self.rate)
+        } else {
+            Duration::MAX
+        };
+        Err(ExtensionError::throttled("Rate limit exceeded", Some(wait)))
+    }
+
+    /// Wait for a token and take it.
+    pub async fn acquire(&self) -> Result<(), ExtensionError> {
+        loop {
+            match self.try_acquire() {
+                Err(ExtensionError::Throttled {
+                    retry_after: Some(wait),
+                    ..
+                }) => sleep(wait).await,
+                result => return result,
+            }
+        }
+    }
+
+    /// Run `operation` once a token is available, e.g. to keep an
+    /// `SdkClient` below a request rate.
+    pub async fn call<F, Fut, T>(&self, operation: F) -> Result<T, ExtensionError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, ExtensionError>>,
+    {
+        self.acquire().await?;
+        operation().await
+    }
+
+    /// Whether the bucket has refilled completely, i.e. nothing was taken
+    /// recently.
+    pub fn is_idle(&self) -> Result<bool, ExtensionError> {
+        let (tokens, last) = *self.bucket();
+        Ok(tokens + last.elapsed().as_secs_f64() * self.rate >= self.burst)
+    }
+}
+
+/// Point-in-time usage of a `Bulkhead`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BulkheadStats {
+    pub name: String,
+    pub max_concurrent: usize,
+    pub max_queued: usize,
+    pub active: usize,
+    pub queued: usize,
+    pub completed_total: u64,
+    pub rejected_total: u64,
+}
+
+impl BulkheadStats {
+    /// Share of the concurrency limit in use, from 0 to 1.
+    pub fn saturation(&self) -> f64 {
+        self.active as f64 / self.max_concurrent as f64
+    }
+}
+
+/// Limits how many calls run a protected section at once. Calls over the
+/// limit wait in a queue of at most `max_queued`, optionally for at most
+/// `max_wait`; calls that find the queue full or wait too long are
+/// rejected rather than piling up on the runtime.
+pub struct Bulkhead {
+    name: String,
+    max_concurrent: usize,
+    max_queued: usize,
+    max_wait: Option<Duration>,
+    permits: Semaphore,
+    queued: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Leaves the queue when dropped, including when the waiting call is cancelled.
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Bulkhead {
+    pub fn new(name: impl Into<String>, max_concurrent: usize, max_queued: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Bulkhead {
+            name: name.into(),
+            max_concurrent,
+            max_queued,
+            max_wait: None,
+            permits: Semaphore::new(max_concurrent),
+            queued: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Reject calls that wait in the queue longer than `max_wait`.
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn call<F, Fut, T>(&self, operation: F) -> Result<T, ExtensionError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, ExtensionError>>,
+    {
+        let _permit = match self.permits.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queued {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    return Err(self.reject(ExtensionError::unknown(format!(
+                        "Bulkhead [{}] is full",
+                        self.name
+                    ))));
+                }
+                let slot = QueueSlot(&self.queued);
+                let acquired = match self.max_wait {
+                    Some(max_wait) => tokio::time::timeout(max_wait, self.permits.acquire())
+                        .await
+                        .map_err(|_| {
+                            ExtensionError::timeout(format!(
+                                "Bulkhead [{}] queue wait exceeded {:?}",
+                                self.name, max_wait
+                            ))
+                        }),
+                    None => Ok(self.permits.acquire().await),
+                };
+                drop(slot);
+                match acquired {
+                    Ok(permit) => permit.map_err(|_| {
+                        ExtensionError::unknown(format!("Bulkhead [{}] is closed", self.name))
+                    })?,
+                    Err(e) => return Err(self.reject(e)),
+                }
+            }
+        };
+        let result = operation().await;
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    fn reject(&self, error: ExtensionError) -> ExtensionError {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        error
+    }
+
+    pub fn stats(&self) -> BulkheadStats {
+        BulkheadStats {
+            name: self.name.clone(),
+            max_concurrent: self.max_concurrent,
+            max_queued: self.max_queued,
+            active: self.max_concurrent - self.permits.available_permits(),
+            queued: self.queued.load(Ordering::SeqCst),
+            completed_total: self.completed.load(Ordering::Relaxed),
+            rejected_total: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Weight of each successful call in the latency baseline, a moving
+/// average: a lasting change in latency moves the baseline within a few
+/// dozen calls, while a single fast or slow call barely does.
+const BASELINE_WEIGHT: f64 = 0.05;
+
+/// Point-in-time state of an `AdaptiveLimiter`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AdaptiveLimiterStats {
+    pub name: String,
+    pub limit: usize,
+    pub in_flight: usize,
+    pub baseline_latency_ms: Option<f64>,
+    pub rejected_total: u64,
+}
+
+/// Concurrency limit that follows observed latency instead of a hand-tuned
+/// number (AIMD). The baseline is a moving average of the latency of
+/// successful calls. While the limit is in use, each call finishing within
+/// `tolerance` times the baseline raises it by about one per
+/// limit's worth of calls; a slower call, a timeout or a throttled call
+/// multiplies it by `backoff`. Calls over the limit are rejected as
+/// throttled rather than queued.
+pub struct AdaptiveLimiter {
+    name: String,
+    min_limit: usize,
+    max_limit: usize,
+    tolerance: f64,
+    backoff: f64,
+    state: std::sync::Mutex<LimiterState>,
+    rejected: AtomicU64,
+}
+
+struct LimiterState {
+    limit: f64,
+    in_flight: usize,
+    baseline: Option<Duration>,
+}
+
+/// Leaves the in-flight count when dropped, including when the call is cancelled.
+struct InFlightSlot<'a>(&'a AdaptiveLimiter);
+
+impl Drop for InFlightSlot<'_> {
+    fn drop(&mut self) {
+        self.0.state().in_flight -= 1;
+    }
+}
+
+impl AdaptiveLimiter {
+    pub fn new(name: impl Into<String>, initial_limit: usize) -> Self {
+        AdaptiveLimiter {
+            name: name.into(),
+            min_limit: 1,
+            max_limit: 1000,
+            tolerance: 2.0,
+            backoff: 0.9,
+            state: std::sync::Mutex::new(LimiterState {
+                limit: initial_limit.max(1) as f64,
+                in_flight: 0,
+                baseline: None,
+            }),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Keep the limit between `min_limit` and `max_limit`.
+    pub fn limits(mut self, min_limit: usize, max_limit: usize) -> Self {
+        self.min_limit = min_limit.max(1);
+        self.max_limit = max_limit.max(self.min_limit);
+        if let Ok(state) = self.state.get_mut() {
+            state.limit = state
+                .limit
+                .clamp(self.min_limit as f64, self.max_limit as f64);
+        }
+        self
+    }
+
+    /// How many times the baseline latency a call may take before the limit
+    /// is lowered.
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance.max(1.0);
+        self
+    }
+
+    /// Factor the limit is multiplied by on overload, between 0 and 1.
+    pub fn backoff(mut self, backoff: f64) -> Self {
+        self.backoff = backoff.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn limit(&self) -> Result<usize, ExtensionError> {
+        Ok(self.state().limit as usize)
+    }
+
+    pub async fn call<F, Fut, T>(&self, operation: F) -> Result<T, ExtensionError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, ExtensionError>>,
+    {
+        let _slot = self.enter()?;
+        let start = Instant::now();
+        let result = operation().await;
+        match &result {
+            Ok(_) => self.on_sample(start.elapsed(), false)?,
+            Err(ExtensionError::TimeoutError(_) | ExtensionError::Throttled { .. }) => {
+                self.on_sample(start.elapsed(), true)?
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    fn enter(&self) -> Result<InFlightSlot<'_>, ExtensionError> {
+        let mut state = self.state();
+        if state.in_flight >= state.limit as usize {
+            drop(state);
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(ExtensionError::throttled(
+                format!("Concurrency limit of [{}] reached", self.name),
+                None,
+            ));
+        }
+        state.in_flight += 1;
+        Ok(InFlightSlot(self))
+    }
+
+    fn on_sample(&self, latency: Duration, overloaded: bool) -> Result<(), ExtensionError> {
+        let mut state = self.state();
+        let baseline = state.baseline.unwrap_or(latency);
+        if overloaded || latency.as_secs_f64() > baseline.as_secs_f64() * self.tolerance {
+            state.limit = (state.limit * self.backoff).max(self.min_limit as f64);
+        } else if state.in_flight as f64 * 2.0 >= state.limit {
+            state.limit = (state.limit + 1.0 / state.limit).min(self.max_limit as f64);
+        }
+        if !overloaded {
+            state.baseline =
+                Some(baseline.mul_f64(1.0 - BASELINE_WEIGHT) + latency.mul_f64(BASELINE_WEIGHT));
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<AdaptiveLimiterStats, ExtensionError> {
+        let state = self.state();
+        Ok(AdaptiveLimiterStats {
+            name: self.name.clone(),
+            limit: state.limit as usize,
+            in_flight: state.in_flight,
+            baseline_latency_ms: state
+                .baseline
+                .map(|baseline| baseline.as_secs_f64() * 1000.0),
+            rejected_total: self.rejected.load(Ordering::Relaxed),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[tokio::test]
     async fn test_retry_success() {
         let policy = RetryPolicy {
@@ -183,8 +1056,9 @@ mod tests {
             max_delay: Duration::from_secs(1),
             exponential_base: 2.0,
             jitter: false,
+            budget: None,
         };
-        
+
         let mut attempt_count = 0;
         let result = retry_with_policy(&policy, || {
             attempt_count += 1;
@@ -195,31 +1069,356 @@ mod tests {
                     Ok("success")
                 }
             }
-        }).await;
-        
+        })
+        .await;
+
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "success");
+
+        let mut attempt_count = 0;
+        let result: Result<(), _> = retry_with_policy(&policy, || {
+            attempt_count += 1;
+            async { Err(ExtensionError::configuration("missing port")) }
+        })
+        .await;
+        assert!(matches!(result, Err(ExtensionError::ConfigurationError(_))));
+        assert_eq!(attempt_count, 1);
+
+        let throttled = ExtensionError::throttled("queue full", Some(Duration::from_millis(500)));
+        assert_eq!(
+            policy.retry_delay(1, &throttled),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            policy.retry_delay(1, &ExtensionError::transport("reset")),
+            Duration::from_millis(10)
+        );
     }
-    
+
     #[tokio::test]
     async fn test_circuit_breaker() {
         let cb = CircuitBreaker::new(2, 2, Duration::from_millis(100));
-        
+
         assert_eq!(cb.get_state().await, CircuitState::Closed);
-        
-        let _ = cb.call(|| async { Err::<(), _>(ExtensionError::unknown("fail")) }).await;
-        let _ = cb.call(|| async { Err::<(), _>(ExtensionError::unknown("fail")) }).await;
-        
+
+        let _ = cb
+            .call(|| async { Err::<(), _>(ExtensionError::unknown("fail")) })
+            .await;
+        let _ = cb
+            .call(|| async { Err::<(), _>(ExtensionError::unknown("fail")) })
+            .await;
+
         assert_eq!(cb.get_state().await, CircuitState::Open);
-        
+
         let result = cb.call(|| async { Ok("should fail") }).await;
         assert!(result.is_err());
-        
+
         tokio::time::sleep(Duration::from_millis(150)).await;
-        
+
         let _ = cb.call(|| async { Ok("success") }).await;
         let _ = cb.call(|| async { Ok("success") }).await;
-        
+
         assert_eq!(cb.get_state().await, CircuitState::Closed);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_failure_rate_and_registry_health() {
+        let transitions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = transitions.clone();
+        let breaker = CircuitBreaker::new(u32::MAX, 1, Duration::from_secs(60))
+            .with_failure_rate(0.5, Duration::from_secs(60), 4)
+            .with_listener(move |from, to| recorded.lock().unwrap().push((from, to)));
+        for success in [false, true, false] {
+            let _ = breaker
+                .call(|| async move {
+                    if success {
+                        Ok(())
+                    } else {
+                        Err(ExtensionError::unknown("fail"))
+                    }
+                })
+                .await;
+        }
+        assert_eq!(breaker.get_state().await, CircuitState::Closed);
+        let _ = breaker
+            .call(|| async { Err::<(), _>(ExtensionError::unknown("fail")) })
+            .await;
+        assert_eq!(breaker.get_state().await, CircuitState::Open);
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![(CircuitState::Closed, CircuitState::Open)]
+        );
+
+        let health = HealthService::new();
+        let registry =
+            CircuitBreakerRegistry::new(|_| CircuitBreaker::new(1, 1, Duration::from_secs(60)))
+                .with_health(health.clone());
+        let _ = registry
+            .get("node-1")
+            .unwrap()
+            .call(|| async { Ok(()) })
+            .await;
+        let _ = registry
+            .get("node-2")
+            .unwrap()
+            .call(|| async { Err::<(), _>(ExtensionError::unknown("fail")) })
+            .await;
+        assert!(Arc::ptr_eq(
+            &registry.get("node-2").unwrap(),
+            &registry.get("node-2").unwrap()
+        ));
+        assert_eq!(registry.states().unwrap()["node-1"], CircuitState::Closed);
+        assert_eq!(registry.states().unwrap()["node-2"], CircuitState::Open);
+
+        let check = registry.check_health().await;
+        assert_eq!(check.status, HealthStatus::Degraded);
+        assert_eq!(check.details["node-2"], "open");
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while health
+                .get_check(CIRCUIT_BREAKERS_CHECK)
+                .await
+                .map(|check| check.status)
+                != Some(HealthStatus::Degraded)
+            {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget() {
+        let budget = Arc::new(RetryBudget::new(0.2, Duration::from_secs(60)).min_retries(1));
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(1),
+            jitter: false,
+            ..RetryPolicy::default()
+        }
+        .with_budget(budget.clone());
+        for _ in 0..9 {
+            budget.record_request().unwrap();
+        }
+
+        let mut attempts = 0;
+        let result: Result<(), _> = retry_with_policy(&policy, || {
+            attempts += 1;
+            async { Err(ExtensionError::transport("cluster overloaded")) }
+        })
+        .await;
+        assert!(matches!(result, Err(ExtensionError::Unknown(_))));
+        assert_eq!(attempts, 3);
+        assert_eq!(budget.usage().unwrap(), (10, 2));
+
+        let mut attempts = 0;
+        let result: Result<(), _> = retry_with_policy(&policy, || {
+            attempts += 1;
+            async { Err(ExtensionError::transport("cluster overloaded")) }
+        })
+        .await;
+        assert!(matches!(result, Err(ExtensionError::TransportError(_))));
+        assert_eq!(attempts, 1);
+        assert_eq!(budget.usage().unwrap(), (11, 2));
+    }
+
+    #[tokio::test]
+    async fn test_combinators_compose_with_breaker_and_retry() {
+        let breaker = CircuitBreaker::new(1, 1, Duration::from_secs(60));
+        let cache = ResultCache::new(Duration::from_secs(60));
+        let calls = std::sync::atomic::AtomicU32::new(0);
+        let fetch = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok("fresh")
+        };
+
+        let slow = with_fallback(
+            breaker.call(|| with_timeout(fetch(), Duration::from_millis(10))),
+            |e| async move {
+                assert!(matches!(e, ExtensionError::TimeoutError(_)));
+                Ok("fallback")
+            },
+        );
+        assert_eq!(slow.await.unwrap(), "fallback");
+        assert_eq!(breaker.get_state().await, CircuitState::Open);
+
+        cache.insert("greeting", "cached").unwrap();
+        assert_eq!(
+            with_cache(&cache, "greeting", fetch()).await.unwrap(),
+            "cached"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            initial_delay: Duration::from_millis(1),
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        let result =
+            retry_with_policy(&policy, || with_timeout(fetch(), Duration::from_millis(1))).await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        cache.invalidate(&"greeting").unwrap();
+        assert_eq!(cache.get(&"greeting").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::per_second(100.0, 2);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        let Err(ExtensionError::Throttled {
+            retry_after: Some(wait),
+            ..
+        }) = limiter.try_acquire()
+        else {
+            panic!("expected the limiter to be empty");
+        };
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(10));
+        assert!(!limiter.is_idle().unwrap());
+
+        let result = limiter
+            .call(|| async { Ok::<_, ExtensionError>("called") })
+            .await;
+        assert_eq!(result.unwrap(), "called");
+
+        let stopped = RateLimiter::per_second(0.0, 1);
+        assert!(stopped.try_acquire().is_ok());
+        assert!(matches!(
+            stopped.try_acquire(),
+            Err(ExtensionError::Throttled {
+                retry_after: Some(Duration::MAX),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_poisoned_locks_are_recovered() {
+        fn poison<T>(lock: &std::sync::Mutex<T>) {
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let _guard = lock.lock().unwrap();
+                panic!("poisoning");
+            }));
+        }
+
+        let limiter = RateLimiter::per_second(100.0, 2);
+        poison(&limiter.bucket);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.call(|| async { Ok(()) }).await.is_ok());
+
+        let cache = ResultCache::new(Duration::from_secs(60));
+        poison(&cache.entries);
+        assert_eq!(
+            with_cache(&cache, "greeting", async { Ok("fresh") })
+                .await
+                .unwrap(),
+            "fresh"
+        );
+
+        let adaptive = AdaptiveLimiter::new("search", 2);
+        let slot = adaptive.enter().unwrap();
+        poison(&adaptive.state);
+        drop(slot);
+        assert!(adaptive.call(|| async { Ok(()) }).await.is_ok());
+        assert_eq!(adaptive.stats().unwrap().in_flight, 0);
+
+        let registry = CircuitBreakerRegistry::default();
+        poison(&registry.breakers);
+        assert!(registry.get("node-1").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bulkhead() {
+        let bulkhead = Arc::new(Bulkhead::new("downstream", 1, 1).max_wait(Duration::from_secs(5)));
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn({
+            let bulkhead = bulkhead.clone();
+            async move {
+                bulkhead
+                    .call(|| async {
+                        released
+                            .await
+                            .map_err(|e| ExtensionError::unknown(e.to_string()))
+                    })
+                    .await
+            }
+        });
+        while bulkhead.stats().active == 0 {
+            tokio::task::yield_now().await;
+        }
+        let queued = tokio::spawn({
+            let bulkhead = bulkhead.clone();
+            async move { bulkhead.call(|| async { Ok("queued") }).await }
+        });
+        while bulkhead.stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let stats = bulkhead.stats();
+        assert_eq!((stats.active, stats.queued), (1, 1));
+        assert_eq!(stats.saturation(), 1.0);
+        assert!(bulkhead.call(|| async { Ok(()) }).await.is_err());
+
+        release.send(()).unwrap();
+        running.await.unwrap().unwrap();
+        assert_eq!(queued.await.unwrap().unwrap(), "queued");
+
+        let stats = bulkhead.stats();
+        assert_eq!((stats.active, stats.queued), (0, 0));
+        assert_eq!((stats.completed_total, stats.rejected_total), (2, 1));
+
+        let waiting = Bulkhead::new("slow", 1, 1).max_wait(Duration::from_millis(10));
+        let _held = waiting.permits.try_acquire().unwrap();
+        assert!(matches!(
+            waiting.call(|| async { Ok(()) }).await,
+            Err(ExtensionError::TimeoutError(_))
+        ));
+        assert_eq!(waiting.stats().queued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_limiter() {
+        let limiter = AdaptiveLimiter::new("search", 2).limits(1, 3).backoff(0.5);
+        let held = (limiter.enter().unwrap(), limiter.enter().unwrap());
+        let rejected = limiter.call(|| async { Ok(()) }).await;
+        assert!(matches!(rejected, Err(ExtensionError::Throttled { .. })));
+
+        for _ in 0..10 {
+            limiter.on_sample(Duration::from_millis(10), false).unwrap();
+        }
+        assert_eq!(limiter.limit().unwrap(), 3);
+        drop(held);
+        assert_eq!(limiter.call(|| async { Ok("ok") }).await.unwrap(), "ok");
+
+        limiter.on_sample(Duration::from_millis(50), false).unwrap();
+        assert_eq!(limiter.limit().unwrap(), 1);
+        let timed_out = limiter
+            .call(|| async { Err::<(), _>(ExtensionError::timeout("slow")) })
+            .await;
+        assert!(timed_out.is_err());
+        assert_eq!(limiter.limit().unwrap(), 1);
+
+        let stats = limiter.stats().unwrap();
+        assert_eq!((stats.in_flight, stats.rejected_total), (0, 1));
+        assert!((11.0..12.0).contains(&stats.baseline_latency_ms.unwrap()));
+    }
+
+    #[test]
+    fn test_adaptive_limiter_steady_noisy_latency() {
+        let limiter = AdaptiveLimiter::new("noisy", 8).limits(1, 16);
+        let _held: Vec<_> = (0..4).map(|_| limiter.enter().unwrap()).collect();
+        for i in 0..1000u64 {
+            limiter
+                .on_sample(Duration::from_millis(5 + i * 7919 % 11), false)
+                .unwrap();
+        }
+
+        let limit = limiter.limit().unwrap();
+        assert!(limit >= 8, "limit collapsed to {}", limit);
+        assert!((8.0..12.0).contains(&limiter.stats().unwrap().baseline_latency_ms.unwrap()));
+    }
+}
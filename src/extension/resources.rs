@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::extension::health::{HealthService, HealthStatus};
+use crate::extension::metadata::ExtensionMetrics;
+
+/// Health check the sampler reports its readings under.
+pub const RESOURCES_CHECK: &str = "resources";
+
+/// Clock ticks per second in `/proc/<pid>/stat`. Linux fixes this at 100
+/// for userspace whatever the kernel's internal tick rate.
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+/// Statistics of the tokio runtime the sampler runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RuntimeStats {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    pub global_queue_depth: usize,
+}
+
+impl RuntimeStats {
+    /// Stats of the current runtime, `None` outside of one.
+    pub fn current() -> Option<Self> {
+        let metrics = tokio::runtime::Handle::try_current().ok()?.metrics();
+        Some(RuntimeStats {
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+        })
+    }
+}
+
+/// One reading of the process's resource usage. Values the platform does
+/// not expose are `None`; everything but the runtime stats comes from
+/// `/proc` and is only available on Linux.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub memory_usage_bytes: Option<u64>,
+    pub cpu_usage_percent: Option<f32>,
+    pub open_fds: Option<u64>,
+    pub uptime_seconds: u64,
+    pub runtime: Option<RuntimeStats>,
+}
+
+/// Samples memory, CPU, open file descriptors and tokio runtime stats on an
+/// interval. CPU usage is the share of one core used since the previous
+/// sample, so it exceeds 100 on a busy multi-threaded runtime.
+pub struct ResourceSampler {
+    health: Option<HealthService>,
+    interval: Duration,
+    started: Instant,
+    cpu: Mutex<(Instant, Option<Duration>)>,
+    latest: Mutex<ResourceUsage>,
+}
+
+impl ResourceSampler {
+    pub fn new() -> Self {
+        ResourceSampler {
+            health: None,
+            interval: Duration::from_secs(10),
+            started: Instant::now(),
+            cpu: Mutex::new((Instant::now(), process_cpu_time())),
+            latest: Mutex::new(ResourceUsage::default()),
+        }
+    }
+
+    /// Publish each reading as the details of the `resources` check of
+    /// `health`.
+    pub fn with_health(mut self, health: HealthService) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The most recent reading, all `None` before the first sample.
+    pub fn latest(&self) -> ResourceUsage {
+        self.latest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Copy the latest reading into `metrics`.
+    pub fn apply(&self, metrics: &mut ExtensionMetrics) {
+        let usage = self.latest();
+        metrics.memory_usage_bytes = usage.memory_usage_bytes;
+        metrics.cpu_usage_percent = usage.cpu_usage_percent;
+        metrics.uptime_seconds = usage.uptime_seconds;
+    }
+
+    /// Take a reading now.
+    pub async fn sample(&self) -> ResourceUsage {
+        let usage = ResourceUsage {
+            memory_usage_bytes: resident_memory_bytes(),
+            cpu_usage_percent: self.cpu_usage_percent(),
+            open_fds: open_fds(),
+            uptime_seconds: self.started.elapsed().as_secs(),
+            runtime: RuntimeStats::current(),
+        };
+        *self.latest.lock().unwrap_or_else(PoisonError::into_inner) = usage.clone();
+
+        if let Some(health) = &self.health {
+            if health.get_check(RESOURCES_CHECK).await.is_none() {
+                health.register_check(RESOURCES_CHECK).await;
+            }
+            let _ = health
+                .update_check(RESOURCES_CHECK, HealthStatus::Healthy, None)
+                .await;
+            if let Ok(serde_json::Value::Object(details)) = serde_json::to_value(&usage) {
+                for (key, value) in details {
+                    let _ = health.add_detail(RESOURCES_CHECK, key, value).await;
+                }
+            }
+        }
+        usage
+    }
+
+    fn cpu_usage_percent(&self) -> Option<f32> {
+        let now = Instant::now();
+        let cpu_time = process_cpu_time()?;
+        let mut previous = self.cpu.lock().unwrap_or_else(PoisonError::into_inner);
+        let (at, last) = std::mem::replace(&mut *previous, (now, Some(cpu_time)));
+        let wall = now.duration_since(at).as_secs_f64();
+        if wall <= 0.0 {
+            return None;
+        }
+        let used = cpu_time.saturating_sub(last?).as_secs_f64();
+        Some((used / wall * 100.0) as f32)
+    }
+
+    /// Sample every `interval` until the returned task is aborted.
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                let usage = self.sample().await;
+                debug!("Sampled resource usage: {:?}", usage);
+            }
+        })
+    }
+}
+
+impl Default for ResourceSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// User plus system CPU time of the process.
+fn process_cpu_time() -> Option<Duration> {
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, so fields are counted from the
+    // closing parenthesis: utime and stime are the 14th and 15th fields.
+    let fields: Vec<&str> = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(Duration::from_secs_f64(
+        (utime + stime) as f64 / CLOCK_TICKS_PER_SEC,
+    ))
+}
+
+fn open_fds() -> Option<u64> {
+    Some(std::fs::read_dir("/proc/self/fd").ok()?.count() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sample_updates_health_and_metrics() {
+        let health = HealthService::new();
+        let sampler = ResourceSampler::new().with_health(health.clone());
+        assert_eq!(sampler.latest(), ResourceUsage::default());
+
+        let usage = sampler.sample().await;
+        assert!(usage.runtime.unwrap().workers >= 1);
+        if cfg!(target_os = "linux") {
+            assert!(usage.memory_usage_bytes.unwrap() > 0);
+            assert!(usage.open_fds.unwrap() > 0);
+            assert!(usage.cpu_usage_percent.unwrap() >= 0.0);
+        }
+
+        let check = health.get_check(RESOURCES_CHECK).await.unwrap();
+        assert_eq!(check.status, HealthStatus::Healthy);
+        assert!(check.details.contains_key("memory_usage_bytes"));
+        assert!(check.details.contains_key("runtime"));
+
+        let mut metrics = ExtensionMetrics::new();
+        sampler.apply(&mut metrics);
+        assert_eq!(metrics.memory_usage_bytes, usage.memory_usage_bytes);
+    }
+}
@@ -1,19 +1,69 @@
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;
 use tokio::signal;
-use tokio::sync::RwLock;
-use tracing::{info, error, warn};
+use tokio::sync::{watch, RwLock};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{error, info, warn, Instrument};
 
+use crate::client::{ClientPolicies, SdkClient, POLICY_SETTINGS_PREFIX};
 use crate::extension::{
-    Extension, ExtensionContext, ExtensionError,
-    lifecycle::{LifecycleManager, ExtensionState, LoggingStateListener},
+    config_watcher::{settings_reload_interval, ConfigWatcher},
+    dependency::ExtensionDependencyResponse,
+    diagnostics::DiagnosticsStateListener,
+    dispatcher::RequestDispatcher,
+    events::{EventStateListener, SdkEvent},
+    features::FeaturesSection,
+    health::HealthService,
+    lifecycle::{ExtensionState, LifecycleManager, LoggingStateListener, TransitionHook},
+    logging::{connection_span, request_span},
+    metrics_reporter::MetricsReporter,
+    reconnect::ReconnectWatchdog,
+    setting::Setting,
+    settings_update::{settings_poll_interval, ClusterSettingsPoller},
+    settings_validation::SettingsValidator,
+    slow_log::{RequestTimer, SlowLog},
+    startup::StartupWait,
+    supervisor::HealthSupervisor,
+    units::TimeValue,
+    Extension, ExtensionContext, ExtensionError, ExtensionInit, ResourceSampler,
+    ServiceAccountToken,
 };
+use crate::metrics::{metrics_port, LifecycleMetrics, MetricsRegistry, MetricsServer};
+use crate::rest::{ErrorMapper, RestMiddleware, RestRouter};
+use crate::transport::action::TransportActionRegistry;
+use crate::transport::connections::{ConnectionGuard, ConnectionRegistry};
+use crate::transport::inbound::ProtocolMode;
+use crate::transport::spill::ResponseSpooler;
+use crate::transport::MessageSigner;
+
+/// How long shutdown may take before remaining work is aborted.
+pub fn shutdown_timeout() -> Setting<TimeValue> {
+    Setting::time("extension.shutdown_timeout").default(TimeValue::seconds(30))
+}
 
 pub struct ExtensionRunner {
     extension: Arc<RwLock<Box<dyn Extension>>>,
     context: Arc<ExtensionContext>,
     lifecycle: Arc<LifecycleManager>,
+    dispatcher: Arc<RequestDispatcher>,
+    rest_middleware: Vec<Arc<dyn RestMiddleware>>,
+    error_mappers: Vec<Arc<dyn ErrorMapper>>,
+    protocol_mode: ProtocolMode,
+    connections: Arc<ConnectionRegistry>,
+    connection_tasks: Mutex<JoinSet<()>>,
+    spooler: Option<Arc<ResponseSpooler>>,
+    client_policies: Arc<ClientPolicies>,
+    settings_poller: Option<JoinHandle<()>>,
+    config_watcher: Option<Arc<ConfigWatcher>>,
+    config_reloader: Option<JoinHandle<()>>,
+    metrics: Option<Arc<MetricsRegistry>>,
+    metrics_server: Option<JoinHandle<()>>,
+    resource_sampler: Option<JoinHandle<()>>,
+    metrics_reporter: Option<JoinHandle<()>>,
+    diagnostics_follower: Option<JoinHandle<()>>,
+    health: Option<HealthService>,
+    slow_log: SlowLog,
     port: u16,
 }
 
@@ -23,76 +73,301 @@ impl ExtensionRunner {
         context: ExtensionContext,
         port: u16,
     ) -> Result<Self, ExtensionError> {
-        let lifecycle = Arc::new(LifecycleManager::new());
-        
+        let lifecycle = context.lifecycle.clone();
+
         Ok(ExtensionRunner {
             extension: Arc::new(RwLock::new(extension)),
             context: Arc::new(context),
             lifecycle,
+            dispatcher: Arc::new(RequestDispatcher::default()),
+            rest_middleware: Vec::new(),
+            error_mappers: Vec::new(),
+            protocol_mode: ProtocolMode::Lenient,
+            connections: Arc::new(ConnectionRegistry::new()),
+            connection_tasks: Mutex::new(JoinSet::new()),
+            spooler: None,
+            client_policies: Arc::new(ClientPolicies::default()),
+            settings_poller: None,
+            config_watcher: None,
+            config_reloader: None,
+            metrics: None,
+            metrics_server: None,
+            resource_sampler: None,
+            metrics_reporter: None,
+            diagnostics_follower: None,
+            health: None,
+            slow_log: SlowLog::new(),
             port,
         })
     }
-    
+
+    /// Middleware applied to every REST route, outermost first.
+    pub fn with_rest_middleware(mut self, middleware: Vec<Arc<dyn RestMiddleware>>) -> Self {
+        self.rest_middleware = middleware;
+        self
+    }
+
+    /// Mappers turning REST handler errors into responses, tried in order.
+    pub fn with_error_mappers(mut self, mappers: Vec<Arc<dyn ErrorMapper>>) -> Self {
+        self.error_mappers = mappers;
+        self
+    }
+
+    /// How strictly inbound transport messages are validated.
+    pub fn with_protocol_mode(mut self, mode: ProtocolMode) -> Self {
+        self.protocol_mode = mode;
+        self
+    }
+
+    /// Track OpenSearch connections in `registry`, e.g. one shared with a
+    /// `ConnectionsHandler`.
+    pub fn with_connection_registry(mut self, registry: Arc<ConnectionRegistry>) -> Self {
+        self.connections = registry;
+        self
+    }
+
+    /// Spool large transport responses to disk through `spooler`.
+    pub fn with_response_spooler(mut self, spooler: Arc<ResponseSpooler>) -> Self {
+        self.spooler = Some(spooler);
+        self
+    }
+
+    /// Record transport requests, connections and lifecycle state in
+    /// `registry`, served on the `metrics_port` setting when it is set.
+    pub fn with_metrics(mut self, registry: Arc<MetricsRegistry>) -> Self {
+        self.metrics = Some(registry);
+        self
+    }
+
+    /// Log requests slower than `slow_log`'s thresholds. The
+    /// `slow_log_threshold` setting supplies the default threshold when
+    /// `slow_log` has none.
+    pub fn with_slow_log(mut self, slow_log: SlowLog) -> Self {
+        self.slow_log = slow_log;
+        self
+    }
+
+    /// Reload settings files through `watcher` while running, telling the
+    /// extension through `Extension::on_settings_change`.
+    pub fn with_config_watcher(mut self, watcher: Arc<ConfigWatcher>) -> Self {
+        self.config_watcher = Some(watcher);
+        self
+    }
+
+    /// Supervise the overall status of `health` as configured by the
+    /// `supervisor_*` settings, restarting or exiting when it stays unhealthy.
+    pub fn with_health(mut self, health: HealthService) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    pub fn config_watcher(&self) -> Option<&Arc<ConfigWatcher>> {
+        self.config_watcher.as_ref()
+    }
+
+    pub fn connections(&self) -> &Arc<ConnectionRegistry> {
+        &self.connections
+    }
+
+    /// A handle to watch and control the extension from elsewhere while
+    /// `run` is in progress, e.g. to shut it down.
+    pub fn handle(&self) -> ExtensionHandle {
+        ExtensionHandle::new(self.lifecycle.clone())
+    }
+
+    /// Run the extension until it is shut down. Fails with
+    /// `ExtensionError::Unhealthy` if the health supervisor gave up on it.
     pub async fn run(&mut self) -> Result<(), ExtensionError> {
-        self.lifecycle.add_listener(Box::new(LoggingStateListener)).await;
-        
-        self.lifecycle.transition_to(ExtensionState::Initializing).await?;
-        
-        {
-            let mut ext = self.extension.write().await;
-            ext.initialize(&self.context).await?;
+        self.lifecycle
+            .add_listener(Box::new(LoggingStateListener))
+            .await;
+        self.lifecycle
+            .add_hook(Arc::new(ExtensionHooks {
+                extension: self.extension.clone(),
+                context: self.context.clone(),
+            }))
+            .await;
+        self.lifecycle
+            .add_listener(Box::new(EventStateListener(self.context.events.clone())))
+            .await;
+        self.lifecycle
+            .add_listener(Box::new(DiagnosticsStateListener(
+                self.context.diagnostics.clone(),
+            )))
+            .await;
+        self.context
+            .diagnostics
+            .attach_connections(self.connections.clone());
+        self.diagnostics_follower = Some(
+            self.context
+                .diagnostics
+                .clone()
+                .follow(&self.context.events),
+        );
+        if let Some(metrics) = &self.metrics {
+            self.lifecycle
+                .add_listener(Box::new(LifecycleMetrics::new(metrics.clone())))
+                .await;
+            metrics.register_collector(self.connections.clone());
+            metrics.register_collector(self.context.diagnostics.clone());
         }
-        
-        self.lifecycle.transition_to(ExtensionState::Initialized).await?;
-        
-        self.register_with_opensearch().await?;
-        
-        self.lifecycle.transition_to(ExtensionState::Running).await?;
-        
-        let listener = TcpListener::bind(format!("0.0.0.0:{}", self.port))
-            .await
-            .map_err(|e| ExtensionError::initialization(
-                format!("Failed to bind to port {}: {}", self.port, e)
-            ))?;
-        
+
+        self.lifecycle
+            .transition_to(ExtensionState::Initializing)
+            .await?;
+
+        self.load_environment_settings().await;
+        if let Err(e) = self.validate_settings().await {
+            return self.fail(e).await;
+        }
+        if let Err(e) = self.load_client_policies() {
+            return self.fail(e).await;
+        }
+        if let Err(e) = self.wait_for_startup().await {
+            return self.fail(e).await;
+        }
+
+        let initialized = self.extension.write().await.initialize(&self.context).await;
+        if let Err(e) = initialized {
+            return self.fail(e).await;
+        }
+
+        self.lifecycle
+            .transition_to(ExtensionState::Initialized)
+            .await?;
+
+        let listener = match self.start().await {
+            Ok(listener) => listener,
+            Err(e) => {
+                self.lifecycle.fail(&e).await?;
+                // Release whatever `start` got running before it failed.
+                if let Err(cleanup) = self.shutdown().await {
+                    warn!("Failed to clean up after a failed start: {}", cleanup);
+                }
+                return Err(e);
+            }
+        };
+
         info!("Extension listening on port {}", self.port);
-        
+
         let shutdown_signal = Self::create_shutdown_signal();
         let server_loop = self.run_server(listener);
-        
+        let watchdog = self.watch_connection();
+        let supervisor = self.supervise_health();
+        let mut states = self.lifecycle.subscribe();
+        let stop_requested = states.wait_for(|state| *state == ExtensionState::Stopping);
+        let mut gave_up = None;
+
         tokio::select! {
+            _ = watchdog => {}
+            reason = supervisor => {
+                error!("Giving up on the extension: {}", reason);
+                gave_up = Some(reason);
+            }
             result = server_loop => {
                 if let Err(e) = result {
                     error!("Server error: {}", e);
-                    self.lifecycle.transition_to(ExtensionState::Failed).await?;
+                    self.lifecycle.fail(&e).await?;
                 }
             }
             _ = shutdown_signal => {
                 info!("Shutdown signal received");
             }
+            _ = stop_requested => {
+                info!("Shutdown requested");
+            }
+        }
+
+        self.shutdown().await?;
+        match gave_up {
+            Some(reason) => Err(reason),
+            None => Ok(()),
         }
-        
-        self.shutdown().await
     }
-    
+
+    /// Register with OpenSearch, start the background tasks, move to
+    /// `Running` and bind the transport port.
+    async fn start(&mut self) -> Result<TcpListener, ExtensionError> {
+        self.dispatcher = Arc::new(self.build_dispatcher().await?);
+        if let Some(metrics) = &self.metrics {
+            metrics.register_collector(self.dispatcher.transport_actions().metrics().clone());
+        }
+
+        self.register_with_opensearch().await?;
+
+        self.register_custom_settings().await;
+
+        self.register_transport_actions().await;
+        self.register_rest_actions().await;
+        self.validate_dependencies().await?;
+        self.start_settings_poller().await?;
+        self.start_config_watcher()?;
+        self.start_metrics_server().await?;
+
+        self.lifecycle
+            .transition_to(ExtensionState::Running)
+            .await?;
+
+        TcpListener::bind(format!("0.0.0.0:{}", self.port))
+            .await
+            .map_err(|e| {
+                ExtensionError::initialization(format!(
+                    "Failed to bind to port {}: {}",
+                    self.port, e
+                ))
+            })
+    }
+
+    /// Move to `Failed` because of `error` and return it.
+    async fn fail(&self, error: ExtensionError) -> Result<(), ExtensionError> {
+        self.lifecycle.fail(&error).await?;
+        Err(error)
+    }
+
+    fn connection_tasks(&self) -> std::sync::MutexGuard<'_, JoinSet<()>> {
+        self.connection_tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
     async fn run_server(&self, listener: TcpListener) -> Result<(), ExtensionError> {
+        let extension_id = self.extension.read().await.unique_id().to_string();
         loop {
-            if !self.lifecycle.is_running().await {
+            if !self.lifecycle.current_state().await.is_active() {
                 break;
             }
-            
+
             match listener.accept().await {
                 Ok((stream, addr)) => {
                     info!("New connection from {}", addr);
-                    
-                    let extension = self.extension.clone();
+
                     let context = self.context.clone();
-                    
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, extension, context).await {
-                            error!("Error handling connection: {}", e);
+                    let dispatcher = self.dispatcher.clone();
+                    let mode = self.protocol_mode;
+                    let connection = self.connections.open(addr);
+                    let spooler = self.spooler.clone();
+                    let metrics = self.metrics.clone();
+
+                    let span = connection_span(&extension_id, addr);
+                    let mut tasks = self.connection_tasks();
+                    while tasks.try_join_next().is_some() {}
+                    tasks.spawn(
+                        async move {
+                            let events = context.events.clone();
+                            if let Err(e) = Self::handle_connection(
+                                stream, context, dispatcher, mode, connection, spooler, metrics,
+                            )
+                            .await
+                            {
+                                error!("Error handling connection: {}", e);
+                                events.publish(SdkEvent::ConnectionError {
+                                    peer: addr.to_string(),
+                                    error: e.to_string(),
+                                });
+                            }
                         }
-                    });
+                        .instrument(span),
+                    );
                 }
                 Err(e) => {
                     error!("Failed to accept connection: {}", e);
@@ -100,104 +375,727 @@ impl ExtensionRunner {
                 }
             }
         }
-        
+
         Ok(())
     }
-    
+
     async fn handle_connection(
-        mut stream: tokio::net::TcpStream,
-        _extension: Arc<RwLock<Box<dyn Extension>>>,
-        _context: Arc<ExtensionContext>,
+        stream: tokio::net::TcpStream,
+        context: Arc<ExtensionContext>,
+        dispatcher: Arc<RequestDispatcher>,
+        mode: ProtocolMode,
+        connection: ConnectionGuard,
+        spooler: Option<Arc<ResponseSpooler>>,
+        metrics: Option<Arc<MetricsRegistry>>,
     ) -> Result<(), ExtensionError> {
-        use tokio::io::{AsyncReadExt, AsyncWriteExt};
-        
-        let mut buffer = vec![0u8; 1024];
-        let n = stream.read(&mut buffer).await
-            .map_err(|e| ExtensionError::transport(format!("Failed to read from stream: {}", e)))?;
-        
-        if n == 0 {
-            return Ok(());
+        use crate::extension::features::STREAMING_RESPONSES;
+        use crate::rest::error_mapper::catch_panic;
+        use crate::transport::inbound::{max_message_size, read_message_with, write_response_body};
+        use crate::transport::spill::ResponseBody;
+        use crate::transport::{Deadline, ExceptionResponse, ThreadContext};
+
+        let opened = Instant::now();
+        let counters = connection.counters();
+        let max_size = max_message_size().get(&context.settings)?.as_bytes() as usize;
+        let (mut reader, mut writer) = stream.into_split();
+
+        while let Some(message) = read_message_with(&mut reader, mode, max_size)
+            .await
+            .inspect_err(|_| counters.record_error())?
+        {
+            counters.record_inbound(message.wire_size());
+            if !message.is_request() {
+                warn!(
+                    "Ignoring unexpected response for request {}",
+                    message.header.request_id
+                );
+                continue;
+            }
+
+            let _in_flight = counters.begin_request();
+            let started = Instant::now();
+            let action = message.action.as_deref().unwrap_or_default();
+            let span = request_span(message.header.request_id, action);
+            let timer = RequestTimer::start();
+            let dispatch = timer
+                .run(dispatcher.dispatch(&message))
+                .instrument(span.clone());
+            let dispatched = match Deadline::from_thread_context(&message.thread_context) {
+                Some(deadline) => catch_panic(deadline.run(dispatch)).await,
+                None => catch_panic(dispatch).await,
+            };
+            // Handlers catch their own panics; this keeps the connection
+            // alive should anything else in dispatch panic.
+            let result = dispatched.unwrap_or_else(|panic| {
+                span.in_scope(|| error!("Dispatch panicked: {}", panic));
+                context.diagnostics.record_panic(action, &panic);
+                Err(ExtensionError::unknown(format!(
+                    "request [{}] failed unexpectedly",
+                    action
+                )))
+            });
+            let (content, is_error) = match result {
+                Ok(content) => (content, false),
+                Err(e) => {
+                    span.in_scope(|| error!("Failed to handle request: {}", e));
+                    context
+                        .diagnostics
+                        .record_error("transport", format!("{}: {}", action, e));
+                    counters.record_error();
+                    (ExceptionResponse::from_error(&e).to_bytes()?, true)
+                }
+            };
+
+            if let Some(metrics) = &metrics {
+                let labels = [("action", action)];
+                metrics
+                    .counter(
+                        "extension_transport_requests_total",
+                        "Transport requests handled",
+                        &labels,
+                    )
+                    .inc();
+                if is_error {
+                    metrics
+                        .counter(
+                            "extension_transport_request_errors_total",
+                            "Transport requests that failed",
+                            &labels,
+                        )
+                        .inc();
+                }
+                metrics
+                    .histogram(
+                        "extension_transport_request_duration_seconds",
+                        "Time to handle a transport request",
+                        &labels,
+                    )
+                    .observe(started.elapsed().as_secs_f64());
+            }
+
+            let mut thread_context = ThreadContext::new();
+            if let Some(signer) = dispatcher.signer() {
+                signer.sign_response(
+                    &mut thread_context,
+                    message.header.request_id,
+                    action,
+                    &content,
+                );
+            }
+            let body = match &spooler {
+                Some(spooler) if context.features.is_enabled(STREAMING_RESPONSES) => {
+                    spooler.spool(content).await
+                }
+                _ => ResponseBody::Memory(content),
+            };
+            let written = write_response_body(
+                &mut writer,
+                &message.header,
+                &thread_context,
+                body,
+                is_error,
+            )
+            .await
+            .inspect_err(|_| counters.record_error())?;
+            counters.record_outbound(written);
+            span.in_scope(|| {
+                dispatcher
+                    .slow_log()
+                    .check(action, message.header.request_id, &timer)
+            });
+            if message.is_handshake() {
+                counters.record_handshake(opened.elapsed());
+            }
         }
-        
-        let response = b"Hello from extension";
-        stream.write_all(response).await
-            .map_err(|e| ExtensionError::transport(format!("Failed to write response: {}", e)))?;
-        
+
         Ok(())
     }
-    
+
+    async fn build_dispatcher(&self) -> Result<RequestDispatcher, ExtensionError> {
+        let ext = self.extension.read().await;
+
+        let mut transport_actions =
+            TransportActionRegistry::new().with_diagnostics(self.context.diagnostics.clone());
+        for action in ext.transport_actions() {
+            transport_actions.register(action)?;
+        }
+
+        let version = semver::Version::parse(ext.version()).map_err(|e| {
+            ExtensionError::initialization(format!(
+                "Invalid extension version '{}': {}",
+                ext.version(),
+                e
+            ))
+        })?;
+        let dependency_info =
+            ExtensionDependencyResponse::new(ext.unique_id(), version, ext.dependencies());
+
+        let rest_router = self
+            .rest_middleware
+            .iter()
+            .cloned()
+            .fold(RestRouter::new(), RestRouter::with_middleware);
+        let mut rest_router = self
+            .error_mappers
+            .iter()
+            .cloned()
+            .fold(rest_router, RestRouter::with_error_mapper);
+        for handler in ext.rest_handlers() {
+            rest_router.register(handler)?;
+        }
+
+        let interfaces = if transport_actions.is_empty() {
+            Vec::new()
+        } else {
+            vec!["ActionExtension".to_string()]
+        };
+        let mut init = ExtensionInit::new(ext.name(), interfaces);
+        init.register(Arc::new(FeaturesSection(self.context.features.clone())))?;
+        for provider in ext.init_state() {
+            init.register(provider)?;
+        }
+
+        let dispatcher = RequestDispatcher::new(transport_actions)
+            .with_features(self.context.features.clone())
+            .with_dependency_info(dependency_info)
+            .with_init(init)
+            .with_rest_router(rest_router, self.context.clone())
+            .with_slow_log(
+                self.slow_log
+                    .clone()
+                    .with_settings(&self.context.settings)?,
+            );
+        Ok(
+            match MessageSigner::from_settings(&self.context.settings)? {
+                Some(signer) => {
+                    info!("Signing transport messages with the shared secret");
+                    dispatcher.with_signer(Arc::new(signer))
+                }
+                None => dispatcher,
+            },
+        )
+    }
+
+    async fn load_environment_settings(&self) {
+        use crate::extension::environment::request_environment_settings;
+
+        let unique_id = self.extension.read().await.unique_id().to_string();
+
+        match request_environment_settings(&self.context.transport_client, &unique_id).await {
+            Ok(response) => match response.apply_to(&self.context.settings) {
+                Ok(applied) => info!("Applied {} environment settings from OpenSearch", applied),
+                Err(e) => warn!("Failed to apply environment settings: {}", e),
+            },
+            Err(e) => {
+                warn!(
+                    "Failed to request environment settings from OpenSearch: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Check the declared settings again now that the node's environment
+    /// settings are loaded.
+    async fn validate_settings(&self) -> Result<(), ExtensionError> {
+        let ext = self.extension.read().await;
+        SettingsValidator::new(ext.setting_registry(), ext.custom_settings())
+            .validate(&self.context.settings)?
+            .into_result()
+    }
+
+    /// Register again after losing the cluster whenever heartbeats to it
+    /// fail, unless `reconnect_enabled` is off. Never returns; the server
+    /// loop decides when to stop.
+    async fn watch_connection(&self) {
+        let lifecycle = self.lifecycle.clone();
+        let client = self.context.transport_client.clone();
+        match ReconnectWatchdog::from_settings(lifecycle, client, &self.context.settings) {
+            Ok(Some(watchdog)) => watchdog.run(|| self.reregister()).await,
+            Ok(None) => {}
+            Err(e) => warn!("Not watching the connection to OpenSearch: {}", e),
+        }
+        std::future::pending().await
+    }
+
+    /// Apply the health supervisor's policy, if a `HealthService` is set and
+    /// `supervisor_action` is not `none`. Returns only to give up on the
+    /// extension, with the reason.
+    async fn supervise_health(&self) -> ExtensionError {
+        if let Some(health) = self.health.clone() {
+            match HealthSupervisor::from_settings(
+                health,
+                self.lifecycle.clone(),
+                &self.context.settings,
+            ) {
+                Ok(Some(supervisor)) => {
+                    if let Err(reason) = supervisor.run(|| self.restart_extension()).await {
+                        return reason;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Not supervising extension health: {}", e),
+            }
+        }
+        std::future::pending().await
+    }
+
+    /// Shut the extension down and initialize it again, paused meanwhile so
+    /// that requests are turned away rather than reaching it half-built.
+    /// If it cannot be brought back, it is left `Failed`.
+    async fn restart_extension(&self) -> Result<(), ExtensionError> {
+        self.lifecycle.pause().await?;
+        let restarted = async {
+            let mut ext = self.extension.write().await;
+            ext.shutdown().await?;
+            ext.initialize(&self.context).await
+        }
+        .await;
+        let resumed = match restarted {
+            Ok(()) => self.lifecycle.resume().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = resumed {
+            self.lifecycle.fail(&e).await?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Repeat the registration flow, failing if the cluster rejects it.
+    async fn reregister(&self) -> Result<(), ExtensionError> {
+        self.try_register().await?;
+        self.register_custom_settings().await;
+        self.register_transport_actions().await;
+        self.register_rest_actions().await;
+        Ok(())
+    }
+
+    /// Register, tolerating failure: the extension can still serve the
+    /// cluster if OpenSearch initiates the handshake itself.
     async fn register_with_opensearch(&self) -> Result<(), ExtensionError> {
-        use crate::extension::registration::{ExtensionIdentity, ExtensionRegistration, RegistrationProtocol};
-        
+        if let Err(e) = self.try_register().await {
+            warn!("Failed to register with OpenSearch: {}", e);
+        }
+        Ok(())
+    }
+
+    async fn try_register(&self) -> Result<(), ExtensionError> {
+        use crate::extension::registration::{
+            ExtensionCapabilities, ExtensionIdentity, ExtensionRegistration, RegistrationProtocol,
+        };
+
         let ext = self.extension.read().await;
-        
+
         info!(
             "Registering extension '{}' (ID: {}, version: {}) with OpenSearch",
             ext.name(),
             ext.unique_id(),
             ext.version()
         );
-        
+
         let identity = ExtensionIdentity::from_extension(&**ext);
-        
+
         // Get the actual bind address from settings or use default
-        let bind_address = self.context.settings
+        let bind_address = self
+            .context
+            .settings
             .get("bind_address")
             .ok()
             .flatten()
             .and_then(|v| match v {
                 crate::extension::context::SettingValue::String(s) => Some(s),
-                _ => None
+                _ => None,
             })
             .unwrap_or_else(|| "0.0.0.0".to_string());
-        
-        let registration = ExtensionRegistration::new(
-            identity,
-            bind_address,
-            self.port,
-        );
-        
+
+        let capabilities = ExtensionCapabilities {
+            supports_rest_actions: !self.dispatcher.rest_router().is_empty(),
+            supports_settings_extension: !ext.custom_settings().is_empty()
+                || !ext.setting_registry().is_empty(),
+            supports_action_extension: !self.dispatcher.transport_actions().is_empty(),
+            ..Default::default()
+        };
+
+        let registration = ExtensionRegistration::new(identity, bind_address, self.port)
+            .with_capabilities(capabilities);
+
         let protocol = RegistrationProtocol::new(registration);
-        
-        match protocol.register_with_opensearch("localhost").await {
-            Ok(response) => {
-                if response.success {
-                    info!("Successfully registered with OpenSearch cluster: {:?}", response.cluster_name);
-                } else {
-                    warn!("Registration failed: {:?}", response.message);
+
+        drop(ext);
+        match protocol.register_with(&self.context.transport_client).await {
+            Ok(response) if response.success => {
+                info!(
+                    "Successfully registered with OpenSearch cluster: {:?}",
+                    response.cluster_name
+                );
+                if let Some(token) = response.service_account_token {
+                    self.context
+                        .service_account
+                        .set_token(ServiceAccountToken::new(token));
                 }
+                self.context.events.publish(SdkEvent::Registered {
+                    cluster_name: response.cluster_name,
+                });
+                Ok(())
+            }
+            Ok(response) => {
+                let error = response
+                    .message
+                    .unwrap_or_else(|| "registration rejected".to_string());
+                self.context.events.publish(SdkEvent::RegistrationFailed {
+                    error: error.clone(),
+                });
+                Err(ExtensionError::registration(error))
             }
             Err(e) => {
-                warn!("Failed to register with OpenSearch: {}", e);
+                self.context.events.publish(SdkEvent::RegistrationFailed {
+                    error: e.to_string(),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    async fn register_rest_actions(&self) {
+        use crate::rest::handler::register_rest_actions;
+
+        let routes = self.dispatcher.rest_router().routes();
+        if routes.is_empty() {
+            return;
+        }
+
+        let unique_id = self.extension.read().await.unique_id().to_string();
+
+        match register_rest_actions(&self.context.transport_client, &unique_id, &routes).await {
+            Ok(()) => info!("Registered {} REST actions with OpenSearch", routes.len()),
+            Err(e) => warn!("Failed to register REST actions with OpenSearch: {}", e),
+        }
+    }
+
+    /// Wait for OpenSearch and the declared dependencies to answer, if the
+    /// `wait_for_dependencies` setting is on.
+    async fn wait_for_startup(&self) -> Result<(), ExtensionError> {
+        let Some(startup) = StartupWait::from_settings(
+            self.context.transport_client.clone(),
+            &self.context.settings,
+        )?
+        else {
+            return Ok(());
+        };
+        let dependencies = self.extension.read().await.dependencies();
+        startup
+            .dependencies(
+                dependencies
+                    .into_iter()
+                    .map(|dependency| dependency.unique_id)
+                    .collect(),
+            )
+            .wait()
+            .await
+    }
+
+    async fn validate_dependencies(&self) -> Result<(), ExtensionError> {
+        use crate::extension::dependency::{request_dependency_info, DependencyResolver};
+
+        let ext = self.extension.read().await;
+        let dependencies = ext.dependencies();
+        if dependencies.is_empty() {
+            return Ok(());
+        }
+
+        let mut resolver = DependencyResolver::new();
+        for dependency in &dependencies {
+            match request_dependency_info(&self.context.transport_client, &dependency.unique_id)
+                .await
+            {
+                Ok(info) => resolver.add_dependency_info(&info),
+                Err(e) => {
+                    warn!(
+                        "Skipping dependency validation, failed to fetch '{}': {}",
+                        dependency.unique_id, e
+                    );
+                    return Ok(());
+                }
             }
         }
-        
+
+        let version = semver::Version::parse(ext.version())
+            .map_err(|e| ExtensionError::dependency(e.to_string()))?;
+        resolver.add_extension(ext.unique_id(), version, dependencies);
+
+        let order = resolver.resolve().map_err(ExtensionError::dependency)?;
+        info!(
+            "Validated extension dependencies against the cluster: {}",
+            order.join(" -> ")
+        );
         Ok(())
     }
-    
+
+    /// Load the client policies from the `client.policy.*` settings.
+    fn load_client_policies(&mut self) -> Result<(), ExtensionError> {
+        let policies = ClientPolicies::from_settings(&self.context.settings)?;
+        self.client_policies = Arc::new(match &self.health {
+            Some(health) => policies.with_health(health.clone()),
+            None => policies,
+        });
+        Ok(())
+    }
+
+    /// Poll the cluster settings for updates to consumed settings and to
+    /// the client policies, which are reloaded when they change.
+    async fn start_settings_poller(&mut self) -> Result<(), ExtensionError> {
+        let interval = settings_poll_interval().get(&self.context.settings)?;
+        let (policies, settings) = (self.client_policies.clone(), self.context.settings.clone());
+        let poller =
+            ClusterSettingsPoller::new(self.sdk_client().await, self.context.settings.clone())
+                .watch_prefix(POLICY_SETTINGS_PREFIX)
+                .on_change(move |changed| {
+                    if !changed
+                        .iter()
+                        .any(|key| key.starts_with(POLICY_SETTINGS_PREFIX))
+                    {
+                        return;
+                    }
+                    match policies.reload(&settings) {
+                        Ok(true) => info!("Reloaded client policies"),
+                        Ok(false) => {}
+                        Err(e) => warn!("Keeping the current client policies: {}", e),
+                    }
+                });
+        let poller = Arc::new(poller);
+        self.settings_poller = Some(poller.start(interval.as_duration()));
+        info!("Polling cluster settings every {}", interval);
+        Ok(())
+    }
+
+    /// Watch the settings files, if any, and pass reloaded settings on to
+    /// the extension.
+    fn start_config_watcher(&mut self) -> Result<(), ExtensionError> {
+        let Some(watcher) = self.config_watcher.clone() else {
+            return Ok(());
+        };
+        let interval = settings_reload_interval().get(&self.context.settings)?;
+        let (extension, settings) = (self.extension.clone(), self.context.settings.clone());
+        self.config_reloader = Some(tokio::spawn(async move {
+            watcher
+                .run(interval.as_duration(), |changed| {
+                    let (extension, settings) = (extension.clone(), settings.clone());
+                    async move {
+                        if let Err(e) = extension
+                            .write()
+                            .await
+                            .on_settings_change(&settings, &changed)
+                            .await
+                        {
+                            warn!(
+                                "Extension failed to apply reloaded settings {:?}: {}",
+                                changed, e
+                            );
+                        }
+                    }
+                })
+                .await
+        }));
+        info!("Watching settings files for changes every {}", interval);
+        Ok(())
+    }
+
+    /// A client calling the cluster as the extension's service account.
+    async fn sdk_client(&self) -> SdkClient {
+        let unique_id = self.extension.read().await.unique_id().to_string();
+        SdkClient::new(self.context.transport_client.clone(), unique_id)
+            .with_policies(self.client_policies.clone())
+    }
+
+    /// Write metrics snapshots to the index in the `metrics_report_index`
+    /// setting, if it is set.
+    async fn start_metrics_reporter(
+        &mut self,
+        sampler: Option<Arc<ResourceSampler>>,
+    ) -> Result<(), ExtensionError> {
+        let Some(reporter) =
+            MetricsReporter::from_settings(self.sdk_client().await, &self.context.settings)?
+        else {
+            return Ok(());
+        };
+        let reporter = reporter.actions(self.dispatcher.transport_actions().metrics().clone());
+        let reporter = match sampler {
+            Some(sampler) => reporter.resources(sampler),
+            None => reporter,
+        };
+        info!("Reporting metrics snapshots to index {}", reporter.index());
+        self.metrics_reporter = Some(Arc::new(reporter).start());
+        Ok(())
+    }
+
+    /// Sample resource usage into the metrics registry, report snapshots to
+    /// the cluster if configured, and serve the registry if a metrics port
+    /// is configured.
+    async fn start_metrics_server(&mut self) -> Result<(), ExtensionError> {
+        let Some(metrics) = self.metrics.clone() else {
+            return self.start_metrics_reporter(None).await;
+        };
+        let sampler = Arc::new(ResourceSampler::new());
+        metrics.register_collector(sampler.clone());
+        self.resource_sampler = Some(sampler.clone().start());
+        self.start_metrics_reporter(Some(sampler)).await?;
+
+        let Some(port) = metrics_port().get_opt(&self.context.settings)? else {
+            return Ok(());
+        };
+        self.metrics_server = Some(
+            MetricsServer::new(metrics.clone())
+                .bind(port as u16)
+                .await?,
+        );
+        Ok(())
+    }
+
+    async fn register_custom_settings(&self) {
+        use crate::extension::custom_settings::{
+            register_custom_settings, RegisterCustomSettingsRequest,
+        };
+
+        let request = {
+            let ext = self.extension.read().await;
+            let mut settings = ext.custom_settings();
+            settings.extend(ext.setting_registry().descriptors());
+            if settings.is_empty() {
+                return;
+            }
+            RegisterCustomSettingsRequest::new(ext.unique_id(), settings)
+        };
+
+        match register_custom_settings(&self.context.transport_client, &request).await {
+            Ok(()) => info!(
+                "Registered {} custom settings with OpenSearch",
+                request.settings.len()
+            ),
+            Err(e) => warn!("Failed to register custom settings with OpenSearch: {}", e),
+        }
+    }
+
+    async fn register_transport_actions(&self) {
+        use crate::transport::action::{
+            register_transport_actions, RegisterTransportActionsRequest,
+        };
+
+        let actions = self.dispatcher.transport_actions().action_names();
+        if actions.is_empty() {
+            return;
+        }
+
+        let unique_id = self.extension.read().await.unique_id().to_string();
+        let request = RegisterTransportActionsRequest::new(unique_id, actions);
+
+        match register_transport_actions(&self.context.transport_client, &request).await {
+            Ok(()) => info!(
+                "Registered {} transport actions with OpenSearch",
+                request.actions.len()
+            ),
+            Err(e) => warn!(
+                "Failed to register transport actions with OpenSearch: {}",
+                e
+            ),
+        }
+    }
+
+    /// Stop serving and release the runner's tasks. An extension that
+    /// already failed is cleaned up but stays `Failed`; one already
+    /// `Stopping`, e.g. through `ExtensionHandle::shutdown`, carries on.
     async fn shutdown(&mut self) -> Result<(), ExtensionError> {
         info!("Shutting down extension");
-        
-        self.lifecycle.transition_to(ExtensionState::Stopping).await?;
-        
-        {
-            let mut ext = self.extension.write().await;
-            ext.shutdown().await?;
+
+        let state = self.lifecycle.current_state().await;
+        let failed = state == ExtensionState::Failed;
+        if !failed && state != ExtensionState::Stopping {
+            self.lifecycle
+                .transition_to(ExtensionState::Stopping)
+                .await?;
+        }
+        let budget = shutdown_timeout()
+            .get(&self.context.settings)?
+            .as_duration();
+        let deadline = tokio::time::Instant::now() + budget;
+        let mut aborted = Vec::new();
+
+        if let Some(poller) = self.settings_poller.take() {
+            poller.abort();
+        }
+        if let Some(reloader) = self.config_reloader.take() {
+            reloader.abort();
+        }
+
+        let drained = tokio::time::timeout_at(deadline, async {
+            while self.connections.in_flight() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        if drained.is_err() {
+            aborted.push(format!(
+                "in-flight requests ({})",
+                self.connections.in_flight()
+            ));
+        }
+        self.connection_tasks().abort_all();
+        let mut result = Ok(());
+
+        // A failing extension shutdown is reported once the runner's own
+        // tasks are released, rather than leaving them running.
+        let stopped = tokio::time::timeout_at(deadline, async {
+            self.extension.write().await.shutdown().await
+        })
+        .await;
+        match stopped {
+            Ok(Err(e)) => {
+                error!("Extension shutdown failed: {}", e);
+                self.context
+                    .diagnostics
+                    .record_error("shutdown", e.to_string());
+                result = result.and(Err(e));
+            }
+            Ok(Ok(())) => {}
+            Err(_) => aborted.push("extension shutdown".to_string()),
         }
-        
-        self.lifecycle.transition_to(ExtensionState::Stopped).await?;
-        
+        if !aborted.is_empty() {
+            let report = aborted.join(", ");
+            warn!("Shutdown took longer than {:?}, aborted {}", budget, report);
+            self.context
+                .diagnostics
+                .record_error("shutdown", format!("aborted {}", report));
+        }
+
+        if let Some(server) = self.metrics_server.take() {
+            server.abort();
+        }
+        if let Some(sampler) = self.resource_sampler.take() {
+            sampler.abort();
+        }
+        if let Some(reporter) = self.metrics_reporter.take() {
+            reporter.abort();
+        }
+        if let Some(follower) = self.diagnostics_follower.take() {
+            follower.abort();
+        }
+        if !failed {
+            self.lifecycle
+                .transition_to(ExtensionState::Stopped)
+                .await?;
+        }
+
         info!("Extension shutdown complete");
-        Ok(())
+        result
     }
-    
+
     async fn create_shutdown_signal() {
         let ctrl_c = async {
             signal::ctrl_c()
                 .await
                 .expect("Failed to install Ctrl+C handler");
         };
-        
+
         #[cfg(unix)]
         let terminate = async {
             signal::unix::signal(signal::unix::SignalKind::terminate())
@@ -205,10 +1103,10 @@ impl ExtensionRunner {
                 .recv()
                 .await;
         };
-        
+
         #[cfg(not(unix))]
         let terminate = std::future::pending::<()>();
-        
+
         tokio::select! {
             _ = ctrl_c => {},
             _ = terminate => {},
@@ -216,6 +1114,59 @@ impl ExtensionRunner {
     }
 }
 
+/// Calls the extension's lifecycle hooks around the runner's transitions.
+struct ExtensionHooks {
+    extension: Arc<RwLock<Box<dyn Extension>>>,
+    context: Arc<ExtensionContext>,
+}
+
+#[async_trait::async_trait]
+impl TransitionHook for ExtensionHooks {
+    async fn before(&self, from: ExtensionState, to: ExtensionState) -> Result<(), ExtensionError> {
+        match to {
+            ExtensionState::Running if from == ExtensionState::Initialized => {
+                self.extension
+                    .write()
+                    .await
+                    .on_pre_start(&self.context)
+                    .await
+            }
+            ExtensionState::Stopping => {
+                if let Err(e) = self.extension.write().await.on_pre_stop().await {
+                    warn!("Extension pre-stop hook failed: {}", e);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn after(
+        &self,
+        from: ExtensionState,
+        to: ExtensionState,
+        failure: Option<&ExtensionError>,
+    ) {
+        match (to, failure) {
+            (ExtensionState::Running, _) if from == ExtensionState::Initialized => {
+                if let Err(e) = self
+                    .extension
+                    .write()
+                    .await
+                    .on_post_start(&self.context)
+                    .await
+                {
+                    warn!("Extension post-start hook failed: {}", e);
+                }
+            }
+            (ExtensionState::Failed, Some(error)) => {
+                self.extension.write().await.on_failed(error).await
+            }
+            _ => {}
+        }
+    }
+}
+
 pub struct ExtensionHandle {
     lifecycle: Arc<LifecycleManager>,
 }
@@ -224,20 +1175,43 @@ impl ExtensionHandle {
     pub fn new(lifecycle: Arc<LifecycleManager>) -> Self {
         ExtensionHandle { lifecycle }
     }
-    
+
     pub async fn state(&self) -> ExtensionState {
         self.lifecycle.current_state().await
     }
-    
+
     pub async fn is_running(&self) -> bool {
         self.lifecycle.is_running().await
     }
-    
+
+    /// Follow state changes as they happen.
+    pub fn subscribe(&self) -> watch::Receiver<ExtensionState> {
+        self.lifecycle.subscribe()
+    }
+
+    /// Wait until the extension reaches `state`, e.g. `Running` once
+    /// started or `Stopped` after shutdown, failing after `timeout`.
+    pub async fn await_state(
+        &self,
+        state: ExtensionState,
+        timeout: Duration,
+    ) -> Result<(), ExtensionError> {
+        self.lifecycle.await_state(state, timeout).await
+    }
+
+    pub async fn pause(&self) -> Result<(), ExtensionError> {
+        self.lifecycle.pause().await
+    }
+
+    pub async fn resume(&self) -> Result<(), ExtensionError> {
+        self.lifecycle.resume().await
+    }
+
     pub async fn shutdown(&self) -> Result<(), ExtensionError> {
         if self.lifecycle.is_terminal().await {
             return Ok(());
         }
-        
+
         self.lifecycle.transition_to(ExtensionState::Stopping).await
     }
 }
@@ -245,27 +1219,497 @@ impl ExtensionHandle {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::extension::custom_settings::{CustomSettingDescriptor, SettingType};
     use crate::extension::ExtensionContext;
     use crate::transport::TransportClient;
-    
+
     struct TestExtension;
-    
+
     #[async_trait::async_trait]
     impl Extension for TestExtension {
-        fn name(&self) -> &str { "test" }
-        fn unique_id(&self) -> &str { "test-ext" }
-        fn version(&self) -> &str { "1.0.0" }
-        fn opensearch_version(&self) -> &str { "3.0.0" }
-        
+        fn name(&self) -> &str {
+            "test"
+        }
+        fn unique_id(&self) -> &str {
+            "test-ext"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
         async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
             Ok(())
         }
-        
+
         async fn shutdown(&mut self) -> Result<(), ExtensionError> {
             Ok(())
         }
     }
-    
+
+    struct StuckExtension;
+
+    #[async_trait::async_trait]
+    impl Extension for StuckExtension {
+        fn name(&self) -> &str {
+            "stuck"
+        }
+        fn unique_id(&self) -> &str {
+            "stuck-ext"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
+        async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<(), ExtensionError> {
+            std::future::pending().await
+        }
+    }
+
+    struct BrokenShutdownExtension;
+
+    #[async_trait::async_trait]
+    impl Extension for BrokenShutdownExtension {
+        fn name(&self) -> &str {
+            "broken-shutdown"
+        }
+        fn unique_id(&self) -> &str {
+            "broken-shutdown-ext"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
+        async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<(), ExtensionError> {
+            Err(ExtensionError::unknown("flush failed"))
+        }
+    }
+
+    struct OneShotExtension(bool);
+
+    #[async_trait::async_trait]
+    impl Extension for OneShotExtension {
+        fn name(&self) -> &str {
+            "one-shot"
+        }
+        fn unique_id(&self) -> &str {
+            "one-shot-ext"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
+        async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
+            if std::mem::replace(&mut self.0, true) {
+                return Err(ExtensionError::initialization("already used"));
+            }
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<(), ExtensionError> {
+            Ok(())
+        }
+    }
+
+    struct StrictExtension;
+
+    #[async_trait::async_trait]
+    impl Extension for StrictExtension {
+        fn name(&self) -> &str {
+            "strict"
+        }
+        fn unique_id(&self) -> &str {
+            "strict-ext"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
+        fn custom_settings(&self) -> Vec<CustomSettingDescriptor> {
+            vec![CustomSettingDescriptor::new(
+                "strict.enabled",
+                SettingType::Boolean,
+            )]
+        }
+
+        async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> Result<(), ExtensionError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_invalid_settings_leave_extension_failed() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("127.0.0.1", port)))
+            .build()
+            .unwrap();
+        context.settings.set("strict.enabled", "maybe").unwrap();
+        let runtime = context.thread_pool.clone();
+        let mut runner = ExtensionRunner::new(Box::new(StrictExtension), context, 0).unwrap();
+
+        runtime.block_on(async {
+            let error = runner.run().await.unwrap_err();
+            assert!(error.to_string().contains("'strict.enabled'"));
+            assert_eq!(
+                runner.lifecycle.current_state().await,
+                ExtensionState::Failed
+            );
+        });
+    }
+
+    #[test]
+    fn test_handle_shutdown_stops_runner() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("127.0.0.1", port)))
+            .build()
+            .unwrap();
+        context
+            .settings
+            .set("extension.reconnect.enabled", "false")
+            .unwrap();
+        let runtime = context.thread_pool.clone();
+        let mut runner = ExtensionRunner::new(Box::new(TestExtension), context, 0).unwrap();
+        let handle = runner.handle();
+
+        runtime.block_on(async {
+            let stop = async {
+                handle
+                    .await_state(ExtensionState::Running, Duration::from_secs(5))
+                    .await?;
+                handle.shutdown().await?;
+                handle
+                    .await_state(ExtensionState::Stopped, Duration::from_secs(5))
+                    .await
+            };
+            let (ran, stopped) = tokio::time::timeout(Duration::from_secs(10), async {
+                tokio::join!(runner.run(), stop)
+            })
+            .await
+            .unwrap();
+            ran.unwrap();
+            stopped.unwrap();
+        });
+        assert_eq!(runtime.block_on(handle.state()), ExtensionState::Stopped);
+    }
+
+    #[test]
+    fn test_failed_restart_leaves_extension_failed() {
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9200)))
+            .build()
+            .unwrap();
+        let runtime = context.thread_pool.clone();
+        let mut runner =
+            ExtensionRunner::new(Box::new(OneShotExtension(true)), context, 0).unwrap();
+
+        runtime.block_on(async {
+            for state in [
+                ExtensionState::Initializing,
+                ExtensionState::Initialized,
+                ExtensionState::Running,
+            ] {
+                runner.lifecycle.transition_to(state).await.unwrap();
+            }
+            assert!(runner.restart_extension().await.is_err());
+            assert_eq!(
+                runner.lifecycle.current_state().await,
+                ExtensionState::Failed
+            );
+
+            runner.shutdown().await.unwrap();
+            assert_eq!(
+                runner.lifecycle.current_state().await,
+                ExtensionState::Failed
+            );
+        });
+    }
+
+    #[test]
+    fn test_shutdown_aborts_work_over_budget() {
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9200)))
+            .build()
+            .unwrap();
+        context
+            .settings
+            .set("extension.shutdown_timeout", "50ms")
+            .unwrap();
+        let runtime = context.thread_pool.clone();
+        let mut runner = ExtensionRunner::new(Box::new(StuckExtension), context, 0).unwrap();
+
+        runtime.block_on(async {
+            for state in [
+                ExtensionState::Initializing,
+                ExtensionState::Initialized,
+                ExtensionState::Running,
+            ] {
+                runner.lifecycle.transition_to(state).await.unwrap();
+            }
+            let connection = runner.connections.open("127.0.0.1:9300".parse().unwrap());
+            runner.connection_tasks().spawn(async move {
+                let _request = connection.counters().begin_request();
+                std::future::pending::<()>().await
+            });
+            while runner.connections.in_flight() == 0 {
+                tokio::task::yield_now().await;
+            }
+
+            tokio::time::timeout(Duration::from_secs(5), runner.shutdown())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                runner.lifecycle.current_state().await,
+                ExtensionState::Stopped
+            );
+            let error = runner.context.diagnostics.recent_errors().pop().unwrap();
+            assert_eq!(
+                error.message,
+                "aborted in-flight requests (1), extension shutdown"
+            );
+        });
+    }
+
+    #[test]
+    fn test_failed_extension_shutdown_still_stops_runner() {
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9200)))
+            .build()
+            .unwrap();
+        let runtime = context.thread_pool.clone();
+        let mut runner =
+            ExtensionRunner::new(Box::new(BrokenShutdownExtension), context, 0).unwrap();
+
+        runtime.block_on(async {
+            for state in [
+                ExtensionState::Initializing,
+                ExtensionState::Initialized,
+                ExtensionState::Running,
+            ] {
+                runner.lifecycle.transition_to(state).await.unwrap();
+            }
+            let sampler = tokio::spawn(std::future::pending::<()>());
+            let sampler_handle = sampler.abort_handle();
+            runner.resource_sampler = Some(sampler);
+
+            let error = runner.shutdown().await.unwrap_err();
+            assert!(error.to_string().contains("flush failed"));
+            assert_eq!(
+                runner.lifecycle.current_state().await,
+                ExtensionState::Stopped
+            );
+            while !sampler_handle.is_finished() {
+                tokio::task::yield_now().await;
+            }
+            let recorded = runner.context.diagnostics.recent_errors().pop().unwrap();
+            assert_eq!(recorded.source, "shutdown");
+        });
+    }
+
+    /// Answers every request on `listener` with a successful registration,
+    /// counting registration requests.
+    async fn accept_registrations(
+        listener: TcpListener,
+        registrations: Arc<std::sync::atomic::AtomicUsize>,
+    ) {
+        use crate::transport::inbound::{read_message, write_response};
+        use crate::transport::ThreadContext;
+
+        while let Ok((mut stream, _)) = listener.accept().await {
+            // Heartbeats connect without sending anything.
+            let Ok(Some(request)) = read_message(&mut stream).await else {
+                continue;
+            };
+            if request.action.as_deref() == Some("internal:discovery/register") {
+                registrations.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+            let body = serde_json::json!({"success": true, "cluster_name": "remote", "service_account_token": "token"});
+            write_response(
+                &mut stream,
+                &request.header,
+                &ThreadContext::new(),
+                body.to_string().as_bytes(),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_registers_with_configured_node() {
+        let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // The configured node differs from the localhost:9300 default only by port.
+        assert_ne!(port, 9300);
+        let registrations = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        runtime.spawn(accept_registrations(listener, registrations.clone()));
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("127.0.0.1", port)))
+            .thread_pool(runtime.clone())
+            .build()
+            .unwrap();
+        let runner = ExtensionRunner::new(Box::new(TestExtension), context, 0).unwrap();
+
+        runtime.block_on(runner.try_register()).unwrap();
+        assert_eq!(registrations.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(runner.context.service_account.has_token());
+    }
+
+    #[test]
+    fn test_start_failure_fails_extension() {
+        let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        runtime.spawn(accept_registrations(
+            listener,
+            Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+        ));
+        let taken = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("127.0.0.1", port)))
+            .thread_pool(runtime.clone())
+            .build()
+            .unwrap();
+        let mut runner = ExtensionRunner::new(
+            Box::new(TestExtension),
+            context,
+            taken.local_addr().unwrap().port(),
+        )
+        .unwrap();
+
+        runtime.block_on(async {
+            let error = tokio::time::timeout(Duration::from_secs(10), runner.run())
+                .await
+                .unwrap()
+                .unwrap_err();
+            assert!(error.to_string().contains("Failed to bind"));
+            assert_eq!(
+                runner.lifecycle.current_state().await,
+                ExtensionState::Failed
+            );
+        });
+    }
+
+    #[test]
+    fn test_reregister_targets_configured_node() {
+        let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // The configured node differs from the localhost:9300 default only by port.
+        assert_ne!(port, 9300);
+        let registrations = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        runtime.spawn(accept_registrations(listener, registrations.clone()));
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("127.0.0.1", port)))
+            .thread_pool(runtime.clone())
+            .build()
+            .unwrap();
+        let runner = ExtensionRunner::new(Box::new(TestExtension), context, 0).unwrap();
+
+        runtime.block_on(async {
+            runner.context.transport_client.connect().await.unwrap();
+            runner.reregister().await.unwrap();
+        });
+        assert_eq!(registrations.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_client_policies_follow_cluster_settings() {
+        use crate::client::{OperationCategory, RemoteExtensionActionResponse};
+        use crate::interface::Serialize as _;
+        use crate::transport::inbound::{read_message, write_response};
+        use crate::transport::ThreadContext;
+
+        let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        runtime.spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let request = read_message(&mut stream).await.unwrap().unwrap();
+                let body = serde_json::json!({"persistent": {"client.policy.search.retries": "4"}});
+                let mut bytes = Vec::new();
+                RemoteExtensionActionResponse {
+                    success: true,
+                    response_bytes: body.to_string().into_bytes(),
+                }
+                .serialize(&mut bytes)
+                .unwrap();
+                write_response(
+                    &mut stream,
+                    &request.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+        });
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("127.0.0.1", port)))
+            .thread_pool(runtime.clone())
+            .build()
+            .unwrap();
+        context
+            .settings
+            .set("client.policy.search.retries", 2)
+            .unwrap();
+        let mut runner = ExtensionRunner::new(Box::new(TestExtension), context, 0).unwrap();
+
+        runtime.block_on(async {
+            runner.load_client_policies().unwrap();
+            let client = runner.sdk_client().await;
+            assert_eq!(
+                client.policies().policy(OperationCategory::Search).retries,
+                2
+            );
+
+            runner.start_settings_poller().await.unwrap();
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while client.policies().policy(OperationCategory::Search).retries != 4 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap();
+            runner.settings_poller.take().unwrap().abort();
+        });
+    }
+
     #[test]
     fn test_extension_runner_creation() {
         let runtime = tokio::runtime::Runtime::new().unwrap();
@@ -276,8 +1720,8 @@ mod tests {
             .thread_pool(Arc::new(runtime))
             .build()
             .unwrap();
-        
+
         let runner = ExtensionRunner::new(extension, context, 1234);
         assert!(runner.is_ok());
     }
-}
\ No newline at end of file
+}
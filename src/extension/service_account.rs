@@ -0,0 +1,251 @@
+use serde_json::Value;
+use std::fmt;
+use std::sync::{PoisonError, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+use crate::extension::identity::decode_base64url;
+use crate::extension::ExtensionError;
+use crate::interface::{read_string, write_string};
+use crate::transport::TransportClient;
+
+/// Asks OpenSearch to issue a new service-account token for the extension.
+pub const SERVICE_ACCOUNT_TOKEN_ACTION: &str = "internal:extensions/serviceaccounttoken";
+
+/// Request header carrying the service-account token on outbound calls.
+pub const SERVICE_ACCOUNT_HEADER: &str = "X-Extension-Service-Account-Token";
+
+/// Tokens are renewed this long before they expire, so calls in flight do
+/// not race the expiry.
+const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Token OpenSearch issues to the extension's service account. The value is
+/// never printed, so tokens do not end up in logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ServiceAccountToken {
+    token: String,
+    expires_at: Option<SystemTime>,
+}
+
+impl ServiceAccountToken {
+    /// Wrap `token`, reading its expiry from the `exp` claim when it is a JWT.
+    pub fn new(token: impl Into<String>) -> Self {
+        let token = token.into();
+        let expires_at = jwt_expiry(&token);
+        ServiceAccountToken { token, expires_at }
+    }
+
+    pub fn with_expiry(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    /// Whether the token expires within `margin` from now. Tokens without
+    /// an expiry never do.
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        self.expires_at
+            .is_some_and(|at| at <= SystemTime::now() + margin)
+    }
+}
+
+impl fmt::Debug for ServiceAccountToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccountToken")
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Holds the extension's service-account token, shared by every `SdkClient`
+/// of the extension. The token is set from the registration response and
+/// re-requested from OpenSearch when it is about to expire.
+#[derive(Debug)]
+pub struct ServiceAccount {
+    token: RwLock<Option<ServiceAccountToken>>,
+    refresh: tokio::sync::Mutex<()>,
+    refresh_margin: Duration,
+}
+
+impl ServiceAccount {
+    pub fn new() -> Self {
+        ServiceAccount {
+            token: RwLock::new(None),
+            refresh: tokio::sync::Mutex::new(()),
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+        }
+    }
+
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    /// Replace the token, e.g. after registration is redone.
+    pub fn set_token(&self, token: ServiceAccountToken) {
+        debug!(
+            "Service account token set, expires at {:?}",
+            token.expires_at()
+        );
+        *self.token.write().unwrap_or_else(PoisonError::into_inner) = Some(token);
+    }
+
+    pub fn clear(&self) {
+        *self.token.write().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    pub fn has_token(&self) -> bool {
+        self.token
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some()
+    }
+
+    fn current(&self) -> Option<ServiceAccountToken> {
+        self.token
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The token to attach to an outbound call, refreshing it through
+    /// `transport` first if it is about to expire. `None` when OpenSearch
+    /// never issued one, as on clusters without the security plugin.
+    pub async fn token(
+        &self,
+        transport: &TransportClient,
+        unique_id: &str,
+    ) -> Result<Option<String>, ExtensionError> {
+        match self.current() {
+            None => Ok(None),
+            Some(token) if !token.expires_within(self.refresh_margin) => Ok(Some(token.token)),
+            Some(_) => self.refresh(transport, unique_id).await.map(Some),
+        }
+    }
+
+    /// Request a new token from OpenSearch. Concurrent callers share one
+    /// request.
+    pub async fn refresh(
+        &self,
+        transport: &TransportClient,
+        unique_id: &str,
+    ) -> Result<String, ExtensionError> {
+        let _guard = self.refresh.lock().await;
+        if let Some(token) = self
+            .current()
+            .filter(|token| !token.expires_within(self.refresh_margin))
+        {
+            return Ok(token.token);
+        }
+
+        let mut request = Vec::new();
+        write_string(&mut request, unique_id).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize token request: {}", e))
+        })?;
+        let response = transport
+            .send_request(SERVICE_ACCOUNT_TOKEN_ACTION, &request)
+            .await?;
+        let token = read_string(&mut response.as_slice()).map_err(|e| {
+            ExtensionError::protocol(format!("Invalid service account token response: {}", e))
+        })?;
+        if token.is_empty() {
+            return Err(ExtensionError::protocol(
+                "OpenSearch did not issue a service account token",
+            ));
+        }
+
+        let token = ServiceAccountToken::new(token);
+        self.set_token(token.clone());
+        Ok(token.token)
+    }
+}
+
+impl Default for ServiceAccount {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn jwt_expiry(token: &str) -> Option<SystemTime> {
+    let [_, payload, _] = token.split('.').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let claims: Value = serde_json::from_slice(&decode_base64url(payload)?).ok()?;
+    let exp = claims.get("exp")?.as_u64()?;
+    Some(UNIX_EPOCH + Duration::from_secs(exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_refresh_expiring_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_message(&mut socket).await.unwrap().unwrap();
+            let mut response = Vec::new();
+            write_string(&mut response, "fresh-token").unwrap();
+            write_response(
+                &mut socket,
+                &request.header,
+                &ThreadContext::new(),
+                &response,
+                false,
+            )
+            .await
+            .unwrap();
+            read_string(&mut request.content.as_slice()).unwrap()
+        });
+        let transport = TransportClient::new("127.0.0.1", port);
+
+        let account = ServiceAccount::new();
+        assert_eq!(
+            account.token(&transport, "hello-world").await.unwrap(),
+            None
+        );
+        account.set_token(ServiceAccountToken::new("issued-token"));
+        assert_eq!(
+            account
+                .token(&transport, "hello-world")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("issued-token")
+        );
+
+        account.set_token(ServiceAccountToken::new("old-token").with_expiry(SystemTime::now()));
+        assert_eq!(
+            account
+                .token(&transport, "hello-world")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("fresh-token")
+        );
+        assert_eq!(server.await.unwrap(), "hello-world");
+        assert!(!format!("{:?}", account).contains("fresh-token"));
+
+        // {"sub":"hello-world","exp":1700000000}
+        let jwt = "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiJoZWxsby13b3JsZCIsImV4cCI6MTcwMDAwMDAwMH0.c2ln";
+        let token = ServiceAccountToken::new(jwt);
+        assert_eq!(
+            token.expires_at(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert!(token.expires_within(Duration::ZERO));
+    }
+}
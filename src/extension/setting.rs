@@ -0,0 +1,829 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::custom_settings::{CustomSettingDescriptor, SettingScope, SettingType};
+use crate::extension::units::{ByteSizeValue, TimeValue};
+use crate::extension::ExtensionError;
+
+/// Rust type a setting reads as.
+pub trait SettingKind: Clone + Debug + Send + Sync + 'static {
+    const TYPE: SettingType;
+
+    fn from_value(value: &SettingValue) -> Option<Self>;
+
+    fn into_value(self) -> SettingValue;
+}
+
+impl SettingKind for i64 {
+    const TYPE: SettingType = SettingType::Integer;
+
+    /// Strings are parsed, as values from OpenSearch arrive as strings.
+    fn from_value(value: &SettingValue) -> Option<Self> {
+        match value {
+            SettingValue::Integer(i) => Some(*i),
+            SettingValue::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> SettingValue {
+        SettingValue::Integer(self)
+    }
+}
+
+impl SettingKind for f64 {
+    const TYPE: SettingType = SettingType::Float;
+
+    /// Integers and numeric strings are accepted where a float is declared.
+    fn from_value(value: &SettingValue) -> Option<Self> {
+        match value {
+            SettingValue::Float(f) => Some(*f),
+            SettingValue::Integer(i) => Some(*i as f64),
+            SettingValue::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> SettingValue {
+        SettingValue::Float(self)
+    }
+}
+
+impl SettingKind for bool {
+    const TYPE: SettingType = SettingType::Boolean;
+
+    fn from_value(value: &SettingValue) -> Option<Self> {
+        match value {
+            SettingValue::Boolean(b) => Some(*b),
+            SettingValue::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> SettingValue {
+        SettingValue::Boolean(self)
+    }
+}
+
+impl SettingKind for String {
+    const TYPE: SettingType = SettingType::String;
+
+    fn from_value(value: &SettingValue) -> Option<Self> {
+        match value {
+            SettingValue::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> SettingValue {
+        SettingValue::String(self)
+    }
+}
+
+impl SettingKind for ByteSizeValue {
+    const TYPE: SettingType = SettingType::ByteSize;
+
+    /// Strings such as `"512mb"` are parsed.
+    fn from_value(value: &SettingValue) -> Option<Self> {
+        match value {
+            SettingValue::ByteSize(b) => Some(*b),
+            SettingValue::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> SettingValue {
+        SettingValue::ByteSize(self)
+    }
+}
+
+impl SettingKind for TimeValue {
+    const TYPE: SettingType = SettingType::Time;
+
+    /// Strings such as `"30s"` are parsed.
+    fn from_value(value: &SettingValue) -> Option<Self> {
+        match value {
+            SettingValue::Time(t) => Some(*t),
+            SettingValue::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> SettingValue {
+        SettingValue::Time(self)
+    }
+}
+
+type Validator<T> = Arc<dyn Fn(&T) -> Result<(), String> + Send + Sync>;
+
+/// A typed setting definition, e.g.
+/// `Setting::int("myext.batch_size").default(100).min(1).dynamic().node_scope()`.
+/// Values are validated whenever they are read.
+#[derive(Clone)]
+pub struct Setting<T: SettingKind> {
+    key: String,
+    default: Option<T>,
+    dynamic: bool,
+    scope: SettingScope,
+    secure: bool,
+    validators: Vec<Validator<T>>,
+}
+
+impl Setting<i64> {
+    pub fn int(key: impl Into<String>) -> Self {
+        Setting::new(key)
+    }
+
+    pub fn min(self, min: i64) -> Self {
+        self.validator(move |value| {
+            if *value < min {
+                Err(format!("must be at least {}", min))
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    pub fn max(self, max: i64) -> Self {
+        self.validator(move |value| {
+            if *value > max {
+                Err(format!("must be at most {}", max))
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+impl Setting<f64> {
+    pub fn float(key: impl Into<String>) -> Self {
+        Setting::new(key)
+    }
+
+    pub fn min(self, min: f64) -> Self {
+        self.validator(move |value| {
+            if *value < min {
+                Err(format!("must be at least {}", min))
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    pub fn max(self, max: f64) -> Self {
+        self.validator(move |value| {
+            if *value > max {
+                Err(format!("must be at most {}", max))
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+impl Setting<bool> {
+    pub fn bool(key: impl Into<String>) -> Self {
+        Setting::new(key)
+    }
+}
+
+impl Setting<String> {
+    pub fn string(key: impl Into<String>) -> Self {
+        Setting::new(key)
+    }
+
+    pub fn one_of(self, allowed: &[&str]) -> Self {
+        let allowed: Vec<String> = allowed.iter().map(|value| value.to_string()).collect();
+        self.validator(move |value| {
+            if allowed.contains(value) {
+                Ok(())
+            } else {
+                Err(format!("must be one of [{}]", allowed.join(", ")))
+            }
+        })
+    }
+}
+
+impl Setting<ByteSizeValue> {
+    pub fn byte_size(key: impl Into<String>) -> Self {
+        Setting::new(key)
+    }
+
+    pub fn min(self, min: ByteSizeValue) -> Self {
+        self.validator(move |value| {
+            if *value < min {
+                Err(format!("must be at least {}", min))
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    pub fn max(self, max: ByteSizeValue) -> Self {
+        self.validator(move |value| {
+            if *value > max {
+                Err(format!("must be at most {}", max))
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+impl Setting<TimeValue> {
+    pub fn time(key: impl Into<String>) -> Self {
+        Setting::new(key)
+    }
+
+    pub fn min(self, min: TimeValue) -> Self {
+        self.validator(move |value| {
+            if *value < min {
+                Err(format!("must be at least {}", min))
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    pub fn max(self, max: TimeValue) -> Self {
+        self.validator(move |value| {
+            if *value > max {
+                Err(format!("must be at most {}", max))
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+impl<T: SettingKind> Setting<T> {
+    fn new(key: impl Into<String>) -> Self {
+        Setting {
+            key: key.into(),
+            default: None,
+            dynamic: false,
+            scope: SettingScope::Node,
+            secure: false,
+            validators: Vec::new(),
+        }
+    }
+
+    pub fn default(mut self, value: impl Into<T>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    pub fn dynamic(mut self) -> Self {
+        self.dynamic = true;
+        self
+    }
+
+    pub fn node_scope(mut self) -> Self {
+        self.scope = SettingScope::Node;
+        self
+    }
+
+    pub fn index_scope(mut self) -> Self {
+        self.scope = SettingScope::Index;
+        self
+    }
+
+    /// Hold a secret such as a key or password. The value is never taken
+    /// from the settings themselves, which end up in logs and diagnostics,
+    /// but read from the file named by the `<key>_file` setting, and the
+    /// key is redacted from diagnostic bundles.
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    /// Check values with `validator`, which describes what is wrong, e.g. `"must be even"`.
+    pub fn validator(
+        mut self,
+        validator: impl Fn(&T) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.validators.push(Arc::new(validator));
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn default_value(&self) -> Option<&T> {
+        self.default.as_ref()
+    }
+
+    /// The setting naming the file a secure setting is read from.
+    pub fn file_key(&self) -> String {
+        format!("{}_file", self.key)
+    }
+
+    fn check(&self, value: &T) -> Result<(), ExtensionError> {
+        for validator in &self.validators {
+            validator(value).map_err(|reason| {
+                let shown = if self.secure {
+                    "<redacted>".to_string()
+                } else {
+                    format!("{:?}", value)
+                };
+                ExtensionError::configuration(format!(
+                    "Invalid value {} for setting '{}': {}",
+                    shown, self.key, reason
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn parse(&self, raw: &SettingValue) -> Result<T, ExtensionError> {
+        let value = T::from_value(raw).ok_or_else(|| {
+            ExtensionError::configuration(format!(
+                "Setting '{}' is a {}, expected {}",
+                self.key,
+                SettingType::of(raw).name(),
+                T::TYPE.name()
+            ))
+        })?;
+        self.check(&value)?;
+        Ok(value)
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    /// The value in `settings`, validated, or `None` when it is not set.
+    pub fn get_opt(&self, settings: &Settings) -> Result<Option<T>, ExtensionError> {
+        if self.secure {
+            return self.read_secure(settings);
+        }
+        settings
+            .get(&self.key)?
+            .map(|raw| self.parse(&raw))
+            .transpose()
+    }
+
+    fn read_secure(&self, settings: &Settings) -> Result<Option<T>, ExtensionError> {
+        settings.mark_secure(&self.key)?;
+        if settings.get(&self.key)?.is_some() {
+            return Err(ExtensionError::configuration(format!(
+                "Secure setting '{}' must be set through '{}'",
+                self.key,
+                self.file_key()
+            )));
+        }
+        let Some(path) = settings.get_string(&self.file_key())? else {
+            return Ok(None);
+        };
+        let raw = std::fs::read_to_string(&path).map_err(|e| {
+            ExtensionError::configuration(format!(
+                "Failed to read secure setting '{}' from {}: {}",
+                self.key, path, e
+            ))
+        })?;
+        let raw = raw.trim_end_matches(['\r', '\n']).to_string();
+        self.parse(&SettingValue::String(raw)).map(Some)
+    }
+
+    /// The value in `settings`, or the default when it is not set.
+    pub fn get(&self, settings: &Settings) -> Result<T, ExtensionError> {
+        match self.get_opt(settings)? {
+            Some(value) => Ok(value),
+            None => self.default.clone().ok_or_else(|| {
+                ExtensionError::configuration(format!("Setting '{}' is required", self.key))
+            }),
+        }
+    }
+
+    /// Validate and store `value`.
+    pub fn set(&self, settings: &Settings, value: impl Into<T>) -> Result<(), ExtensionError> {
+        if self.secure {
+            return Err(ExtensionError::configuration(format!(
+                "Secure setting '{}' must be set through '{}'",
+                self.key,
+                self.file_key()
+            )));
+        }
+        let value = value.into();
+        self.check(&value)?;
+        settings.set(self.key.clone(), value.into_value())
+    }
+
+    pub fn descriptor(&self) -> CustomSettingDescriptor {
+        let mut descriptor =
+            CustomSettingDescriptor::new(self.key.clone(), T::TYPE).scope(self.scope);
+        descriptor.default_value = self.default.clone().map(SettingKind::into_value);
+        descriptor.dynamic = self.dynamic;
+        descriptor
+    }
+}
+
+/// A setting repeated per named group, such as `myext.targets.<name>.url`.
+#[derive(Clone)]
+pub struct AffixSetting<T: SettingKind> {
+    prefix: String,
+    suffix: String,
+    pattern: String,
+    setting: Arc<dyn Fn(String) -> Setting<T> + Send + Sync>,
+}
+
+impl<T: SettingKind> AffixSetting<T> {
+    /// `setting` builds the definition for a concrete key, e.g.
+    /// `AffixSetting::new("myext.targets", "url", |key| Setting::string(key).dynamic())`.
+    pub fn new(
+        prefix: impl Into<String>,
+        suffix: impl Into<String>,
+        setting: impl Fn(String) -> Setting<T> + Send + Sync + 'static,
+    ) -> Self {
+        let prefix = prefix.into().trim_end_matches('.').to_string();
+        let suffix = suffix.into().trim_start_matches('.').to_string();
+        AffixSetting {
+            pattern: format!("{}.*.{}", prefix, suffix),
+            prefix,
+            suffix,
+            setting: Arc::new(setting),
+        }
+    }
+
+    /// The key pattern advertised to OpenSearch, e.g. `myext.targets.*.url`.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The setting for the group `name`.
+    pub fn concrete(&self, name: &str) -> Setting<T> {
+        (self.setting)(format!("{}.{}.{}", self.prefix, name, self.suffix))
+    }
+
+    /// Names of the groups that set this setting.
+    pub fn names(&self, settings: &Settings) -> Result<Vec<String>, ExtensionError> {
+        let prefix = format!("{}.", self.prefix);
+        let suffix = format!(".{}", self.suffix);
+        Ok(settings
+            .keys()?
+            .into_iter()
+            .filter_map(|key| {
+                let name = key.strip_prefix(&prefix)?.strip_suffix(&suffix)?;
+                (!name.is_empty() && !name.contains('.')).then(|| name.to_string())
+            })
+            .collect())
+    }
+
+    pub fn get(&self, settings: &Settings, name: &str) -> Result<T, ExtensionError> {
+        self.concrete(name).get(settings)
+    }
+
+    /// The validated value for every group that sets it, by group name.
+    pub fn get_all(&self, settings: &Settings) -> Result<BTreeMap<String, T>, ExtensionError> {
+        self.names(settings)?
+            .into_iter()
+            .map(|name| Ok((name.clone(), self.get(settings, &name)?)))
+            .collect()
+    }
+}
+
+/// Type-erased view of a `Setting<T>` for the registry.
+trait AnySetting: Send + Sync {
+    fn key(&self) -> &str;
+
+    fn descriptor(&self) -> CustomSettingDescriptor;
+
+    fn validate(&self, settings: &Settings) -> Result<(), ExtensionError>;
+
+    /// Whether `value` may replace the current one; `None` resets the setting.
+    fn accepts(&self, value: Option<&SettingValue>) -> Result<(), ExtensionError>;
+}
+
+impl<T: SettingKind> AnySetting for Setting<T> {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn descriptor(&self) -> CustomSettingDescriptor {
+        Setting::descriptor(self)
+    }
+
+    fn validate(&self, settings: &Settings) -> Result<(), ExtensionError> {
+        if let Some(default) = &self.default {
+            self.check(default)?;
+        }
+        self.get_opt(settings).map(|_| ())
+    }
+
+    fn accepts(&self, value: Option<&SettingValue>) -> Result<(), ExtensionError> {
+        match (value, &self.default) {
+            (Some(_), _) if self.secure => Err(ExtensionError::configuration(format!(
+                "Secure setting '{}' must be set through '{}'",
+                self.key,
+                self.file_key()
+            ))),
+            (Some(raw), _) => self.parse(raw).map(|_| ()),
+            (None, Some(_)) => Ok(()),
+            (None, None) => Err(ExtensionError::configuration(format!(
+                "Setting '{}' is required and cannot be reset",
+                self.key
+            ))),
+        }
+    }
+}
+
+impl<T: SettingKind> AnySetting for AffixSetting<T> {
+    fn key(&self) -> &str {
+        &self.pattern
+    }
+
+    fn descriptor(&self) -> CustomSettingDescriptor {
+        self.concrete("*").descriptor()
+    }
+
+    fn validate(&self, settings: &Settings) -> Result<(), ExtensionError> {
+        self.get_all(settings).map(|_| ())
+    }
+
+    fn accepts(&self, value: Option<&SettingValue>) -> Result<(), ExtensionError> {
+        self.concrete("*").accepts(value)
+    }
+}
+
+/// A callback registered with `Settings::add_update_consumer`.
+#[derive(Clone)]
+pub(crate) struct UpdateConsumer {
+    setting: Arc<dyn AnySetting>,
+    callback: Arc<dyn Fn(&Settings) + Send + Sync>,
+}
+
+impl UpdateConsumer {
+    pub(crate) fn new<T: SettingKind>(
+        setting: &Setting<T>,
+        consumer: impl Fn(T) + Send + Sync + 'static,
+    ) -> Result<Self, ExtensionError> {
+        if !setting.dynamic {
+            return Err(ExtensionError::configuration(format!(
+                "Setting '{}' is not dynamic and cannot have update consumers",
+                setting.key
+            )));
+        }
+        let typed = setting.clone();
+        Ok(UpdateConsumer {
+            setting: Arc::new(setting.clone()),
+            callback: Arc::new(move |settings| match typed.get(settings) {
+                Ok(value) => consumer(value),
+                Err(e) => warn!("Skipping update consumer: {}", e),
+            }),
+        })
+    }
+
+    pub(crate) fn key(&self) -> &str {
+        self.setting.key()
+    }
+
+    pub(crate) fn accepts(&self, value: Option<&SettingValue>) -> Result<(), ExtensionError> {
+        self.setting.accepts(value)
+    }
+
+    pub(crate) fn notify(&self, settings: &Settings) {
+        (self.callback)(settings)
+    }
+}
+
+/// The settings an extension defines, returned from
+/// `Extension::setting_registry`. They are advertised to OpenSearch with
+/// the extension's custom settings and checked once node settings are loaded.
+#[derive(Clone, Default)]
+pub struct SettingRegistry {
+    settings: Vec<Arc<dyn AnySetting>>,
+}
+
+impl SettingRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: SettingKind>(&mut self, setting: &Setting<T>) -> Result<(), ExtensionError> {
+        if self.settings.iter().any(|s| s.key() == setting.key) {
+            return Err(ExtensionError::configuration(format!(
+                "Setting '{}' is already registered",
+                setting.key
+            )));
+        }
+        self.settings.push(Arc::new(setting.clone()));
+        Ok(())
+    }
+
+    pub fn register_affix<T: SettingKind>(
+        &mut self,
+        setting: &AffixSetting<T>,
+    ) -> Result<(), ExtensionError> {
+        if self.settings.iter().any(|s| s.key() == setting.pattern) {
+            return Err(ExtensionError::configuration(format!(
+                "Setting '{}' is already registered",
+                setting.pattern
+            )));
+        }
+        self.settings.push(Arc::new(setting.clone()));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        self.settings.iter().map(|s| s.key()).collect()
+    }
+
+    pub fn descriptors(&self) -> Vec<CustomSettingDescriptor> {
+        self.settings.iter().map(|s| s.descriptor()).collect()
+    }
+
+    /// Check every registered setting present in `settings`, returning one
+    /// error per invalid value.
+    pub fn validate(&self, settings: &Settings) -> Vec<ExtensionError> {
+        self.settings
+            .iter()
+            .filter_map(|s| s.validate(settings).err())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_typed_settings() {
+        let batch_size = Setting::int("myext.batch_size")
+            .default(100)
+            .min(1)
+            .max(10_000)
+            .dynamic();
+        let mode = Setting::string("myext.mode")
+            .default("fast")
+            .one_of(&["fast", "safe"])
+            .index_scope();
+        let ratio = Setting::float("myext.ratio").max(1.0);
+
+        let settings = Settings::new();
+        assert_eq!(batch_size.get(&settings).unwrap(), 100);
+        assert!(ratio.get(&settings).is_err());
+
+        settings.set("myext.batch_size", 0).unwrap();
+        settings.set("myext.ratio", 1).unwrap();
+        assert!(batch_size
+            .get(&settings)
+            .unwrap_err()
+            .to_string()
+            .contains("must be at least 1"));
+        assert_eq!(ratio.get(&settings).unwrap(), 1.0);
+        assert!(mode.set(&settings, "slow").is_err());
+        mode.set(&settings, "safe").unwrap();
+        assert_eq!(mode.get(&settings).unwrap(), "safe");
+
+        let mut registry = SettingRegistry::new();
+        registry.register(&batch_size).unwrap();
+        registry.register(&mode).unwrap();
+        assert!(registry.register(&batch_size).is_err());
+        assert_eq!(registry.validate(&settings).len(), 1);
+
+        let descriptors = registry.descriptors();
+        assert_eq!(
+            descriptors[0].default_value,
+            Some(SettingValue::Integer(100))
+        );
+        assert!(descriptors[0].dynamic);
+        assert_eq!(descriptors[1].scope, SettingScope::Index);
+        assert!(descriptors.iter().all(|d| d.validate().is_ok()));
+    }
+
+    #[test]
+    fn test_affix_and_group_settings() {
+        let url = AffixSetting::new("myext.targets", "url", |key| {
+            Setting::string(key).validator(|url| {
+                if url.starts_with("http") {
+                    Ok(())
+                } else {
+                    Err("must be an http url".to_string())
+                }
+            })
+        });
+        let port = AffixSetting::new("myext.targets", "port", |key| {
+            Setting::int(key).default(9200).min(1)
+        });
+
+        let settings = Settings::new();
+        settings
+            .set("myext.targets.east.url", "http://east")
+            .unwrap();
+        settings
+            .set("myext.targets.west.url", "http://west")
+            .unwrap();
+        settings.set("myext.targets.west.port", 9300).unwrap();
+        settings
+            .set("myext.targets.west.tls.enabled", true)
+            .unwrap();
+
+        assert_eq!(url.names(&settings).unwrap(), vec!["east", "west"]);
+        assert_eq!(url.get_all(&settings).unwrap()["west"], "http://west");
+        assert_eq!(port.get(&settings, "east").unwrap(), 9200);
+        assert_eq!(port.get_all(&settings).unwrap().len(), 1);
+
+        let groups = settings.get_group("myext.targets").unwrap();
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["east", "west"]);
+        assert_eq!(groups["west"].get_integer("port").unwrap(), Some(9300));
+        assert_eq!(
+            groups["west"].get_boolean("tls.enabled").unwrap(),
+            Some(true)
+        );
+
+        let mut registry = SettingRegistry::new();
+        registry.register_affix(&url).unwrap();
+        assert!(registry.register_affix(&url).is_err());
+        assert_eq!(registry.descriptors()[0].key, "myext.targets.*.url");
+        settings
+            .set("myext.targets.north.url", "ftp://north")
+            .unwrap();
+        assert_eq!(registry.validate(&settings).len(), 1);
+    }
+
+    #[test]
+    fn test_byte_size_and_time_settings() {
+        let buffer = Setting::byte_size("myext.buffer")
+            .default(ByteSizeValue::mb(64))
+            .max(ByteSizeValue::gb(1));
+        let timeout = Setting::time("myext.timeout")
+            .default(Duration::from_secs(30))
+            .min(TimeValue::seconds(1));
+
+        let settings = Settings::new();
+        assert_eq!(
+            timeout.get(&settings).unwrap().as_duration(),
+            Duration::from_secs(30)
+        );
+        settings.set("myext.buffer", "512mb").unwrap();
+        settings.set("myext.timeout", "500ms").unwrap();
+        assert_eq!(buffer.get(&settings).unwrap(), ByteSizeValue::mb(512));
+        assert!(timeout
+            .get(&settings)
+            .unwrap_err()
+            .to_string()
+            .contains("must be at least 1s"));
+
+        settings.set("myext.buffer", "lots").unwrap();
+        assert!(buffer.get(&settings).is_err());
+        assert_eq!(
+            buffer.descriptor().default_value,
+            Some(SettingValue::ByteSize(ByteSizeValue::mb(64)))
+        );
+    }
+
+    #[test]
+    fn test_secure_settings_read_from_file() {
+        let dir = std::env::temp_dir().join(format!("secure-setting-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("password");
+        std::fs::write(&path, "hunter2-hunter2\n").unwrap();
+        let password = Setting::string("myext.password")
+            .secure()
+            .validator(|value| {
+                if value.len() >= 8 {
+                    Ok(())
+                } else {
+                    Err("is too short".to_string())
+                }
+            });
+
+        let settings = Settings::new();
+        assert_eq!(password.get_opt(&settings).unwrap(), None);
+        assert!(settings.is_secure("myext.password").unwrap());
+        assert!(password.set(&settings, "hunter2-hunter2").is_err());
+
+        settings
+            .set("myext.password_file", path.to_str().unwrap())
+            .unwrap();
+        assert_eq!(
+            password.get_opt(&settings).unwrap().as_deref(),
+            Some("hunter2-hunter2")
+        );
+
+        std::fs::write(&path, "short").unwrap();
+        let error = password.get(&settings).unwrap_err().to_string();
+        assert!(error.contains("<redacted>") && error.contains("is too short"));
+
+        settings.set("myext.password", "inline").unwrap();
+        assert!(password
+            .get_opt(&settings)
+            .unwrap_err()
+            .to_string()
+            .contains("myext.password_file"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::ExtensionError;
+
+/// Loaders for deployment configuration. `ExtensionBuilder` layers them as
+/// setting defaults < files < environment < explicit `setting` calls.
+impl Settings {
+    /// Load a YAML file. Nested maps become dotted keys, so
+    /// `hello: {greeting: hi}` sets `hello.greeting`.
+    pub fn from_yaml(path: impl AsRef<Path>) -> Result<Settings, ExtensionError> {
+        let path = path.as_ref();
+        let content = read(path)?;
+        let value: Value = serde_yaml::from_str(&content).map_err(|e| {
+            ExtensionError::configuration(format!("Invalid YAML in {}: {}", path.display(), e))
+        })?;
+        flattened(path, value)
+    }
+
+    /// Load a TOML file. Tables become dotted keys like YAML maps.
+    pub fn from_toml(path: impl AsRef<Path>) -> Result<Settings, ExtensionError> {
+        let path = path.as_ref();
+        let content = read(path)?;
+        let table: toml::Table = toml::from_str(&content).map_err(|e| {
+            ExtensionError::configuration(format!("Invalid TOML in {}: {}", path.display(), e))
+        })?;
+        flattened(path, toml_to_json(toml::Value::Table(table)))
+    }
+
+    /// Load a YAML (`.yml`, `.yaml`) or TOML (`.toml`) file by its extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Settings, ExtensionError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yml") | Some("yaml") => Self::from_yaml(path),
+            Some("toml") => Self::from_toml(path),
+            _ => Err(ExtensionError::configuration(format!(
+                "Unsupported settings file {}, expected .yml, .yaml or .toml",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Load environment variables starting with `prefix`. The rest of the
+    /// name is lowercased with `__` separating key parts, so with prefix
+    /// `HELLO_` the variable `HELLO_HTTP__MAX_CONTENT` sets `http.max_content`.
+    /// Values are kept as strings, which typed `Setting`s parse on read.
+    pub fn from_env(prefix: &str) -> Settings {
+        Self::from_vars(prefix, std::env::vars())
+    }
+
+    fn from_vars(prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> Settings {
+        let settings = Settings::new();
+        for (name, value) in vars {
+            let Some(rest) = name.strip_prefix(prefix).filter(|rest| !rest.is_empty()) else {
+                continue;
+            };
+            let key = rest.to_ascii_lowercase().replace("__", ".");
+            settings.set(key, value).unwrap_or(());
+        }
+        settings
+    }
+}
+
+fn read(path: &Path) -> Result<String, ExtensionError> {
+    std::fs::read_to_string(path).map_err(|e| {
+        ExtensionError::configuration(format!(
+            "Failed to read settings file {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+fn flattened(path: &Path, value: Value) -> Result<Settings, ExtensionError> {
+    let Value::Object(root) = value else {
+        if value.is_null() {
+            return Ok(Settings::new());
+        }
+        return Err(ExtensionError::configuration(format!(
+            "Settings file {} must contain a map of settings",
+            path.display()
+        )));
+    };
+    let mut values = HashMap::new();
+    for (key, value) in root {
+        flatten(key, value, &mut values);
+    }
+
+    let settings = Settings::new();
+    for (key, value) in values {
+        let value: SettingValue = serde_json::from_value(value).map_err(|e| {
+            ExtensionError::configuration(format!(
+                "Invalid value for '{}' in {}: {}",
+                key,
+                path.display(),
+                e
+            ))
+        })?;
+        settings.set(key, value)?;
+    }
+    Ok(settings)
+}
+
+fn flatten(key: String, value: Value, values: &mut HashMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (child, value) in map {
+                flatten(format!("{}.{}", key, child), value, values);
+            }
+        }
+        Value::Null => {}
+        value => {
+            values.insert(key, value);
+        }
+    }
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_files_and_env() {
+        let dir = std::env::temp_dir().join(format!("settings-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let yaml = dir.join("hello.yml");
+        std::fs::write(
+            &yaml,
+            "hello:\n  greeting: hi\n  shards: 2\n  tags: [a, b]\nunset: ~\n",
+        )
+        .unwrap();
+        let toml = dir.join("hello.toml");
+        std::fs::write(
+            &toml,
+            "[hello]\ngreeting = \"hey\"\nratio = 0.5\n\n[hello.cache]\nenabled = true\n",
+        )
+        .unwrap();
+
+        let settings = Settings::from_file(&yaml).unwrap();
+        assert_eq!(
+            settings.get_string("hello.greeting").unwrap(),
+            Some("hi".to_string())
+        );
+        assert_eq!(settings.get_integer("hello.shards").unwrap(), Some(2));
+        assert_eq!(
+            settings.get("hello.tags").unwrap(),
+            Some(SettingValue::List(vec!["a".into(), "b".into()]))
+        );
+        assert_eq!(settings.get("unset").unwrap(), None);
+
+        let settings = Settings::from_file(&toml).unwrap();
+        assert_eq!(settings.get_float("hello.ratio").unwrap(), Some(0.5));
+        assert_eq!(
+            settings.get_boolean("hello.cache.enabled").unwrap(),
+            Some(true)
+        );
+        assert!(Settings::from_file(dir.join("hello.json")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let vars = [
+            ("HELLO_HTTP__MAX_CONTENT".to_string(), "10mb".to_string()),
+            ("HELLO_".to_string(), "ignored".to_string()),
+            ("OTHER_KEY".to_string(), "ignored".to_string()),
+        ];
+        let settings = Settings::from_vars("HELLO_", vars);
+        assert_eq!(
+            settings.get_string("http.max_content").unwrap(),
+            Some("10mb".to_string())
+        );
+        assert_eq!(settings.get("other_key").unwrap(), None);
+    }
+}
@@ -0,0 +1,247 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::client::SdkClient;
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+use crate::interface::{
+    read_bool, read_string, read_vint, write_bool, write_string, write_vint, Deserialize, Serialize,
+};
+
+/// Sent by OpenSearch when dynamic settings the extension consumes change.
+pub const UPDATE_SETTINGS_ACTION: &str = "internal:extensions/updatesettings";
+
+/// How often `ClusterSettingsPoller` reads the cluster settings.
+pub fn settings_poll_interval() -> Setting<TimeValue> {
+    Setting::time("extension.settings_poll_interval")
+        .default(TimeValue::seconds(30))
+        .min(TimeValue::seconds(1))
+}
+
+/// Changed settings. A missing value means the setting was reset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateSettingsRequest {
+    pub settings: BTreeMap<String, Option<String>>,
+}
+
+impl UpdateSettingsRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.insert(key.into(), Some(value.into()));
+        self
+    }
+
+    pub fn reset(mut self, key: impl Into<String>) -> Self {
+        self.settings.insert(key.into(), None);
+        self
+    }
+
+    /// Apply the update to `settings`, returning the keys that changed.
+    pub fn apply_to(&self, settings: &Settings) -> Result<Vec<String>, ExtensionError> {
+        settings.apply_updates(
+            self.settings
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().map(SettingValue::String))),
+        )
+    }
+}
+
+impl Serialize for UpdateSettingsRequest {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_vint(buf, self.settings.len() as u32)?;
+        for (key, value) in &self.settings {
+            written += write_string(buf, key)?;
+            written += write_bool(buf, value.is_some())?;
+            if let Some(value) = value {
+                written += write_string(buf, value)?;
+            }
+        }
+        Ok(written)
+    }
+}
+
+impl Deserialize for UpdateSettingsRequest {
+    type Output = UpdateSettingsRequest;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let count = read_vint(buf)?;
+        let mut settings = BTreeMap::new();
+        for _ in 0..count {
+            let key = read_string(buf)?;
+            let value = if read_bool(buf)? {
+                Some(read_string(buf)?)
+            } else {
+                None
+            };
+            settings.insert(key, value);
+        }
+        Ok(UpdateSettingsRequest { settings })
+    }
+}
+
+type ChangeListener = dyn Fn(&[String]) + Send + Sync;
+
+/// Reads the cluster settings and applies changes to the settings that have
+/// update consumers or are under a watched prefix, for nodes that do not
+/// send `UPDATE_SETTINGS_ACTION`.
+pub struct ClusterSettingsPoller {
+    client: SdkClient,
+    settings: Settings,
+    prefixes: Vec<String>,
+    listeners: Vec<Box<ChangeListener>>,
+    last: Mutex<BTreeMap<String, Value>>,
+}
+
+impl ClusterSettingsPoller {
+    pub fn new(client: SdkClient, settings: Settings) -> Self {
+        ClusterSettingsPoller {
+            client,
+            settings,
+            prefixes: Vec::new(),
+            listeners: Vec::new(),
+            last: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Also apply every setting under `prefix`, consumed or not.
+    pub fn watch_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes
+            .push(format!("{}.", prefix.into().trim_end_matches('.')));
+        self
+    }
+
+    /// Call `listener` with the changed keys after each poll that changed any.
+    pub fn on_change(mut self, listener: impl Fn(&[String]) + Send + Sync + 'static) -> Self {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    fn watched(&self, key: &str) -> bool {
+        self.prefixes
+            .iter()
+            .any(|prefix| key.starts_with(prefix.as_str()))
+    }
+
+    /// Poll once, returning the keys that changed.
+    pub async fn poll(&self) -> Result<Vec<String>, ExtensionError> {
+        let mut keys = self.settings.update_consumer_keys()?;
+        if keys.is_empty() && self.prefixes.is_empty() {
+            return Ok(Vec::new());
+        }
+        let response = self.client.cluster_settings(false).await?;
+
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = response
+            .defaults
+            .keys()
+            .chain(response.persistent.keys())
+            .chain(response.transient.keys());
+        keys.extend(
+            current
+                .chain(last.keys())
+                .filter(|key| self.watched(key))
+                .cloned(),
+        );
+        keys.sort();
+        keys.dedup();
+        let mut updates = Vec::new();
+        for key in keys {
+            match (response.get(&key), last.get(&key)) {
+                (Some(value), previous) if previous != Some(value) => {
+                    let setting = serde_json::from_value(value.clone())
+                        .unwrap_or_else(|_| SettingValue::String(value.to_string()));
+                    updates.push((key.clone(), Some(setting)));
+                    last.insert(key, value.clone());
+                }
+                (None, Some(_)) => {
+                    updates.push((key.clone(), None));
+                    last.remove(&key);
+                }
+                _ => {}
+            }
+        }
+        drop(last);
+
+        let changed = self.settings.apply_updates(updates)?;
+        if !changed.is_empty() {
+            for listener in &self.listeners {
+                listener(&changed);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Poll every `interval` until the returned task is aborted.
+    pub fn start(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.poll().await {
+                    Ok(changed) if !changed.is_empty() => {
+                        info!("Applied cluster settings update for {:?}", changed)
+                    }
+                    Ok(_) => debug!("No consumed cluster settings changed"),
+                    Err(e) => warn!("Failed to apply cluster settings update: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    #[test]
+    fn test_update_settings_notifies_consumers() {
+        let request = UpdateSettingsRequest::new()
+            .set("myext.batch_size", "250")
+            .reset("myext.mode");
+        let mut bytes = Vec::new();
+        request.serialize(&mut bytes).unwrap();
+        let decoded = UpdateSettingsRequest::deserialize(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, request);
+
+        let batch_size = Setting::int("myext.batch_size")
+            .default(100)
+            .min(1)
+            .dynamic();
+        let settings = Settings::new();
+        let seen = Arc::new(AtomicI64::new(0));
+        let observed = seen.clone();
+        settings
+            .add_update_consumer(&batch_size, move |size| {
+                observed.store(size, Ordering::SeqCst)
+            })
+            .unwrap();
+        assert!(settings
+            .add_update_consumer(&Setting::int("myext.static"), |_| {})
+            .is_err());
+
+        assert_eq!(
+            decoded.apply_to(&settings).unwrap(),
+            vec!["myext.batch_size".to_string()]
+        );
+        assert_eq!(seen.load(Ordering::SeqCst), 250);
+        assert_eq!(batch_size.get(&settings).unwrap(), 250);
+
+        let rejected = UpdateSettingsRequest::new()
+            .set("myext.batch_size", "0")
+            .set("myext.other", "x");
+        assert!(rejected.apply_to(&settings).is_err());
+        assert_eq!(settings.get("myext.other").unwrap(), None);
+        assert!(decoded.apply_to(&settings).unwrap().is_empty());
+    }
+}
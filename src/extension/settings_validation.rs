@@ -0,0 +1,229 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::extension::context::{SettingValue, Settings};
+use crate::extension::custom_settings::{CustomSettingDescriptor, SettingType};
+use crate::extension::setting::{SettingKind, SettingRegistry};
+use crate::extension::units::{ByteSizeValue, TimeValue};
+use crate::extension::ExtensionError;
+
+/// Checks every declared setting at startup and collects all problems,
+/// rather than failing on the first one at first access.
+///
+/// Keys are only reported as unknown within the namespaces the extension
+/// declares (`myext.` for `myext.batch_size`), since `Settings` also holds
+/// node environment and SDK settings.
+pub struct SettingsValidator {
+    registry: SettingRegistry,
+    descriptors: Vec<CustomSettingDescriptor>,
+}
+
+impl SettingsValidator {
+    pub fn new(registry: SettingRegistry, descriptors: Vec<CustomSettingDescriptor>) -> Self {
+        SettingsValidator {
+            registry,
+            descriptors,
+        }
+    }
+
+    fn declared(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .registry
+            .keys()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        keys.extend(self.descriptors.iter().map(|d| d.key.clone()));
+        keys
+    }
+
+    pub fn validate(&self, settings: &Settings) -> Result<SettingsReport, ExtensionError> {
+        let mut report = SettingsReport::default();
+        for e in self.registry.validate(settings) {
+            report.push_error(e);
+        }
+        let problems = &mut report.problems;
+
+        for descriptor in &self.descriptors {
+            if let Some(value) = settings.get(&descriptor.key)? {
+                if !conforms(&value, descriptor.setting_type) {
+                    problems.push(format!(
+                        "Setting '{}' is a {}, expected {}",
+                        descriptor.key,
+                        SettingType::of(&value).name(),
+                        descriptor.setting_type.name()
+                    ));
+                }
+            }
+        }
+
+        let declared = self.declared();
+        let namespaces: BTreeSet<&str> = declared
+            .iter()
+            .filter_map(|key| key.split_once('.').map(|(namespace, _)| namespace))
+            .collect();
+        for key in settings.keys()? {
+            let in_namespace = key
+                .split_once('.')
+                .is_some_and(|(namespace, _)| namespaces.contains(namespace));
+            if !in_namespace || declared.iter().any(|pattern| matches(pattern, &key)) {
+                continue;
+            }
+            match closest(&key, &declared) {
+                Some(suggestion) => problems.push(format!(
+                    "Unknown setting '{}', did you mean '{}'?",
+                    key, suggestion
+                )),
+                None => problems.push(format!("Unknown setting '{}'", key)),
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Problems found by `SettingsValidator`, one line each.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SettingsReport {
+    pub problems: Vec<String>,
+}
+
+impl SettingsReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Record `error`, without the "Configuration error" prefix.
+    pub fn push_error(&mut self, error: ExtensionError) {
+        self.problems.push(match error {
+            ExtensionError::ConfigurationError(message) => message,
+            other => other.to_string(),
+        });
+    }
+
+    pub fn into_result(self) -> Result<(), ExtensionError> {
+        if self.is_ok() {
+            Ok(())
+        } else {
+            Err(ExtensionError::configuration(self.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for SettingsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let noun = if self.problems.len() == 1 {
+            "problem"
+        } else {
+            "problems"
+        };
+        write!(f, "{} settings {}:", self.problems.len(), noun)?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `value` can be read as `setting_type`, parsing strings as typed
+/// settings do.
+fn conforms(value: &SettingValue, setting_type: SettingType) -> bool {
+    match setting_type {
+        SettingType::Integer => i64::from_value(value).is_some(),
+        SettingType::Float => f64::from_value(value).is_some(),
+        SettingType::Boolean => bool::from_value(value).is_some(),
+        SettingType::ByteSize => ByteSizeValue::from_value(value).is_some(),
+        SettingType::Time => TimeValue::from_value(value).is_some(),
+        other => SettingType::of(value) == other,
+    }
+}
+
+/// Whether `key` is `pattern`, where a `*` in an affix pattern matches one
+/// key part.
+fn matches(pattern: &str, key: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => key
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(suffix))
+            .is_some_and(|name| !name.is_empty() && !name.contains('.')),
+        None => pattern == key,
+    }
+}
+
+/// The declared key closest to `key`, if it is within a couple of edits.
+fn closest<'a>(key: &str, declared: &'a [String]) -> Option<&'a str> {
+    declared
+        .iter()
+        .filter(|candidate| !candidate.contains('*'))
+        .map(|candidate| (edit_distance(key, candidate), candidate.as_str()))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::setting::{AffixSetting, Setting};
+
+    #[test]
+    fn test_aggregated_report() {
+        let mut registry = SettingRegistry::new();
+        registry
+            .register(&Setting::int("myext.batch_size").default(100).min(1))
+            .unwrap();
+        registry
+            .register(&Setting::string("myext.mode").one_of(&["fast", "safe"]))
+            .unwrap();
+        registry
+            .register_affix(&AffixSetting::new("myext.targets", "url", Setting::string))
+            .unwrap();
+        let validator = SettingsValidator::new(
+            registry,
+            vec![CustomSettingDescriptor::new(
+                "myext.enabled",
+                SettingType::Boolean,
+            )],
+        );
+
+        let settings = Settings::new();
+        settings.set("myext.batch_size", "50").unwrap();
+        settings
+            .set("myext.targets.east.url", "http://east")
+            .unwrap();
+        settings.set("path.home", "/usr/share/opensearch").unwrap();
+        assert!(validator.validate(&settings).unwrap().is_ok());
+
+        settings.set("myext.batch_size", 0).unwrap();
+        settings.set("myext.mode", "slow").unwrap();
+        settings.set("myext.enabled", "maybe").unwrap();
+        settings.set("myext.batchsize", 10).unwrap();
+        settings.set("myext.colour", "red").unwrap();
+        let report = validator.validate(&settings).unwrap();
+        assert_eq!(report.problems.len(), 5);
+        assert!(report.problems.contains(
+            &"Unknown setting 'myext.batchsize', did you mean 'myext.batch_size'?".to_string()
+        ));
+        assert!(report
+            .problems
+            .contains(&"Setting 'myext.enabled' is a string, expected boolean".to_string()));
+
+        let message = report.into_result().unwrap_err().to_string();
+        assert!(message.contains("5 settings problems:\n  - "));
+        assert!(message.ends_with("\n  - Unknown setting 'myext.colour'"));
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::extension::context::Settings;
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+use crate::rest::Route;
+
+/// Threshold above which any request is logged as slow, unless its route
+/// or action has its own. Requests are not slow-logged while it is unset.
+pub fn slow_log_threshold() -> Setting<TimeValue> {
+    Setting::time("extension.slowlog.threshold").dynamic()
+}
+
+tokio::task_local! {
+    static TIMER: RequestTimer;
+}
+
+/// What handled a request: a REST route or a transport action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlowLogTarget {
+    Route(Route),
+    Action(String),
+}
+
+/// Where the time of a request went. `queue` runs from the request being
+/// read to its handler starting, `serialization` from the handler
+/// returning to the response being written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RequestTiming {
+    pub queue: Duration,
+    pub handler: Duration,
+    pub serialization: Duration,
+}
+
+impl RequestTiming {
+    pub fn total(&self) -> Duration {
+        self.queue + self.handler + self.serialization
+    }
+}
+
+#[derive(Debug)]
+struct TimerState {
+    received: Instant,
+    handler: Option<(Instant, Instant)>,
+    target: Option<SlowLogTarget>,
+}
+
+/// Collects the timing of one request as it moves through the dispatcher.
+#[derive(Debug, Clone)]
+pub struct RequestTimer(Arc<Mutex<TimerState>>);
+
+impl RequestTimer {
+    /// A timer for a request read just now.
+    pub fn start() -> Self {
+        RequestTimer(Arc::new(Mutex::new(TimerState {
+            received: Instant::now(),
+            handler: None,
+            target: None,
+        })))
+    }
+
+    /// Run `future` with this timer as the current one.
+    pub async fn run<F: Future>(&self, future: F) -> F::Output {
+        TIMER.scope(self.clone(), future).await
+    }
+
+    /// The handler that ran, if one was timed.
+    pub fn target(&self) -> Option<SlowLogTarget> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .target
+            .clone()
+    }
+
+    /// Timing of the request, taking now as the moment its response was
+    /// written. Without a timed handler everything counts as handler time.
+    pub fn finish(&self) -> RequestTiming {
+        let now = Instant::now();
+        let state = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.handler {
+            Some((start, end)) => RequestTiming {
+                queue: start.duration_since(state.received),
+                handler: end.duration_since(start),
+                serialization: now.duration_since(end),
+            },
+            None => RequestTiming {
+                handler: now.duration_since(state.received),
+                ..RequestTiming::default()
+            },
+        }
+    }
+}
+
+/// Time `handler` against the current `RequestTimer`, if any.
+pub async fn time_handler<F: Future>(target: SlowLogTarget, handler: F) -> F::Output {
+    let Ok(timer) = TIMER.try_with(Clone::clone) else {
+        return handler.await;
+    };
+    let start = Instant::now();
+    let output = handler.await;
+    let mut state = timer.0.lock().unwrap_or_else(PoisonError::into_inner);
+    state.handler = Some((start, Instant::now()));
+    state.target = Some(target);
+    output
+}
+
+/// Slow-log thresholds per REST route and transport action, with a default
+/// for the rest.
+#[derive(Debug, Clone, Default)]
+pub struct SlowLog {
+    threshold: Option<Duration>,
+    routes: HashMap<Route, Duration>,
+    actions: HashMap<String, Duration>,
+}
+
+impl SlowLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    pub fn route(mut self, route: Route, threshold: Duration) -> Self {
+        self.routes.insert(route, threshold);
+        self
+    }
+
+    pub fn action(mut self, action: impl Into<String>, threshold: Duration) -> Self {
+        self.actions.insert(action.into(), threshold);
+        self
+    }
+
+    /// Use the `slow_log_threshold` setting as the default threshold, when
+    /// it is set and none was configured in code.
+    pub fn with_settings(mut self, settings: &Settings) -> Result<Self, ExtensionError> {
+        if self.threshold.is_none() {
+            self.threshold = slow_log_threshold()
+                .get_opt(settings)?
+                .map(|t| t.as_duration());
+        }
+        Ok(self)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold.is_some() || !self.routes.is_empty() || !self.actions.is_empty()
+    }
+
+    /// Threshold of a request sent as transport `action` and handled by
+    /// `target`.
+    pub fn threshold_for(&self, action: &str, target: Option<&SlowLogTarget>) -> Option<Duration> {
+        let specific = match target {
+            Some(SlowLogTarget::Route(route)) => self.routes.get(route),
+            Some(SlowLogTarget::Action(name)) => self.actions.get(name),
+            None => None,
+        };
+        specific
+            .or_else(|| self.actions.get(action))
+            .copied()
+            .or(self.threshold)
+    }
+
+    /// Log the request if it took longer than its threshold. Returns whether
+    /// it did.
+    pub fn check(&self, action: &str, request_id: u64, timer: &RequestTimer) -> bool {
+        let target = timer.target();
+        let Some(threshold) = self.threshold_for(action, target.as_ref()) else {
+            return false;
+        };
+        let timing = timer.finish();
+        if timing.total() < threshold {
+            return false;
+        }
+        let handler = match &target {
+            Some(SlowLogTarget::Route(route)) => route.to_string(),
+            Some(SlowLogTarget::Action(name)) => name.clone(),
+            None => action.to_string(),
+        };
+        warn!(
+            target: "slowlog",
+            request_id,
+            action,
+            handler = %handler,
+            total_ms = timing.total().as_secs_f64() * 1000.0,
+            queue_ms = timing.queue.as_secs_f64() * 1000.0,
+            handler_ms = timing.handler.as_secs_f64() * 1000.0,
+            serialization_ms = timing.serialization.as_secs_f64() * 1000.0,
+            threshold_ms = threshold.as_secs_f64() * 1000.0,
+            "Slow request"
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::Method;
+
+    #[tokio::test]
+    async fn test_thresholds_and_timing_breakdown() {
+        let search = Route::new(Method::Get, "/_search");
+        let slow_log = SlowLog::new()
+            .threshold(Duration::from_secs(60))
+            .route(search.clone(), Duration::from_millis(5))
+            .action("cluster:admin/hello/echo", Duration::ZERO);
+
+        let timer = RequestTimer::start();
+        timer
+            .run(time_handler(
+                SlowLogTarget::Route(search.clone()),
+                tokio::time::sleep(Duration::from_millis(10)),
+            ))
+            .await;
+        let timing = timer.finish();
+        assert!(timing.handler >= Duration::from_millis(10));
+        assert!(timing.total() >= timing.handler);
+        assert!(slow_log.check(
+            "internal:extensions/restexecuteonextensiontaction",
+            7,
+            &timer
+        ));
+
+        let fast = RequestTimer::start();
+        fast.run(time_handler(
+            SlowLogTarget::Route(Route::new(Method::Get, "/")),
+            async {},
+        ))
+        .await;
+        assert!(!slow_log.check(
+            "internal:extensions/restexecuteonextensiontaction",
+            8,
+            &fast
+        ));
+
+        let action = SlowLogTarget::Action("cluster:admin/hello/echo".to_string());
+        assert_eq!(
+            slow_log.threshold_for("internal:extensions/handle-transportaction", Some(&action)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(time_handler(action, async { 1 }).await, 1);
+
+        assert!(!SlowLog::new().is_enabled());
+        let settings = Settings::new();
+        settings
+            .set("extension.slowlog.threshold", "250ms")
+            .unwrap();
+        let configured = SlowLog::new().with_settings(&settings).unwrap();
+        assert_eq!(
+            configured.threshold_for("any", None),
+            Some(Duration::from_millis(250))
+        );
+    }
+}
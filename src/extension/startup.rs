@@ -0,0 +1,169 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::extension::context::Settings;
+use crate::extension::dependency::request_dependency_info;
+use crate::extension::resilience::RetryPolicy;
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+use crate::transport::TransportClient;
+
+/// Whether the runner waits for OpenSearch and the declared dependencies
+/// before initializing the extension.
+pub fn wait_for_dependencies() -> Setting<bool> {
+    Setting::bool("extension.startup.wait_for_dependencies").default(false)
+}
+
+/// How long the runner waits before giving up on startup.
+pub fn startup_max_wait() -> Setting<TimeValue> {
+    Setting::time("extension.startup.max_wait")
+        .default(TimeValue::minutes(5))
+        .min(TimeValue::seconds(1))
+}
+
+/// Probes OpenSearch, and then each dependency extension through the
+/// cluster, until all of them answer, so that an extension started next to
+/// its cluster does not register before the cluster is up.
+pub struct StartupWait {
+    client: Arc<TransportClient>,
+    dependencies: Vec<String>,
+    max_wait: Duration,
+    backoff: RetryPolicy,
+}
+
+impl StartupWait {
+    pub fn new(client: Arc<TransportClient>) -> Self {
+        StartupWait {
+            client,
+            dependencies: Vec::new(),
+            max_wait: Duration::from_secs(300),
+            backoff: RetryPolicy {
+                max_attempts: u32::MAX,
+                initial_delay: Duration::from_millis(500),
+                max_delay: Duration::from_secs(15),
+                ..RetryPolicy::default()
+            },
+        }
+    }
+
+    /// A wait configured by the `startup_*` settings, or `None` when
+    /// `wait_for_dependencies` is off.
+    pub fn from_settings(
+        client: Arc<TransportClient>,
+        settings: &Settings,
+    ) -> Result<Option<Self>, ExtensionError> {
+        if !wait_for_dependencies().get(settings)? {
+            return Ok(None);
+        }
+        Ok(Some(
+            Self::new(client).max_wait(startup_max_wait().get(settings)?.as_duration()),
+        ))
+    }
+
+    /// Unique ids of the extensions that must be registered with the cluster.
+    pub fn dependencies(mut self, dependencies: Vec<String>) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Delays between probes.
+    pub fn backoff(mut self, backoff: RetryPolicy) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    async fn probe(&self) -> Result<(), ExtensionError> {
+        self.client.connect().await?;
+        for dependency in &self.dependencies {
+            request_dependency_info(&self.client, dependency)
+                .await
+                .map_err(|e| {
+                    ExtensionError::dependency(format!(
+                        "Dependency '{}' is not available: {}",
+                        dependency, e
+                    ))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Probe with backoff until everything answers, failing with the last
+    /// error once `max_wait` has passed.
+    pub async fn wait(&self) -> Result<(), ExtensionError> {
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self.probe().await {
+                Ok(()) => {
+                    info!(
+                        "OpenSearch and {} dependencies ready after {:?}",
+                        self.dependencies.len(),
+                        started.elapsed()
+                    );
+                    return Ok(());
+                }
+                Err(e) => e,
+            };
+            let remaining = self.max_wait.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                return Err(ExtensionError::timeout(format!(
+                    "Not ready to start after {:?}: {}",
+                    self.max_wait, error
+                )));
+            }
+            let delay = self.backoff.retry_delay(attempt, &error).min(remaining);
+            warn!(
+                "Waiting {:?} for OpenSearch and dependencies: {}",
+                delay, error
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_waits_until_cluster_is_up() {
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = closed.local_addr().unwrap();
+        drop(closed);
+        let backoff = RetryPolicy {
+            initial_delay: Duration::from_millis(5),
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+        let startup = StartupWait::new(Arc::new(TransportClient::new("127.0.0.1", addr.port())))
+            .max_wait(Duration::from_secs(5))
+            .backoff(backoff.clone());
+
+        let cluster = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+            while let Ok((stream, _)) = listener.accept().await {
+                drop(stream);
+            }
+        });
+        startup.wait().await.unwrap();
+        cluster.abort();
+
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = closed.local_addr().unwrap().port();
+        drop(closed);
+        let down = StartupWait::new(Arc::new(TransportClient::new("127.0.0.1", port)))
+            .max_wait(Duration::from_millis(20))
+            .backoff(backoff);
+        let error = down.wait().await.unwrap_err();
+        assert!(matches!(error, ExtensionError::TimeoutError(_)));
+    }
+}
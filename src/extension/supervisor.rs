@@ -0,0 +1,244 @@
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::extension::context::Settings;
+use crate::extension::health::{HealthService, HealthStatus};
+use crate::extension::lifecycle::{ExtensionState, LifecycleManager};
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+
+/// Exit code of a process that gave up on an unhealthy extension, so that
+/// orchestrators can tell it apart from a crash or a clean stop.
+pub const UNHEALTHY_EXIT_CODE: i32 = 75;
+
+/// What to do once the extension stays unhealthy: `none`, `restart` or `exit`.
+pub fn supervisor_action() -> Setting<String> {
+    Setting::string("extension.supervisor.action")
+        .default("none".to_string())
+        .one_of(&["none", "restart", "exit"])
+}
+
+/// How long the overall health must stay `Unhealthy` before acting.
+pub fn unhealthy_after() -> Setting<TimeValue> {
+    Setting::time("extension.supervisor.unhealthy_after")
+        .default(TimeValue::seconds(60))
+        .min(TimeValue::seconds(1))
+}
+
+/// Restarts after which the supervisor gives up and exits instead.
+pub fn max_restarts() -> Setting<i64> {
+    Setting::int("extension.supervisor.max_restarts")
+        .default(3)
+        .min(0)
+}
+
+/// Minimum time between two restarts.
+pub fn restart_cooldown() -> Setting<TimeValue> {
+    Setting::time("extension.supervisor.restart_cooldown").default(TimeValue::minutes(5))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisorAction {
+    /// Shut the extension down and initialize it again in place.
+    Restart,
+    /// Stop the extension and exit with `UNHEALTHY_EXIT_CODE`.
+    Exit,
+}
+
+impl FromStr for SupervisorAction {
+    type Err = ExtensionError;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action.to_ascii_lowercase().as_str() {
+            "restart" => Ok(SupervisorAction::Restart),
+            "exit" => Ok(SupervisorAction::Exit),
+            other => Err(ExtensionError::configuration(format!(
+                "Unknown supervisor action '{}', expected restart or exit",
+                other
+            ))),
+        }
+    }
+}
+
+/// Watches the overall status of a `HealthService` while the extension
+/// runs. Once it has been `Unhealthy` for `unhealthy_after`, the extension
+/// is restarted, at most `max_restarts` times and no sooner than `cooldown`
+/// after the previous restart, or the supervisor gives up so the process
+/// can exit and let its orchestrator replace it.
+pub struct HealthSupervisor {
+    health: HealthService,
+    lifecycle: Arc<LifecycleManager>,
+    action: SupervisorAction,
+    interval: Duration,
+    unhealthy_after: Duration,
+    max_restarts: u32,
+    cooldown: Duration,
+}
+
+impl HealthSupervisor {
+    pub fn new(
+        health: HealthService,
+        lifecycle: Arc<LifecycleManager>,
+        action: SupervisorAction,
+    ) -> Self {
+        HealthSupervisor {
+            health,
+            lifecycle,
+            action,
+            interval: Duration::from_secs(5),
+            unhealthy_after: Duration::from_secs(60),
+            max_restarts: 3,
+            cooldown: Duration::from_secs(300),
+        }
+    }
+
+    /// A supervisor configured by the `supervisor_*` settings, or `None`
+    /// when the action is `none`.
+    pub fn from_settings(
+        health: HealthService,
+        lifecycle: Arc<LifecycleManager>,
+        settings: &Settings,
+    ) -> Result<Option<Self>, ExtensionError> {
+        let action = supervisor_action().get(settings)?;
+        if action == "none" {
+            return Ok(None);
+        }
+        let unhealthy_after = unhealthy_after().get(settings)?.as_duration();
+        Ok(Some(
+            Self::new(health, lifecycle, action.parse()?)
+                .interval((unhealthy_after / 4).max(Duration::from_millis(250)))
+                .unhealthy_after(unhealthy_after)
+                .max_restarts(max_restarts().get(settings)? as u32)
+                .cooldown(restart_cooldown().get(settings)?.as_duration()),
+        ))
+    }
+
+    /// How often the health status is checked.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn unhealthy_after(mut self, unhealthy_after: Duration) -> Self {
+        self.unhealthy_after = unhealthy_after;
+        self
+    }
+
+    pub fn max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Check the health every `interval` and call `restart` when the policy
+    /// says so. Returns `Ok` when the extension stops, and
+    /// `ExtensionError::Unhealthy` once the supervisor gives up on it.
+    pub async fn run<F, Fut>(&self, mut restart: F) -> Result<(), ExtensionError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), ExtensionError>>,
+    {
+        let mut ticker = tokio::time::interval(self.interval);
+        let mut unhealthy_since = None;
+        let mut last_restart: Option<Instant> = None;
+        let mut restarts = 0;
+        loop {
+            ticker.tick().await;
+            let state = self.lifecycle.current_state().await;
+            if state.is_terminal() || state == ExtensionState::Stopping {
+                return Ok(());
+            }
+            if !state.is_running()
+                || self.health.get_overall_status().await != HealthStatus::Unhealthy
+            {
+                unhealthy_since = None;
+                continue;
+            }
+            let since = *unhealthy_since.get_or_insert_with(Instant::now);
+            if since.elapsed() < self.unhealthy_after {
+                continue;
+            }
+            if self.action == SupervisorAction::Exit {
+                return Err(ExtensionError::unhealthy(format!(
+                    "for over {:?}",
+                    self.unhealthy_after
+                )));
+            }
+            if restarts >= self.max_restarts {
+                return Err(ExtensionError::unhealthy(format!(
+                    "still after {} restarts",
+                    restarts
+                )));
+            }
+            if last_restart.is_some_and(|at| at.elapsed() < self.cooldown) {
+                continue;
+            }
+
+            restarts += 1;
+            warn!(
+                "Extension unhealthy for over {:?}, restarting it ({} of {})",
+                self.unhealthy_after, restarts, self.max_restarts
+            );
+            restart()
+                .await
+                .map_err(|e| ExtensionError::unhealthy(format!("restart failed: {}", e)))?;
+            info!("Extension restarted");
+            last_restart = Some(Instant::now());
+            unhealthy_since = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_restarts_until_limit_then_gives_up() {
+        let lifecycle = Arc::new(LifecycleManager::new());
+        for state in [
+            ExtensionState::Initializing,
+            ExtensionState::Initialized,
+            ExtensionState::Running,
+        ] {
+            lifecycle.transition_to(state).await.unwrap();
+        }
+        let health = HealthService::new();
+        health.register_check("index").await;
+        health
+            .update_check("index", HealthStatus::Unhealthy, Some("red".to_string()))
+            .await
+            .unwrap();
+
+        let supervisor = HealthSupervisor::new(health, lifecycle, SupervisorAction::Restart)
+            .interval(Duration::from_millis(2))
+            .unhealthy_after(Duration::from_millis(10))
+            .max_restarts(2)
+            .cooldown(Duration::ZERO);
+        let restarts = AtomicU32::new(0);
+        let outcome = tokio::time::timeout(
+            Duration::from_secs(5),
+            supervisor.run(|| async {
+                restarts.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(restarts.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            outcome.unwrap_err().to_string(),
+            "Extension unhealthy: still after 2 restarts"
+        );
+    }
+}
@@ -1,26 +1,85 @@
+use crate::extension::{
+    context::Settings, custom_settings::CustomSettingDescriptor, setting::SettingRegistry,
+    ExtensionContext, ExtensionDependency, ExtensionError, InitStateProvider,
+};
+use crate::rest::RestHandler;
+use crate::transport::action::TransportAction;
 use async_trait::async_trait;
-use crate::extension::{ExtensionContext, ExtensionDependency, ExtensionError};
+use std::sync::Arc;
 
 #[async_trait]
 pub trait Extension: Send + Sync + 'static {
     fn name(&self) -> &str;
-    
+
     fn unique_id(&self) -> &str;
-    
+
     fn version(&self) -> &str;
-    
+
     fn opensearch_version(&self) -> &str;
-    
+
     fn java_version(&self) -> &str {
         "11"
     }
-    
+
     fn dependencies(&self) -> Vec<ExtensionDependency> {
         vec![]
     }
-    
+
+    /// Settings advertised to OpenSearch at startup
+    fn custom_settings(&self) -> Vec<CustomSettingDescriptor> {
+        vec![]
+    }
+
+    /// Typed settings, advertised alongside `custom_settings` and validated once node settings are loaded
+    fn setting_registry(&self) -> SettingRegistry {
+        SettingRegistry::new()
+    }
+
+    /// Named actions callable from OpenSearch via `internal:extensions/handle-transportaction`
+    fn transport_actions(&self) -> Vec<Arc<dyn TransportAction>> {
+        vec![]
+    }
+
+    /// REST endpoints registered with OpenSearch and served via `internal:extensions/restexecuteonextensiontaction`
+    fn rest_handlers(&self) -> Vec<Arc<dyn RestHandler>> {
+        vec![]
+    }
+
+    /// Named sections of custom state sent to OpenSearch in the init response
+    fn init_state(&self) -> Vec<Arc<dyn InitStateProvider>> {
+        vec![]
+    }
+
     async fn initialize(&mut self, context: &ExtensionContext) -> Result<(), ExtensionError>;
-    
+
+    /// Called once registered, before the extension starts serving requests.
+    /// An error fails the start.
+    async fn on_pre_start(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
+        Ok(())
+    }
+
+    /// Called once the extension is serving requests. Errors are logged.
+    async fn on_post_start(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
+        Ok(())
+    }
+
+    /// Called when shutdown begins, before `shutdown`. Errors are logged.
+    async fn on_pre_stop(&mut self) -> Result<(), ExtensionError> {
+        Ok(())
+    }
+
+    /// Called after the extension failed with `error`
+    async fn on_failed(&mut self, _error: &ExtensionError) {}
+
+    /// Called after a settings reload changed the `changed` keys of `settings`
+    async fn on_settings_change(
+        &mut self,
+        _settings: &Settings,
+        _changed: &[String],
+    ) -> Result<(), ExtensionError> {
+        Ok(())
+    }
+
     async fn shutdown(&mut self) -> Result<(), ExtensionError>;
 }
 
@@ -33,15 +92,23 @@ mod tests {
 
     #[async_trait]
     impl Extension for TestExtension {
-        fn name(&self) -> &str { "test" }
-        fn unique_id(&self) -> &str { "test-id" }
-        fn version(&self) -> &str { "1.0.0" }
-        fn opensearch_version(&self) -> &str { "3.0.0" }
-        
+        fn name(&self) -> &str {
+            "test"
+        }
+        fn unique_id(&self) -> &str {
+            "test-id"
+        }
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+        fn opensearch_version(&self) -> &str {
+            "3.0.0"
+        }
+
         async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
             Ok(())
         }
-        
+
         async fn shutdown(&mut self) -> Result<(), ExtensionError> {
             Ok(())
         }
@@ -58,4 +125,11 @@ mod tests {
         let ext = TestExtension;
         assert_eq!(ext.dependencies(), vec![]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_default_custom_settings() {
+        let ext = TestExtension;
+        assert!(ext.custom_settings().is_empty());
+        assert!(ext.setting_registry().is_empty());
+    }
+}
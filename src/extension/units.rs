@@ -0,0 +1,263 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::extension::ExtensionError;
+
+const BYTE_UNITS: &[(&str, u64)] = &[
+    ("pb", 1 << 50),
+    ("tb", 1 << 40),
+    ("gb", 1 << 30),
+    ("mb", 1 << 20),
+    ("kb", 1 << 10),
+    ("b", 1),
+];
+
+const TIME_UNITS: &[(&str, u64)] = &[
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("micros", 1_000),
+    ("nanos", 1),
+];
+
+/// Split `"512mb"` into `("512", "mb")`.
+fn split_unit(value: &str) -> (&str, &str) {
+    let value = value.trim();
+    let at = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    (value[..at].trim(), value[at..].trim())
+}
+
+/// Format `amount` in the largest unit that represents it exactly.
+fn format_units(f: &mut fmt::Formatter<'_>, amount: u64, units: &[(&str, u64)]) -> fmt::Result {
+    let (unit, size) = units
+        .iter()
+        .find(|(_, size)| amount != 0 && amount.is_multiple_of(*size))
+        .unwrap_or(&units[units.len() - 1]);
+    write!(f, "{}{}", amount / size, unit)
+}
+
+/// A size in bytes, written OpenSearch-style as `"512mb"` or `"1.5gb"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ByteSizeValue(u64);
+
+impl ByteSizeValue {
+    pub const fn bytes(bytes: u64) -> Self {
+        ByteSizeValue(bytes)
+    }
+
+    pub const fn kb(kb: u64) -> Self {
+        ByteSizeValue(kb << 10)
+    }
+
+    pub const fn mb(mb: u64) -> Self {
+        ByteSizeValue(mb << 20)
+    }
+
+    pub const fn gb(gb: u64) -> Self {
+        ByteSizeValue(gb << 30)
+    }
+
+    pub fn as_bytes(&self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for ByteSizeValue {
+    type Err = ExtensionError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            ExtensionError::configuration(format!(
+                "Invalid byte size '{}', expected a number with a unit such as 512mb",
+                value
+            ))
+        };
+        let (number, unit) = split_unit(value);
+        let unit = unit.to_ascii_lowercase();
+        let size = match unit.as_str() {
+            "" if number.parse::<u64>() == Ok(0) => Some(1),
+            "k" | "m" | "g" | "t" | "p" => BYTE_UNITS
+                .iter()
+                .find(|(u, _)| u.starts_with(&unit))
+                .map(|(_, s)| *s),
+            unit => BYTE_UNITS.iter().find(|(u, _)| *u == unit).map(|(_, s)| *s),
+        }
+        .ok_or_else(invalid)?;
+
+        match number.parse::<u64>() {
+            Ok(n) => n.checked_mul(size).map(ByteSizeValue).ok_or_else(invalid),
+            Err(_) => {
+                let n: f64 = number.parse().map_err(|_| invalid())?;
+                let bytes = n * size as f64;
+                if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+                    return Err(invalid());
+                }
+                Ok(ByteSizeValue(bytes as u64))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ByteSizeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_units(f, self.0, BYTE_UNITS)
+    }
+}
+
+impl Serialize for ByteSizeValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSizeValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A duration written OpenSearch-style as `"30s"` or `"500ms"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct TimeValue(Duration);
+
+impl TimeValue {
+    pub const fn millis(millis: u64) -> Self {
+        TimeValue(Duration::from_millis(millis))
+    }
+
+    pub const fn seconds(seconds: u64) -> Self {
+        TimeValue(Duration::from_secs(seconds))
+    }
+
+    pub const fn minutes(minutes: u64) -> Self {
+        TimeValue(Duration::from_secs(minutes * 60))
+    }
+
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl From<Duration> for TimeValue {
+    fn from(duration: Duration) -> Self {
+        TimeValue(duration)
+    }
+}
+
+impl From<TimeValue> for Duration {
+    fn from(value: TimeValue) -> Self {
+        value.0
+    }
+}
+
+impl FromStr for TimeValue {
+    type Err = ExtensionError;
+
+    /// Fractional values such as `1.5h` are rejected, as in OpenSearch.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            ExtensionError::configuration(format!(
+                "Invalid time value '{}', expected a whole number with a unit such as 30s",
+                value
+            ))
+        };
+        let (number, unit) = split_unit(value);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let unit = unit.to_ascii_lowercase();
+        let nanos = match TIME_UNITS.iter().find(|(u, _)| *u == unit) {
+            Some((_, size)) => number.checked_mul(*size).ok_or_else(invalid)?,
+            None if unit.is_empty() && number == 0 => 0,
+            None => return Err(invalid()),
+        };
+        Ok(TimeValue(Duration::from_nanos(nanos)))
+    }
+}
+
+impl fmt::Display for TimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nanos = self.0.as_nanos().min(u64::MAX as u128) as u64;
+        format_units(f, nanos, TIME_UNITS)
+    }
+}
+
+impl Serialize for TimeValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_size_value() {
+        assert_eq!(
+            "512mb".parse::<ByteSizeValue>().unwrap(),
+            ByteSizeValue::mb(512)
+        );
+        assert_eq!(
+            "1.5GB".parse::<ByteSizeValue>().unwrap().as_bytes(),
+            3 << 29
+        );
+        assert_eq!(
+            "10k".parse::<ByteSizeValue>().unwrap(),
+            ByteSizeValue::kb(10)
+        );
+        assert_eq!(
+            "0".parse::<ByteSizeValue>().unwrap(),
+            ByteSizeValue::bytes(0)
+        );
+        assert!("512".parse::<ByteSizeValue>().is_err());
+        assert!("12xb".parse::<ByteSizeValue>().is_err());
+
+        assert_eq!(ByteSizeValue::mb(512).to_string(), "512mb");
+        assert_eq!(ByteSizeValue::bytes(1500).to_string(), "1500b");
+        assert_eq!(
+            serde_json::to_string(&ByteSizeValue::gb(2)).unwrap(),
+            "\"2gb\""
+        );
+    }
+
+    #[test]
+    fn test_time_value() {
+        assert_eq!(
+            "30s".parse::<TimeValue>().unwrap().as_duration(),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            "500ms".parse::<TimeValue>().unwrap(),
+            TimeValue::millis(500)
+        );
+        assert_eq!(
+            "2d".parse::<TimeValue>().unwrap(),
+            TimeValue::minutes(2 * 24 * 60)
+        );
+        assert!("1.5h".parse::<TimeValue>().is_err());
+        assert!("30".parse::<TimeValue>().is_err());
+
+        assert_eq!(TimeValue::millis(90_000).to_string(), "90s");
+        assert_eq!(TimeValue::minutes(120).to_string(), "2h");
+        assert_eq!(
+            serde_json::from_str::<TimeValue>("\"1m\"").unwrap(),
+            TimeValue::seconds(60)
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use crate::geo::{BoundingBox, GeoPoint};
+
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Longest geohash OpenSearch supports.
+pub const MAX_PRECISION: usize = 12;
+
+/// Encode a point as a geohash of `precision` characters (clamped to 1..=12).
+pub fn encode(point: &GeoPoint, precision: usize) -> String {
+    let precision = precision.clamp(1, MAX_PRECISION);
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let mut even = true;
+
+    while hash.len() < precision {
+        let mut index = 0;
+        for _ in 0..5 {
+            let (range, value): (&mut (f64, f64), f64) = if even {
+                (&mut lon_range, point.lon)
+            } else {
+                (&mut lat_range, point.lat)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            index <<= 1;
+            if value >= mid {
+                index |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even = !even;
+        }
+        hash.push(BASE32[index] as char);
+    }
+
+    hash
+}
+
+/// Decode a geohash into its cell's center and bounds.
+pub fn decode(hash: &str) -> Result<(GeoPoint, BoundingBox), String> {
+    if hash.is_empty() || hash.len() > MAX_PRECISION {
+        return Err(format!(
+            "geohash '{}' must have 1 to {} characters",
+            hash, MAX_PRECISION
+        ));
+    }
+
+    let (mut lat_range, mut lon_range) = ((-90.0_f64, 90.0_f64), (-180.0_f64, 180.0_f64));
+    let mut even = true;
+
+    for c in hash.chars() {
+        let index = BASE32
+            .iter()
+            .position(|&b| b as char == c.to_ascii_lowercase())
+            .ok_or_else(|| format!("invalid character '{}' in geohash '{}'", c, hash))?;
+
+        for bit in (0..5).rev() {
+            let range = if even { &mut lon_range } else { &mut lat_range };
+            let mid = (range.0 + range.1) / 2.0;
+            if (index >> bit) & 1 == 1 {
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even = !even;
+        }
+    }
+
+    let center = GeoPoint::new(
+        (lat_range.0 + lat_range.1) / 2.0,
+        (lon_range.0 + lon_range.1) / 2.0,
+    );
+    let bounds = BoundingBox::new(
+        GeoPoint::new(lat_range.1, lon_range.0),
+        GeoPoint::new(lat_range.0, lon_range.1),
+    );
+    Ok((center, bounds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geohash_round_trip() {
+        let point = GeoPoint::new(57.64911, 10.40744);
+        assert_eq!(encode(&point, 11), "u4pruydqqvj");
+
+        let (center, bounds) = decode("u4pruydqqvj").unwrap();
+        assert!(bounds.contains(&point));
+        assert!(center.distance_to(&point) < 1.0);
+
+        assert!(decode("u4pa").is_err());
+        assert!(decode("").is_err());
+    }
+}
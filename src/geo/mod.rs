@@ -0,0 +1,10 @@
+//! Geospatial types matching OpenSearch's `geo_point` and `geo_shape` formats.
+
+pub mod geohash;
+pub mod point;
+pub mod query;
+pub mod shape;
+
+pub use point::{BoundingBox, GeoPoint, EARTH_MEAN_RADIUS};
+pub use query::SpatialRelation;
+pub use shape::GeoShape;
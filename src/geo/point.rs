@@ -0,0 +1,233 @@
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::geo::geohash;
+
+/// Mean earth radius in meters, as used by OpenSearch's `GeoUtils`.
+pub const EARTH_MEAN_RADIUS: f64 = 6_371_008.771_4;
+
+/// A `geo_point`. Serializes as `{"lat": .., "lon": ..}` and deserializes from
+/// any format OpenSearch accepts: an object, a `[lon, lat]` array, a
+/// `"lat,lon"` string, a geohash or a WKT `POINT (lon lat)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        GeoPoint { lat, lon }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lon)
+    }
+
+    /// Great-circle distance in meters (haversine).
+    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_MEAN_RADIUS * a.sqrt().asin()
+    }
+
+    pub fn geohash(&self, precision: usize) -> String {
+        geohash::encode(self, precision)
+    }
+
+    pub fn to_wkt(&self) -> String {
+        format!("POINT ({} {})", self.lon, self.lat)
+    }
+
+    fn from_value(value: &Value) -> Result<Self, String> {
+        let point = match value {
+            Value::Object(object) => {
+                let coordinate = |name: &str| {
+                    object
+                        .get(name)
+                        .and_then(Value::as_f64)
+                        .ok_or_else(|| format!("geo_point object requires a numeric [{}]", name))
+                };
+                GeoPoint::new(coordinate("lat")?, coordinate("lon")?)
+            }
+            Value::Array(values) => match values.as_slice() {
+                [lon, lat] | [lon, lat, _] => GeoPoint::new(
+                    lat.as_f64().ok_or("geo_point latitude must be a number")?,
+                    lon.as_f64().ok_or("geo_point longitude must be a number")?,
+                ),
+                _ => return Err("geo_point array must be [lon, lat]".to_string()),
+            },
+            Value::String(s) => s.parse()?,
+            other => return Err(format!("unsupported geo_point value {}", other)),
+        };
+
+        if point.is_valid() {
+            Ok(point)
+        } else {
+            Err(format!("geo_point {} is out of range", point))
+        }
+    }
+}
+
+impl FromStr for GeoPoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let upper = s.to_ascii_uppercase();
+
+        if let Some(rest) = upper.strip_prefix("POINT") {
+            let coordinates: Vec<f64> = rest
+                .trim()
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("invalid WKT point '{}'", s))?;
+            return match coordinates.as_slice() {
+                [lon, lat] | [lon, lat, _] => Ok(GeoPoint::new(*lat, *lon)),
+                _ => Err(format!("invalid WKT point '{}'", s)),
+            };
+        }
+
+        match s.split_once(',') {
+            Some((lat, lon)) => {
+                let lat = lat
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid latitude in '{}'", s))?;
+                let lon = lon
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid longitude in '{}'", s))?;
+                Ok(GeoPoint::new(lat, lon))
+            }
+            None => geohash::decode(s).map(|(point, _)| point),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for GeoPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        GeoPoint::from_value(&value).map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for GeoPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.lat, self.lon)
+    }
+}
+
+/// A `geo_bounding_box` given by its top-left and bottom-right corners.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub top_left: GeoPoint,
+    pub bottom_right: GeoPoint,
+}
+
+impl BoundingBox {
+    pub fn new(top_left: GeoPoint, bottom_right: GeoPoint) -> Self {
+        BoundingBox {
+            top_left,
+            bottom_right,
+        }
+    }
+
+    /// Smallest box containing every point within `distance` meters of `center`.
+    pub fn around(center: &GeoPoint, distance: f64) -> Self {
+        let d_lat = (distance / EARTH_MEAN_RADIUS).to_degrees();
+        let top = (center.lat + d_lat).min(90.0);
+        let bottom = (center.lat - d_lat).max(-90.0);
+
+        let cos_lat = center.lat.to_radians().cos();
+        let (left, right) = if top >= 90.0 || bottom <= -90.0 || cos_lat <= f64::EPSILON {
+            (-180.0, 180.0)
+        } else {
+            let d_lon = d_lat / cos_lat;
+            (wrap_lon(center.lon - d_lon), wrap_lon(center.lon + d_lon))
+        };
+
+        BoundingBox::new(GeoPoint::new(top, left), GeoPoint::new(bottom, right))
+    }
+
+    /// Whether the box crosses the antimeridian.
+    pub fn crosses_dateline(&self) -> bool {
+        self.top_left.lon > self.bottom_right.lon
+    }
+
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        let lat_ok = point.lat <= self.top_left.lat && point.lat >= self.bottom_right.lat;
+        let lon_ok = if self.crosses_dateline() {
+            point.lon >= self.top_left.lon || point.lon <= self.bottom_right.lon
+        } else {
+            point.lon >= self.top_left.lon && point.lon <= self.bottom_right.lon
+        };
+        lat_ok && lon_ok
+    }
+}
+
+fn wrap_lon(lon: f64) -> f64 {
+    if lon > 180.0 {
+        lon - 360.0
+    } else if lon < -180.0 {
+        lon + 360.0
+    } else {
+        lon
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_geo_point_formats() {
+        let expected = GeoPoint::new(41.12, -71.34);
+        for value in [
+            json!({"lat": 41.12, "lon": -71.34}),
+            json!([-71.34, 41.12]),
+            json!("41.12,-71.34"),
+            json!("POINT (-71.34 41.12)"),
+        ] {
+            assert_eq!(serde_json::from_value::<GeoPoint>(value).unwrap(), expected);
+        }
+
+        let from_hash: GeoPoint = serde_json::from_value(json!("drm3btev3e86")).unwrap();
+        assert!(from_hash.distance_to(&expected) < 1.0);
+        assert!(serde_json::from_value::<GeoPoint>(json!({"lat": 91.0, "lon": 0.0})).is_err());
+        assert_eq!(
+            serde_json::to_value(expected).unwrap(),
+            json!({"lat": 41.12, "lon": -71.34})
+        );
+    }
+
+    #[test]
+    fn test_distance_and_bounding_box() {
+        let pune = GeoPoint::new(18.5204, 73.8567);
+        let mumbai = GeoPoint::new(19.0760, 72.8777);
+        let distance = pune.distance_to(&mumbai);
+        assert!(
+            (distance - 119_800.0).abs() < 1_000.0,
+            "distance was {}",
+            distance
+        );
+
+        let bbox = BoundingBox::around(&pune, 150_000.0);
+        assert!(bbox.contains(&mumbai));
+        assert!(!bbox.contains(&GeoPoint::new(28.6139, 77.2090)));
+
+        let dateline = BoundingBox::new(GeoPoint::new(10.0, 170.0), GeoPoint::new(-10.0, -170.0));
+        assert!(dateline.contains(&GeoPoint::new(0.0, 179.0)));
+        assert!(!dateline.contains(&GeoPoint::new(0.0, 0.0)));
+    }
+}
@@ -0,0 +1,94 @@
+use serde_json::{json, Value};
+
+use crate::geo::{BoundingBox, GeoPoint, GeoShape};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpatialRelation {
+    #[default]
+    Intersects,
+    Disjoint,
+    Within,
+    Contains,
+}
+
+impl SpatialRelation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpatialRelation::Intersects => "intersects",
+            SpatialRelation::Disjoint => "disjoint",
+            SpatialRelation::Within => "within",
+            SpatialRelation::Contains => "contains",
+        }
+    }
+}
+
+/// `geo_distance` query; `distance` uses OpenSearch units such as `"12km"`.
+pub fn geo_distance(field: &str, center: &GeoPoint, distance: &str) -> Value {
+    json!({
+        "geo_distance": {
+            "distance": distance,
+            field: center,
+        }
+    })
+}
+
+/// `geo_distance` query with the distance given in meters.
+pub fn geo_distance_meters(field: &str, center: &GeoPoint, meters: f64) -> Value {
+    geo_distance(field, center, &format!("{}m", meters))
+}
+
+pub fn geo_bounding_box(field: &str, bbox: &BoundingBox) -> Value {
+    json!({
+        "geo_bounding_box": {
+            field: bbox,
+        }
+    })
+}
+
+pub fn geo_shape(field: &str, shape: &GeoShape, relation: SpatialRelation) -> Value {
+    json!({
+        "geo_shape": {
+            field: {
+                "shape": shape,
+                "relation": relation.name(),
+            }
+        }
+    })
+}
+
+/// `geohash_grid` aggregation bucketing `field` at `precision`.
+pub fn geohash_grid(field: &str, precision: usize) -> Value {
+    json!({
+        "geohash_grid": {
+            "field": field,
+            "precision": precision,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geo_queries() {
+        let center = GeoPoint::new(40.0, -70.0);
+        assert_eq!(
+            geo_distance("location", &center, "12km"),
+            json!({"geo_distance": {"distance": "12km", "location": {"lat": 40.0, "lon": -70.0}}})
+        );
+
+        let bbox = BoundingBox::new(GeoPoint::new(40.73, -74.1), GeoPoint::new(40.01, -71.12));
+        assert_eq!(
+            geo_bounding_box("location", &bbox)["geo_bounding_box"]["location"]["top_left"],
+            json!({"lat": 40.73, "lon": -74.1})
+        );
+
+        let query = geo_shape("area", &GeoShape::point(&center), SpatialRelation::Within);
+        assert_eq!(query["geo_shape"]["area"]["relation"], "within");
+        assert_eq!(
+            query["geo_shape"]["area"]["shape"]["coordinates"],
+            json!([-70.0, 40.0])
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::geo::{BoundingBox, GeoPoint};
+
+/// `[lon, lat]`, the coordinate order used by GeoJSON and `geo_shape`.
+pub type Coordinate = [f64; 2];
+
+/// A `geo_shape` value in OpenSearch's GeoJSON form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "coordinates")]
+pub enum GeoShape {
+    #[serde(rename = "point", alias = "Point", alias = "POINT")]
+    Point(Coordinate),
+    #[serde(rename = "linestring", alias = "LineString", alias = "LINESTRING")]
+    LineString(Vec<Coordinate>),
+    #[serde(rename = "polygon", alias = "Polygon", alias = "POLYGON")]
+    Polygon(Vec<Vec<Coordinate>>),
+    #[serde(rename = "multipoint", alias = "MultiPoint", alias = "MULTIPOINT")]
+    MultiPoint(Vec<Coordinate>),
+    #[serde(
+        rename = "multilinestring",
+        alias = "MultiLineString",
+        alias = "MULTILINESTRING"
+    )]
+    MultiLineString(Vec<Vec<Coordinate>>),
+    #[serde(
+        rename = "multipolygon",
+        alias = "MultiPolygon",
+        alias = "MULTIPOLYGON"
+    )]
+    MultiPolygon(Vec<Vec<Vec<Coordinate>>>),
+    /// `[[min_lon, max_lat], [max_lon, min_lat]]`
+    #[serde(rename = "envelope", alias = "Envelope", alias = "ENVELOPE")]
+    Envelope([Coordinate; 2]),
+}
+
+impl GeoShape {
+    pub fn point(point: &GeoPoint) -> Self {
+        GeoShape::Point([point.lon, point.lat])
+    }
+
+    pub fn envelope(bbox: &BoundingBox) -> Self {
+        GeoShape::Envelope([
+            [bbox.top_left.lon, bbox.top_left.lat],
+            [bbox.bottom_right.lon, bbox.bottom_right.lat],
+        ])
+    }
+
+    /// Polygon from a single outer ring, closing the ring if needed.
+    pub fn polygon(ring: Vec<GeoPoint>) -> Result<Self, String> {
+        let mut coordinates: Vec<Coordinate> = ring.iter().map(|p| [p.lon, p.lat]).collect();
+        if coordinates.first() != coordinates.last() {
+            coordinates.push(coordinates[0]);
+        }
+        if coordinates.len() < 4 {
+            return Err("a polygon ring needs at least three distinct points".to_string());
+        }
+        Ok(GeoShape::Polygon(vec![coordinates]))
+    }
+
+    fn coordinates(&self) -> Vec<Coordinate> {
+        match self {
+            GeoShape::Point(c) => vec![*c],
+            GeoShape::LineString(cs) | GeoShape::MultiPoint(cs) => cs.clone(),
+            GeoShape::Polygon(rings) | GeoShape::MultiLineString(rings) => rings.concat(),
+            GeoShape::MultiPolygon(polygons) => {
+                polygons.iter().flat_map(|rings| rings.concat()).collect()
+            }
+            GeoShape::Envelope(corners) => corners.to_vec(),
+        }
+    }
+
+    /// Bounding box of all coordinates; `None` for an empty shape.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let coordinates = self.coordinates();
+        let first = coordinates.first()?;
+        let (mut min_lon, mut max_lon, mut min_lat, mut max_lat) =
+            (first[0], first[0], first[1], first[1]);
+        for [lon, lat] in &coordinates {
+            min_lon = min_lon.min(*lon);
+            max_lon = max_lon.max(*lon);
+            min_lat = min_lat.min(*lat);
+            max_lat = max_lat.max(*lat);
+        }
+        Some(BoundingBox::new(
+            GeoPoint::new(max_lat, min_lon),
+            GeoPoint::new(min_lat, max_lon),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_geo_shape_serialization() {
+        let shape: GeoShape = serde_json::from_value(json!({
+            "type": "Polygon",
+            "coordinates": [[[100.0, 0.0], [101.0, 0.0], [101.0, 1.0], [100.0, 1.0], [100.0, 0.0]]]
+        }))
+        .unwrap();
+
+        let bbox = shape.bounding_box().unwrap();
+        assert_eq!(bbox.top_left, GeoPoint::new(1.0, 100.0));
+        assert_eq!(bbox.bottom_right, GeoPoint::new(0.0, 101.0));
+
+        let built = GeoShape::polygon(vec![
+            GeoPoint::new(0.0, 100.0),
+            GeoPoint::new(0.0, 101.0),
+            GeoPoint::new(1.0, 101.0),
+            GeoPoint::new(1.0, 100.0),
+        ])
+        .unwrap();
+        assert_eq!(built, shape);
+
+        assert_eq!(
+            serde_json::to_value(GeoShape::envelope(&bbox)).unwrap(),
+            json!({"type": "envelope", "coordinates": [[100.0, 1.0], [101.0, 0.0]]})
+        );
+    }
+}
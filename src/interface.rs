@@ -1,4 +1,4 @@
-use byteorder::WriteBytesExt;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{self, Read, Write};
 
 pub trait Serialize {
@@ -12,6 +12,179 @@ pub trait Deserialize {
     fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output>;
 }
 
+/// Write a variable-length int, 7 bits per byte (OpenSearch `StreamOutput::writeVInt`)
+pub fn write_vint(buf: &mut impl Write, mut value: u32) -> io::Result<usize> {
+    let mut written = 1;
+    while value & !0x7F != 0 {
+        buf.write_u8(((value & 0x7F) | 0x80) as u8)?;
+        value >>= 7;
+        written += 1;
+    }
+    buf.write_u8(value as u8)?;
+    Ok(written)
+}
+
+/// Read a variable-length int written by `write_vint`
+pub fn read_vint(buf: &mut impl Read) -> io::Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = buf.read_u8()?;
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Variable-length int is too long",
+    ))
+}
+
+/// Write a string the way OpenSearch `StreamOutput::writeString` does: the
+/// UTF-16 length, then each UTF-16 unit in one to three bytes. Surrogates are
+/// encoded separately, so a supplementary character takes six bytes.
+pub fn write_string(buf: &mut impl Write, value: &str) -> io::Result<usize> {
+    let units: Vec<u16> = value.encode_utf16().collect();
+    let mut bytes = Vec::with_capacity(units.len());
+    for unit in &units {
+        match *unit {
+            c @ 0x0000..=0x007F => bytes.push(c as u8),
+            c @ 0x0080..=0x07FF => {
+                bytes.extend_from_slice(&[0xC0 | (c >> 6) as u8, 0x80 | (c & 0x3F) as u8])
+            }
+            c => bytes.extend_from_slice(&[
+                0xE0 | (c >> 12) as u8,
+                0x80 | ((c >> 6) & 0x3F) as u8,
+                0x80 | (c & 0x3F) as u8,
+            ]),
+        }
+    }
+    let written = write_vint(buf, units.len() as u32)?;
+    buf.write_all(&bytes)?;
+    Ok(written + bytes.len())
+}
+
+/// Read one UTF-16 unit encoded by `write_string` (OpenSearch `StreamInput::readString`)
+pub(crate) fn read_string_unit(buf: &mut impl Read) -> io::Result<u16> {
+    let first = buf.read_u8()? as u16;
+    match first >> 4 {
+        0..=7 => Ok(first),
+        12 | 13 => Ok(((first & 0x1F) << 6) | (buf.read_u8()? as u16 & 0x3F)),
+        14 => {
+            let second = buf.read_u8()? as u16;
+            let third = buf.read_u8()? as u16;
+            Ok(((first & 0x0F) << 12) | ((second & 0x3F) << 6) | (third & 0x3F))
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid string; unexpected character: {:#04x}", first),
+        )),
+    }
+}
+
+/// Read a string written by `write_string`
+pub fn read_string(buf: &mut impl Read) -> io::Result<String> {
+    let len = read_vint(buf)? as usize;
+    let mut units = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    for _ in 0..len {
+        units.push(read_string_unit(buf)?);
+    }
+    String::from_utf16(&units).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write a length-prefixed byte array
+pub fn write_byte_array(buf: &mut impl Write, value: &[u8]) -> io::Result<usize> {
+    let written = write_vint(buf, value.len() as u32)?;
+    buf.write_all(value)?;
+    Ok(written + value.len())
+}
+
+/// Largest buffer allocated up front for a length read off the wire; longer
+/// buffers grow as their bytes actually arrive.
+pub(crate) const MAX_PREALLOCATION: usize = 64 * 1024;
+
+/// Read a length-prefixed byte array
+pub fn read_byte_array(buf: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_vint(buf)? as usize;
+    let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    buf.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Byte array of {} bytes ends after {}", len, bytes.len()),
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Write a boolean as a single byte
+pub fn write_bool(buf: &mut impl Write, value: bool) -> io::Result<usize> {
+    buf.write_u8(value as u8)?;
+    Ok(1)
+}
+
+/// Read a boolean written by `write_bool`
+pub fn read_bool(buf: &mut impl Read) -> io::Result<bool> {
+    match buf.read_u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        other => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid boolean byte: {}", other),
+        )),
+    }
+}
+
+/// Write a count-prefixed list of strings
+pub fn write_string_array(buf: &mut impl Write, values: &[String]) -> io::Result<usize> {
+    let mut written = write_vint(buf, values.len() as u32)?;
+    for value in values {
+        written += write_string(buf, value)?;
+    }
+    Ok(written)
+}
+
+/// Read a count-prefixed list of strings
+pub fn read_string_array(buf: &mut impl Read) -> io::Result<Vec<String>> {
+    let count = read_vint(buf)? as usize;
+    (0..count).map(|_| read_string(buf)).collect()
+}
+
+/// Write the parent `TaskId` that Java's `TransportRequest` reads ahead of
+/// every request body. Extensions send requests outside of any task, so
+/// this is the empty id: an empty node id and no task number.
+pub fn write_empty_task_id(buf: &mut impl Write) -> io::Result<usize> {
+    write_string(buf, "")
+}
+
+/// Read the parent `TaskId` ahead of a request body, returning the node id
+/// and task number unless it is the empty id.
+pub fn read_task_id(buf: &mut impl Read) -> io::Result<Option<(String, i64)>> {
+    let node_id = read_string(buf)?;
+    if node_id.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((node_id, buf.read_i64::<BigEndian>()?)))
+}
+
+/// `message` as the body of a Java `TransportRequest` that carries a
+/// protobuf, such as `ExtensionRequest`: the parent task id, then the
+/// encoded message as a byte array.
+pub fn proto_request_bytes(message: &impl prost::Message) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_empty_task_id(&mut buf)
+        .and_then(|_| write_byte_array(&mut buf, &message.encode_to_vec()))
+        .expect("writing to a Vec cannot fail");
+    buf
+}
+
+/// Decode the protobuf body of a request framed by `proto_request_bytes`.
+pub fn parse_proto_request<M: prost::Message + Default>(mut bytes: &[u8]) -> io::Result<M> {
+    read_task_id(&mut bytes)?;
+    let encoded = read_byte_array(&mut bytes)?;
+    M::decode(encoded.as_slice()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
 /// Request object (client -> server)
 /// Reference: https://github.com/opensearch-project/opensearch-sdk-py/blob/main/src/opensearch_sdk_py/transport/transport_status.py#L9
 #[derive(Debug)]
@@ -79,3 +252,53 @@ impl Deserialize for Request {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vint_round_trip() {
+        for value in [0u32, 1, 127, 128, 300, 16_384, u32::MAX] {
+            let mut buf = Vec::new();
+            let written = write_vint(&mut buf, value).unwrap();
+            assert_eq!(written, buf.len());
+            assert_eq!(read_vint(&mut buf.as_slice()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_string_array_round_trip() {
+        let values = vec!["pretty".to_string(), "filter_path".to_string()];
+        let mut buf = Vec::new();
+        write_string_array(&mut buf, &values).unwrap();
+        assert_eq!(read_string_array(&mut buf.as_slice()).unwrap(), values);
+    }
+
+    #[test]
+    fn test_string_matches_java_encoding() {
+        // Bytes produced by Java's `StreamOutput.writeString("h\u20ac\u00e9\ud83d\ude00")`
+        let java = [
+            0x05, 0x68, 0xE2, 0x82, 0xAC, 0xC3, 0xA9, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80,
+        ];
+        let mut buf = Vec::new();
+        let written = write_string(&mut buf, "h€é😀").unwrap();
+        assert_eq!(buf, java);
+        assert_eq!(written, java.len());
+        assert_eq!(read_string(&mut java.as_slice()).unwrap(), "h€é😀");
+
+        let error = read_string(&mut [0x01, 0xF0].as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let lone_surrogate = [0x01, 0xED, 0xA0, 0xBD];
+        assert!(read_string(&mut lone_surrogate.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_oversized_byte_array_length_rejected() {
+        let mut buf = Vec::new();
+        write_vint(&mut buf, u32::MAX).unwrap();
+        buf.extend_from_slice(b"short");
+        let error = read_byte_array(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
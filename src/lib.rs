@@ -1,3 +1,10 @@
+pub mod client;
+pub mod codec;
 pub mod extension;
+pub mod geo;
 pub mod interface;
+pub mod metrics;
+pub mod proto;
+pub mod rest;
 pub mod transport;
+pub mod xcontent;
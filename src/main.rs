@@ -1,163 +1,222 @@
-use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use clap::{value_parser, Arg, ArgMatches, Command};
+use opensearch_sdk_rs::extension::logging::{init_logging, LogFormat, LoggingControl};
+use opensearch_sdk_rs::extension::supervisor::UNHEALTHY_EXIT_CODE;
+use opensearch_sdk_rs::extension::{Extension, ExtensionBuilder, ExtensionContext, ExtensionError};
+use opensearch_sdk_rs::rest::{ExtensionRestRequest, Method, RestHandler, RestResponse, Route};
+use serde_json::json;
+use tokio::runtime::Runtime;
+use tracing::{error, info};
+
+const DEFAULT_PORT: &str = "1234";
+const EXTENSION_NAME: &str = "Hello World";
+const EXTENSION_ID: &str = "hello-world-rs";
+const EXTENSION_VERSION: &str = "0.1.0";
+
+/// Where the binary listens and which OpenSearch node it talks to, from
+/// the command line or the matching `OPENSEARCH_EXT_*` variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    port: u16,
+    opensearch_host: String,
+    opensearch_port: u16,
+    config: Option<PathBuf>,
+    log_level: Option<String>,
+}
 
-use opensearch_sdk_rs::transport::{transport_status, TransportTcpHeader};
+impl Config {
+    fn command() -> Command {
+        Command::new("server")
+            .about("OpenSearch SDK for Rust - Hello World Extension")
+            .arg(
+                Arg::new("port")
+                    .long("port")
+                    .env("OPENSEARCH_EXT_PORT")
+                    .value_parser(value_parser!(u16))
+                    .default_value(DEFAULT_PORT)
+                    .help("Port the extension listens on"),
+            )
+            .arg(
+                Arg::new("opensearch-host")
+                    .long("opensearch-host")
+                    .env("OPENSEARCH_EXT_OPENSEARCH_HOST")
+                    .default_value("localhost")
+                    .help("Host of the OpenSearch transport endpoint"),
+            )
+            .arg(
+                Arg::new("opensearch-port")
+                    .long("opensearch-port")
+                    .env("OPENSEARCH_EXT_OPENSEARCH_PORT")
+                    .value_parser(value_parser!(u16))
+                    .default_value("9300")
+                    .help("Port of the OpenSearch transport endpoint"),
+            )
+            .arg(
+                Arg::new("config")
+                    .long("config")
+                    .env("OPENSEARCH_EXT_CONFIG")
+                    .value_parser(value_parser!(PathBuf))
+                    .help("Settings file, in YAML, JSON or TOML"),
+            )
+            .arg(
+                Arg::new("log-level")
+                    .long("log-level")
+                    .env("OPENSEARCH_EXT_LOG_LEVEL")
+                    .help("Log level, overriding RUST_LOG"),
+            )
+    }
 
-const DEFAULT_PORT: u32 = 1234;
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Config {
+            port: *matches.get_one("port").expect("port has a default"),
+            opensearch_host: matches
+                .get_one::<String>("opensearch-host")
+                .expect("host has a default")
+                .clone(),
+            opensearch_port: *matches
+                .get_one("opensearch-port")
+                .expect("port has a default"),
+            config: matches.get_one::<PathBuf>("config").cloned(),
+            log_level: matches.get_one::<String>("log-level").cloned(),
+        }
+    }
 
-#[derive(Debug)]
-pub struct Host {
-    address: Ipv4Addr,
-    port: u32,
-}
+    pub fn parse() -> Self {
+        Self::from_matches(&Self::command().get_matches())
+    }
 
-impl Host {
-    pub fn new(port: u32) -> Host {
-        Host {
-            address: Ipv4Addr::new(127, 0, 0, 1),
-            port,
+    pub fn builder(&self) -> ExtensionBuilder {
+        let builder = ExtensionBuilder::new(EXTENSION_NAME)
+            .unique_id(EXTENSION_ID)
+            .version(EXTENSION_VERSION)
+            .port(self.port)
+            .transport_endpoint(self.opensearch_host.clone(), self.opensearch_port);
+        match &self.config {
+            Some(path) => builder.settings_file(path),
+            None => builder,
         }
     }
+}
 
-    pub fn run(&self) {
-        let listener = TcpListener::bind(format!("{}:{}", &self.address, &self.port))
-            .unwrap_or_else(|_| panic!("Unable to bind to port: {}", &self.port));
-
-        println!(
-            "🚀 OpenSearch Extension SDK (Rust) started on {}:{}",
-            self.address, self.port
-        );
-        println!("📡 Waiting for OpenSearch connections...");
-
-        let mut count = 0;
-
-        for stream in listener.incoming() {
-            match stream {
-                Ok(stream) => {
-                    count += 1;
-                    println!("[{}] 📨 Connection from {:?}", count, stream.peer_addr());
-
-                    if let Err(e) = self.handle_connection(stream, count) {
-                        eprintln!("[{}] ❌ Error handling connection: {:?}", count, e);
-                    }
-                }
-                Err(e) => {
-                    eprintln!("❌ Error accepting connection: {:?}", e);
-                }
-            }
-        }
+struct HelloHandler;
+
+#[async_trait]
+impl RestHandler for HelloHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![Route::new(Method::Get, "/hello")]
     }
 
-    fn handle_connection(
+    async fn handle(
         &self,
-        stream: TcpStream,
-        connection_id: usize,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        match TransportTcpHeader::from_stream(stream.try_clone()?) {
-            Ok(header) => {
-                println!("[{}] 📋 Parsed header: {:?}", connection_id, header);
-
-                if header.is_handshake() {
-                    println!("[{}] 🤝 Handling handshake request", connection_id);
-                    self.handle_handshake(stream, header, connection_id)?;
-                } else if header.is_request_response() {
-                    println!("[{}] 📨 Handling request/response", connection_id);
-                    self.handle_request_response(stream, header, connection_id)?;
-                } else {
-                    println!(
-                        "[{}] ❓ Unknown request type: {}",
-                        connection_id, header.status
-                    );
-                }
-            }
-            Err(e) => {
-                eprintln!("[{}] ❌ Error parsing header: {:?}", connection_id, e);
-            }
-        }
-        Ok(())
+        request: ExtensionRestRequest,
+        _context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        RestResponse::ok().negotiate(&request).json(&json!({
+            "message": "Hello World from OpenSearch Rust Extension!",
+            "status": "ok",
+            "extension": EXTENSION_ID,
+        }))
     }
+}
 
-    fn handle_handshake(
-        &self,
-        mut stream: TcpStream,
-        header: TransportTcpHeader,
-        connection_id: usize,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("[{}] 🤝 Processing handshake", connection_id);
-
-        // Create a simple handshake response
-        let response_content = b"Hello from OpenSearch Rust SDK!";
-
-        let response_header = TransportTcpHeader::new(
-            header.request_id,
-            transport_status::STATUS_REQRES,
-            header.version,
-            response_content.len() as u32,
-            0, // variable header size
-        );
-
-        response_header.write_response(&mut stream, response_content)?;
-        println!("[{}] ✅ Handshake response sent", connection_id);
+struct HelloWorld;
 
-        Ok(())
+#[async_trait]
+impl Extension for HelloWorld {
+    fn name(&self) -> &str {
+        EXTENSION_NAME
     }
 
-    fn handle_request_response(
-        &self,
-        mut stream: TcpStream,
-        header: TransportTcpHeader,
-        connection_id: usize,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("[{}] 📨 Processing request/response", connection_id);
-
-        // Create a simple hello world response
-        let response_content = br#"{"message": "Hello World from OpenSearch Rust Extension!", "status": "ok", "extension": "hello-world-rs"}"#;
-
-        let response_header = TransportTcpHeader::new(
-            header.request_id,
-            transport_status::STATUS_REQRES,
-            header.version,
-            response_content.len() as u32,
-            0, // variable header size
-        );
-
-        response_header.write_response(&mut stream, response_content)?;
-        println!("[{}] ✅ Response sent", connection_id);
+    fn unique_id(&self) -> &str {
+        EXTENSION_ID
+    }
 
+    fn version(&self) -> &str {
+        EXTENSION_VERSION
+    }
+
+    fn opensearch_version(&self) -> &str {
+        "3.0.0"
+    }
+
+    fn rest_handlers(&self) -> Vec<Arc<dyn RestHandler>> {
+        vec![Arc::new(HelloHandler)]
+    }
+
+    async fn initialize(&mut self, _context: &ExtensionContext) -> Result<(), ExtensionError> {
         Ok(())
     }
-}
 
-impl Default for Host {
-    fn default() -> Self {
-        Host {
-            address: Ipv4Addr::new(127, 0, 0, 1),
-            port: DEFAULT_PORT,
-        }
+    async fn shutdown(&mut self) -> Result<(), ExtensionError> {
+        Ok(())
     }
 }
 
-fn main() {
-    println!("🦀 OpenSearch SDK for Rust - Hello World Extension");
-    println!("==================================================");
-
-    let host = Host::new(1234);
-    host.run();
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::parse();
+    let format = std::env::var("LOG_FORMAT")
+        .ok()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(LogFormat::Text);
+    init_logging(format)?;
+    if let Some(level) = &config.log_level {
+        LoggingControl::global().set_level(level)?;
+    }
+    info!("OpenSearch SDK for Rust - Hello World Extension");
+
+    let runtime = Arc::new(Runtime::new()?);
+    let mut runner = config
+        .builder()
+        .thread_pool(runtime.clone())
+        .build(HelloWorld)?;
+    match runtime.block_on(runner.run()) {
+        Err(ExtensionError::Unhealthy(reason)) => {
+            error!("Exiting unhealthy: {}", reason);
+            std::process::exit(UNHEALTHY_EXIT_CODE);
+        }
+        result => Ok(result?),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn parse(args: &[&str]) -> Config {
+        let args = std::iter::once("server").chain(args.iter().copied());
+        Config::from_matches(&Config::command().try_get_matches_from(args).unwrap())
+    }
+
     #[test]
-    fn test_host_creation() {
-        let host = Host::new(8080);
-        assert_eq!(host.port, 8080);
-        assert_eq!(host.address, Ipv4Addr::new(127, 0, 0, 1));
+    fn test_default_config() {
+        let config = parse(&[]);
+        assert_eq!(config.port, 1234);
+        assert_eq!(config.opensearch_host, "localhost");
+        assert_eq!(config.opensearch_port, 9300);
+        assert_eq!(config.config, None);
     }
 
     #[test]
-    fn test_default_host() {
-        let host = Host::default();
-        assert_eq!(host.port, DEFAULT_PORT);
+    fn test_config_from_arguments() {
+        let config = parse(&[
+            "--port",
+            "8080",
+            "--opensearch-host",
+            "node-1",
+            "--opensearch-port",
+            "9301",
+            "--log-level",
+            "debug",
+        ]);
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.opensearch_host, "node-1");
+        assert_eq!(config.opensearch_port, 9301);
+        assert_eq!(config.log_level.as_deref(), Some("debug"));
+        assert!(Config::command()
+            .try_get_matches_from(["server", "--port", "not-a-port"])
+            .is_err());
     }
 }
@@ -0,0 +1,869 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::extension::diagnostics::Diagnostics;
+use crate::extension::lifecycle::{ExtensionState, StateListener};
+use crate::extension::metadata::ExtensionMetrics;
+use crate::extension::resilience::{AdaptiveLimiter, Bulkhead};
+use crate::extension::resources::ResourceSampler;
+use crate::extension::setting::Setting;
+use crate::extension::ExtensionError;
+use crate::rest::middleware::ResponseTimeMetrics;
+use crate::transport::action::ActionMetrics;
+use crate::transport::{ConnectionRegistry, UsageTracker};
+
+/// Content type of the Prometheus text exposition format.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Latency buckets in seconds, from 1ms to 10s.
+pub const DEFAULT_LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Quantiles reported for request durations.
+pub const DURATION_QUANTILES: &[f64] = &[0.5, 0.9, 0.99];
+
+/// Port of the HTTP endpoint serving `/metrics`. Metrics are not served
+/// while it is unset.
+pub fn metrics_port() -> Setting<i64> {
+    Setting::int("extension.metrics.port")
+        .node_scope()
+        .min(1)
+        .max(65535)
+}
+
+type Labels = Vec<(String, String)>;
+
+fn sorted_labels(pairs: &[(&str, &str)]) -> Labels {
+    let mut labels: Labels = pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    labels.sort();
+    labels
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricKind {
+    fn name(&self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram => "histogram",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that goes up and down, stored as `f64` bits.
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    buckets: Vec<AtomicU64>,
+    state: Mutex<(u64, f64)>,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Histogram {
+            bounds: bounds.to_vec(),
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            state: Mutex::new((0, 0.0)),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            if value <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.0 += 1;
+        state.1 += value;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).0
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Arc<Counter>),
+    Gauge(Arc<Gauge>),
+    Histogram(Arc<Histogram>),
+}
+
+struct Family {
+    help: String,
+    kind: MetricKind,
+    series: BTreeMap<Labels, Metric>,
+}
+
+/// A value read from another component at scrape time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub help: &'static str,
+    pub kind: MetricKind,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+}
+
+impl Sample {
+    pub fn new(name: impl Into<String>, help: &'static str, kind: MetricKind, value: f64) -> Self {
+        Sample {
+            name: name.into(),
+            help,
+            kind,
+            labels: Vec::new(),
+            value,
+        }
+    }
+
+    pub fn label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Reports the current state of a component when metrics are scraped.
+pub trait MetricsCollector: Send + Sync {
+    fn collect(&self) -> Vec<Sample>;
+}
+
+/// Counters, gauges and histograms of the extension, rendered in the
+/// Prometheus text format together with the samples of its collectors.
+#[derive(Default)]
+pub struct MetricsRegistry {
+    families: Mutex<BTreeMap<String, Family>>,
+    collectors: Mutex<Vec<Arc<dyn MetricsCollector>>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The series of `name` for `labels`, created on first use. When `name`
+    /// is already a different kind of metric, the series is created
+    /// detached from the registry, so it works but is never exported.
+    fn metric(
+        &self,
+        name: &str,
+        help: &str,
+        kind: MetricKind,
+        labels: Labels,
+        create: impl FnOnce() -> Metric,
+    ) -> Metric {
+        let mut families = self.families.lock().unwrap_or_else(PoisonError::into_inner);
+        let family = families.entry(name.to_string()).or_insert_with(|| Family {
+            help: help.to_string(),
+            kind,
+            series: BTreeMap::new(),
+        });
+        if family.kind != kind {
+            warn!(
+                "Metric {} is a {}, not exporting it as a {}",
+                name,
+                family.kind.name(),
+                kind.name()
+            );
+            return create();
+        }
+        family.series.entry(labels).or_insert_with(create).clone()
+    }
+
+    /// The counter `name` with `labels`, created on first use.
+    pub fn counter(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Arc<Counter> {
+        match self.metric(
+            name,
+            help,
+            MetricKind::Counter,
+            sorted_labels(labels),
+            || Metric::Counter(Arc::default()),
+        ) {
+            Metric::Counter(counter) => counter,
+            _ => Arc::default(),
+        }
+    }
+
+    pub fn gauge(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Arc<Gauge> {
+        match self.metric(name, help, MetricKind::Gauge, sorted_labels(labels), || {
+            Metric::Gauge(Arc::default())
+        }) {
+            Metric::Gauge(gauge) => gauge,
+            _ => Arc::default(),
+        }
+    }
+
+    /// The histogram `name` with `labels`, using `DEFAULT_LATENCY_BUCKETS`.
+    pub fn histogram(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Arc<Histogram> {
+        let create = || Metric::Histogram(Arc::new(Histogram::new(DEFAULT_LATENCY_BUCKETS)));
+        match self.metric(
+            name,
+            help,
+            MetricKind::Histogram,
+            sorted_labels(labels),
+            create,
+        ) {
+            Metric::Histogram(histogram) => histogram,
+            _ => Arc::new(Histogram::new(DEFAULT_LATENCY_BUCKETS)),
+        }
+    }
+
+    pub fn register_collector(&self, collector: Arc<dyn MetricsCollector>) {
+        self.collectors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(collector);
+    }
+
+    /// Every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in self
+            .families
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            write_family_header(&mut out, name, &family.help, family.kind);
+            for (labels, metric) in &family.series {
+                match metric {
+                    Metric::Counter(counter) => {
+                        write_sample(&mut out, name, labels, counter.get() as f64)
+                    }
+                    Metric::Gauge(gauge) => write_sample(&mut out, name, labels, gauge.get()),
+                    Metric::Histogram(histogram) => {
+                        write_histogram(&mut out, name, labels, histogram)
+                    }
+                }
+            }
+        }
+
+        let collectors = self
+            .collectors
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut collected: BTreeMap<String, Vec<Sample>> = BTreeMap::new();
+        for sample in collectors.iter().flat_map(|collector| collector.collect()) {
+            collected
+                .entry(sample.name.clone())
+                .or_default()
+                .push(sample);
+        }
+        for (name, samples) in collected {
+            write_family_header(&mut out, &name, samples[0].help, samples[0].kind);
+            for sample in samples {
+                write_sample(&mut out, &name, &sample.labels, sample.value);
+            }
+        }
+        out
+    }
+}
+
+fn write_family_header(out: &mut String, name: &str, help: &str, kind: MetricKind) {
+    let _ = writeln!(
+        out,
+        "# HELP {} {}",
+        name,
+        help.replace('\\', "\\\\").replace('\n', "\\n")
+    );
+    let _ = writeln!(out, "# TYPE {} {}", name, kind.name());
+}
+
+fn write_labels(out: &mut String, labels: &[(String, String)]) {
+    if labels.is_empty() {
+        return;
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    let _ = write!(out, "{{{}}}", labels.join(","));
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &[(String, String)], value: f64) {
+    out.push_str(name);
+    write_labels(out, labels);
+    let _ = writeln!(out, " {}", value);
+}
+
+fn write_histogram(
+    out: &mut String,
+    name: &str,
+    labels: &[(String, String)],
+    histogram: &Histogram,
+) {
+    let bucket_name = format!("{}_bucket", name);
+    for (bound, bucket) in histogram.bounds.iter().zip(&histogram.buckets) {
+        let mut bucket_labels = labels.to_vec();
+        bucket_labels.push(("le".to_string(), bound.to_string()));
+        write_sample(
+            out,
+            &bucket_name,
+            &bucket_labels,
+            bucket.load(Ordering::Relaxed) as f64,
+        );
+    }
+    let (count, sum) = *histogram
+        .state
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let mut bucket_labels = labels.to_vec();
+    bucket_labels.push(("le".to_string(), "+Inf".to_string()));
+    write_sample(out, &bucket_name, &bucket_labels, count as f64);
+    write_sample(out, &format!("{}_sum", name), labels, sum);
+    write_sample(out, &format!("{}_count", name), labels, count as f64);
+}
+
+impl MetricsCollector for ConnectionRegistry {
+    fn collect(&self) -> Vec<Sample> {
+        let connections = self.connections();
+        let mut samples = vec![Sample::new(
+            "extension_transport_connections",
+            "Open transport connections from OpenSearch",
+            MetricKind::Gauge,
+            connections.len() as f64,
+        )];
+        for stats in connections {
+            let per_connection = [
+                (
+                    "extension_transport_connection_bytes_in_total",
+                    "Bytes received on a connection",
+                    stats.bytes_in,
+                ),
+                (
+                    "extension_transport_connection_bytes_out_total",
+                    "Bytes sent on a connection",
+                    stats.bytes_out,
+                ),
+                (
+                    "extension_transport_connection_requests_total",
+                    "Requests received on a connection",
+                    stats.requests,
+                ),
+                (
+                    "extension_transport_connection_errors_total",
+                    "Errors on a connection",
+                    stats.errors,
+                ),
+            ];
+            for (name, help, value) in per_connection {
+                let sample = stats.labels().into_iter().fold(
+                    Sample::new(name, help, MetricKind::Counter, value as f64),
+                    |sample, (k, v)| sample.label(k, v),
+                );
+                samples.push(sample);
+            }
+        }
+        samples
+    }
+}
+
+impl MetricsCollector for ResponseTimeMetrics {
+    fn collect(&self) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for (route, metrics) in self.snapshot() {
+            let (method, path) = (route.method.to_string(), route.path.clone());
+            let route_sample = |name, help, kind, value| {
+                Sample::new(name, help, kind, value)
+                    .label("method", method.clone())
+                    .label("path", path.clone())
+            };
+            samples.push(route_sample(
+                "extension_rest_requests_total",
+                "REST requests per route",
+                MetricKind::Counter,
+                metrics.requests_total as f64,
+            ));
+            samples.push(route_sample(
+                "extension_rest_requests_failed_total",
+                "REST requests per route answered with a 5xx status or an error",
+                MetricKind::Counter,
+                metrics.requests_failed as f64,
+            ));
+            if let Some(average) = metrics.average_request_duration() {
+                samples.push(route_sample(
+                    "extension_rest_request_duration_ms_avg",
+                    "Average REST request duration",
+                    MetricKind::Gauge,
+                    average,
+                ));
+            }
+            for (quantile, value) in duration_quantiles(&metrics) {
+                samples.push(
+                    route_sample(
+                        "extension_rest_request_duration_ms",
+                        "REST request duration quantiles per route",
+                        MetricKind::Gauge,
+                        value,
+                    )
+                    .label("quantile", quantile),
+                );
+            }
+        }
+        samples
+    }
+}
+
+impl MetricsCollector for ActionMetrics {
+    fn collect(&self) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for (action, metrics) in self.snapshot() {
+            let action_sample = |name, help, kind, value| {
+                Sample::new(name, help, kind, value).label("action", action.clone())
+            };
+            samples.push(action_sample(
+                "extension_action_requests_total",
+                "Transport action requests per action",
+                MetricKind::Counter,
+                metrics.requests_total as f64,
+            ));
+            samples.push(action_sample(
+                "extension_action_requests_failed_total",
+                "Transport action requests per action that failed",
+                MetricKind::Counter,
+                metrics.requests_failed as f64,
+            ));
+            for (quantile, value) in duration_quantiles(&metrics) {
+                samples.push(
+                    action_sample(
+                        "extension_action_duration_ms",
+                        "Transport action duration quantiles per action",
+                        MetricKind::Gauge,
+                        value,
+                    )
+                    .label("quantile", quantile),
+                );
+            }
+        }
+        samples
+    }
+}
+
+impl MetricsCollector for Diagnostics {
+    fn collect(&self) -> Vec<Sample> {
+        self.handler_panics()
+            .into_iter()
+            .map(|(handler, panics)| {
+                Sample::new(
+                    "extension_handler_panics_total",
+                    "Panics caught in REST and transport handlers",
+                    MetricKind::Counter,
+                    panics as f64,
+                )
+                .label("handler", handler)
+            })
+            .collect()
+    }
+}
+
+impl MetricsCollector for AdaptiveLimiter {
+    fn collect(&self) -> Vec<Sample> {
+        let Ok(stats) = self.stats() else {
+            return Vec::new();
+        };
+        let limiter_sample = |name, help, kind, value| {
+            Sample::new(name, help, kind, value).label("limiter", stats.name.clone())
+        };
+        vec![
+            limiter_sample(
+                "extension_adaptive_limit",
+                "Current concurrency limit",
+                MetricKind::Gauge,
+                stats.limit as f64,
+            ),
+            limiter_sample(
+                "extension_adaptive_in_flight",
+                "Calls in flight",
+                MetricKind::Gauge,
+                stats.in_flight as f64,
+            ),
+            limiter_sample(
+                "extension_adaptive_rejected_total",
+                "Calls rejected over the limit",
+                MetricKind::Counter,
+                stats.rejected_total as f64,
+            ),
+        ]
+    }
+}
+
+impl MetricsCollector for Bulkhead {
+    fn collect(&self) -> Vec<Sample> {
+        let stats = self.stats();
+        let bulkhead_sample = |name, help, kind, value| {
+            Sample::new(name, help, kind, value).label("bulkhead", stats.name.clone())
+        };
+        vec![
+            bulkhead_sample(
+                "extension_bulkhead_active",
+                "Calls running in a bulkhead",
+                MetricKind::Gauge,
+                stats.active as f64,
+            ),
+            bulkhead_sample(
+                "extension_bulkhead_queued",
+                "Calls waiting to enter a bulkhead",
+                MetricKind::Gauge,
+                stats.queued as f64,
+            ),
+            bulkhead_sample(
+                "extension_bulkhead_max_concurrent",
+                "Concurrency limit of a bulkhead",
+                MetricKind::Gauge,
+                stats.max_concurrent as f64,
+            ),
+            bulkhead_sample(
+                "extension_bulkhead_saturation",
+                "Share of a bulkhead's concurrency limit in use",
+                MetricKind::Gauge,
+                stats.saturation(),
+            ),
+            bulkhead_sample(
+                "extension_bulkhead_rejected_total",
+                "Calls a bulkhead rejected because its queue was full or the wait too long",
+                MetricKind::Counter,
+                stats.rejected_total as f64,
+            ),
+        ]
+    }
+}
+
+impl MetricsCollector for ResourceSampler {
+    fn collect(&self) -> Vec<Sample> {
+        let usage = self.latest();
+        let mut samples = vec![Sample::new(
+            "extension_uptime_seconds",
+            "Seconds since the resource sampler started",
+            MetricKind::Gauge,
+            usage.uptime_seconds as f64,
+        )];
+        if let Some(bytes) = usage.memory_usage_bytes {
+            samples.push(Sample::new(
+                "process_resident_memory_bytes",
+                "Resident memory of the extension process",
+                MetricKind::Gauge,
+                bytes as f64,
+            ));
+        }
+        if let Some(percent) = usage.cpu_usage_percent {
+            samples.push(Sample::new(
+                "process_cpu_usage_percent",
+                "CPU used since the previous sample, as a percentage of one core",
+                MetricKind::Gauge,
+                percent as f64,
+            ));
+        }
+        if let Some(fds) = usage.open_fds {
+            samples.push(Sample::new(
+                "process_open_fds",
+                "Open file descriptors of the extension process",
+                MetricKind::Gauge,
+                fds as f64,
+            ));
+        }
+        if let Some(runtime) = usage.runtime {
+            samples.push(Sample::new(
+                "tokio_workers",
+                "Worker threads of the tokio runtime",
+                MetricKind::Gauge,
+                runtime.workers as f64,
+            ));
+            samples.push(Sample::new(
+                "tokio_alive_tasks",
+                "Tasks alive on the tokio runtime",
+                MetricKind::Gauge,
+                runtime.alive_tasks as f64,
+            ));
+            samples.push(Sample::new(
+                "tokio_global_queue_depth",
+                "Tasks waiting in the tokio runtime's global queue",
+                MetricKind::Gauge,
+                runtime.global_queue_depth as f64,
+            ));
+        }
+        samples
+    }
+}
+
+fn duration_quantiles(metrics: &ExtensionMetrics) -> Vec<(String, f64)> {
+    DURATION_QUANTILES
+        .iter()
+        .filter_map(|q| Some((q.to_string(), metrics.request_duration_percentile(*q)?)))
+        .collect()
+}
+
+impl MetricsCollector for UsageTracker {
+    fn collect(&self) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for (action, stats) in self.report().by_action {
+            samples.push(
+                Sample::new(
+                    "extension_client_calls_total",
+                    "Calls made to the cluster per action",
+                    MetricKind::Counter,
+                    stats.calls as f64,
+                )
+                .label("action", action.clone()),
+            );
+            samples.push(
+                Sample::new(
+                    "extension_client_failures_total",
+                    "Failed calls to the cluster per action",
+                    MetricKind::Counter,
+                    stats.failures as f64,
+                )
+                .label("action", action),
+            );
+        }
+        samples
+    }
+}
+
+const STATES: &[ExtensionState] = &[
+    ExtensionState::Created,
+    ExtensionState::Initializing,
+    ExtensionState::Initialized,
+    ExtensionState::Running,
+    ExtensionState::Paused,
+    ExtensionState::Reconnecting,
+    ExtensionState::Stopping,
+    ExtensionState::Stopped,
+    ExtensionState::Failed,
+];
+
+/// Tracks the lifecycle state as `extension_state{state="..."}`, 1 for the
+/// current state and 0 for the others.
+pub struct LifecycleMetrics {
+    registry: Arc<MetricsRegistry>,
+}
+
+impl LifecycleMetrics {
+    pub fn new(registry: Arc<MetricsRegistry>) -> Self {
+        let metrics = LifecycleMetrics { registry };
+        metrics.set(ExtensionState::Created);
+        metrics
+    }
+
+    fn set(&self, current: ExtensionState) {
+        for state in STATES {
+            let name = format!("{:?}", state).to_lowercase();
+            self.registry
+                .gauge(
+                    "extension_state",
+                    "Lifecycle state of the extension",
+                    &[("state", &name)],
+                )
+                .set(if *state == current { 1.0 } else { 0.0 });
+        }
+    }
+}
+
+#[async_trait]
+impl StateListener for LifecycleMetrics {
+    async fn on_state_change(&self, _old_state: ExtensionState, new_state: ExtensionState) {
+        self.set(new_state);
+    }
+}
+
+/// Serves `GET /metrics` over plain HTTP for Prometheus to scrape.
+pub struct MetricsServer {
+    registry: Arc<MetricsRegistry>,
+}
+
+impl MetricsServer {
+    pub fn new(registry: Arc<MetricsRegistry>) -> Self {
+        MetricsServer { registry }
+    }
+
+    pub async fn bind(self, port: u16) -> Result<JoinHandle<()>, ExtensionError> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).await.map_err(|e| {
+            ExtensionError::initialization(format!("Failed to bind metrics port {}: {}", port, e))
+        })?;
+        info!("Serving metrics on port {}", port);
+        Ok(self.serve(listener))
+    }
+
+    /// Answer scrapes on `listener` until the returned task is aborted.
+    pub fn serve(self, listener: TcpListener) -> JoinHandle<()> {
+        let registry = self.registry;
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let registry = registry.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]);
+                    let response = match request.split_whitespace().take(2).collect::<Vec<_>>()[..]
+                    {
+                        ["GET", "/metrics"] => {
+                            http_response("200 OK", PROMETHEUS_CONTENT_TYPE, &registry.render())
+                        }
+                        ["GET", _] => http_response("404 Not Found", "text/plain", "Not found\n"),
+                        _ => http_response(
+                            "405 Method Not Allowed",
+                            "text/plain",
+                            "Method not allowed\n",
+                        ),
+                    };
+                    if let Err(e) = stream.write_all(response.as_bytes()).await {
+                        debug!("Failed to answer metrics scrape: {}", e);
+                    }
+                });
+            }
+        })
+    }
+}
+
+fn http_response(status: &str, content_type: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let registry = MetricsRegistry::new();
+        registry
+            .counter(
+                "extension_transport_requests_total",
+                "Transport requests",
+                &[("action", "a")],
+            )
+            .inc_by(3);
+        registry
+            .gauge("extension_up", "Whether the extension is up", &[])
+            .set(1.0);
+        let latency = registry.histogram("extension_latency_seconds", "Latency", &[]);
+        latency.observe(0.003);
+        latency.observe(2.0);
+
+        let rest = Arc::new(ResponseTimeMetrics::new());
+        registry.register_collector(rest.clone());
+        registry.register_collector(Arc::new(ConnectionRegistry::new()));
+        let actions = Arc::new(ActionMetrics::new());
+        actions.record("cluster:admin/hello/echo", 40.0, true);
+        registry.register_collector(actions);
+
+        let text = registry.render();
+        assert!(text.contains("# TYPE extension_transport_requests_total counter\nextension_transport_requests_total{action=\"a\"} 3\n"));
+        assert!(text.contains("extension_up 1\n"));
+        assert!(text.contains("extension_latency_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("extension_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("extension_latency_seconds_sum 2.003\n"));
+        assert!(text.contains("extension_transport_connections 0\n"));
+        assert!(!text.contains("extension_rest_requests_total"));
+        assert!(text
+            .contains("extension_action_requests_total{action=\"cluster:admin/hello/echo\"} 1\n"));
+        assert!(text.contains("extension_action_duration_ms{action=\"cluster:admin/hello/echo\",quantile=\"0.99\"} 40\n"));
+    }
+
+    #[test]
+    fn test_kind_mismatch_is_detached() {
+        let registry = MetricsRegistry::new();
+        registry.counter("extension_jobs", "Jobs", &[]).inc();
+        let gauge = registry.gauge("extension_jobs", "Jobs", &[("queue", "a")]);
+        gauge.set(5.0);
+        registry
+            .histogram("extension_jobs", "Jobs", &[])
+            .observe(1.0);
+
+        let text = registry.render();
+        assert!(text.contains("# TYPE extension_jobs counter\nextension_jobs 1\n"));
+        assert!(!text.contains("queue") && !text.contains("extension_jobs_bucket"));
+        assert_eq!(gauge.get(), 5.0);
+    }
+
+    #[tokio::test]
+    async fn test_serve_metrics_and_lifecycle_state() {
+        let registry = Arc::new(MetricsRegistry::new());
+        let lifecycle = LifecycleMetrics::new(registry.clone());
+        lifecycle
+            .on_state_change(ExtensionState::Created, ExtensionState::Running)
+            .await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = MetricsServer::new(registry).serve(listener);
+
+        let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        server.abort();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(PROMETHEUS_CONTENT_TYPE));
+        assert!(response.contains("extension_state{state=\"running\"} 1\n"));
+        assert!(response.contains("extension_state{state=\"created\"} 0\n"));
+    }
+}
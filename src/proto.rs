@@ -0,0 +1,6 @@
+//! Protobuf messages compiled from `src/*.proto` by `build.rs`.
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/org.opensearch.extensions.proto.rs"
+));
@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::client::ClientPolicies;
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Method, RestHandler, RestResponse, Route};
+
+/// Shows the client's effective policy and circuit state per operation
+/// category at `GET /_client/policies`.
+pub struct ClientPoliciesHandler {
+    policies: Arc<ClientPolicies>,
+}
+
+impl ClientPoliciesHandler {
+    pub fn new(policies: Arc<ClientPolicies>) -> Self {
+        ClientPoliciesHandler { policies }
+    }
+}
+
+#[async_trait]
+impl RestHandler for ClientPoliciesHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![Route::new(Method::Get, "/_client/policies")]
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        _context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        let policies = self.policies.report().await?;
+        RestResponse::ok()
+            .negotiate(&request)
+            .json(&serde_json::json!({ "policies": policies }))
+    }
+}
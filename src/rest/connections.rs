@@ -0,0 +1,46 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Method, RestHandler, RestResponse, Route};
+use crate::transport::ConnectionRegistry;
+
+/// Lists live OpenSearch connections and their counters at
+/// `GET /_connections`, or a single one at `GET /_connections/{id}`.
+pub struct ConnectionsHandler {
+    registry: Arc<ConnectionRegistry>,
+}
+
+impl ConnectionsHandler {
+    pub fn new(registry: Arc<ConnectionRegistry>) -> Self {
+        ConnectionsHandler { registry }
+    }
+}
+
+#[async_trait]
+impl RestHandler for ConnectionsHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![
+            Route::new(Method::Get, "/_connections"),
+            Route::new(Method::Get, "/_connections/{id}"),
+        ]
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        _context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        // Only `/_connections/{id}` has an `id`; the router turns it away elsewhere.
+        if !request.params.contains_key("id") {
+            let connections = self.registry.connections();
+            return RestResponse::ok()
+                .negotiate(&request)
+                .json(&serde_json::json!({ "connections": connections }));
+        }
+        match self.registry.get(request.required_param("id")?) {
+            Some(stats) => RestResponse::ok().negotiate(&request).json(&stats),
+            None => Ok(RestResponse::not_found()),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Method, RestHandler, RestMiddleware, RestResponse, Route};
+
+/// Serves the diagnostic bundle of the extension's `Diagnostics` at
+/// `GET /_diag`, with secret settings redacted.
+///
+/// The handler is administrative and keeps serving while paused, so it
+/// takes the `gate` middleware that must admit a caller to any of its
+/// routes, e.g. `requires_permissions(&client, &["cluster:monitor/myext/diagnostics"])`.
+pub struct DiagnosticsHandler {
+    gate: Arc<dyn RestMiddleware>,
+}
+
+impl DiagnosticsHandler {
+    pub fn new(gate: Arc<dyn RestMiddleware>) -> Self {
+        DiagnosticsHandler { gate }
+    }
+}
+
+#[async_trait]
+impl RestHandler for DiagnosticsHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![Route::new(Method::Get, "/_diag")]
+    }
+
+    fn available_when_paused(&self) -> bool {
+        true
+    }
+
+    fn middleware(&self, _route: &Route) -> Vec<Arc<dyn RestMiddleware>> {
+        vec![self.gate.clone()]
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        let bundle = context.diagnostics.bundle(&context.settings);
+        RestResponse::ok().negotiate(&request).json(&bundle)
+    }
+}
@@ -0,0 +1,169 @@
+use std::any::Any;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::extension::ExtensionError;
+use crate::rest::{RestResponse, RestStatus};
+
+/// Turns errors returned by REST handlers into responses. Mappers are tried
+/// in registration order; returning `None` defers to the next one and
+/// finally to `DefaultErrorMapper`.
+pub trait ErrorMapper: Send + Sync {
+    fn map_error(&self, error: &ExtensionError) -> Option<RestResponse>;
+}
+
+/// Maps every `ExtensionError` variant to a status and OpenSearch-style
+/// exception type.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultErrorMapper;
+
+impl DefaultErrorMapper {
+    pub fn status_and_type(error: &ExtensionError) -> (RestStatus, &'static str) {
+        match error {
+            ExtensionError::InvalidRequest(_) => {
+                (RestStatus::BadRequest, "illegal_argument_exception")
+            }
+            ExtensionError::Throttled { .. } => {
+                (RestStatus::TooManyRequests, "rejected_execution_exception")
+            }
+            ExtensionError::Remote { status, .. } => (
+                RestStatus::from_code(*status).unwrap_or(RestStatus::InternalServerError),
+                "status_exception",
+            ),
+            ExtensionError::TimeoutError(_) => (RestStatus::GatewayTimeout, "timeout_exception"),
+            ExtensionError::TransportError(_) => {
+                (RestStatus::ServiceUnavailable, "transport_exception")
+            }
+            ExtensionError::DependencyError(_) => {
+                (RestStatus::ServiceUnavailable, "dependency_exception")
+            }
+            ExtensionError::SerializationError(_) => {
+                (RestStatus::InternalServerError, "serialization_exception")
+            }
+            ExtensionError::ProtocolError(_) => {
+                (RestStatus::InternalServerError, "protocol_exception")
+            }
+            ExtensionError::ConfigurationError(_) => {
+                (RestStatus::InternalServerError, "configuration_exception")
+            }
+            ExtensionError::InitializationError(_) => {
+                (RestStatus::InternalServerError, "initialization_exception")
+            }
+            ExtensionError::RegistrationError(_) => {
+                (RestStatus::InternalServerError, "registration_exception")
+            }
+            ExtensionError::ShutdownError(_) => {
+                (RestStatus::InternalServerError, "shutdown_exception")
+            }
+            ExtensionError::Unhealthy(_) => (RestStatus::ServiceUnavailable, "unhealthy_exception"),
+            ExtensionError::IoError(_) => (RestStatus::InternalServerError, "io_exception"),
+            ExtensionError::Unknown(_) => (RestStatus::InternalServerError, "exception"),
+        }
+    }
+}
+
+impl ErrorMapper for DefaultErrorMapper {
+    fn map_error(&self, error: &ExtensionError) -> Option<RestResponse> {
+        let (status, error_type) = Self::status_and_type(error);
+        let reason = match error {
+            ExtensionError::IoError(e) => e.to_string(),
+            ExtensionError::InitializationError(reason)
+            | ExtensionError::TransportError(reason)
+            | ExtensionError::ConfigurationError(reason)
+            | ExtensionError::RegistrationError(reason)
+            | ExtensionError::DependencyError(reason)
+            | ExtensionError::ShutdownError(reason)
+            | ExtensionError::Unhealthy(reason)
+            | ExtensionError::SerializationError(reason)
+            | ExtensionError::ProtocolError(reason)
+            | ExtensionError::TimeoutError(reason)
+            | ExtensionError::InvalidRequest(reason)
+            | ExtensionError::Unknown(reason) => reason.clone(),
+            ExtensionError::Throttled { message, .. } | ExtensionError::Remote { message, .. } => {
+                message.clone()
+            }
+        };
+        let response = RestResponse::exception(status, error_type, reason);
+        Some(match error {
+            ExtensionError::Throttled {
+                retry_after: Some(retry_after),
+                ..
+            } => response.with_header(
+                "Retry-After",
+                (retry_after.as_secs_f64().ceil().max(1.0) as u64).to_string(),
+            ),
+            _ => response,
+        })
+    }
+}
+
+/// Future adapter returning `Err` with the panic message if `F` panics
+/// while being polled.
+pub struct CatchUnwind<F> {
+    future: F,
+}
+
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future = &mut self.future;
+        match catch_unwind(AssertUnwindSafe(|| Pin::new(future).poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(payload) => Poll::Ready(Err(panic_message(payload.as_ref()))),
+        }
+    }
+}
+
+/// Catch panics raised while polling `future`.
+pub fn catch_panic<F: Future>(future: F) -> CatchUnwind<Pin<Box<F>>> {
+    CatchUnwind {
+        future: Box::pin(future),
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_default_mapping() {
+        let response = DefaultErrorMapper
+            .map_error(&ExtensionError::timeout("search took too long"))
+            .unwrap();
+        let body: Value = serde_json::from_slice(&response.content).unwrap();
+
+        assert_eq!(response.status, RestStatus::GatewayTimeout);
+        assert_eq!(body["error"]["type"], "timeout_exception");
+        assert_eq!(body["error"]["reason"], "search took too long");
+        assert_eq!(body["status"], 504);
+
+        let throttled =
+            ExtensionError::throttled("queue full", Some(std::time::Duration::from_millis(1500)));
+        let response = DefaultErrorMapper.map_error(&throttled).unwrap();
+        assert_eq!(response.status, RestStatus::TooManyRequests);
+        assert_eq!(
+            response.headers.get("Retry-After"),
+            Some(&vec!["2".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic() {
+        assert_eq!(catch_panic(async { 1 }).await, Ok(1));
+        let result = catch_panic(async { panic!("boom {}", 42) }).await;
+        assert_eq!(result, Err::<(), _>("boom 42".to_string()));
+    }
+}
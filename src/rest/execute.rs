@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::interface::{
+    read_bool, read_byte_array, read_string, read_string_array, read_vint, write_bool,
+    write_byte_array, write_string, write_string_array, write_vint, Deserialize, Serialize,
+};
+use crate::rest::RestStatus;
+
+pub const JSON_CONTENT_TYPE: &str = "application/json; charset=UTF-8";
+pub const TEXT_CONTENT_TYPE: &str = "text/plain; charset=UTF-8";
+
+/// Response to `internal:extensions/restexecuteonextensiontaction`, carrying
+/// the HTTP result OpenSearch relays back to the REST client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestExecuteOnExtensionResponse {
+    pub status: RestStatus,
+    pub content_type: String,
+    pub content: Vec<u8>,
+    pub headers: HashMap<String, Vec<String>>,
+    pub consumed_params: Vec<String>,
+    /// Whether the handler took the request body, so OpenSearch does not
+    /// reject the request for carrying one.
+    pub content_consumed: bool,
+}
+
+impl RestExecuteOnExtensionResponse {
+    pub fn new(
+        status: RestStatus,
+        content_type: impl Into<String>,
+        content: impl Into<Vec<u8>>,
+    ) -> Self {
+        RestExecuteOnExtensionResponse {
+            status,
+            content_type: content_type.into(),
+            content: content.into(),
+            headers: HashMap::new(),
+            consumed_params: Vec::new(),
+            content_consumed: false,
+        }
+    }
+
+    pub fn json(status: RestStatus, content: impl Into<Vec<u8>>) -> Self {
+        Self::new(status, JSON_CONTENT_TYPE, content)
+    }
+
+    pub fn text(status: RestStatus, content: impl Into<String>) -> Self {
+        Self::new(status, TEXT_CONTENT_TYPE, content.into())
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.add_header(name, value);
+        self
+    }
+
+    pub fn add_header(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.headers
+            .entry(name.into())
+            .or_default()
+            .push(value.into());
+    }
+
+    pub fn with_consumed_params(mut self, params: Vec<String>) -> Self {
+        self.consumed_params = params;
+        self
+    }
+
+    pub fn with_content_consumed(mut self, content_consumed: bool) -> Self {
+        self.content_consumed = content_consumed;
+        self
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.serialize(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl Serialize for RestExecuteOnExtensionResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_string(buf, self.status.name())?;
+        written += write_string(buf, &self.content_type)?;
+        written += write_byte_array(buf, &self.content)?;
+
+        written += write_vint(buf, self.headers.len() as u32)?;
+        for (name, values) in &self.headers {
+            written += write_string(buf, name)?;
+            written += write_string_array(buf, values)?;
+        }
+
+        written += write_string_array(buf, &self.consumed_params)?;
+        written += write_bool(buf, self.content_consumed)?;
+        Ok(written)
+    }
+}
+
+impl Deserialize for RestExecuteOnExtensionResponse {
+    type Output = RestExecuteOnExtensionResponse;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let name = read_string(buf)?;
+        let status = RestStatus::from_name(&name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown REST status: {}", name),
+            )
+        })?;
+        let content_type = read_string(buf)?;
+        let content = read_byte_array(buf)?;
+
+        let header_count = read_vint(buf)?;
+        let mut headers = HashMap::new();
+        for _ in 0..header_count {
+            let name = read_string(buf)?;
+            headers.insert(name, read_string_array(buf)?);
+        }
+
+        let consumed_params = read_string_array(buf)?;
+        let content_consumed = read_bool(buf)?;
+
+        Ok(RestExecuteOnExtensionResponse {
+            status,
+            content_type,
+            content,
+            headers,
+            consumed_params,
+            content_consumed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_round_trip() {
+        let response = RestExecuteOnExtensionResponse::json(
+            RestStatus::Created,
+            br#"{"result":"created"}"#.to_vec(),
+        )
+        .with_header("Warning", "299 deprecated")
+        .with_header("Warning", "299 also deprecated")
+        .with_consumed_params(vec!["pretty".to_string()])
+        .with_content_consumed(true);
+
+        let bytes = response.to_bytes().unwrap();
+        let decoded = RestExecuteOnExtensionResponse::deserialize(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(decoded, response);
+        assert_eq!(decoded.headers["Warning"].len(), 2);
+    }
+
+    #[test]
+    fn test_matches_opensearch_wire_format() {
+        // ExtensionRestResponse.writeTo: status name, content type, content,
+        // headers as a map of lists, consumed params, content consumed.
+        let java: &[u8] = b"\x07CREATED\x1fapplication/json; charset=UTF-8\x02{}\x01\x07Warning\x01\x05299 x\x01\x06pretty\x01";
+        let response = RestExecuteOnExtensionResponse::json(RestStatus::Created, b"{}".to_vec())
+            .with_header("Warning", "299 x")
+            .with_consumed_params(vec!["pretty".to_string()])
+            .with_content_consumed(true);
+
+        assert_eq!(response.to_bytes().unwrap(), java);
+        assert_eq!(
+            RestExecuteOnExtensionResponse::deserialize(&mut &java[..]).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn test_unknown_status_rejected() {
+        let mut bytes = Vec::new();
+        write_string(&mut bytes, "IM_A_TEAPOT").unwrap();
+
+        let result = RestExecuteOnExtensionResponse::deserialize(&mut bytes.as_slice());
+        assert!(result.is_err());
+    }
+}
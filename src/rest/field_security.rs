@@ -0,0 +1,543 @@
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::response_params::simple_match;
+use crate::rest::{ExtensionRestRequest, Next, RestMiddleware, RestResponse};
+
+/// Fields one role may see, as dotted path patterns where `*` matches any
+/// characters. A pattern also covers everything below the path it names.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldRule {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl FieldRule {
+    /// Every field is visible.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// No field is visible.
+    pub fn deny_all() -> Self {
+        Self::default().exclude(["*"])
+    }
+
+    /// Only fields matching `patterns` are visible.
+    pub fn include<S: Into<String>>(mut self, patterns: impl IntoIterator<Item = S>) -> Self {
+        self.include.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Fields matching `patterns` are hidden, even if included.
+    pub fn exclude<S: Into<String>>(mut self, patterns: impl IntoIterator<Item = S>) -> Self {
+        self.exclude.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn is_visible(&self, path: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| covers(p, path));
+        included && !self.exclude.iter().any(|p| covers(p, path))
+    }
+
+    /// Whether anything below `path` could be visible, so objects on the way
+    /// to an included field are kept.
+    fn may_contain_visible(&self, path: &str) -> bool {
+        let prefix = format!("{}.", path);
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|p| covers(p, path) || p.starts_with(&prefix) || p.contains('*'));
+        included && !self.exclude.iter().any(|p| covers(p, path))
+    }
+}
+
+/// `pattern` matches `path` or one of its parent objects.
+fn covers(pattern: &str, path: &str) -> bool {
+    simple_match(pattern, path)
+        || path
+            .match_indices('.')
+            .any(|(i, _)| simple_match(pattern, &path[..i]))
+}
+
+/// The union of the rules for a principal's roles.
+struct Visibility<'a> {
+    rules: Vec<&'a FieldRule>,
+}
+
+impl Visibility<'_> {
+    fn is_visible(&self, path: &str) -> bool {
+        self.rules.iter().any(|rule| rule.is_visible(path))
+    }
+
+    fn may_contain_visible(&self, path: &str) -> bool {
+        self.rules.iter().any(|rule| rule.may_contain_visible(path))
+    }
+
+    fn is_unrestricted(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| **rule == FieldRule::allow_all())
+    }
+}
+
+type RoleResolver = dyn Fn(&ExtensionRestRequest) -> Vec<String> + Send + Sync;
+
+/// Field-level security for search responses. Hides fields the principal's
+/// roles may not see from `_source`, `fields` and `highlight` of every hit
+/// (including inner and top hits), and drops aggregations computed over
+/// hidden fields or scripts.
+pub struct FieldLevelSecurity {
+    roles: Arc<RoleResolver>,
+    rules: HashMap<String, FieldRule>,
+    default_rule: FieldRule,
+}
+
+impl FieldLevelSecurity {
+    /// Take roles from the principal OpenSearch authenticated. Requests
+    /// without a readable principal get the default rule.
+    pub fn new() -> Self {
+        Self::with_role_resolver(|request| {
+            request
+                .principal()
+                .and_then(|principal| principal.user().ok())
+                .map(|user| user.roles)
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn with_role_resolver(
+        roles: impl Fn(&ExtensionRestRequest) -> Vec<String> + Send + Sync + 'static,
+    ) -> Self {
+        FieldLevelSecurity {
+            roles: Arc::new(roles),
+            rules: HashMap::new(),
+            default_rule: FieldRule::allow_all(),
+        }
+    }
+
+    /// Read roles from a comma-separated request header. Clients can set any
+    /// header, so only use this behind a proxy that strips the header from
+    /// incoming requests and sets it itself.
+    pub fn roles_from_trusted_proxy_header(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self::with_role_resolver(move |request| {
+            request
+                .header(&name)
+                .map(|roles| {
+                    roles
+                        .split(',')
+                        .map(|r| r.trim().to_string())
+                        .filter(|r| !r.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn role(mut self, role: impl Into<String>, rule: FieldRule) -> Self {
+        self.rules.insert(role.into(), rule);
+        self
+    }
+
+    /// Rule for principals with none of the configured roles.
+    pub fn default_rule(mut self, rule: FieldRule) -> Self {
+        self.default_rule = rule;
+        self
+    }
+
+    fn visibility(&self, roles: &[String]) -> Visibility<'_> {
+        let rules: Vec<&FieldRule> = roles
+            .iter()
+            .filter_map(|role| self.rules.get(role))
+            .collect();
+        if rules.is_empty() {
+            Visibility {
+                rules: vec![&self.default_rule],
+            }
+        } else {
+            Visibility { rules }
+        }
+    }
+
+    /// Filter a search response body in place. `search` is the request body,
+    /// used to find the fields each aggregation reads.
+    pub fn filter_response(
+        &self,
+        request: &ExtensionRestRequest,
+        search: Option<&Value>,
+        response: &mut Value,
+    ) {
+        self.filter_for_roles(&(self.roles)(request), search, response);
+    }
+
+    fn filter_for_roles(&self, roles: &[String], search: Option<&Value>, response: &mut Value) {
+        let visibility = self.visibility(roles);
+        if visibility.is_unrestricted() {
+            return;
+        }
+
+        filter_hits(&visibility, response);
+        let definitions = search.and_then(|s| s.get("aggs").or_else(|| s.get("aggregations")));
+        if let Some(aggregations) = response
+            .get_mut("aggregations")
+            .and_then(Value::as_object_mut)
+        {
+            filter_aggregations(&visibility, definitions, aggregations);
+        }
+    }
+}
+
+fn filter_hits(visibility: &Visibility, response: &mut Value) {
+    let Some(hits) = response
+        .pointer_mut("/hits/hits")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    for hit in hits.iter_mut().filter_map(Value::as_object_mut) {
+        if let Some(Value::Object(source)) = hit.get_mut("_source") {
+            filter_source(visibility, "", source);
+        }
+        for section in ["fields", "highlight"] {
+            if let Some(Value::Object(fields)) = hit.get_mut(section) {
+                fields.retain(|path, _| visibility.is_visible(path));
+            }
+        }
+        if let Some(Value::Object(inner_hits)) = hit.get_mut("inner_hits") {
+            inner_hits
+                .values_mut()
+                .for_each(|inner| filter_hits(visibility, inner));
+        }
+    }
+}
+
+fn filter_source(visibility: &Visibility, prefix: &str, object: &mut Map<String, Value>) {
+    object.retain(|key, value| {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        filter_value(visibility, &path, value)
+    });
+}
+
+/// Returns whether `value` at `path` should be kept after filtering it.
+fn filter_value(visibility: &Visibility, path: &str, value: &mut Value) -> bool {
+    match value {
+        Value::Object(object) => {
+            if !visibility.may_contain_visible(path) {
+                return false;
+            }
+            filter_source(visibility, path, object);
+            !object.is_empty() || visibility.is_visible(path)
+        }
+        Value::Array(values) if values.iter().any(Value::is_object) => {
+            values.retain_mut(|v| filter_value(visibility, path, v));
+            !values.is_empty()
+        }
+        _ => visibility.is_visible(path),
+    }
+}
+
+/// Top-level aggregations without a definition in the request are dropped,
+/// since there is no telling what they read.
+fn filter_aggregations(
+    visibility: &Visibility,
+    definitions: Option<&Value>,
+    results: &mut Map<String, Value>,
+) {
+    results.retain(
+        |key, result| match definitions.and_then(|d| d.get(aggregation_name(key))) {
+            Some(definition) => filter_aggregation(visibility, definition, result),
+            None => false,
+        },
+    );
+}
+
+/// `typed_keys` prefixes names with the aggregation type, as in `sterms#by_tag`.
+fn aggregation_name(key: &str) -> &str {
+    key.split_once('#').map_or(key, |(_, name)| name)
+}
+
+/// Returns whether the aggregation may be shown, filtering its hits and sub-aggregations.
+fn filter_aggregation(visibility: &Visibility, definition: &Value, result: &mut Value) -> bool {
+    if !aggregation_is_visible(visibility, definition) {
+        return false;
+    }
+    filter_hits(visibility, result);
+
+    let Some(sub_definitions) = definition
+        .get("aggs")
+        .or_else(|| definition.get("aggregations"))
+        .and_then(Value::as_object)
+    else {
+        return true;
+    };
+    let Some(object) = result.as_object_mut() else {
+        return true;
+    };
+
+    let buckets: Vec<&mut Value> = match object.get_mut("buckets") {
+        Some(Value::Array(buckets)) => buckets.iter_mut().collect(),
+        Some(Value::Object(buckets)) => buckets.values_mut().collect(),
+        _ => Vec::new(),
+    };
+    for bucket in buckets.into_iter().filter_map(Value::as_object_mut) {
+        filter_sub_aggregations(visibility, sub_definitions, bucket);
+    }
+    // Single-bucket aggregations such as `filter` and `nested` hold sub-aggregations directly.
+    filter_sub_aggregations(visibility, sub_definitions, object);
+    true
+}
+
+fn filter_sub_aggregations(
+    visibility: &Visibility,
+    definitions: &Map<String, Value>,
+    bucket: &mut Map<String, Value>,
+) {
+    bucket.retain(|key, result| match definitions.get(aggregation_name(key)) {
+        Some(definition) => filter_aggregation(visibility, definition, result),
+        None => true,
+    });
+}
+
+/// Whether every field an aggregation reads, outside its sub-aggregations, is visible.
+fn aggregation_is_visible(visibility: &Visibility, definition: &Value) -> bool {
+    let Some(definition) = definition.as_object() else {
+        return false;
+    };
+    definition
+        .iter()
+        .filter(|(kind, _)| !matches!(kind.as_str(), "aggs" | "aggregations" | "meta"))
+        .all(|(_, body)| {
+            let mut fields = Vec::new();
+            if !referenced_fields(body, &mut fields) {
+                return false;
+            }
+            fields.iter().all(|field| visibility.is_visible(field))
+        })
+}
+
+/// Collect `field` values and query field names under `value`. Returns
+/// false if the fields read cannot be told, as with scripts, query strings
+/// and field wildcards, so such aggregations are treated as hidden.
+fn referenced_fields(value: &Value, fields: &mut Vec<String>) -> bool {
+    match value {
+        Value::Object(object) => object.iter().all(|(key, value)| match key.as_str() {
+            "script" | "script_score" | "scripted_metric" | "query_string" => false,
+            "field" | "path" | "default_field" => {
+                value.as_str().is_some_and(|field| add_field(field, fields))
+            }
+            "fields" => value.as_array().is_some_and(|values| {
+                values
+                    .iter()
+                    .all(|v| v.as_str().is_some_and(|field| add_field(field, fields)))
+            }),
+            // Without `fields` these search the index's default fields.
+            "simple_query_string" | "multi_match" | "combined_fields" => {
+                value.get("fields").is_some() && referenced_fields(value, fields)
+            }
+            "term" | "terms" | "match" | "match_phrase" | "prefix" | "wildcard" | "regexp"
+            | "fuzzy" | "range" => {
+                match value {
+                    Value::Object(object) => object
+                        .keys()
+                        .filter(|k| !matches!(k.as_str(), "boost" | "_name"))
+                        .all(|field| add_field(field, fields)),
+                    // `multi_terms` lists its sources as `{"terms": [{"field": ...}, ...]}`.
+                    Value::Array(values) => values
+                        .iter()
+                        .all(|v| v.is_object() && referenced_fields(v, fields)),
+                    _ => false,
+                }
+            }
+            _ => referenced_fields(value, fields),
+        }),
+        Value::Array(values) => values.iter().all(|v| referenced_fields(v, fields)),
+        _ => true,
+    }
+}
+
+/// Adds `field` without any `^boost` suffix. Wildcard patterns are refused.
+fn add_field(field: &str, fields: &mut Vec<String>) -> bool {
+    let field = field.split_once('^').map_or(field, |(name, _)| name);
+    if field.contains('*') {
+        return false;
+    }
+    fields.push(field.to_string());
+    true
+}
+
+#[async_trait]
+impl RestMiddleware for FieldLevelSecurity {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        let search = if request.has_content() {
+            Some(request.content_value()?)
+        } else {
+            None
+        };
+        let roles = (self.roles)(&request);
+
+        let mut response = next.run(request, context).await?;
+        if !response.content_type.contains("json") || response.content.is_empty() {
+            return Ok(response);
+        }
+
+        let mut body: Value = serde_json::from_slice(&response.content).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to parse search response: {}", e))
+        })?;
+        self.filter_for_roles(&roles, search.as_ref(), &mut body);
+        response.content = serde_json::to_vec(&body).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize search response: {}", e))
+        })?;
+        Ok(response)
+    }
+}
+
+impl Default for FieldLevelSecurity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::Method;
+    use serde_json::json;
+
+    fn security() -> FieldLevelSecurity {
+        FieldLevelSecurity::new()
+            .role(
+                "analyst",
+                FieldRule::allow_all().include(["title", "author.name", "tags"]),
+            )
+            .role(
+                "auditor",
+                FieldRule::allow_all().exclude(["author.email", "salary"]),
+            )
+            .default_rule(FieldRule::deny_all())
+    }
+
+    fn request(roles: &str) -> ExtensionRestRequest {
+        let mut request = ExtensionRestRequest::new(Method::Post, "/search");
+        request.principal_token = format!("alice||{}|", roles);
+        request
+    }
+
+    fn response() -> Value {
+        json!({
+            "hits": {"hits": [{
+                "_id": "1",
+                "_source": {"title": "t", "salary": 100, "author": {"name": "n", "email": "e@x"}, "tags": ["a"]},
+                "fields": {"author.email": ["e@x"], "title": ["t"]},
+                "highlight": {"salary": ["<em>100</em>"], "title": ["<em>t</em>"]},
+                "inner_hits": {"comments": {"hits": {"hits": [{"_source": {"salary": 1, "title": "c"}}]}}}
+            }]},
+            "aggregations": {
+                "sterms#by_tag": {"buckets": [{
+                    "key": "a",
+                    "doc_count": 1,
+                    "avg_salary": {"value": 100.0},
+                    "latest": {"hits": {"hits": [{"_source": {"salary": 100, "title": "t"}}]}}
+                }]},
+                "by_email": {"buckets": [{"key": "e@x", "doc_count": 1}]},
+                "multi_terms#by_pair": {"buckets": [{"key": ["a", 100], "doc_count": 1}]},
+                "rich": {"doc_count": 1},
+                "matching": {"doc_count": 1},
+                "unknown": {"value": 1}
+            }
+        })
+    }
+
+    #[test]
+    fn test_masked_fields_never_leak() {
+        let search = json!({"aggs": {
+            "by_tag": {"terms": {"field": "tags"}, "aggs": {
+                "avg_salary": {"avg": {"field": "salary"}},
+                "latest": {"top_hits": {"size": 1}}
+            }},
+            "by_email": {"terms": {"field": "author.email"}},
+            "by_pair": {"multi_terms": {"terms": [{"field": "tags"}, {"field": "salary"}]}},
+            "rich": {"filter": {"range": {"salary": {"gte": 1000}}}},
+            "matching": {"filter": {"query_string": {"query": "salary:>1000"}}}
+        }});
+
+        let mut body = response();
+        security().filter_response(&request("analyst"), Some(&search), &mut body);
+        let text = body.to_string();
+        assert!(
+            !text.contains("salary") && !text.contains("e@x") && !text.contains("100"),
+            "{}",
+            text
+        );
+
+        let hit = &body["hits"]["hits"][0];
+        assert_eq!(
+            hit["_source"],
+            json!({"title": "t", "author": {"name": "n"}, "tags": ["a"]})
+        );
+        assert_eq!(hit["highlight"], json!({"title": ["<em>t</em>"]}));
+        assert_eq!(
+            hit["inner_hits"]["comments"]["hits"]["hits"][0]["_source"],
+            json!({"title": "c"})
+        );
+        assert_eq!(
+            body["aggregations"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["sterms#by_tag"]
+        );
+        assert_eq!(
+            body["aggregations"]["sterms#by_tag"]["buckets"][0]["latest"]["hits"]["hits"][0]
+                ["_source"],
+            json!({"title": "t"})
+        );
+
+        // Roles combine: the auditor may see salary-free fields, including author.name via analyst.
+        let mut body = response();
+        security().filter_response(&request("auditor, analyst"), Some(&search), &mut body);
+        assert_eq!(
+            body["hits"]["hits"][0]["_source"]["author"],
+            json!({"name": "n"})
+        );
+        assert!(!body.to_string().contains("e@x"));
+
+        let mut body = response();
+        security().filter_response(&request(""), Some(&search), &mut body);
+        assert_eq!(body["hits"]["hits"][0]["_source"], json!({}));
+        assert_eq!(body["aggregations"], json!({}));
+    }
+
+    #[test]
+    fn test_roles_come_from_principal() {
+        let search = json!({});
+
+        // A client-supplied header does not grant roles.
+        let mut body = response();
+        let forged =
+            ExtensionRestRequest::new(Method::Post, "/search").with_header("X-Roles", "auditor");
+        security().filter_response(&forged, Some(&search), &mut body);
+        assert_eq!(body["hits"]["hits"][0]["_source"], json!({}));
+
+        let mut body = response();
+        let proxied = FieldLevelSecurity::roles_from_trusted_proxy_header("X-Roles")
+            .role("auditor", FieldRule::allow_all().exclude(["salary"]))
+            .default_rule(FieldRule::deny_all());
+        proxied.filter_response(&forged, Some(&search), &mut body);
+        assert_eq!(body["hits"]["hits"][0]["_source"]["title"], json!("t"));
+    }
+}
@@ -0,0 +1,324 @@
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Method, RestHandler, RestMiddleware, RestResponse, Route};
+
+pub fn get(path: impl Into<String>) -> Route {
+    Route::new(Method::Get, path)
+}
+
+pub fn post(path: impl Into<String>) -> Route {
+    Route::new(Method::Post, path)
+}
+
+pub fn put(path: impl Into<String>) -> Route {
+    Route::new(Method::Put, path)
+}
+
+pub fn delete(path: impl Into<String>) -> Route {
+    Route::new(Method::Delete, path)
+}
+
+pub fn patch(path: impl Into<String>) -> Route {
+    Route::new(Method::Patch, path)
+}
+
+pub fn head(path: impl Into<String>) -> Route {
+    Route::new(Method::Head, path)
+}
+
+/// An async function usable as a route handler, such as
+/// `async fn list_jobs(request: ExtensionRestRequest, context: &ExtensionContext) -> Result<RestResponse, ExtensionError>`.
+pub trait RouteFn<'a>: Fn(ExtensionRestRequest, &'a ExtensionContext) -> Self::Future {
+    type Future: Future<Output = Result<RestResponse, ExtensionError>> + Send + 'a;
+}
+
+impl<'a, F, Fut> RouteFn<'a> for F
+where
+    F: Fn(ExtensionRestRequest, &'a ExtensionContext) -> Fut,
+    Fut: Future<Output = Result<RestResponse, ExtensionError>> + Send + 'a,
+{
+    type Future = Fut;
+}
+
+#[async_trait]
+trait ErasedRouteFn: Send + Sync {
+    async fn call(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError>;
+}
+
+struct FnRoute<F>(F);
+
+#[async_trait]
+impl<F> ErasedRouteFn for FnRoute<F>
+where
+    F: for<'a> RouteFn<'a> + Send + Sync,
+{
+    async fn call(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        (self.0)(request, context).await
+    }
+}
+
+enum Target {
+    Fn(Arc<dyn ErasedRouteFn>),
+    Handler(Arc<dyn RestHandler>),
+}
+
+struct GroupEntry {
+    route: Route,
+    middleware: Vec<Arc<dyn RestMiddleware>>,
+    target: Target,
+}
+
+/// Routes sharing a path prefix, middleware and naming, e.g.
+/// `RouteGroup::new("/jobs").middleware(auth).route(get("/"), list_jobs).route(get("/{id}"), get_job)`.
+///
+/// Group middleware runs outside middleware of nested groups and of the
+/// handlers themselves. Routes without a name are named
+/// `<prefix>:<method>_<path>` once `named` is set.
+pub struct RouteGroup {
+    prefix: String,
+    name_prefix: Option<String>,
+    middleware: Vec<Arc<dyn RestMiddleware>>,
+    entries: Vec<GroupEntry>,
+}
+
+impl RouteGroup {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        RouteGroup {
+            prefix: normalize_prefix(&prefix.into()),
+            name_prefix: None,
+            middleware: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Name the group's unnamed routes after `prefix`.
+    pub fn named(mut self, prefix: impl Into<String>) -> Self {
+        self.name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Middleware for every route in the group, outermost first.
+    pub fn middleware(mut self, middleware: Arc<dyn RestMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    pub fn route<F>(mut self, route: Route, handler: F) -> Self
+    where
+        F: for<'a> RouteFn<'a> + Send + Sync + 'static,
+    {
+        self.entries.push(GroupEntry {
+            route,
+            middleware: Vec::new(),
+            target: Target::Fn(Arc::new(FnRoute(handler))),
+        });
+        self
+    }
+
+    /// Mount every route of an existing handler under the group.
+    pub fn handler(mut self, handler: Arc<dyn RestHandler>) -> Self {
+        for route in handler.routes() {
+            let middleware = handler.middleware(&route);
+            self.entries.push(GroupEntry {
+                route,
+                middleware,
+                target: Target::Handler(handler.clone()),
+            });
+        }
+        self
+    }
+
+    /// Mount `group` under this one, inheriting its prefix, middleware and
+    /// naming unless the nested group sets its own name prefix.
+    pub fn nest(mut self, group: RouteGroup) -> Self {
+        self.entries.extend(group.into_entries());
+        self
+    }
+
+    fn into_entries(self) -> Vec<GroupEntry> {
+        let RouteGroup {
+            prefix,
+            name_prefix,
+            middleware,
+            entries,
+        } = self;
+        entries
+            .into_iter()
+            .map(|mut entry| {
+                entry.route.path = join_path(&prefix, &entry.route.path);
+                if let (None, Some(name_prefix)) = (&entry.route.name, &name_prefix) {
+                    entry.route.name = Some(route_name(name_prefix, &entry.route));
+                }
+                entry.middleware = middleware.iter().cloned().chain(entry.middleware).collect();
+                entry
+            })
+            .collect()
+    }
+
+    /// One handler per route, ready for `RestRouter::register` or
+    /// `Extension::rest_handlers`.
+    pub fn into_handlers(self) -> Vec<Arc<dyn RestHandler>> {
+        self.into_entries()
+            .into_iter()
+            .map(|entry| Arc::new(GroupRoute(entry)) as Arc<dyn RestHandler>)
+            .collect()
+    }
+}
+
+struct GroupRoute(GroupEntry);
+
+#[async_trait]
+impl RestHandler for GroupRoute {
+    fn routes(&self) -> Vec<Route> {
+        vec![self.0.route.clone()]
+    }
+
+    fn middleware(&self, _route: &Route) -> Vec<Arc<dyn RestMiddleware>> {
+        self.0.middleware.clone()
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        match &self.0.target {
+            Target::Fn(handler) => handler.call(request, context).await,
+            Target::Handler(handler) => handler.handle(request, context).await,
+        }
+    }
+}
+
+fn normalize_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
+fn join_path(prefix: &str, path: &str) -> String {
+    let path = path.trim_matches('/');
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, true) => "/".to_string(),
+        (false, true) => prefix.to_string(),
+        _ => format!("{}/{}", prefix, path),
+    }
+}
+
+fn route_name(prefix: &str, route: &Route) -> String {
+    let segments: Vec<&str> = route
+        .path
+        .split('/')
+        .map(|segment| segment.trim_matches(|c| c == '{' || c == '}'))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let method = route.method.name().to_lowercase();
+    if segments.is_empty() {
+        format!("{}:{}", prefix, method)
+    } else {
+        format!("{}:{}_{}", prefix, method, segments.join("_"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::{Next, RestRouter};
+    use crate::transport::TransportClient;
+
+    async fn list_jobs(
+        _request: ExtensionRestRequest,
+        _context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        Ok(RestResponse::ok().text("jobs"))
+    }
+
+    async fn get_job(
+        request: ExtensionRestRequest,
+        _context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        let id: String = request.required_param("id")?;
+        Ok(RestResponse::ok().text(format!("job {}", id)))
+    }
+
+    struct Tag(&'static str);
+
+    #[async_trait]
+    impl RestMiddleware for Tag {
+        async fn handle(
+            &self,
+            request: ExtensionRestRequest,
+            context: &ExtensionContext,
+            next: Next<'_>,
+        ) -> Result<RestResponse, ExtensionError> {
+            let response = next.run(request, context).await?;
+            Ok(response.with_header("X-Chain", self.0))
+        }
+    }
+
+    #[test]
+    fn test_route_group() {
+        let group = RouteGroup::new("/myext/")
+            .named("myext")
+            .middleware(Arc::new(Tag("outer")))
+            .route(get("/jobs"), list_jobs)
+            .nest(
+                RouteGroup::new("/jobs")
+                    .middleware(Arc::new(Tag("inner")))
+                    .route(get("/{id}"), get_job)
+                    .route(delete("/{id}").named("myext:remove_job"), get_job),
+            );
+        let handlers = group.into_handlers();
+        let routes: Vec<(String, Option<String>)> = handlers
+            .iter()
+            .flat_map(|h| h.routes())
+            .map(|r| (r.path, r.name))
+            .collect();
+        assert_eq!(
+            routes,
+            vec![
+                (
+                    "/myext/jobs".to_string(),
+                    Some("myext:get_myext_jobs".to_string())
+                ),
+                (
+                    "/myext/jobs/{id}".to_string(),
+                    Some("myext:get_myext_jobs_id".to_string())
+                ),
+                (
+                    "/myext/jobs/{id}".to_string(),
+                    Some("myext:remove_job".to_string())
+                ),
+            ]
+        );
+
+        let mut router = RestRouter::new();
+        for handler in handlers {
+            router.register(handler).unwrap();
+        }
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9300)))
+            .build()
+            .unwrap();
+        let request = ExtensionRestRequest::new(Method::Get, "/myext/jobs/7");
+        let response = context
+            .thread_pool
+            .block_on(router.handle(request, &context))
+            .unwrap();
+        assert_eq!(response.content, b"job 7");
+        assert_eq!(response.headers["X-Chain"], vec!["inner", "outer"]);
+    }
+}
@@ -0,0 +1,164 @@
+use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::interface::proto_request_bytes;
+use crate::proto::{ExtensionIdentity, RegisterRestActions};
+use crate::rest::{ExtensionRestRequest, Method, RestMiddleware, RestResponse};
+use crate::transport::TransportClient;
+
+pub const REGISTER_REST_ACTIONS_ACTION: &str = "internal:discovery/registerrestactions";
+pub const REST_EXECUTE_ON_EXTENSION_ACTION: &str =
+    "internal:extensions/restexecuteonextensiontaction";
+
+/// A method and path served by a `RestHandler`. Paths are relative to the
+/// `/_extensions/_<unique_id>` prefix OpenSearch adds when registering them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Route {
+    pub method: Method,
+    pub path: String,
+    pub name: Option<String>,
+    pub deprecation_message: Option<String>,
+    pub params: Vec<String>,
+}
+
+impl Route {
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Route {
+            method,
+            path: path.into(),
+            name: None,
+            deprecation_message: None,
+            params: Vec::new(),
+        }
+    }
+
+    /// A route kept for backwards compatibility. It still reaches its handler,
+    /// but responses carry a `Warning` header with `message`.
+    pub fn deprecated(method: Method, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Route {
+            deprecation_message: Some(message.into()),
+            ..Route::new(method, path)
+        }
+    }
+
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecation_message.is_some()
+    }
+
+    /// Give the route a unique action name, used by OpenSearch for permissions.
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Query parameters the route accepts besides its path parameters and
+    /// the response parameters. Requests carrying any other parameter are
+    /// rejected before the handler runs.
+    pub fn params<S: Into<String>>(mut self, names: impl IntoIterator<Item = S>) -> Self {
+        self.params.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Encoding expected in `RegisterRestActions`: `"METHOD /path [name]"`,
+    /// or `"METHOD /path message"` for deprecated routes.
+    pub fn registration_string(&self) -> String {
+        match self.deprecation_message.as_ref().or(self.name.as_ref()) {
+            Some(suffix) => format!("{} {} {}", self.method, self.path, suffix),
+            None => format!("{} {}", self.method, self.path),
+        }
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)
+    }
+}
+
+#[async_trait]
+pub trait RestHandler: Send + Sync + 'static {
+    fn routes(&self) -> Vec<Route>;
+
+    /// Whether the handler keeps serving while the extension is paused, as
+    /// administrative endpoints should. Such handlers should also gate their
+    /// routes through `middleware`, since nothing else guards them.
+    fn available_when_paused(&self) -> bool {
+        false
+    }
+
+    /// Middleware run for `route` only, inside any global middleware.
+    fn middleware(&self, _route: &Route) -> Vec<Arc<dyn RestMiddleware>> {
+        Vec::new()
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError>;
+}
+
+pub fn register_rest_actions_bytes(unique_id: &str, routes: &[Route]) -> Vec<u8> {
+    proto_request_bytes(&RegisterRestActions {
+        identity: Some(ExtensionIdentity {
+            unique_id: unique_id.to_string(),
+        }),
+        rest_actions: routes
+            .iter()
+            .filter(|route| !route.is_deprecated())
+            .map(Route::registration_string)
+            .collect(),
+        deprecated_rest_actions: routes
+            .iter()
+            .filter(|route| route.is_deprecated())
+            .map(Route::registration_string)
+            .collect(),
+    })
+}
+
+pub async fn register_rest_actions(
+    client: &TransportClient,
+    unique_id: &str,
+    routes: &[Route],
+) -> Result<(), ExtensionError> {
+    client
+        .expect_ack(
+            REGISTER_REST_ACTIONS_ACTION,
+            &register_rest_actions_bytes(unique_id, routes),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_rest_actions_encoding() {
+        let routes = vec![
+            Route::new(Method::Get, "/hello"),
+            Route::new(Method::Put, "/hello/{name}").named("hello_world:greet"),
+            Route::deprecated(
+                Method::Get,
+                "/hi",
+                "[GET /hi] is deprecated, use [GET /hello]",
+            ),
+        ];
+
+        let decoded: RegisterRestActions = crate::interface::parse_proto_request(
+            &register_rest_actions_bytes("hello-world", &routes),
+        )
+        .unwrap();
+        assert_eq!(decoded.identity.unwrap().unique_id, "hello-world");
+        assert_eq!(
+            decoded.rest_actions,
+            vec!["GET /hello", "PUT /hello/{name} hello_world:greet"]
+        );
+        assert_eq!(
+            decoded.deprecated_rest_actions,
+            vec!["GET /hi [GET /hi] is deprecated, use [GET /hello]"]
+        );
+    }
+}
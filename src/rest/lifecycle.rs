@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Method, RestHandler, RestMiddleware, RestResponse, Route};
+
+/// Shows the lifecycle state at `GET /_lifecycle` and pauses or resumes the
+/// extension at `POST /_lifecycle/pause` and `POST /_lifecycle/resume`.
+/// While paused the extension stays registered but refuses other requests.
+///
+/// The handler is administrative and keeps serving while paused, so it
+/// takes the `gate` middleware that must admit a caller to any of its
+/// routes, e.g. `requires_permissions(&client, &["cluster:admin/myext/lifecycle"])`.
+pub struct LifecycleHandler {
+    gate: Arc<dyn RestMiddleware>,
+}
+
+impl LifecycleHandler {
+    pub fn new(gate: Arc<dyn RestMiddleware>) -> Self {
+        LifecycleHandler { gate }
+    }
+}
+
+#[async_trait]
+impl RestHandler for LifecycleHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![
+            Route::new(Method::Get, "/_lifecycle"),
+            Route::new(Method::Post, "/_lifecycle/pause"),
+            Route::new(Method::Post, "/_lifecycle/resume"),
+        ]
+    }
+
+    fn available_when_paused(&self) -> bool {
+        true
+    }
+
+    fn middleware(&self, _route: &Route) -> Vec<Arc<dyn RestMiddleware>> {
+        vec![self.gate.clone()]
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        let lifecycle = &context.lifecycle;
+        match request.path.as_str() {
+            "/_lifecycle/pause" => lifecycle
+                .pause()
+                .await
+                .map_err(|e| ExtensionError::invalid_request(e.to_string()))?,
+            "/_lifecycle/resume" => lifecycle
+                .resume()
+                .await
+                .map_err(|e| ExtensionError::invalid_request(e.to_string()))?,
+            _ => {}
+        }
+        RestResponse::ok()
+            .negotiate(&request)
+            .json(&json!({ "state": lifecycle.current_state().await }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::lifecycle::ExtensionState;
+    use crate::rest::middleware::AuthCheck;
+    use crate::rest::{RestRouter, RestStatus};
+    use crate::transport::TransportClient;
+
+    struct HelloHandler;
+
+    #[async_trait]
+    impl RestHandler for HelloHandler {
+        fn routes(&self) -> Vec<Route> {
+            vec![Route::new(Method::Get, "/hello")]
+        }
+
+        async fn handle(
+            &self,
+            _request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            Ok(RestResponse::ok().text("hi"))
+        }
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut router = RestRouter::new();
+        let gate = Arc::new(AuthCheck::new(|request| request.principal_token == "admin"));
+        router
+            .register(Arc::new(LifecycleHandler::new(gate)))
+            .unwrap();
+        router.register(Arc::new(HelloHandler)).unwrap();
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9300)))
+            .build()
+            .unwrap();
+        let call_as = |principal: &str, method: Method, path: &str| {
+            let mut request = ExtensionRestRequest::new(method, path);
+            request.principal_token = principal.to_string();
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+        };
+        let call = |method: Method, path: &str| call_as("admin", method, path);
+
+        assert_eq!(
+            call(Method::Post, "/_lifecycle/pause").status,
+            RestStatus::BadRequest
+        );
+        context.thread_pool.block_on(async {
+            for state in [
+                ExtensionState::Initializing,
+                ExtensionState::Initialized,
+                ExtensionState::Running,
+            ] {
+                context.lifecycle.transition_to(state).await.unwrap();
+            }
+        });
+
+        let paused = call(Method::Post, "/_lifecycle/pause");
+        assert_eq!(
+            String::from_utf8(paused.content).unwrap(),
+            r#"{"state":"paused"}"#
+        );
+        let request = ExtensionRestRequest::new(Method::Get, "/hello")
+            .with_param("pretty", "true")
+            .with_param("name", "x");
+        let refused = context
+            .thread_pool
+            .block_on(router.handle(request, &context))
+            .unwrap();
+        assert_eq!(refused.status, RestStatus::ServiceUnavailable);
+        let mut consumed = refused.consumed_params;
+        consumed.sort();
+        assert_eq!(consumed, ["name", "pretty"]);
+        assert_eq!(call(Method::Get, "/_lifecycle").status, RestStatus::Ok);
+        assert_eq!(
+            call_as("", Method::Post, "/_lifecycle/resume").status,
+            RestStatus::Unauthorized
+        );
+        assert_eq!(
+            call_as("bob", Method::Post, "/_lifecycle/resume").status,
+            RestStatus::Forbidden
+        );
+        assert!(context.thread_pool.block_on(context.lifecycle.is_paused()));
+
+        call(Method::Post, "/_lifecycle/resume");
+        assert_eq!(call(Method::Get, "/hello").status, RestStatus::Ok);
+    }
+}
@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Method, RestHandler, RestMiddleware, RestResponse, Route};
+
+#[derive(Debug, Deserialize)]
+struct LogLevelChange {
+    level: String,
+    #[serde(default)]
+    module: Option<String>,
+}
+
+/// Shows the log levels at `GET /_logging` and changes them at
+/// `PUT /_logging` with a body like `{"level": "debug"}`, optionally
+/// scoped with `"module"`.
+///
+/// The handler is administrative and keeps serving while paused, so it
+/// takes the `gate` middleware that must admit a caller to any of its
+/// routes, e.g. `requires_permissions(&client, &["cluster:admin/myext/logging"])`.
+pub struct LoggingHandler {
+    gate: Arc<dyn RestMiddleware>,
+}
+
+impl LoggingHandler {
+    pub fn new(gate: Arc<dyn RestMiddleware>) -> Self {
+        LoggingHandler { gate }
+    }
+}
+
+#[async_trait]
+impl RestHandler for LoggingHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![
+            Route::new(Method::Get, "/_logging"),
+            Route::new(Method::Put, "/_logging"),
+        ]
+    }
+
+    fn available_when_paused(&self) -> bool {
+        true
+    }
+
+    fn middleware(&self, _route: &Route) -> Vec<Arc<dyn RestMiddleware>> {
+        vec![self.gate.clone()]
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        let logging = context.logging();
+        if request.method == Method::Put {
+            let change: LogLevelChange = request.content_as()?;
+            match &change.module {
+                Some(module) => logging.set_module_level(module, &change.level)?,
+                None => logging.set_level(&change.level)?,
+            }
+        }
+        RestResponse::ok()
+            .negotiate(&request)
+            .json(&logging.levels())
+    }
+}
@@ -0,0 +1,575 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+use tracing::info;
+
+use crate::client::SdkClient;
+use crate::extension::metadata::ExtensionMetrics;
+use crate::extension::resilience::{AdaptiveLimiter, RateLimiter};
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, RestHandler, RestResponse, RestStatus, Route};
+
+/// Wraps handler calls. Implementations either answer the request
+/// themselves or pass it on with `next.run(request, context)`.
+#[async_trait]
+pub trait RestMiddleware: Send + Sync + 'static {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError>;
+}
+
+/// The rest of the chain for the matched route, ending in its handler.
+pub struct Next<'a> {
+    route: &'a Route,
+    handler: &'a dyn RestHandler,
+    middleware: &'a [Arc<dyn RestMiddleware>],
+}
+
+impl<'a> Next<'a> {
+    pub fn new(
+        route: &'a Route,
+        handler: &'a dyn RestHandler,
+        middleware: &'a [Arc<dyn RestMiddleware>],
+    ) -> Self {
+        Next {
+            route,
+            handler,
+            middleware,
+        }
+    }
+
+    pub fn route(&self) -> &Route {
+        self.route
+    }
+
+    pub async fn run(
+        self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => {
+                let next = Next {
+                    middleware: rest,
+                    ..self
+                };
+                first.handle(request, context, next).await
+            }
+            None => self.handler.handle(request, context).await,
+        }
+    }
+}
+
+/// Logs every request with its status and duration.
+pub struct RequestLogging;
+
+#[async_trait]
+impl RestMiddleware for RequestLogging {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        let (method, path) = (request.method, request.path.clone());
+        let start = Instant::now();
+        let result = next.run(request, context).await;
+
+        match &result {
+            Ok(response) => info!(
+                "{} {} -> {} in {:?}",
+                method,
+                path,
+                response.status.code(),
+                start.elapsed()
+            ),
+            Err(e) => info!(
+                "{} {} -> failed in {:?}: {}",
+                method,
+                path,
+                start.elapsed(),
+                e
+            ),
+        }
+        result
+    }
+}
+
+type AuthPredicate = dyn Fn(&ExtensionRestRequest) -> bool + Send + Sync;
+
+/// Rejects requests that fail `check` with 403, and requests without a
+/// principal with 401.
+pub struct AuthCheck {
+    check: Box<AuthPredicate>,
+}
+
+impl AuthCheck {
+    pub fn new(check: impl Fn(&ExtensionRestRequest) -> bool + Send + Sync + 'static) -> Self {
+        AuthCheck {
+            check: Box::new(check),
+        }
+    }
+
+    /// Only require OpenSearch to have forwarded a principal token.
+    pub fn require_principal() -> Self {
+        Self::new(|_| true)
+    }
+}
+
+#[async_trait]
+impl RestMiddleware for AuthCheck {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        if request.principal_token.is_empty() {
+            return Ok(RestResponse::error(
+                RestStatus::Unauthorized,
+                format!(
+                    "missing authentication credentials for REST request [{}]",
+                    request.path
+                ),
+            ));
+        }
+        if !(self.check)(&request) {
+            return Ok(RestResponse::error(
+                RestStatus::Forbidden,
+                format!("no permissions for [{}]", next.route()),
+            ));
+        }
+        next.run(request, context).await
+    }
+}
+
+/// Rejects callers lacking any of a route's permissions with 403, checking
+/// them with the security plugin. Requests without a principal get 401.
+pub struct RequirePermissions {
+    client: SdkClient,
+    permissions: Vec<String>,
+}
+
+impl RequirePermissions {
+    pub fn new(client: SdkClient, permissions: &[&str]) -> Self {
+        RequirePermissions {
+            client,
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+/// Middleware for a route or `RouteGroup` requiring `permissions`, e.g.
+/// `requires_permissions(&client, &["cluster:admin/myext/write"])`.
+pub fn requires_permissions(client: &SdkClient, permissions: &[&str]) -> Arc<dyn RestMiddleware> {
+    Arc::new(RequirePermissions::new(client.clone(), permissions))
+}
+
+#[async_trait]
+impl RestMiddleware for RequirePermissions {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        let Some(principal) = request.principal() else {
+            return Ok(RestResponse::error(
+                RestStatus::Unauthorized,
+                format!(
+                    "missing authentication credentials for REST request [{}]",
+                    request.path
+                ),
+            ));
+        };
+        let missing = self
+            .client
+            .security()
+            .missing_permissions(&principal, &self.permissions)
+            .await?;
+        if !missing.is_empty() {
+            let user = principal.user().map(|user| user.name).unwrap_or_default();
+            return Ok(RestResponse::exception(
+                RestStatus::Forbidden,
+                "security_exception",
+                format!(
+                    "no permissions for [{}] and User [name={}]",
+                    missing.join(", "),
+                    user
+                ),
+            ));
+        }
+        next.run(request, context).await
+    }
+}
+
+/// Buckets kept before idle ones are dropped, bounding memory when limits
+/// are per principal.
+const MAX_RATE_LIMIT_BUCKETS: usize = 10_000;
+
+/// A bucket's route, when it has a limit of its own, and principal.
+type BucketKey = (Option<Route>, String);
+
+/// Limits requests to `rate` per second with bursts up to `burst`; excess
+/// requests get 429. By default all routes share one bucket. Routes given
+/// their own limit with `route` get a bucket each, and `per_principal`
+/// gives every principal its own buckets.
+pub struct RateLimit {
+    rate: f64,
+    burst: u32,
+    routes: HashMap<Route, (f64, u32)>,
+    per_principal: bool,
+    buckets: Mutex<HashMap<BucketKey, Arc<RateLimiter>>>,
+}
+
+impl RateLimit {
+    pub fn per_second(rate: f64, burst: u32) -> Self {
+        RateLimit {
+            rate,
+            burst,
+            routes: HashMap::new(),
+            per_principal: false,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Limit `route` to `rate` requests per second instead of the default.
+    pub fn route(mut self, route: Route, rate: f64, burst: u32) -> Self {
+        self.routes.insert(route, (rate, burst));
+        self
+    }
+
+    /// Apply the limits to each principal separately. Requests without one
+    /// share a bucket.
+    pub fn per_principal(mut self) -> Self {
+        self.per_principal = true;
+        self
+    }
+
+    fn limiter(
+        &self,
+        route: &Route,
+        request: &ExtensionRestRequest,
+    ) -> Result<Arc<RateLimiter>, ExtensionError> {
+        let (key_route, (rate, burst)) = match self.routes.get(route) {
+            Some(limit) => (Some(route.clone()), *limit),
+            None => (None, (self.rate, self.burst)),
+        };
+        let principal = if self.per_principal {
+            request.principal_token.clone()
+        } else {
+            String::new()
+        };
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.len() >= MAX_RATE_LIMIT_BUCKETS {
+            buckets.retain(|_, limiter| matches!(limiter.is_idle(), Ok(false)));
+        }
+        Ok(buckets
+            .entry((key_route, principal))
+            .or_insert_with(|| Arc::new(RateLimiter::per_second(rate, burst)))
+            .clone())
+    }
+}
+
+#[async_trait]
+impl RestMiddleware for RateLimit {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        match self.limiter(next.route(), &request)?.try_acquire() {
+            Err(ExtensionError::Throttled { retry_after, .. }) => {
+                let wait = retry_after.unwrap_or_default().as_secs_f64();
+                let retry_after = wait.ceil().clamp(1.0, u32::MAX as f64) as u64;
+                return Ok(RestResponse::error(
+                    RestStatus::TooManyRequests,
+                    format!("rate limit exceeded for [{}]", next.route()),
+                )
+                .with_header("Retry-After", retry_after.to_string()));
+            }
+            result => result?,
+        }
+        next.run(request, context).await
+    }
+}
+
+/// Caps concurrent requests with an `AdaptiveLimiter`, so the limit follows
+/// handler latency. Requests over the limit get 429.
+pub struct AdaptiveConcurrency {
+    limiter: Arc<AdaptiveLimiter>,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(limiter: Arc<AdaptiveLimiter>) -> Self {
+        AdaptiveConcurrency { limiter }
+    }
+
+    pub fn limiter(&self) -> &Arc<AdaptiveLimiter> {
+        &self.limiter
+    }
+}
+
+#[async_trait]
+impl RestMiddleware for AdaptiveConcurrency {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        self.limiter.call(|| next.run(request, context)).await
+    }
+}
+
+/// Records request count, failures and durations per route. Responses with
+/// a 5xx status count as failures.
+#[derive(Default)]
+pub struct ResponseTimeMetrics {
+    routes: Mutex<HashMap<Route, ExtensionMetrics>>,
+}
+
+impl ResponseTimeMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route_metrics(&self, route: &Route) -> Option<ExtensionMetrics> {
+        self.routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(route)
+            .cloned()
+    }
+
+    pub fn snapshot(&self) -> HashMap<Route, ExtensionMetrics> {
+        self.routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[async_trait]
+impl RestMiddleware for ResponseTimeMetrics {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        let route = next.route().clone();
+        let start = Instant::now();
+        let result = next.run(request, context).await;
+
+        let success = matches!(&result, Ok(response) if response.status.code() < 500);
+        self.routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(route)
+            .or_default()
+            .record_request(start.elapsed().as_secs_f64() * 1000.0, success);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::{Method, RestRouter};
+    use crate::transport::TransportClient;
+
+    struct PingHandler;
+
+    #[async_trait]
+    impl RestHandler for PingHandler {
+        fn routes(&self) -> Vec<Route> {
+            vec![
+                Route::new(Method::Get, "/ping"),
+                Route::new(Method::Get, "/admin"),
+            ]
+        }
+
+        fn middleware(&self, route: &Route) -> Vec<Arc<dyn RestMiddleware>> {
+            if route.path == "/admin" {
+                vec![Arc::new(AuthCheck::new(|request| {
+                    request.principal_token == "admin"
+                }))]
+            } else {
+                Vec::new()
+            }
+        }
+
+        async fn handle(
+            &self,
+            _request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            Ok(RestResponse::ok().text("pong"))
+        }
+    }
+
+    #[test]
+    fn test_middleware_chain() {
+        let metrics = Arc::new(ResponseTimeMetrics::new());
+        let mut router = RestRouter::new()
+            .with_middleware(metrics.clone())
+            .with_middleware(Arc::new(RateLimit::per_second(0.001, 3)));
+        router.register(Arc::new(PingHandler)).unwrap();
+
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9300)))
+            .build()
+            .unwrap();
+        let status = |path: &str, principal: &str| {
+            let mut request = ExtensionRestRequest::new(Method::Get, path);
+            request.principal_token = principal.to_string();
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+                .status
+        };
+
+        assert_eq!(status("/ping", ""), RestStatus::Ok);
+        assert_eq!(status("/admin", ""), RestStatus::Unauthorized);
+        assert_eq!(status("/admin", "reader"), RestStatus::Forbidden);
+        assert_eq!(status("/admin", "admin"), RestStatus::TooManyRequests);
+
+        let ping = metrics
+            .route_metrics(&Route::new(Method::Get, "/ping"))
+            .unwrap();
+        assert_eq!(ping.requests_total, 1);
+        assert_eq!(
+            metrics
+                .route_metrics(&Route::new(Method::Get, "/admin"))
+                .unwrap()
+                .requests_total,
+            3
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_per_route_and_principal() {
+        let mut router = RestRouter::new().with_middleware(Arc::new(
+            RateLimit::per_second(0.001, 1)
+                .route(Route::new(Method::Get, "/admin"), 0.001, 2)
+                .per_principal(),
+        ));
+        router.register(Arc::new(PingHandler)).unwrap();
+
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9300)))
+            .build()
+            .unwrap();
+        let call = |path: &str, principal: &str| {
+            let mut request = ExtensionRestRequest::new(Method::Get, path);
+            request.principal_token = principal.to_string();
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+        };
+
+        assert_eq!(call("/ping", "alice").status, RestStatus::Ok);
+        let limited = call("/ping", "alice");
+        assert_eq!(limited.status, RestStatus::TooManyRequests);
+        assert_eq!(
+            limited.headers.get("Retry-After"),
+            Some(&vec!["1000".to_string()])
+        );
+        assert_eq!(call("/ping", "bob").status, RestStatus::Ok);
+
+        assert_eq!(call("/admin", "admin").status, RestStatus::Ok);
+        assert_eq!(call("/admin", "admin").status, RestStatus::Ok);
+        assert_eq!(call("/admin", "admin").status, RestStatus::TooManyRequests);
+    }
+
+    #[test]
+    fn test_require_permissions() {
+        use crate::client::RemoteExtensionActionResponse;
+        use crate::interface::Serialize as _;
+        use crate::rest::RouteGroup;
+        use crate::transport::inbound::{read_message, write_response};
+        use crate::transport::ThreadContext;
+
+        let context = ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9300)))
+            .build()
+            .unwrap();
+        let listener = context
+            .thread_pool
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        context.thread_pool.spawn(async move {
+            for granted in [true, false] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_message(&mut stream).await.unwrap().unwrap();
+                let body =
+                    serde_json::json!({"permissions": {"cluster:admin/myext/write": granted}});
+                let mut bytes = Vec::new();
+                RemoteExtensionActionResponse {
+                    success: true,
+                    response_bytes: body.to_string().into_bytes(),
+                }
+                .serialize(&mut bytes)
+                .unwrap();
+                write_response(
+                    &mut stream,
+                    &request.header,
+                    &ThreadContext::new(),
+                    &bytes,
+                    false,
+                )
+                .await
+                .unwrap();
+            }
+        });
+
+        async fn write(
+            _request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            Ok(RestResponse::ok())
+        }
+        let client = SdkClient::new(
+            Arc::new(TransportClient::new("127.0.0.1", port)),
+            "hello-world",
+        );
+        let group = RouteGroup::new("/")
+            .middleware(requires_permissions(
+                &client,
+                &["cluster:admin/myext/write"],
+            ))
+            .route(crate::rest::group::post("/write"), write);
+        let mut router = RestRouter::new();
+        for handler in group.into_handlers() {
+            router.register(handler).unwrap();
+        }
+        let call = |principal: &str| {
+            let mut request = ExtensionRestRequest::new(Method::Post, "/write");
+            request.principal_token = principal.to_string();
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+        };
+
+        assert_eq!(call("").status, RestStatus::Unauthorized);
+        assert_eq!(call("alice").status, RestStatus::Ok);
+        let denied = call("bob");
+        assert_eq!(denied.status, RestStatus::Forbidden);
+        let body: serde_json::Value = serde_json::from_slice(&denied.content).unwrap();
+        assert_eq!(body["error"]["type"], "security_exception");
+        assert_eq!(
+            body["error"]["reason"],
+            "no permissions for [cluster:admin/myext/write] and User [name=bob]"
+        );
+    }
+}
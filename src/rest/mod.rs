@@ -0,0 +1,41 @@
+pub mod client_policies;
+pub mod connections;
+pub mod diagnostics;
+pub mod error_mapper;
+pub mod execute;
+pub mod field_security;
+pub mod group;
+pub mod handler;
+pub mod lifecycle;
+pub mod logging;
+pub mod middleware;
+pub mod path;
+pub mod policy;
+pub mod query_cost;
+pub mod request;
+pub mod response;
+pub mod response_params;
+pub mod router;
+pub mod status;
+pub mod usage;
+
+pub use client_policies::ClientPoliciesHandler;
+pub use connections::ConnectionsHandler;
+pub use diagnostics::DiagnosticsHandler;
+pub use error_mapper::{DefaultErrorMapper, ErrorMapper};
+pub use execute::RestExecuteOnExtensionResponse;
+pub use field_security::{FieldLevelSecurity, FieldRule};
+pub use group::RouteGroup;
+pub use handler::{RestHandler, Route};
+pub use lifecycle::LifecycleHandler;
+pub use logging::LoggingHandler;
+pub use middleware::{Next, RestMiddleware};
+pub use path::PathTemplate;
+pub use policy::{CacheControl, ResponsePolicy};
+pub use query_cost::{QueryCost, QueryCostLimiter, QueryLimits};
+pub use request::{ExtensionRestRequest, Method};
+pub use response::RestResponse;
+pub use response_params::{FilterPath, ResponseParams};
+pub use router::RestRouter;
+pub use status::RestStatus;
+pub use usage::UsageReportHandler;
@@ -0,0 +1,162 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    /// `*`: any single segment
+    Wildcard,
+    /// `**`: any number of trailing segments, including none
+    CatchAll,
+}
+
+impl Segment {
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Literal(_) => 3,
+            Segment::Param(_) => 2,
+            Segment::Wildcard => 1,
+            Segment::CatchAll => 0,
+        }
+    }
+}
+
+/// Route path such as `/hello/{name}` or `/_plugins/myext/**`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+impl PathTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = split(template).collect();
+        let mut segments = Vec::with_capacity(parts.len());
+
+        for (index, part) in parts.iter().enumerate() {
+            let segment = match *part {
+                "**" if index + 1 == parts.len() => Segment::CatchAll,
+                "**" => return Err(format!("'**' must be the last segment in '{}'", template)),
+                "*" => Segment::Wildcard,
+                part if part.starts_with('{') && part.ends_with('}') => {
+                    let name = &part[1..part.len() - 1];
+                    if name.is_empty() {
+                        return Err(format!("Empty parameter name in '{}'", template));
+                    }
+                    Segment::Param(name.to_string())
+                }
+                part if part.contains(['{', '}']) => {
+                    return Err(format!("Invalid segment '{}' in '{}'", part, template));
+                }
+                part => Segment::Literal(part.to_string()),
+            };
+            segments.push(segment);
+        }
+
+        Ok(PathTemplate {
+            template: template.to_string(),
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Match `path`, returning the extracted path parameters.
+    pub fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+        let mut params = HashMap::new();
+        let mut parts = split(path);
+
+        for segment in &self.segments {
+            match segment {
+                Segment::CatchAll => return Some(params),
+                Segment::Literal(literal) => {
+                    if parts.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.insert(name.clone(), parts.next()?.to_string());
+                }
+                Segment::Wildcard => {
+                    parts.next()?;
+                }
+            }
+        }
+
+        match parts.next() {
+            Some(_) => None,
+            None => Some(params),
+        }
+    }
+
+    /// Order templates by how specific they are, comparing segment by segment:
+    /// literal, then parameter, then `*`, then `**`.
+    pub fn specificity_cmp(&self, other: &PathTemplate) -> Ordering {
+        let ranks = |template: &PathTemplate| {
+            template
+                .segments
+                .iter()
+                .map(Segment::rank)
+                .collect::<Vec<_>>()
+        };
+        ranks(self).cmp(&ranks(other))
+    }
+
+    /// Whether both templates match exactly the same paths.
+    pub fn is_equivalent(&self, other: &PathTemplate) -> bool {
+        self.segments.len() == other.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(&other.segments)
+                .all(|(a, b)| match (a, b) {
+                    (Segment::Literal(a), Segment::Literal(b)) => a == b,
+                    (a, b) => a.rank() == b.rank() && a.rank() != 3,
+                })
+    }
+}
+
+fn split(path: &str) -> impl Iterator<Item = &str> {
+    path.split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_matching() {
+        let template = PathTemplate::parse("/hello/{name}").unwrap();
+        let params = template.matches("/hello/world/").unwrap();
+        assert_eq!(params["name"], "world");
+        assert!(template.matches("/hello").is_none());
+        assert!(template.matches("/hello/world/again").is_none());
+
+        let catch_all = PathTemplate::parse("/_plugins/myext/**").unwrap();
+        assert!(catch_all.matches("/_plugins/myext").is_some());
+        assert!(catch_all.matches("/_plugins/myext/a/b/c").is_some());
+        assert!(catch_all.matches("/_plugins/other/a").is_none());
+
+        assert!(PathTemplate::parse("/a/**/b").is_err());
+        assert!(PathTemplate::parse("/a/{}").is_err());
+    }
+
+    #[test]
+    fn test_template_specificity() {
+        let literal = PathTemplate::parse("/hello/world").unwrap();
+        let param = PathTemplate::parse("/hello/{name}").unwrap();
+        let catch_all = PathTemplate::parse("/hello/**").unwrap();
+
+        assert_eq!(literal.specificity_cmp(&param), Ordering::Greater);
+        assert_eq!(param.specificity_cmp(&catch_all), Ordering::Greater);
+        assert!(param.is_equivalent(&PathTemplate::parse("/hello/{id}").unwrap()));
+        assert!(!param.is_equivalent(&literal));
+    }
+}
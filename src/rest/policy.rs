@@ -0,0 +1,205 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::rest::{RestExecuteOnExtensionResponse, RestStatus};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheControl {
+    NoStore,
+    NoCache,
+    Private(Duration),
+    Public(Duration),
+}
+
+impl CacheControl {
+    pub fn header_value(&self) -> String {
+        match self {
+            CacheControl::NoStore => "no-store".to_string(),
+            CacheControl::NoCache => "no-cache".to_string(),
+            CacheControl::Private(max_age) => format!("private, max-age={}", max_age.as_secs()),
+            CacheControl::Public(max_age) => format!("public, max-age={}", max_age.as_secs()),
+        }
+    }
+}
+
+/// Caching and compression applied to a route's successful responses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResponsePolicy {
+    pub cache_control: Option<CacheControl>,
+    pub etag: bool,
+    pub compress: bool,
+    pub min_compress_size: usize,
+}
+
+impl ResponsePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    pub fn with_etag(mut self) -> Self {
+        self.etag = true;
+        self
+    }
+
+    pub fn with_compression(mut self, min_size: usize) -> Self {
+        self.compress = true;
+        self.min_compress_size = min_size;
+        self
+    }
+
+    /// Apply the policy given the incoming request headers. A matching
+    /// `If-None-Match` turns the response into an empty `304 Not Modified`.
+    pub fn apply(
+        &self,
+        request_headers: &HashMap<String, Vec<String>>,
+        mut response: RestExecuteOnExtensionResponse,
+    ) -> io::Result<RestExecuteOnExtensionResponse> {
+        if !response.status.is_success() {
+            return Ok(response);
+        }
+
+        if let Some(cache_control) = &self.cache_control {
+            response.add_header("Cache-Control", cache_control.header_value());
+        }
+
+        if self.etag {
+            let etag = etag_for(&response.content);
+            let not_modified = header_values(request_headers, "If-None-Match")
+                .flat_map(|value| value.split(','))
+                .any(|tag| {
+                    let tag = tag.trim();
+                    tag == "*" || tag.trim_start_matches("W/") == etag
+                });
+
+            response.add_header("ETag", etag);
+            if not_modified {
+                response.status = RestStatus::NotModified;
+                response.content.clear();
+                return Ok(response);
+            }
+        }
+
+        if self.compress
+            && response.content.len() >= self.min_compress_size
+            && header_values(&response.headers, "Content-Encoding")
+                .next()
+                .is_none()
+            && accepts_gzip(request_headers)
+        {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&response.content)?;
+            response.content = encoder.finish()?;
+            response.add_header("Content-Encoding", "gzip");
+            response.add_header("Vary", "Accept-Encoding");
+        }
+
+        Ok(response)
+    }
+}
+
+/// Strong ETag derived from the response content.
+pub fn etag_for(content: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("\"{:x}-{:016x}\"", content.len(), hasher.finish())
+}
+
+fn header_values<'a>(
+    headers: &'a HashMap<String, Vec<String>>,
+    name: &'a str,
+) -> impl Iterator<Item = &'a str> {
+    headers
+        .iter()
+        .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+        .flat_map(|(_, values)| values.iter().map(String::as_str))
+}
+
+fn accepts_gzip(request_headers: &HashMap<String, Vec<String>>) -> bool {
+    header_values(request_headers, "Accept-Encoding")
+        .flat_map(|value| value.split(','))
+        .any(|encoding| {
+            let mut parts = encoding.trim().split(';');
+            let name = parts.next().unwrap_or_default().trim();
+            let disabled = parts.any(|param| param.trim().replace(' ', "") == "q=0");
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !disabled
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn headers(name: &str, value: &str) -> HashMap<String, Vec<String>> {
+        HashMap::from([(name.to_string(), vec![value.to_string()])])
+    }
+
+    #[test]
+    fn test_cache_headers_and_etag() {
+        let policy = ResponsePolicy::new()
+            .cache_control(CacheControl::Public(Duration::from_secs(300)))
+            .with_etag();
+        let body = br#"{"schema":"v1"}"#.to_vec();
+
+        let response = policy
+            .apply(
+                &HashMap::new(),
+                RestExecuteOnExtensionResponse::json(RestStatus::Ok, body.clone()),
+            )
+            .unwrap();
+        assert_eq!(
+            response.headers["Cache-Control"],
+            vec!["public, max-age=300"]
+        );
+        let etag = response.headers["ETag"][0].clone();
+        assert_eq!(etag, etag_for(&body));
+
+        let cached = policy
+            .apply(
+                &headers("if-none-match", &etag),
+                RestExecuteOnExtensionResponse::json(RestStatus::Ok, body),
+            )
+            .unwrap();
+        assert_eq!(cached.status, RestStatus::NotModified);
+        assert!(cached.content.is_empty());
+    }
+
+    #[test]
+    fn test_gzip_compression() {
+        let policy = ResponsePolicy::new().with_compression(16);
+        let body = "dashboard-data ".repeat(64);
+
+        let plain = policy
+            .apply(
+                &HashMap::new(),
+                RestExecuteOnExtensionResponse::text(RestStatus::Ok, body.clone()),
+            )
+            .unwrap();
+        assert_eq!(plain.content, body.as_bytes());
+
+        let compressed = policy
+            .apply(
+                &headers("Accept-Encoding", "br, gzip"),
+                RestExecuteOnExtensionResponse::text(RestStatus::Ok, body.clone()),
+            )
+            .unwrap();
+        assert_eq!(compressed.headers["Content-Encoding"], vec!["gzip"]);
+
+        let mut decoded = String::new();
+        GzDecoder::new(compressed.content.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+    }
+}
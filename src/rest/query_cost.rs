@@ -0,0 +1,370 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::debug;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Next, RestMiddleware, RestResponse, RestStatus};
+
+/// Header carrying the estimated cost of the request's query.
+pub const QUERY_COST_HEADER: &str = "X-Query-Cost";
+
+/// Buckets assumed for bucket aggregations without an explicit `size`,
+/// such as histograms.
+const DEFAULT_BUCKETS: u64 = 10;
+
+/// Counts gathered from a search body by `QueryCost::analyze`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueryCost {
+    pub clauses: usize,
+    pub max_depth: usize,
+    pub terms_values: usize,
+    pub wildcards: usize,
+    pub leading_wildcards: usize,
+    pub regexps: usize,
+    pub scripts: usize,
+    pub aggregations: usize,
+    /// Upper bound on buckets, multiplying through nested bucket aggregations.
+    pub buckets: u64,
+}
+
+impl QueryCost {
+    /// Analyze a `_search` body with `query` and `aggs`/`aggregations`.
+    pub fn analyze(body: &Value) -> Self {
+        let mut cost = QueryCost::default();
+        if let Some(query) = body.get("query") {
+            cost.visit_query(query, 1);
+        }
+        if let Some(aggs) = body.as_object().and_then(aggregations) {
+            cost.buckets = cost.visit_aggs(aggs);
+        }
+        cost
+    }
+
+    /// Single relative figure for logging and limits: one point per clause,
+    /// with expensive constructs weighted up.
+    pub fn score(&self) -> u64 {
+        self.clauses as u64
+            + self.terms_values as u64 / 100
+            + 10 * (self.wildcards + self.scripts) as u64
+            + 20 * self.regexps as u64
+            + 50 * self.leading_wildcards as u64
+            + self.aggregations as u64
+            + self.buckets / 100
+    }
+
+    fn visit_query(&mut self, query: &Value, depth: usize) {
+        let Some(query) = query.as_object() else {
+            return;
+        };
+        for (kind, body) in query {
+            self.clauses += 1;
+            self.max_depth = self.max_depth.max(depth);
+
+            match kind.as_str() {
+                "bool" => {
+                    for occur in ["must", "should", "filter", "must_not"] {
+                        for child in children(body.get(occur)) {
+                            self.visit_query(child, depth + 1);
+                        }
+                    }
+                }
+                "dis_max" => children(body.get("queries"))
+                    .for_each(|child| self.visit_query(child, depth + 1)),
+                "boosting" => {
+                    for part in ["positive", "negative"] {
+                        children(body.get(part))
+                            .for_each(|child| self.visit_query(child, depth + 1));
+                    }
+                }
+                "constant_score" => children(body.get("filter"))
+                    .for_each(|child| self.visit_query(child, depth + 1)),
+                "nested" | "has_child" | "has_parent" | "function_score" | "script_score" => {
+                    children(body.get("query"))
+                        .for_each(|child| self.visit_query(child, depth + 1));
+                    if kind == "script_score" {
+                        self.scripts += 1;
+                    }
+                }
+                "wildcard" => {
+                    self.wildcards += 1;
+                    if field_value(body, "value").is_some_and(|v| v.starts_with(['*', '?'])) {
+                        self.leading_wildcards += 1;
+                    }
+                }
+                "regexp" => self.regexps += 1,
+                "script" => self.scripts += 1,
+                "terms" => {
+                    self.terms_values += body
+                        .as_object()
+                        .map(|fields| {
+                            fields
+                                .values()
+                                .filter_map(Value::as_array)
+                                .map(Vec::len)
+                                .sum()
+                        })
+                        .unwrap_or(0);
+                }
+                "query_string" | "simple_query_string" => {
+                    let text = body
+                        .get("query")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    if text.contains(['*', '?']) {
+                        self.wildcards += 1;
+                    }
+                    if text
+                        .split_whitespace()
+                        .any(|term| term.starts_with(['*', '?']))
+                    {
+                        self.leading_wildcards += 1;
+                    }
+                    if text.contains('/') {
+                        self.regexps += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the buckets produced by `aggs`, including their sub-aggregations.
+    fn visit_aggs(&mut self, aggs: &Map<String, Value>) -> u64 {
+        let mut total = 0u64;
+        for agg in aggs.values().filter_map(Value::as_object) {
+            self.aggregations += 1;
+
+            let buckets = agg
+                .iter()
+                .find_map(|(kind, body)| bucket_count(kind, body))
+                .unwrap_or(0);
+            let sub_buckets = match aggregations(agg) {
+                Some(sub) => self.visit_aggs(sub).max(1),
+                None => 1,
+            };
+            if agg.keys().any(|kind| kind == "scripted_metric") {
+                self.scripts += 1;
+            }
+            total = total.saturating_add(buckets.saturating_mul(sub_buckets));
+        }
+        total
+    }
+}
+
+fn aggregations(value: &Map<String, Value>) -> Option<&Map<String, Value>> {
+    value
+        .get("aggs")
+        .or_else(|| value.get("aggregations"))
+        .and_then(Value::as_object)
+}
+
+/// A query clause given as a single object or an array of them.
+fn children(value: Option<&Value>) -> impl Iterator<Item = &Value> {
+    let values: Vec<&Value> = match value {
+        Some(Value::Array(values)) => values.iter().collect(),
+        Some(value) => vec![value],
+        None => Vec::new(),
+    };
+    values.into_iter()
+}
+
+/// The value of a term-level query, in either `{"field": "v"}` or
+/// `{"field": {"value": "v"}}` form.
+fn field_value<'a>(body: &'a Value, key: &str) -> Option<&'a str> {
+    let (_, value) = body.as_object()?.iter().next()?;
+    value
+        .as_str()
+        .or_else(|| value.get(key).and_then(Value::as_str))
+}
+
+fn bucket_count(kind: &str, body: &Value) -> Option<u64> {
+    let size = || {
+        body.get("size")
+            .and_then(Value::as_u64)
+            .unwrap_or(DEFAULT_BUCKETS)
+    };
+    match kind {
+        "terms" | "multi_terms" | "significant_terms" | "rare_terms" | "composite" => Some(size()),
+        "histogram"
+        | "date_histogram"
+        | "auto_date_histogram"
+        | "range"
+        | "date_range"
+        | "filters"
+        | "geohash_grid"
+        | "geotile_grid" => Some(
+            body.get("ranges")
+                .or_else(|| body.get("filters"))
+                .map(|v| {
+                    v.as_array()
+                        .map(Vec::len)
+                        .or_else(|| v.as_object().map(Map::len))
+                        .unwrap_or(0) as u64
+                })
+                .unwrap_or_else(size),
+        ),
+        _ => None,
+    }
+}
+
+/// Limits enforced by `QueryCostLimiter`. Defaults follow OpenSearch's
+/// `indices.query.bool.max_clause_count` and `search.max_buckets`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryLimits {
+    pub max_clauses: usize,
+    pub max_depth: usize,
+    pub max_buckets: u64,
+    pub allow_leading_wildcards: bool,
+    pub allow_regexps: bool,
+    pub allow_scripts: bool,
+    pub max_score: Option<u64>,
+}
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        QueryLimits {
+            max_clauses: 1024,
+            max_depth: 20,
+            max_buckets: 65_535,
+            allow_leading_wildcards: false,
+            allow_regexps: true,
+            allow_scripts: true,
+            max_score: None,
+        }
+    }
+}
+
+impl QueryLimits {
+    /// Every limit `cost` exceeds, as human-readable reasons.
+    pub fn violations(&self, cost: &QueryCost) -> Vec<String> {
+        let mut violations = Vec::new();
+        if cost.clauses > self.max_clauses {
+            violations.push(format!(
+                "query has {} clauses, more than the limit of {}",
+                cost.clauses, self.max_clauses
+            ));
+        }
+        if cost.max_depth > self.max_depth {
+            violations.push(format!(
+                "query is nested {} levels deep, more than the limit of {}",
+                cost.max_depth, self.max_depth
+            ));
+        }
+        if cost.buckets > self.max_buckets {
+            violations.push(format!(
+                "aggregations may create {} buckets, more than the limit of {}",
+                cost.buckets, self.max_buckets
+            ));
+        }
+        if cost.leading_wildcards > 0 && !self.allow_leading_wildcards {
+            violations.push("leading wildcards are not allowed".to_string());
+        }
+        if cost.regexps > 0 && !self.allow_regexps {
+            violations.push("regexp queries are not allowed".to_string());
+        }
+        if cost.scripts > 0 && !self.allow_scripts {
+            violations.push("scripts are not allowed".to_string());
+        }
+        if let Some(max_score) = self.max_score.filter(|max| cost.score() > *max) {
+            violations.push(format!(
+                "query cost {} is more than the limit of {}",
+                cost.score(),
+                max_score
+            ));
+        }
+        violations
+    }
+}
+
+/// Middleware for routes that proxy user searches: rejects bodies that
+/// exceed `QueryLimits` with 400 and sets `X-Query-Cost` on the response.
+#[derive(Debug, Clone, Default)]
+pub struct QueryCostLimiter {
+    limits: QueryLimits,
+}
+
+impl QueryCostLimiter {
+    pub fn new(limits: QueryLimits) -> Self {
+        QueryCostLimiter { limits }
+    }
+}
+
+#[async_trait]
+impl RestMiddleware for QueryCostLimiter {
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+        next: Next<'_>,
+    ) -> Result<RestResponse, ExtensionError> {
+        if !request.has_content() {
+            return next.run(request, context).await;
+        }
+
+        let cost = QueryCost::analyze(&request.content_value()?);
+        debug!("Estimated query cost for {}: {:?}", next.route(), cost);
+
+        let violations = self.limits.violations(&cost);
+        if !violations.is_empty() {
+            return Ok(RestResponse::error(
+                RestStatus::BadRequest,
+                format!("query rejected: {}", violations.join("; ")),
+            )
+            .with_header(QUERY_COST_HEADER, cost.score().to_string()));
+        }
+
+        let response = next.run(request, context).await?;
+        Ok(response.with_header(QUERY_COST_HEADER, cost.score().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_query_cost_and_limits() {
+        let body = json!({
+            "query": {
+                "bool": {
+                    "must": [{"match": {"title": "rust"}}, {"wildcard": {"user": {"value": "*son"}}}],
+                    "filter": {"terms": {"tag": ["a", "b", "c"]}},
+                    "should": [{"regexp": {"name": "k.*y"}}]
+                }
+            },
+            "aggs": {
+                "by_tag": {"terms": {"field": "tag", "size": 50}, "aggs": {"per_day": {"date_histogram": {"field": "ts"}}}},
+                "avg_size": {"avg": {"field": "size"}}
+            }
+        });
+
+        let cost = QueryCost::analyze(&body);
+        assert_eq!(cost.clauses, 5);
+        assert_eq!(cost.max_depth, 2);
+        assert_eq!(
+            (cost.wildcards, cost.leading_wildcards, cost.regexps),
+            (1, 1, 1)
+        );
+        assert_eq!(cost.terms_values, 3);
+        assert_eq!(cost.aggregations, 3);
+        assert_eq!(cost.buckets, 50 * 10);
+
+        let violations = QueryLimits::default().violations(&cost);
+        assert_eq!(violations, vec!["leading wildcards are not allowed"]);
+
+        let strict = QueryLimits {
+            max_buckets: 100,
+            allow_leading_wildcards: true,
+            ..Default::default()
+        };
+        assert_eq!(strict.violations(&cost).len(), 1);
+        assert!(QueryLimits {
+            allow_leading_wildcards: true,
+            ..Default::default()
+        }
+        .violations(&cost)
+        .is_empty());
+    }
+}
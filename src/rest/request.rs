@@ -0,0 +1,443 @@
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::extension::{ExtensionError, PrincipalIdentifier};
+use crate::interface::{
+    read_bool, read_byte_array, read_string, read_string_array, read_vint, write_bool,
+    write_byte_array, write_string, write_string_array, write_vint, Deserialize, Serialize,
+};
+use crate::xcontent;
+
+/// Parameters OpenSearch applies to the response itself, accepted on every route.
+pub const RESPONSE_PARAMS: [&str; 5] = ["error_trace", "filter_path", "format", "human", "pretty"];
+
+/// HTTP methods in the order of OpenSearch's `RestRequest.Method`, whose
+/// ordinals are used on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Head,
+    Patch,
+    Trace,
+    Connect,
+}
+
+impl Method {
+    const ALL: [Method; 9] = [
+        Method::Get,
+        Method::Post,
+        Method::Put,
+        Method::Delete,
+        Method::Options,
+        Method::Head,
+        Method::Patch,
+        Method::Trace,
+        Method::Connect,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+            Method::Head => "HEAD",
+            Method::Patch => "PATCH",
+            Method::Trace => "TRACE",
+            Method::Connect => "CONNECT",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|method| method.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn ordinal(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn from_ordinal(ordinal: u32) -> Option<Self> {
+        Self::ALL.get(ordinal as usize).copied()
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Whether a request's body was read, shared by the clones of a request
+/// so the router can tell OpenSearch after the handler ran.
+#[derive(Debug, Clone, Default)]
+pub struct ContentRead(Arc<AtomicBool>);
+
+impl ContentRead {
+    pub fn mark(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_read(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for ContentRead {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_read() == other.is_read()
+    }
+}
+
+/// REST request forwarded by OpenSearch over
+/// `internal:extensions/restexecuteonextensiontaction`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionRestRequest {
+    pub method: Method,
+    pub uri: String,
+    pub path: String,
+    pub params: HashMap<String, String>,
+    pub headers: HashMap<String, Vec<String>>,
+    pub media_type: Option<String>,
+    pub content: Vec<u8>,
+    pub principal_token: String,
+    /// Parameters of the matched route, set by the router: its path
+    /// parameters and those declared with `Route::params`.
+    pub accepted_params: Option<Arc<HashSet<String>>>,
+    /// Set by `content` and the methods parsing it. Reading the `content`
+    /// field directly is not tracked.
+    pub content_read: ContentRead,
+}
+
+impl ExtensionRestRequest {
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        let path = path.into();
+        ExtensionRestRequest {
+            method,
+            uri: path.clone(),
+            path,
+            params: HashMap::new(),
+            headers: HashMap::new(),
+            media_type: None,
+            content: Vec::new(),
+            principal_token: String::new(),
+            accepted_params: None,
+            content_read: ContentRead::default(),
+        }
+    }
+
+    pub fn with_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers
+            .entry(name.into())
+            .or_default()
+            .push(value.into());
+        self
+    }
+
+    pub fn with_content(
+        mut self,
+        media_type: impl Into<String>,
+        content: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.media_type = Some(media_type.into());
+        self.content = content.into();
+        self
+    }
+
+    /// Raw value of a parameter.
+    pub fn raw_param(&self, name: &str) -> Option<&str> {
+        if !self.is_accepted(name) {
+            return None;
+        }
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Optional parameter parsed as `T`; a value that fails to parse is an invalid request.
+    ///
+    /// Routes declare the query parameters they accept with `Route::params`,
+    /// and the router rejects requests carrying any other parameter before
+    /// the handler runs. `name` must therefore be a path parameter, a
+    /// declared parameter or one of `RESPONSE_PARAMS`; anything else is
+    /// logged as a bug in the handler and reads as absent.
+    pub fn param<T: FromStr>(&self, name: &str) -> Result<Option<T>, ExtensionError>
+    where
+        T::Err: fmt::Display,
+    {
+        self.raw_param(name)
+            .map(|value| {
+                value.parse::<T>().map_err(|e| {
+                    ExtensionError::invalid_request(format!(
+                        "Failed to parse parameter [{}] with value [{}]: {}",
+                        name, value, e
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    pub fn param_or<T: FromStr>(&self, name: &str, default: T) -> Result<T, ExtensionError>
+    where
+        T::Err: fmt::Display,
+    {
+        Ok(self.param(name)?.unwrap_or(default))
+    }
+
+    pub fn required_param<T: FromStr>(&self, name: &str) -> Result<T, ExtensionError>
+    where
+        T::Err: fmt::Display,
+    {
+        self.param(name)?.ok_or_else(|| {
+            ExtensionError::invalid_request(format!(
+                "request [{}] is missing required parameter [{}]",
+                self.path, name
+            ))
+        })
+    }
+
+    pub fn has_param(&self, name: &str) -> bool {
+        self.is_accepted(name) && self.params.contains_key(name)
+    }
+
+    /// Supplied parameters that are neither accepted by the matched route
+    /// nor response parameters, sorted.
+    pub fn unrecognized_params(&self) -> Vec<String> {
+        let accepted = |name: &String| {
+            self.accepted_params
+                .as_ref()
+                .is_some_and(|accepted| accepted.contains(name))
+        };
+        let mut unrecognized: Vec<String> = self
+            .params
+            .keys()
+            .filter(|name| !accepted(name) && !RESPONSE_PARAMS.contains(&name.as_str()))
+            .cloned()
+            .collect();
+        unrecognized.sort();
+        unrecognized
+    }
+
+    /// A handler reading a parameter its route does not declare would never
+    /// see it supplied, since the router rejects such requests.
+    fn is_accepted(&self, name: &str) -> bool {
+        let accepted = self
+            .accepted_params
+            .as_ref()
+            .is_none_or(|accepted| accepted.contains(name))
+            || RESPONSE_PARAMS.contains(&name);
+        if !accepted {
+            warn!(
+                "Parameter [{}] read for [{}] is not declared with `Route::params`",
+                name, self.path
+            );
+        }
+        accepted
+    }
+
+    /// First value of a header, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, values)| values.first())
+            .map(String::as_str)
+    }
+
+    /// The user OpenSearch authenticated for this request, if any.
+    pub fn principal(&self) -> Option<PrincipalIdentifier> {
+        (!self.principal_token.is_empty())
+            .then(|| PrincipalIdentifier::new(self.principal_token.clone()))
+    }
+
+    pub fn has_content(&self) -> bool {
+        !self.content.is_empty()
+    }
+
+    /// The raw body, marking it as consumed.
+    pub fn content(&self) -> &[u8] {
+        self.content_read.mark();
+        &self.content
+    }
+
+    /// Parse the body in whichever XContent type it was sent as.
+    pub fn content_value(&self) -> Result<serde_json::Value, ExtensionError> {
+        xcontent::parse(self.media_type.as_deref(), self.content())
+            .map(|(_, value)| value)
+            .map_err(|e| {
+                ExtensionError::invalid_request(format!("request body is not valid: {}", e))
+            })
+    }
+
+    /// Parse the body and deserialize it into `T`.
+    pub fn content_as<T: DeserializeOwned>(&self) -> Result<T, ExtensionError> {
+        serde_json::from_value(self.content_value()?).map_err(|e| {
+            ExtensionError::invalid_request(format!("request body is not valid: {}", e))
+        })
+    }
+}
+
+impl Serialize for ExtensionRestRequest {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_vint(buf, self.method.ordinal())?;
+        written += write_string(buf, &self.uri)?;
+        written += write_string(buf, &self.path)?;
+
+        written += write_vint(buf, self.params.len() as u32)?;
+        for (name, value) in &self.params {
+            written += write_string(buf, name)?;
+            written += write_string(buf, value)?;
+        }
+
+        written += write_vint(buf, self.headers.len() as u32)?;
+        for (name, values) in &self.headers {
+            written += write_string(buf, name)?;
+            written += write_string_array(buf, values)?;
+        }
+
+        written += write_bool(buf, self.media_type.is_some())?;
+        if let Some(media_type) = &self.media_type {
+            written += write_string(buf, media_type)?;
+        }
+
+        written += write_byte_array(buf, &self.content)?;
+        written += write_string(buf, &self.principal_token)?;
+        Ok(written)
+    }
+}
+
+impl Deserialize for ExtensionRestRequest {
+    type Output = ExtensionRestRequest;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let ordinal = read_vint(buf)?;
+        let method = Method::from_ordinal(ordinal).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown REST method ordinal: {}", ordinal),
+            )
+        })?;
+        let uri = read_string(buf)?;
+        let path = read_string(buf)?;
+
+        let param_count = read_vint(buf)?;
+        let mut params = HashMap::new();
+        for _ in 0..param_count {
+            let name = read_string(buf)?;
+            params.insert(name, read_string(buf)?);
+        }
+
+        let header_count = read_vint(buf)?;
+        let mut headers = HashMap::new();
+        for _ in 0..header_count {
+            let name = read_string(buf)?;
+            headers.insert(name, read_string_array(buf)?);
+        }
+
+        let media_type = if read_bool(buf)? {
+            Some(read_string(buf)?)
+        } else {
+            None
+        };
+        let content = read_byte_array(buf)?;
+        let principal_token = read_string(buf)?;
+
+        Ok(ExtensionRestRequest {
+            method,
+            uri,
+            path,
+            params,
+            headers,
+            media_type,
+            content,
+            principal_token,
+            accepted_params: None,
+            content_read: ContentRead::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rest_request_round_trip() {
+        let request = ExtensionRestRequest::new(Method::Post, "/hello")
+            .with_param("pretty", "true")
+            .with_header("Content-Type", "application/json")
+            .with_content("application/json", br#"{"name":"world"}"#.to_vec());
+
+        let mut bytes = Vec::new();
+        request.serialize(&mut bytes).unwrap();
+        let decoded = ExtensionRestRequest::deserialize(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(decoded, request);
+        assert_eq!(decoded.header("content-type"), Some("application/json"));
+        assert_eq!(
+            decoded.content_value().unwrap(),
+            serde_json::json!({"name": "world"})
+        );
+        assert_eq!(
+            Method::from_ordinal(Method::Patch.ordinal()),
+            Some(Method::Patch)
+        );
+        assert_eq!(Method::from_name("delete"), Some(Method::Delete));
+    }
+
+    #[test]
+    fn test_typed_params() {
+        let request = ExtensionRestRequest::new(Method::Get, "/hello")
+            .with_param("size", "10")
+            .with_param("from", "ten")
+            .with_param("pretty", "true")
+            .with_param("unknown", "x");
+
+        assert_eq!(request.param::<u32>("size").unwrap(), Some(10));
+        assert!(request.param::<u32>("from").is_err());
+        assert!(request
+            .param_or("verbose", false)
+            .is_ok_and(|verbose| !verbose));
+        assert!(request.required_param::<String>("name").is_err());
+
+        assert_eq!(
+            request.unrecognized_params(),
+            vec!["from", "size", "unknown"]
+        );
+        let accepted = ["from", "size"].map(String::from).into_iter().collect();
+        let request = ExtensionRestRequest {
+            accepted_params: Some(Arc::new(accepted)),
+            ..request
+        };
+        assert_eq!(request.unrecognized_params(), vec!["unknown"]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_undeclared_param_read() {
+        let mut request = ExtensionRestRequest {
+            accepted_params: Some(Arc::new(HashSet::from(["size".to_string()]))),
+            ..ExtensionRestRequest::new(Method::Get, "/hello")
+        };
+        request.params.insert("limit".to_string(), "10".to_string());
+        assert_eq!(request.param::<u32>("size").unwrap(), None);
+        assert!(request.raw_param("pretty").is_none());
+        assert_eq!(request.param::<u32>("limit").unwrap(), None);
+        assert!(!request.has_param("limit"));
+    }
+}
@@ -0,0 +1,190 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::extension::ExtensionError;
+use crate::rest::execute::{JSON_CONTENT_TYPE, TEXT_CONTENT_TYPE};
+use crate::rest::{ExtensionRestRequest, RestExecuteOnExtensionResponse, RestStatus};
+use crate::xcontent::XContentType;
+
+/// Response produced by a `RestHandler`, e.g.
+/// `RestResponse::ok().negotiate(&request).json(&value)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestResponse {
+    pub status: RestStatus,
+    pub content_type: String,
+    pub content: Vec<u8>,
+    pub headers: HashMap<String, Vec<String>>,
+    format: XContentType,
+    pretty: bool,
+}
+
+impl RestResponse {
+    pub fn new(
+        status: RestStatus,
+        content_type: impl Into<String>,
+        content: impl Into<Vec<u8>>,
+    ) -> Self {
+        RestResponse {
+            status,
+            content_type: content_type.into(),
+            content: content.into(),
+            headers: HashMap::new(),
+            format: XContentType::Json,
+            pretty: false,
+        }
+    }
+
+    /// An empty response with `status`.
+    pub fn status(status: RestStatus) -> Self {
+        Self::new(status, JSON_CONTENT_TYPE, Vec::new())
+    }
+
+    pub fn ok() -> Self {
+        Self::status(RestStatus::Ok)
+    }
+
+    pub fn created() -> Self {
+        Self::status(RestStatus::Created)
+    }
+
+    pub fn accepted() -> Self {
+        Self::status(RestStatus::Accepted)
+    }
+
+    pub fn no_content() -> Self {
+        Self::status(RestStatus::NoContent)
+    }
+
+    pub fn not_found() -> Self {
+        Self::status(RestStatus::NotFound)
+    }
+
+    pub fn bad_request() -> Self {
+        Self::status(RestStatus::BadRequest)
+    }
+
+    /// OpenSearch-style error body: `{"error": {"type", "reason"}, "status"}`.
+    pub fn error(status: RestStatus, reason: impl Into<String>) -> Self {
+        let error_type = match status {
+            RestStatus::BadRequest => "illegal_argument_exception",
+            RestStatus::NotFound => "resource_not_found_exception",
+            _ => "status_exception",
+        };
+        Self::exception(status, error_type, reason)
+    }
+
+    /// An OpenSearch-style error body with an explicit exception `type`.
+    pub fn exception(status: RestStatus, error_type: &str, reason: impl Into<String>) -> Self {
+        let body = serde_json::json!({
+            "error": { "type": error_type, "reason": reason.into() },
+            "status": status.code(),
+        });
+        Self::status(status).bytes(JSON_CONTENT_TYPE, body.to_string())
+    }
+
+    /// Pick the body format and pretty-printing from the request's `format`,
+    /// `Accept` and `pretty`. Call before setting a serde body.
+    pub fn negotiate(mut self, request: &ExtensionRestRequest) -> Self {
+        self.format = request
+            .raw_param("format")
+            .and_then(XContentType::from_name)
+            .or_else(|| {
+                request
+                    .header("Accept")
+                    .and_then(XContentType::from_media_type)
+            })
+            .unwrap_or_default();
+        self.pretty = matches!(request.raw_param("pretty"), Some("") | Some("true"));
+        self
+    }
+
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    pub fn format(mut self, format: XContentType) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Serialize `value` as the body in the negotiated format (JSON by default).
+    pub fn json<T: Serialize + ?Sized>(mut self, value: &T) -> Result<Self, ExtensionError> {
+        self.content = self.format.to_vec(value, self.pretty)?;
+        self.content_type = self.format.media_type().to_string();
+        Ok(self)
+    }
+
+    pub fn text(self, content: impl Into<String>) -> Self {
+        self.bytes(TEXT_CONTENT_TYPE, content.into())
+    }
+
+    pub fn bytes(mut self, content_type: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        self.content_type = content_type.into();
+        self.content = content.into();
+        self
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers
+            .entry(name.into())
+            .or_default()
+            .push(value.into());
+        self
+    }
+
+    pub fn into_execute_response(
+        self,
+        consumed_params: Vec<String>,
+    ) -> RestExecuteOnExtensionResponse {
+        RestExecuteOnExtensionResponse {
+            status: self.status,
+            content_type: self.content_type,
+            content: self.content,
+            headers: self.headers,
+            consumed_params,
+            content_consumed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::Method;
+    use serde_json::json;
+
+    #[test]
+    fn test_response_builder() {
+        let value = json!({"greeting": "hello"});
+
+        let response = RestResponse::created().json(&value).unwrap();
+        assert_eq!(response.status, RestStatus::Created);
+        assert_eq!(response.content_type, JSON_CONTENT_TYPE);
+        assert_eq!(response.content, br#"{"greeting":"hello"}"#);
+
+        let request = ExtensionRestRequest::new(Method::Get, "/hello").with_param("pretty", "");
+        let pretty = RestResponse::ok().negotiate(&request).json(&value).unwrap();
+        assert_eq!(
+            String::from_utf8(pretty.content).unwrap(),
+            "{\n  \"greeting\": \"hello\"\n}"
+        );
+
+        let request = ExtensionRestRequest::new(Method::Get, "/hello")
+            .with_header("Accept", "application/yaml");
+        let yaml = RestResponse::ok().negotiate(&request).json(&value).unwrap();
+        assert_eq!(yaml.content_type, XContentType::Yaml.media_type());
+        assert_eq!(yaml.content, b"greeting: hello\n");
+
+        let request = ExtensionRestRequest::new(Method::Get, "/hello").with_param("format", "cbor");
+        let cbor = RestResponse::ok().negotiate(&request).json(&value).unwrap();
+        assert_eq!(XContentType::Cbor.parse(&cbor.content).unwrap(), value);
+
+        let error = RestResponse::error(RestStatus::NotFound, "no such job [42]");
+        let body: serde_json::Value = serde_json::from_slice(&error.content).unwrap();
+        assert_eq!(
+            body,
+            json!({"error": {"type": "resource_not_found_exception", "reason": "no such job [42]"}, "status": 404})
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use serde_json::{Map, Value};
+
+use crate::rest::{ExtensionRestRequest, RestResponse};
+use crate::xcontent::XContentType;
+
+/// Match `text` against a pattern where `*` matches any run of characters.
+pub fn simple_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some((b'*', rest)) => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+            Some((c, rest)) => text
+                .split_first()
+                .is_some_and(|(t, text)| t == c && matches(rest, text)),
+        }
+    }
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+/// A parsed `filter_path`: comma-separated dotted paths where `*` matches
+/// within a field name, `**` matches any number of levels and a leading `-`
+/// excludes instead of includes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterPath {
+    includes: Vec<Vec<String>>,
+    excludes: Vec<Vec<String>>,
+}
+
+impl FilterPath {
+    pub fn parse(filter_path: &str) -> Self {
+        let mut filter = FilterPath::default();
+        for path in filter_path
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let (target, path) = match path.strip_prefix('-') {
+                Some(path) => (&mut filter.excludes, path),
+                None => (&mut filter.includes, path),
+            };
+            target.push(path.split('.').map(str::to_string).collect());
+        }
+        filter
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty() && self.excludes.is_empty()
+    }
+
+    /// Keep only included paths, then drop excluded ones. A body with no
+    /// included path becomes an empty object.
+    pub fn apply(&self, value: Value) -> Value {
+        let value = if self.includes.is_empty() {
+            value
+        } else {
+            include(value, &self.includes).unwrap_or_else(|| Value::Object(Map::new()))
+        };
+        if self.excludes.is_empty() {
+            value
+        } else {
+            let mut value = value;
+            exclude(&mut value, &self.excludes);
+            value
+        }
+    }
+}
+
+/// Remaining filters after descending into `key`. An empty filter means
+/// the key matched a whole path.
+fn advance(filters: &[Vec<String>], key: &str) -> Vec<Vec<String>> {
+    let mut next = Vec::new();
+    for filter in filters {
+        match filter.split_first() {
+            Some((segment, rest)) if segment == "**" => {
+                next.push(filter.clone());
+                if rest.is_empty() {
+                    next.push(Vec::new());
+                } else {
+                    next.extend(advance(&[rest.to_vec()], key));
+                }
+            }
+            Some((segment, rest)) if simple_match(segment, key) => next.push(rest.to_vec()),
+            _ => {}
+        }
+    }
+    next
+}
+
+fn include(value: Value, filters: &[Vec<String>]) -> Option<Value> {
+    match value {
+        Value::Object(object) => {
+            let kept: Map<String, Value> = object
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    let next = advance(filters, &key);
+                    if next.iter().any(Vec::is_empty) {
+                        Some((key, value))
+                    } else if next.is_empty() {
+                        None
+                    } else {
+                        include(value, &next).map(|value| (key, value))
+                    }
+                })
+                .collect();
+            (!kept.is_empty()).then_some(Value::Object(kept))
+        }
+        Value::Array(values) => {
+            let kept: Vec<Value> = values
+                .into_iter()
+                .filter_map(|v| include(v, filters))
+                .collect();
+            (!kept.is_empty()).then_some(Value::Array(kept))
+        }
+        _ => None,
+    }
+}
+
+fn exclude(value: &mut Value, filters: &[Vec<String>]) {
+    match value {
+        Value::Object(object) => object.retain(|key, value| {
+            let next = advance(filters, key);
+            if next.iter().any(Vec::is_empty) {
+                return false;
+            }
+            if !next.is_empty() {
+                exclude(value, &next);
+            }
+            true
+        }),
+        Value::Array(values) => values.iter_mut().for_each(|v| exclude(v, filters)),
+        _ => {}
+    }
+}
+
+/// Add the human-readable fields OpenSearch emits with `?human`: a
+/// `size` next to every `size_in_bytes` and a `time` next to every
+/// `time_in_millis`.
+pub fn humanize(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            let mut readable = Vec::new();
+            for (key, value) in object.iter_mut() {
+                humanize(value);
+                if let Some(number) = value.as_f64() {
+                    if let Some(field) = key.strip_suffix("_in_bytes") {
+                        readable.push((field.to_string(), format_bytes(number)));
+                    } else if let Some(field) = key.strip_suffix("_in_millis") {
+                        readable.push((field.to_string(), format_millis(number)));
+                    }
+                }
+            }
+            for (field, text) in readable {
+                object.entry(field).or_insert(Value::String(text));
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(humanize),
+        _ => {}
+    }
+}
+
+/// Like OpenSearch's `format1Decimals`: one decimal, dropped when zero.
+fn one_decimal(value: f64, suffix: &str) -> String {
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{}{}", rounded as i64, suffix)
+    } else {
+        format!("{:.1}{}", rounded, suffix)
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 6] = ["b", "kb", "mb", "gb", "tb", "pb"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    one_decimal(value, UNITS[unit])
+}
+
+fn format_millis(millis: f64) -> String {
+    const UNITS: [(f64, &str); 4] = [
+        (86_400_000.0, "d"),
+        (3_600_000.0, "h"),
+        (60_000.0, "m"),
+        (1000.0, "s"),
+    ];
+    UNITS
+        .iter()
+        .find(|(size, _)| millis >= *size)
+        .map(|(size, suffix)| one_decimal(millis / size, suffix))
+        .unwrap_or_else(|| one_decimal(millis, "ms"))
+}
+
+/// The `filter_path`, `pretty` and `human` parameters OpenSearch honours on
+/// every API, applied to a handler's XContent response.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResponseParams {
+    pub filter_path: Option<FilterPath>,
+    pub pretty: bool,
+    pub human: bool,
+}
+
+impl ResponseParams {
+    pub fn from_request(request: &ExtensionRestRequest) -> Self {
+        let flag = |name| matches!(request.raw_param(name), Some("") | Some("true"));
+        ResponseParams {
+            filter_path: request
+                .raw_param("filter_path")
+                .map(FilterPath::parse)
+                .filter(|f| !f.is_empty()),
+            pretty: flag("pretty"),
+            human: flag("human"),
+        }
+    }
+
+    /// Rewrite the body. Responses that are empty, not XContent or not
+    /// parseable are returned unchanged.
+    pub fn apply(&self, mut response: RestResponse) -> RestResponse {
+        if *self == ResponseParams::default() || response.content.is_empty() {
+            return response;
+        }
+        let Some(content_type) = XContentType::from_media_type(&response.content_type) else {
+            return response;
+        };
+        let Ok(mut body) = content_type.parse(&response.content) else {
+            return response;
+        };
+
+        if self.human {
+            humanize(&mut body);
+        }
+        if let Some(filter_path) = &self.filter_path {
+            body = filter_path.apply(body);
+        }
+        if let Ok(content) = content_type.to_vec(&body, self.pretty) {
+            response.content = content;
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_path() {
+        let body = json!({
+            "took": 3,
+            "hits": {"total": {"value": 2}, "hits": [
+                {"_id": "1", "_source": {"title": "a", "secret": "x"}},
+                {"_id": "2", "_source": {"title": "b", "secret": "y"}}
+            ]},
+            "_shards": {"total": 1, "failed": 0}
+        });
+
+        let filtered = FilterPath::parse("took,hits.hits._id").apply(body.clone());
+        assert_eq!(
+            filtered,
+            json!({"took": 3, "hits": {"hits": [{"_id": "1"}, {"_id": "2"}]}})
+        );
+
+        let filtered = FilterPath::parse("**.title,-_shards").apply(body.clone());
+        assert_eq!(
+            filtered,
+            json!({"hits": {"hits": [{"_source": {"title": "a"}}, {"_source": {"title": "b"}}]}})
+        );
+
+        let filtered = FilterPath::parse("-hits.hits._source.secret,-_sh*").apply(body.clone());
+        assert_eq!(
+            filtered["hits"]["hits"][1]["_source"],
+            json!({"title": "b"})
+        );
+        assert!(filtered.get("_shards").is_none());
+
+        assert_eq!(FilterPath::parse("nothing").apply(body), json!({}));
+    }
+
+    #[test]
+    fn test_human_and_pretty() {
+        let request = ExtensionRestRequest::new(crate::rest::Method::Get, "/_stats")
+            .with_param("human", "")
+            .with_param("pretty", "true")
+            .with_param("filter_path", "store");
+        let response = RestResponse::ok()
+            .json(&json!({"store": {"size_in_bytes": 1_572_864, "throttle_time_in_millis": 90_000}, "other": 1}))
+            .unwrap();
+
+        let response = ResponseParams::from_request(&request).apply(response);
+        let body: Value = serde_json::from_slice(&response.content).unwrap();
+        assert_eq!(
+            body,
+            json!({"store": {
+                "size_in_bytes": 1_572_864, "size": "1.5mb",
+                "throttle_time_in_millis": 90_000, "throttle_time": "1.5m"
+            }})
+        );
+        assert!(String::from_utf8(response.content)
+            .unwrap()
+            .contains("\n  \"store\""));
+        assert_eq!(format_millis(150.0), "150ms");
+        assert_eq!(format_bytes(512.0), "512b");
+    }
+}
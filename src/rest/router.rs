@@ -0,0 +1,645 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::{debug, error, warn};
+
+use crate::extension::slow_log::{time_handler, SlowLogTarget};
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::error_mapper::catch_panic;
+use crate::rest::execute::JSON_CONTENT_TYPE;
+use crate::rest::{
+    DefaultErrorMapper, ErrorMapper, ExtensionRestRequest, Method, Next, PathTemplate,
+    ResponseParams, RestExecuteOnExtensionResponse, RestHandler, RestMiddleware, RestResponse,
+    RestStatus, Route,
+};
+
+#[derive(Clone)]
+struct RouteEntry {
+    route: Route,
+    template: PathTemplate,
+    handler: Arc<dyn RestHandler>,
+    middleware: Vec<Arc<dyn RestMiddleware>>,
+}
+
+/// Maps the routes declared by `RestHandler`s to their handler, matching
+/// paths against each route's `PathTemplate`.
+#[derive(Clone, Default)]
+pub struct RestRouter {
+    routes: Vec<RouteEntry>,
+    middleware: Vec<Arc<dyn RestMiddleware>>,
+    error_mappers: Vec<Arc<dyn ErrorMapper>>,
+    deprecated_calls: Arc<Mutex<HashMap<Route, u64>>>,
+}
+
+impl RestRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add middleware applied to every route, outermost first.
+    pub fn with_middleware(mut self, middleware: Arc<dyn RestMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    /// Add a mapper consulted, before `DefaultErrorMapper`, for errors
+    /// returned by handlers.
+    pub fn with_error_mapper(mut self, mapper: Arc<dyn ErrorMapper>) -> Self {
+        self.error_mappers.push(mapper);
+        self
+    }
+
+    pub fn register(&mut self, handler: Arc<dyn RestHandler>) -> Result<(), ExtensionError> {
+        for route in handler.routes() {
+            let template =
+                PathTemplate::parse(&route.path).map_err(ExtensionError::configuration)?;
+
+            let duplicate = self.routes.iter().any(|entry| {
+                entry.route.method == route.method && entry.template.is_equivalent(&template)
+            });
+            if duplicate {
+                return Err(ExtensionError::configuration(format!(
+                    "REST route '{}' is already registered",
+                    route
+                )));
+            }
+
+            let middleware = handler.middleware(&route);
+            self.routes.push(RouteEntry {
+                route,
+                template,
+                handler: handler.clone(),
+                middleware,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn routes(&self) -> Vec<Route> {
+        self.routes
+            .iter()
+            .map(|entry| entry.route.clone())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// How often each deprecated route has been called.
+    pub fn deprecated_calls(&self) -> HashMap<Route, u64> {
+        self.deprecated_calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub async fn handle(
+        &self,
+        mut request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestExecuteOnExtensionResponse, ExtensionError> {
+        debug!("Handling REST request {} {}", request.method, request.path);
+
+        let matches: Vec<_> = self
+            .routes
+            .iter()
+            .filter_map(|entry| {
+                entry
+                    .template
+                    .matches(&request.path)
+                    .map(|params| (entry, params))
+            })
+            .collect();
+
+        let best = matches
+            .iter()
+            .filter(|(entry, _)| entry.route.method == request.method)
+            .max_by(|a, b| a.0.template.specificity_cmp(&b.0.template));
+
+        if let Some((entry, params)) = best {
+            if context.lifecycle.is_paused().await && !entry.handler.available_when_paused() {
+                let response = error_response(
+                    RestStatus::ServiceUnavailable,
+                    "extension is paused".to_string(),
+                );
+                return Ok(response.into_execute_response(request.params.keys().cloned().collect()));
+            }
+            let mut accepted: HashSet<String> = entry.route.params.iter().cloned().collect();
+            for (name, value) in params {
+                accepted.insert(name.clone());
+                request.params.insert(name.clone(), value.clone());
+            }
+            request.accepted_params = Some(Arc::new(accepted));
+            return self.execute(entry, request, context).await;
+        }
+
+        let response = if matches.is_empty() {
+            error_response(
+                RestStatus::BadRequest,
+                format!(
+                    "no handler found for uri [{}] and method [{}]",
+                    request.uri, request.method
+                ),
+            )
+        } else {
+            let mut allowed: Vec<Method> = matches
+                .iter()
+                .map(|(entry, _)| entry.route.method)
+                .collect();
+            allowed.sort();
+            allowed.dedup();
+            let allowed: Vec<&str> = allowed.iter().map(Method::name).collect();
+
+            error_response(
+                RestStatus::MethodNotAllowed,
+                format!(
+                    "Incorrect HTTP method for uri [{}] and method [{}], allowed: [{}]",
+                    request.uri,
+                    request.method,
+                    allowed.join(", ")
+                ),
+            )
+            .with_header("Allow", allowed.join(","))
+        };
+
+        let consumed_params = request.params.keys().cloned().collect();
+        Ok(response.into_execute_response(consumed_params))
+    }
+
+    /// Reject the request if it supplies a parameter the route does not
+    /// accept, mirroring OpenSearch's strict parameter checks, otherwise run
+    /// the middleware chain and handler. Handler errors and panics become
+    /// error responses.
+    async fn execute(
+        &self,
+        entry: &RouteEntry,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> Result<RestExecuteOnExtensionResponse, ExtensionError> {
+        let path = request.path.clone();
+        let content_read = request.content_read.clone();
+        let supplied: Vec<String> = request.params.keys().cloned().collect();
+        let response_params = ResponseParams::from_request(&request);
+
+        let unrecognized = request.unrecognized_params();
+        let response = if unrecognized.is_empty() {
+            self.run_handler(entry, request, context).await
+        } else {
+            let plural = if unrecognized.len() > 1 { "s" } else { "" };
+            let names: Vec<String> = unrecognized
+                .iter()
+                .map(|name| format!("[{}]", name))
+                .collect();
+            illegal_argument(format!(
+                "request [{}] contains unrecognized parameter{}: {}",
+                path,
+                plural,
+                names.join(", ")
+            ))
+        };
+
+        let response = response_params.apply(response);
+        let response = match &entry.route.deprecation_message {
+            Some(message) => {
+                self.record_deprecated_call(&entry.route, message);
+                response.with_header("Warning", deprecation_warning(message))
+            }
+            None => response,
+        };
+
+        Ok(response
+            .into_execute_response(supplied)
+            .with_content_consumed(content_read.is_read()))
+    }
+
+    async fn run_handler(
+        &self,
+        entry: &RouteEntry,
+        request: ExtensionRestRequest,
+        context: &ExtensionContext,
+    ) -> RestResponse {
+        let path = request.path.clone();
+        let middleware: Vec<_> = self
+            .middleware
+            .iter()
+            .chain(&entry.middleware)
+            .cloned()
+            .collect();
+        let next = Next::new(&entry.route, entry.handler.as_ref(), &middleware);
+
+        let target = SlowLogTarget::Route(entry.route.clone());
+        match time_handler(target, catch_panic(next.run(request, context))).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => self.map_error(&path, e),
+            Err(panic) => {
+                error!("REST handler for {} panicked: {}", entry.route, panic);
+                context
+                    .diagnostics
+                    .record_panic(&entry.route.to_string(), &panic);
+                self.map_error(
+                    &path,
+                    ExtensionError::unknown(format!("handler for [{}] failed unexpectedly", path)),
+                )
+            }
+        }
+    }
+
+    fn map_error(&self, path: &str, error: ExtensionError) -> RestResponse {
+        let response = self
+            .error_mappers
+            .iter()
+            .find_map(|mapper| mapper.map_error(&error))
+            .or_else(|| DefaultErrorMapper.map_error(&error))
+            .unwrap_or_else(|| {
+                RestResponse::error(RestStatus::InternalServerError, error.to_string())
+            });
+        if response.status.code() >= 500 {
+            warn!("REST request for [{}] failed: {}", path, error);
+        } else {
+            debug!("REST request for [{}] failed: {}", path, error);
+        }
+        response
+    }
+
+    /// Count the call, logging the deprecation on first use only.
+    fn record_deprecated_call(&self, route: &Route, message: &str) {
+        let mut calls = self
+            .deprecated_calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let count = calls.entry(route.clone()).or_default();
+        *count += 1;
+        if *count == 1 {
+            warn!("Deprecated REST route {} was called: {}", route, message);
+        }
+    }
+}
+
+/// `Warning` header value in the format OpenSearch uses for deprecations.
+fn deprecation_warning(message: &str) -> String {
+    format!(
+        "299 OpenSearch \"{}\"",
+        message.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+fn illegal_argument(reason: String) -> RestResponse {
+    RestResponse::error(RestStatus::BadRequest, reason)
+}
+
+fn error_response(status: RestStatus, reason: String) -> RestResponse {
+    let body = serde_json::json!({ "error": reason, "status": status.code() });
+    RestResponse::status(status).bytes(JSON_CONTENT_TYPE, body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::TransportClient;
+    use async_trait::async_trait;
+
+    struct HelloHandler;
+
+    #[async_trait]
+    impl RestHandler for HelloHandler {
+        fn routes(&self) -> Vec<Route> {
+            vec![
+                Route::new(Method::Get, "/hello").params(["name", "verbose"]),
+                Route::new(Method::Get, "/hello/{name}").params(["verbose"]),
+                Route::new(Method::Put, "/hello/{name}").params(["verbose"]),
+                Route::new(Method::Get, "/hello/world").params(["name", "verbose"]),
+                Route::new(Method::Get, "/files/**").params(["name", "verbose"]),
+                Route::deprecated(
+                    Method::Get,
+                    "/hi/{name}",
+                    "[GET /hi/{name}] is deprecated, use [GET /hello/{name}]",
+                )
+                .params(["verbose"]),
+            ]
+        }
+
+        async fn handle(
+            &self,
+            request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            let name = request.param_or("name", "literal".to_string())?;
+            let _verbose: bool = request.param_or("verbose", false)?;
+            Ok(RestResponse::ok().text(format!("Hello {}!", name)))
+        }
+    }
+
+    fn context() -> ExtensionContext {
+        ExtensionContext::builder()
+            .transport_client(Arc::new(TransportClient::new("localhost", 9300)))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_router_dispatch() {
+        let mut router = RestRouter::new();
+        router.register(Arc::new(HelloHandler)).unwrap();
+        assert!(router.register(Arc::new(HelloHandler)).is_err());
+
+        let context = context();
+        let handle = |request: ExtensionRestRequest| {
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+        };
+
+        let response = handle(
+            ExtensionRestRequest::new(Method::Get, "/hello/rust").with_param("pretty", "true"),
+        );
+        assert_eq!(response.content, b"Hello rust!");
+        let mut consumed = response.consumed_params.clone();
+        consumed.sort();
+        assert_eq!(consumed, vec!["name", "pretty"]);
+
+        assert_eq!(
+            handle(
+                ExtensionRestRequest::new(Method::Get, "/hello/rust")
+                    .with_param("verbose", "maybe")
+            )
+            .status,
+            RestStatus::BadRequest
+        );
+        let unrecognized =
+            handle(ExtensionRestRequest::new(Method::Get, "/hello/rust").with_param("size", "1"));
+        assert_eq!(unrecognized.status, RestStatus::BadRequest);
+        assert!(String::from_utf8(unrecognized.content)
+            .unwrap()
+            .contains("unrecognized parameter: [size]"));
+
+        assert_eq!(
+            handle(ExtensionRestRequest::new(Method::Get, "/hello/world")).content,
+            b"Hello literal!"
+        );
+        assert_eq!(
+            handle(ExtensionRestRequest::new(Method::Get, "/files/a/b.txt")).status,
+            RestStatus::Ok
+        );
+        assert_eq!(
+            handle(ExtensionRestRequest::new(Method::Get, "/nothing")).status,
+            RestStatus::BadRequest
+        );
+
+        let deprecated = handle(ExtensionRestRequest::new(Method::Get, "/hi/rust"));
+        assert_eq!(deprecated.content, b"Hello rust!");
+        assert_eq!(
+            deprecated.headers["Warning"],
+            vec![r#"299 OpenSearch "[GET /hi/{name}] is deprecated, use [GET /hello/{name}]""#]
+        );
+        assert_eq!(router.deprecated_calls().values().sum::<u64>(), 1);
+
+        let not_allowed = handle(ExtensionRestRequest::new(Method::Delete, "/hello/rust"));
+        assert_eq!(not_allowed.status, RestStatus::MethodNotAllowed);
+        assert_eq!(not_allowed.headers["Allow"], vec!["GET,PUT"]);
+    }
+
+    struct CountingHandler(Arc<std::sync::atomic::AtomicUsize>);
+
+    #[async_trait]
+    impl RestHandler for CountingHandler {
+        fn routes(&self) -> Vec<Route> {
+            vec![Route::new(Method::Post, "/jobs").params(["wait"])]
+        }
+
+        async fn handle(
+            &self,
+            _request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(RestResponse::ok())
+        }
+    }
+
+    struct EchoHandler;
+
+    #[async_trait]
+    impl RestHandler for EchoHandler {
+        fn routes(&self) -> Vec<Route> {
+            vec![Route::new(Method::Post, "/echo")]
+        }
+
+        async fn handle(
+            &self,
+            request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            Ok(RestResponse::ok().text(String::from_utf8_lossy(request.content()).into_owned()))
+        }
+    }
+
+    #[test]
+    fn test_content_consumed_only_when_read() {
+        let mut router = RestRouter::new();
+        router.register(Arc::new(HelloHandler)).unwrap();
+        router.register(Arc::new(EchoHandler)).unwrap();
+        let context = context();
+        let handle = |request: ExtensionRestRequest| {
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+        };
+
+        let echoed = handle(
+            ExtensionRestRequest::new(Method::Post, "/echo").with_content("text/plain", "hi"),
+        );
+        assert_eq!(echoed.content, b"hi");
+        assert!(echoed.content_consumed);
+
+        let ignored = handle(
+            ExtensionRestRequest::new(Method::Put, "/hello/rust").with_content("text/plain", "hi"),
+        );
+        assert_eq!(ignored.status, RestStatus::Ok);
+        assert!(!ignored.content_consumed);
+    }
+
+    #[test]
+    fn test_unrecognized_params_rejected_before_handler() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut router = RestRouter::new();
+        router
+            .register(Arc::new(CountingHandler(calls.clone())))
+            .unwrap();
+
+        let context = context();
+        let handle = |request: ExtensionRestRequest| {
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+        };
+
+        let rejected = handle(
+            ExtensionRestRequest::new(Method::Post, "/jobs")
+                .with_param("wait", "true")
+                .with_param("force", "true"),
+        );
+        assert_eq!(rejected.status, RestStatus::BadRequest);
+        assert!(String::from_utf8(rejected.content)
+            .unwrap()
+            .contains("unrecognized parameter: [force]"));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        assert_eq!(
+            handle(
+                ExtensionRestRequest::new(Method::Post, "/jobs")
+                    .with_param("wait", "true")
+                    .with_param("pretty", "")
+            )
+            .status,
+            RestStatus::Ok
+        );
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    struct LegacyHandler;
+
+    impl LegacyHandler {
+        fn old_route() -> Route {
+            Route::deprecated(Method::Get, "/old", r#"use [GET /new] with "v2" \ paths"#)
+        }
+
+        fn legacy_route() -> Route {
+            Route::deprecated(Method::Post, "/legacy", "[POST /legacy] is deprecated")
+        }
+    }
+
+    #[async_trait]
+    impl RestHandler for LegacyHandler {
+        fn routes(&self) -> Vec<Route> {
+            vec![
+                Route::new(Method::Get, "/new"),
+                Self::old_route(),
+                Self::legacy_route(),
+            ]
+        }
+
+        async fn handle(
+            &self,
+            _request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            Ok(RestResponse::ok())
+        }
+    }
+
+    #[test]
+    fn test_deprecated_routes_warn_and_count() {
+        let mut router = RestRouter::new();
+        router.register(Arc::new(LegacyHandler)).unwrap();
+
+        let context = context();
+        let handle = |request: ExtensionRestRequest| {
+            context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap()
+        };
+
+        let current = handle(ExtensionRestRequest::new(Method::Get, "/new"));
+        assert!(!current.headers.contains_key("Warning"));
+        assert!(router.deprecated_calls().is_empty());
+
+        for _ in 0..3 {
+            let old = handle(ExtensionRestRequest::new(Method::Get, "/old"));
+            assert_eq!(
+                old.headers["Warning"],
+                vec![r#"299 OpenSearch "use [GET /new] with \"v2\" \\ paths""#]
+            );
+        }
+        let rejected =
+            handle(ExtensionRestRequest::new(Method::Post, "/legacy").with_param("size", "1"));
+        assert_eq!(rejected.status, RestStatus::BadRequest);
+        assert_eq!(
+            rejected.headers["Warning"],
+            vec![r#"299 OpenSearch "[POST /legacy] is deprecated""#]
+        );
+
+        let calls = router.deprecated_calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[&LegacyHandler::old_route()], 3);
+        assert_eq!(calls[&LegacyHandler::legacy_route()], 1);
+    }
+
+    struct FailingHandler;
+
+    #[async_trait]
+    impl RestHandler for FailingHandler {
+        fn routes(&self) -> Vec<Route> {
+            vec![Route::new(Method::Get, "/fail/{kind}")]
+        }
+
+        async fn handle(
+            &self,
+            request: ExtensionRestRequest,
+            _context: &ExtensionContext,
+        ) -> Result<RestResponse, ExtensionError> {
+            match request
+                .param::<String>("kind")?
+                .unwrap_or_default()
+                .as_str()
+            {
+                "panic" => panic!("handler bug"),
+                "timeout" => Err(ExtensionError::timeout("cluster did not answer")),
+                _ => Err(ExtensionError::transport("connection refused")),
+            }
+        }
+    }
+
+    struct TransportAsBadGateway;
+
+    impl ErrorMapper for TransportAsBadGateway {
+        fn map_error(&self, error: &ExtensionError) -> Option<RestResponse> {
+            matches!(error, ExtensionError::TransportError(_)).then(|| {
+                RestResponse::exception(
+                    RestStatus::BadGateway,
+                    "cluster_unreachable_exception",
+                    error.to_string(),
+                )
+            })
+        }
+    }
+
+    #[test]
+    fn test_error_mapping() {
+        let mut router = RestRouter::new().with_error_mapper(Arc::new(TransportAsBadGateway));
+        router.register(Arc::new(FailingHandler)).unwrap();
+
+        let context = context();
+        let handle = |kind: &str| {
+            let request = ExtensionRestRequest::new(Method::Get, format!("/fail/{}", kind));
+            let response = context
+                .thread_pool
+                .block_on(router.handle(request, &context))
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&response.content).unwrap();
+            (
+                response.status,
+                body["error"]["type"].as_str().unwrap().to_string(),
+            )
+        };
+
+        assert_eq!(
+            handle("panic"),
+            (RestStatus::InternalServerError, "exception".to_string())
+        );
+        assert_eq!(
+            handle("timeout"),
+            (RestStatus::GatewayTimeout, "timeout_exception".to_string())
+        );
+        assert_eq!(
+            handle("down"),
+            (
+                RestStatus::BadGateway,
+                "cluster_unreachable_exception".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RestStatus {
+    Ok,
+    Created,
+    Accepted,
+    NoContent,
+    NotModified,
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    Conflict,
+    PreconditionFailed,
+    RequestEntityTooLarge,
+    UnsupportedMediaType,
+    TooManyRequests,
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    ServiceUnavailable,
+    GatewayTimeout,
+}
+
+impl RestStatus {
+    pub fn code(&self) -> u16 {
+        match self {
+            RestStatus::Ok => 200,
+            RestStatus::Created => 201,
+            RestStatus::Accepted => 202,
+            RestStatus::NoContent => 204,
+            RestStatus::NotModified => 304,
+            RestStatus::BadRequest => 400,
+            RestStatus::Unauthorized => 401,
+            RestStatus::Forbidden => 403,
+            RestStatus::NotFound => 404,
+            RestStatus::MethodNotAllowed => 405,
+            RestStatus::NotAcceptable => 406,
+            RestStatus::Conflict => 409,
+            RestStatus::PreconditionFailed => 412,
+            RestStatus::RequestEntityTooLarge => 413,
+            RestStatus::UnsupportedMediaType => 415,
+            RestStatus::TooManyRequests => 429,
+            RestStatus::InternalServerError => 500,
+            RestStatus::NotImplemented => 501,
+            RestStatus::BadGateway => 502,
+            RestStatus::ServiceUnavailable => 503,
+            RestStatus::GatewayTimeout => 504,
+        }
+    }
+
+    pub fn from_code(code: u16) -> Option<Self> {
+        let status = match code {
+            200 => RestStatus::Ok,
+            201 => RestStatus::Created,
+            202 => RestStatus::Accepted,
+            204 => RestStatus::NoContent,
+            304 => RestStatus::NotModified,
+            400 => RestStatus::BadRequest,
+            401 => RestStatus::Unauthorized,
+            403 => RestStatus::Forbidden,
+            404 => RestStatus::NotFound,
+            405 => RestStatus::MethodNotAllowed,
+            406 => RestStatus::NotAcceptable,
+            409 => RestStatus::Conflict,
+            412 => RestStatus::PreconditionFailed,
+            413 => RestStatus::RequestEntityTooLarge,
+            415 => RestStatus::UnsupportedMediaType,
+            429 => RestStatus::TooManyRequests,
+            500 => RestStatus::InternalServerError,
+            501 => RestStatus::NotImplemented,
+            502 => RestStatus::BadGateway,
+            503 => RestStatus::ServiceUnavailable,
+            504 => RestStatus::GatewayTimeout,
+            _ => return None,
+        };
+        Some(status)
+    }
+
+    /// Upper snake case name, as used in OpenSearch error bodies
+    pub fn name(&self) -> &'static str {
+        match self {
+            RestStatus::Ok => "OK",
+            RestStatus::Created => "CREATED",
+            RestStatus::Accepted => "ACCEPTED",
+            RestStatus::NoContent => "NO_CONTENT",
+            RestStatus::NotModified => "NOT_MODIFIED",
+            RestStatus::BadRequest => "BAD_REQUEST",
+            RestStatus::Unauthorized => "UNAUTHORIZED",
+            RestStatus::Forbidden => "FORBIDDEN",
+            RestStatus::NotFound => "NOT_FOUND",
+            RestStatus::MethodNotAllowed => "METHOD_NOT_ALLOWED",
+            RestStatus::NotAcceptable => "NOT_ACCEPTABLE",
+            RestStatus::Conflict => "CONFLICT",
+            RestStatus::PreconditionFailed => "PRECONDITION_FAILED",
+            RestStatus::RequestEntityTooLarge => "REQUEST_ENTITY_TOO_LARGE",
+            RestStatus::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            RestStatus::TooManyRequests => "TOO_MANY_REQUESTS",
+            RestStatus::InternalServerError => "INTERNAL_SERVER_ERROR",
+            RestStatus::NotImplemented => "NOT_IMPLEMENTED",
+            RestStatus::BadGateway => "BAD_GATEWAY",
+            RestStatus::ServiceUnavailable => "SERVICE_UNAVAILABLE",
+            RestStatus::GatewayTimeout => "GATEWAY_TIMEOUT",
+        }
+    }
+
+    /// Status with the given `name`, as written by OpenSearch's `RestStatus.writeTo`
+    pub fn from_name(name: &str) -> Option<Self> {
+        const ALL: [RestStatus; 21] = [
+            RestStatus::Ok,
+            RestStatus::Created,
+            RestStatus::Accepted,
+            RestStatus::NoContent,
+            RestStatus::NotModified,
+            RestStatus::BadRequest,
+            RestStatus::Unauthorized,
+            RestStatus::Forbidden,
+            RestStatus::NotFound,
+            RestStatus::MethodNotAllowed,
+            RestStatus::NotAcceptable,
+            RestStatus::Conflict,
+            RestStatus::PreconditionFailed,
+            RestStatus::RequestEntityTooLarge,
+            RestStatus::UnsupportedMediaType,
+            RestStatus::TooManyRequests,
+            RestStatus::InternalServerError,
+            RestStatus::NotImplemented,
+            RestStatus::BadGateway,
+            RestStatus::ServiceUnavailable,
+            RestStatus::GatewayTimeout,
+        ];
+        ALL.into_iter().find(|status| status.name() == name)
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code())
+    }
+}
+
+impl fmt::Display for RestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code(), self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_code_round_trip() {
+        for status in [
+            RestStatus::Ok,
+            RestStatus::NotFound,
+            RestStatus::ServiceUnavailable,
+        ] {
+            assert_eq!(RestStatus::from_code(status.code()), Some(status));
+        }
+        assert_eq!(RestStatus::from_code(299), None);
+        assert!(RestStatus::Created.is_success());
+        assert!(!RestStatus::BadRequest.is_success());
+    }
+}
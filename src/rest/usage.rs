@@ -0,0 +1,38 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::extension::{ExtensionContext, ExtensionError};
+use crate::rest::{ExtensionRestRequest, Method, RestHandler, RestResponse, Route};
+use crate::transport::UsageTracker;
+
+/// Serves the `UsageTracker` report at `GET /_usage`. `?reset=true` starts
+/// a new reporting period after responding.
+pub struct UsageReportHandler {
+    tracker: Arc<UsageTracker>,
+}
+
+impl UsageReportHandler {
+    pub fn new(tracker: Arc<UsageTracker>) -> Self {
+        UsageReportHandler { tracker }
+    }
+}
+
+#[async_trait]
+impl RestHandler for UsageReportHandler {
+    fn routes(&self) -> Vec<Route> {
+        vec![Route::new(Method::Get, "/_usage").params(["reset"])]
+    }
+
+    async fn handle(
+        &self,
+        request: ExtensionRestRequest,
+        _context: &ExtensionContext,
+    ) -> Result<RestResponse, ExtensionError> {
+        let report = if request.param_or("reset", false)? {
+            self.tracker.reset()
+        } else {
+            self.tracker.report()
+        };
+        RestResponse::ok().negotiate(&request).json(&report)
+    }
+}
@@ -1,14 +1,51 @@
+pub mod action;
 pub mod client;
+pub mod conformance;
+pub mod connections;
+pub mod deadline;
+pub mod endpoints;
+pub mod hedging;
+pub mod inbound;
+pub mod response;
+pub mod signing;
+pub mod spill;
+pub mod thread_context;
+pub mod usage;
 
 use std::io::{Error, ErrorKind, Read, Write};
 use std::net::TcpStream;
 
 pub use client::TransportClient;
+pub use connections::{ConnectionRegistry, ConnectionStats};
+pub use deadline::Deadline;
+pub use endpoints::{EndpointSet, EndpointWatcher};
+pub use hedging::{HedgeStats, HedgedTransport};
+pub use response::{AcknowledgedResponse, ExceptionResponse};
+pub use signing::MessageSigner;
+pub use spill::{ResponseSpooler, SpillStats};
+pub use thread_context::ThreadContext;
+pub use usage::{UsageScope, UsageTracker};
 
 const MARKER_BYTES: &[u8; 2] = b"ES";
+const MESSAGE_LENGTH_SIZE: usize = 4;
 const REQUEST_ID_SIZE: usize = 8;
 const STATUS_SIZE: usize = 1;
 const VERSION_ID_SIZE: usize = 4;
+const VARIABLE_HEADER_SIZE_SIZE: usize = 4;
+
+/// Size of the fixed part of the header, up to and including the variable header size
+pub const HEADER_SIZE: usize = MARKER_BYTES.len()
+    + MESSAGE_LENGTH_SIZE
+    + REQUEST_ID_SIZE
+    + STATUS_SIZE
+    + VERSION_ID_SIZE
+    + VARIABLE_HEADER_SIZE_SIZE;
+
+/// Action of the handshake OpenSearch sends when opening a connection
+pub const HANDSHAKE_ACTION: &str = "internal:tcp/handshake";
+
+/// Message length sent by OpenSearch for keep-alive pings (`-1` as a signed int)
+pub const PING_MESSAGE_LENGTH: u32 = u32::MAX;
 
 // Reference: https://github.com/opensearch-project/opensearch-sdk-py/blob/main/src/opensearch_sdk_py/transport/tcp_header.py
 #[derive(Debug)]
@@ -40,6 +77,7 @@ impl TransportTcpHeader {
             + REQUEST_ID_SIZE
             + STATUS_SIZE
             + VERSION_ID_SIZE
+            + VARIABLE_HEADER_SIZE_SIZE
             + variable_header_size as usize;
         Self {
             message_length: message_length
@@ -68,6 +106,51 @@ impl TransportTcpHeader {
         self.status == transport_status::STATUS_COMPRESS
     }
 
+    /// Number of content bytes following the variable header
+    pub fn content_size(&self) -> usize {
+        (self.message_length as usize).saturating_sub(
+            REQUEST_ID_SIZE
+                + STATUS_SIZE
+                + VERSION_ID_SIZE
+                + VARIABLE_HEADER_SIZE_SIZE
+                + self.variable_header_size as usize,
+        )
+    }
+
+    /// Parse the fixed header from the first `HEADER_SIZE` bytes of a message
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Header is truncated"));
+        }
+
+        if &bytes[..2] != MARKER_BYTES {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid header prefix"));
+        }
+
+        let be_u32 = |offset: usize| {
+            u32::from_be_bytes(bytes[offset..offset + 4].try_into().expect("4 byte slice"))
+        };
+
+        Ok(Self {
+            message_length: be_u32(2),
+            request_id: u64::from_be_bytes(bytes[6..14].try_into().expect("8 byte slice")),
+            status: bytes[14],
+            version: be_u32(15),
+            variable_header_size: be_u32(19),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(MARKER_BYTES);
+        bytes.extend_from_slice(&self.message_length.to_be_bytes());
+        bytes.extend_from_slice(&self.request_id.to_be_bytes());
+        bytes.push(self.status);
+        bytes.extend_from_slice(&self.version.to_be_bytes());
+        bytes.extend_from_slice(&self.variable_header_size.to_be_bytes());
+        bytes
+    }
+
     pub fn from_stream(mut stream: TcpStream) -> Result<Self, Error> {
         let mut prefix = [0u8; 2];
         stream
@@ -146,5 +229,22 @@ mod tests {
         assert_eq!(header.version, 1000);
         assert!(header.is_request_response());
         assert!(!header.is_handshake());
+        assert_eq!(header.content_size(), 100);
+    }
+
+    #[test]
+    fn test_header_bytes_round_trip() {
+        let header = TransportTcpHeader::new(42, transport_status::STATUS_REQRES, 7, 10, 3);
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), HEADER_SIZE);
+
+        let parsed = TransportTcpHeader::parse(&bytes).unwrap();
+        assert_eq!(parsed.request_id, 42);
+        assert_eq!(parsed.version, 7);
+        assert_eq!(parsed.variable_header_size, 3);
+        assert_eq!(parsed.message_length, header.message_length);
+        assert_eq!(parsed.content_size(), 10);
+
+        assert!(TransportTcpHeader::parse(b"XX").is_err());
     }
 }
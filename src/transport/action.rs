@@ -0,0 +1,383 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+use tracing::error;
+
+use crate::extension::diagnostics::Diagnostics;
+use crate::extension::metadata::ExtensionMetrics;
+use crate::extension::slow_log::{time_handler, SlowLogTarget};
+use crate::extension::ExtensionError;
+use crate::interface::{
+    read_byte_array, read_string, read_string_array, read_task_id, write_byte_array,
+    write_empty_task_id, write_string, write_string_array, Deserialize, Serialize,
+};
+use crate::rest::error_mapper::catch_panic;
+use crate::transport::TransportClient;
+
+pub const REGISTER_TRANSPORT_ACTIONS_ACTION: &str = "internal:discovery/registertransportactions";
+pub const HANDLE_TRANSPORT_ACTION: &str = "internal:extensions/handle-transportaction";
+
+/// A named action the extension exposes to OpenSearch and other plugins.
+#[async_trait]
+pub trait TransportAction: Send + Sync + 'static {
+    fn name(&self) -> &str;
+
+    async fn execute(&self, request: Vec<u8>) -> Result<Vec<u8>, ExtensionError>;
+}
+
+/// Records request count, failures and durations per transport action.
+#[derive(Debug, Default)]
+pub struct ActionMetrics {
+    actions: Mutex<HashMap<String, ExtensionMetrics>>,
+}
+
+impl ActionMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, action: &str, duration_ms: f64, success: bool) {
+        self.actions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(action.to_string())
+            .or_default()
+            .record_request(duration_ms, success);
+    }
+
+    pub fn action_metrics(&self, action: &str) -> Option<ExtensionMetrics> {
+        self.actions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(action)
+            .cloned()
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, ExtensionMetrics> {
+        self.actions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct TransportActionRegistry {
+    actions: HashMap<String, Arc<dyn TransportAction>>,
+    metrics: Arc<ActionMetrics>,
+    diagnostics: Option<Arc<Diagnostics>>,
+}
+
+impl TransportActionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count panics caught in actions in `diagnostics`.
+    pub fn with_diagnostics(mut self, diagnostics: Arc<Diagnostics>) -> Self {
+        self.diagnostics = Some(diagnostics);
+        self
+    }
+
+    pub fn register(&mut self, action: Arc<dyn TransportAction>) -> Result<(), ExtensionError> {
+        let name = action.name().to_string();
+        if self.actions.contains_key(&name) {
+            return Err(ExtensionError::configuration(format!(
+                "Transport action '{}' is already registered",
+                name
+            )));
+        }
+
+        self.actions.insert(name, action);
+        Ok(())
+    }
+
+    pub fn action_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.actions.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub async fn handle(
+        &self,
+        request: ExtensionActionRequest,
+    ) -> Result<ExtensionActionResponse, ExtensionError> {
+        let action = self.actions.get(&request.action).ok_or_else(|| {
+            ExtensionError::protocol(format!(
+                "No transport action registered for '{}'",
+                request.action
+            ))
+        })?;
+
+        let start = Instant::now();
+        let target = SlowLogTarget::Action(request.action.clone());
+        let result =
+            match time_handler(target, catch_panic(action.execute(request.request_bytes))).await {
+                Ok(result) => result,
+                Err(panic) => {
+                    error!("Transport action {} panicked: {}", request.action, panic);
+                    if let Some(diagnostics) = &self.diagnostics {
+                        diagnostics.record_panic(&request.action, &panic);
+                    }
+                    Err(ExtensionError::unknown(format!(
+                        "transport action [{}] failed unexpectedly",
+                        request.action
+                    )))
+                }
+            };
+        self.metrics.record(
+            &request.action,
+            start.elapsed().as_secs_f64() * 1000.0,
+            result.is_ok(),
+        );
+        Ok(ExtensionActionResponse::new(result?))
+    }
+
+    /// Latency and failures of the registered actions.
+    pub fn metrics(&self) -> &Arc<ActionMetrics> {
+        &self.metrics
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterTransportActionsRequest {
+    pub unique_id: String,
+    pub actions: Vec<String>,
+}
+
+impl RegisterTransportActionsRequest {
+    pub fn new(unique_id: impl Into<String>, actions: Vec<String>) -> Self {
+        RegisterTransportActionsRequest {
+            unique_id: unique_id.into(),
+            actions,
+        }
+    }
+}
+
+impl Serialize for RegisterTransportActionsRequest {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_empty_task_id(buf)?;
+        written += write_string(buf, &self.unique_id)?;
+        Ok(written + write_string_array(buf, &self.actions)?)
+    }
+}
+
+impl Deserialize for RegisterTransportActionsRequest {
+    type Output = RegisterTransportActionsRequest;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        read_task_id(buf)?;
+        let unique_id = read_string(buf)?;
+        let actions = read_string_array(buf)?;
+        Ok(RegisterTransportActionsRequest { unique_id, actions })
+    }
+}
+
+/// Payload of `internal:extensions/handle-transportaction`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionActionRequest {
+    pub action: String,
+    pub request_bytes: Vec<u8>,
+}
+
+impl ExtensionActionRequest {
+    pub fn new(action: impl Into<String>, request_bytes: Vec<u8>) -> Self {
+        ExtensionActionRequest {
+            action: action.into(),
+            request_bytes,
+        }
+    }
+}
+
+impl Serialize for ExtensionActionRequest {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let written = write_string(buf, &self.action)?;
+        Ok(written + write_byte_array(buf, &self.request_bytes)?)
+    }
+}
+
+impl Deserialize for ExtensionActionRequest {
+    type Output = ExtensionActionRequest;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let action = read_string(buf)?;
+        let request_bytes = read_byte_array(buf)?;
+        Ok(ExtensionActionRequest {
+            action,
+            request_bytes,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionActionResponse {
+    pub response_bytes: Vec<u8>,
+}
+
+impl ExtensionActionResponse {
+    pub fn new(response_bytes: Vec<u8>) -> Self {
+        ExtensionActionResponse { response_bytes }
+    }
+}
+
+impl Serialize for ExtensionActionResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        write_byte_array(buf, &self.response_bytes)
+    }
+}
+
+impl Deserialize for ExtensionActionResponse {
+    type Output = ExtensionActionResponse;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        Ok(ExtensionActionResponse::new(read_byte_array(buf)?))
+    }
+}
+
+pub async fn register_transport_actions(
+    client: &TransportClient,
+    request: &RegisterTransportActionsRequest,
+) -> Result<(), ExtensionError> {
+    let mut bytes = Vec::new();
+    request.serialize(&mut bytes).map_err(|e| {
+        ExtensionError::serialization(format!("Failed to serialize transport actions: {}", e))
+    })?;
+
+    client
+        .expect_ack(REGISTER_TRANSPORT_ACTIONS_ACTION, &bytes)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EchoAction;
+
+    #[async_trait]
+    impl TransportAction for EchoAction {
+        fn name(&self) -> &str {
+            "cluster:admin/hello/echo"
+        }
+
+        async fn execute(&self, request: Vec<u8>) -> Result<Vec<u8>, ExtensionError> {
+            Ok(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_dispatch() {
+        let mut registry = TransportActionRegistry::new();
+        registry.register(Arc::new(EchoAction)).unwrap();
+        assert!(registry.register(Arc::new(EchoAction)).is_err());
+        assert_eq!(registry.action_names(), vec!["cluster:admin/hello/echo"]);
+
+        let response = registry
+            .handle(ExtensionActionRequest::new(
+                "cluster:admin/hello/echo",
+                b"ping".to_vec(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.response_bytes, b"ping");
+        let echo = registry
+            .metrics()
+            .action_metrics("cluster:admin/hello/echo")
+            .unwrap();
+        assert_eq!((echo.requests_total, echo.requests_failed), (1, 0));
+        assert!(echo.request_duration_percentile(0.99).is_some());
+
+        let missing = registry
+            .handle(ExtensionActionRequest::new(
+                "cluster:admin/hello/missing",
+                vec![],
+            ))
+            .await;
+        assert!(missing.is_err());
+    }
+
+    struct PanickingAction;
+
+    #[async_trait]
+    impl TransportAction for PanickingAction {
+        fn name(&self) -> &str {
+            "cluster:admin/hello/panic"
+        }
+
+        async fn execute(&self, _request: Vec<u8>) -> Result<Vec<u8>, ExtensionError> {
+            panic!("index out of bounds")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicking_action_becomes_error() {
+        let diagnostics = Arc::new(Diagnostics::new());
+        let mut registry = TransportActionRegistry::new().with_diagnostics(diagnostics.clone());
+        registry.register(Arc::new(PanickingAction)).unwrap();
+        registry.register(Arc::new(EchoAction)).unwrap();
+
+        let error = registry
+            .handle(ExtensionActionRequest::new(
+                "cluster:admin/hello/panic",
+                vec![],
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown error: transport action [cluster:admin/hello/panic] failed unexpectedly"
+        );
+        let panicked = registry
+            .metrics()
+            .action_metrics("cluster:admin/hello/panic")
+            .unwrap();
+        assert_eq!((panicked.requests_total, panicked.requests_failed), (1, 1));
+        assert_eq!(diagnostics.handler_panics()["cluster:admin/hello/panic"], 1);
+        assert_eq!(
+            diagnostics.recent_errors()[0].message,
+            "cluster:admin/hello/panic: index out of bounds"
+        );
+
+        let response = registry
+            .handle(ExtensionActionRequest::new(
+                "cluster:admin/hello/echo",
+                b"ping".to_vec(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.response_bytes, b"ping");
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let register = RegisterTransportActionsRequest::new(
+            "hello-world-rs",
+            vec!["a".to_string(), "b".to_string()],
+        );
+        let mut bytes = Vec::new();
+        register.serialize(&mut bytes).unwrap();
+        // Empty parent TaskId, then the unique id and the action names as Java strings.
+        let mut expected = vec![0x00, 14];
+        expected.extend_from_slice(b"hello-world-rs");
+        expected.extend_from_slice(&[2, 1, b'a', 1, b'b']);
+        assert_eq!(bytes, expected);
+        assert_eq!(
+            RegisterTransportActionsRequest::deserialize(&mut bytes.as_slice()).unwrap(),
+            register
+        );
+
+        let request = ExtensionActionRequest::new("a", vec![1, 2, 3]);
+        let mut bytes = Vec::new();
+        request.serialize(&mut bytes).unwrap();
+        assert_eq!(
+            ExtensionActionRequest::deserialize(&mut bytes.as_slice()).unwrap(),
+            request
+        );
+    }
+}
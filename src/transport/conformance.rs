@@ -0,0 +1,410 @@
+use std::fmt;
+use std::io;
+
+use crate::interface::read_string_unit;
+use crate::transport::{transport_status, HEADER_SIZE};
+
+/// Status bits OpenSearch defines; any other bit is reserved.
+const KNOWN_STATUS_BITS: u8 = transport_status::STATUS_REQRES
+    | transport_status::STATUS_ERROR
+    | transport_status::STATUS_COMPRESS
+    | transport_status::STATUS_HANDSHAKE;
+
+/// Largest count or length accepted before it is reported as implausible.
+const MAX_PLAUSIBLE_COUNT: u32 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    Truncated,
+    InvalidMarker,
+    LengthMismatch,
+    ReservedBits,
+    InvalidUtf8,
+    OutOfRange,
+    NonCanonicalVInt,
+    TrailingBytes,
+}
+
+/// One deviation from the transport protocol, located by byte offset and field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub offset: usize,
+    pub field: String,
+    pub kind: ViolationKind,
+    pub detail: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at offset {} ({:?}): {}",
+            self.field, self.offset, self.kind, self.detail
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConformanceReport {
+    pub violations: Vec<Violation>,
+}
+
+impl ConformanceReport {
+    pub fn is_conformant(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.violations.is_empty() {
+            return write!(f, "message conforms to the transport protocol");
+        }
+        write!(f, "{} protocol violation(s)", self.violations.len())?;
+        for violation in &self.violations {
+            write!(f, "; {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cursor that records a violation instead of failing on malformed input.
+/// Reads return `None` once the input is exhausted.
+struct StrictReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    report: ConformanceReport,
+}
+
+impl<'a> StrictReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        StrictReader {
+            bytes,
+            offset: 0,
+            report: ConformanceReport::default(),
+        }
+    }
+
+    fn violation(
+        &mut self,
+        offset: usize,
+        field: &str,
+        kind: ViolationKind,
+        detail: impl Into<String>,
+    ) {
+        self.report.violations.push(Violation {
+            offset,
+            field: field.to_string(),
+            kind,
+            detail: detail.into(),
+        });
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    fn take(&mut self, len: usize, field: &str) -> Option<&'a [u8]> {
+        if len > self.remaining() {
+            let detail = format!("needs {} bytes, {} left", len, self.remaining());
+            self.violation(self.offset, field, ViolationKind::Truncated, detail);
+            self.offset = self.bytes.len();
+            return None;
+        }
+        let bytes = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self, field: &str) -> Option<u8> {
+        self.take(1, field).map(|b| b[0])
+    }
+
+    fn u32(&mut self, field: &str) -> Option<u32> {
+        self.take(4, field)
+            .map(|b| u32::from_be_bytes(b.try_into().expect("4 byte slice")))
+    }
+
+    fn u64(&mut self, field: &str) -> Option<u64> {
+        self.take(8, field)
+            .map(|b| u64::from_be_bytes(b.try_into().expect("8 byte slice")))
+    }
+
+    fn vint(&mut self, field: &str) -> Option<u32> {
+        let start = self.offset;
+        let mut value = 0u32;
+        for (i, shift) in (0..35).step_by(7).enumerate() {
+            let byte = self.u8(field)?;
+            if i == 4 && byte & 0xF0 != 0 {
+                self.violation(
+                    start,
+                    field,
+                    ViolationKind::OutOfRange,
+                    "variable-length int overflows 32 bits",
+                );
+                return None;
+            }
+            value |= ((byte & 0x7F) as u32) << shift;
+            if byte & 0x80 == 0 {
+                if i > 0 && byte == 0 {
+                    self.violation(
+                        start,
+                        field,
+                        ViolationKind::NonCanonicalVInt,
+                        format!("{} encoded in {} bytes", value, i + 1),
+                    );
+                }
+                return Some(value);
+            }
+        }
+        unreachable!("the fifth byte always terminates or overflows")
+    }
+
+    /// A count or length, checked against what is left of the input.
+    fn length(&mut self, field: &str, min_item_size: usize) -> Option<usize> {
+        let start = self.offset;
+        let len = self.vint(field)?;
+        if len > MAX_PLAUSIBLE_COUNT || len as usize * min_item_size > self.remaining() {
+            let detail = format!("{} exceeds the {} bytes left", len, self.remaining());
+            self.violation(start, field, ViolationKind::LengthMismatch, detail);
+            return None;
+        }
+        Some(len as usize)
+    }
+
+    fn string(&mut self, field: &str) -> Option<String> {
+        let len = self.length(field, 1)?;
+        let start = self.offset;
+        let mut units = Vec::with_capacity(len);
+        for _ in 0..len {
+            let unit_offset = self.offset;
+            let mut rest = &self.bytes[self.offset..];
+            match read_string_unit(&mut rest) {
+                Ok(unit) => units.push(unit),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.violation(
+                        unit_offset,
+                        field,
+                        ViolationKind::Truncated,
+                        "string ends mid-character",
+                    );
+                    self.offset = self.bytes.len();
+                    return None;
+                }
+                Err(e) => {
+                    self.violation(
+                        unit_offset,
+                        field,
+                        ViolationKind::InvalidUtf8,
+                        e.to_string(),
+                    );
+                    return None;
+                }
+            }
+            self.offset = self.bytes.len() - rest.len();
+        }
+        match String::from_utf16(&units) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                self.violation(start, field, ViolationKind::InvalidUtf8, e.to_string());
+                Some(String::from_utf16_lossy(&units))
+            }
+        }
+    }
+
+    fn string_array(&mut self, field: &str) -> Option<Vec<String>> {
+        let count = self.length(field, 1)?;
+        (0..count)
+            .map(|i| self.string(&format!("{}[{}]", field, i)))
+            .collect()
+    }
+}
+
+/// Check every field of a complete transport message: the fixed header,
+/// the thread context, features and action of requests, and the lengths
+/// tying them together. Compressed content is not inspected.
+pub fn validate_message(bytes: &[u8]) -> ConformanceReport {
+    let mut reader = StrictReader::new(bytes);
+    validate(&mut reader);
+    reader.report
+}
+
+fn validate(r: &mut StrictReader<'_>) -> Option<()> {
+    let marker_offset = r.offset;
+    let marker = r.take(2, "header.marker")?;
+    if marker != b"ES" {
+        r.violation(
+            marker_offset,
+            "header.marker",
+            ViolationKind::InvalidMarker,
+            format!("expected \"ES\", got {:?}", marker),
+        );
+    }
+
+    let length_offset = r.offset;
+    let message_length = r.u32("header.message_length")? as usize;
+    let fixed = HEADER_SIZE - 6;
+    if message_length < fixed {
+        let detail = format!(
+            "{} is shorter than the {} byte fixed header",
+            message_length, fixed
+        );
+        r.violation(
+            length_offset,
+            "header.message_length",
+            ViolationKind::LengthMismatch,
+            detail,
+        );
+    } else if message_length != r.remaining() {
+        let detail = format!(
+            "declares {} bytes, message has {}",
+            message_length,
+            r.remaining()
+        );
+        r.violation(
+            length_offset,
+            "header.message_length",
+            ViolationKind::LengthMismatch,
+            detail,
+        );
+    }
+
+    r.u64("header.request_id")?;
+    let status_offset = r.offset;
+    let status = r.u8("header.status")?;
+    if status & !KNOWN_STATUS_BITS != 0 {
+        let detail = format!(
+            "status {:#010b} sets reserved bits {:#010b}",
+            status,
+            status & !KNOWN_STATUS_BITS
+        );
+        r.violation(
+            status_offset,
+            "header.status",
+            ViolationKind::ReservedBits,
+            detail,
+        );
+    }
+    r.u32("header.version")?;
+
+    let size_offset = r.offset;
+    let variable_header_size = r.u32("header.variable_header_size")? as usize;
+    if variable_header_size > r.remaining() {
+        let detail = format!(
+            "{} exceeds the {} bytes left",
+            variable_header_size,
+            r.remaining()
+        );
+        r.violation(
+            size_offset,
+            "header.variable_header_size",
+            ViolationKind::LengthMismatch,
+            detail,
+        );
+        return None;
+    }
+    let variable_end = r.offset + variable_header_size;
+
+    for i in 0..r.length("thread_context.request_headers", 2)? {
+        r.string(&format!("thread_context.request_headers[{}].name", i))?;
+        r.string(&format!("thread_context.request_headers[{}].value", i))?;
+    }
+    for i in 0..r.length("thread_context.response_headers", 2)? {
+        r.string(&format!("thread_context.response_headers[{}].name", i))?;
+        r.string_array(&format!("thread_context.response_headers[{}].values", i))?;
+    }
+
+    if status & transport_status::STATUS_REQRES == 0 {
+        r.string_array("features")?;
+        let action_offset = r.offset;
+        let action = r.string("action")?;
+        if action.is_empty() && status & transport_status::STATUS_HANDSHAKE == 0 {
+            r.violation(
+                action_offset,
+                "action",
+                ViolationKind::OutOfRange,
+                "request has an empty action",
+            );
+        }
+    }
+
+    if r.offset > variable_end {
+        let detail = format!(
+            "variable header runs {} bytes past its declared size",
+            r.offset - variable_end
+        );
+        r.violation(
+            variable_end,
+            "header.variable_header_size",
+            ViolationKind::LengthMismatch,
+            detail,
+        );
+    } else if r.offset < variable_end {
+        let detail = format!(
+            "{} unparsed bytes in the variable header",
+            variable_end - r.offset
+        );
+        r.violation(
+            r.offset,
+            "variable_header",
+            ViolationKind::TrailingBytes,
+            detail,
+        );
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::{write_string_array, Serialize};
+    use crate::transport::{ThreadContext, TransportTcpHeader};
+
+    fn request(status: u8, action: &[u8]) -> Vec<u8> {
+        let mut variable_header = Vec::new();
+        ThreadContext::new()
+            .serialize(&mut variable_header)
+            .unwrap();
+        write_string_array(&mut variable_header, &[]).unwrap();
+        variable_header.push(action.len() as u8);
+        variable_header.extend_from_slice(action);
+
+        let header = TransportTcpHeader::new(1, status, 1, 3, variable_header.len() as u32);
+        let mut bytes = header.to_bytes();
+        bytes.extend_from_slice(&variable_header);
+        bytes.extend_from_slice(b"abc");
+        bytes
+    }
+
+    #[test]
+    fn test_validate_message() {
+        let valid = request(0, b"internal:test");
+        assert!(validate_message(&valid).is_conformant());
+
+        let report = validate_message(&request(0x40, b"bad\xffaction"));
+        let kinds: Vec<_> = report
+            .violations
+            .iter()
+            .map(|v| (v.field.as_str(), v.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("header.status", ViolationKind::ReservedBits),
+                ("action", ViolationKind::InvalidUtf8)
+            ]
+        );
+        assert_eq!(report.violations[1].offset, HEADER_SIZE + 4 + 3);
+
+        let truncated = &valid[..valid.len() - 2];
+        let report = validate_message(truncated);
+        assert_eq!(report.violations[0].kind, ViolationKind::LengthMismatch);
+
+        let mut reader = StrictReader::new(&[0x81, 0x00]);
+        assert_eq!(reader.vint("count"), Some(1));
+        assert_eq!(
+            reader.report.violations[0].kind,
+            ViolationKind::NonCanonicalVInt
+        );
+    }
+}
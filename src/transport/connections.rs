@@ -0,0 +1,274 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::info;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Live counters of one OpenSearch peer connection.
+#[derive(Debug)]
+pub struct ConnectionCounters {
+    id: u64,
+    peer: SocketAddr,
+    opened_at: u64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    requests: AtomicU64,
+    errors: AtomicU64,
+    in_flight: AtomicU64,
+    handshake_rtt_micros: AtomicU64,
+    last_activity: AtomicU64,
+}
+
+/// Counts a request as in flight on its connection until dropped.
+pub struct InFlightRequest<'a>(&'a ConnectionCounters);
+
+impl Drop for InFlightRequest<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ConnectionCounters {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn record_inbound(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.last_activity.store(now_millis(), Ordering::Relaxed);
+    }
+
+    pub fn record_outbound(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.last_activity.store(now_millis(), Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark a request as being handled until the returned guard is dropped.
+    pub fn begin_request(&self) -> InFlightRequest<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightRequest(self)
+    }
+
+    /// Record the handshake round trip. Only the first one counts.
+    pub fn record_handshake(&self, rtt: Duration) {
+        let micros = (rtt.as_micros() as u64).max(1);
+        let _ = self.handshake_rtt_micros.compare_exchange(
+            0,
+            micros,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    pub fn snapshot(&self) -> ConnectionStats {
+        let rtt = self.handshake_rtt_micros.load(Ordering::Relaxed);
+        ConnectionStats {
+            id: self.id,
+            peer: self.peer.to_string(),
+            opened_at_millis: self.opened_at,
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            handshake_rtt_micros: (rtt > 0).then_some(rtt),
+            last_activity_millis: self.last_activity.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time view of a connection. Timestamps are milliseconds since
+/// the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionStats {
+    pub id: u64,
+    pub peer: String,
+    pub opened_at_millis: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub requests: u64,
+    pub errors: u64,
+    /// Requests being handled right now.
+    pub in_flight: u64,
+    /// Time from accepting the connection to answering its first handshake.
+    pub handshake_rtt_micros: Option<u64>,
+    pub last_activity_millis: u64,
+}
+
+impl ConnectionStats {
+    /// Labels identifying this connection on exported metrics.
+    pub fn labels(&self) -> BTreeMap<&'static str, String> {
+        BTreeMap::from([
+            ("connection_id", self.id.to_string()),
+            ("peer", self.peer.clone()),
+        ])
+    }
+}
+
+/// Registers a connection for as long as it is held.
+pub struct ConnectionGuard {
+    registry: Arc<ConnectionRegistry>,
+    counters: Arc<ConnectionCounters>,
+}
+
+impl ConnectionGuard {
+    pub fn counters(&self) -> &ConnectionCounters {
+        &self.counters
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.registry
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.counters.id);
+    }
+}
+
+/// Connections from OpenSearch peers that are currently open.
+#[derive(Debug, Default)]
+pub struct ConnectionRegistry {
+    next_id: AtomicU64,
+    connections: Mutex<BTreeMap<u64, Arc<ConnectionCounters>>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking a connection from `peer` until the guard is dropped.
+    pub fn open(self: &Arc<Self>, peer: SocketAddr) -> ConnectionGuard {
+        let now = now_millis();
+        let counters = Arc::new(ConnectionCounters {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+            peer,
+            opened_at: now,
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            handshake_rtt_micros: AtomicU64::new(0),
+            last_activity: AtomicU64::new(now),
+        });
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(counters.id, counters.clone());
+        ConnectionGuard {
+            registry: self.clone(),
+            counters,
+        }
+    }
+
+    pub fn connections(&self) -> Vec<ConnectionStats> {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .map(|c| c.snapshot())
+            .collect()
+    }
+
+    /// Requests being handled across all open connections.
+    pub fn in_flight(&self) -> u64 {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .map(|c| c.in_flight.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn get(&self, id: u64) -> Option<ConnectionStats> {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+            .map(|c| c.snapshot())
+    }
+
+    /// Log one line per live connection every `interval` until the returned
+    /// task is aborted.
+    pub fn start_diagnostics_logging(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                for stats in self.connections() {
+                    info!(
+                        "Connection {} to {}: {} requests ({} failed), {} bytes in, {} bytes out, handshake {:?}us, idle {}ms",
+                        stats.id,
+                        stats.peer,
+                        stats.requests,
+                        stats.errors,
+                        stats.bytes_in,
+                        stats.bytes_out,
+                        stats.handshake_rtt_micros,
+                        now_millis().saturating_sub(stats.last_activity_millis)
+                    );
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_lifecycle() {
+        let registry = Arc::new(ConnectionRegistry::new());
+        let guard = registry.open("10.0.0.1:9300".parse().unwrap());
+        let other = registry.open("10.0.0.2:9300".parse().unwrap());
+
+        guard.counters().record_inbound(100);
+        guard.counters().record_outbound(40);
+        guard.counters().record_error();
+        guard
+            .counters()
+            .record_handshake(Duration::from_micros(250));
+        guard.counters().record_handshake(Duration::from_millis(5));
+        let request = other.counters().begin_request();
+        assert_eq!(registry.in_flight(), 1);
+        drop(request);
+        assert_eq!(registry.in_flight(), 0);
+
+        let stats = registry.get(guard.counters().id).unwrap();
+        assert_eq!(
+            (
+                stats.bytes_in,
+                stats.bytes_out,
+                stats.requests,
+                stats.errors
+            ),
+            (100, 40, 1, 1)
+        );
+        assert_eq!(stats.handshake_rtt_micros, Some(250));
+        assert_eq!(stats.labels()["peer"], "10.0.0.1:9300");
+
+        drop(other);
+        assert_eq!(registry.connections().len(), 1);
+        drop(guard);
+        assert!(registry.connections().is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::extension::ExtensionError;
+use crate::transport::ThreadContext;
+
+/// Request header carrying the caller's remaining time budget in milliseconds.
+pub const DEADLINE_HEADER: &str = "_extension_deadline_ms";
+
+tokio::task_local! {
+    static DEADLINE: Deadline;
+}
+
+/// The moment by which the request being handled must be answered.
+/// `SdkClient` calls made inside `Deadline::run` fail fast once it has
+/// passed and are otherwise cut off when it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Self {
+        Deadline(Instant::now() + timeout)
+    }
+
+    pub fn at(instant: Instant) -> Self {
+        Deadline(instant)
+    }
+
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// The deadline of the enclosing `run`, if any.
+    pub fn current() -> Option<Deadline> {
+        DEADLINE.try_with(|deadline| *deadline).ok()
+    }
+
+    /// Run `future` under this deadline. Inside another `run` the earlier of
+    /// the two applies, so nested calls never extend their caller's budget.
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        let deadline = Self::current().map_or(self, |current| current.min(self));
+        DEADLINE.scope(deadline, future).await
+    }
+
+    /// The deadline a caller sent in `DEADLINE_HEADER`, if any.
+    pub fn from_thread_context(context: &ThreadContext) -> Option<Deadline> {
+        let millis = context.request_header(DEADLINE_HEADER)?.parse().ok()?;
+        Some(Deadline::after(Duration::from_millis(millis)))
+    }
+
+    /// Pass the remaining budget on to the receiver of `context`.
+    pub fn write_to(&self, context: &mut ThreadContext) {
+        context.put_request_header(DEADLINE_HEADER, self.remaining().as_millis().to_string());
+    }
+
+    /// Bound `future`, which does `what`, by the current deadline: fail at
+    /// once if it has passed, and with a timeout if it passes meanwhile.
+    pub async fn enforce<F, T>(what: &str, future: F) -> Result<T, ExtensionError>
+    where
+        F: Future<Output = Result<T, ExtensionError>>,
+    {
+        let Some(deadline) = Self::current() else {
+            return future.await;
+        };
+        if deadline.is_expired() {
+            return Err(ExtensionError::timeout(format!(
+                "Deadline exceeded before {}",
+                what
+            )));
+        }
+        tokio::time::timeout(deadline.remaining(), future)
+            .await
+            .map_err(|_| ExtensionError::timeout(format!("Deadline exceeded during {}", what)))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_nested_calls_inherit_remaining_budget() {
+        assert_eq!(Deadline::current(), None);
+        assert_eq!(
+            Deadline::enforce("search", async { Ok(1) }).await.unwrap(),
+            1
+        );
+
+        let mut context = ThreadContext::new();
+        Deadline::after(Duration::from_millis(50)).write_to(&mut context);
+        let inbound = Deadline::from_thread_context(&context).unwrap();
+        assert!(inbound.remaining() <= Duration::from_millis(50));
+
+        inbound
+            .run(async {
+                let outer = Deadline::current().unwrap();
+                Deadline::after(Duration::from_secs(60))
+                    .run(async {
+                        assert_eq!(Deadline::current(), Some(outer));
+                    })
+                    .await;
+
+                let slow = Deadline::enforce("search", async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(())
+                });
+                assert!(matches!(slow.await, Err(ExtensionError::TimeoutError(_))));
+
+                let error = Deadline::enforce("get", async { Ok(()) })
+                    .await
+                    .unwrap_err();
+                assert_eq!(
+                    error.to_string(),
+                    "Timeout error: Deadline exceeded before get"
+                );
+            })
+            .await;
+    }
+}
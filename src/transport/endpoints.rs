@@ -0,0 +1,211 @@
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::extension::discovery_backend::DiscoveryBackend;
+use crate::extension::ExtensionError;
+use crate::transport::client::TransportConnectionPool;
+
+/// Addresses currently behind the OpenSearch endpoint, handed out round-robin.
+#[derive(Debug, Default)]
+pub struct EndpointSet {
+    addresses: RwLock<Vec<SocketAddr>>,
+    next: AtomicUsize,
+}
+
+/// Change between two resolutions of an endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointDiff {
+    pub added: Vec<SocketAddr>,
+    pub removed: Vec<SocketAddr>,
+}
+
+impl EndpointDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl EndpointSet {
+    pub fn new(addresses: Vec<SocketAddr>) -> Self {
+        let set = EndpointSet::default();
+        set.replace(addresses);
+        set
+    }
+
+    pub fn addresses(&self) -> Vec<SocketAddr> {
+        self.addresses
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Next address to connect to, or `None` while the set is empty.
+    pub fn next(&self) -> Option<SocketAddr> {
+        let addresses = self
+            .addresses
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        if addresses.is_empty() {
+            return None;
+        }
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        Some(addresses[i % addresses.len()])
+    }
+
+    /// Swap in a freshly resolved address set, returning what changed.
+    pub fn replace(&self, addresses: Vec<SocketAddr>) -> EndpointDiff {
+        let new: BTreeSet<_> = addresses.into_iter().collect();
+        let mut current = self
+            .addresses
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let old: BTreeSet<_> = current.iter().copied().collect();
+
+        let diff = EndpointDiff {
+            added: new.difference(&old).copied().collect(),
+            removed: old.difference(&new).copied().collect(),
+        };
+        if !diff.is_empty() {
+            *current = new.into_iter().collect();
+        }
+        diff
+    }
+}
+
+/// Re-resolves the DNS name of an OpenSearch endpoint, or asks a
+/// `DiscoveryBackend` for it, on an interval and keeps an `EndpointSet` current. Idle pooled connections to removed
+/// addresses are dropped and new addresses are connected to ahead of use;
+/// requests already running keep their own connection until they finish.
+pub struct EndpointWatcher {
+    host: String,
+    port: u16,
+    endpoints: Arc<EndpointSet>,
+    pool: Option<Arc<TransportConnectionPool>>,
+    backend: Option<Arc<dyn DiscoveryBackend>>,
+    interval: Duration,
+}
+
+impl EndpointWatcher {
+    pub fn new(host: impl Into<String>, port: u16, endpoints: Arc<EndpointSet>) -> Self {
+        EndpointWatcher {
+            host: host.into(),
+            port,
+            endpoints,
+            pool: None,
+            backend: None,
+            interval: Duration::from_secs(30),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Drain and warm `pool` as addresses come and go.
+    pub fn with_pool(mut self, pool: Arc<TransportConnectionPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Locate the endpoint through `backend`, with the host as the service
+    /// name, instead of resolving the host.
+    pub fn with_backend(mut self, backend: Arc<dyn DiscoveryBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub async fn resolve(&self) -> Result<Vec<SocketAddr>, ExtensionError> {
+        if let Some(backend) = &self.backend {
+            return backend.locate(&self.host).await;
+        }
+        let addresses = tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| {
+                ExtensionError::transport(format!("Failed to resolve {}: {}", self.host, e))
+            })?;
+        Ok(addresses.collect())
+    }
+
+    /// Resolve once and apply the result. A failed or empty resolution
+    /// leaves the current addresses in place.
+    pub async fn refresh(&self) -> Result<EndpointDiff, ExtensionError> {
+        let addresses = self.resolve().await?;
+        if addresses.is_empty() {
+            return Err(ExtensionError::transport(format!(
+                "{} resolved to no addresses",
+                self.host
+            )));
+        }
+
+        let diff = self.endpoints.replace(addresses);
+        if diff.is_empty() {
+            return Ok(diff);
+        }
+        info!(
+            "Endpoint {} changed: added {:?}, removed {:?}",
+            self.host, diff.added, diff.removed
+        );
+
+        if let Some(pool) = &self.pool {
+            let drained = pool.drain(&diff.removed).await;
+            let warmed = pool.warm(&diff.added).await;
+            debug!(
+                "Dropped {} idle connections and opened {} new ones",
+                drained, warmed
+            );
+        }
+        Ok(diff)
+    }
+
+    /// Refresh every interval until the returned task is aborted.
+    pub fn start(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.refresh().await {
+                    warn!("Keeping current addresses for {}: {}", self.host, e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_replace_and_round_robin() {
+        let set = EndpointSet::new(vec![addr("10.0.0.1:9300"), addr("10.0.0.2:9300")]);
+        let first = set.next().unwrap();
+        assert_ne!(set.next().unwrap(), first);
+        assert_eq!(set.next().unwrap(), first);
+
+        let diff = set.replace(vec![addr("10.0.0.2:9300"), addr("10.0.0.3:9300")]);
+        assert_eq!(diff.added, vec![addr("10.0.0.3:9300")]);
+        assert_eq!(diff.removed, vec![addr("10.0.0.1:9300")]);
+        assert!(set.replace(set.addresses()).is_empty());
+
+        assert_eq!(EndpointSet::default().next(), None);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_resolves_host() {
+        let endpoints = Arc::new(EndpointSet::default());
+        let watcher = EndpointWatcher::new("127.0.0.1", 9300, endpoints.clone());
+        let diff = watcher.refresh().await.unwrap();
+        assert_eq!(diff.added, vec![addr("127.0.0.1:9300")]);
+        assert!(watcher.refresh().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::debug;
+
+use crate::extension::ExtensionError;
+use crate::transport::TransportClient;
+
+/// Counters of a `HedgedTransport`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HedgeStats {
+    pub requests_total: u64,
+    /// Requests that fired at least one backup.
+    pub hedged_total: u64,
+    /// Requests answered by a backup rather than the primary.
+    pub backup_wins_total: u64,
+}
+
+/// Run `attempt(0)` and, each time `delay` passes without a success, start
+/// `attempt(1)`, `attempt(2)` and so on up to `attempts` in total. A failed
+/// attempt starts the next one right away. Returns the first success with
+/// the index of the attempt that produced it, or the last error once every
+/// attempt failed. Attempts still running are aborted.
+pub async fn hedge<F, Fut, T>(
+    delay: Duration,
+    attempts: usize,
+    mut attempt: F,
+) -> Result<(usize, T), ExtensionError>
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = Result<T, ExtensionError>> + Send + 'static,
+    T: Send + 'static,
+{
+    let attempts = attempts.max(1);
+    let mut running = JoinSet::new();
+    let mut started = 0;
+    let mut last_error = None;
+    loop {
+        if running.is_empty() {
+            if started == attempts {
+                return Err(last_error
+                    .unwrap_or_else(|| ExtensionError::unknown("No hedged attempt was made")));
+            }
+            let fut = attempt(started);
+            let index = started;
+            running.spawn(async move { (index, fut.await) });
+            started += 1;
+        }
+
+        let backup_due = tokio::time::sleep(delay);
+        tokio::select! {
+            Some(joined) = running.join_next() => match joined {
+                Ok((index, Ok(value))) => return Ok((index, value)),
+                Ok((index, Err(e))) => {
+                    debug!("Hedged attempt {} failed: {}", index, e);
+                    last_error = Some(e);
+                }
+                Err(e) => last_error = Some(ExtensionError::unknown(format!("Hedged attempt panicked: {}", e))),
+            },
+            _ = backup_due, if started < attempts => {
+                let fut = attempt(started);
+                let index = started;
+                running.spawn(async move { (index, fut.await) });
+                started += 1;
+            }
+        }
+    }
+}
+
+/// Sends each request to the first endpoint and, if it has not answered
+/// within `delay`, also to the next one, returning whichever answers first.
+/// Meant for small latency-sensitive calls such as discovery and health
+/// checks, where an occasional duplicate request is cheaper than waiting
+/// on a slow node.
+pub struct HedgedTransport {
+    endpoints: Vec<Arc<TransportClient>>,
+    delay: Duration,
+    max_backups: usize,
+    requests: AtomicU64,
+    hedged: AtomicU64,
+    backup_wins: AtomicU64,
+}
+
+impl HedgedTransport {
+    pub fn new(endpoints: Vec<Arc<TransportClient>>, delay: Duration) -> Self {
+        HedgedTransport {
+            endpoints,
+            delay,
+            max_backups: 1,
+            requests: AtomicU64::new(0),
+            hedged: AtomicU64::new(0),
+            backup_wins: AtomicU64::new(0),
+        }
+    }
+
+    /// Fire up to `max_backups` backup requests, one per further endpoint.
+    pub fn max_backups(mut self, max_backups: usize) -> Self {
+        self.max_backups = max_backups;
+        self
+    }
+
+    pub async fn send_request(&self, action: &str, data: &[u8]) -> Result<Vec<u8>, ExtensionError> {
+        if self.endpoints.is_empty() {
+            return Err(ExtensionError::configuration(
+                "Hedged transport has no endpoints",
+            ));
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let attempts = self.endpoints.len().min(self.max_backups + 1);
+        let (action, data) = (Arc::<str>::from(action), Arc::<[u8]>::from(data));
+        let mut hedged = false;
+        let result = hedge(self.delay, attempts, |index| {
+            if index > 0 && !hedged {
+                hedged = true;
+                self.hedged.fetch_add(1, Ordering::Relaxed);
+            }
+            let (client, action, data) =
+                (self.endpoints[index].clone(), action.clone(), data.clone());
+            async move { client.send_request(&action, &data).await }
+        })
+        .await;
+
+        let (index, response) = result?;
+        if index > 0 {
+            self.backup_wins.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(response)
+    }
+
+    pub fn stats(&self) -> HedgeStats {
+        HedgeStats {
+            requests_total: self.requests.load(Ordering::Relaxed),
+            hedged_total: self.hedged.load(Ordering::Relaxed),
+            backup_wins_total: self.backup_wins.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::inbound::{read_message, write_response};
+    use crate::transport::ThreadContext;
+    use tokio::net::TcpListener;
+
+    async fn node(reply: &'static [u8], latency: Duration) -> Arc<TransportClient> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let Ok(Some(request)) = read_message(&mut stream).await else {
+                        return;
+                    };
+                    tokio::time::sleep(latency).await;
+                    let _ = write_response(
+                        &mut stream,
+                        &request.header,
+                        &ThreadContext::new(),
+                        reply,
+                        false,
+                    )
+                    .await;
+                });
+            }
+        });
+        Arc::new(TransportClient::new("127.0.0.1", port))
+    }
+
+    #[tokio::test]
+    async fn test_backup_answers_for_slow_primary() {
+        let slow = node(b"slow", Duration::from_secs(5)).await;
+        let fast = node(b"fast", Duration::ZERO).await;
+        let transport = HedgedTransport::new(vec![slow, fast.clone()], Duration::from_millis(20));
+
+        let response = transport
+            .send_request("internal:discovery/extensions", b"ping")
+            .await
+            .unwrap();
+        assert_eq!(response, b"fast");
+        assert_eq!(
+            transport.stats(),
+            HedgeStats {
+                requests_total: 1,
+                hedged_total: 1,
+                backup_wins_total: 1
+            }
+        );
+
+        let primary_only = HedgedTransport::new(vec![fast], Duration::from_millis(20));
+        assert_eq!(
+            primary_only
+                .send_request("internal:discovery/extensions", b"ping")
+                .await
+                .unwrap(),
+            b"fast"
+        );
+        assert_eq!(primary_only.stats().hedged_total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_attempt_starts_next_at_once() {
+        let result = hedge(Duration::from_secs(60), 2, |index| async move {
+            match index {
+                0 => Err(ExtensionError::transport("connection refused")),
+                _ => Ok("backup"),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), (1, "backup"));
+
+        let failed = hedge(Duration::from_millis(1), 2, |_| async {
+            Err::<(), _>(ExtensionError::transport("down"))
+        })
+        .await;
+        assert!(matches!(failed, Err(ExtensionError::TransportError(_))));
+    }
+}
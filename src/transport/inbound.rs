@@ -0,0 +1,381 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::extension::setting::Setting;
+use crate::extension::units::ByteSizeValue;
+use crate::extension::ExtensionError;
+use crate::interface::{read_string, read_string_array, Deserialize, Serialize, MAX_PREALLOCATION};
+use crate::transport::conformance::validate_message;
+use crate::transport::spill::ResponseBody;
+use crate::transport::{
+    transport_status, ThreadContext, TransportTcpHeader, HEADER_SIZE, PING_MESSAGE_LENGTH,
+};
+
+/// A fully read transport message: fixed header, variable header and content.
+#[derive(Debug)]
+pub struct InboundMessage {
+    pub header: TransportTcpHeader,
+    pub thread_context: ThreadContext,
+    pub features: Vec<String>,
+    pub action: Option<String>,
+    pub content: Vec<u8>,
+}
+
+impl InboundMessage {
+    /// OpenSearch marks responses with the REQRES bit; requests leave it clear
+    pub fn is_request(&self) -> bool {
+        self.header.status & transport_status::STATUS_REQRES == 0
+    }
+
+    pub fn is_handshake(&self) -> bool {
+        self.header.status & transport_status::STATUS_HANDSHAKE != 0
+    }
+
+    /// Size of the message on the wire, including its header.
+    pub fn wire_size(&self) -> usize {
+        self.header.message_length as usize + 6
+    }
+
+    fn parse_variable_header(
+        header: &TransportTcpHeader,
+        bytes: &[u8],
+    ) -> Result<(ThreadContext, Vec<String>, Option<String>), std::io::Error> {
+        let mut buf = bytes;
+        let thread_context = ThreadContext::deserialize(&mut buf)?;
+
+        if header.status & transport_status::STATUS_REQRES != 0 {
+            return Ok((thread_context, Vec::new(), None));
+        }
+
+        let features = read_string_array(&mut buf)?;
+        let action = read_string(&mut buf)?;
+        Ok((thread_context, features, Some(action)))
+    }
+}
+
+/// How strictly inbound messages are checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProtocolMode {
+    /// Parse what is needed and tolerate the rest.
+    #[default]
+    Lenient,
+    /// Validate every field and reject messages with a full violation report.
+    Strict,
+}
+
+/// Largest message accepted by default, header included.
+pub const DEFAULT_MAX_MESSAGE_SIZE: ByteSizeValue = ByteSizeValue::mb(100);
+
+/// Largest inbound transport message, header included. Larger messages
+/// are rejected from their header, before anything is allocated for them.
+pub fn max_message_size() -> Setting<ByteSizeValue> {
+    Setting::byte_size("transport.max_message_size").default(DEFAULT_MAX_MESSAGE_SIZE)
+}
+
+/// Read the next message from `reader`, skipping keep-alive pings.
+/// Returns `Ok(None)` when the peer closed the connection between messages.
+pub async fn read_message<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<InboundMessage>, ExtensionError> {
+    read_message_with(
+        reader,
+        ProtocolMode::Lenient,
+        DEFAULT_MAX_MESSAGE_SIZE.as_bytes() as usize,
+    )
+    .await
+}
+
+/// Like `read_message`, checking each message according to `mode` and
+/// rejecting messages over `max_size` bytes.
+pub async fn read_message_with<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    mode: ProtocolMode,
+    max_size: usize,
+) -> Result<Option<InboundMessage>, ExtensionError> {
+    loop {
+        let mut prefix = [0u8; 6];
+        match reader.read_exact(&mut prefix).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => {
+                return Err(ExtensionError::transport(format!(
+                    "Failed to read header: {}",
+                    e
+                )))
+            }
+        }
+
+        if u32::from_be_bytes([prefix[2], prefix[3], prefix[4], prefix[5]]) == PING_MESSAGE_LENGTH {
+            continue;
+        }
+
+        let mut header_bytes = [0u8; HEADER_SIZE];
+        header_bytes[..6].copy_from_slice(&prefix);
+        reader
+            .read_exact(&mut header_bytes[6..])
+            .await
+            .map_err(|e| ExtensionError::transport(format!("Failed to read header: {}", e)))?;
+
+        let header = TransportTcpHeader::parse(&header_bytes)
+            .map_err(|e| ExtensionError::protocol(format!("Invalid transport header: {}", e)))?;
+
+        let size = header.message_length as usize + 6;
+        if size > max_size {
+            return Err(ExtensionError::protocol(format!(
+                "Message of {} bytes exceeds the maximum of {} bytes",
+                size, max_size
+            )));
+        }
+        if size < HEADER_SIZE + header.variable_header_size as usize {
+            return Err(ExtensionError::protocol(format!(
+                "Variable header of {} bytes does not fit in a message of {} bytes",
+                header.variable_header_size, size
+            )));
+        }
+
+        let variable_header = read_part(reader, header.variable_header_size as usize)
+            .await
+            .map_err(|e| {
+                ExtensionError::transport(format!("Failed to read variable header: {}", e))
+            })?;
+        let content = read_part(reader, header.content_size())
+            .await
+            .map_err(|e| {
+                ExtensionError::transport(format!("Failed to read message content: {}", e))
+            })?;
+
+        if mode == ProtocolMode::Strict {
+            let mut message = header_bytes.to_vec();
+            message.extend_from_slice(&variable_header);
+            message.extend_from_slice(&content);
+            let report = validate_message(&message);
+            if !report.is_conformant() {
+                return Err(ExtensionError::protocol(format!(
+                    "Request {} violates the transport protocol: {}",
+                    header.request_id, report
+                )));
+            }
+        }
+
+        let (thread_context, features, action) =
+            InboundMessage::parse_variable_header(&header, &variable_header)
+                .map_err(|e| ExtensionError::protocol(format!("Invalid variable header: {}", e)))?;
+
+        return Ok(Some(InboundMessage {
+            header,
+            thread_context,
+            features,
+            action,
+            content,
+        }));
+    }
+}
+
+/// Read exactly `len` bytes, allocating as they arrive rather than up front.
+async fn read_part<R: AsyncRead + Unpin>(reader: &mut R, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    reader.take(len as u64).read_to_end(&mut bytes).await?;
+    if bytes.len() != len {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("{} of {} bytes received", bytes.len(), len),
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Header and variable header of a response to `request` carrying
+/// `content_len` bytes of content.
+fn response_prefix(
+    request: &TransportTcpHeader,
+    thread_context: &ThreadContext,
+    content_len: usize,
+    is_error: bool,
+) -> Result<Vec<u8>, ExtensionError> {
+    let mut variable_header = Vec::new();
+    thread_context
+        .serialize(&mut variable_header)
+        .map_err(|e| {
+            ExtensionError::serialization(format!("Failed to serialize thread context: {}", e))
+        })?;
+
+    let mut status = transport_status::STATUS_REQRES;
+    if is_error {
+        status |= transport_status::STATUS_ERROR;
+    }
+    if request.status & transport_status::STATUS_HANDSHAKE != 0 {
+        status |= transport_status::STATUS_HANDSHAKE;
+    }
+
+    let header = TransportTcpHeader::new(
+        request.request_id,
+        status,
+        request.version,
+        content_len as u32,
+        variable_header.len() as u32,
+    );
+
+    let mut prefix = header.to_bytes();
+    prefix.extend_from_slice(&variable_header);
+    Ok(prefix)
+}
+
+/// Write a response to `request`, echoing its request ID and version.
+/// Returns the number of bytes written.
+pub async fn write_response<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    request: &TransportTcpHeader,
+    thread_context: &ThreadContext,
+    content: &[u8],
+    is_error: bool,
+) -> Result<usize, ExtensionError> {
+    let mut message = response_prefix(request, thread_context, content.len(), is_error)?;
+    message.extend_from_slice(content);
+
+    writer
+        .write_all(&message)
+        .await
+        .map_err(|e| ExtensionError::transport(format!("Failed to write response: {}", e)))?;
+    writer
+        .flush()
+        .await
+        .map_err(|e| ExtensionError::transport(format!("Failed to flush response: {}", e)))?;
+    Ok(message.len())
+}
+
+/// Like `write_response`, streaming spilled content from its file. The
+/// file is removed once written, or on failure.
+pub async fn write_response_body<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    request: &TransportTcpHeader,
+    thread_context: &ThreadContext,
+    body: ResponseBody,
+    is_error: bool,
+) -> Result<usize, ExtensionError> {
+    let file = match body {
+        ResponseBody::Memory(content) => {
+            return write_response(writer, request, thread_context, &content, is_error).await
+        }
+        ResponseBody::Spilled(file) => file,
+    };
+
+    let prefix = response_prefix(request, thread_context, file.len(), is_error)?;
+    writer
+        .write_all(&prefix)
+        .await
+        .map_err(|e| ExtensionError::transport(format!("Failed to write response: {}", e)))?;
+    let mut content = tokio::fs::File::open(file.path()).await.map_err(|e| {
+        ExtensionError::transport(format!("Failed to open {}: {}", file.path().display(), e))
+    })?;
+    let copied = tokio::io::copy(&mut content, writer)
+        .await
+        .map_err(|e| ExtensionError::transport(format!("Failed to write response: {}", e)))?;
+    writer
+        .flush()
+        .await
+        .map_err(|e| ExtensionError::transport(format!("Failed to flush response: {}", e)))?;
+
+    if copied as usize != file.len() {
+        return Err(ExtensionError::transport(format!(
+            "Spilled response {} changed size: expected {} bytes, sent {}",
+            file.path().display(),
+            file.len(),
+            copied
+        )));
+    }
+    Ok(prefix.len() + file.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::{write_string, write_string_array};
+
+    fn request_bytes(request_id: u64, action: &str, content: &[u8]) -> Vec<u8> {
+        let mut variable_header = Vec::new();
+        ThreadContext::new()
+            .serialize(&mut variable_header)
+            .unwrap();
+        write_string_array(&mut variable_header, &[]).unwrap();
+        write_string(&mut variable_header, action).unwrap();
+
+        let header = TransportTcpHeader::new(
+            request_id,
+            0,
+            1,
+            content.len() as u32,
+            variable_header.len() as u32,
+        );
+        let mut bytes = header.to_bytes();
+        bytes.extend_from_slice(&variable_header);
+        bytes.extend_from_slice(content);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_read_request_skips_pings() {
+        let mut bytes = b"ES".to_vec();
+        bytes.extend_from_slice(&PING_MESSAGE_LENGTH.to_be_bytes());
+        bytes.extend(request_bytes(9, "internal:test/action", b"payload"));
+
+        let mut reader = bytes.as_slice();
+        let message = read_message(&mut reader).await.unwrap().unwrap();
+
+        assert!(message.is_request());
+        assert_eq!(message.header.request_id, 9);
+        assert_eq!(message.action.as_deref(), Some("internal:test/action"));
+        assert_eq!(message.content, b"payload");
+
+        assert!(read_message(&mut reader).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_reports_violations() {
+        let mut bytes = request_bytes(3, "internal:test/action", b"payload");
+        bytes[14] |= 0x20;
+
+        assert!(read_message(&mut bytes.as_slice()).await.unwrap().is_some());
+        let max_size = DEFAULT_MAX_MESSAGE_SIZE.as_bytes() as usize;
+        let error = read_message_with(&mut bytes.as_slice(), ProtocolMode::Strict, max_size)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("header.status at offset 14 (ReservedBits)"));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_messages_rejected_from_header() {
+        let bytes = request_bytes(4, "internal:test/action", &[0u8; 64]);
+        let error = read_message_with(&mut bytes.as_slice(), ProtocolMode::Lenient, 64)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("exceeds the maximum of 64 bytes"));
+
+        let huge = TransportTcpHeader::new(4, 0, 1, 0x7fff_0000, 0x7fff_0000).to_bytes();
+        let error = read_message(&mut huge.as_slice()).await.unwrap_err();
+        assert!(error.to_string().contains("exceeds the maximum"));
+
+        let mut truncated = TransportTcpHeader::new(4, 0, 1, 1 << 20, 16).to_bytes();
+        truncated.extend_from_slice(&[0u8; 32]);
+        let error = read_message(&mut truncated.as_slice()).await.unwrap_err();
+        assert!(error.to_string().contains("Failed to read message content"));
+    }
+
+    #[tokio::test]
+    async fn test_write_response_is_readable() {
+        let request = TransportTcpHeader::new(5, transport_status::STATUS_HANDSHAKE, 3, 0, 0);
+        let mut out = Vec::new();
+        write_response(&mut out, &request, &ThreadContext::new(), b"ok", false)
+            .await
+            .unwrap();
+
+        let message = read_message(&mut out.as_slice()).await.unwrap().unwrap();
+        assert!(!message.is_request());
+        assert!(message.is_handshake());
+        assert_eq!(message.header.request_id, 5);
+        assert_eq!(message.action, None);
+        assert_eq!(message.content, b"ok");
+    }
+}
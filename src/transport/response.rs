@@ -0,0 +1,140 @@
+use std::io::{self, Read, Write};
+
+use crate::extension::ExtensionError;
+use crate::interface::{read_bool, write_bool, write_string, write_vint, Deserialize, Serialize};
+use crate::rest::error_mapper::DefaultErrorMapper;
+use crate::rest::RestStatus;
+
+/// Id OpenSearch registers `NotSerializableExceptionWrapper` under.
+const NOT_SERIALIZABLE_EXCEPTION_WRAPPER_ID: u32 = 62;
+
+/// Boolean acknowledgement that ends most extension protocol exchanges
+/// (registering REST actions, settings, transport actions, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcknowledgedResponse {
+    pub acknowledged: bool,
+}
+
+impl AcknowledgedResponse {
+    pub fn new(acknowledged: bool) -> Self {
+        AcknowledgedResponse { acknowledged }
+    }
+
+    pub fn acknowledged() -> Self {
+        Self::new(true)
+    }
+
+    pub fn is_acknowledged(&self) -> bool {
+        self.acknowledged
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(1);
+        self.serialize(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl Serialize for AcknowledgedResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        write_bool(buf, self.acknowledged)
+    }
+}
+
+impl Deserialize for AcknowledgedResponse {
+    type Output = AcknowledgedResponse;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        Ok(AcknowledgedResponse::new(read_bool(buf)?))
+    }
+}
+
+/// Body of an error response, written the way Java's
+/// `StreamOutput.writeException` writes an exception class it has no id for:
+/// as a `NotSerializableExceptionWrapper` keeping the exception name,
+/// message and status, so `StreamInput.readException` can rebuild it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionResponse {
+    /// OpenSearch-style exception name, e.g. `illegal_argument_exception`.
+    pub name: String,
+    pub message: String,
+    pub status: RestStatus,
+}
+
+impl ExceptionResponse {
+    pub fn from_error(error: &ExtensionError) -> Self {
+        let (status, name) = DefaultErrorMapper::status_and_type(error);
+        ExceptionResponse {
+            name: name.to_string(),
+            message: error.to_string(),
+            status,
+        }
+    }
+
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.serialize(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl Serialize for ExceptionResponse {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        // Exception present, as a registered OpenSearchException subclass.
+        let mut written = write_bool(buf, true)?;
+        written += write_vint(buf, 0)?;
+        written += write_vint(buf, NOT_SERIALIZABLE_EXCEPTION_WRAPPER_ID)?;
+        // OpenSearchException.writeTo: the message the wrapper builds from
+        // the name, no cause, no stack trace or suppressed exceptions, no
+        // headers and no metadata.
+        written += write_bool(buf, true)?;
+        written += write_string(buf, &format!("{}: {}", self.name, self.message))?;
+        written += write_bool(buf, false)?;
+        written += write_vint(buf, 0)?;
+        written += write_vint(buf, 0)?;
+        written += write_vint(buf, 0)?;
+        written += write_vint(buf, 0)?;
+        // NotSerializableExceptionWrapper.writeTo
+        written += write_string(buf, &self.name)?;
+        written += write_string(buf, self.status.name())?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acknowledged_round_trip() {
+        for ack in [true, false] {
+            let bytes = AcknowledgedResponse::new(ack).to_bytes().unwrap();
+            assert_eq!(bytes, vec![ack as u8]);
+
+            let decoded = AcknowledgedResponse::deserialize(&mut bytes.as_slice()).unwrap();
+            assert_eq!(decoded.is_acknowledged(), ack);
+        }
+    }
+
+    #[test]
+    fn test_exception_response_bytes() {
+        let error = ExtensionError::invalid_request("bad");
+        let bytes = ExceptionResponse::from_error(&error).to_bytes().unwrap();
+
+        let message = format!("illegal_argument_exception: {}", error);
+        let mut expected = vec![1, 0, 62, 1, message.len() as u8];
+        expected.extend_from_slice(message.as_bytes());
+        expected.extend_from_slice(&[0, 0, 0, 0, 0]);
+        expected.push(26);
+        expected.extend_from_slice(b"illegal_argument_exception");
+        expected.push(11);
+        expected.extend_from_slice(b"BAD_REQUEST");
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_invalid_ack_byte() {
+        let bytes = [7u8];
+        assert!(AcknowledgedResponse::deserialize(&mut bytes.as_slice()).is_err());
+    }
+}
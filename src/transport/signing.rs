@@ -0,0 +1,288 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::extension::context::Settings;
+use crate::extension::setting::Setting;
+use crate::extension::units::TimeValue;
+use crate::extension::ExtensionError;
+use crate::transport::ThreadContext;
+
+/// Request or response header carrying the hex HMAC-SHA256 of a message.
+pub const SIGNATURE_HEADER: &str = "X-Extension-Signature";
+
+/// Header carrying when a message was signed, in milliseconds since the
+/// epoch. It is covered by the signature, so a captured message can only
+/// be replayed within the skew window.
+pub const SIGNATURE_TIMESTAMP_HEADER: &str = "X-Extension-Signature-Timestamp";
+
+/// Secret shared with OpenSearch for signing messages, for deployments
+/// without mTLS. Signing is off while it is unset. The secret is read from
+/// the file named by `extension.signing.secret_file`.
+pub fn signing_secret() -> Setting<String> {
+    Setting::string("extension.signing.secret")
+        .node_scope()
+        .secure()
+}
+
+/// How far a signature timestamp may be from the local clock.
+pub fn signing_max_skew() -> Setting<TimeValue> {
+    Setting::time("extension.signing.max_skew")
+        .default(TimeValue::minutes(5))
+        .min(TimeValue::seconds(1))
+        .node_scope()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Signs and verifies messages with HMAC-SHA256 over the request id, the
+/// signing time, the action name and the message content.
+#[derive(Clone)]
+pub struct MessageSigner {
+    secret: Vec<u8>,
+    max_skew: Duration,
+}
+
+impl MessageSigner {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Result<Self, ExtensionError> {
+        let secret = secret.into();
+        if secret.len() < 16 {
+            return Err(ExtensionError::configuration(
+                "Signing secret must be at least 16 bytes",
+            ));
+        }
+        Ok(MessageSigner {
+            secret,
+            max_skew: Duration::from_secs(5 * 60),
+        })
+    }
+
+    /// Reject messages signed more than `max_skew` before or after now.
+    pub fn with_max_skew(mut self, max_skew: Duration) -> Self {
+        self.max_skew = max_skew;
+        self
+    }
+
+    /// A signer for the `signing_secret` setting, or `None` when it is unset.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, ExtensionError> {
+        let max_skew = signing_max_skew().get(settings)?.as_duration();
+        Ok(signing_secret()
+            .get_opt(settings)?
+            .map(Self::new)
+            .transpose()?
+            .map(|signer| signer.with_max_skew(max_skew)))
+    }
+
+    fn mac(&self, request_id: u64, timestamp: u64, action: &str, content: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(format!("{}\n{}\n{}\n", request_id, timestamp, action).as_bytes());
+        mac.update(content);
+        mac
+    }
+
+    pub fn sign(&self, request_id: u64, timestamp: u64, action: &str, content: &[u8]) -> String {
+        self.mac(request_id, timestamp, action, content)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Sign request `request_id` now, in the request headers of `thread_context`.
+    pub fn sign_request(
+        &self,
+        thread_context: &mut ThreadContext,
+        request_id: u64,
+        action: &str,
+        content: &[u8],
+    ) {
+        let timestamp = now_millis();
+        thread_context.put_request_header(SIGNATURE_TIMESTAMP_HEADER, timestamp.to_string());
+        thread_context.put_request_header(
+            SIGNATURE_HEADER,
+            self.sign(request_id, timestamp, action, content),
+        );
+    }
+
+    /// Sign the response to `request_id` now, in the response headers of `thread_context`.
+    pub fn sign_response(
+        &self,
+        thread_context: &mut ThreadContext,
+        request_id: u64,
+        action: &str,
+        content: &[u8],
+    ) {
+        let timestamp = now_millis();
+        thread_context.add_response_header(SIGNATURE_TIMESTAMP_HEADER, timestamp.to_string());
+        thread_context.add_response_header(
+            SIGNATURE_HEADER,
+            self.sign(request_id, timestamp, action, content),
+        );
+    }
+
+    /// Check the signature headers of request `request_id`.
+    pub fn verify_request(
+        &self,
+        thread_context: &ThreadContext,
+        request_id: u64,
+        action: &str,
+        content: &[u8],
+    ) -> Result<(), ExtensionError> {
+        self.verify(
+            request_id,
+            action,
+            content,
+            thread_context.request_header(SIGNATURE_HEADER),
+            thread_context.request_header(SIGNATURE_TIMESTAMP_HEADER),
+        )
+    }
+
+    /// Check `signature` in constant time, and that `timestamp` is within
+    /// the skew window.
+    pub fn verify(
+        &self,
+        request_id: u64,
+        action: &str,
+        content: &[u8],
+        signature: Option<&str>,
+        timestamp: Option<&str>,
+    ) -> Result<(), ExtensionError> {
+        let signature = signature
+            .ok_or_else(|| ExtensionError::protocol(format!("Request {} is not signed", action)))?;
+        let bytes = decode_hex(signature).ok_or_else(|| {
+            ExtensionError::protocol(format!("Malformed signature on request {}", action))
+        })?;
+        let timestamp: u64 = timestamp
+            .and_then(|timestamp| timestamp.parse().ok())
+            .ok_or_else(|| {
+                ExtensionError::protocol(format!(
+                    "Missing or malformed signature timestamp on request {}",
+                    action
+                ))
+            })?;
+        let skew = Duration::from_millis(now_millis().abs_diff(timestamp));
+        if skew > self.max_skew {
+            return Err(ExtensionError::protocol(format!(
+                "Signature on request {} is {:?} off the local clock, more than the allowed {:?}",
+                action, skew, self.max_skew
+            )));
+        }
+        self.mac(request_id, timestamp, action, content)
+            .verify_slice(&bytes)
+            .map_err(|_| {
+                ExtensionError::protocol(format!("Invalid signature on request {}", action))
+            })
+    }
+}
+
+impl fmt::Debug for MessageSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageSigner")
+            .field("secret", &"<redacted>")
+            .field("max_skew", &self.max_skew)
+            .finish()
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        const ACTION: &str = "internal:extensions/restexecuteonextensiontaction";
+        let signer = MessageSigner::new("0123456789abcdef").unwrap();
+        let mut context = ThreadContext::new();
+        signer.sign_request(&mut context, 7, ACTION, b"body");
+        let signature = context.request_header(SIGNATURE_HEADER);
+        let timestamp = context.request_header(SIGNATURE_TIMESTAMP_HEADER);
+        assert_eq!(signature.unwrap().len(), 64);
+
+        assert!(signer.verify_request(&context, 7, ACTION, b"body").is_ok());
+        assert!(signer
+            .verify_request(&context, 7, ACTION, b"tampered")
+            .is_err());
+        assert!(signer.verify_request(&context, 8, ACTION, b"body").is_err());
+        assert!(signer
+            .verify_request(&context, 7, "other:action", b"body")
+            .is_err());
+        assert!(signer
+            .verify(7, ACTION, b"body", Some("zz"), timestamp)
+            .is_err());
+        assert!(signer.verify(7, ACTION, b"body", None, timestamp).is_err());
+        assert!(signer.verify(7, ACTION, b"body", signature, None).is_err());
+
+        let stale = now_millis() - 10 * 60 * 1000;
+        let replayed = signer.sign(7, stale, ACTION, b"body");
+        let error = signer
+            .verify(
+                7,
+                ACTION,
+                b"body",
+                Some(&replayed),
+                Some(&stale.to_string()),
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("off the local clock"));
+        let lenient = signer.clone().with_max_skew(Duration::from_secs(3600));
+        assert!(lenient
+            .verify(
+                7,
+                ACTION,
+                b"body",
+                Some(&replayed),
+                Some(&stale.to_string())
+            )
+            .is_ok());
+
+        assert!(MessageSigner::new("short").is_err());
+    }
+
+    #[test]
+    fn test_secret_read_from_file() {
+        let dir = std::env::temp_dir().join(format!("message-signer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("secret");
+        std::fs::write(&path, "0123456789abcdef\n").unwrap();
+
+        let settings = Settings::new();
+        assert!(MessageSigner::from_settings(&settings).unwrap().is_none());
+        settings
+            .set("extension.signing.secret", "0123456789abcdef")
+            .unwrap();
+        assert!(MessageSigner::from_settings(&settings).is_err());
+
+        let settings = Settings::new();
+        settings
+            .set("extension.signing.secret_file", path.to_str().unwrap())
+            .unwrap();
+        settings.set("extension.signing.max_skew", "30s").unwrap();
+        let configured = MessageSigner::from_settings(&settings).unwrap().unwrap();
+        let signer = MessageSigner::new("0123456789abcdef").unwrap();
+        assert_eq!(
+            configured.sign(1, 2, "a", b"b"),
+            signer.sign(1, 2, "a", b"b")
+        );
+        assert_eq!(configured.max_skew, Duration::from_secs(30));
+        assert!(settings.is_secure("extension.signing.secret").unwrap());
+        assert!(!format!("{:?}", configured).contains("0123"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+use crate::extension::ExtensionError;
+
+/// Response content, either held in memory or spooled to a temporary file.
+#[derive(Debug)]
+pub enum ResponseBody {
+    Memory(Vec<u8>),
+    Spilled(SpilledFile),
+}
+
+impl ResponseBody {
+    pub fn len(&self) -> usize {
+        match self {
+            ResponseBody::Memory(content) => content.len(),
+            ResponseBody::Spilled(file) => file.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A temporary file holding response content. The file is removed when
+/// this is dropped, whether or not the response was sent.
+#[derive(Debug)]
+pub struct SpilledFile {
+    path: PathBuf,
+    len: usize,
+}
+
+impl SpilledFile {
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for SpilledFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!(
+                "Failed to remove spilled response {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SpillStats {
+    pub responses: u64,
+    pub spilled: u64,
+    pub spilled_bytes: u64,
+    pub failures: u64,
+}
+
+/// Moves response content above a size threshold out of memory and into
+/// temporary files, which the framing layer then streams to the peer.
+#[derive(Debug)]
+pub struct ResponseSpooler {
+    threshold: usize,
+    dir: PathBuf,
+    next_file: AtomicU64,
+    responses: AtomicU64,
+    spilled: AtomicU64,
+    spilled_bytes: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl ResponseSpooler {
+    /// Spill responses larger than `threshold` bytes to the system temp directory.
+    pub fn new(threshold: usize) -> Self {
+        ResponseSpooler {
+            threshold,
+            dir: std::env::temp_dir(),
+            next_file: AtomicU64::new(0),
+            responses: AtomicU64::new(0),
+            spilled: AtomicU64::new(0),
+            spilled_bytes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Keep `content` in memory if it is under the threshold, otherwise write
+    /// it to a temporary file and release the buffer. If the file cannot be
+    /// written the content stays in memory.
+    pub async fn spool(&self, content: Vec<u8>) -> ResponseBody {
+        self.responses.fetch_add(1, Ordering::Relaxed);
+        if content.len() <= self.threshold {
+            return ResponseBody::Memory(content);
+        }
+
+        match self.write_file(&content).await {
+            Ok(file) => {
+                self.spilled.fetch_add(1, Ordering::Relaxed);
+                self.spilled_bytes
+                    .fetch_add(content.len() as u64, Ordering::Relaxed);
+                ResponseBody::Spilled(file)
+            }
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                warn!("Keeping {} byte response in memory: {}", content.len(), e);
+                ResponseBody::Memory(content)
+            }
+        }
+    }
+
+    async fn write_file(&self, content: &[u8]) -> Result<SpilledFile, ExtensionError> {
+        let name = format!(
+            "opensearch-sdk-response-{}-{}",
+            std::process::id(),
+            self.next_file.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = self.dir.join(name);
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+            .map_err(|e| {
+                ExtensionError::transport(format!("Failed to create {}: {}", path.display(), e))
+            })?;
+
+        // From here on the file is removed on drop, including on error.
+        let spilled = SpilledFile {
+            path,
+            len: content.len(),
+        };
+        file.write_all(content).await.map_err(|e| {
+            ExtensionError::transport(format!("Failed to write {}: {}", spilled.path.display(), e))
+        })?;
+        file.flush().await.map_err(|e| {
+            ExtensionError::transport(format!("Failed to write {}: {}", spilled.path.display(), e))
+        })?;
+        Ok(spilled)
+    }
+
+    pub fn stats(&self) -> SpillStats {
+        SpillStats {
+            responses: self.responses.load(Ordering::Relaxed),
+            spilled: self.spilled.load(Ordering::Relaxed),
+            spilled_bytes: self.spilled_bytes.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::inbound::{read_message, write_response_body};
+    use crate::transport::{transport_status, ThreadContext, TransportTcpHeader};
+
+    #[tokio::test]
+    async fn test_spill_and_stream() {
+        let spooler = ResponseSpooler::new(16);
+        assert!(matches!(
+            spooler.spool(vec![1; 16]).await,
+            ResponseBody::Memory(_)
+        ));
+
+        let content: Vec<u8> = (0..100).collect();
+        let body = spooler.spool(content.clone()).await;
+        let path = match &body {
+            ResponseBody::Spilled(file) => file.path().to_path_buf(),
+            other => panic!("expected a spilled body, got {:?}", other),
+        };
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+        assert_eq!(
+            spooler.stats(),
+            SpillStats {
+                responses: 2,
+                spilled: 1,
+                spilled_bytes: 100,
+                failures: 0
+            }
+        );
+
+        let request = TransportTcpHeader::new(7, transport_status::STATUS_HANDSHAKE, 3, 0, 0);
+        let mut out = Vec::new();
+        let written = write_response_body(&mut out, &request, &ThreadContext::new(), body, false)
+            .await
+            .unwrap();
+        assert_eq!(written, out.len());
+        assert!(!path.exists());
+
+        let message = read_message(&mut out.as_slice()).await.unwrap().unwrap();
+        assert_eq!(message.content, content);
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::interface::{
+    read_string, read_string_array, read_vint, write_string, write_string_array, write_vint,
+    Deserialize, Serialize,
+};
+
+/// Headers carried in the variable header of every transport message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThreadContext {
+    pub request_headers: HashMap<String, String>,
+    pub response_headers: HashMap<String, Vec<String>>,
+}
+
+impl ThreadContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request_header(&self, name: &str) -> Option<&str> {
+        self.request_headers.get(name).map(String::as_str)
+    }
+
+    pub fn put_request_header(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.request_headers.insert(name.into(), value.into());
+    }
+
+    pub fn add_response_header(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.response_headers
+            .entry(name.into())
+            .or_default()
+            .push(value.into());
+    }
+}
+
+impl Serialize for ThreadContext {
+    fn serialize(&self, buf: &mut impl Write) -> io::Result<usize> {
+        let mut written = write_vint(buf, self.request_headers.len() as u32)?;
+        for (name, value) in &self.request_headers {
+            written += write_string(buf, name)?;
+            written += write_string(buf, value)?;
+        }
+
+        written += write_vint(buf, self.response_headers.len() as u32)?;
+        for (name, values) in &self.response_headers {
+            written += write_string(buf, name)?;
+            written += write_string_array(buf, values)?;
+        }
+        Ok(written)
+    }
+}
+
+impl Deserialize for ThreadContext {
+    type Output = ThreadContext;
+
+    fn deserialize(buf: &mut impl Read) -> io::Result<Self::Output> {
+        let mut context = ThreadContext::new();
+
+        for _ in 0..read_vint(buf)? {
+            let name = read_string(buf)?;
+            context.request_headers.insert(name, read_string(buf)?);
+        }
+
+        for _ in 0..read_vint(buf)? {
+            let name = read_string(buf)?;
+            context
+                .response_headers
+                .insert(name, read_string_array(buf)?);
+        }
+
+        Ok(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_context_round_trip() {
+        let mut context = ThreadContext::new();
+        context.put_request_header("X-Opaque-Id", "abc");
+        context.add_response_header("Warning", "299 deprecated");
+
+        let mut buf = Vec::new();
+        let written = context.serialize(&mut buf).unwrap();
+        assert_eq!(written, buf.len());
+
+        let decoded = ThreadContext::deserialize(&mut buf.as_slice()).unwrap();
+        assert_eq!(decoded, context);
+        assert_eq!(decoded.request_header("X-Opaque-Id"), Some("abc"));
+    }
+}
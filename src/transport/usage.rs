@@ -0,0 +1,252 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::info;
+
+tokio::task_local! {
+    static SCOPE: UsageScope;
+}
+
+/// Index and tenant that calls made inside `UsageScope::run` are attributed to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UsageScope {
+    pub index: Option<String>,
+    pub tenant: Option<String>,
+}
+
+impl UsageScope {
+    pub fn index(mut self, index: impl Into<String>) -> Self {
+        self.index = Some(index.into());
+        self
+    }
+
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Run `future`, attributing the cluster calls it makes to this scope.
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        SCOPE.scope(self, future).await
+    }
+
+    pub fn current() -> UsageScope {
+        SCOPE.try_with(Clone::clone).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageStats {
+    pub calls: u64,
+    pub failures: u64,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    pub took_millis: u64,
+    pub max_took_millis: u64,
+}
+
+impl UsageStats {
+    fn record(
+        &mut self,
+        request_bytes: usize,
+        response_bytes: usize,
+        took: Duration,
+        success: bool,
+    ) {
+        let took = took.as_millis() as u64;
+        self.calls += 1;
+        self.failures += u64::from(!success);
+        self.request_bytes += request_bytes as u64;
+        self.response_bytes += response_bytes as u64;
+        self.took_millis += took;
+        self.max_took_millis = self.max_took_millis.max(took);
+    }
+
+    fn merge(&mut self, other: &UsageStats) {
+        self.calls += other.calls;
+        self.failures += other.failures;
+        self.request_bytes += other.request_bytes;
+        self.response_bytes += other.response_bytes;
+        self.took_millis += other.took_millis;
+        self.max_took_millis = self.max_took_millis.max(other.max_took_millis);
+    }
+}
+
+/// Usage aggregated per action, index and tenant. Calls made outside a
+/// scope are grouped under `_none`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageReport {
+    pub since: SystemTime,
+    pub total: UsageStats,
+    pub by_action: BTreeMap<String, UsageStats>,
+    pub by_index: BTreeMap<String, UsageStats>,
+    pub by_tenant: BTreeMap<String, UsageStats>,
+}
+
+const UNSCOPED: &str = "_none";
+
+/// Records every call the extension makes to the cluster.
+#[derive(Debug)]
+pub struct UsageTracker {
+    since: Mutex<SystemTime>,
+    usage: Mutex<BTreeMap<(String, UsageScope), UsageStats>>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        UsageTracker {
+            since: Mutex::new(SystemTime::now()),
+            usage: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record a call to `action` in the current `UsageScope`.
+    pub fn record(
+        &self,
+        action: &str,
+        request_bytes: usize,
+        response_bytes: usize,
+        took: Duration,
+        success: bool,
+    ) {
+        self.usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((action.to_string(), UsageScope::current()))
+            .or_default()
+            .record(request_bytes, response_bytes, took, success);
+    }
+
+    pub fn report(&self) -> UsageReport {
+        let mut report = UsageReport {
+            since: *self.since.lock().unwrap_or_else(PoisonError::into_inner),
+            total: UsageStats::default(),
+            by_action: BTreeMap::new(),
+            by_index: BTreeMap::new(),
+            by_tenant: BTreeMap::new(),
+        };
+        for ((action, scope), stats) in self
+            .usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            let index = scope.index.clone().unwrap_or_else(|| UNSCOPED.to_string());
+            let tenant = scope.tenant.clone().unwrap_or_else(|| UNSCOPED.to_string());
+
+            report.total.merge(stats);
+            report
+                .by_action
+                .entry(action.clone())
+                .or_default()
+                .merge(stats);
+            report.by_index.entry(index).or_default().merge(stats);
+            report.by_tenant.entry(tenant).or_default().merge(stats);
+        }
+        report
+    }
+
+    /// Return the report so far and start counting from zero.
+    pub fn reset(&self) -> UsageReport {
+        let report = self.report();
+        self.usage
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        *self.since.lock().unwrap_or_else(PoisonError::into_inner) = SystemTime::now();
+        report
+    }
+
+    /// Log a summary every `interval`, resetting the counters each time,
+    /// until the returned task is aborted.
+    pub fn start_summary_logging(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let report = self.reset();
+                if report.total.calls == 0 {
+                    continue;
+                }
+                info!(
+                    "Cluster usage over the last {:?}: {} calls ({} failed), {} bytes sent, {} bytes received, {}ms total",
+                    interval,
+                    report.total.calls,
+                    report.total.failures,
+                    report.total.request_bytes,
+                    report.total.response_bytes,
+                    report.total.took_millis
+                );
+                for (action, stats) in &report.by_action {
+                    info!(
+                        "  {}: {} calls, {}ms max",
+                        action, stats.calls, stats.max_took_millis
+                    );
+                }
+            }
+        })
+    }
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_usage_aggregation() {
+        let tracker = UsageTracker::new();
+        tracker.record(
+            "indices:data/write/index",
+            100,
+            20,
+            Duration::from_millis(5),
+            true,
+        );
+
+        let scope = UsageScope::default().index("logs").tenant("acme");
+        scope
+            .run(async {
+                tracker.record(
+                    "indices:data/read/search",
+                    50,
+                    400,
+                    Duration::from_millis(12),
+                    true,
+                );
+                tracker.record(
+                    "indices:data/read/search",
+                    50,
+                    0,
+                    Duration::from_millis(30),
+                    false,
+                );
+            })
+            .await;
+
+        let report = tracker.report();
+        assert_eq!(report.total.calls, 3);
+        assert_eq!(report.total.request_bytes, 200);
+
+        let search = &report.by_action["indices:data/read/search"];
+        assert_eq!(
+            (search.calls, search.failures, search.max_took_millis),
+            (2, 1, 30)
+        );
+        assert_eq!(report.by_index["logs"].response_bytes, 400);
+        assert_eq!(report.by_tenant["_none"].calls, 1);
+        assert_eq!(report.by_tenant["acme"].took_millis, 42);
+
+        assert_eq!(tracker.reset().total.calls, 3);
+        assert_eq!(tracker.report().total.calls, 0);
+    }
+}
@@ -0,0 +1,222 @@
+//! Content types OpenSearch exchanges REST bodies in, parsed into and written
+//! from a single document model (`serde_json::Value`).
+
+pub mod smile;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+use crate::extension::ExtensionError;
+use crate::rest::execute::JSON_CONTENT_TYPE;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum XContentType {
+    #[default]
+    Json,
+    Yaml,
+    Cbor,
+    Smile,
+}
+
+impl XContentType {
+    pub const ALL: [XContentType; 4] = [
+        XContentType::Json,
+        XContentType::Yaml,
+        XContentType::Cbor,
+        XContentType::Smile,
+    ];
+
+    /// Short name, as used by the `format` request parameter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            XContentType::Json => "json",
+            XContentType::Yaml => "yaml",
+            XContentType::Cbor => "cbor",
+            XContentType::Smile => "smile",
+        }
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            XContentType::Json => JSON_CONTENT_TYPE,
+            XContentType::Yaml => "application/yaml",
+            XContentType::Cbor => "application/cbor",
+            XContentType::Smile => "application/smile",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Type for a `Content-Type` or `Accept` value, ignoring parameters and
+    /// vendor prefixes such as `application/vnd.opensearch+json`. `*/*` is JSON.
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        media_type.split(',').find_map(|candidate| {
+            let essence = candidate
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            match essence.as_str() {
+                "*/*" | "application/*" => Some(XContentType::Json),
+                "text/yaml" | "application/x-yaml" => Some(XContentType::Yaml),
+                _ => {
+                    let (kind, subtype) = essence.split_once('/')?;
+                    if kind != "application" {
+                        return None;
+                    }
+                    Self::from_name(subtype.rsplit('+').next().unwrap_or(subtype))
+                }
+            }
+        })
+    }
+
+    /// Sniff the type from the first bytes of `content`, as OpenSearch does
+    /// when a body arrives without a usable `Content-Type`.
+    pub fn detect(content: &[u8]) -> Option<Self> {
+        if content.starts_with(smile::HEADER) {
+            return Some(XContentType::Smile);
+        }
+        if content.starts_with(b"---") {
+            return Some(XContentType::Yaml);
+        }
+        // CBOR self-describe tag, or a map header (major type 5)
+        if content.starts_with(&[0xD9, 0xD9, 0xF7]) || content.first().is_some_and(|b| b >> 5 == 5)
+        {
+            return Some(XContentType::Cbor);
+        }
+        match content.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') | Some(b'[') => Some(XContentType::Json),
+            _ => None,
+        }
+    }
+
+    /// Parse `content` into the document model.
+    pub fn parse(&self, content: &[u8]) -> Result<Value, ExtensionError> {
+        let result = match self {
+            XContentType::Json => serde_json::from_slice(content).map_err(|e| e.to_string()),
+            XContentType::Yaml => serde_yaml::from_slice(content).map_err(|e| e.to_string()),
+            XContentType::Cbor => ciborium::de::from_reader(content).map_err(|e| e.to_string()),
+            XContentType::Smile => smile::from_slice(content),
+        };
+        result.map_err(|e| {
+            ExtensionError::serialization(format!("Failed to parse {} content: {}", self, e))
+        })
+    }
+
+    /// Parse `content` and deserialize it into `T`.
+    pub fn parse_as<T: DeserializeOwned>(&self, content: &[u8]) -> Result<T, ExtensionError> {
+        serde_json::from_value(self.parse(content)?).map_err(|e| {
+            ExtensionError::serialization(format!("Failed to parse {} content: {}", self, e))
+        })
+    }
+
+    /// Serialize `value` in this type. `pretty` only affects JSON.
+    pub fn to_vec<T: Serialize + ?Sized>(
+        &self,
+        value: &T,
+        pretty: bool,
+    ) -> Result<Vec<u8>, ExtensionError> {
+        let result = match self {
+            XContentType::Json if pretty => {
+                serde_json::to_vec_pretty(value).map_err(|e| e.to_string())
+            }
+            XContentType::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            XContentType::Yaml => serde_yaml::to_string(value)
+                .map(String::into_bytes)
+                .map_err(|e| e.to_string()),
+            XContentType::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::ser::into_writer(value, &mut buf)
+                    .map(|_| buf)
+                    .map_err(|e| e.to_string())
+            }
+            XContentType::Smile => serde_json::to_value(value)
+                .map(|v| smile::to_vec(&v))
+                .map_err(|e| e.to_string()),
+        };
+        result.map_err(|e| {
+            ExtensionError::serialization(format!("Failed to write {} content: {}", self, e))
+        })
+    }
+}
+
+impl fmt::Display for XContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parse a body, trusting `media_type` when it names a known type and
+/// sniffing the content otherwise.
+pub fn parse(
+    media_type: Option<&str>,
+    content: &[u8],
+) -> Result<(XContentType, Value), ExtensionError> {
+    let content_type = media_type
+        .and_then(XContentType::from_media_type)
+        .or_else(|| XContentType::detect(content))
+        .ok_or_else(|| {
+            ExtensionError::serialization("Unable to detect the content type of the body")
+        })?;
+    Ok((content_type, content_type.parse(content)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_round_trip_all_types() {
+        let document = json!({"name": "hello", "count": 42, "ratio": 1.5, "tags": ["a", "b"], "nested": {"ok": true}});
+        for content_type in XContentType::ALL {
+            let bytes = content_type.to_vec(&document, false).unwrap();
+            assert_eq!(
+                XContentType::detect(&bytes),
+                Some(content_type).filter(|t| *t != XContentType::Yaml)
+            );
+            assert_eq!(
+                content_type.parse(&bytes).unwrap(),
+                document,
+                "{}",
+                content_type
+            );
+
+            let (detected, parsed) = parse(Some(content_type.media_type()), &bytes).unwrap();
+            assert_eq!((detected, parsed), (content_type, document.clone()));
+        }
+    }
+
+    #[test]
+    fn test_media_type_negotiation() {
+        assert_eq!(
+            XContentType::from_media_type("application/json; charset=UTF-8"),
+            Some(XContentType::Json)
+        );
+        assert_eq!(
+            XContentType::from_media_type("application/vnd.opensearch+json;compatible-with=7"),
+            Some(XContentType::Json)
+        );
+        assert_eq!(
+            XContentType::from_media_type("text/html, application/smile"),
+            Some(XContentType::Smile)
+        );
+        assert_eq!(
+            XContentType::from_media_type("*/*"),
+            Some(XContentType::Json)
+        );
+        assert_eq!(XContentType::from_media_type("text/plain"), None);
+        assert_eq!(
+            XContentType::detect(b"---\nfoo: bar\n"),
+            Some(XContentType::Yaml)
+        );
+        assert!(parse(None, b"plain text").is_err());
+    }
+}
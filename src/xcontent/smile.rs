@@ -0,0 +1,401 @@
+//! Minimal SMILE (binary JSON) codec for the document model.
+//!
+//! The writer emits plain SMILE without shared-string back references, which
+//! every reader accepts. The reader also understands shared names and values
+//! as written by Jackson and OpenSearch.
+
+use serde_json::{Map, Number, Value};
+
+pub const HEADER: &[u8; 3] = b":)\n";
+
+const FLAG_SHARED_NAMES: u8 = 0x01;
+const FLAG_SHARED_VALUES: u8 = 0x02;
+const MAX_SHARED: usize = 1024;
+const MAX_SHARED_VALUE_LENGTH: usize = 64;
+/// Deepest nesting of arrays and objects read, as other formats cap it.
+const MAX_DEPTH: usize = 128;
+
+const TOKEN_EMPTY_STRING: u8 = 0x20;
+const TOKEN_NULL: u8 = 0x21;
+const TOKEN_FALSE: u8 = 0x22;
+const TOKEN_TRUE: u8 = 0x23;
+const TOKEN_INT_32: u8 = 0x24;
+const TOKEN_INT_64: u8 = 0x25;
+const TOKEN_FLOAT_32: u8 = 0x28;
+const TOKEN_FLOAT_64: u8 = 0x29;
+const TOKEN_LONG_ASCII: u8 = 0xE0;
+const TOKEN_LONG_UNICODE: u8 = 0xE4;
+const TOKEN_LONG_SHARED_VALUE: u8 = 0xEC;
+const TOKEN_START_ARRAY: u8 = 0xF8;
+const TOKEN_END_ARRAY: u8 = 0xF9;
+const TOKEN_START_OBJECT: u8 = 0xFA;
+const TOKEN_END_OBJECT: u8 = 0xFB;
+const TOKEN_END_STRING: u8 = 0xFC;
+const KEY_EMPTY: u8 = 0x20;
+const KEY_LONG_UNICODE: u8 = 0x34;
+
+pub fn to_vec(value: &Value) -> Vec<u8> {
+    let mut out = HEADER.to_vec();
+    out.push(0x00);
+    write_value(&mut out, value);
+    out
+}
+
+pub fn from_slice(bytes: &[u8]) -> Result<Value, String> {
+    let flags = match bytes {
+        [b':', b')', b'\n', flags, ..] => *flags,
+        _ => return Err("missing SMILE header".to_string()),
+    };
+    if flags >> 4 != 0 {
+        return Err(format!("unsupported SMILE version {}", flags >> 4));
+    }
+
+    let mut reader = Reader {
+        bytes,
+        pos: HEADER.len() + 1,
+        names: (flags & FLAG_SHARED_NAMES != 0).then(Vec::new),
+        values: (flags & FLAG_SHARED_VALUES != 0).then(Vec::new),
+        depth: 0,
+    };
+    let token = reader.next_byte()?;
+    reader.read_value(token)
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(TOKEN_NULL),
+        Value::Bool(false) => out.push(TOKEN_FALSE),
+        Value::Bool(true) => out.push(TOKEN_TRUE),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_string(out, s),
+        Value::Array(values) => {
+            out.push(TOKEN_START_ARRAY);
+            values.iter().for_each(|v| write_value(out, v));
+            out.push(TOKEN_END_ARRAY);
+        }
+        Value::Object(map) => {
+            out.push(TOKEN_START_OBJECT);
+            for (key, v) in map {
+                write_key(out, key);
+                write_value(out, v);
+            }
+            out.push(TOKEN_END_OBJECT);
+        }
+    }
+}
+
+fn write_number(out: &mut Vec<u8>, n: &Number) {
+    if let Some(i) = n.as_i64() {
+        if (-16..=15).contains(&i) {
+            out.push(0xC0 | zigzag(i) as u8);
+        } else if i32::try_from(i).is_ok() {
+            out.push(TOKEN_INT_32);
+            write_vint(out, zigzag(i));
+        } else {
+            out.push(TOKEN_INT_64);
+            write_vint(out, zigzag(i));
+        }
+    } else {
+        // u64 beyond i64::MAX has no SMILE integer form short of BigInteger.
+        out.push(TOKEN_FLOAT_64);
+        let bits = n.as_f64().unwrap_or_default().to_bits();
+        for group in (0..10).rev() {
+            out.push(((bits >> (group * 7)) & 0x7F) as u8);
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    if len == 0 {
+        out.push(TOKEN_EMPTY_STRING);
+    } else if s.is_ascii() && len <= 64 {
+        out.push(if len <= 32 {
+            0x40 | (len - 1) as u8
+        } else {
+            0x60 | (len - 33) as u8
+        });
+        out.extend_from_slice(s.as_bytes());
+    } else if !s.is_ascii() && (2..=65).contains(&len) {
+        out.push(if len <= 33 {
+            0x80 | (len - 2) as u8
+        } else {
+            0xA0 | (len - 34) as u8
+        });
+        out.extend_from_slice(s.as_bytes());
+    } else {
+        out.push(if s.is_ascii() {
+            TOKEN_LONG_ASCII
+        } else {
+            TOKEN_LONG_UNICODE
+        });
+        out.extend_from_slice(s.as_bytes());
+        out.push(TOKEN_END_STRING);
+    }
+}
+
+fn write_key(out: &mut Vec<u8>, key: &str) {
+    let len = key.len();
+    if len == 0 {
+        out.push(KEY_EMPTY);
+    } else if key.is_ascii() && len <= 64 {
+        out.push(0x80 | (len - 1) as u8);
+        out.extend_from_slice(key.as_bytes());
+    } else if !key.is_ascii() && (2..=57).contains(&len) {
+        out.push(0xC0 | (len - 2) as u8);
+        out.extend_from_slice(key.as_bytes());
+    } else {
+        out.push(KEY_LONG_UNICODE);
+        out.extend_from_slice(key.as_bytes());
+        out.push(TOKEN_END_STRING);
+    }
+}
+
+fn zigzag(i: i64) -> u64 {
+    ((i << 1) ^ (i >> 63)) as u64
+}
+
+fn unzigzag(u: u64) -> i64 {
+    ((u >> 1) as i64) ^ -((u & 1) as i64)
+}
+
+/// SMILE vints: 7 bits per byte, the last byte flagged with 0x80 and holding 6 bits.
+fn write_vint(out: &mut Vec<u8>, value: u64) {
+    let mut groups = vec![(value & 0x3F) as u8 | 0x80];
+    let mut rest = value >> 6;
+    while rest != 0 {
+        groups.push((rest & 0x7F) as u8);
+        rest >>= 7;
+    }
+    out.extend(groups.into_iter().rev());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    names: Option<Vec<String>>,
+    values: Option<Vec<String>>,
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn next_byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or("unexpected end of SMILE content")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.pos + len;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or("unexpected end of SMILE content")?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn take_str(&mut self, len: usize) -> Result<String, String> {
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| format!("invalid UTF-8 in SMILE string: {}", e))
+    }
+
+    fn take_terminated_str(&mut self) -> Result<String, String> {
+        let len = self.bytes[self.pos..]
+            .iter()
+            .position(|&b| b == TOKEN_END_STRING)
+            .ok_or("unterminated SMILE string")?;
+        let s = self.take_str(len)?;
+        self.pos += 1;
+        Ok(s)
+    }
+
+    fn read_vint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        loop {
+            let byte = self.next_byte()?;
+            if byte & 0x80 != 0 {
+                return Ok((value << 6) | (byte & 0x3F) as u64);
+            }
+            value = (value << 7) | byte as u64;
+        }
+    }
+
+    fn read_fixed(&mut self, groups: usize) -> Result<u64, String> {
+        self.take(groups)?.iter().try_fold(0u64, |acc, &b| {
+            if b & 0x80 == 0 {
+                Ok((acc << 7) | b as u64)
+            } else {
+                Err("invalid SMILE float".to_string())
+            }
+        })
+    }
+
+    fn read_value(&mut self, token: u8) -> Result<Value, String> {
+        let value = match token {
+            0x01..=0x1F => Value::String(self.shared_value((token - 1) as usize)?),
+            TOKEN_EMPTY_STRING => Value::String(String::new()),
+            TOKEN_NULL => Value::Null,
+            TOKEN_FALSE => Value::Bool(false),
+            TOKEN_TRUE => Value::Bool(true),
+            TOKEN_INT_32 | TOKEN_INT_64 => Value::from(unzigzag(self.read_vint()?)),
+            TOKEN_FLOAT_32 => Value::from(f32::from_bits(self.read_fixed(5)? as u32) as f64),
+            TOKEN_FLOAT_64 => Value::from(f64::from_bits(self.read_fixed(10)?)),
+            0x40..=0x5F => self.inline_string((token & 0x1F) as usize + 1)?,
+            0x60..=0x7F => self.inline_string((token & 0x1F) as usize + 33)?,
+            0x80..=0x9F => self.inline_string((token & 0x1F) as usize + 2)?,
+            0xA0..=0xBF => self.inline_string((token & 0x1F) as usize + 34)?,
+            0xC0..=0xDF => Value::from(unzigzag((token & 0x1F) as u64)),
+            TOKEN_LONG_ASCII | TOKEN_LONG_UNICODE => Value::String(self.take_terminated_str()?),
+            TOKEN_LONG_SHARED_VALUE..=0xEF => {
+                let index = (((token & 0x03) as usize) << 8) | self.next_byte()? as usize;
+                Value::String(self.shared_value(index)?)
+            }
+            TOKEN_START_ARRAY => {
+                self.nest()?;
+                let mut values = Vec::new();
+                loop {
+                    match self.next_byte()? {
+                        TOKEN_END_ARRAY => break,
+                        token => values.push(self.read_value(token)?),
+                    }
+                }
+                self.depth -= 1;
+                Value::Array(values)
+            }
+            TOKEN_START_OBJECT => {
+                self.nest()?;
+                let mut map = Map::new();
+                loop {
+                    match self.next_byte()? {
+                        TOKEN_END_OBJECT => break,
+                        token => {
+                            let key = self.read_key(token)?;
+                            let token = self.next_byte()?;
+                            map.insert(key, self.read_value(token)?);
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Value::Object(map)
+            }
+            other => return Err(format!("unsupported SMILE value token 0x{:02X}", other)),
+        };
+        Ok(value)
+    }
+
+    fn nest(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!(
+                "SMILE document nested deeper than {} levels",
+                MAX_DEPTH
+            ));
+        }
+        Ok(())
+    }
+
+    fn inline_string(&mut self, len: usize) -> Result<Value, String> {
+        let s = self.take_str(len)?;
+        if len <= MAX_SHARED_VALUE_LENGTH {
+            if let Some(values) = &mut self.values {
+                remember(values, &s);
+            }
+        }
+        Ok(Value::String(s))
+    }
+
+    fn read_key(&mut self, token: u8) -> Result<String, String> {
+        let key = match token {
+            KEY_EMPTY => return Ok(String::new()),
+            0x30..=0x33 => {
+                let index = (((token & 0x03) as usize) << 8) | self.next_byte()? as usize;
+                return self.shared_name(index);
+            }
+            0x40..=0x7F => return self.shared_name((token & 0x3F) as usize),
+            KEY_LONG_UNICODE => self.take_terminated_str()?,
+            0x80..=0xBF => self.take_str((token & 0x3F) as usize + 1)?,
+            0xC0..=0xF7 => self.take_str((token & 0x3F) as usize + 2)?,
+            other => return Err(format!("unsupported SMILE key token 0x{:02X}", other)),
+        };
+        if let Some(names) = &mut self.names {
+            remember(names, &key);
+        }
+        Ok(key)
+    }
+
+    fn shared_name(&self, index: usize) -> Result<String, String> {
+        self.names
+            .as_ref()
+            .and_then(|names| names.get(index))
+            .cloned()
+            .ok_or_else(|| format!("invalid SMILE shared name reference {}", index))
+    }
+
+    fn shared_value(&self, index: usize) -> Result<String, String> {
+        self.values
+            .as_ref()
+            .and_then(|values| values.get(index))
+            .cloned()
+            .ok_or_else(|| format!("invalid SMILE shared value reference {}", index))
+    }
+}
+
+/// Shared-string tables are cleared, not evicted, once they fill up.
+fn remember(table: &mut Vec<String>, s: &str) {
+    if table.len() >= MAX_SHARED {
+        table.clear();
+    }
+    table.push(s.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_smile_round_trip() {
+        let document = json!({
+            "name": "rust-extension",
+            "count": 3,
+            "total": 1_234_567_890_123i64,
+            "negative": -300,
+            "ratio": 0.25,
+            "tags": ["a", "ünïcødé", "x".repeat(100)],
+            "nested": {"enabled": true, "missing": null, "": ""},
+        });
+        let bytes = to_vec(&document);
+        assert_eq!(&bytes[..3], HEADER);
+        assert_eq!(from_slice(&bytes).unwrap(), document);
+
+        // {"a":"xy","b":"xy","a":..} written with shared names and values enabled
+        let shared = [
+            b':', b')', b'\n', 0x03, 0xFA, 0x80, b'a', 0x41, b'x', b'y', 0x80, b'b', 0x01, 0xFB,
+        ];
+        assert_eq!(from_slice(&shared).unwrap(), json!({"a": "xy", "b": "xy"}));
+        assert!(from_slice(b"{}").is_err());
+    }
+
+    #[test]
+    fn test_smile_nesting_limited() {
+        let nested = |depth: usize| {
+            let mut bytes = vec![b':', b')', b'\n', 0x00];
+            bytes.extend(std::iter::repeat_n(TOKEN_START_ARRAY, depth));
+            bytes.extend(std::iter::repeat_n(TOKEN_END_ARRAY, depth));
+            bytes
+        };
+        assert!(from_slice(&nested(MAX_DEPTH)).is_ok());
+        let error = from_slice(&nested(MAX_DEPTH + 1)).unwrap_err();
+        assert!(error.contains("nested deeper than 128 levels"));
+
+        let mut attack = vec![b':', b')', b'\n', 0x00];
+        attack.extend(std::iter::repeat_n(TOKEN_START_ARRAY, 1_000_000));
+        let error = crate::xcontent::parse(None, &attack).unwrap_err();
+        assert!(error.to_string().contains("nested deeper"));
+    }
+}